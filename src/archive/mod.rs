@@ -0,0 +1,304 @@
+use ::chain::block_store::{BlockStore, FileBlockStore};
+use ::chain::chain_visitor::{ElectionPhase, VotingClosedVisitor};
+use ::chain::chain_walker::{ChainWalker, LongestPathWalker};
+use ::chain::hasher::Hasher;
+use ::chain::types::{BlockHash, Height};
+use ::signer::Signer;
+use ::signer::key::{self, NodeKey};
+use bincode;
+use serde_json;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The name of the file in the data directory holding the manifest of a sealed archive.
+/// Its presence marks the data directory as sealed.
+pub const MANIFEST_FILE_NAME: &str = "archive-manifest.json";
+
+/// Separates the digest of a manifest from the identifiers of blocks,
+/// so that the signature of a manifest can not be passed off as the signature of a block.
+const MANIFEST_DOMAIN: &str = "node-rs-archive-manifest";
+
+/// A signed statement of a node about the contents of its data directory at the end of an election.
+///
+/// Any file modified, removed or added after sealing is detected by comparing the
+/// data directory to the manifest, whose signature prevents it from being adjusted along.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct ArchiveManifest {
+    /// The time the archive was sealed at, in seconds since the epoch.
+    pub sealed_at: u64,
+    /// The hash of the genesis configuration of the archived chain.
+    pub genesis_configuration_hash: String,
+    /// The height of the head of the canonical chain.
+    pub height: Height,
+    /// The identifier of the head of the canonical chain.
+    pub head_identifier: BlockHash,
+    /// The SHA-256 digest of each file in the data directory, by its path relative to the directory.
+    pub files: BTreeMap<String, String>,
+    /// The public key of the node identity which sealed the archive.
+    pub public_key: String,
+    /// The signature of the digest of all other fields.
+    pub signature: String,
+}
+
+impl ArchiveManifest {
+    /// Returns true, if the manifest is signed with the secret key belonging to its public key.
+    pub fn verify(&self) -> bool {
+        key::verify_signature(&self.public_key, &self.get_digest(), &self.signature)
+    }
+
+    /// Returns the digest of all fields except the signature, which is signed.
+    fn get_digest(&self) -> BlockHash {
+        let bytes = bincode::serialize(&(MANIFEST_DOMAIN, self.sealed_at, &self.genesis_configuration_hash, self.height, &self.head_identifier, &self.files, &self.public_key)).unwrap();
+
+        BlockHash::new(Hasher::Sha256.digest(&bytes))
+    }
+}
+
+/// Returns true, if the given data directory is sealed as archive and must not be written to anymore.
+pub fn is_sealed(data_directory: &Path) -> bool {
+    data_directory.join(MANIFEST_FILE_NAME).exists()
+}
+
+/// Seal the given data directory as archive, once the voting of its election is closed.
+///
+/// Writes the manifest of all files in the directory, signed with the given node identity,
+/// and makes the files and directories read-only. Any node refuses to write into the directory afterwards.
+/// The node using the directory must be stopped before.
+///
+/// Returns an error if the directory is already sealed, holds no chain, or the voting is not closed yet.
+pub fn seal(data_directory: &Path, node_key: &NodeKey) -> Result<ArchiveManifest, String> {
+    if is_sealed(data_directory) {
+        return Err(format!("Data directory {:?} is already sealed as archive", data_directory));
+    }
+
+    let chain = match FileBlockStore::open_read_only(data_directory).and_then(|block_store| block_store.load()) {
+        Ok(Some(chain)) => chain,
+        Ok(None) => {
+            return Err(format!("Data directory {:?} holds no chain", data_directory));
+        }
+        Err(e) => {
+            return Err(e);
+        }
+    };
+
+    let mut voting_closed_visitor = VotingClosedVisitor::new();
+    LongestPathWalker::new().walk_chain(&chain, &mut voting_closed_visitor);
+    if ElectionPhase::Closed != voting_closed_visitor.get_election_phase() {
+        return Err("Refusing to seal the archive before the voting is closed".to_string());
+    }
+
+    let head = match chain.head() {
+        Ok(head) => head,
+        Err(e) => {
+            return Err(format!("Failed to determine the head of the chain: {:?}", e));
+        }
+    };
+
+    let mut files = BTreeMap::new();
+    let mut directories = vec![];
+    match hash_files(data_directory, data_directory, &mut files, &mut directories) {
+        Ok(()) => {}
+        Err(e) => {
+            return Err(e);
+        }
+    }
+
+    let since_the_epoch = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards");
+    let mut manifest = ArchiveManifest {
+        sealed_at: since_the_epoch.as_secs(),
+        genesis_configuration_hash: chain.genesis_configuration_hash.clone(),
+        height: head.height,
+        head_identifier: head.block.identifier,
+        files,
+        public_key: node_key.public_key.clone(),
+        signature: String::new(),
+    };
+    manifest.signature = match node_key.sign(&manifest.get_digest()) {
+        Ok(signature) => signature,
+        Err(e) => {
+            return Err(format!("Failed to sign the archive manifest: {}", e));
+        }
+    };
+
+    let manifest_path = data_directory.join(MANIFEST_FILE_NAME);
+    let write_result = OpenOptions::new().write(true).create_new(true).open(&manifest_path)
+        .and_then(|mut file| file.write_all(serde_json::to_string_pretty(&manifest).unwrap().as_bytes()).and_then(|_| file.sync_all()));
+    match write_result {
+        Ok(()) => {}
+        Err(e) => {
+            return Err(format!("Failed to write the archive manifest {:?}: {:?}", manifest_path, e));
+        }
+    }
+
+    // directories last, so that the files within them can still be changed
+    let paths = manifest.files.keys().map(|relative_path| data_directory.join(relative_path))
+        .chain(Some(manifest_path))
+        .chain(directories.into_iter().rev())
+        .chain(Some(data_directory.to_path_buf()));
+    for path in paths {
+        let set_result = fs::metadata(&path).and_then(|metadata| {
+            let mut permissions = metadata.permissions();
+            permissions.set_readonly(true);
+            fs::set_permissions(&path, permissions)
+        });
+        match set_result {
+            Ok(()) => {}
+            Err(e) => {
+                return Err(format!("Failed to make {:?} read-only: {:?}", path, e));
+            }
+        }
+    }
+
+    Ok(manifest)
+}
+
+/// Verify that the given sealed data directory was not tampered with, i.e. that its manifest is
+/// signed correctly and lists exactly the files in the directory along with their contents.
+///
+/// Returns the manifest, or an error listing all files which were modified, removed or added.
+pub fn verify(data_directory: &Path) -> Result<ArchiveManifest, String> {
+    let manifest_path = data_directory.join(MANIFEST_FILE_NAME);
+    let mut contents = String::new();
+    let read_result = File::open(&manifest_path).and_then(|mut file| file.read_to_string(&mut contents));
+    match read_result {
+        Ok(_) => {}
+        Err(e) => {
+            return Err(format!("Failed to read the archive manifest {:?}: {:?}", manifest_path, e));
+        }
+    }
+
+    let manifest: ArchiveManifest = match serde_json::from_str(&contents) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            return Err(format!("Failed to parse the archive manifest {:?}: {:?}", manifest_path, e));
+        }
+    };
+
+    if !manifest.verify() {
+        return Err(format!("The signature of the archive manifest {:?} does not match its public key", manifest_path));
+    }
+
+    let mut files = BTreeMap::new();
+    match hash_files(data_directory, data_directory, &mut files, &mut vec![]) {
+        Ok(()) => {}
+        Err(e) => {
+            return Err(e);
+        }
+    }
+
+    let mut violations = vec![];
+    for (relative_path, digest) in manifest.files.iter() {
+        match files.get(relative_path) {
+            Some(actual_digest) if actual_digest == digest => {}
+            Some(_) => violations.push(format!("{} was modified", relative_path)),
+            None => violations.push(format!("{} was removed", relative_path))
+        }
+    }
+    for relative_path in files.keys().filter(|relative_path| !manifest.files.contains_key(*relative_path)) {
+        violations.push(format!("{} was added", relative_path));
+    }
+
+    if violations.is_empty() {
+        Ok(manifest)
+    } else {
+        Err(format!("The archive {:?} was tampered with: {}", data_directory, violations.join(", ")))
+    }
+}
+
+/// Collect the digest of each file below the given directory, except for the manifest,
+/// by its path relative to the given data directory, as well as all directories below it.
+fn hash_files(data_directory: &Path, directory: &Path, files: &mut BTreeMap<String, String>, directories: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(e) => {
+            return Err(format!("Failed to list {:?}: {:?}", directory, e));
+        }
+    };
+
+    for entry in entries {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(e) => {
+                return Err(format!("Failed to list {:?}: {:?}", directory, e));
+            }
+        };
+
+        if path.is_dir() {
+            directories.push(path.clone());
+            match hash_files(data_directory, &path, files, directories) {
+                Ok(()) => {}
+                Err(e) => {
+                    return Err(e);
+                }
+            }
+            continue;
+        }
+
+        let relative_path = path.strip_prefix(data_directory).unwrap().to_string_lossy().replace('\\', "/");
+        if relative_path == MANIFEST_FILE_NAME {
+            continue;
+        }
+
+        let mut contents = vec![];
+        match File::open(&path).and_then(|mut file| file.read_to_end(&mut contents)) {
+            Ok(_) => {
+                files.insert(relative_path, Hasher::Sha256.digest(&contents));
+            }
+            Err(e) => {
+                return Err(format!("Failed to read {:?}: {:?}", path, e));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod archive_test {
+
+    use super::*;
+    use ::chain::block::Block;
+    use ::chain::chain::Chain;
+    use ::chain::storage::StorageEncoding;
+    use ::chain::transaction::Transaction;
+    use std::env;
+
+    #[test]
+    fn test_seal_archive() {
+        let data_directory = env::temp_dir().join(format!("node_rs_archive_test_{}", ::std::process::id()));
+        let _ = fs::remove_dir_all(&data_directory);
+        let node_key = NodeKey::generate();
+
+        let mut chain = Chain::new("genesis".to_string(), Hasher::default());
+        let mut store = FileBlockStore::open(&data_directory, StorageEncoding::Json).unwrap();
+        assert_eq!(Ok(()), store.replace(&chain));
+        assert!(seal(&data_directory, &node_key).is_err());
+
+        let opened = Block::new(Hasher::default(), chain.genesis_identifier_hash.clone(), None, vec![Transaction::new_voting_opened(Hasher::default(), "genesis".to_string(), 0)]);
+        let closed = Block::new(Hasher::default(), opened.identifier.clone(), None, vec![Transaction::new_voting_closed(Hasher::default(), "genesis".to_string(), 1)]);
+        chain.add_block(opened.clone());
+        chain.add_block(closed.clone());
+        assert_eq!(Ok(()), store.append(&opened));
+        assert_eq!(Ok(()), store.append(&closed));
+        fs::create_dir_all(data_directory.join("logs")).unwrap();
+        File::create(data_directory.join("logs").join("audit.log")).unwrap().write_all(b"banned").unwrap();
+
+        let manifest = seal(&data_directory, &node_key).unwrap();
+        assert_eq!(Height::new(2), manifest.height);
+        assert_eq!(closed.identifier, manifest.head_identifier);
+        assert_eq!(vec!["blocks.jsonl", "logs/audit.log"], manifest.files.keys().map(String::as_str).collect::<Vec<&str>>());
+        assert_eq!(Ok(manifest.clone()), verify(&data_directory));
+
+        // a sealed directory is neither sealed again nor written to
+        assert!(seal(&data_directory, &node_key).is_err());
+        assert!(FileBlockStore::open(&data_directory, StorageEncoding::Json).is_err());
+        assert!(FileBlockStore::open_read_only(&data_directory).unwrap().append(&closed).is_err());
+
+        let mut forged = manifest.clone();
+        forged.files.remove("logs/audit.log");
+        assert!(!forged.verify());
+    }
+}
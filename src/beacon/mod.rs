@@ -0,0 +1,218 @@
+use ::chain::hasher::Hasher;
+use ::chain::types::{BlockHash, Height};
+use ::http;
+use ::signer::Signer;
+use ::signer::key;
+use bincode;
+use serde_json;
+use std::fs::File;
+use std::io::Read;
+use std::net::SocketAddr;
+use std::process::Command;
+use std::time::Duration;
+
+/// The maximum time to wait for an HTTPS beacon to accept a connection or respond.
+const PUBLICATION_TIMEOUT: u64 = 10;
+
+/// Separates the digest of a checkpoint from the identifiers of blocks,
+/// so that the signature of a checkpoint can not be passed off as the signature of a block.
+const CHECKPOINT_DOMAIN: &str = "node-rs-checkpoint";
+
+fn default_confirmations() -> usize {
+    6
+}
+
+/// Defines the external beacon to which checkpoints of the canonical chain are published.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BeaconConfig {
+    pub publisher: BeaconPublisher,
+    /// The amount of blocks which must follow a block on the canonical chain
+    /// for it to be published as checkpoint.
+    #[serde(default = "default_confirmations")]
+    pub confirmations: usize,
+    /// The interval in seconds in which a new checkpoint is published, if the finalized head advanced.
+    pub interval_secs: u64,
+}
+
+/// The ways a checkpoint is made available to external observers.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum BeaconPublisher {
+    /// Post the JSON encoded checkpoint to the given `https://` URL.
+    Https {
+        url: String,
+    },
+    /// Run the given command with the name of the TXT record and the JSON encoded checkpoint
+    /// as arguments, e.g. a script calling `nsupdate` or the API of a DNS provider.
+    /// The command must exit successfully once the record is published.
+    DnsTxt {
+        record: String,
+        command: String,
+    },
+}
+
+/// A signed statement of a sealer about the finalized head of the canonical chain.
+///
+/// Observers collecting the checkpoints of a network detect a retroactively rewritten chain
+/// by a block at a published height whose identifier differs from the published one.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct Checkpoint {
+    /// The hash of the genesis configuration of the network.
+    pub genesis_configuration_hash: String,
+    /// The height of the finalized head.
+    pub height: Height,
+    /// The identifier of the finalized head.
+    pub block_identifier: BlockHash,
+    /// The time the checkpoint was created at, in seconds since the epoch.
+    pub published_at: u64,
+    /// The address of the sealer publishing the checkpoint, whose public key
+    /// is declared in the genesis configuration.
+    pub sealer: SocketAddr,
+    /// The hex encoded Ed25519 signature of the digest of all other fields.
+    pub signature: String,
+}
+
+impl Checkpoint {
+    /// Create the checkpoint of the given finalized head, signed by the given signer.
+    ///
+    /// Returns an error if the signer fails to sign the checkpoint.
+    pub fn new(genesis_configuration_hash: String, height: Height, block_identifier: BlockHash, published_at: u64, sealer: SocketAddr, signer: &Signer) -> Result<Checkpoint, String> {
+        let mut checkpoint = Checkpoint {
+            genesis_configuration_hash,
+            height,
+            block_identifier,
+            published_at,
+            sealer,
+            signature: String::new(),
+        };
+
+        match signer.sign(&checkpoint.get_digest()) {
+            Ok(signature) => {
+                checkpoint.signature = signature;
+                Ok(checkpoint)
+            }
+            Err(e) => Err(format!("Failed to sign checkpoint at height {}: {}", height, e))
+        }
+    }
+
+    /// Returns true, if the checkpoint is signed with the secret key
+    /// belonging to the given hex encoded public key.
+    pub fn verify(&self, public_key: &str) -> bool {
+        key::verify_signature(public_key, &self.get_digest(), &self.signature)
+    }
+
+    /// Returns the digest of all fields except the signature, which is signed.
+    fn get_digest(&self) -> BlockHash {
+        let bytes = bincode::serialize(&(CHECKPOINT_DOMAIN, &self.genesis_configuration_hash, self.height, &self.block_identifier, self.published_at, self.sealer)).unwrap();
+
+        BlockHash::new(Hasher::Sha256.digest(&bytes))
+    }
+}
+
+impl BeaconConfig {
+    /// Read the beacon configuration from the given JSON file.
+    pub fn from_file(file_name: &str) -> Result<BeaconConfig, String> {
+        let mut contents = String::new();
+        let read_result = File::open(file_name).and_then(|mut file| file.read_to_string(&mut contents));
+        match read_result {
+            Ok(_) => {}
+            Err(e) => {
+                return Err(format!("Failed to read beacon configuration {:?}: {:?}", file_name, e));
+            }
+        }
+
+        let config: BeaconConfig = match serde_json::from_str(&contents) {
+            Ok(config) => config,
+            Err(e) => {
+                return Err(format!("Failed to parse beacon configuration {:?}: {:?}", file_name, e));
+            }
+        };
+
+        match config.publisher {
+            BeaconPublisher::Https { ref url } => {
+                match http::parse_https_url(url) {
+                    Ok(_) => {}
+                    Err(e) => {
+                        return Err(format!("Invalid URL in beacon configuration {:?}: {}", file_name, e));
+                    }
+                }
+            }
+            BeaconPublisher::DnsTxt { ref record, ref command } => {
+                if record.is_empty() || command.is_empty() {
+                    return Err(format!("The DNS publisher in beacon configuration {:?} requires a record and a command", file_name));
+                }
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+impl BeaconPublisher {
+    /// Publish the given checkpoint.
+    pub fn publish(&self, checkpoint: &Checkpoint) -> Result<(), String> {
+        let body = serde_json::to_string(checkpoint).unwrap();
+
+        match *self {
+            BeaconPublisher::Https { ref url } => post(url, &body),
+            BeaconPublisher::DnsTxt { ref record, ref command } => {
+                match Command::new(command).arg(record).arg(&body).status() {
+                    Ok(ref status) if status.success() => Ok(()),
+                    Ok(status) => Err(format!("Command {:?} publishing the TXT record exited with {}", command, status)),
+                    Err(e) => Err(format!("Failed to run command {:?} publishing the TXT record: {:?}", command, e))
+                }
+            }
+        }
+    }
+}
+
+/// Post the given JSON encoded checkpoint to the given URL.
+fn post(url: &str, body: &str) -> Result<(), String> {
+    let (authority, path) = match http::parse_https_url(url) {
+        Ok(parsed_url) => parsed_url,
+        Err(e) => {
+            return Err(e);
+        }
+    };
+
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        authority,
+        body.len(),
+        body
+    );
+
+    let response = match http::send_tls(&authority, &request, Duration::from_secs(PUBLICATION_TIMEOUT), 1024) {
+        Ok(response) => response,
+        Err(e) => {
+            return Err(e);
+        }
+    };
+
+    match http::get_status(&response) {
+        Some(status) if (200..300).contains(&status) => Ok(()),
+        Some(status) => Err(format!("Beacon responded with status {}", status)),
+        None => Err("Beacon sent no valid HTTP response".to_string())
+    }
+}
+
+#[cfg(test)]
+mod beacon_test {
+
+    use super::*;
+    use ::signer::key::NodeKey;
+
+    #[test]
+    fn test_verify_checkpoint() {
+        let node_key = NodeKey::generate();
+        let sealer: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+
+        let checkpoint = Checkpoint::new("genesis".to_string(), Height::new(42), BlockHash::from("head"), 1_600_000_000, sealer, &node_key).unwrap();
+        assert!(checkpoint.verify(&node_key.public_key));
+        assert!(!checkpoint.verify(&NodeKey::generate().public_key));
+
+        let mut rewritten = checkpoint.clone();
+        rewritten.block_identifier = BlockHash::from("other head");
+        assert!(!rewritten.verify(&node_key.public_key));
+    }
+}
@@ -1,51 +1,154 @@
 use std::vec::Vec;
+use std::net::SocketAddr;
 use bincode;
-use sha1::Sha1;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use ::chain::hasher::Hasher;
+use ::chain::merkle;
 use ::chain::transaction::Transaction;
+use ::chain::types::BlockHash;
 
 /// The content of a block.
-/// All contained fields are hashed and represent the identifier of the block.
+/// All contained fields are hashed and represent the identifier of the block,
+/// either directly or, if the block commits to its transactions by their root, by its header.
 #[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
 pub struct BlockContent {
-    pub parent: String,
+    pub parent: BlockHash,
     pub timestamp: u64,
+    /// The address of the sealer which produced this block.
+    /// None for the genesis block.
+    pub sealer: Option<SocketAddr>,
+    /// The transactions ordered by their time of arrival at the sealer,
+    /// and by their identifier if they arrived at the same time.
     pub transactions: Vec<Transaction>,
+    /// The root of the Merkle tree over the transactions.
+    /// Omitted if absent, so that the identifiers of blocks sealed
+    /// by earlier versions of this node do not change.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transactions_root: Option<String>,
+}
+
+/// The header of a block committing to its transactions by their root,
+/// from which the identifier of the block is derived.
+///
+/// Allows to verify that a transaction is included in a block
+/// without knowing the other transactions of the block.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct BlockHeader {
+    pub parent: BlockHash,
+    pub timestamp: u64,
+    /// The address of the sealer which produced the block.
+    pub sealer: Option<SocketAddr>,
+    /// The root of the Merkle tree over the transactions of the block.
+    pub transactions_root: String,
+}
+
+impl BlockHeader {
+    /// Returns the identifier of the block having this header, derived with the given hash function.
+    pub fn hash(&self, hasher: Hasher) -> BlockHash {
+        let bytes = bincode::serialize(self).unwrap();
+
+        BlockHash::new(hasher.digest(&bytes))
+    }
+
+    /// Returns true, if the given identifier is derived from this header
+    /// with the hash function it names.
+    pub fn is_identified_by(&self, identifier: &BlockHash) -> bool {
+        match Hasher::from_identifier(identifier.as_str()) {
+            Some(hasher) => *identifier == self.hash(hasher),
+            None => false
+        }
+    }
 }
 
 /// A block containing the identifier as well as its content, building
 /// up the identifier.
 #[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
 pub struct Block {
-    pub identifier: String,
+    pub identifier: BlockHash,
     pub data: BlockContent,
+    /// The signatures of the identifier by the sealer, if the sealer signs its blocks,
+    /// encoded along with their algorithms, see `SignatureAlgorithm`. Not part of the identifier.
+    #[serde(default)]
+    pub signature: Option<String>,
 }
 
 impl Block {
 
     /// Create a new block with the given parameters:
     ///
+    /// - `hasher`: The hash function deriving the identifier and the root of the transactions
     /// - `previous_hash`: The hash of the previous block
+    /// - `sealer`: The address of the sealer producing this block, None for the genesis block
     /// - `transactions`` A vector of transactions figuring as the data of this block
-    pub fn new(previous_hash: String, transactions: Vec<Transaction>) -> Self {
+    pub fn new(hasher: Hasher, previous_hash: BlockHash, sealer: Option<SocketAddr>, transactions: Vec<Transaction>) -> Self {
         let now = SystemTime::now();
         let since_the_epoch = now.duration_since(UNIX_EPOCH).expect("Time went backwards").as_secs();
 
+        let transactions_root = merkle::merkle_root(hasher, &transactions);
         let block_content = BlockContent {
             parent: previous_hash,
             timestamp: since_the_epoch,
-            transactions
+            sealer,
+            transactions,
+            transactions_root: Some(transactions_root),
         };
 
-        // we only want to hash the transactions to make sure, that these
-        // are not duplicated. We don't care about the references of the block
-        let bytes = bincode::serialize(&block_content).unwrap();
-        let digest = Sha1::from(bytes).hexdigest();
-
         Block {
-            identifier: digest,
+            identifier: Block::hash_content(hasher, &block_content),
             data: block_content,
+            signature: None,
         }
     }
+
+    /// Returns true, if the identifier of this block matches its content,
+    /// i.e. the content was not modified after the block was sealed.
+    /// The identifier and the root of the transactions are verified
+    /// with the hash function they name, so that legacy SHA-1 identifiers still verify.
+    pub fn has_valid_identifier(&self) -> bool {
+        if let Some(ref transactions_root) = self.data.transactions_root {
+            let is_valid_root = match Hasher::from_identifier(transactions_root) {
+                Some(hasher) => *transactions_root == merkle::merkle_root(hasher, &self.data.transactions),
+                None => false
+            };
+            if !is_valid_root {
+                return false;
+            }
+        }
+
+        match Hasher::from_identifier(self.identifier.as_str()) {
+            Some(hasher) => self.identifier.eq(&Block::hash_content(hasher, &self.data)),
+            None => false
+        }
+    }
+
+    /// Returns the header of this block, or None if the block
+    /// does not commit to its transactions by their root.
+    pub fn get_header(&self) -> Option<BlockHeader> {
+        self.data.transactions_root.clone().map(|transactions_root| BlockHeader {
+            parent: self.data.parent.clone(),
+            timestamp: self.data.timestamp,
+            sealer: self.data.sealer,
+            transactions_root,
+        })
+    }
+
+    fn hash_content(hasher: Hasher, block_content: &BlockContent) -> BlockHash {
+        if let Some(ref transactions_root) = block_content.transactions_root {
+            let header = BlockHeader {
+                parent: block_content.parent.clone(),
+                timestamp: block_content.timestamp,
+                sealer: block_content.sealer,
+                transactions_root: transactions_root.clone(),
+            };
+
+            return header.hash(hasher);
+        }
+
+        // we only want to hash the transactions to make sure, that these
+        // are not duplicated. We don't care about the references of the block
+        let bytes = bincode::serialize(block_content).unwrap();
+
+        BlockHash::new(hasher.digest(&bytes))
+    }
 }
\ No newline at end of file
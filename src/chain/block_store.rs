@@ -0,0 +1,416 @@
+use ::archive;
+use ::chain::block::Block;
+use ::chain::chain::Chain;
+use ::chain::storage::{self, StorageCodec, StorageEncoding, StoredBlock, StoredRoot, STORAGE_VERSION};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// A storage backend persisting the blocks of a chain, so that
+/// the chain survives restarts of the node.
+///
+/// Blocks are stored in the order they were added to the chain, hence each
+/// block is preceded by its parent. The adjacency of the blocks is recreated
+/// from their parents when the chain is loaded.
+pub trait BlockStore: Send {
+    /// Load the stored chain.
+    /// Returns None if no chain was stored yet.
+    fn load(&self) -> Result<Option<Chain>, String>;
+
+    /// Persist a block which was added to the stored chain.
+    /// Its parent must have been persisted before.
+    fn append(&mut self, block: &Block) -> Result<(), String>;
+
+    /// Replace all stored blocks by the blocks of the given chain.
+    fn replace(&mut self, chain: &Chain) -> Result<(), String>;
+}
+
+/// Keeps the blocks in memory only. Meant for testing.
+pub struct MemoryBlockStore {
+    root: StoredRoot,
+    blocks: Vec<Block>,
+}
+
+impl MemoryBlockStore {
+    /// Create a new, empty store.
+    pub fn new() -> MemoryBlockStore {
+        MemoryBlockStore {
+            root: StoredRoot::GenesisConfigurationHash(String::new()),
+            blocks: vec![],
+        }
+    }
+}
+
+impl Default for MemoryBlockStore {
+    fn default() -> Self {
+        MemoryBlockStore::new()
+    }
+}
+
+impl BlockStore for MemoryBlockStore {
+    fn load(&self) -> Result<Option<Chain>, String> {
+        match self.root.clone().restore(self.blocks.clone()) {
+            Ok(chain) => Ok(chain),
+            Err(e) => Err(format!("Stored blocks are inconsistent: {}", e))
+        }
+    }
+
+    fn append(&mut self, block: &Block) -> Result<(), String> {
+        if self.blocks.is_empty() {
+            return Err("Cannot append a block before a chain was stored".to_string());
+        }
+
+        self.blocks.push(block.clone());
+
+        Ok(())
+    }
+
+    fn replace(&mut self, chain: &Chain) -> Result<(), String> {
+        self.root = StoredRoot::of(chain);
+        self.blocks = chain.get_ordered_blocks();
+
+        Ok(())
+    }
+}
+
+/// Stores the blocks in an append-only file in a data directory.
+///
+/// The file starts with a header declaring the storage version, followed by the hash of the genesis
+/// configuration, or the state snapshot if the chain starts with one, and the blocks, each encoded as
+/// record by the codec of the storage encoding. Each block is synced to disk before it is acknowledged.
+/// A block only partially written due to a crash is discarded when the store is opened again.
+///
+/// A data directory sealed as archive is never written to again, its blocks are only opened read-only.
+pub struct FileBlockStore {
+    /// The path of the file holding the blocks.
+    path: PathBuf,
+    /// The codec encoding the records of the file.
+    codec: Box<StorageCodec>,
+    /// The file opened for appending, once a chain was stored.
+    file: Option<File>,
+    /// Whether appending and replacing blocks is refused.
+    read_only: bool,
+}
+
+impl FileBlockStore {
+    /// Open the store in the given data directory, creating the directory if necessary.
+    /// If the directory holds blocks stored in another encoding instead, they are migrated
+    /// to the given encoding.
+    ///
+    /// Returns an error if the data directory is sealed as archive.
+    pub fn open(data_directory: &Path, encoding: StorageEncoding) -> Result<FileBlockStore, String> {
+        if archive::is_sealed(data_directory) {
+            return Err(format!("Data directory {:?} is sealed as archive, refusing to write to it", data_directory));
+        }
+
+        match fs::create_dir_all(data_directory) {
+            Ok(()) => {}
+            Err(e) => {
+                return Err(format!("Failed to create data directory {:?}: {:?}", data_directory, e));
+            }
+        }
+
+        let codec = storage::create(encoding);
+        let path = data_directory.join(codec.file_name());
+        if !path.exists() {
+            let mut block_store = FileBlockStore {
+                path,
+                codec,
+                file: None,
+                read_only: false,
+            };
+
+            let other_encoding = StorageEncoding::all().into_iter()
+                .filter(|other_encoding| *other_encoding != encoding)
+                .find(|other_encoding| data_directory.join(storage::create(*other_encoding).file_name()).exists());
+            if let Some(other_encoding) = other_encoding {
+                match block_store.migrate(data_directory, other_encoding) {
+                    Ok(()) => {}
+                    Err(e) => {
+                        return Err(e);
+                    }
+                }
+            }
+
+            return Ok(block_store);
+        }
+
+        let mut contents = vec![];
+        let read_result = File::open(&path).and_then(|mut file| file.read_to_end(&mut contents));
+        match read_result {
+            Ok(_) => {}
+            Err(e) => {
+                return Err(format!("Failed to read stored blocks {:?}: {:?}", path, e));
+            }
+        }
+
+        let (storage_version, complete_length) = match codec.split_records(&contents) {
+            Ok((storage_version, _, complete_length)) => (storage_version, complete_length),
+            Err(e) => {
+                return Err(format!("Failed to read stored blocks {:?}: {}", path, e));
+            }
+        };
+
+        let file = match OpenOptions::new().append(true).open(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                return Err(format!("Failed to open stored blocks {:?}: {:?}", path, e));
+            }
+        };
+
+        // discard a block which was only partially written
+        if complete_length < contents.len() {
+            warn!("Discarding {} bytes of a partially written block in {:?}", contents.len() - complete_length, path);
+
+            match file.set_len(complete_length as u64) {
+                Ok(()) => {}
+                Err(e) => {
+                    return Err(format!("Failed to discard partially written block in {:?}: {:?}", path, e));
+                }
+            }
+        }
+
+        let mut block_store = FileBlockStore {
+            path,
+            codec,
+            file: Some(file),
+            read_only: false,
+        };
+
+        // blocks are appended in the current layout, hence a file of an earlier version is rewritten first
+        if storage_version < STORAGE_VERSION {
+            match block_store.load() {
+                Ok(Some(chain)) => {
+                    match block_store.replace(&chain) {
+                        Ok(()) => {
+                            info!("Upgraded stored blocks {:?} from version {} to {}", block_store.path, storage_version, STORAGE_VERSION);
+                        }
+                        Err(e) => {
+                            return Err(e);
+                        }
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(block_store)
+    }
+
+    /// Open the blocks stored in the given data directory in whichever encoding for reading only,
+    /// without creating, migrating or upgrading anything, e.g. to seal the directory as archive.
+    ///
+    /// Returns an error if the directory holds no stored blocks.
+    pub fn open_read_only(data_directory: &Path) -> Result<FileBlockStore, String> {
+        let codec = StorageEncoding::all().into_iter()
+            .map(storage::create)
+            .find(|codec| data_directory.join(codec.file_name()).exists());
+
+        match codec {
+            Some(codec) => Ok(FileBlockStore {
+                path: data_directory.join(codec.file_name()),
+                codec,
+                file: None,
+                read_only: true,
+            }),
+            None => Err(format!("Data directory {:?} holds no stored blocks", data_directory))
+        }
+    }
+
+    /// Replace the stored blocks by those stored in the given other encoding
+    /// in the same data directory, and remove the latter.
+    fn migrate(&mut self, data_directory: &Path, encoding: StorageEncoding) -> Result<(), String> {
+        let other_block_store = match FileBlockStore::open(data_directory, encoding) {
+            Ok(other_block_store) => other_block_store,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+
+        match other_block_store.load() {
+            Ok(Some(chain)) => {
+                match self.replace(&chain) {
+                    Ok(()) => {}
+                    Err(e) => {
+                        return Err(e);
+                    }
+                }
+            }
+            Ok(None) => {}
+            Err(e) => {
+                return Err(e);
+            }
+        }
+
+        match fs::remove_file(&other_block_store.path) {
+            Ok(()) => {
+                info!("Migrated stored blocks from {:?} to {:?}", other_block_store.path, self.path);
+                Ok(())
+            }
+            Err(e) => Err(format!("Failed to remove migrated blocks {:?}: {:?}", other_block_store.path, e))
+        }
+    }
+}
+
+impl BlockStore for FileBlockStore {
+    fn load(&self) -> Result<Option<Chain>, String> {
+        let mut contents = vec![];
+        let read_result = File::open(&self.path).and_then(|mut file| file.read_to_end(&mut contents));
+        match read_result {
+            Ok(_) => {}
+            Err(_) if self.file.is_none() => {
+                return Ok(None);
+            }
+            Err(e) => {
+                return Err(format!("Failed to read stored blocks {:?}: {:?}", self.path, e));
+            }
+        }
+
+        let (storage_version, mut records) = match self.codec.split_records(&contents) {
+            Ok((storage_version, records, _)) => (storage_version, records.into_iter()),
+            Err(e) => {
+                return Err(format!("Failed to read stored blocks {:?}: {}", self.path, e));
+            }
+        };
+
+        let root: StoredRoot = match records.next().map(|record| self.codec.decode_root(record, storage_version)) {
+            Some(Ok(root)) => root,
+            Some(Err(e)) => {
+                return Err(format!("Failed to parse the genesis configuration hash of the stored blocks {:?}: {}", self.path, e));
+            }
+            None => {
+                return Ok(None);
+            }
+        };
+
+        let mut blocks = vec![];
+        for (index, record) in records.enumerate() {
+            match self.codec.decode_block(record, storage_version) {
+                Ok(block) => blocks.push(Block::from(block)),
+                Err(e) => {
+                    return Err(format!("Failed to parse stored block {} in {:?}: {}", index, self.path, e));
+                }
+            }
+        }
+
+        match root.restore(blocks) {
+            Ok(chain) => Ok(chain),
+            Err(e) => Err(format!("Stored blocks in {:?} are inconsistent: {}", self.path, e))
+        }
+    }
+
+    fn append(&mut self, block: &Block) -> Result<(), String> {
+        if self.read_only {
+            return Err(format!("Stored blocks {:?} are read-only", self.path));
+        }
+
+        let record = self.codec.encode_block(&StoredBlock::from(block));
+        let file = match self.file {
+            Some(ref mut file) => file,
+            None => {
+                return Err("Cannot append a block before a chain was stored".to_string());
+            }
+        };
+
+        match file.write_all(&record).and_then(|_| file.sync_data()) {
+            Ok(()) => Ok(()),
+            Err(e) => Err(format!("Failed to store block {:?} in {:?}: {:?}", block.identifier, self.path, e))
+        }
+    }
+
+    fn replace(&mut self, chain: &Chain) -> Result<(), String> {
+        if self.read_only {
+            return Err(format!("Stored blocks {:?} are read-only", self.path));
+        }
+
+        let mut contents = self.codec.header();
+        contents.extend(self.codec.encode_root(&StoredRoot::of(chain)));
+        for block in chain.get_ordered_blocks() {
+            contents.extend(self.codec.encode_block(&StoredBlock::from(&block)));
+        }
+
+        // replace the file atomically, so that a crash does not lose the previous chain
+        let temporary_path = self.path.with_extension("tmp");
+        let write_result = File::create(&temporary_path)
+            .and_then(|mut file| file.write_all(&contents).and_then(|_| file.sync_all()))
+            .and_then(|_| fs::rename(&temporary_path, &self.path))
+            .and_then(|_| OpenOptions::new().append(true).open(&self.path));
+
+        match write_result {
+            Ok(file) => {
+                self.file = Some(file);
+                Ok(())
+            }
+            Err(e) => Err(format!("Failed to store chain in {:?}: {:?}", self.path, e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod block_store_test {
+
+    use super::*;
+    use ::chain::hasher::Hasher;
+    use std::env;
+
+    #[test]
+    fn test_file_block_store() {
+        for encoding in StorageEncoding::all() {
+            let data_directory = env::temp_dir().join(format!("node_rs_block_store_test_{}_{:?}", ::std::process::id(), encoding));
+            let _ = fs::remove_dir_all(&data_directory);
+
+            let mut chain = Chain::new("genesis".to_string(), Hasher::default());
+            let mut store = FileBlockStore::open(&data_directory, encoding).unwrap();
+            assert_eq!(Ok(None), store.load());
+            assert!(store.append(&chain.head().unwrap().block).is_err());
+            assert_eq!(Ok(()), store.replace(&chain));
+
+            let block = Block::new(Hasher::default(), chain.genesis_identifier_hash.clone(), None, vec![]);
+            chain.add_block(block.clone());
+            assert_eq!(Ok(()), store.append(&block));
+
+            // simulate a crash while appending the next block
+            let path = data_directory.join(storage::create(encoding).file_name());
+            OpenOptions::new().append(true).open(&path).unwrap().write_all(b"{\"identifier\":").unwrap();
+
+            let reopened_store = FileBlockStore::open(&data_directory, encoding).unwrap();
+            let loaded_chain = reopened_store.load().unwrap().unwrap();
+            assert_eq!("genesis", loaded_chain.genesis_configuration_hash);
+            assert_eq!(chain.get_ordered_blocks(), loaded_chain.get_ordered_blocks());
+            assert_eq!(chain.head(), loaded_chain.head());
+
+            let _ = fs::remove_dir_all(&data_directory);
+        }
+    }
+
+    #[test]
+    fn test_migrate_file_block_store() {
+        let data_directory = env::temp_dir().join(format!("node_rs_block_store_migration_test_{}", ::std::process::id()));
+        let _ = fs::remove_dir_all(&data_directory);
+
+        let mut chain = Chain::new("genesis".to_string(), Hasher::default());
+        let block = Block::new(Hasher::default(), chain.genesis_identifier_hash.clone(), None, vec![]);
+        chain.add_block(block.clone());
+
+        // blocks stored by earlier versions of this node, without a header
+        let mut contents = "\"genesis\"\n".to_string();
+        for block in chain.get_ordered_blocks() {
+            contents.push_str(&::serde_json::to_string(&block).unwrap());
+            contents.push('\n');
+        }
+        fs::create_dir_all(&data_directory).unwrap();
+        let json_path = data_directory.join(storage::create(StorageEncoding::Json).file_name());
+        fs::write(&json_path, contents).unwrap();
+
+        let store = FileBlockStore::open(&data_directory, StorageEncoding::Bincode).unwrap();
+        assert!(!json_path.exists());
+        let loaded_chain = store.load().unwrap().unwrap();
+        assert_eq!(chain.get_ordered_blocks(), loaded_chain.get_ordered_blocks());
+        assert_eq!(chain.head(), loaded_chain.head());
+
+        let _ = fs::remove_dir_all(&data_directory);
+    }
+}
@@ -1,76 +1,1267 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::SocketAddr;
+use std::sync::Arc;
 use std::vec::Vec;
 
 use ::chain::block::Block;
-use ::chain::transaction::Transaction;
-use chain::chain_visitor::HeaviestBlockVisitor;
-use chain::chain_walker::HeaviestBlockWalker;
-use chain::chain_walker::ChainWalker;
+use ::chain::chain_visitor::{ChainVisitor, ElectionPhase, VoterRegistryVisitor};
+use ::chain::hasher::Hasher;
+use ::chain::sealer_set::SealerSet;
+use ::chain::snapshot::StateSnapshot;
+use ::chain::transaction::{Transaction, TransactionType};
+use ::chain::types::{BlockHash, Height};
+use crypto_rs::arithmetic::mod_int::ModInt;
+
+/// Errors occurring when the structure of a chain is inconsistent,
+/// e.g. because a chain received from another node is malformed.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum ChainError {
+    /// The block with the given identifier is referenced but not known.
+    MissingBlock(BlockHash),
+    /// The block with the given identifier is its own ancestor.
+    Cycle(BlockHash),
+}
+
+/// The weight of a block sealed by the leader of its height, as in the Clique protocol.
+pub const DIFFICULTY_IN_TURN: u64 = 2;
+
+/// The weight of a block sealed out of turn, i.e. by a co-leader in place of the leader of its height.
+pub const DIFFICULTY_OUT_OF_TURN: u64 = 1;
+
+/// The head of the chain, i.e. the block at the end of the heaviest branch.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct HeadInfo {
+    /// The height of the block, whereas the genesis block has height zero.
+    pub height: Height,
+    /// The sum of the difficulties of the block and all its ancestors.
+    pub total_difficulty: u64,
+    /// The block itself.
+    pub block: Block,
+}
+
+/// A branch which is not part of the canonical chain and fell behind its head.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct AbandonedBranch {
+    /// The identifier of the last block of the branch.
+    pub tip: BlockHash,
+    /// The identifiers of all blocks of the branch which are not part of
+    /// the canonical chain, starting with the tip.
+    pub blocks: Vec<BlockHash>,
+}
 
 #[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
 pub struct Chain {
     /// the hash of the genesis configuration
     pub genesis_configuration_hash: String,
-    /// the hash of the genesis block
-    pub genesis_identifier_hash: String,
+    /// the hash of the genesis block, or of the block of the state snapshot if the chain starts with one
+    pub genesis_identifier_hash: BlockHash,
     /// all known blocks
-    pub blocks: HashMap<String, Block>,
+    pub blocks: HashMap<BlockHash, Block>,
     /// a matrix creating the relation between blocks
     /// key is the parent, values are its children
-    pub adjacent_matrix: HashMap<String, Vec<String>>
+    pub adjacent_matrix: HashMap<BlockHash, Vec<BlockHash>>,
+    /// the height of each block, used to maintain the head without traversing all blocks.
+    /// Maintained by `add_block`, but must be rebuilt using `reindex` if `blocks` or
+    /// `adjacent_matrix` are modified otherwise. Until then, the head is found by traversing all blocks.
+    #[serde(skip)]
+    heights: HashMap<BlockHash, Height>,
+    /// the height and the identifier of the head, valid as long as the heights are
+    #[serde(skip)]
+    head: (Height, BlockHash),
+    /// the height and the identifier of the finalized head, valid as long as the heights are.
+    /// The head never moves to a branch not containing it.
+    #[serde(skip)]
+    finalized: (Height, BlockHash),
+    /// the identifiers of the blocks including each transaction, valid as long as the heights are
+    #[serde(skip)]
+    transaction_blocks: HashMap<String, Vec<BlockHash>>,
+    /// the identifiers of the blocks including a vote of each voter on each question, valid as long as the heights are
+    #[serde(skip)]
+    voter_blocks: HashMap<(Option<String>, usize), Vec<BlockHash>>,
+    /// the identifiers of the blocks at each height, valid as long as the heights are
+    #[serde(skip)]
+    height_blocks: Vec<Vec<BlockHash>>,
+    /// the total difficulty of each block, valid as long as the heights are
+    #[serde(skip)]
+    total_difficulties: HashMap<BlockHash, u64>,
+    /// the sealers authorized to seal the children of each block, valid as long as the heights are.
+    /// Blocks not affecting the sealers share the set of their parent.
+    #[serde(skip)]
+    sealer_sets: HashMap<BlockHash, Arc<SealerSet>>,
+    /// the phase of the election on the branch ending with each block, valid as long as the heights are
+    #[serde(skip)]
+    election_phases: HashMap<BlockHash, ElectionPhase>,
+    /// the latest registration or revocation of each voter on the branch ending with each block, valid as long as the heights are.
+    /// Blocks neither registering nor revoking voters share the registry of their parent.
+    #[serde(skip)]
+    voter_registries: HashMap<BlockHash, Arc<VoterRegistryVisitor>>,
+    /// the sealers of the genesis configuration, or of the state snapshot if the chain starts with one, deciding which
+    /// blocks are sealed in turn. If None, all blocks are considered to be sealed out of turn, so that the longest branch is the heaviest one.
+    #[serde(skip)]
+    genesis_sealer_set: Option<Arc<SealerSet>>,
+    /// the election key committed by any block or by the state snapshot, if the sealers generated it jointly,
+    /// valid as long as the heights are. Blocks committing another key are rejected, hence there is at most one.
+    #[serde(skip)]
+    election_key: Option<ModInt>,
+    /// the summary of the blocks up to the root of the chain, if the chain does not start with the genesis block
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    state_snapshot: Option<StateSnapshot>,
 }
 
-impl Chain {
+impl Chain {
+
+    /// Create a new chain consisting only of a genesis block without transactions.
+    ///
+    /// - `genesis_hash`: The hash of the genesis configuration
+    /// - `hasher`: The hash function deriving the identifier of the genesis block
+    pub fn new(genesis_hash: String, hasher: Hasher) -> Self {
+        // create the genesis block with an empty hash and no transactions
+        let trxs: Vec<Transaction> = vec![];
+        let genesis_block: Block = Block::new(hasher, BlockHash::default(), None, trxs);
+
+        Chain::from_genesis_block(genesis_hash, genesis_block)
+    }
+
+    /// Create a new chain consisting only of the given genesis block.
+    ///
+    /// - `genesis_hash`: The hash of the genesis configuration
+    /// - `genesis_block`: The first block of the chain
+    pub fn from_genesis_block(genesis_hash: String, genesis_block: Block) -> Self {
+        let mut blocks = HashMap::new();
+        blocks.insert(genesis_block.identifier.clone(), genesis_block.clone());
+
+        // Add an entry for the genesis block in the adjacent matrix,
+        // i.e. initialize children of the genesis block as an empty vector.
+        let mut adjacent_matrix: HashMap<BlockHash, Vec<BlockHash>> = HashMap::new();
+        adjacent_matrix.insert(genesis_block.identifier.clone(), vec![]);
+
+        let mut heights = HashMap::new();
+        heights.insert(genesis_block.identifier.clone(), Height::GENESIS);
+
+        let mut total_difficulties = HashMap::new();
+        total_difficulties.insert(genesis_block.identifier.clone(), 0);
+
+        let mut election_phases = HashMap::new();
+        election_phases.insert(genesis_block.identifier.clone(), Chain::get_next_election_phase(&ElectionPhase::NotOpened, &genesis_block));
+
+        let mut voter_registries = HashMap::new();
+        voter_registries.insert(genesis_block.identifier.clone(), Chain::get_next_voter_registry(&Arc::new(VoterRegistryVisitor::new()), &genesis_block));
+
+        trace!("Genesis block hash is: {:?}", genesis_block.identifier.clone());
+
+        Chain {
+            genesis_configuration_hash: genesis_hash,
+            genesis_identifier_hash: genesis_block.identifier.clone(),
+            blocks,
+            adjacent_matrix,
+            heights,
+            head: (Height::GENESIS, genesis_block.identifier.clone()),
+            finalized: (Height::GENESIS, genesis_block.identifier.clone()),
+            transaction_blocks: Chain::index_transactions(&genesis_block),
+            voter_blocks: Chain::index_voters(&genesis_block),
+            height_blocks: vec![vec![genesis_block.identifier.clone()]],
+            total_difficulties,
+            sealer_sets: HashMap::new(),
+            election_phases,
+            voter_registries,
+            genesis_sealer_set: None,
+            election_key: Chain::find_election_key(&genesis_block),
+            state_snapshot: None,
+        }
+    }
+
+    /// Create a new chain consisting only of the block of the given snapshot, continuing the chain
+    /// summarized by it. Its blocks keep the heights and total difficulties they have on the full chain.
+    pub fn from_state_snapshot(state_snapshot: StateSnapshot) -> Self {
+        let mut chain = Chain::from_genesis_block(state_snapshot.genesis_configuration_hash.clone(), state_snapshot.block.clone());
+        let root_hash = chain.genesis_identifier_hash.clone();
+
+        chain.heights.insert(root_hash.clone(), state_snapshot.height);
+        chain.total_difficulties.insert(root_hash.clone(), state_snapshot.total_difficulty);
+        chain.election_phases.insert(root_hash.clone(), state_snapshot.phase.clone());
+        chain.voter_registries.insert(root_hash.clone(), Chain::get_snapshot_voter_registry(&state_snapshot));
+        chain.head = (state_snapshot.height, root_hash.clone());
+        chain.finalized = (state_snapshot.height, root_hash);
+        chain.election_key = state_snapshot.election_key.clone().or_else(|| chain.election_key.take());
+        chain.state_snapshot = Some(state_snapshot);
+
+        chain
+    }
+
+    /// Returns the election key generated jointly by the sealers, or None if it is not committed yet
+    /// or the election key is declared in the genesis configuration.
+    pub fn get_election_key(&self) -> Option<&ModInt> {
+        self.election_key.as_ref()
+    }
+
+    /// Returns the snapshot summarizing the blocks up to the root of the chain,
+    /// or None if the chain starts with the genesis block.
+    pub fn get_state_snapshot(&self) -> Option<&StateSnapshot> {
+        self.state_snapshot.as_ref()
+    }
+
+    /// Returns the height of the first block of the chain, i.e. zero unless the chain starts with a state snapshot.
+    /// Blocks below are not known.
+    pub fn root_height(&self) -> Height {
+        self.state_snapshot.as_ref().map_or(Height::GENESIS, |state_snapshot| state_snapshot.height)
+    }
+
+    /// Returns the total difficulty of the first block of the chain.
+    fn root_total_difficulty(&self) -> u64 {
+        self.state_snapshot.as_ref().map_or(0, |state_snapshot| state_snapshot.total_difficulty)
+    }
+
+    /// Returns the index of the given height in `height_blocks`, or None if it is below the root.
+    fn height_index(&self, height: Height) -> Option<usize> {
+        let root_height = self.root_height();
+        if height < root_height {
+            return None;
+        }
+
+        Some(height.distance_to(root_height))
+    }
+
+    /// Track the sealers starting with the given ones of the genesis configuration, weigh
+    /// the blocks by whether they are sealed in turn by them, and reindex the chain.
+    /// If None, sealers are not tracked and all blocks are equally heavy.
+    /// If the chain starts with a state snapshot, the sealers start with those of the snapshot instead.
+    ///
+    /// Returns an error if the structure of the chain is inconsistent.
+    pub fn set_sealer_set(&mut self, genesis_sealer_set: Option<SealerSet>) -> Result<(), ChainError> {
+        self.genesis_sealer_set = match (genesis_sealer_set, &self.state_snapshot) {
+            (Some(sealer_set), Some(state_snapshot)) => Some(Arc::new(state_snapshot.get_sealer_set(sealer_set.get_vote_epoch_length()))),
+            (sealer_set, _) => sealer_set.map(Arc::new)
+        };
+
+        self.reindex()
+    }
+
+    /// Returns the sealers authorized to seal the children of the block with the given identifier,
+    /// or None if the block is not contained in the chain or sealers are not tracked.
+    ///
+    /// Returns an error if the structure of the chain is inconsistent.
+    pub fn get_sealer_set(&self, block_hash: &BlockHash) -> Result<Option<Arc<SealerSet>>, ChainError> {
+        if self.is_indexed() {
+            return Ok(self.sealer_sets.get(block_hash).cloned());
+        }
+
+        if self.genesis_sealer_set.is_none() || !self.blocks.contains_key(block_hash) {
+            return Ok(None);
+        }
+
+        // collect the ancestors up to the root, then apply them from the oldest to the newest
+        let mut path = vec![];
+        let mut current_hash = block_hash;
+        while *current_hash != self.genesis_identifier_hash {
+            if path.len() >= self.blocks.len() {
+                return Err(ChainError::Cycle(block_hash.clone()));
+            }
+
+            current_hash = match self.blocks.get(current_hash) {
+                Some(block) => {
+                    path.push(block);
+                    &block.data.parent
+                }
+                None => {
+                    return Err(ChainError::MissingBlock(current_hash.clone()));
+                }
+            };
+        }
+
+        let root_height = self.root_height();
+        let mut sealer_set = self.genesis_sealer_set.clone();
+        for (index, block) in path.into_iter().rev().enumerate() {
+            sealer_set = Chain::get_next_sealer_set(&sealer_set, root_height + (index + 1), block);
+        }
+
+        Ok(sealer_set)
+    }
+
+    /// Returns the sealers following the given block at the given height, whose parent is followed by the given sealers.
+    fn get_next_sealer_set(parent_sealer_set: &Option<Arc<SealerSet>>, height: Height, block: &Block) -> Option<Arc<SealerSet>> {
+        parent_sealer_set.as_ref().map(|parent_sealer_set| {
+            match parent_sealer_set.apply(height, block) {
+                Some(sealer_set) => Arc::new(sealer_set),
+                None => Arc::clone(parent_sealer_set)
+            }
+        })
+    }
+
+    /// Returns the phase of the election on the branch ending with the block with the given identifier,
+    /// or None if the block is not contained in the chain.
+    ///
+    /// Returns an error if the structure of the chain is inconsistent.
+    pub fn election_phase_of(&self, block_hash: &BlockHash) -> Result<Option<ElectionPhase>, ChainError> {
+        if self.is_indexed() {
+            return Ok(self.election_phases.get(block_hash).cloned());
+        }
+
+        if !self.blocks.contains_key(block_hash) {
+            return Ok(None);
+        }
+
+        // walk up to the newest block opening or closing the voting
+        let mut amount_of_ancestors = 0;
+        let mut current_hash = block_hash;
+        while *current_hash != self.genesis_identifier_hash {
+            if amount_of_ancestors >= self.blocks.len() {
+                return Err(ChainError::Cycle(block_hash.clone()));
+            }
+
+            let block = match self.blocks.get(current_hash) {
+                Some(block) => block,
+                None => {
+                    return Err(ChainError::MissingBlock(current_hash.clone()));
+                }
+            };
+            if let Some(election_phase) = Chain::find_election_phase(block) {
+                return Ok(Some(election_phase));
+            }
+
+            current_hash = &block.data.parent;
+            amount_of_ancestors += 1;
+        }
+
+        Ok(Some(self.root_election_phase()))
+    }
+
+    /// Returns the phase of the election after the first block of the chain.
+    fn root_election_phase(&self) -> ElectionPhase {
+        match self.state_snapshot {
+            Some(ref state_snapshot) => state_snapshot.phase.clone(),
+            None => match self.blocks.get(&self.genesis_identifier_hash) {
+                Some(genesis_block) => Chain::get_next_election_phase(&ElectionPhase::NotOpened, genesis_block),
+                None => ElectionPhase::NotOpened
+            }
+        }
+    }
+
+    /// Returns the phase of the election following the given block, whose parent is followed by the given phase.
+    fn get_next_election_phase(parent_election_phase: &ElectionPhase, block: &Block) -> ElectionPhase {
+        Chain::find_election_phase(block).unwrap_or_else(|| parent_election_phase.clone())
+    }
+
+    /// Returns the phase of the election entered by the given block, i.e. the one of its last transaction
+    /// opening or closing the voting, or None if the block does neither.
+    fn find_election_phase(block: &Block) -> Option<ElectionPhase> {
+        block.data.transactions.iter().rev()
+            .filter_map(|transaction| match transaction.trx_type {
+                TransactionType::VoteOpened => Some(ElectionPhase::Opened),
+                TransactionType::VoteClosed => Some(ElectionPhase::Closed),
+                _ => None
+            })
+            .next()
+    }
+
+    /// Returns the latest registration or revocation of each voter on the branch ending with the block
+    /// with the given identifier, or None if the block is not contained in the chain.
+    ///
+    /// Returns an error if the structure of the chain is inconsistent.
+    pub fn voter_registry_of(&self, block_hash: &BlockHash) -> Result<Option<Arc<VoterRegistryVisitor>>, ChainError> {
+        if self.is_indexed() {
+            return Ok(self.voter_registries.get(block_hash).cloned());
+        }
+
+        if !self.blocks.contains_key(block_hash) {
+            return Ok(None);
+        }
+
+        // collect the ancestors up to the root, then apply them from the oldest to the newest
+        let mut path = vec![];
+        let mut current_hash = block_hash;
+        while *current_hash != self.genesis_identifier_hash {
+            if path.len() >= self.blocks.len() {
+                return Err(ChainError::Cycle(block_hash.clone()));
+            }
+
+            current_hash = match self.blocks.get(current_hash) {
+                Some(block) => {
+                    path.push(block);
+                    &block.data.parent
+                }
+                None => {
+                    return Err(ChainError::MissingBlock(current_hash.clone()));
+                }
+            };
+        }
+
+        let mut voter_registry = self.root_voter_registry();
+        for block in path.into_iter().rev() {
+            voter_registry = Chain::get_next_voter_registry(&voter_registry, block);
+        }
+
+        Ok(Some(voter_registry))
+    }
+
+    /// Returns the voters registered or revoked up to the first block of the chain.
+    fn root_voter_registry(&self) -> Arc<VoterRegistryVisitor> {
+        let empty_voter_registry = Arc::new(VoterRegistryVisitor::new());
+        match self.state_snapshot {
+            Some(ref state_snapshot) => Chain::get_snapshot_voter_registry(state_snapshot),
+            None => match self.blocks.get(&self.genesis_identifier_hash) {
+                Some(genesis_block) => Chain::get_next_voter_registry(&empty_voter_registry, genesis_block),
+                None => empty_voter_registry
+            }
+        }
+    }
+
+    /// Returns the voters registered or revoked by the blocks summarized by the given snapshot.
+    fn get_snapshot_voter_registry(state_snapshot: &StateSnapshot) -> Arc<VoterRegistryVisitor> {
+        let mut voter_registry = VoterRegistryVisitor::new();
+        voter_registry.visit_snapshot(state_snapshot);
+
+        Arc::new(voter_registry)
+    }
+
+    /// Returns the registry following the given block, whose parent is followed by the given registry.
+    fn get_next_voter_registry(parent_voter_registry: &Arc<VoterRegistryVisitor>, block: &Block) -> Arc<VoterRegistryVisitor> {
+        let mut voter_registrations = block.data.transactions.iter()
+            .filter_map(|transaction| transaction.voter_registration.as_ref())
+            .peekable();
+        if voter_registrations.peek().is_none() {
+            return Arc::clone(parent_voter_registry);
+        }
+
+        // later registrations of the same voter replace earlier ones
+        let mut voter_registry = VoterRegistryVisitor::clone(parent_voter_registry);
+        for voter_registration in voter_registrations {
+            voter_registry.register(voter_registration.clone());
+        }
+
+        Arc::new(voter_registry)
+    }
+
+    /// Returns the difficulty of the given block at the given height, i.e. `DIFFICULTY_IN_TURN`
+    /// if it is sealed by the leader of its height among the sealers authorized by its parent,
+    /// and `DIFFICULTY_OUT_OF_TURN` otherwise. The genesis block has no difficulty.
+    pub fn get_difficulty(parent_sealer_set: Option<&SealerSet>, block: &Block, height: Height) -> u64 {
+        if Height::GENESIS == height {
+            return 0;
+        }
+
+        let leader = parent_sealer_set.and_then(|sealer_set| sealer_set.get_epoch_schedule().get_leader(height));
+        match (leader, block.data.sealer) {
+            (Some(leader), Some(sealer)) if leader == sealer => DIFFICULTY_IN_TURN,
+            _ => DIFFICULTY_OUT_OF_TURN
+        }
+    }
+
+    /// Returns true, if a head of the given total difficulty and height is preferred
+    /// over one of the other total difficulty and height: the heavier one wins, and
+    /// among equally heavy ones the lower one, as it contains more blocks sealed in turn.
+    fn is_heavier(total_difficulty: u64, height: Height, other_total_difficulty: u64, other_height: Height) -> bool {
+        total_difficulty > other_total_difficulty || (total_difficulty == other_total_difficulty && height < other_height)
+    }
+
+    /// Recreate a chain from the given blocks, ordered such that each block is preceded
+    /// by its parent, e.g. as returned by `get_ordered_blocks`. The first block is the genesis block.
+    ///
+    /// Returns None if no blocks are given, or an error if the parent of a block is missing.
+    pub fn from_ordered_blocks(genesis_hash: String, blocks: Vec<Block>) -> Result<Option<Self>, ChainError> {
+        let mut blocks = blocks.into_iter();
+        let mut chain = match blocks.next() {
+            Some(genesis_block) => Chain::from_genesis_block(genesis_hash, genesis_block),
+            None => {
+                return Ok(None);
+            }
+        };
+
+        for block in blocks {
+            if !chain.has_parent_of_block(&block) {
+                return Err(ChainError::MissingBlock(block.data.parent));
+            }
+
+            chain.add_block(block);
+        }
+
+        Ok(Some(chain))
+    }
+
+    /// Recreate a chain starting with the given snapshot from the given blocks, ordered such that
+    /// each block is preceded by its parent, e.g. as returned by `get_ordered_blocks`.
+    /// The first block is the one of the snapshot, hence it is skipped.
+    ///
+    /// Returns an error if the parent of a block is missing.
+    pub fn from_state_snapshot_and_blocks(state_snapshot: StateSnapshot, blocks: Vec<Block>) -> Result<Self, ChainError> {
+        let mut chain = Chain::from_state_snapshot(state_snapshot);
+
+        for block in blocks.into_iter().skip(1) {
+            if !chain.has_parent_of_block(&block) {
+                return Err(ChainError::MissingBlock(block.data.parent));
+            }
+
+            chain.add_block(block);
+        }
+
+        Ok(chain)
+    }
+
+    /// Rebuild the heights of all blocks and the head, e.g. after this chain was deserialized.
+    /// The head is chosen as if the blocks were added parent before child, starting at the finalized head,
+    /// so that the head never moves to a branch which reverts a block finalized before or while doing so.
+    ///
+    /// Returns an error if the structure of the chain is inconsistent.
+    /// In this case, the head is found by traversing all blocks.
+    pub fn reindex(&mut self) -> Result<(), ChainError> {
+        self.heights.clear();
+
+        // a deserialized chain does not know its finalized head, hence it is derived again from the root
+        let finalized = if self.blocks.contains_key(&self.finalized.1) {
+            self.finalized.clone()
+        } else {
+            (self.root_height(), self.genesis_identifier_hash.clone())
+        };
+
+        let mut heights: HashMap<BlockHash, Height> = HashMap::new();
+        let mut height_blocks: Vec<Vec<BlockHash>> = vec![];
+        let mut total_difficulties: HashMap<BlockHash, u64> = HashMap::new();
+        let mut sealer_sets: HashMap<BlockHash, Arc<SealerSet>> = HashMap::new();
+        let mut election_phases: HashMap<BlockHash, ElectionPhase> = HashMap::new();
+        let mut voter_registries: HashMap<BlockHash, Arc<VoterRegistryVisitor>> = HashMap::new();
+        let mut ordered_blocks: Vec<BlockHash> = vec![];
+        let mut queue = VecDeque::new();
+        queue.push_back((self.root_height(), 0, self.genesis_sealer_set.clone(), ElectionPhase::NotOpened, Arc::new(VoterRegistryVisitor::new()), &self.genesis_identifier_hash));
+        while let Some((height, parent_total_difficulty, parent_sealer_set, parent_election_phase, parent_voter_registry, block_hash)) = queue.pop_front() {
+            if heights.insert(block_hash.clone(), height).is_some() {
+                return Err(ChainError::Cycle(block_hash.clone()));
+            }
+            ordered_blocks.push(block_hash.clone());
+            let (total_difficulty, sealer_set, election_phase, voter_registry) = match self.blocks.get(block_hash) {
+                // the sealers of the root already account for its votes
+                Some(_) if self.genesis_identifier_hash.eq(block_hash) => (self.root_total_difficulty(), parent_sealer_set.clone(), self.root_election_phase(), self.root_voter_registry()),
+                Some(block) => (
+                    parent_total_difficulty + Chain::get_difficulty(parent_sealer_set.as_deref(), block, height),
+                    Chain::get_next_sealer_set(&parent_sealer_set, height, block),
+                    Chain::get_next_election_phase(&parent_election_phase, block),
+                    Chain::get_next_voter_registry(&parent_voter_registry, block)
+                ),
+                None => {
+                    return Err(ChainError::MissingBlock(block_hash.clone()));
+                }
+            };
+            total_difficulties.insert(block_hash.clone(), total_difficulty);
+            if let Some(ref sealer_set) = sealer_set {
+                sealer_sets.insert(block_hash.clone(), Arc::clone(sealer_set));
+            }
+            election_phases.insert(block_hash.clone(), election_phase.clone());
+            voter_registries.insert(block_hash.clone(), Arc::clone(&voter_registry));
+            let height_index = height.distance_to(self.root_height());
+            if height_blocks.len() <= height_index {
+                height_blocks.push(vec![]);
+            }
+            height_blocks[height_index].push(block_hash.clone());
+
+            let children = match self.adjacent_matrix.get(block_hash) {
+                Some(children) => children,
+                None => {
+                    return Err(ChainError::MissingBlock(block_hash.clone()));
+                }
+            };
+            for child_hash in children.iter() {
+                queue.push_back((height + 1, total_difficulty, sealer_set.clone(), election_phase.clone(), Arc::clone(&voter_registry), child_hash));
+            }
+        }
+
+        self.heights = heights;
+        self.height_blocks = height_blocks;
+        self.total_difficulties = total_difficulties;
+        self.sealer_sets = sealer_sets;
+        self.election_phases = election_phases;
+        self.voter_registries = voter_registries;
+        self.head = finalized.clone();
+        self.finalized = finalized;
+        for block_hash in ordered_blocks {
+            let (height, total_difficulty) = (self.heights[&block_hash], self.total_difficulties[&block_hash]);
+            self.choose_head(block_hash, height, total_difficulty);
+        }
+        self.transaction_blocks.clear();
+        self.voter_blocks.clear();
+        self.election_key = self.state_snapshot.as_ref().and_then(|state_snapshot| state_snapshot.election_key.clone());
+        for block in self.blocks.values() {
+            if self.election_key.is_none() {
+                self.election_key = Chain::find_election_key(block);
+            }
+            for transaction in block.data.transactions.iter() {
+                self.transaction_blocks.entry(transaction.identifier.clone()).or_default().push(block.identifier.clone());
+            }
+            for (voter, including_blocks) in Chain::index_voters(block) {
+                self.voter_blocks.entry(voter).or_default().extend(including_blocks);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns true, if the heights are known for all blocks.
+    fn is_indexed(&self) -> bool {
+        self.heights.len() == self.blocks.len()
+    }
+
+    /// Returns the height of the block with the given identifier,
+    /// or None if the block is not contained in the chain.
+    ///
+    /// Returns an error if the structure of the chain is inconsistent.
+    pub fn height_of(&self, block_hash: &BlockHash) -> Result<Option<Height>, ChainError> {
+        if self.is_indexed() {
+            return Ok(self.heights.get(block_hash).cloned());
+        }
+
+        if !self.blocks.contains_key(block_hash) {
+            return Ok(None);
+        }
+
+        // count the ancestors up to the root
+        let mut height = self.root_height();
+        let mut current_hash = block_hash;
+        while *current_hash != self.genesis_identifier_hash {
+            if height.distance_to(self.root_height()) >= self.blocks.len() {
+                return Err(ChainError::Cycle(block_hash.clone()));
+            }
+
+            current_hash = match self.blocks.get(current_hash) {
+                Some(block) => &block.data.parent,
+                None => {
+                    return Err(ChainError::MissingBlock(current_hash.clone()));
+                }
+            };
+            height += 1;
+        }
+
+        Ok(Some(height))
+    }
+
+    /// Returns the total difficulty of the block with the given identifier, i.e. the sum of the difficulties of
+    /// the block and all its ancestors, or None if the block is not contained in the chain or the chain is not indexed.
+    pub fn total_difficulty_of(&self, block_hash: &BlockHash) -> Option<u64> {
+        if !self.is_indexed() {
+            return None;
+        }
+
+        self.total_difficulties.get(block_hash).cloned()
+    }
+
+    /// Returns the head of the chain, i.e. the block of the greatest total difficulty among the finalized head and its descendants.
+    /// If multiple blocks have the same total difficulty, the lowest one wins. If they
+    /// also have the same height, the one on the branch which was added first to its parent wins.
+    ///
+    /// Returns an error if the structure of the chain is inconsistent.
+    pub fn head(&self) -> Result<HeadInfo, ChainError> {
+        if !self.is_indexed() {
+            return self.find_head_by_traversal();
+        }
+
+        match self.blocks.get(&self.head.1) {
+            Some(block) => Ok(HeadInfo {
+                height: self.head.0,
+                total_difficulty: self.total_difficulties[&self.head.1],
+                block: block.clone(),
+            }),
+            None => Err(ChainError::MissingBlock(self.head.1.clone()))
+        }
+    }
+
+    /// Walk all blocks to find the heaviest one among the finalized head and its descendants.
+    fn find_head_by_traversal(&self) -> Result<HeadInfo, ChainError> {
+        // a deserialized chain does not know its finalized head, hence all blocks are considered
+        let finalized_hash = if self.blocks.contains_key(&self.finalized.1) {
+            &self.finalized.1
+        } else {
+            &self.genesis_identifier_hash
+        };
+        let mut heaviest_block = None;
+        let mut visited_blocks = HashSet::new();
+
+        // walk depth-first, visiting children in the order they were added
+        let mut stack = vec![(self.root_height(), 0, self.genesis_sealer_set.clone(), false, &self.genesis_identifier_hash)];
+        while let Some((height, parent_total_difficulty, parent_sealer_set, is_parent_finalized, block_hash)) = stack.pop() {
+            if !visited_blocks.insert(block_hash) {
+                return Err(ChainError::Cycle(block_hash.clone()));
+            }
+
+            let (total_difficulty, sealer_set) = match self.blocks.get(block_hash) {
+                Some(_) if self.genesis_identifier_hash.eq(block_hash) => (self.root_total_difficulty(), parent_sealer_set.clone()),
+                Some(block) => (
+                    parent_total_difficulty + Chain::get_difficulty(parent_sealer_set.as_deref(), block, height),
+                    Chain::get_next_sealer_set(&parent_sealer_set, height, block)
+                ),
+                None => {
+                    return Err(ChainError::MissingBlock(block_hash.clone()));
+                }
+            };
+            let is_finalized = is_parent_finalized || finalized_hash.eq(block_hash);
+            let is_heaviest = match heaviest_block {
+                Some((heaviest_height, heaviest_total_difficulty, _)) => Chain::is_heavier(total_difficulty, height, heaviest_total_difficulty, heaviest_height),
+                None => true
+            };
+            if is_finalized && is_heaviest {
+                heaviest_block = Some((height, total_difficulty, block_hash));
+            }
+
+            let children = match self.adjacent_matrix.get(block_hash) {
+                Some(children) => children,
+                None => {
+                    return Err(ChainError::MissingBlock(block_hash.clone()));
+                }
+            };
+
+            for child_hash in children.iter().rev() {
+                stack.push((height + 1, total_difficulty, sealer_set.clone(), is_finalized, child_hash));
+            }
+        }
+
+        match heaviest_block {
+            Some((height, total_difficulty, block_hash)) => match self.blocks.get(block_hash) {
+                Some(block) => Ok(HeadInfo {
+                    height,
+                    total_difficulty,
+                    block: block.clone(),
+                }),
+                None => Err(ChainError::MissingBlock(block_hash.clone()))
+            },
+            None => Err(ChainError::MissingBlock(finalized_hash.clone()))
+        }
+    }
+
+    /// Returns the finalized head of the chain along with its height, i.e. the highest block of the
+    /// canonical chain on which more than half of the sealers authorized to build on it have built.
+    /// Blocks up to the finalized head are irreversible, as the head never moves to a branch not containing it.
+    /// If sealers are not tracked, the genesis block, or the block of the state snapshot, is returned.
+    ///
+    /// Returns an error if the structure of the chain is inconsistent.
+    pub fn finalized_head(&self) -> Result<(Height, &Block), ChainError> {
+        let (height, block_hash) = if self.is_indexed() {
+            self.finalized.clone()
+        } else {
+            let head = match self.head() {
+                Ok(head) => head,
+                Err(e) => {
+                    return Err(e);
+                }
+            };
+
+            match self.find_finalized(&head.block.identifier, head.height, self.root_height()) {
+                Ok(Some(finalized)) => finalized,
+                Ok(None) => (self.root_height(), self.genesis_identifier_hash.clone()),
+                Err(e) => {
+                    return Err(e);
+                }
+            }
+        };
+
+        match self.blocks.get(&block_hash) {
+            Some(block) => Ok((height, block)),
+            None => Err(ChainError::MissingBlock(block_hash))
+        }
+    }
+
+    /// Returns the highest block of the branch ending with the given tip and above the given height
+    /// on which more than half of the sealers authorized to build on it have built, along with its height.
+    /// Returns None if there is no such block or if sealers are not tracked.
+    fn find_finalized(&self, tip_hash: &BlockHash, tip_height: Height, lowest_height: Height) -> Result<Option<(Height, BlockHash)>, ChainError> {
+        // the sealers of the blocks above the current one
+        let mut builders: HashSet<SocketAddr> = HashSet::new();
+        let mut block_hash = tip_hash.clone();
+        let mut height = tip_height;
+
+        while height > lowest_height {
+            let sealer_set = match self.get_sealer_set(&block_hash) {
+                Ok(Some(sealer_set)) => sealer_set,
+                Ok(None) => {
+                    return Ok(None);
+                }
+                Err(e) => {
+                    return Err(e);
+                }
+            };
+
+            let authorized_builders: HashSet<usize> = builders.iter()
+                .filter_map(|builder| sealer_set.index_of(builder))
+                .collect();
+            if authorized_builders.len() > sealer_set.get_sealers().len() / 2 {
+                return Ok(Some((height, block_hash)));
+            }
+
+            let block = match self.blocks.get(&block_hash) {
+                Some(block) => block,
+                None => {
+                    return Err(ChainError::MissingBlock(block_hash));
+                }
+            };
+            if let Some(sealer) = block.data.sealer {
+                builders.insert(sealer);
+            }
+            block_hash = block.data.parent.clone();
+            height -= 1;
+        }
+
+        Ok(None)
+    }
+
+    /// Advance the finalized head along the branch of the head, if more sealers have built on it.
+    fn update_finalized(&mut self) {
+        match self.find_finalized(&self.head.1, self.head.0, self.finalized.0) {
+            Ok(Some(finalized)) => {
+                debug!("Finalized block {:?} at height {}", finalized.1, finalized.0);
+                self.finalized = finalized;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                warn!("Failed to determine the finalized head: {:?}", e);
+            }
+        }
+    }
+
+    /// Returns true, if the finalized head is an ancestor of the given block at the given height, or the block itself.
+    fn contains_finalized(&self, block_hash: &BlockHash, height: Height) -> bool {
+        if height < self.finalized.0 {
+            return false;
+        }
+
+        let mut ancestor_hash = block_hash;
+        for _ in 0..height.distance_to(self.finalized.0) {
+            ancestor_hash = match self.blocks.get(ancestor_hash) {
+                Some(ancestor) => &ancestor.data.parent,
+                None => {
+                    return false;
+                }
+            };
+        }
+
+        self.finalized.1.eq(ancestor_hash)
+    }
+
+    #[deprecated(note = "Panics on inconsistent chains, use `Chain::head()` instead")]
+    #[allow(deprecated)]
+    pub fn get_current_block_number(&self) -> usize {
+        self.get_current_block().0.as_usize()
+    }
+
+    #[deprecated(note = "Panics on inconsistent chains, use `Chain::head()` instead")]
+    #[allow(deprecated)]
+    pub fn get_current_block_timestamp(&self) -> u64 {
+        self.get_current_block().1.data.timestamp
+    }
+
+    #[deprecated(note = "Panics on inconsistent chains, use `Chain::head()` instead")]
+    pub fn get_current_block(&self) -> (Height, Block) {
+        let head = self.head().expect("Failed to determine the head of the chain");
+
+        (head.height, head.block)
+    }
+
+    /// Returns a key identifying the current state of the chain, i.e. the
+    /// amount of known blocks along with the identifier of the current block.
+    /// As long as the key does not change, neither does the chain.
+    pub fn get_snapshot_key(&self) -> (usize, BlockHash) {
+        match self.head() {
+            Ok(head) => (self.blocks.len(), head.block.identifier),
+            Err(_) => (self.blocks.len(), BlockHash::default())
+        }
+    }
+
+    /// Returns the height and the block of the canonical chain including
+    /// the transaction with the given identifier, if any.
+    ///
+    /// Returns an error if the structure of the chain is inconsistent.
+    pub fn find_canonical_transaction(&self, transaction_identifier: &str) -> Result<Option<(Height, &Block)>, ChainError> {
+        let head = match self.head() {
+            Ok(head) => head,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+
+        if !self.is_indexed() {
+            let canonical_path = match self.get_canonical_path() {
+                Ok(canonical_path) => canonical_path,
+                Err(e) => {
+                    return Err(e);
+                }
+            };
+
+            for (index, block_hash) in canonical_path.iter().enumerate() {
+                let block = &self.blocks[block_hash];
+                if block.data.transactions.iter().any(|transaction| transaction.identifier.eq(transaction_identifier)) {
+                    return Ok(Some((head.height - index, block)));
+                }
+            }
+
+            return Ok(None);
+        }
+
+        match self.transaction_blocks.get(transaction_identifier) {
+            Some(including_blocks) => self.find_canonical_among(&head, including_blocks),
+            None => Ok(None)
+        }
+    }
+
+    /// Returns true, if the canonical chain includes a vote of the voter with the given index on the question
+    /// with the given identifier, either in one of its blocks or among the votes summarized by its state snapshot.
+    /// None identifies the question whose voters are declared in `public_uciv.json`.
+    ///
+    /// Returns an error if the structure of the chain is inconsistent.
+    pub fn has_canonical_vote(&self, question_id: &Option<String>, voter_idx: usize) -> Result<bool, ChainError> {
+        if let Some(ref state_snapshot) = self.state_snapshot {
+            match state_snapshot.get_question_voters(question_id) {
+                Ok(ref voters) if voters.contains(&voter_idx) => {
+                    return Ok(true);
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("Skipping votes in snapshot at block {:?}: {}", state_snapshot.block.identifier, e);
+                }
+            }
+        }
+
+        let head = match self.head() {
+            Ok(head) => head,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+
+        if !self.is_indexed() {
+            let canonical_path = match self.get_canonical_path() {
+                Ok(canonical_path) => canonical_path,
+                Err(e) => {
+                    return Err(e);
+                }
+            };
+
+            return Ok(canonical_path.iter()
+                .any(|block_hash| Chain::index_voters(&self.blocks[block_hash]).contains_key(&(question_id.clone(), voter_idx))));
+        }
+
+        match self.voter_blocks.get(&(question_id.clone(), voter_idx)) {
+            Some(including_blocks) => self.find_canonical_among(&head, including_blocks).map(|found| found.is_some()),
+            None => Ok(false)
+        }
+    }
+
+    /// Returns the height and the block of the first of the given blocks which is part of the canonical chain
+    /// ending with the given head, if any. Requires the heights of all blocks to be known.
+    fn find_canonical_among<'a>(&'a self, head: &HeadInfo, block_hashes: &[BlockHash]) -> Result<Option<(Height, &'a Block)>, ChainError> {
+        for block_hash in block_hashes.iter() {
+            let height = self.heights[block_hash];
+            if height > head.height {
+                continue;
+            }
+
+            match self.find_canonical_block_hash(head, height) {
+                Ok(ref canonical_hash) if canonical_hash.eq(block_hash) => {
+                    return Ok(Some((height, &self.blocks[block_hash])));
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the blocks at the given height on all branches of the chain,
+    /// i.e. the block of the canonical chain along with those of competing forks.
+    ///
+    /// Returns an error if the structure of the chain is inconsistent.
+    pub fn blocks_at_height(&self, height: Height) -> Result<Vec<&Block>, ChainError> {
+        let height_index = match self.height_index(height) {
+            Some(height_index) => height_index,
+            None => {
+                return Ok(vec![]);
+            }
+        };
+
+        if !self.is_indexed() {
+            // walk breadth-first, level by level, down to the requested height
+            let mut level = vec![&self.genesis_identifier_hash];
+            for _ in 0..height_index {
+                let mut next_level = vec![];
+                for block_hash in level {
+                    match self.adjacent_matrix.get(block_hash) {
+                        Some(children) => next_level.extend(children.iter()),
+                        None => {
+                            return Err(ChainError::MissingBlock(block_hash.clone()));
+                        }
+                    }
+                }
+                if next_level.len() > self.blocks.len() {
+                    return Err(ChainError::Cycle(self.genesis_identifier_hash.clone()));
+                }
+
+                level = next_level;
+            }
+
+            let mut blocks = vec![];
+            for block_hash in level {
+                match self.blocks.get(block_hash) {
+                    Some(block) => blocks.push(block),
+                    None => {
+                        return Err(ChainError::MissingBlock(block_hash.clone()));
+                    }
+                }
+            }
+
+            return Ok(blocks);
+        }
 
-    pub fn new(genesis_hash: String) -> Self {
-        // create the genesis block with an empty hash and no transactions
-        let trxs: Vec<Transaction> = vec![];
-        let genesis_block: Block = Block::new(String::new(), trxs);
+        match self.height_blocks.get(height_index) {
+            Some(block_hashes) => Ok(block_hashes.iter().map(|block_hash| &self.blocks[block_hash]).collect()),
+            None => Ok(vec![])
+        }
+    }
 
-        let mut blocks = HashMap::new();
-        blocks.insert(genesis_block.identifier.clone(), genesis_block.clone());
+    /// Returns the block of the canonical chain at the given height,
+    /// or None if the canonical chain is not as high.
+    ///
+    /// Returns an error if the structure of the chain is inconsistent.
+    pub fn block_by_number(&self, height: Height) -> Result<Option<&Block>, ChainError> {
+        let head = match self.head() {
+            Ok(head) => head,
+            Err(e) => {
+                return Err(e);
+            }
+        };
 
-        // Add an entry for the genesis block in the adjacent matrix,
-        // i.e. initialize children of the genesis block as an empty vector.
-        let mut adjacent_matrix: HashMap<String, Vec<String>> = HashMap::new();
-        adjacent_matrix.insert(genesis_block.identifier.clone(), vec![]);
+        let height_index = match self.height_index(height) {
+            Some(height_index) if height <= head.height => height_index,
+            _ => {
+                return Ok(None);
+            }
+        };
 
-        trace!("Genesis block hash is: {:?}", genesis_block.identifier.clone());
+        // a block without competitors is part of the canonical chain
+        if self.is_indexed() && self.height_blocks[height_index].len() == 1 {
+            return Ok(Some(&self.blocks[&self.height_blocks[height_index][0]]));
+        }
 
-        Chain {
-            genesis_configuration_hash: genesis_hash,
-            genesis_identifier_hash: genesis_block.identifier.clone(),
-            blocks,
-            adjacent_matrix
+        match self.find_canonical_block_hash(&head, height) {
+            Ok(block_hash) => match self.blocks.get(&block_hash) {
+                Some(block) => Ok(Some(block)),
+                None => Err(ChainError::MissingBlock(block_hash))
+            },
+            Err(e) => Err(e)
         }
     }
 
-    pub fn get_current_block_number(&self) -> usize {
-        self.get_current_block().0
+    /// Returns the identifier of the ancestor of the given head at the given height,
+    /// which must not be higher than the head, by walking up from the head.
+    fn find_canonical_block_hash(&self, head: &HeadInfo, height: Height) -> Result<BlockHash, ChainError> {
+        let mut ancestor_hash = &head.block.identifier;
+        for _ in 0..head.height.distance_to(height) {
+            ancestor_hash = match self.blocks.get(ancestor_hash) {
+                Some(ancestor) => &ancestor.data.parent,
+                None => {
+                    return Err(ChainError::MissingBlock(ancestor_hash.clone()));
+                }
+            };
+        }
+
+        Ok(ancestor_hash.clone())
     }
 
-    pub fn get_current_block_timestamp(&self) -> u64 {
-        self.get_current_block().1.data.timestamp
+    /// Returns all blocks of the chain, starting with the genesis block,
+    /// ordered such that each block is preceded by its parent.
+    /// Adding the blocks in this order to a new chain will recreate this chain,
+    /// unless it starts with a state snapshot, whose block comes first instead.
+    pub fn get_ordered_blocks(&self) -> Vec<Block> {
+        let mut ordered_blocks = vec![];
+        let mut queue = VecDeque::new();
+        queue.push_back(self.genesis_identifier_hash.clone());
+
+        while let Some(block_hash) = queue.pop_front() {
+            ordered_blocks.push(self.blocks.get(&block_hash).unwrap().clone());
+
+            for child_hash in self.adjacent_matrix.get(&block_hash).unwrap().iter() {
+                queue.push_back(child_hash.clone());
+            }
+        }
+
+        ordered_blocks
+    }
+
+    /// Returns all blocks of the chain along with their height, ordered by their height and then
+    /// their identifier, so that each block is preceded by its parent. Unlike the order of
+    /// `get_ordered_blocks`, blocks added later never precede the blocks which were known before
+    /// at the same or a lower position.
+    pub fn get_blocks_by_height(&self) -> Vec<(Height, Block)> {
+        let mut blocks_by_height = vec![];
+        let mut queue = VecDeque::new();
+        queue.push_back((self.root_height(), self.genesis_identifier_hash.clone()));
+
+        while let Some((height, block_hash)) = queue.pop_front() {
+            blocks_by_height.push((height, self.blocks[&block_hash].clone()));
+
+            for child_hash in self.adjacent_matrix[&block_hash].iter() {
+                queue.push_back((height + 1, child_hash.clone()));
+            }
+        }
+
+        blocks_by_height.sort_by(|a, b| (a.0, &a.1.identifier).cmp(&(b.0, &b.1.identifier)));
+        blocks_by_height
+    }
+
+    /// Returns the identifiers of all blocks of the canonical chain,
+    /// starting with the head and ending with the genesis block.
+    ///
+    /// Returns an error if the structure of the chain is inconsistent.
+    pub fn get_canonical_path(&self) -> Result<Vec<BlockHash>, ChainError> {
+        let head = match self.head() {
+            Ok(head) => head,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+
+        let mut canonical_path = vec![];
+        let mut block_hash = head.block.identifier;
+        loop {
+            let parent_hash = match self.blocks.get(&block_hash) {
+                Some(block) => block.data.parent.clone(),
+                None => {
+                    return Err(ChainError::MissingBlock(block_hash));
+                }
+            };
+
+            let is_genesis_block = block_hash.eq(&self.genesis_identifier_hash);
+            canonical_path.push(block_hash);
+            if is_genesis_block {
+                break;
+            }
+            if canonical_path.len() > self.blocks.len() {
+                return Err(ChainError::Cycle(parent_hash));
+            }
+
+            block_hash = parent_hash;
+        }
+
+        Ok(canonical_path)
+    }
+
+    /// Returns all branches which are not part of the canonical chain
+    /// and whose tip is at least `depth` blocks behind the head.
+    ///
+    /// Returns an error if the structure of the chain is inconsistent.
+    pub fn get_abandoned_branches(&self, depth: usize) -> Result<Vec<AbandonedBranch>, ChainError> {
+        let canonical_path = match self.get_canonical_path() {
+            Ok(canonical_path) => canonical_path,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+
+        // the height of each canonical block, whereas the head is the first one
+        let head_height = canonical_path.len() - 1;
+        let canonical_heights: HashMap<&BlockHash, usize> = canonical_path.iter()
+            .enumerate()
+            .map(|(index, block_hash)| (block_hash, head_height - index))
+            .collect();
+
+        let mut abandoned_branches = vec![];
+        for (tip, children) in self.adjacent_matrix.iter() {
+            if !children.is_empty() || canonical_heights.contains_key(tip) {
+                continue;
+            }
+
+            // walk up to the block where the branch forked off the canonical chain
+            let mut branch_blocks = vec![];
+            let mut block_hash = tip;
+            let fork_height = loop {
+                if let Some(height) = canonical_heights.get(block_hash) {
+                    break *height;
+                }
+                if branch_blocks.len() > self.blocks.len() {
+                    return Err(ChainError::Cycle(tip.clone()));
+                }
+
+                branch_blocks.push(block_hash.clone());
+                block_hash = match self.blocks.get(block_hash) {
+                    Some(block) => &block.data.parent,
+                    None => {
+                        return Err(ChainError::MissingBlock(block_hash.clone()));
+                    }
+                };
+            };
+
+            if fork_height + branch_blocks.len() + depth <= head_height {
+                abandoned_branches.push(AbandonedBranch {
+                    tip: tip.clone(),
+                    blocks: branch_blocks,
+                });
+            }
+        }
+
+        Ok(abandoned_branches)
     }
 
-    pub fn get_current_block(&self) -> (usize, Block) {
-        let mut heaviest_block_visitor = HeaviestBlockVisitor::new();
-        let longest_path_walker = HeaviestBlockWalker::new();
-        longest_path_walker.walk_chain(&self, &mut heaviest_block_visitor);
+    /// Remove all blocks which are neither ancestors nor descendants of the finalized head.
+    /// Their branches forked off below the finalized height, so that they can never become canonical.
+    ///
+    /// Returns the removed blocks, or an error if the structure of the chain is inconsistent.
+    pub fn prune_stale_branches(&mut self) -> Result<Vec<Block>, ChainError> {
+        let finalized_hash = match self.finalized_head() {
+            Ok((height, _)) if self.root_height() == height => {
+                return Ok(vec![]);
+            }
+            Ok((_, block)) => block.identifier.clone(),
+            Err(e) => {
+                return Err(e);
+            }
+        };
+
+        let mut retained: HashSet<BlockHash> = HashSet::new();
+        let mut block_hash = finalized_hash.clone();
+        while block_hash != self.genesis_identifier_hash {
+            if !retained.insert(block_hash.clone()) {
+                return Err(ChainError::Cycle(block_hash));
+            }
+            block_hash = match self.blocks.get(&block_hash) {
+                Some(block) => block.data.parent.clone(),
+                None => {
+                    return Err(ChainError::MissingBlock(block_hash));
+                }
+            };
+        }
+        retained.insert(block_hash);
+
+        let mut queue: VecDeque<&BlockHash> = VecDeque::new();
+        queue.push_back(&finalized_hash);
+        while let Some(block_hash) = queue.pop_front() {
+            for child_hash in self.adjacent_matrix.get(block_hash).into_iter().flatten() {
+                if retained.insert(child_hash.clone()) {
+                    queue.push_back(child_hash);
+                }
+            }
+        }
 
-        let heaviest_block_height_option = heaviest_block_visitor.height;
-        assert!(heaviest_block_height_option.is_some());
-        let heaviest_block_height = heaviest_block_height_option.unwrap();
+        if retained.len() >= self.blocks.len() {
+            return Ok(vec![]);
+        }
 
-        let option = heaviest_block_visitor.heaviest_block;
-        assert!(option.is_some());
-        let heaviest_block_reference = option.unwrap();
+        let stale_hashes: Vec<BlockHash> = self.blocks.keys()
+            .filter(|block_hash| !retained.contains(*block_hash))
+            .cloned()
+            .collect();
+        let mut stale_blocks = Vec::with_capacity(stale_hashes.len());
+        for block_hash in stale_hashes {
+            self.adjacent_matrix.remove(&block_hash);
+            if let Some(block) = self.blocks.remove(&block_hash) {
+                stale_blocks.push(block);
+            }
+        }
+        for children in self.adjacent_matrix.values_mut() {
+            children.retain(|child_hash| retained.contains(child_hash));
+        }
 
-        (heaviest_block_height, (*self.blocks.get(&heaviest_block_reference).unwrap()).clone())
+        match self.reindex() {
+            Ok(()) => Ok(stale_blocks),
+            Err(e) => Err(e)
+        }
     }
 
     /// Returns true, if the parent of the given block exists, false otherwise.
-    pub fn has_parent_of_block(self, block: Block) -> bool {
+    pub fn has_parent_of_block(&self, block: &Block) -> bool {
         let parent_block = self.adjacent_matrix.get(&block.data.parent);
 
         parent_block.is_some()
@@ -95,7 +1286,7 @@ impl Chain {
             .entry(block.data.parent.clone())
             .and_modify(|parent_block_children| {
                 if ! parent_block_children.contains(&block.identifier.clone()) {
-                    info!("Adding block {:?} containing transactions [{:?}] to chain.", block.identifier.clone(), trx_identifiers.join(", "));
+                    info!("Adding block {:?} sealed by {:?} containing transactions [{:?}] to chain.", block.identifier.clone(), block.data.sealer, trx_identifiers.join(", "));
                     parent_block_children.push(block.identifier.clone());
                 } else {
                     debug!("Not adding block {:?} as it is already contained.", block.identifier.clone());
@@ -114,6 +1305,9 @@ impl Chain {
             .entry(block.identifier.clone())
             .or_insert(vec![]);
 
+        let is_indexed = self.is_indexed();
+        let block_hash = block.identifier.clone();
+
         // insert the block finally,
         // returns None if no block was contained at the given key,
         // but returns the old value if a block was already contained with the same key.
@@ -121,13 +1315,134 @@ impl Chain {
 
         // this is a sanity check only, we should never panic here, but if we do
         // this might cause a huge mess...
-        match previous_block_option {
-            None => {
-                return true;
+        if let Some(previous_block) = previous_block_option {
+            panic!("Double insert of block {:?}", previous_block.identifier.clone())
+        }
+
+        if is_indexed {
+            for (transaction_identifier, including_blocks) in Chain::index_transactions(&self.blocks[&block_hash]) {
+                self.transaction_blocks.entry(transaction_identifier).or_default().extend(including_blocks);
+            }
+            for (voter, including_blocks) in Chain::index_voters(&self.blocks[&block_hash]) {
+                self.voter_blocks.entry(voter).or_default().extend(including_blocks);
+            }
+            if self.election_key.is_none() {
+                self.election_key = Chain::find_election_key(&self.blocks[&block_hash]);
+            }
+            self.update_head(block_hash);
+        }
+
+        true
+    }
+
+    /// Returns the election key committed by the given block, if any.
+    fn find_election_key(block: &Block) -> Option<ModInt> {
+        block.data.transactions.iter()
+            .filter_map(|transaction| transaction.election_key.clone())
+            .next()
+    }
+
+    /// Returns the identifiers of the transactions of the given block, each along with the block.
+    fn index_transactions(block: &Block) -> HashMap<String, Vec<BlockHash>> {
+        block.data.transactions.iter()
+            .map(|transaction| (transaction.identifier.clone(), vec![block.identifier.clone()]))
+            .collect()
+    }
+
+    /// Returns the questions and the indices of the voters casting a vote in the given block, each along with the block.
+    fn index_voters(block: &Block) -> HashMap<(Option<String>, usize), Vec<BlockHash>> {
+        block.data.transactions.iter()
+            .filter(|transaction| TransactionType::Vote == transaction.trx_type)
+            .filter_map(|transaction| transaction.data.as_ref())
+            .map(|trx_data| ((trx_data.question_id.clone(), trx_data.voter_idx), vec![block.identifier.clone()]))
+            .collect()
+    }
+
+    /// Record the height and the total difficulty of the given, newly added leaf and make it
+    /// the head, if it is heavier than or preferred over the current head and does not revert the finalized head.
+    fn update_head(&mut self, block_hash: BlockHash) {
+        let (parent_height, parent_total_difficulty, parent_sealer_set, parent_election_phase, parent_voter_registry) = match self.blocks.get(&block_hash).map(|block| &block.data.parent) {
+            Some(parent_hash) if self.heights.contains_key(parent_hash) => (
+                self.heights[parent_hash],
+                self.total_difficulties[parent_hash],
+                self.sealer_sets.get(parent_hash).cloned(),
+                self.election_phases[parent_hash].clone(),
+                Arc::clone(&self.voter_registries[parent_hash])
+            ),
+            // the parent is unknown, hence the head is found by traversal from now on
+            _ => {
+                return;
             }
-            Some(previous_block) => {
-                panic!("Double insert of block {:?}", previous_block.identifier.clone())
+        };
+
+        let height = parent_height + 1;
+        let total_difficulty = parent_total_difficulty + Chain::get_difficulty(parent_sealer_set.as_deref(), &self.blocks[&block_hash], height);
+        if let Some(sealer_set) = Chain::get_next_sealer_set(&parent_sealer_set, height, &self.blocks[&block_hash]) {
+            self.sealer_sets.insert(block_hash.clone(), sealer_set);
+        }
+        let election_phase = Chain::get_next_election_phase(&parent_election_phase, &self.blocks[&block_hash]);
+        self.election_phases.insert(block_hash.clone(), election_phase);
+        let voter_registry = Chain::get_next_voter_registry(&parent_voter_registry, &self.blocks[&block_hash]);
+        self.voter_registries.insert(block_hash.clone(), voter_registry);
+        self.heights.insert(block_hash.clone(), height);
+        self.total_difficulties.insert(block_hash.clone(), total_difficulty);
+        let height_index = height.distance_to(self.root_height());
+        if self.height_blocks.len() <= height_index {
+            self.height_blocks.push(vec![]);
+        }
+        self.height_blocks[height_index].push(block_hash.clone());
+
+        self.choose_head(block_hash, height, total_difficulty);
+    }
+
+    /// Make the given block of the given height and total difficulty the head, if it is
+    /// heavier than or preferred over the current head and does not revert the finalized head.
+    fn choose_head(&mut self, block_hash: BlockHash, height: Height, total_difficulty: u64) {
+        let head_total_difficulty = self.total_difficulties[&self.head.1];
+        let is_new_head = if total_difficulty == head_total_difficulty && height == self.head.0 {
+            self.is_preferred_over_head(block_hash.as_str())
+        } else {
+            Chain::is_heavier(total_difficulty, height, head_total_difficulty, self.head.0)
+        };
+        // a block extending the head contains the finalized head as well
+        let is_extending_head = self.blocks[&block_hash].data.parent == self.head.1;
+        if is_new_head && !is_extending_head && !self.contains_finalized(&block_hash, height) {
+            warn!("Not switching to block {:?} as its branch reverts the finalized block {:?}", block_hash, self.finalized.1);
+            return;
+        }
+        if is_new_head {
+            self.head = (height, block_hash);
+            self.update_finalized();
+        }
+    }
+
+    /// Returns true, if the given block of the same height as the head is on a branch
+    /// which was added to the common ancestor of both before the branch of the head.
+    fn is_preferred_over_head(&self, block_hash: &str) -> bool {
+        let mut block_hash = block_hash;
+        let mut head_hash = self.head.1.as_str();
+
+        // both blocks have the same height, hence their ancestors meet at the same height
+        loop {
+            let (parent_hash, head_parent_hash) = match (self.blocks.get(block_hash), self.blocks.get(head_hash)) {
+                (Some(block), Some(head)) => (block.data.parent.as_str(), head.data.parent.as_str()),
+                _ => {
+                    return false;
+                }
+            };
+
+            if parent_hash == head_parent_hash {
+                return match self.adjacent_matrix.get(parent_hash) {
+                    Some(children) => {
+                        let position = |hash: &str| children.iter().position(|child_hash| child_hash == hash);
+                        position(block_hash) < position(head_hash)
+                    }
+                    None => false
+                };
             }
+
+            block_hash = parent_hash;
+            head_hash = head_parent_hash;
         }
     }
 }
@@ -136,20 +1451,36 @@ impl Chain {
 mod chain_test {
 
     use ::chain::block::{Block, BlockContent};
-    use ::chain::chain::Chain;
+    use ::chain::chain::{Chain, ChainError, DIFFICULTY_IN_TURN, DIFFICULTY_OUT_OF_TURN};
+    use ::chain::hasher::Hasher;
+    use ::chain::sealer_set::{SealerSet, DEFAULT_VOTE_EPOCH_LENGTH};
+    use ::chain::transaction::{SealerVote, Transaction, TransactionType, VoterRegistration};
+    use ::chain::types::{BlockHash, Height};
+    use crypto_rs::arithmetic::mod_int::ModInt;
+    use crypto_rs::cai::uciv::{CaiProof, ImageSet, PreImageSet};
+    use crypto_rs::el_gamal::ciphertext::CipherText;
+    use crypto_rs::el_gamal::encryption::PublicKey;
+    use crypto_rs::el_gamal::membership_proof::MembershipProof;
+    use num::One;
+    use std::collections::BTreeMap;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
 
     #[test]
     fn test_add_duplicate_block() {
-        let mut chain = Chain::new(String::new());
+        let mut chain = Chain::new(String::new(), Hasher::default());
         let genesis_id = chain.genesis_identifier_hash.clone();
 
         let block = Block {
-            identifier: "1".to_string(),
+            identifier: BlockHash::from("1"),
             data: BlockContent {
                 parent: genesis_id.clone(),
                 timestamp: 1,
-                transactions: vec![]
-            }
+                sealer: None,
+                transactions: vec![],
+                transactions_root: None
+            },
+            signature: None
         };
 
         assert!(chain.blocks.len().eq(&1));
@@ -166,4 +1497,545 @@ mod chain_test {
         assert!(chain.adjacent_matrix.get(&genesis_id.clone()).unwrap().len().eq(&1));
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_rebuild_from_ordered_blocks() {
+        let mut chain = Chain::new(String::new(), Hasher::default());
+        let genesis_id = chain.genesis_identifier_hash.clone();
+
+        // two competing branches on the first level
+        for (identifier, parent) in vec![("11", genesis_id.clone()), ("12", genesis_id.clone()), ("2", BlockHash::from("12")), ("3", BlockHash::from("2"))] {
+            chain.add_block(Block {
+                identifier: BlockHash::from(identifier),
+                data: BlockContent {
+                    parent,
+                    timestamp: 1,
+                    sealer: None,
+                    transactions: vec![],
+                    transactions_root: None
+                },
+                signature: None
+            });
+        }
+
+        let ordered_blocks = chain.get_ordered_blocks();
+        assert_eq!(5, ordered_blocks.len());
+        assert!(ordered_blocks[0].identifier.eq(&genesis_id));
+
+        let mut rebuilt_chain = Chain::from_genesis_block(String::new(), ordered_blocks[0].clone());
+        for block in ordered_blocks.into_iter().skip(1) {
+            assert!(rebuilt_chain.has_parent_of_block(&block));
+            rebuilt_chain.add_block(block);
+        }
+
+        assert_eq!(chain, rebuilt_chain);
+    }
+
+    #[test]
+    fn test_head() {
+        let mut chain = Chain::new(String::new(), Hasher::default());
+        let genesis_id = chain.genesis_identifier_hash.clone();
+
+        let head = chain.head().unwrap();
+        assert_eq!(Height::new(0), head.height);
+        assert!(head.block.identifier.eq(&genesis_id));
+
+        // two branches of equal height, the first added one wins
+        for (identifier, parent) in vec![("11", genesis_id.clone()), ("12", genesis_id.clone())] {
+            chain.add_block(Block {
+                identifier: BlockHash::from(identifier),
+                data: BlockContent {
+                    parent,
+                    timestamp: 1,
+                    sealer: None,
+                    transactions: vec![],
+                    transactions_root: None
+                },
+                signature: None
+            });
+        }
+
+        let head = chain.head().unwrap();
+        assert_eq!(Height::new(1), head.height);
+        assert!(head.block.identifier.eq(&BlockHash::from("11")));
+
+        // a later block on the branch added first wins over the head of the same height
+        for (identifier, parent) in vec![("22", BlockHash::from("12")), ("21", BlockHash::from("11"))] {
+            chain.add_block(Block {
+                identifier: BlockHash::from(identifier),
+                data: BlockContent {
+                    parent,
+                    timestamp: 1,
+                    sealer: None,
+                    transactions: vec![],
+                    transactions_root: None
+                },
+                signature: None
+            });
+            assert!(chain.head().unwrap().block.identifier.eq(&BlockHash::from(identifier)));
+        }
+        assert_eq!(Height::new(2), chain.head().unwrap().height);
+
+        // a child referenced without being known
+        chain.adjacent_matrix.get_mut("22").unwrap().push(BlockHash::from("missing"));
+        assert_eq!(Err(ChainError::MissingBlock(BlockHash::from("missing"))), chain.reindex());
+        assert_eq!(Err(ChainError::MissingBlock(BlockHash::from("missing"))), chain.head());
+    }
+
+    #[test]
+    fn test_head_of_forked_chain() {
+        let mut chain = Chain::new(String::new(), Hasher::default());
+        let genesis_id = chain.genesis_identifier_hash.clone();
+
+        // a main branch of height 7 with a short fork at each of the first levels
+        let mut blocks = vec![];
+        let mut parent = genesis_id.clone();
+        for level in 1..8 {
+            if level < 6 {
+                blocks.push((BlockHash::new(format!("fork-{}", level)), parent.clone()));
+            }
+            blocks.push((BlockHash::new(format!("{}", level)), parent.clone()));
+            parent = BlockHash::new(format!("{}", level));
+        }
+        // a fork at level 3 which eventually overtakes the main branch
+        parent = BlockHash::from("fork-3");
+        for level in 4..9 {
+            blocks.push((BlockHash::new(format!("fork-3-{}", level)), parent.clone()));
+            parent = BlockHash::new(format!("fork-3-{}", level));
+        }
+
+        for (identifier, parent) in blocks {
+            chain.add_block(Block {
+                identifier,
+                data: BlockContent {
+                    parent,
+                    timestamp: 1,
+                    sealer: None,
+                    transactions: vec![],
+                    transactions_root: None
+                },
+                signature: None
+            });
+
+            // the tracked head must be the same as the one found by traversing all blocks
+            assert_eq!(chain.find_head_by_traversal(), chain.head());
+        }
+
+        let head = chain.head().unwrap();
+        assert_eq!(Height::new(8), head.height);
+        assert!(head.block.identifier.eq(&BlockHash::from("fork-3-8")));
+
+        // only branches whose tip is at least three blocks behind the head are abandoned
+        let mut abandoned_branches = chain.get_abandoned_branches(3).unwrap();
+        abandoned_branches.sort_by(|a, b| a.tip.cmp(&b.tip));
+        let tips: Vec<&str> = abandoned_branches.iter().map(|branch| branch.tip.as_str()).collect();
+        assert_eq!(vec!["fork-1", "fork-2", "fork-4", "fork-5"], tips);
+        assert_eq!(vec![BlockHash::from("fork-4"), BlockHash::from("3")], abandoned_branches[2].blocks);
+        assert_eq!(5, chain.get_abandoned_branches(0).unwrap().len());
+
+        // a deserialized chain is not indexed until reindexed
+        let mut deserialized_chain: Chain = ::serde_json::from_str(&::serde_json::to_string(&chain).unwrap()).unwrap();
+        assert!(!deserialized_chain.is_indexed());
+        assert_eq!(chain.head(), deserialized_chain.head());
+        assert_eq!(Ok(()), deserialized_chain.reindex());
+        assert!(deserialized_chain.is_indexed());
+        assert_eq!(chain.head(), deserialized_chain.head());
+    }
+
+
+    #[test]
+    fn test_find_canonical_transaction() {
+        let mut chain = Chain::new(String::new(), Hasher::default());
+        let genesis_id = chain.genesis_identifier_hash.clone();
+        let transaction = Transaction::new_voting_opened(Hasher::default(), "genesis".to_string(), 0);
+
+        let blocks = vec![
+            ("11", genesis_id.clone(), vec![transaction.clone()]),
+            ("12", genesis_id.clone(), vec![]),
+            ("22", BlockHash::from("12"), vec![]),
+        ];
+        for (identifier, parent, transactions) in blocks {
+            chain.add_block(Block {
+                identifier: BlockHash::from(identifier),
+                data: BlockContent {
+                    parent,
+                    timestamp: 1,
+                    sealer: None,
+                    transactions,
+                    transactions_root: None
+                },
+                signature: None
+            });
+
+            // the index must yield the same as walking the canonical chain
+            let deserialized_chain: Chain = ::serde_json::from_str(&::serde_json::to_string(&chain).unwrap()).unwrap();
+            assert_eq!(deserialized_chain.find_canonical_transaction(&transaction.identifier), chain.find_canonical_transaction(&transaction.identifier));
+
+            if identifier == "11" {
+                let (height, block) = chain.find_canonical_transaction(&transaction.identifier).unwrap().unwrap();
+                assert_eq!(Height::new(1), height);
+                assert!(block.identifier.eq("11"));
+            }
+        }
+
+        // the block including the transaction is no longer part of the canonical chain
+        assert_eq!(Ok(None), chain.find_canonical_transaction(&transaction.identifier));
+        assert_eq!(Ok(None), chain.find_canonical_transaction("unknown"));
+    }
+
+    #[test]
+    fn test_has_canonical_vote() {
+        let mut chain = Chain::new(String::new(), Hasher::default());
+        let genesis_id = chain.genesis_identifier_hash.clone();
+        let public_key = PublicKey { p: ModInt::one(), q: ModInt::one(), h: ModInt::one(), g: ModInt::one() };
+        let cipher_text = CipherText { big_h: ModInt::one(), big_g: ModInt::one(), random: ModInt::one() };
+        let vote = Transaction::new_vote(
+            Hasher::default(),
+            3,
+            cipher_text.clone(),
+            MembershipProof::new(public_key.clone(), ModInt::one(), cipher_text.clone(), vec![ModInt::one()]),
+            CaiProof::new(public_key, cipher_text, PreImageSet { pre_images: vec![ModInt::one()] }, ImageSet { images: vec![ModInt::one()] }, 0, vec![ModInt::one()]),
+        );
+        let question = Some("q2".to_string());
+
+        let blocks = vec![
+            ("11", genesis_id.clone(), vec![vote.clone()]),
+            ("12", genesis_id.clone(), vec![]),
+            ("22", BlockHash::from("12"), vec![]),
+        ];
+        for (identifier, parent, transactions) in blocks {
+            chain.add_block(Block {
+                identifier: BlockHash::from(identifier),
+                data: BlockContent {
+                    parent,
+                    timestamp: 1,
+                    sealer: None,
+                    transactions,
+                    transactions_root: None
+                },
+                signature: None
+            });
+
+            // the index must yield the same as walking the canonical chain
+            let deserialized_chain: Chain = ::serde_json::from_str(&::serde_json::to_string(&chain).unwrap()).unwrap();
+            assert_eq!(deserialized_chain.has_canonical_vote(&None, 3), chain.has_canonical_vote(&None, 3));
+
+            if identifier == "11" {
+                assert_eq!(Ok(true), chain.has_canonical_vote(&None, 3));
+                // votes on each question are independent
+                assert_eq!(Ok(false), chain.has_canonical_vote(&question, 3));
+            }
+        }
+
+        // the block including the vote is no longer part of the canonical chain
+        assert_eq!(Ok(false), chain.has_canonical_vote(&None, 3));
+        assert_eq!(Ok(false), chain.has_canonical_vote(&None, 0));
+    }
+
+    #[test]
+    fn test_voter_registry_of_branch() {
+        let mut chain = Chain::new(String::new(), Hasher::default());
+        let genesis_id = chain.genesis_identifier_hash.clone();
+        let voter_registration = |voter_idx: usize, image_set: Option<ImageSet>| Transaction::from_voter_registration(Hasher::default(), TransactionType::VoterRegistered, VoterRegistration {
+            election: String::new(),
+            question_id: None,
+            voter_idx,
+            image_set,
+            credential: format!("voter-{}", voter_idx),
+            registered_at: 1,
+            signature: None,
+        });
+        let image_set = ImageSet { images: vec![ModInt::one()] };
+
+        // the second block registers a voter and revokes it again, the fork registers another one
+        let blocks = vec![
+            ("1", genesis_id.clone(), vec![voter_registration(0, Some(image_set.clone()))]),
+            ("2", BlockHash::from("1"), vec![voter_registration(1, Some(image_set.clone())), voter_registration(1, None)]),
+            ("3", BlockHash::from("2"), vec![]),
+            ("22", BlockHash::from("1"), vec![voter_registration(2, Some(image_set.clone()))]),
+        ];
+        for (identifier, parent, transactions) in blocks {
+            chain.add_block(Block {
+                identifier: BlockHash::from(identifier),
+                data: BlockContent {
+                    parent,
+                    timestamp: 1,
+                    sealer: None,
+                    transactions,
+                    transactions_root: None
+                },
+                signature: None
+            });
+        }
+
+        let voter_registry = chain.voter_registry_of(&BlockHash::from("3")).unwrap().unwrap();
+        assert!(voter_registry.is_eligible(&None, 0, &[]));
+        assert!(!voter_registry.is_eligible(&None, 1, &[image_set.clone(), image_set.clone()]));
+        assert!(!voter_registry.is_eligible(&None, 2, &[]));
+        // blocks not affecting the registry share the one of their parent
+        assert!(Arc::ptr_eq(&voter_registry, &chain.voter_registry_of(&BlockHash::from("2")).unwrap().unwrap()));
+
+        let fork_registry = chain.voter_registry_of(&BlockHash::from("22")).unwrap().unwrap();
+        assert!(fork_registry.is_eligible(&None, 2, &[]));
+        assert_eq!(None, fork_registry.get_voter_registration(&None, 1));
+        assert!(chain.voter_registry_of(&genesis_id).unwrap().unwrap().get_voter_registrations().is_empty());
+        assert_eq!(None, chain.voter_registry_of(&BlockHash::from("unknown")).unwrap());
+
+        // the index must yield the same as walking the branch
+        let deserialized_chain: Chain = ::serde_json::from_str(&::serde_json::to_string(&chain).unwrap()).unwrap();
+        for identifier in vec!["1", "2", "3", "22"] {
+            assert_eq!(chain.voter_registry_of(&BlockHash::from(identifier)), deserialized_chain.voter_registry_of(&BlockHash::from(identifier)));
+        }
+    }
+
+    #[test]
+    fn test_blocks_by_height() {
+        let mut chain = Chain::new(String::new(), Hasher::default());
+        let genesis_id = chain.genesis_identifier_hash.clone();
+
+        // a fork at the first level, whose second branch overtakes the first one
+        for (identifier, parent) in vec![("11", genesis_id.clone()), ("12", genesis_id.clone()), ("22", BlockHash::from("12"))] {
+            chain.add_block(Block {
+                identifier: BlockHash::from(identifier),
+                data: BlockContent {
+                    parent,
+                    timestamp: 1,
+                    sealer: None,
+                    transactions: vec![],
+                    transactions_root: None
+                },
+                signature: None
+            });
+        }
+
+        // the index must yield the same as walking the chain
+        let deserialized_chain: Chain = ::serde_json::from_str(&::serde_json::to_string(&chain).unwrap()).unwrap();
+        for chain in vec![&chain, &deserialized_chain] {
+            let identifiers = |height: usize| -> Vec<BlockHash> {
+                chain.blocks_at_height(Height::new(height)).unwrap().iter().map(|block| block.identifier.clone()).collect()
+            };
+            assert_eq!(vec![genesis_id.clone()], identifiers(0));
+            assert_eq!(vec![BlockHash::from("11"), BlockHash::from("12")], identifiers(1));
+            assert_eq!(vec![BlockHash::from("22")], identifiers(2));
+            assert!(identifiers(3).is_empty());
+
+            let number = |height: usize| chain.block_by_number(Height::new(height)).unwrap().map(|block| block.identifier.clone());
+            assert_eq!(Some(genesis_id.clone()), number(0));
+            assert_eq!(Some(BlockHash::from("12")), number(1));
+            assert_eq!(Some(BlockHash::from("22")), number(2));
+            assert_eq!(None, number(3));
+        }
+    }
+
+    #[test]
+    fn test_head_of_heaviest_branch() {
+        let sealers: Vec<SocketAddr> = vec!["127.0.0.1:9000".parse().unwrap(), "127.0.0.1:9001".parse().unwrap(), "127.0.0.1:9002".parse().unwrap()];
+        let mut chain = Chain::new(String::new(), Hasher::default());
+        chain.set_sealer_set(Some(SealerSet::new(sealers.clone(), BTreeMap::new(), DEFAULT_VOTE_EPOCH_LENGTH))).unwrap();
+        let genesis_id = chain.genesis_identifier_hash.clone();
+
+        // the first sealer leads height one, the second one height two
+        let blocks = vec![
+            ("11", genesis_id.clone(), sealers[1], "11"),
+            ("12", genesis_id.clone(), sealers[0], "12"),
+            ("21", BlockHash::from("11"), sealers[1], "21"),
+            // as heavy and as high as the head, but on a branch added later
+            ("22", BlockHash::from("12"), sealers[2], "21"),
+            // as heavy as the head, but higher
+            ("23", BlockHash::from("11"), sealers[0], "21"),
+            ("33", BlockHash::from("23"), sealers[0], "21"),
+        ];
+        for (identifier, parent, sealer, expected_head) in blocks {
+            chain.add_block(Block {
+                identifier: BlockHash::from(identifier),
+                data: BlockContent {
+                    parent,
+                    timestamp: 1,
+                    sealer: Some(sealer),
+                    transactions: vec![],
+                    transactions_root: None
+                },
+                signature: None
+            });
+
+            assert_eq!(BlockHash::from(expected_head), chain.head().unwrap().block.identifier);
+            assert_eq!(chain.find_head_by_traversal(), chain.head());
+        }
+
+        let head = chain.head().unwrap();
+        assert_eq!(Height::new(2), head.height);
+        assert_eq!(DIFFICULTY_OUT_OF_TURN + DIFFICULTY_IN_TURN, head.total_difficulty);
+
+        // without sealers, all blocks are as heavy, hence the longest branch wins
+        let mut unweighted_chain = chain.clone();
+        unweighted_chain.set_sealer_set(None).unwrap();
+        assert_eq!(BlockHash::from("33"), unweighted_chain.head().unwrap().block.identifier);
+    }
+
+    #[test]
+    fn test_finalized_head() {
+        let sealers: Vec<SocketAddr> = vec!["127.0.0.1:9000".parse().unwrap(), "127.0.0.1:9001".parse().unwrap(), "127.0.0.1:9002".parse().unwrap()];
+        let mut chain = Chain::new(String::new(), Hasher::default());
+        chain.set_sealer_set(Some(SealerSet::new(sealers.clone(), BTreeMap::new(), DEFAULT_VOTE_EPOCH_LENGTH))).unwrap();
+        let genesis_id = chain.genesis_identifier_hash.clone();
+        let new_block = |identifier: &str, parent: BlockHash, sealer: SocketAddr| Block {
+            identifier: BlockHash::from(identifier),
+            data: BlockContent {
+                parent,
+                timestamp: 1,
+                sealer: Some(sealer),
+                transactions: vec![],
+                transactions_root: None
+            },
+            signature: None
+        };
+
+        // a block is final once two of the three sealers built on it
+        chain.add_block(new_block("1", genesis_id.clone(), sealers[0]));
+        chain.add_block(new_block("2", BlockHash::from("1"), sealers[1]));
+        assert_eq!(Height::GENESIS, chain.finalized_head().unwrap().0);
+        chain.add_block(new_block("3", BlockHash::from("2"), sealers[2]));
+        assert_eq!((Height::new(1), BlockHash::from("1")), chain.finalized_head().map(|(height, block)| (height, block.identifier.clone())).unwrap());
+
+        // a heavier branch reverting the finalized block does not become the head
+        let mut parent = genesis_id.clone();
+        for index in 0..7 {
+            let identifier = format!("b{}", index);
+            chain.add_block(new_block(&identifier, parent, sealers[(index + 1) % 3]));
+            parent = BlockHash::from(identifier);
+        }
+        assert_eq!(BlockHash::from("3"), chain.head().unwrap().block.identifier);
+
+        // only the branch forked off below the finalized block is pruned
+        chain.add_block(new_block("c0", BlockHash::from("1"), sealers[2]));
+        let mut pruned: Vec<BlockHash> = chain.prune_stale_branches().unwrap().into_iter().map(|block| block.identifier).collect();
+        pruned.sort();
+        assert_eq!((0..7).map(|index| BlockHash::from(format!("b{}", index))).collect::<Vec<BlockHash>>(), pruned);
+        assert_eq!(5, chain.blocks.len());
+        assert_eq!(vec![BlockHash::from("1")], chain.adjacent_matrix[&genesis_id]);
+        assert_eq!(BlockHash::from("3"), chain.head().unwrap().block.identifier);
+        assert!(chain.prune_stale_branches().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_head_matches_traversal() {
+        let sealers: Vec<SocketAddr> = vec!["127.0.0.1:9000".parse().unwrap(), "127.0.0.1:9001".parse().unwrap(), "127.0.0.1:9002".parse().unwrap()];
+        let mut chain = Chain::new(String::new(), Hasher::default());
+        chain.set_sealer_set(Some(SealerSet::new(sealers.clone(), BTreeMap::new(), DEFAULT_VOTE_EPOCH_LENGTH))).unwrap();
+        let genesis_id = chain.genesis_identifier_hash.clone();
+        let new_block = |identifier: &str, parent: &BlockHash, sealer: SocketAddr| Block {
+            identifier: BlockHash::from(identifier),
+            data: BlockContent {
+                parent: parent.clone(),
+                timestamp: 1,
+                sealer: Some(sealer),
+                transactions: vec![],
+                transactions_root: None
+            },
+            signature: None
+        };
+
+        // each block along with the expected head once it is added, branches are extended out of order
+        let blocks = vec![
+            (new_block("a1", &genesis_id, sealers[0]), "a1"),
+            (new_block("b1", &genesis_id, sealers[1]), "a1"),
+            // the heavier branch wins
+            (new_block("b2", &BlockHash::from("b1"), sealers[1]), "b2"),
+            // among equally heavy blocks of the same height, the one on the branch added first to their common ancestor wins
+            (new_block("a2", &BlockHash::from("a1"), sealers[0]), "a2"),
+            (new_block("c1", &genesis_id, sealers[0]), "a2"),
+            (new_block("c2", &BlockHash::from("c1"), sealers[2]), "a2"),
+            // finalizes "b1"
+            (new_block("b3", &BlockHash::from("b2"), sealers[0]), "b3"),
+            (new_block("e2", &BlockHash::from("b1"), sealers[1]), "b3"),
+            (new_block("e3", &BlockHash::from("e2"), sealers[2]), "e3"),
+            // among equally heavy blocks, the lower one wins
+            (new_block("f4", &BlockHash::from("b3"), sealers[1]), "e3"),
+        ];
+        for (block, expected_head) in blocks {
+            chain.add_block(block);
+
+            assert_eq!(BlockHash::from(expected_head), chain.head().unwrap().block.identifier);
+            assert_eq!(chain.find_head_by_traversal(), chain.head());
+        }
+        assert_eq!(BlockHash::from("b1"), chain.finalized_head().unwrap().1.identifier);
+
+        // the head survives reindexing, which adds the blocks in a different order
+        assert_eq!(Ok(()), chain.reindex());
+        assert_eq!(BlockHash::from("e3"), chain.head().unwrap().block.identifier);
+        assert_eq!(chain.find_head_by_traversal(), chain.head());
+        assert_eq!(BlockHash::from("b1"), chain.finalized_head().unwrap().1.identifier);
+    }
+
+    #[test]
+    fn test_reindex_keeps_finalized_head() {
+        let sealers: Vec<SocketAddr> = vec!["127.0.0.1:9000".parse().unwrap(), "127.0.0.1:9001".parse().unwrap(), "127.0.0.1:9002".parse().unwrap()];
+        let sealer_set = SealerSet::new(sealers.clone(), BTreeMap::new(), DEFAULT_VOTE_EPOCH_LENGTH);
+        let mut chain = Chain::new(String::new(), Hasher::default());
+        chain.set_sealer_set(Some(sealer_set.clone())).unwrap();
+        let genesis_id = chain.genesis_identifier_hash.clone();
+        let new_block = |identifier: &str, parent: BlockHash, sealer: SocketAddr| Block {
+            identifier: BlockHash::from(identifier),
+            data: BlockContent {
+                parent,
+                timestamp: 1,
+                sealer: Some(sealer),
+                transactions: vec![],
+                transactions_root: None
+            },
+            signature: None
+        };
+
+        // all sealers build in turn on the first block, while the first sealer alone builds a heavier branch
+        chain.add_block(new_block("1", genesis_id.clone(), sealers[0]));
+        chain.add_block(new_block("2", BlockHash::from("1"), sealers[1]));
+        chain.add_block(new_block("3", BlockHash::from("2"), sealers[2]));
+        let mut parent = genesis_id.clone();
+        for index in 0..7 {
+            let identifier = format!("b{}", index);
+            chain.add_block(new_block(&identifier, parent, sealers[0]));
+            parent = BlockHash::from(identifier);
+        }
+        assert!(chain.total_difficulty_of(&BlockHash::from("b6")).unwrap() > chain.head().unwrap().total_difficulty);
+
+        let head_and_finalized = |chain: &Chain| (
+            chain.head().unwrap().block.identifier,
+            chain.finalized_head().unwrap().1.identifier.clone()
+        );
+        let expected = (BlockHash::from("3"), BlockHash::from("1"));
+        assert_eq!(expected, head_and_finalized(&chain));
+        assert_eq!(chain.head(), chain.find_head_by_traversal());
+
+        // reindexing does not revert the finalized block
+        assert_eq!(Ok(()), chain.reindex());
+        assert_eq!(expected, head_and_finalized(&chain));
+        assert_eq!(chain.head(), chain.find_head_by_traversal());
+
+        // neither does restoring the chain, which derives the finalized block again
+        let mut restored_chain = Chain::from_ordered_blocks(String::new(), chain.get_ordered_blocks()).unwrap().unwrap();
+        assert_eq!(Ok(()), restored_chain.set_sealer_set(Some(sealer_set)));
+        assert_eq!(expected, head_and_finalized(&restored_chain));
+    }
+
+    #[test]
+    fn test_sealer_set_of_branch() {
+        let sealer: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let candidate: SocketAddr = "127.0.0.1:9001".parse().unwrap();
+        let mut chain = Chain::new(String::new(), Hasher::default());
+        chain.set_sealer_set(Some(SealerSet::new(vec![sealer], BTreeMap::new(), DEFAULT_VOTE_EPOCH_LENGTH))).unwrap();
+        let genesis_id = chain.genesis_identifier_hash.clone();
+
+        // the only sealer forms a majority on its own
+        let vote = SealerVote { voter: sealer, candidate, authorize: true, public_key: None, cast_at: 0 };
+        let vote_block = Block::new(Hasher::default(), genesis_id.clone(), Some(sealer), vec![Transaction::new_sealer_vote(Hasher::default(), vote)]);
+        chain.add_block(vote_block.clone());
+
+        assert_eq!(&[sealer], chain.get_sealer_set(&genesis_id).unwrap().unwrap().get_sealers());
+        assert_eq!(&[sealer, candidate], chain.get_sealer_set(&vote_block.identifier).unwrap().unwrap().get_sealers());
+        assert_eq!(None, chain.get_sealer_set(&BlockHash::from("unknown")).unwrap());
+
+        // the candidate leads the second height
+        let candidate_block = Block::new(Hasher::default(), vote_block.identifier.clone(), Some(candidate), vec![]);
+        chain.add_block(candidate_block);
+        assert_eq!(2 * DIFFICULTY_IN_TURN, chain.head().unwrap().total_difficulty);
+    }
+}
@@ -1,15 +1,27 @@
 use ::chain::block::Block;
-use ::chain::transaction::{Transaction, TransactionType};
-use crypto_rs::el_gamal::ciphertext::CipherText;
+use ::chain::epoch::{EpochSchedule, EpochStatistics};
+use ::chain::sealer_set::SealerSet;
+use ::chain::snapshot::{self, QuestionSnapshot, StateSnapshot};
+use ::chain::transaction::{DecryptionShare, FinalTally, Transaction, TransactionQuery, TransactionType, VoterRegistration};
+use ::chain::transaction_registry::{RevotePolicy, TallyState, TransactionRegistry};
+use ::chain::types::{BlockHash, Height};
 use crypto_rs::el_gamal::additive::Operate;
-use crypto_rs::el_gamal::encryption::{PublicKey, encrypt};
-use crypto_rs::arithmetic::mod_int::ModInt;
-use num::Zero;
-use std::collections::HashSet;
+use crypto_rs::el_gamal::ciphertext::CipherText;
+use crypto_rs::el_gamal::encryption::PublicKey;
+use crypto_rs::arithmetic::mod_int::{From as ModIntFrom, ModInt};
+use crypto_rs::cai::uciv::ImageSet;
+use num::{BigInt, One, Zero};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::SocketAddr;
 
 pub trait ChainVisitor {
     /// Visit a particular block
-    fn visit_block(&mut self, height: usize, block: &Block);
+    fn visit_block(&mut self, height: Height, block: &Block);
+
+    /// Visit the snapshot summarizing the blocks below the root of the chain,
+    /// once the walk reaches the root of a chain starting with a snapshot.
+    /// Visitors only interested in blocks ignore it.
+    fn visit_snapshot(&mut self, _state_snapshot: &StateSnapshot) {}
 }
 
 /// This visitor expects to be called on each level
@@ -39,7 +51,7 @@ impl FindTransactionVisitor {
 
 impl ChainVisitor for FindTransactionVisitor {
     /// Visit a block of the blockchain.
-    fn visit_block(&mut self, _height: usize, block: &Block) {
+    fn visit_block(&mut self, _height: Height, block: &Block) {
         match self.found_transaction {
             Some(_) => {
                 return;
@@ -62,14 +74,16 @@ impl ChainVisitor for FindTransactionVisitor {
 ///
 /// After it is called, it's heaviest_block contains
 /// a hash of the heaviest block currently in the chain.
+#[deprecated(note = "Panics if visited more than once, use `Chain::head()` instead")]
 pub struct HeaviestBlockVisitor {
     /// The height of the heaviest block.
-    pub height: Option<usize>,
+    pub height: Option<Height>,
     /// The hash of the string once it is assigned,
     /// or None, if this visitor was never visited.
-    pub heaviest_block: Option<String>,
+    pub heaviest_block: Option<BlockHash>,
 }
 
+#[allow(deprecated)]
 impl HeaviestBlockVisitor {
     /// Create a new `HeaviestBlockVisitor` having
     /// a `None` hash of the heaviest block.
@@ -81,9 +95,10 @@ impl HeaviestBlockVisitor {
     }
 }
 
+#[allow(deprecated)]
 impl ChainVisitor for HeaviestBlockVisitor {
     /// Expects to be called only once. Will panic otherwise.
-    fn visit_block(&mut self, height: usize, block: &Block) {
+    fn visit_block(&mut self, height: Height, block: &Block) {
         match self.heaviest_block {
             Some(ref block_hash) => {
                 panic!("Cannot assign the heaviest block a second time. Previous heaviest block was {:?}", block_hash);
@@ -96,86 +111,756 @@ impl ChainVisitor for HeaviestBlockVisitor {
     }
 }
 
+/// The phase of an election as recorded on the chain.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub enum ElectionPhase {
+    /// The voting was never opened.
+    NotOpened,
+    /// The voting is open and votes are accepted.
+    Opened,
+    /// The voting is closed and no more votes are accepted.
+    Closed,
+}
+
+/// Determines whether the voting is closed, i.e. whether the most recent
+/// transaction opening or closing the voting is one closing it.
+///
+/// Expects to be walked from the bottom up of the chain
+/// to the root to work correctly.
+pub struct VotingClosedVisitor {
+    latest_phase_transaction: Option<TransactionType>,
+}
+
+impl VotingClosedVisitor {
+    pub fn new() -> VotingClosedVisitor {
+        VotingClosedVisitor {
+            latest_phase_transaction: None,
+        }
+    }
+
+    /// Returns true, if the voting was closed and not opened again afterwards.
+    pub fn is_voting_closed(&self) -> bool {
+        Some(TransactionType::VoteClosed) == self.latest_phase_transaction
+    }
+
+    /// Returns the current phase of the election.
+    pub fn get_election_phase(&self) -> ElectionPhase {
+        match self.latest_phase_transaction {
+            Some(TransactionType::VoteOpened) => ElectionPhase::Opened,
+            Some(TransactionType::VoteClosed) => ElectionPhase::Closed,
+            _ => ElectionPhase::NotOpened
+        }
+    }
+}
+
+impl Default for VotingClosedVisitor {
+    fn default() -> Self {
+        VotingClosedVisitor::new()
+    }
+}
+
+impl ChainVisitor for VotingClosedVisitor {
+    fn visit_block(&mut self, _height: Height, block: &Block) {
+        if self.latest_phase_transaction.is_some() {
+            return;
+        }
+
+        // the last transaction within the newest block determines the phase
+        for transaction in block.data.transactions.iter().rev() {
+            match transaction.trx_type {
+                TransactionType::VoteOpened | TransactionType::VoteClosed => {
+                    self.latest_phase_transaction = Some(transaction.trx_type.clone());
+                    return;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn visit_snapshot(&mut self, state_snapshot: &StateSnapshot) {
+        if self.latest_phase_transaction.is_some() {
+            return;
+        }
+
+        self.latest_phase_transaction = match state_snapshot.phase {
+            ElectionPhase::Opened => Some(TransactionType::VoteOpened),
+            ElectionPhase::Closed => Some(TransactionType::VoteClosed),
+            ElectionPhase::NotOpened => None
+        };
+    }
+}
+
+/// Counts the blocks sealed by each sealer.
+///
+/// Expects to be called on each block of the canonical chain,
+/// e.g. by walking it with a `LongestPathWalker`.
+pub struct SealerStatisticsVisitor {
+    blocks_by_sealer: HashMap<SocketAddr, usize>,
+    unattributed_blocks: usize,
+}
+
+impl SealerStatisticsVisitor {
+    pub fn new() -> SealerStatisticsVisitor {
+        SealerStatisticsVisitor {
+            blocks_by_sealer: HashMap::new(),
+            unattributed_blocks: 0,
+        }
+    }
+
+    /// Get the amount of blocks sealed by the given sealer.
+    pub fn get_sealed_blocks(&self, sealer: &SocketAddr) -> usize {
+        *self.blocks_by_sealer.get(sealer).unwrap_or(&0)
+    }
+
+    /// Get the amount of blocks which do not specify their sealer.
+    pub fn get_unattributed_blocks(&self) -> usize {
+        self.unattributed_blocks
+    }
+}
+
+impl Default for SealerStatisticsVisitor {
+    fn default() -> Self {
+        SealerStatisticsVisitor::new()
+    }
+}
+
+impl ChainVisitor for SealerStatisticsVisitor {
+    fn visit_block(&mut self, _height: Height, block: &Block) {
+        match block.data.sealer {
+            Some(sealer) => {
+                *self.blocks_by_sealer.entry(sealer).or_insert(0) += 1;
+            }
+            None => {
+                self.unattributed_blocks += 1;
+            }
+        }
+    }
+}
+
+/// Collects statistics about each epoch, i.e. the participation of
+/// the sealers and the amount of blocks and transactions.
+///
+/// Expects to be called on each block of the canonical chain,
+/// e.g. by walking it with a `LongestPathWalker`.
+pub struct EpochStatisticsVisitor {
+    schedule: EpochSchedule,
+    statistics_by_epoch: BTreeMap<usize, EpochStatistics>,
+}
+
+impl EpochStatisticsVisitor {
+    pub fn new(schedule: EpochSchedule) -> EpochStatisticsVisitor {
+        EpochStatisticsVisitor {
+            schedule,
+            statistics_by_epoch: BTreeMap::new(),
+        }
+    }
+
+    /// Get the statistics of all epochs having at least one block, from the oldest to the newest.
+    pub fn get_epoch_statistics(&self) -> Vec<EpochStatistics> {
+        self.statistics_by_epoch.values().cloned().collect()
+    }
+}
+
+impl ChainVisitor for EpochStatisticsVisitor {
+    fn visit_block(&mut self, height: Height, block: &Block) {
+        let epoch = match self.schedule.get_epoch(height) {
+            Some(epoch) => epoch,
+            None => {
+                // the genesis block does not belong to any epoch
+                return;
+            }
+        };
+        let leader_index = self.schedule.get_leader_index(height).unwrap();
+
+        let schedule = &self.schedule;
+        let statistics = self.statistics_by_epoch.entry(epoch.number)
+            .or_insert_with(|| EpochStatistics::new(epoch, schedule));
+        statistics.blocks += 1;
+        statistics.transactions += block.data.transactions.len();
+
+        match block.data.sealer {
+            Some(sealer) => {
+                if let Some(participation) = statistics.participation.iter_mut().find(|participation| participation.sealer == sealer) {
+                    participation.sealed_blocks += 1;
+                }
+                if statistics.participation[leader_index].sealer != sealer {
+                    statistics.participation[leader_index].missed_slots += 1;
+                }
+            }
+            None => {
+                statistics.unattributed_blocks += 1;
+            }
+        }
+    }
+}
+
+/// Collects the blocks of the canonical chain which are considered final,
+/// i.e. which are followed by at least a given amount of confirming blocks.
+///
+/// Expects to be walked from the bottom up of the chain
+/// to the root to work correctly.
+pub struct FinalizedBlocksVisitor {
+    /// The amount of blocks which must follow a block for it to be final.
+    confirmations: usize,
+    /// Only blocks above this height are collected.
+    after_height: Height,
+    /// The height of the head, known once the first block was visited.
+    head_height: Option<Height>,
+    /// The collected blocks along with their height, from the newest to the oldest.
+    finalized_blocks: Vec<(Height, Block)>,
+}
+
+impl FinalizedBlocksVisitor {
+    /// Create a new visitor.
+    ///
+    /// - confirmations: The amount of blocks which must follow a block for it to be final.
+    /// - after_height: Only blocks above this height are collected.
+    pub fn new(confirmations: usize, after_height: Height) -> FinalizedBlocksVisitor {
+        FinalizedBlocksVisitor {
+            confirmations,
+            after_height,
+            head_height: None,
+            finalized_blocks: vec![],
+        }
+    }
+
+    /// Get the collected blocks along with their height, from the oldest to the newest.
+    pub fn get_finalized_blocks(&self) -> Vec<(Height, Block)> {
+        self.finalized_blocks.iter().rev().cloned().collect()
+    }
+}
+
+impl ChainVisitor for FinalizedBlocksVisitor {
+    fn visit_block(&mut self, height: Height, block: &Block) {
+        let head_height = *self.head_height.get_or_insert(height);
+
+        if height + self.confirmations <= head_height && height > self.after_height {
+            self.finalized_blocks.push((height, block.clone()));
+        }
+    }
+}
+
+/// Collects the transactions selected by a query, starting with the newest ones,
+/// until the limit of the query is reached.
+pub struct QueryTransactionsVisitor {
+    query: TransactionQuery,
+    found_transactions: Vec<Transaction>,
+}
+
+impl QueryTransactionsVisitor {
+    /// Create a new visitor.
+    ///
+    /// - query: The query selecting the transactions to collect.
+    pub fn new(query: TransactionQuery) -> QueryTransactionsVisitor {
+        QueryTransactionsVisitor {
+            query,
+            found_transactions: vec![],
+        }
+    }
+
+    /// Get the collected transactions, from the newest to the oldest.
+    pub fn get_found_transactions(&self) -> Vec<Transaction> {
+        self.found_transactions.clone()
+    }
+}
+
+impl ChainVisitor for QueryTransactionsVisitor {
+    fn visit_block(&mut self, _height: Height, block: &Block) {
+        for transaction in block.data.transactions.iter() {
+            if self.found_transactions.len() >= self.query.limit {
+                return;
+            }
+
+            if self.query.matches(transaction) {
+                self.found_transactions.push(transaction.clone());
+            }
+        }
+    }
+}
+
+/// Collects the decryption shares published by the sealers on the canonical chain.
+/// Shares included before the state snapshot the chain starts with, if any, are not known.
+pub struct DecryptionSharesVisitor {
+    decryption_shares: Vec<DecryptionShare>,
+}
+
+impl DecryptionSharesVisitor {
+    pub fn new() -> DecryptionSharesVisitor {
+        DecryptionSharesVisitor {
+            decryption_shares: vec![],
+        }
+    }
+
+    /// Get the collected decryption shares, from the oldest to the newest.
+    pub fn get_decryption_shares(&self) -> Vec<DecryptionShare> {
+        self.decryption_shares.iter().rev().cloned().collect()
+    }
+}
+
+impl Default for DecryptionSharesVisitor {
+    fn default() -> Self {
+        DecryptionSharesVisitor::new()
+    }
+}
+
+impl ChainVisitor for DecryptionSharesVisitor {
+    fn visit_block(&mut self, _height: Height, block: &Block) {
+        // blocks are visited from the newest to the oldest
+        for transaction in block.data.transactions.iter().rev() {
+            if let Some(ref decryption_share) = transaction.decryption_share {
+                self.decryption_shares.push(decryption_share.clone());
+            }
+        }
+    }
+}
+
+/// Collects the commitments of the dealers of the election key and the election key
+/// committed on the canonical chain, including those summarized by the state snapshot the chain starts with.
+/// Only the oldest commitment of each dealer and the oldest election key count.
+pub struct KeyGenerationVisitor {
+    dkg_commitments: BTreeMap<SocketAddr, Vec<ModInt>>,
+    election_key: Option<ModInt>,
+}
+
+impl KeyGenerationVisitor {
+    pub fn new() -> KeyGenerationVisitor {
+        KeyGenerationVisitor {
+            dkg_commitments: BTreeMap::new(),
+            election_key: None,
+        }
+    }
+
+    /// Get the commitments of each dealer.
+    pub fn get_dkg_commitments(&self) -> &BTreeMap<SocketAddr, Vec<ModInt>> {
+        &self.dkg_commitments
+    }
+
+    /// Get the committed election key, or None if the key generation is not completed.
+    pub fn get_election_key(&self) -> Option<&ModInt> {
+        self.election_key.as_ref()
+    }
+}
+
+impl Default for KeyGenerationVisitor {
+    fn default() -> Self {
+        KeyGenerationVisitor::new()
+    }
+}
+
+impl ChainVisitor for KeyGenerationVisitor {
+    fn visit_block(&mut self, _height: Height, block: &Block) {
+        // blocks are visited from the newest to the oldest, hence older ones replace newer ones
+        for transaction in block.data.transactions.iter().rev() {
+            if let Some(ref dkg_commitment) = transaction.dkg_commitment {
+                self.dkg_commitments.insert(dkg_commitment.dealer, dkg_commitment.commitments.clone());
+            }
+            if let Some(ref election_key) = transaction.election_key {
+                self.election_key = Some(election_key.clone());
+            }
+        }
+    }
+
+    fn visit_snapshot(&mut self, state_snapshot: &StateSnapshot) {
+        for (dealer, commitments) in state_snapshot.dkg_commitments.iter() {
+            self.dkg_commitments.insert(*dealer, commitments.clone());
+        }
+        if let Some(ref election_key) = state_snapshot.election_key {
+            self.election_key = Some(election_key.clone());
+        }
+    }
+}
+
+/// Collects the final tally of each question committed on the canonical chain,
+/// including those summarized by the state snapshot the chain starts with.
+/// Only the oldest final tally of each question counts.
+pub struct FinalTallyVisitor {
+    final_tallies: BTreeMap<Option<String>, FinalTally>,
+}
+
+impl FinalTallyVisitor {
+    pub fn new() -> FinalTallyVisitor {
+        FinalTallyVisitor {
+            final_tallies: BTreeMap::new(),
+        }
+    }
+
+    /// Get the final tally committed on the question with the given identifier, if any.
+    pub fn get_final_tally(&self, question_id: &Option<String>) -> Option<&FinalTally> {
+        self.final_tallies.get(question_id)
+    }
+
+    /// Get the final tallies of all questions, ordered by the identifier of their question.
+    pub fn get_final_tallies(&self) -> Vec<FinalTally> {
+        self.final_tallies.values().cloned().collect()
+    }
+}
+
+impl Default for FinalTallyVisitor {
+    fn default() -> Self {
+        FinalTallyVisitor::new()
+    }
+}
+
+impl ChainVisitor for FinalTallyVisitor {
+    fn visit_block(&mut self, _height: Height, block: &Block) {
+        // blocks are visited from the newest to the oldest, hence older ones replace newer ones
+        for transaction in block.data.transactions.iter().rev() {
+            if let Some(ref final_tally) = transaction.final_tally {
+                self.final_tallies.insert(final_tally.question_id.clone(), final_tally.clone());
+            }
+        }
+    }
+
+    fn visit_snapshot(&mut self, state_snapshot: &StateSnapshot) {
+        for final_tally in state_snapshot.final_tallies.iter() {
+            self.final_tallies.insert(final_tally.question_id.clone(), final_tally.clone());
+        }
+    }
+}
+
+/// Collects the latest registration or revocation of each voter registered by the election authority
+/// on the canonical chain, including those summarized by the state snapshot the chain starts with.
+/// Voters are identified by the question they are registered for and their index.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct VoterRegistryVisitor {
+    voter_registrations: BTreeMap<(Option<String>, usize), VoterRegistration>,
+}
+
+impl VoterRegistryVisitor {
+    pub fn new() -> VoterRegistryVisitor {
+        VoterRegistryVisitor {
+            voter_registrations: BTreeMap::new(),
+        }
+    }
+
+    /// Get the latest registration or revocation of the voter with the given index on the question
+    /// with the given identifier, or None if the election authority never registered it.
+    pub fn get_voter_registration(&self, question_id: &Option<String>, voter_idx: usize) -> Option<&VoterRegistration> {
+        self.voter_registrations.get(&(question_id.clone(), voter_idx))
+    }
+
+    /// Get the latest registration or revocation of each voter, ordered by the identifier of their question and their index.
+    pub fn get_voter_registrations(&self) -> Vec<VoterRegistration> {
+        self.voter_registrations.values().cloned().collect()
+    }
+
+    /// Apply the given registration or revocation, replacing any earlier one of the same voter.
+    pub fn register(&mut self, voter_registration: VoterRegistration) {
+        self.voter_registrations.insert((voter_registration.question_id.clone(), voter_registration.voter_idx), voter_registration);
+    }
+
+    /// Returns true, if the voter with the given index may vote on the question with the given identifier,
+    /// i.e. it is registered on the chain, or declared in the given images of the genesis configuration and not revoked.
+    pub fn is_eligible(&self, question_id: &Option<String>, voter_idx: usize, image_sets: &[ImageSet]) -> bool {
+        match self.get_voter_registration(question_id, voter_idx) {
+            Some(voter_registration) => voter_registration.image_set.is_some(),
+            None => voter_idx < image_sets.len()
+        }
+    }
+
+    /// Returns the images of all voters eligible on the question with the given identifier, starting with the given
+    /// ones declared in the genesis configuration. Revoked voters and indices never registered hold no images.
+    pub fn get_image_sets(&self, question_id: &Option<String>, image_sets: &[ImageSet]) -> Vec<ImageSet> {
+        let mut eligible_image_sets = image_sets.to_vec();
+        for voter_registration in self.voter_registrations.values().filter(|voter_registration| voter_registration.question_id == *question_id) {
+            if eligible_image_sets.len() <= voter_registration.voter_idx {
+                eligible_image_sets.resize(voter_registration.voter_idx + 1, ImageSet { images: vec![] });
+            }
+            eligible_image_sets[voter_registration.voter_idx] = voter_registration.image_set.clone().unwrap_or(ImageSet { images: vec![] });
+        }
+
+        eligible_image_sets
+    }
+
+    /// Returns the images of all voters eligible on each of the given further questions, see `get_image_sets`.
+    pub fn get_question_image_sets(&self, questions: &BTreeMap<String, Vec<ImageSet>>) -> BTreeMap<String, Vec<ImageSet>> {
+        questions.iter()
+            .map(|(question_id, image_sets)| (question_id.clone(), self.get_image_sets(&Some(question_id.clone()), image_sets)))
+            .collect()
+    }
+}
+
+impl Default for VoterRegistryVisitor {
+    fn default() -> Self {
+        VoterRegistryVisitor::new()
+    }
+}
+
+impl ChainVisitor for VoterRegistryVisitor {
+    fn visit_block(&mut self, _height: Height, block: &Block) {
+        // blocks are visited from the newest to the oldest, hence older ones never replace newer ones
+        for transaction in block.data.transactions.iter().rev() {
+            if let Some(ref voter_registration) = transaction.voter_registration {
+                self.voter_registrations.entry((voter_registration.question_id.clone(), voter_registration.voter_idx))
+                    .or_insert_with(|| voter_registration.clone());
+            }
+        }
+    }
+
+    fn visit_snapshot(&mut self, state_snapshot: &StateSnapshot) {
+        for voter_registration in state_snapshot.voter_registrations.iter() {
+            self.voter_registrations.entry((voter_registration.question_id.clone(), voter_registration.voter_idx))
+                .or_insert_with(|| voter_registration.clone());
+        }
+    }
+}
+
 /// Sums up all votes contained in the transactions, after the voting has been opened
 /// and until it is closed again.
 ///
+/// The sum starts with the trivial encryption of zero, i.e. the neutral element of the addition,
+/// so that all nodes derive the same sum from the same chain. Of several votes of the same voter
+/// on the same question, only the oldest or the newest one is counted, depending on the revote policy.
+///
 /// Expects to be walked from the bottom up of the chain
 /// to the root to work correctly.
 pub struct SumCipherTextVisitor {
-    sum_cipher_text: CipherText,
-    total_votes: usize,
+    tally: TallyState,
     zero_cipher_text: CipherText,
-    is_voting_opened: bool,
-    is_voting_closed: bool,
-    traversed_vote_idx: HashSet<usize>,
+    registry: TransactionRegistry,
 }
 
 impl SumCipherTextVisitor {
-    pub fn new(public_key: PublicKey) -> SumCipherTextVisitor {
-        let cipher_text = encrypt(&public_key, ModInt::zero());
+    pub fn new(public_key: PublicKey, revote_policy: RevotePolicy) -> SumCipherTextVisitor {
+        let cipher_text = CipherText {
+            big_g: ModInt::from_value_modulus(BigInt::one(), public_key.p.value.clone()),
+            big_h: ModInt::from_value_modulus(BigInt::one(), public_key.p.value.clone()),
+            random: ModInt::from_value_modulus(BigInt::zero(), public_key.q.value.clone()),
+        };
 
         SumCipherTextVisitor {
-            sum_cipher_text: cipher_text.clone(),
-            total_votes: 0,
+            tally: TallyState::new(cipher_text.clone(), revote_policy),
             zero_cipher_text: cipher_text,
-            is_voting_opened: false,
-            is_voting_closed: true,
-            traversed_vote_idx: HashSet::new(),
+            registry: TransactionRegistry::with_default_kinds(),
         }
     }
 
+    /// Returns the amount and the sum of the votes on the question whose voters are declared in `public_uciv.json`.
     pub fn get_votes(&self) -> (usize, CipherText) {
+        self.get_votes_on(None)
+    }
+
+    /// Returns the amount and the sum of the votes on the question with the given identifier,
+    /// or on the question whose voters are declared in `public_uciv.json` if None.
+    pub fn get_votes_on(&self, question_id: Option<String>) -> (usize, CipherText) {
         // Now check that the voting was opened.
         // Note, that we cannot do this during block traversal as we do not know
         // when we've arrived at the root of the chain. Yes, we may check the parent hash
         // to be null/empty but this creates a dependency on how the genesis block is structured.
-        if self.is_voting_opened {
-            return (self.total_votes, self.sum_cipher_text.clone());
-        } else {
+        if !self.tally.is_voting_opened {
             warn!("Voting was never opened.");
             return (0, self.zero_cipher_text.clone());
         }
+
+        match self.tally.questions.get(&question_id) {
+            Some(question) => question.get_votes(),
+            None => (0, self.zero_cipher_text.clone())
+        }
     }
 }
 
 impl ChainVisitor for SumCipherTextVisitor {
-    fn visit_block(&mut self, _height: usize, block: &Block) {
+    fn visit_block(&mut self, _height: Height, block: &Block) {
         // Note: The blockchain is visited from the newest block first and is then
         // traversed from the bottom up.
 
         debug!("Counting votes in block {:?}", block.identifier.clone());
 
-        // homomorphically add the cipher text
-        for transaction in block.data.transactions.clone() {
-            match transaction.trx_type {
-                TransactionType::VoteOpened => {
-                    info!("Found open vote transaction {:?}", transaction.identifier.clone());
-                    self.is_voting_opened = true
+        // homomorphically add the cipher text, visiting the newest transaction of the block first as well
+        for transaction in block.data.transactions.iter().rev() {
+            self.registry.apply_to_tally(transaction, &mut self.tally);
+        }
+    }
+
+    /// Add the votes counted up to the root of the chain.
+    /// If the last vote of a voter counts, a voter who voted again above the root is counted twice,
+    /// as the vote replaced by the newer one is only contained in the sum of the snapshot.
+    /// If the first vote counts, the votes above the root of voters contained in the snapshot are dropped.
+    fn visit_snapshot(&mut self, state_snapshot: &StateSnapshot) {
+        debug!("Counting votes in snapshot at block {:?}", state_snapshot.block.identifier);
+
+        self.tally.is_voting_opened = self.tally.is_voting_opened || state_snapshot.is_voting_opened;
+
+        for (question_id, question_snapshot) in state_snapshot.get_question_snapshots() {
+            let voters = match snapshot::decode_voters(&question_snapshot.voters) {
+                Ok(voters) => voters,
+                Err(e) => {
+                    warn!("Skipping to count votes on question {:?} in snapshot at block {:?}: {}", question_id, state_snapshot.block.identifier, e);
+                    continue;
                 }
-                TransactionType::VoteClosed => {
-                    info!("Found close vote transaction {:?}", transaction.identifier.clone());
-                    self.is_voting_closed = true
+            };
+
+            let question = self.tally.question(question_id);
+            for voter_idx in voters.iter() {
+                question.earliest_votes.remove(voter_idx);
+            }
+            if let Some(sum_cipher_text) = question_snapshot.sum_cipher_text {
+                question.sum_cipher_text = question.sum_cipher_text.clone().operate(sum_cipher_text);
+            }
+            question.total_votes += question_snapshot.total_votes;
+            question.traversed_vote_idx.extend(voters);
+        }
+    }
+}
+
+/// The votes on a single question counted by a `StateSnapshotVisitor`.
+#[derive(Default)]
+struct QuestionVotes {
+    sum_cipher_text: Option<CipherText>,
+    total_votes: usize,
+    voters: HashSet<usize>,
+    /// The oldest vote of each voter visited so far, if the first vote of a voter counts.
+    /// These are only added to the sum once all votes are visited.
+    earliest_votes: BTreeMap<usize, CipherText>,
+}
+
+impl QuestionVotes {
+    fn add_votes(&mut self, sum_cipher_text: &CipherText, total_votes: usize) {
+        self.sum_cipher_text = Some(match self.sum_cipher_text.take() {
+            Some(current_sum) => current_sum.operate(sum_cipher_text.clone()),
+            None => sum_cipher_text.clone()
+        });
+        self.total_votes += total_votes;
+    }
+
+    /// Add the oldest vote of each voter to the sum, once all votes are visited.
+    fn add_earliest_votes(&mut self) {
+        let earliest_votes = ::std::mem::take(&mut self.earliest_votes);
+        for (voter_idx, cipher_text) in earliest_votes {
+            self.add_votes(&cipher_text, 1);
+            self.voters.insert(voter_idx);
+        }
+    }
+}
+
+/// Summarizes the phase of the election and the votes counted up to the block the walk starts with,
+/// in order to take a `StateSnapshot` at it.
+///
+/// Votes are counted as by a `SumCipherTextVisitor`, i.e. only the oldest or the newest vote of each voter
+/// depending on the revote policy, but their sum starts with the first counted vote instead of an encryption of zero,
+/// so that all nodes derive the same snapshot at the same block.
+///
+/// Expects to be walked from the bottom up of the chain
+/// to the root to work correctly.
+pub struct StateSnapshotVisitor {
+    voting_closed_visitor: VotingClosedVisitor,
+    key_generation_visitor: KeyGenerationVisitor,
+    final_tally_visitor: FinalTallyVisitor,
+    voter_registry_visitor: VoterRegistryVisitor,
+    is_voting_opened: bool,
+    /// The votes counted on each question, by its identifier, where None identifies
+    /// the question whose voters are declared in `public_uciv.json`.
+    questions: BTreeMap<Option<String>, QuestionVotes>,
+    revote_policy: RevotePolicy,
+}
+
+impl StateSnapshotVisitor {
+    pub fn new(revote_policy: RevotePolicy) -> StateSnapshotVisitor {
+        StateSnapshotVisitor {
+            voting_closed_visitor: VotingClosedVisitor::new(),
+            key_generation_visitor: KeyGenerationVisitor::new(),
+            final_tally_visitor: FinalTallyVisitor::new(),
+            voter_registry_visitor: VoterRegistryVisitor::new(),
+            is_voting_opened: false,
+            questions: BTreeMap::new(),
+            revote_policy,
+        }
+    }
+
+    /// Create the snapshot from the visited blocks.
+    ///
+    /// - genesis_configuration_hash: The hash of the genesis configuration of the chain.
+    /// - height: The height of the block the walk started with.
+    /// - total_difficulty: The total difficulty of the block.
+    /// - block: The block the walk started with.
+    /// - sealer_set: The sealers authorized to seal the children of the block.
+    pub fn into_state_snapshot(mut self, genesis_configuration_hash: String, height: Height, total_difficulty: u64, block: Block, sealer_set: &SealerSet) -> StateSnapshot {
+        for question in self.questions.values_mut() {
+            question.add_earliest_votes();
+        }
+
+        let default_question = self.questions.remove(&None).unwrap_or_default();
+        let questions = self.questions.into_iter()
+            .filter_map(|(question_id, question)| question_id.map(|question_id| (question_id, QuestionSnapshot {
+                sum_cipher_text: question.sum_cipher_text,
+                total_votes: question.total_votes,
+                voters: snapshot::encode_voters(&question.voters),
+            })))
+            .collect();
+
+        StateSnapshot {
+            genesis_configuration_hash,
+            height,
+            total_difficulty,
+            block,
+            sealers: sealer_set.get_sealers().to_vec(),
+            sealer_keys: sealer_set.get_sealer_keys().clone(),
+            pending_votes: sealer_set.get_pending_votes().to_vec(),
+            phase: self.voting_closed_visitor.get_election_phase(),
+            is_voting_opened: self.is_voting_opened,
+            sum_cipher_text: default_question.sum_cipher_text,
+            total_votes: default_question.total_votes,
+            voters: snapshot::encode_voters(&default_question.voters),
+            questions,
+            dkg_commitments: self.key_generation_visitor.dkg_commitments,
+            election_key: self.key_generation_visitor.election_key,
+            final_tallies: self.final_tally_visitor.get_final_tallies(),
+            voter_registrations: self.voter_registry_visitor.get_voter_registrations(),
+        }
+    }
+}
+
+impl ChainVisitor for StateSnapshotVisitor {
+    fn visit_block(&mut self, height: Height, block: &Block) {
+        self.voting_closed_visitor.visit_block(height, block);
+        self.key_generation_visitor.visit_block(height, block);
+        self.final_tally_visitor.visit_block(height, block);
+        self.voter_registry_visitor.visit_block(height, block);
+
+        for transaction in block.data.transactions.iter().rev() {
+            match (&transaction.trx_type, &transaction.data) {
+                (TransactionType::VoteOpened, _) => {
+                    self.is_voting_opened = true;
                 }
-                TransactionType::Vote => {
-                    // chain is traversed bottom up, so check first whether the voting
-                    // was closed at the end.
-                    if !self.is_voting_closed {
-                        warn!("Skipping to count vote in transaction {:?} as voting was not yet closed", transaction.identifier.clone());
-                    } else {
-
-                        // check whether we already counted a vote for the same voter
-                        let trx_data = transaction.data.unwrap();
-                        if self.traversed_vote_idx.contains(&trx_data.voter_idx) {
-                            info!("Voter with index {:?} has voted already. Ignoring transaction {:?}", trx_data.voter_idx, transaction.identifier.clone())
-                        } else {
-                            info!("Counting vote in transaction {:?}", transaction.identifier.clone());
-                            self.sum_cipher_text = self.sum_cipher_text.clone().operate(trx_data.cipher_text);
-                            self.total_votes = self.total_votes + 1;
-                            self.traversed_vote_idx.insert(trx_data.voter_idx);
-                        }
+                (TransactionType::Vote, Some(trx_data)) => {
+                    let question = self.questions.entry(trx_data.question_id.clone()).or_default();
+                    if RevotePolicy::FirstVoteCounts == self.revote_policy {
+                        // an older vote of the same voter is visited later, replacing this one
+                        question.earliest_votes.insert(trx_data.voter_idx, trx_data.cipher_text.clone());
+                    } else if question.voters.insert(trx_data.voter_idx) {
+                        question.add_votes(&trx_data.cipher_text, 1);
                     }
                 }
+                _ => {}
+            }
+        }
+    }
+
+    fn visit_snapshot(&mut self, state_snapshot: &StateSnapshot) {
+        self.voting_closed_visitor.visit_snapshot(state_snapshot);
+        self.key_generation_visitor.visit_snapshot(state_snapshot);
+        self.final_tally_visitor.visit_snapshot(state_snapshot);
+        self.voter_registry_visitor.visit_snapshot(state_snapshot);
+
+        self.is_voting_opened = self.is_voting_opened || state_snapshot.is_voting_opened;
+
+        for (question_id, question_snapshot) in state_snapshot.get_question_snapshots() {
+            let voters = match snapshot::decode_voters(&question_snapshot.voters) {
+                Ok(voters) => voters,
+                Err(e) => {
+                    warn!("Skipping votes on question {:?} in snapshot at block {:?}: {}", question_id, state_snapshot.block.identifier, e);
+                    continue;
+                }
+            };
+
+            let question = self.questions.entry(question_id).or_default();
+            for voter_idx in voters.iter() {
+                question.earliest_votes.remove(voter_idx);
             }
+            if let Some(ref sum_cipher_text) = question_snapshot.sum_cipher_text {
+                question.add_votes(sum_cipher_text, question_snapshot.total_votes);
+            }
+            question.voters.extend(voters);
         }
     }
-}
\ No newline at end of file
+}
@@ -1,6 +1,7 @@
 use ::chain::block::Block;
 use ::chain::chain::Chain;
 use ::chain::chain_visitor::ChainVisitor;
+use ::chain::types::Height;
 
 /// A ChainWalker walks the given chain in a particular order
 /// and can invoke the given visitor at any point during its traversal.
@@ -17,71 +18,43 @@ pub trait ChainWalker {
     fn walk_chain<F: ChainVisitor>(&self, chain: &Chain, visitor: &mut F);
 }
 
-/// The heaviest block walker walks the given chain to find
-/// the deepest block currently known and invokes any provided visitor
-/// with the block found at the end of the longest path.
+/// The heaviest block walker finds the head of the given chain,
+/// i.e. the block of the greatest total difficulty, and invokes any provided visitor
+/// with the block found at the end of the heaviest branch.
 pub struct HeaviestBlockWalker {}
 
 impl HeaviestBlockWalker {
     pub fn new() -> HeaviestBlockWalker {
         HeaviestBlockWalker {}
     }
-
-    fn traverse_level(parent_level: usize, parent_block: &Block, chain: &Chain) -> (usize, String) {
-        let mut most_deepest_block = (parent_level, parent_block.identifier.clone());
-
-        // get all children of the current parent block
-        let children = chain.adjacent_matrix.get(parent_block.identifier.as_str()).unwrap();
-
-        let current_child_level = parent_level + 1;
-        for child_hash in children.iter() {
-            let child = chain.blocks.get(child_hash).unwrap();
-
-            let result: (usize, String) = HeaviestBlockWalker::traverse_level(current_child_level, child, &chain);
-
-            // update current most deepest depth and the corresponding block hash
-            if result.0 > most_deepest_block.0 {
-                most_deepest_block.0 = result.0;
-                most_deepest_block.1 = result.1;
-            }
-        }
-
-        most_deepest_block
-    }
 }
 
 impl ChainWalker for HeaviestBlockWalker {
-    /// Visits the given chain to find the deepest block in the chain, i.e. the one
-    /// having the most parents. Once found, it will invoke the given visitor with
-    /// the corresponding found block.
+    /// Visits the given chain to find the heaviest block in the chain, i.e. the one
+    /// whose branch has the greatest total difficulty. Once found, it will invoke the given visitor with
+    /// the corresponding found block. See `Chain::head`.
     ///
-    /// - `chain`: The chain to search for the deepest block.
-    /// - `visitor`: A visitor which should be invoked with the deepest block found.
+    /// - `chain`: The chain to search for the heaviest block.
+    /// - `visitor`: A visitor which should be invoked with the heaviest block found.
     ///
     /// A visitor can be of any type as long as it implements the trait `ChainVisitor`.
     fn walk_chain<F: ChainVisitor>(&self, chain: &Chain, visitor: &mut F) {
-        let genesis_children = chain.adjacent_matrix.get(chain.genesis_identifier_hash.clone().as_str()).unwrap();
-
-        let mut current_deepest_block = (0, chain.genesis_identifier_hash.clone());
-        for genesis_child_hash in genesis_children.iter() {
-            let genesis_child = chain.blocks.get(genesis_child_hash).unwrap();
-
-            // genesis child is already at depth 1
-            let result: (usize, String) = HeaviestBlockWalker::traverse_level(1, genesis_child, &chain);
-
-            // update current most deepest depth and the corresponding block hash
-            if result.0 > current_deepest_block.0 {
-                current_deepest_block.0 = result.0;
-                current_deepest_block.1 = result.1;
+        let head = match chain.head() {
+            Ok(head) => head,
+            Err(e) => {
+                warn!("Not walking chain as its head cannot be determined: {:?}", e);
+                return;
             }
-        }
+        };
 
-        // visit the block being at the most deepest position
-        let deepest_block = chain.blocks.get(current_deepest_block.1.as_str()).unwrap();
-        visitor.visit_block(current_deepest_block.0, deepest_block);
+        // visit the block at the end of the heaviest branch
+        visitor.visit_block(head.height, &head.block);
     }
 }
 
+/// Walks the canonical chain, i.e. the heaviest branch, from its head
+/// up to the genesis block, which is not visited.
+/// If the chain starts with a state snapshot, the walk ends with the snapshot in place of its block, which it summarizes.
 pub struct LongestPathWalker {}
 
 impl LongestPathWalker {
@@ -89,136 +62,147 @@ impl LongestPathWalker {
         LongestPathWalker {}
     }
 
-    fn traverse_level(parent_level: usize, parent_block: &Block, chain: &Chain) -> (usize, String) {
-        let mut most_deepest_block = (parent_level, parent_block.identifier.clone());
-
-        // get all children of the current parent block
-        let children = chain.adjacent_matrix.get(parent_block.identifier.as_str()).unwrap();
-
-        let current_child_level = parent_level + 1;
-        for child_hash in children.iter() {
-            let child = chain.blocks.get(child_hash).unwrap();
-
-            let result: (usize, String) = LongestPathWalker::traverse_level(current_child_level, child, &chain);
-
-            // update current most deepest depth and the corresponding block hash
-            if result.0 > most_deepest_block.0 {
-                most_deepest_block.0 = result.0;
-                most_deepest_block.1 = result.1;
-            }
-        }
-
-        most_deepest_block
+    /// Walk the canonical chain from the given block at the given height
+    /// instead of the head, e.g. to summarize the chain up to a finalized block.
+    pub fn walk_from<F: ChainVisitor>(&self, chain: &Chain, height: Height, block: &Block, visitor: &mut F) {
+        LongestPathWalker::traverse_bottom_up(height, block, chain, visitor);
     }
 
-    fn traverse_bottom_up<F: ChainVisitor>(child_level: usize, child_block: &Block, chain: &Chain, visitor: &mut F) {
+    /// Visit the given block and its ancestors one after another, so that neither the
+    /// height of the chain nor the amount of forks affects the depth of the call stack.
+    /// Stops with a warning if an ancestor is missing, as the chain is inconsistent then.
+    fn traverse_bottom_up<F: ChainVisitor>(child_level: Height, child_block: &Block, chain: &Chain, visitor: &mut F) {
+        let mut level = child_level;
+        let mut block = child_block;
+
         // check whether we've reached the genesis block
         // which we do not visit
-        if child_block.data.parent == String::new() {
-            return;
-        }
+        while !block.data.parent.is_empty() {
+            if let Some(state_snapshot) = chain.get_state_snapshot() {
+                if block.identifier == chain.genesis_identifier_hash {
+                    visitor.visit_snapshot(state_snapshot);
+                    return;
+                }
+            }
 
-        visitor.visit_block(child_level, child_block);
+            visitor.visit_block(level, block);
 
-        let parent_block = chain.blocks.get(child_block.data.parent.as_str()).unwrap();
-        Self::traverse_bottom_up(child_level - 1, parent_block, chain, visitor);
+            block = match chain.blocks.get(block.data.parent.as_str()) {
+                Some(parent_block) => parent_block,
+                None => {
+                    warn!("Stopping walk at block {:?} as its parent {:?} is missing", block.identifier, block.data.parent);
+                    return;
+                }
+            };
+            level -= 1;
+        }
     }
 }
 
 impl ChainWalker for LongestPathWalker {
     fn walk_chain<F: ChainVisitor>(&self, chain: &Chain, visitor: &mut F) {
-        let genesis_children = chain.adjacent_matrix.get(chain.genesis_identifier_hash.clone().as_str()).unwrap();
-
-        let mut current_deepest_block = (0, chain.genesis_identifier_hash.clone());
-        for genesis_child_hash in genesis_children.iter() {
-            let genesis_child = chain.blocks.get(genesis_child_hash).unwrap();
-
-            // genesis child is already at depth 1
-            let result: (usize, String) = LongestPathWalker::traverse_level(1, genesis_child, &chain);
-
-            // update current most deepest depth and the corresponding block hash
-            if result.0 > current_deepest_block.0 {
-                current_deepest_block.0 = result.0;
-                current_deepest_block.1 = result.1;
+        let head = match chain.head() {
+            Ok(head) => head,
+            Err(e) => {
+                warn!("Not walking chain as its head cannot be determined: {:?}", e);
+                return;
             }
-        }
+        };
 
-        // visit the block being at the most deepest position
-        let deepest_block = chain.blocks.get(current_deepest_block.1.as_str()).unwrap();
-        LongestPathWalker::traverse_bottom_up(current_deepest_block.0, deepest_block, chain, visitor);
+        LongestPathWalker::traverse_bottom_up(head.height, &head.block, chain, visitor);
     }
 }
 
 #[cfg(test)]
+#[allow(deprecated)]
 mod chain_walker_test {
 
     use ::chain::block::{Block, BlockContent};
     use ::chain::chain::Chain;
-    use ::chain::chain_visitor::{HeaviestBlockVisitor, SumCipherTextVisitor};
+    use ::chain::chain_visitor::{HeaviestBlockVisitor, SealerStatisticsVisitor, SumCipherTextVisitor};
     use ::chain::chain_walker::{ChainWalker, HeaviestBlockWalker, LongestPathWalker};
+    use ::chain::hasher::Hasher;
     use ::chain::transaction::Transaction;
+    use ::chain::transaction_registry::RevotePolicy;
+    use ::chain::types::{BlockHash, Height};
     use crypto_rs::el_gamal::encryption::{PublicKey};
     use crypto_rs::el_gamal::ciphertext::CipherText;
     use crypto_rs::el_gamal::membership_proof::MembershipProof;
-    use crypto_rs::arithmetic::mod_int::ModInt;
+    use crypto_rs::arithmetic::mod_int::{From as ModIntFrom, ModInt};
     use crypto_rs::cai::uciv::{CaiProof, PreImageSet, ImageSet};
-    use num::One;
+    use num::{BigInt, One};
+    use std::net::SocketAddr;
 
     /// Test that the longest chain is found if no conflicting
     /// branch is present, i.e. a branch having the exact amount of children
     /// as another once.
     #[test]
     fn test_heaviest_path() {
-        let mut chain = Chain::new(String::new());
+        let mut chain = Chain::new(String::new(), Hasher::default());
         let genesis_id = chain.genesis_identifier_hash.clone();
 
         // first level
         chain.add_block(Block {
-            identifier: "1".to_string(),
+            identifier: BlockHash::from("1"),
             data: BlockContent {
                 parent: genesis_id,
                 timestamp: 1,
-                transactions: vec![]
-            }
+                sealer: None,
+                transactions: vec![],
+                transactions_root: None
+            },
+            signature: None
         });
 
         // second level
         chain.add_block(Block {
-            identifier: "21".to_string(),
+            identifier: BlockHash::from("21"),
             data: BlockContent {
-                parent: "1".to_string(),
+                parent: BlockHash::from("1"),
                 timestamp: 2,
-                transactions: vec![]
-            }
+                sealer: None,
+                transactions: vec![],
+                transactions_root: None
+            },
+            signature: None
         });
 
         chain.add_block(Block {
-            identifier: "22".to_string(),
+            identifier: BlockHash::from("22"),
             data: BlockContent {
-                parent: "1".to_string(),
+                parent: BlockHash::from("1"),
                 timestamp: 3,
-                transactions: vec![]
-            }
+                sealer: None,
+                transactions: vec![],
+                transactions_root: None
+            },
+            signature: None
         });
 
         // third level
         chain.add_block(Block {
-            identifier: "3".to_string(),
+            identifier: BlockHash::from("3"),
             data: BlockContent {
-                parent: "22".to_string(),
+                parent: BlockHash::from("22"),
                 timestamp: 4,
-                transactions: vec![]
-            }
+                sealer: None,
+                transactions: vec![],
+                transactions_root: None
+            },
+            signature: None
         });
 
         // fourth level
         chain.add_block(Block {
-            identifier: "4".to_string(),
+            identifier: BlockHash::from("4"),
             data: BlockContent {
-                parent: "3".to_string(),
+                parent: BlockHash::from("3"),
                 timestamp: 5,
-                transactions: vec![]
-            }
+                sealer: None,
+                transactions: vec![],
+                transactions_root: None
+            },
+            signature: None
         });
 
         let mut heaviest_block_visitor = HeaviestBlockVisitor::new();
@@ -227,28 +211,31 @@ mod chain_walker_test {
 
         let heaviest_block_height = heaviest_block_visitor.height;
         assert!(heaviest_block_height.is_some(), "Expected that heaviest block height is of type Some()");
-        assert!(heaviest_block_height.unwrap().eq(&4), "Expected that heaviest block height is 4");
+        assert!(heaviest_block_height.unwrap().eq(&Height::new(4)), "Expected that heaviest block height is 4");
 
         let heaviest_block = heaviest_block_visitor.heaviest_block;
         assert!(heaviest_block.is_some());
         let expected_heaviest_block = heaviest_block.unwrap();
         println!("expected heaviest block {:?}", expected_heaviest_block);
-        assert!(expected_heaviest_block.eq(&"4".to_string()));
+        assert!(expected_heaviest_block.eq(&BlockHash::from("4")));
     }
 
     #[test]
     fn test_heaviest_path_for_two_blocks() {
-        let mut chain = Chain::new(String::new());
+        let mut chain = Chain::new(String::new(), Hasher::default());
         let genesis_id = chain.genesis_identifier_hash.clone();
 
         // first level
         chain.add_block(Block {
-            identifier: "1".to_string(),
+            identifier: BlockHash::from("1"),
             data: BlockContent {
                 parent: genesis_id,
                 timestamp: 1,
-                transactions: vec![]
-            }
+                sealer: None,
+                transactions: vec![],
+                transactions_root: None
+            },
+            signature: None
         });
 
         let mut heaviest_block_visitor = HeaviestBlockVisitor::new();
@@ -257,18 +244,18 @@ mod chain_walker_test {
 
         let heaviest_block_height = heaviest_block_visitor.height;
         assert!(heaviest_block_height.is_some(), "Expected that heaviest block height is of type Some()");
-        assert!(heaviest_block_height.unwrap().eq(&1), "Expected that heaviest block height is 1");
+        assert!(heaviest_block_height.unwrap().eq(&Height::new(1)), "Expected that heaviest block height is 1");
 
         let option = heaviest_block_visitor.heaviest_block;
         assert!(option.is_some());
         let expected_heaviest_block = option.unwrap();
         println!("expected heaviest block {:?}", expected_heaviest_block);
-        assert!(expected_heaviest_block.eq(&"1".to_string()));
+        assert!(expected_heaviest_block.eq(&BlockHash::from("1")));
     }
 
     #[test]
     fn test_heaviest_path_for_empty_chain() {
-        let chain = Chain::new(String::new());
+        let chain = Chain::new(String::new(), Hasher::default());
 
         let mut heaviest_block_visitor = HeaviestBlockVisitor::new();
         let longest_path_walker = HeaviestBlockWalker::new();
@@ -276,17 +263,17 @@ mod chain_walker_test {
 
         let heaviest_block_height = heaviest_block_visitor.height;
         assert!(heaviest_block_height.is_some(), "Expected that heaviest block height is of type Some()");
-        assert!(heaviest_block_height.unwrap().eq(&0), "Expected that heaviest block height is 0");
+        assert!(heaviest_block_height.unwrap().eq(&Height::new(0)), "Expected that heaviest block height is 0");
 
         let option = heaviest_block_visitor.heaviest_block;
         assert!(option.is_some());
         let expected_heaviest_block = option.unwrap();
-        assert!(chain.blocks.get(expected_heaviest_block.as_str()).unwrap().data.parent.eq(&String::new()));
+        assert!(chain.blocks.get(expected_heaviest_block.as_str()).unwrap().data.parent.is_empty());
     }
 
     #[test]
     fn test_longest_path_sum() {
-        let mut chain = Chain::new(String::new());
+        let mut chain = Chain::new(String::new(), Hasher::default());
         let genesis_id = chain.genesis_identifier_hash.clone();
 
         let public_key = PublicKey {
@@ -310,28 +297,32 @@ mod chain_walker_test {
             images: vec![ModInt::one()]
         };
 
-        let open_trx = Transaction::new_voting_opened();
+        let open_trx = Transaction::new_voting_opened(Hasher::default(), "genesis".to_string(), 0);
 
         let trx = Transaction::new_vote(
+            Hasher::default(),
             0,
             cipher_text.clone(),
             MembershipProof::new(public_key.clone(), ModInt::one(), cipher_text.clone(), vec![ModInt::one()]),
             CaiProof::new(public_key.clone(), cipher_text.clone(), pre_image_set.clone(), image_set.clone(), 0, vec![ModInt::one()]),
         );
 
-        let close_trx = Transaction::new_voting_opened();
+        let close_trx = Transaction::new_voting_opened(Hasher::default(), "genesis".to_string(), 0);
 
         // first level
         chain.add_block(Block {
-            identifier: "1".to_string(),
+            identifier: BlockHash::from("1"),
             data: BlockContent {
                 parent: genesis_id,
                 timestamp: 1,
-                transactions: vec![open_trx.clone(), trx.clone(), close_trx.clone()]
-            }
+                sealer: None,
+                transactions: vec![open_trx.clone(), trx.clone(), close_trx.clone()],
+                transactions_root: None
+            },
+            signature: None
         });
 
-        let mut sum_cipher_text_visitor = SumCipherTextVisitor::new(public_key);
+        let mut sum_cipher_text_visitor = SumCipherTextVisitor::new(public_key, RevotePolicy::default());
         let longest_path_walker = LongestPathWalker::new();
         longest_path_walker.walk_chain(&chain, &mut sum_cipher_text_visitor);
 
@@ -340,4 +331,173 @@ mod chain_walker_test {
         assert_eq!(1, total_votes.0);
     }
 
+    #[test]
+    fn test_revote_policy() {
+        // proofs are not verified when summing up the votes
+        let proof_key = PublicKey {
+            p: ModInt::one(),
+            q: ModInt::one(),
+            h: ModInt::one(),
+            g: ModInt::one(),
+        };
+        let public_key = PublicKey {
+            p: ModInt::from_value_modulus(BigInt::from(23), BigInt::from(23)),
+            q: ModInt::from_value_modulus(BigInt::from(11), BigInt::from(11)),
+            h: ModInt::from_value_modulus(BigInt::from(4), BigInt::from(23)),
+            g: ModInt::from_value_modulus(BigInt::from(4), BigInt::from(23)),
+        };
+
+        let vote = |voter_idx: usize, value: u64| {
+            let cipher_text = CipherText {
+                big_g: ModInt::from_value_modulus(BigInt::from(value), BigInt::from(23)),
+                big_h: ModInt::from_value_modulus(BigInt::from(value), BigInt::from(23)),
+                random: ModInt::from_value_modulus(BigInt::from(value), BigInt::from(11)),
+            };
+
+            Transaction::new_vote(
+                Hasher::default(),
+                voter_idx,
+                cipher_text.clone(),
+                MembershipProof::new(proof_key.clone(), ModInt::one(), cipher_text.clone(), vec![ModInt::one()]),
+                CaiProof::new(proof_key.clone(), cipher_text, PreImageSet { pre_images: vec![ModInt::one()] }, ImageSet { images: vec![ModInt::one()] }, 0, vec![ModInt::one()]),
+            )
+        };
+
+        // each block holds the given transactions, the first one opening the voting and the last one closing it
+        let sum_votes = |blocks: Vec<Vec<Transaction>>, revote_policy: RevotePolicy| {
+            let mut chain = Chain::new(String::new(), Hasher::default());
+            let mut parent = chain.genesis_identifier_hash.clone();
+            let amount_of_blocks = blocks.len();
+            for (position, mut transactions) in blocks.into_iter().enumerate() {
+                if 0 == position {
+                    transactions.insert(0, Transaction::new_voting_opened(Hasher::default(), "genesis".to_string(), 0));
+                }
+                if amount_of_blocks == position + 1 {
+                    transactions.push(Transaction::new_voting_closed(Hasher::default(), "genesis".to_string(), 1));
+                }
+
+                let identifier = BlockHash::from(format!("{}", position + 1));
+                chain.add_block(Block {
+                    identifier: identifier.clone(),
+                    data: BlockContent {
+                        parent,
+                        timestamp: position as u64 + 1,
+                        sealer: None,
+                        transactions,
+                        transactions_root: None
+                    },
+                    signature: None
+                });
+                parent = identifier;
+            }
+
+            let mut sum_cipher_text_visitor = SumCipherTextVisitor::new(public_key.clone(), revote_policy);
+            LongestPathWalker::new().walk_chain(&chain, &mut sum_cipher_text_visitor);
+
+            sum_cipher_text_visitor.get_votes()
+        };
+
+        // the first voter votes again in a later block, the second one within the same block
+        let blocks = vec![vec![vote(0, 2), vote(1, 5), vote(1, 7)], vec![vote(0, 3)]];
+
+        let first_votes = sum_votes(vec![vec![vote(0, 2), vote(1, 5)]], RevotePolicy::FirstVoteCounts);
+        assert_eq!(2, first_votes.0);
+        assert_eq!(first_votes, sum_votes(blocks.clone(), RevotePolicy::FirstVoteCounts));
+
+        let last_votes = sum_votes(vec![vec![vote(1, 7)], vec![vote(0, 3)]], RevotePolicy::LastVoteCounts);
+        assert_eq!(2, last_votes.0);
+        assert_eq!(last_votes, sum_votes(blocks, RevotePolicy::LastVoteCounts));
+        assert!(first_votes.1 != last_votes.1);
+    }
+
+    #[test]
+    fn test_sealer_statistics() {
+        let mut chain = Chain::new(String::new(), Hasher::default());
+        let genesis_id = chain.genesis_identifier_hash.clone();
+
+        let first_sealer: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let second_sealer: SocketAddr = "127.0.0.1:9001".parse().unwrap();
+
+        chain.add_block(Block {
+            identifier: BlockHash::from("1"),
+            data: BlockContent {
+                parent: genesis_id,
+                timestamp: 1,
+                sealer: Some(first_sealer),
+                transactions: vec![],
+                transactions_root: None
+            },
+            signature: None
+        });
+
+        chain.add_block(Block {
+            identifier: BlockHash::from("2"),
+            data: BlockContent {
+                parent: BlockHash::from("1"),
+                timestamp: 2,
+                sealer: Some(second_sealer),
+                transactions: vec![],
+                transactions_root: None
+            },
+            signature: None
+        });
+
+        chain.add_block(Block {
+            identifier: BlockHash::from("3"),
+            data: BlockContent {
+                parent: BlockHash::from("2"),
+                timestamp: 3,
+                sealer: Some(first_sealer),
+                transactions: vec![],
+                transactions_root: None
+            },
+            signature: None
+        });
+
+        let mut sealer_statistics_visitor = SealerStatisticsVisitor::new();
+        let longest_path_walker = LongestPathWalker::new();
+        longest_path_walker.walk_chain(&chain, &mut sealer_statistics_visitor);
+
+        assert_eq!(2, sealer_statistics_visitor.get_sealed_blocks(&first_sealer));
+        assert_eq!(1, sealer_statistics_visitor.get_sealed_blocks(&second_sealer));
+        // the genesis block is not visited by the longest path walker
+        assert_eq!(0, sealer_statistics_visitor.get_unattributed_blocks());
+    }
+
+    #[test]
+    fn test_walk_deep_chain_with_missing_ancestor() {
+        let mut chain = Chain::new(String::new(), Hasher::default());
+        let sealer: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let amount_of_blocks = 100_000;
+
+        let mut parent = chain.genesis_identifier_hash.clone();
+        for index in 1..=amount_of_blocks {
+            let identifier = BlockHash::from(format!("{}", index));
+            chain.add_block(Block {
+                identifier: identifier.clone(),
+                data: BlockContent {
+                    parent,
+                    timestamp: index as u64,
+                    sealer: Some(sealer),
+                    transactions: vec![],
+                    transactions_root: None
+                },
+                signature: None
+            });
+            parent = identifier;
+        }
+
+        // the walk does not grow the call stack with the height of the chain
+        let mut sealer_statistics_visitor = SealerStatisticsVisitor::new();
+        LongestPathWalker::new().walk_chain(&chain, &mut sealer_statistics_visitor);
+        assert_eq!(amount_of_blocks, sealer_statistics_visitor.get_sealed_blocks(&sealer));
+
+        // the walk stops at a block whose parent is missing instead of panicking
+        let tip = chain.blocks[&BlockHash::from("10")].clone();
+        chain.blocks.remove(&BlockHash::from("5"));
+        let mut sealer_statistics_visitor = SealerStatisticsVisitor::new();
+        LongestPathWalker::new().walk_from(&chain, Height::new(10), &tip, &mut sealer_statistics_visitor);
+        assert_eq!(5, sealer_statistics_visitor.get_sealed_blocks(&sealer));
+    }
+
 }
\ No newline at end of file
@@ -0,0 +1,165 @@
+use ::chain::types::Height;
+use std::net::SocketAddr;
+
+/// A round of the sealing schedule, in which each sealer leads the sealing
+/// of exactly one block, in the order of the sealers in the genesis configuration.
+///
+/// The first epoch starts with the block following the genesis block,
+/// which itself does not belong to any epoch.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Epoch {
+    /// The number of the epoch, starting at zero.
+    pub number: usize,
+    /// The height of the first block of the epoch.
+    pub first_height: Height,
+    /// The height of the last block of the epoch.
+    pub last_height: Height,
+}
+
+impl Epoch {
+    /// Returns true, if the block at the given height belongs to this epoch.
+    pub fn contains(&self, height: Height) -> bool {
+        self.first_height <= height && height <= self.last_height
+    }
+}
+
+/// Maps heights to epochs and to the sealer expected to lead the sealing of the block at a height.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct EpochSchedule {
+    /// The sealers in the order of the genesis configuration.
+    sealers: Vec<SocketAddr>,
+}
+
+impl EpochSchedule {
+    /// Create a new schedule for the given sealers,
+    /// in the order of the genesis configuration.
+    pub fn new(sealers: Vec<SocketAddr>) -> EpochSchedule {
+        EpochSchedule {
+            sealers
+        }
+    }
+
+    /// Returns the amount of blocks of each epoch.
+    pub fn get_epoch_length(&self) -> usize {
+        self.sealers.len()
+    }
+
+    /// Returns the epoch with the given number.
+    pub fn get_epoch_by_number(&self, number: usize) -> Epoch {
+        let first_height = Height::new(number * self.get_epoch_length() + 1);
+
+        Epoch {
+            number,
+            first_height,
+            last_height: first_height + (self.get_epoch_length() - 1),
+        }
+    }
+
+    /// Returns the epoch the block at the given height belongs to,
+    /// or None for the genesis block.
+    pub fn get_epoch(&self, height: Height) -> Option<Epoch> {
+        if Height::GENESIS == height {
+            return None;
+        }
+
+        Some(self.get_epoch_by_number((height.as_usize() - 1) / self.get_epoch_length()))
+    }
+
+    /// Returns the index of the sealer expected to lead the sealing of the block at the given height,
+    /// or None for the genesis block.
+    pub fn get_leader_index(&self, height: Height) -> Option<usize> {
+        if Height::GENESIS == height {
+            return None;
+        }
+
+        Some((height.as_usize() - 1) % self.get_epoch_length())
+    }
+
+    /// Returns the sealer expected to lead the sealing of the block at the given height,
+    /// or None for the genesis block.
+    pub fn get_leader(&self, height: Height) -> Option<SocketAddr> {
+        self.get_leader_index(height).map(|leader_index| self.sealers[leader_index])
+    }
+
+    /// Returns the sealers in the order of the genesis configuration.
+    pub fn get_sealers(&self) -> &Vec<SocketAddr> {
+        &self.sealers
+    }
+}
+
+/// The participation of a single sealer in an epoch.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct SealerParticipation {
+    /// The address of the sealer.
+    pub sealer: SocketAddr,
+    /// The amount of blocks of the epoch sealed by the sealer, as leader or as co-leader.
+    pub sealed_blocks: usize,
+    /// The amount of blocks of the epoch which the sealer was expected to lead,
+    /// but which were sealed by a co-leader instead.
+    pub missed_slots: usize,
+}
+
+/// Statistics about the blocks of a single epoch of the canonical chain.
+///
+/// As long as an epoch is not completed, only its blocks sealed so far are considered.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct EpochStatistics {
+    pub epoch: Epoch,
+    /// The amount of blocks of the epoch on the canonical chain.
+    pub blocks: usize,
+    /// The amount of transactions included in the blocks of the epoch.
+    pub transactions: usize,
+    /// The participation of each sealer, in the order of the genesis configuration.
+    pub participation: Vec<SealerParticipation>,
+    /// The amount of blocks of the epoch which do not specify their sealer.
+    pub unattributed_blocks: usize,
+}
+
+impl EpochStatistics {
+    /// Create empty statistics for the given epoch of the given schedule.
+    pub fn new(epoch: Epoch, schedule: &EpochSchedule) -> EpochStatistics {
+        EpochStatistics {
+            epoch,
+            blocks: 0,
+            transactions: 0,
+            participation: schedule.get_sealers().iter()
+                .map(|sealer| SealerParticipation {
+                    sealer: *sealer,
+                    sealed_blocks: 0,
+                    missed_slots: 0,
+                })
+                .collect(),
+            unattributed_blocks: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod epoch_test {
+
+    use super::*;
+
+    #[test]
+    fn test_epoch_schedule() {
+        let sealers: Vec<SocketAddr> = vec!["127.0.0.1:9000".parse().unwrap(), "127.0.0.1:9001".parse().unwrap(), "127.0.0.1:9002".parse().unwrap()];
+        let schedule = EpochSchedule::new(sealers.clone());
+
+        assert_eq!(None, schedule.get_epoch(Height::GENESIS));
+        assert_eq!(None, schedule.get_leader(Height::GENESIS));
+
+        let first_epoch = schedule.get_epoch(Height::new(1)).unwrap();
+        assert_eq!(0, first_epoch.number);
+        assert_eq!(Height::new(1), first_epoch.first_height);
+        assert_eq!(Height::new(3), first_epoch.last_height);
+        assert_eq!(Some(first_epoch), schedule.get_epoch(Height::new(3)));
+
+        let second_epoch = schedule.get_epoch(Height::new(4)).unwrap();
+        assert_eq!(1, second_epoch.number);
+        assert!(second_epoch.contains(Height::new(6)));
+        assert!(!second_epoch.contains(Height::new(7)));
+
+        assert_eq!(Some(sealers[0]), schedule.get_leader(Height::new(1)));
+        assert_eq!(Some(sealers[2]), schedule.get_leader(Height::new(3)));
+        assert_eq!(Some(sealers[0]), schedule.get_leader(Height::new(4)));
+    }
+}
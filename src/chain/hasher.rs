@@ -0,0 +1,84 @@
+use ::mac::encode_hex;
+use blake2::Blake2s256;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+/// Separates the name of the hash function from the digest in a versioned identifier.
+const ALGORITHM_SEPARATOR: char = ':';
+
+/// The hash function from which the identifiers of blocks and transactions
+/// as well as the roots of their Merkle trees are derived.
+///
+/// Identifiers are versioned by prefixing the digest with the name of the hash function,
+/// e.g. `sha256:<hex digest>`. Identifiers without prefix are SHA-1 digests, as derived
+/// by earlier versions of this node, so that their blocks and transactions still verify.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Hasher {
+    /// SHA-1, which is collision-broken. Only kept to derive and verify legacy identifiers.
+    Sha1,
+    #[default]
+    Sha256,
+    Blake2s,
+}
+
+impl Hasher {
+    /// Returns the hash function the given identifier was derived with,
+    /// or None if it names an unknown hash function.
+    pub fn from_identifier(identifier: &str) -> Option<Hasher> {
+        match identifier.find(ALGORITHM_SEPARATOR) {
+            Some(index) => Hasher::from_name(&identifier[..index]),
+            None => Some(Hasher::Sha1)
+        }
+    }
+
+    /// Returns the hash function with the given name, as used in the prefix of identifiers.
+    pub fn from_name(name: &str) -> Option<Hasher> {
+        match name {
+            "sha1" => Some(Hasher::Sha1),
+            "sha256" => Some(Hasher::Sha256),
+            "blake2s" => Some(Hasher::Blake2s),
+            _ => None
+        }
+    }
+
+    /// Returns the name of this hash function.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Hasher::Sha1 => "sha1",
+            Hasher::Sha256 => "sha256",
+            Hasher::Blake2s => "blake2s",
+        }
+    }
+
+    /// Returns the versioned identifier of the given bytes.
+    /// SHA-1 digests are not prefixed, so that they match the identifiers of earlier versions.
+    pub fn digest(&self, bytes: &[u8]) -> String {
+        match *self {
+            Hasher::Sha1 => Sha1::from(bytes).hexdigest(),
+            Hasher::Sha256 => format!("{}{}{}", self.name(), ALGORITHM_SEPARATOR, encode_hex(&Sha256::digest(bytes))),
+            Hasher::Blake2s => format!("{}{}{}", self.name(), ALGORITHM_SEPARATOR, encode_hex(&Blake2s256::digest(bytes))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod hasher_test {
+
+    use super::*;
+
+    #[test]
+    fn test_versioned_identifiers() {
+        // as derived by earlier versions, e.g. for the root of an empty Merkle tree
+        let legacy = "da39a3ee5e6b4b0d3255bfef95601890afd80709";
+        assert_eq!(legacy, Hasher::Sha1.digest(b""));
+        assert_eq!(Some(Hasher::Sha1), Hasher::from_identifier(legacy));
+
+        let identifier = Hasher::Sha256.digest(b"");
+        assert_eq!("sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855", identifier);
+        assert_eq!(Some(Hasher::Sha256), Hasher::from_identifier(&identifier));
+        assert_eq!(Some(Hasher::Blake2s), Hasher::from_identifier(&Hasher::Blake2s.digest(b"")));
+
+        assert_eq!(None, Hasher::from_identifier("md5:d41d8cd98f00b204e9800998ecf8427e"));
+    }
+}
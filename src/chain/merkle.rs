@@ -0,0 +1,146 @@
+use ::chain::hasher::Hasher;
+use ::chain::transaction::Transaction;
+use bincode;
+
+/// Prefixes the hash of a leaf, so that a leaf can not be passed off as an inner node.
+const LEAF_PREFIX: u8 = 0;
+
+/// Prefixes the hash of an inner node.
+const NODE_PREFIX: u8 = 1;
+
+/// Returns the hash of the given transaction as leaf of a Merkle tree.
+pub fn hash_leaf(hasher: Hasher, transaction: &Transaction) -> String {
+    let mut bytes = vec![LEAF_PREFIX];
+    bytes.extend(bincode::serialize(transaction).unwrap());
+
+    hasher.digest(&bytes)
+}
+
+/// Returns the hash of the inner node with the given children.
+fn hash_node(hasher: Hasher, left: &str, right: &str) -> String {
+    let mut bytes = vec![NODE_PREFIX];
+    bytes.extend(left.as_bytes());
+    bytes.extend(right.as_bytes());
+
+    hasher.digest(&bytes)
+}
+
+/// Returns the next level of the tree above the given one.
+/// A node without a sibling is promoted to the next level as it is.
+fn next_level(hasher: Hasher, level: &[String]) -> Vec<String> {
+    level.chunks(2)
+        .map(|pair| match pair.len() {
+            2 => hash_node(hasher, &pair[0], &pair[1]),
+            _ => pair[0].clone()
+        })
+        .collect()
+}
+
+/// Returns the root of the Merkle tree over the given transactions, in their order in the block.
+pub fn merkle_root(hasher: Hasher, transactions: &[Transaction]) -> String {
+    let mut level: Vec<String> = transactions.iter().map(|transaction| hash_leaf(hasher, transaction)).collect();
+    if level.is_empty() {
+        return hasher.digest(&[]);
+    }
+
+    while level.len() > 1 {
+        level = next_level(hasher, &level);
+    }
+
+    level.remove(0)
+}
+
+/// The sibling of a node on the path from a leaf to the root of a Merkle tree.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub enum MerkleStep {
+    /// The sibling is the left child of the parent node.
+    Left(String),
+    /// The sibling is the right child of the parent node.
+    Right(String),
+}
+
+/// Proves that a leaf is part of a Merkle tree with a particular root,
+/// without knowing any of the other leaves.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct MerkleProof {
+    /// The siblings on the path from the leaf to the root, starting at the leaf.
+    pub path: Vec<MerkleStep>,
+}
+
+impl MerkleProof {
+    /// Create the proof for the transaction at the given index of the given transactions,
+    /// hashed with the given hash function.
+    /// Returns None if there is no transaction at the index.
+    pub fn new(hasher: Hasher, transactions: &[Transaction], index: usize) -> Option<MerkleProof> {
+        if index >= transactions.len() {
+            return None;
+        }
+
+        let mut level: Vec<String> = transactions.iter().map(|transaction| hash_leaf(hasher, transaction)).collect();
+        let mut index = index;
+        let mut path = vec![];
+
+        while level.len() > 1 {
+            if index % 2 == 1 {
+                path.push(MerkleStep::Left(level[index - 1].clone()));
+            } else if index + 1 < level.len() {
+                path.push(MerkleStep::Right(level[index + 1].clone()));
+            }
+
+            level = next_level(hasher, &level);
+            index /= 2;
+        }
+
+        Some(MerkleProof {
+            path
+        })
+    }
+
+    /// Returns true, if the given transaction is a leaf of the tree with the given root.
+    /// The tree is hashed with the hash function the root was derived with.
+    pub fn verify(&self, transaction: &Transaction, root: &str) -> bool {
+        let hasher = match Hasher::from_identifier(root) {
+            Some(hasher) => hasher,
+            None => {
+                return false;
+            }
+        };
+
+        let mut hash = hash_leaf(hasher, transaction);
+        for step in self.path.iter() {
+            hash = match *step {
+                MerkleStep::Left(ref sibling) => hash_node(hasher, sibling, &hash),
+                MerkleStep::Right(ref sibling) => hash_node(hasher, &hash, sibling),
+            };
+        }
+
+        hash == root
+    }
+}
+
+#[cfg(test)]
+mod merkle_test {
+
+    use super::*;
+
+    #[test]
+    fn test_merkle_proof() {
+        let transactions: Vec<Transaction> = (0..5)
+            .map(|_| Transaction::new_voting_opened(Hasher::default(), "genesis".to_string(), 0))
+            .enumerate()
+            .map(|(index, mut transaction)| {
+                transaction.identifier = format!("transaction-{}", index);
+                transaction
+            })
+            .collect();
+        let root = merkle_root(Hasher::default(), &transactions);
+
+        for (index, transaction) in transactions.iter().enumerate() {
+            let proof = MerkleProof::new(Hasher::default(), &transactions, index).unwrap();
+            assert!(proof.verify(transaction, &root));
+            assert!(!proof.verify(&transactions[(index + 1) % transactions.len()], &root));
+        }
+
+        assert_eq!(None, MerkleProof::new(Hasher::default(), &transactions, transactions.len()));
+    }
+}
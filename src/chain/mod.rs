@@ -1,9 +1,33 @@
+/// The hash functions from which the versioned identifiers of blocks and transactions are derived.
+pub mod hasher;
+
 /// A block of the blockchain.
 pub mod block;
 
+/// The height and the hash identifying a block.
+pub mod types;
+
+/// The epochs of the sealing schedule, i.e. rounds in which each sealer leads the sealing of one block.
+pub mod epoch;
+
+/// The sealers authorized at each block, evolving by the votes of the sealers on adding or removing sealers.
+pub mod sealer_set;
+
 /// The data structure of the blockchain.
 pub mod chain;
 
+/// Summaries of the state of the chain up to a finalized block, allowing nodes to join without all blocks.
+pub mod snapshot;
+
+/// The encodings of the blocks persisted on disk, versioned independently of the messages exchanged with other peers.
+pub mod storage;
+
+/// Storage backends persisting the blocks of the chain across restarts.
+pub mod block_store;
+
+/// A write-ahead log of blocks and buffered transactions, replayed after a crash.
+pub mod write_ahead_log;
+
 /// Visitors of the chain which can be used in combination with a chain walker.
 pub mod chain_visitor;
 
@@ -15,3 +39,9 @@ pub mod chain_walker;
 
 /// A transaction of the blockchain.
 pub mod transaction;
+
+/// Merkle trees over the transactions of a block, proving the inclusion of a single transaction.
+pub mod merkle;
+
+/// A registry of transaction kinds, implementing the behaviour specific to each transaction type.
+pub mod transaction_registry;
\ No newline at end of file
@@ -0,0 +1,284 @@
+use ::chain::block::Block;
+use ::chain::epoch::EpochSchedule;
+use ::chain::transaction::{SealerVote, TransactionType};
+use ::chain::types::Height;
+use ::p2p::peers;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+
+/// The amount of blocks after which pending votes on sealers are discarded,
+/// if the genesis configuration does not declare it. Equals the epoch length of Clique.
+pub const DEFAULT_VOTE_EPOCH_LENGTH: usize = 30000;
+
+/// A vote on a sealer which did not reach a majority yet.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct PendingVote {
+    /// The sealer which cast the vote.
+    pub voter: SocketAddr,
+    /// The address of the sealer to add or to remove.
+    pub candidate: SocketAddr,
+    /// True to add the candidate, false to remove it.
+    pub authorize: bool,
+    /// The public key of the candidate, if it is to be added.
+    pub public_key: Option<String>,
+}
+
+/// The sealers authorized to seal the children of a block, along with the
+/// votes on adding or removing sealers which are pending on the branch up to it.
+///
+/// Starting with the sealers of the genesis configuration, the set evolves by the
+/// sealer votes of each block, in the order of the blocks. Once more than half of
+/// the sealers voted to add the same candidate or to remove the same sealer, the
+/// change takes effect for the following blocks and all votes on it are discarded.
+/// Votes not reaching a majority are discarded at the start of each vote epoch,
+/// i.e. at every height divisible by the vote epoch length.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct SealerSet {
+    /// The sealers in the order of the genesis configuration,
+    /// followed by the added sealers in the order they were added.
+    sealers: Vec<SocketAddr>,
+    /// The public key of each sealer. If not empty, blocks must be signed by their sealer.
+    sealer_keys: BTreeMap<SocketAddr, String>,
+    /// The votes which did not reach a majority yet, in the order they were cast.
+    pending_votes: Vec<PendingVote>,
+    /// The amount of blocks after which pending votes are discarded.
+    vote_epoch_length: usize,
+}
+
+impl SealerSet {
+    /// Create the set of the given sealers without any pending votes.
+    ///
+    /// - `sealers` The sealers in the order of the genesis configuration.
+    /// - `sealer_keys` The public key of each sealer, or none if blocks are not signed.
+    /// - `vote_epoch_length` The amount of blocks after which pending votes are discarded.
+    pub fn new(sealers: Vec<SocketAddr>, sealer_keys: BTreeMap<SocketAddr, String>, vote_epoch_length: usize) -> SealerSet {
+        SealerSet {
+            sealers,
+            sealer_keys,
+            pending_votes: vec![],
+            vote_epoch_length,
+        }
+    }
+
+    /// Restore the set of the given sealers along with the votes pending on them, e.g. from a `StateSnapshot`.
+    ///
+    /// - `sealers` The authorized sealers, in the order in which they lead the sealing.
+    /// - `sealer_keys` The public key of each sealer, or none if blocks are not signed.
+    /// - `pending_votes` The votes which did not reach a majority yet, in the order they were cast.
+    /// - `vote_epoch_length` The amount of blocks after which pending votes are discarded.
+    pub fn restore(sealers: Vec<SocketAddr>, sealer_keys: BTreeMap<SocketAddr, String>, pending_votes: Vec<PendingVote>, vote_epoch_length: usize) -> SealerSet {
+        SealerSet {
+            sealers,
+            sealer_keys,
+            pending_votes,
+            vote_epoch_length,
+        }
+    }
+
+    /// Returns the amount of blocks after which pending votes are discarded.
+    pub fn get_vote_epoch_length(&self) -> usize {
+        self.vote_epoch_length
+    }
+
+    /// Returns the authorized sealers, in the order in which they lead the sealing.
+    pub fn get_sealers(&self) -> &[SocketAddr] {
+        &self.sealers
+    }
+
+    /// Returns the public key of each sealer.
+    pub fn get_sealer_keys(&self) -> &BTreeMap<SocketAddr, String> {
+        &self.sealer_keys
+    }
+
+    /// Returns the votes which did not reach a majority yet, in the order they were cast.
+    pub fn get_pending_votes(&self) -> &[PendingVote] {
+        &self.pending_votes
+    }
+
+    /// Returns true, if blocks must be signed by their sealer.
+    pub fn requires_signatures(&self) -> bool {
+        !self.sealer_keys.is_empty()
+    }
+
+    /// Returns the index of the given sealer, or None if it is not authorized.
+    pub fn index_of(&self, sealer: &SocketAddr) -> Option<usize> {
+        self.sealers.iter().position(|known_sealer| peers::is_same_peer(known_sealer, sealer))
+    }
+
+    /// Returns the schedule mapping heights to epochs and their leaders among these sealers.
+    pub fn get_epoch_schedule(&self) -> EpochSchedule {
+        EpochSchedule::new(self.sealers.clone())
+    }
+
+    /// Returns the distance of the sealer with the given index to the leader of the given height,
+    /// in the order of the sealers, wrapping around to the first sealer after the last one.
+    /// The leader itself has distance zero, the co-leaders follow it.
+    pub fn get_offset_to_leader(&self, sealer_index: usize, height: Height) -> usize {
+        let leader_index = self.get_epoch_schedule().get_leader_index(height).unwrap_or(0);
+
+        (sealer_index + self.sealers.len() - leader_index) % self.sealers.len()
+    }
+
+    /// Check whether the given vote would change anything if it reached a majority.
+    ///
+    /// Returns the reason if the vote is not cast by a sealer, it adds a sealer or removes a non-sealer,
+    /// it would remove the last sealer, or it lacks the public key of the candidate although required.
+    pub fn check_vote(&self, vote: &SealerVote) -> Result<(), String> {
+        if self.index_of(&vote.voter).is_none() {
+            return Err(format!("{} is not a sealer", vote.voter));
+        }
+
+        let is_sealer = self.index_of(&vote.candidate).is_some();
+        if vote.authorize && is_sealer {
+            return Err(format!("{} is already a sealer", vote.candidate));
+        }
+        if !vote.authorize && !is_sealer {
+            return Err(format!("{} is not a sealer", vote.candidate));
+        }
+        if !vote.authorize && self.sealers.len() == 1 {
+            return Err(format!("{} is the last sealer", vote.candidate));
+        }
+        if vote.authorize && self.requires_signatures() && vote.public_key.is_none() {
+            return Err(format!("Blocks must be signed, hence the public key of {} is required", vote.candidate));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the set following the given block at the given height, whose parent this set follows,
+    /// or None if the block does not change the sealers or the pending votes.
+    pub fn apply(&self, height: Height, block: &Block) -> Option<SealerSet> {
+        let is_vote_epoch_start = height.as_usize().is_multiple_of(self.vote_epoch_length);
+        let votes: Vec<&SealerVote> = block.data.transactions.iter()
+            .filter(|transaction| TransactionType::SealerVote == transaction.trx_type)
+            .filter_map(|transaction| transaction.sealer_vote.as_ref())
+            .collect();
+
+        if votes.is_empty() && (!is_vote_epoch_start || self.pending_votes.is_empty()) {
+            return None;
+        }
+
+        let mut next_sealer_set = self.clone();
+        if is_vote_epoch_start {
+            debug!("Discarding {} pending votes on sealers at the start of the vote epoch at height {}", next_sealer_set.pending_votes.len(), height);
+            next_sealer_set.pending_votes.clear();
+        }
+
+        for vote in votes {
+            // only the sealer of the block vouches for the votes included in it
+            match block.data.sealer {
+                Some(ref sealer) if peers::is_same_peer(sealer, &vote.voter) => next_sealer_set.cast(vote),
+                _ => {
+                    debug!("Ignoring vote of {} on {} in block {} sealed by {:?}", vote.voter, vote.candidate, block.identifier, block.data.sealer);
+                }
+            }
+        }
+
+        Some(next_sealer_set)
+    }
+
+    /// Count the given vote, adding or removing its candidate if it reaches a majority.
+    fn cast(&mut self, vote: &SealerVote) {
+        if let Err(reason) = self.check_vote(vote) {
+            debug!("Ignoring vote of {} on {}: {}", vote.voter, vote.candidate, reason);
+            return;
+        }
+
+        // a later vote of a sealer on the same candidate replaces the earlier one
+        self.pending_votes.retain(|pending_vote| {
+            !(peers::is_same_peer(&pending_vote.voter, &vote.voter) && peers::is_same_peer(&pending_vote.candidate, &vote.candidate))
+        });
+        self.pending_votes.push(PendingVote {
+            voter: vote.voter,
+            candidate: vote.candidate,
+            authorize: vote.authorize,
+            public_key: vote.public_key.clone(),
+        });
+
+        // votes to add a candidate only agree if they declare the same public key
+        let amount_votes = self.pending_votes.iter()
+            .filter(|pending_vote| peers::is_same_peer(&pending_vote.candidate, &vote.candidate))
+            .filter(|pending_vote| pending_vote.authorize == vote.authorize && pending_vote.public_key == vote.public_key)
+            .count();
+        if amount_votes <= self.sealers.len() / 2 {
+            return;
+        }
+
+        if vote.authorize {
+            info!("Sealers voted to add {} as sealer", vote.candidate);
+            self.sealers.push(vote.candidate);
+            if let Some(ref public_key) = vote.public_key {
+                self.sealer_keys.insert(vote.candidate, public_key.clone());
+            }
+        } else {
+            info!("Sealers voted to remove sealer {}", vote.candidate);
+            self.sealers.retain(|sealer| !peers::is_same_peer(sealer, &vote.candidate));
+            self.sealer_keys.retain(|sealer, _| !peers::is_same_peer(sealer, &vote.candidate));
+            // the removed sealer has no say anymore
+            self.pending_votes.retain(|pending_vote| !peers::is_same_peer(&pending_vote.voter, &vote.candidate));
+        }
+
+        self.pending_votes.retain(|pending_vote| !peers::is_same_peer(&pending_vote.candidate, &vote.candidate));
+    }
+}
+
+#[cfg(test)]
+mod sealer_set_test {
+
+    use super::*;
+    use ::chain::hasher::Hasher;
+    use ::chain::transaction::Transaction;
+    use ::chain::types::BlockHash;
+
+    fn new_block(sealer: SocketAddr, candidate: SocketAddr, authorize: bool) -> Block {
+        let vote = SealerVote {
+            voter: sealer,
+            candidate,
+            authorize,
+            public_key: None,
+            cast_at: 0,
+        };
+
+        Block::new(Hasher::default(), BlockHash::from("parent"), Some(sealer), vec![Transaction::new_sealer_vote(Hasher::default(), vote)])
+    }
+
+    #[test]
+    fn test_apply() {
+        let sealers: Vec<SocketAddr> = vec!["127.0.0.1:9000".parse().unwrap(), "127.0.0.1:9001".parse().unwrap(), "127.0.0.1:9002".parse().unwrap()];
+        let candidate: SocketAddr = "127.0.0.1:9003".parse().unwrap();
+        let sealer_set = SealerSet::new(sealers.clone(), BTreeMap::new(), 10);
+
+        // blocks without votes change nothing
+        let empty_block = Block::new(Hasher::default(), BlockHash::from("parent"), Some(sealers[0]), vec![]);
+        assert_eq!(None, sealer_set.apply(Height::new(1), &empty_block));
+
+        // a single vote is pending, the second one forms a majority
+        let sealer_set = sealer_set.apply(Height::new(1), &new_block(sealers[0], candidate, true)).unwrap();
+        assert_eq!(1, sealer_set.get_pending_votes().len());
+        assert_eq!(None, sealer_set.index_of(&candidate));
+        let sealer_set = sealer_set.apply(Height::new(2), &new_block(sealers[1], candidate, true)).unwrap();
+        assert_eq!(Some(3), sealer_set.index_of(&candidate));
+        assert!(sealer_set.get_pending_votes().is_empty());
+
+        // votes included by another sealer than the voter do not count
+        let mut foreign_block = new_block(sealers[0], sealers[2], false);
+        foreign_block.data.sealer = Some(sealers[1]);
+        assert!(sealer_set.apply(Height::new(3), &foreign_block).unwrap().get_pending_votes().is_empty());
+
+        // pending votes are discarded at the start of the next vote epoch
+        let sealer_set = sealer_set.apply(Height::new(9), &new_block(sealers[0], sealers[2], false)).unwrap();
+        let sealer_set = sealer_set.apply(Height::new(10), &empty_block).unwrap();
+        assert!(sealer_set.get_pending_votes().is_empty());
+
+        // removing a sealer requires three of four votes
+        let mut removed_sealer_set = sealer_set.clone();
+        for (height, voter) in vec![sealers[0], sealers[1], candidate].into_iter().enumerate() {
+            removed_sealer_set = removed_sealer_set.apply(Height::new(11 + height), &new_block(voter, sealers[2], false)).unwrap();
+        }
+        assert_eq!(&[sealers[0], sealers[1], candidate], removed_sealer_set.get_sealers());
+
+        // votes without effect are rejected
+        let vote = SealerVote { voter: sealers[0], candidate: sealers[1], authorize: true, public_key: None, cast_at: 0 };
+        assert!(sealer_set.check_vote(&vote).is_err());
+    }
+}
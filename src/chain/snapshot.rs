@@ -0,0 +1,212 @@
+use ::chain::block::Block;
+use ::chain::chain_visitor::ElectionPhase;
+use ::chain::sealer_set::{PendingVote, SealerSet};
+use ::chain::transaction::{FinalTally, VoterRegistration};
+use ::chain::types::Height;
+use ::mac::{decode_hex, encode_hex};
+use crypto_rs::arithmetic::mod_int::ModInt;
+use crypto_rs::el_gamal::ciphertext::CipherText;
+use std::collections::{BTreeMap, HashSet};
+use std::net::SocketAddr;
+
+/// A summary of the canonical chain up to a finalized block, from which a node
+/// continues the chain without receiving the blocks up to it.
+///
+/// A chain created from a snapshot uses its block as root in place of the genesis block.
+/// Walking the canonical chain ends with the snapshot, which then provides the sealers as well as
+/// the phase and the running tally of the election. Snapshots are not signed, hence a node only
+/// adopts a snapshot served identically by a majority of its peers.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct StateSnapshot {
+    /// The hash of the genesis configuration of the chain.
+    pub genesis_configuration_hash: String,
+    /// The height of the block.
+    pub height: Height,
+    /// The sum of the difficulties of the block and all its ancestors.
+    pub total_difficulty: u64,
+    /// The finalized block the snapshot was taken at.
+    pub block: Block,
+    /// The sealers authorized to seal the children of the block, in the order in which they lead the sealing.
+    pub sealers: Vec<SocketAddr>,
+    /// The public key of each sealer, empty if blocks are not signed.
+    pub sealer_keys: BTreeMap<SocketAddr, String>,
+    /// The votes on sealers pending at the block, in the order they were cast.
+    pub pending_votes: Vec<PendingVote>,
+    /// The phase of the election at the block.
+    pub phase: ElectionPhase,
+    /// Whether the block or any of its ancestors includes a transaction opening the voting.
+    pub is_voting_opened: bool,
+    /// The homomorphic sum of the votes counted up to the block, or None if none are counted.
+    /// Unlike the sum of a tally, it does not include an encryption of zero, hence all nodes derive the same snapshot.
+    pub sum_cipher_text: Option<CipherText>,
+    /// The amount of votes counted up to the block.
+    pub total_votes: usize,
+    /// The indices of the voters whose votes are counted, see `encode_voters`.
+    pub voters: String,
+    /// The votes counted up to the block on each question declared in addition to the one of
+    /// `public_uciv.json`, whose votes are summarized by the fields above, by its identifier.
+    /// Omitted if empty, so that snapshots of elections with a single question do not change.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub questions: BTreeMap<String, QuestionSnapshot>,
+    /// The commitments of each dealer of the election key, if the sealers generated it jointly.
+    /// Omitted if empty, so that snapshots of elections with a pre-generated key do not change.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dkg_commitments: BTreeMap<SocketAddr, Vec<ModInt>>,
+    /// The election key committed up to the block, if the sealers generated it jointly.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub election_key: Option<ModInt>,
+    /// The final tally of each question committed up to the block, ordered by the identifier of their question.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub final_tallies: Vec<FinalTally>,
+    /// The latest registration or revocation of each voter registered on the chain up to the block,
+    /// ordered by the identifier of their question and the index of the voter.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub voter_registrations: Vec<VoterRegistration>,
+}
+
+/// A summary of the votes on a single question counted up to the block of a `StateSnapshot`.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct QuestionSnapshot {
+    /// The homomorphic sum of the votes counted up to the block, or None if none are counted.
+    pub sum_cipher_text: Option<CipherText>,
+    /// The amount of votes counted up to the block.
+    pub total_votes: usize,
+    /// The indices of the voters whose votes are counted, see `encode_voters`.
+    pub voters: String,
+}
+
+impl StateSnapshot {
+    /// Returns the indices of the voters whose votes are counted up to the block,
+    /// or an error if they are not encoded correctly.
+    pub fn get_voters(&self) -> Result<HashSet<usize>, String> {
+        decode_voters(&self.voters)
+    }
+
+    /// Returns the indices of the voters whose votes on the question with the given identifier are counted
+    /// up to the block, where None identifies the question whose voters are declared in `public_uciv.json`.
+    /// Returns an error if they are not encoded correctly.
+    pub fn get_question_voters(&self, question_id: &Option<String>) -> Result<HashSet<usize>, String> {
+        match *question_id {
+            Some(ref question_id) => match self.questions.get(question_id) {
+                Some(question) => decode_voters(&question.voters),
+                None => Ok(HashSet::new())
+            },
+            None => self.get_voters()
+        }
+    }
+
+    /// Returns the votes counted up to the block on each question, where None identifies
+    /// the question whose voters are declared in `public_uciv.json`.
+    pub fn get_question_snapshots(&self) -> Vec<(Option<String>, QuestionSnapshot)> {
+        let default_question = QuestionSnapshot {
+            sum_cipher_text: self.sum_cipher_text.clone(),
+            total_votes: self.total_votes,
+            voters: self.voters.clone(),
+        };
+
+        Some((None, default_question)).into_iter()
+            .chain(self.questions.iter().map(|(question_id, question)| (Some(question_id.clone()), question.clone())))
+            .collect()
+    }
+
+    /// Returns the sealers authorized to seal the children of the block.
+    ///
+    /// - `vote_epoch_length` The amount of blocks after which pending votes are discarded, as in the genesis configuration.
+    pub fn get_sealer_set(&self, vote_epoch_length: usize) -> SealerSet {
+        SealerSet::restore(self.sealers.clone(), self.sealer_keys.clone(), self.pending_votes.clone(), vote_epoch_length)
+    }
+}
+
+/// Encode the given voter indices as hex encoded bitmap, in which
+/// bit `index % 8` of byte `index / 8` is set for each index.
+/// Requires one byte per eight voters instead of a list of numbers.
+pub fn encode_voters(voters: &HashSet<usize>) -> String {
+    let mut bitmap = vec![0u8; voters.iter().max().map_or(0, |max_index| max_index / 8 + 1)];
+    for index in voters.iter() {
+        bitmap[index / 8] |= 1 << (index % 8);
+    }
+
+    encode_hex(&bitmap)
+}
+
+/// Decode the voter indices of the given bitmap, see `encode_voters`.
+/// Returns an error if it is not valid hex.
+pub fn decode_voters(voters: &str) -> Result<HashSet<usize>, String> {
+    let bitmap = match decode_hex(voters) {
+        Some(bitmap) => bitmap,
+        None => {
+            return Err(format!("Voters {:?} are not hex encoded", voters));
+        }
+    };
+
+    Ok((0..bitmap.len() * 8)
+        .filter(|index| bitmap[index / 8] & (1 << (index % 8)) != 0)
+        .collect())
+}
+
+#[cfg(test)]
+mod snapshot_test {
+
+    use super::*;
+    use ::chain::chain::Chain;
+    use ::chain::hasher::Hasher;
+    use ::chain::sealer_set::DEFAULT_VOTE_EPOCH_LENGTH;
+
+    #[test]
+    fn test_encode_voters() {
+        let voters: HashSet<usize> = vec![0, 3, 8, 17].into_iter().collect();
+        assert_eq!("090102", encode_voters(&voters));
+        assert_eq!(Ok(voters), decode_voters("090102"));
+        assert_eq!("", encode_voters(&HashSet::new()));
+        assert!(decode_voters("0g").is_err());
+    }
+
+    #[test]
+    fn test_chain_from_snapshot() {
+        let sealer: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let root = Block::new(Hasher::default(), "parent".into(), Some(sealer), vec![]);
+        let snapshot = StateSnapshot {
+            genesis_configuration_hash: "genesis".to_string(),
+            height: Height::new(10),
+            total_difficulty: 20,
+            block: root.clone(),
+            sealers: vec![sealer],
+            sealer_keys: BTreeMap::new(),
+            pending_votes: vec![],
+            phase: ElectionPhase::Opened,
+            is_voting_opened: true,
+            sum_cipher_text: None,
+            total_votes: 0,
+            voters: String::new(),
+            questions: BTreeMap::new(),
+            dkg_commitments: BTreeMap::new(),
+            election_key: None,
+            final_tallies: vec![],
+            voter_registrations: vec![],
+        };
+
+        let mut chain = Chain::from_state_snapshot(snapshot.clone());
+        assert_eq!(Ok(()), chain.set_sealer_set(Some(SealerSet::new(vec![], BTreeMap::new(), DEFAULT_VOTE_EPOCH_LENGTH))));
+        assert_eq!(Some(&snapshot), chain.get_state_snapshot());
+        assert_eq!(Height::new(10), chain.root_height());
+        assert_eq!(vec![sealer], chain.get_sealer_set(&root.identifier).unwrap().unwrap().get_sealers().to_vec());
+
+        // the only sealer leads every height, hence its blocks are sealed in turn
+        let block = Block::new(Hasher::default(), root.identifier.clone(), Some(sealer), vec![]);
+        chain.add_block(block.clone());
+        let head = chain.head().unwrap();
+        assert_eq!(Height::new(11), head.height);
+        assert_eq!(22, head.total_difficulty);
+        assert_eq!(Ok(Some(&block)), chain.block_by_number(Height::new(11)));
+        assert_eq!(Ok(Some(&root)), chain.block_by_number(Height::new(10)));
+        assert_eq!(Ok(None), chain.block_by_number(Height::new(9)));
+        assert_eq!(Ok(vec![&block]), chain.blocks_at_height(Height::new(11)));
+        assert_eq!(Height::new(10), chain.finalized_head().unwrap().0);
+
+        // the snapshot survives serialization and reindexing
+        let mut copy: Chain = ::serde_json::from_str(&::serde_json::to_string(&chain).unwrap()).unwrap();
+        assert_eq!(Ok(()), copy.set_sealer_set(Some(SealerSet::new(vec![], BTreeMap::new(), DEFAULT_VOTE_EPOCH_LENGTH))));
+        assert_eq!(Ok(head), copy.head());
+        assert_eq!(Ok(Some(Height::new(11))), copy.height_of(&block.identifier));
+    }
+}
@@ -0,0 +1,910 @@
+use ::chain::block::{Block, BlockContent};
+use ::chain::chain::Chain;
+use ::chain::chain_visitor::ElectionPhase;
+use ::chain::sealer_set::PendingVote;
+use ::chain::snapshot::{QuestionSnapshot, StateSnapshot};
+use ::chain::transaction::{DecryptionShare, DkgCommitment, FinalTally, PhaseChange, SealerVote, Transaction, TransactionData, TransactionMetadata, TransactionType, VoterRegistration};
+use ::chain::types::{BlockHash, Height};
+use ::credential::EligibilityCredential;
+use ::threshold::DecryptionProof;
+use bincode;
+use crypto_rs::arithmetic::mod_int::ModInt;
+use crypto_rs::cai::uciv::{CaiProof, ImageSet};
+use crypto_rs::el_gamal::ciphertext::CipherText;
+use crypto_rs::el_gamal::membership_proof::MembershipProof;
+use serde_json;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+/// The version of the layout of the stored records, declared in the header of each stored file.
+///
+/// The stored types below mirror the types exchanged with other peers, but change independently
+/// of them: a change of the wire format only requires adapting the conversions, while a change of
+/// the stored types increments this version and converts records of the previous layout when loading them.
+pub const STORAGE_VERSION: u32 = 1;
+
+/// The name of the file in the data directory holding the blocks encoded with `JsonStorageCodec`.
+pub const JSON_FILE_NAME: &str = "blocks.jsonl";
+
+/// The name of the file in the data directory holding the blocks encoded with `BincodeStorageCodec`.
+pub const BINCODE_FILE_NAME: &str = "blocks.bin";
+
+/// The bytes a file of blocks encoded with bincode starts with, followed by the storage version.
+const BINCODE_MAGIC: &[u8; 4] = b"NRSB";
+
+/// The encoding of the blocks persisted in the data directory.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum StorageEncoding {
+    /// One JSON document per line, as written by earlier versions of this node, see `JsonStorageCodec`.
+    Json,
+    /// Length-prefixed bincode records, see `BincodeStorageCodec`.
+    Bincode,
+}
+
+impl StorageEncoding {
+    /// Returns all available encodings.
+    pub fn all() -> Vec<StorageEncoding> {
+        vec![StorageEncoding::Json, StorageEncoding::Bincode]
+    }
+}
+
+impl FromStr for StorageEncoding {
+    type Err = String;
+
+    fn from_str(encoding: &str) -> Result<Self, Self::Err> {
+        match encoding {
+            "json" => Ok(StorageEncoding::Json),
+            "bincode" => Ok(StorageEncoding::Bincode),
+            other => Err(format!("Unknown storage encoding {:?}", other))
+        }
+    }
+}
+
+/// Returns the codec of the given encoding.
+pub fn create(encoding: StorageEncoding) -> Box<StorageCodec> {
+    match encoding {
+        StorageEncoding::Json => Box::new(JsonStorageCodec),
+        StorageEncoding::Bincode => Box::new(BincodeStorageCodec)
+    }
+}
+
+/// Encodes the records of a stored chain, i.e. its root followed by its blocks, in a file.
+///
+/// Each encoded record is framed, so that a record only partially written due to a crash
+/// is recognized and discarded.
+pub trait StorageCodec: Send + Sync {
+    /// The name of the file in the data directory holding the records.
+    fn file_name(&self) -> &'static str;
+
+    /// The bytes the file starts with, declaring the storage version.
+    fn header(&self) -> Vec<u8>;
+
+    /// Encode the given root as framed record.
+    fn encode_root(&self, root: &StoredRoot) -> Vec<u8>;
+
+    /// Encode the given block as framed record.
+    fn encode_block(&self, block: &StoredBlock) -> Vec<u8>;
+
+    /// Split the given contents of a file into the complete records following the header.
+    /// Returns the storage version declared by the header and the records, along with the length
+    /// of the contents up to the end of the last complete record, or an error if the header is invalid
+    /// or declares a version newer than `STORAGE_VERSION`.
+    fn split_records<'a>(&self, contents: &'a [u8]) -> Result<(u32, Vec<&'a [u8]>, usize), String>;
+
+    /// Decode the root of the chain from the given record, stored in the given version.
+    fn decode_root(&self, record: &[u8], storage_version: u32) -> Result<StoredRoot, String>;
+
+    /// Decode a block from the given record, stored in the given version.
+    fn decode_block(&self, record: &[u8], storage_version: u32) -> Result<StoredBlock, String>;
+}
+
+/// The header line of a file encoded with `JsonStorageCodec`.
+#[derive(Serialize, Deserialize)]
+struct JsonHeader {
+    storage_version: u32,
+}
+
+/// The form in which `JsonStorageCodec` stores the root, so that
+/// a chain starting with the genesis block starts with its plain hash.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum UntaggedRoot {
+    GenesisConfigurationHash(String),
+    StateSnapshot(Box<StoredStateSnapshot>),
+}
+
+/// Stores one JSON document per line in `blocks.jsonl`, preceded by a line declaring the storage version.
+/// Files of earlier versions of this node lack this line and are read as version 1.
+/// Fields added by later versions are optional, hence records of all versions are decoded alike.
+///
+/// Readable with text tools, but larger and slower to load than `BincodeStorageCodec`.
+pub struct JsonStorageCodec;
+
+impl StorageCodec for JsonStorageCodec {
+    fn file_name(&self) -> &'static str {
+        JSON_FILE_NAME
+    }
+
+    fn header(&self) -> Vec<u8> {
+        let mut header = serde_json::to_vec(&JsonHeader { storage_version: STORAGE_VERSION }).unwrap();
+        header.push(b'\n');
+
+        header
+    }
+
+    fn encode_root(&self, root: &StoredRoot) -> Vec<u8> {
+        let untagged_root = match root.clone() {
+            StoredRoot::GenesisConfigurationHash(genesis_configuration_hash) => UntaggedRoot::GenesisConfigurationHash(genesis_configuration_hash),
+            StoredRoot::StateSnapshot(state_snapshot) => UntaggedRoot::StateSnapshot(state_snapshot)
+        };
+
+        let mut line = serde_json::to_vec(&untagged_root).unwrap();
+        line.push(b'\n');
+
+        line
+    }
+
+    fn encode_block(&self, block: &StoredBlock) -> Vec<u8> {
+        let mut line = serde_json::to_vec(block).unwrap();
+        line.push(b'\n');
+
+        line
+    }
+
+    fn split_records<'a>(&self, contents: &'a [u8]) -> Result<(u32, Vec<&'a [u8]>, usize), String> {
+        // a line without its terminating newline was only partially written
+        let complete_length = contents.iter().rposition(|byte| *byte == b'\n').map_or(0, |index| index + 1);
+        let mut records: Vec<&[u8]> = contents[..complete_length].split(|byte| *byte == b'\n').collect();
+        records.pop();
+
+        let header = records.first().and_then(|record| serde_json::from_slice::<JsonHeader>(record).ok());
+        let storage_version = match header {
+            Some(header) => {
+                records.remove(0);
+                header.storage_version
+            }
+            None => 1
+        };
+
+        if storage_version > STORAGE_VERSION {
+            return Err(format!("Blocks are stored in version {}, but only versions up to {} are supported", storage_version, STORAGE_VERSION));
+        }
+
+        Ok((storage_version, records, complete_length))
+    }
+
+    fn decode_root(&self, record: &[u8], _storage_version: u32) -> Result<StoredRoot, String> {
+        match serde_json::from_slice(record) {
+            Ok(UntaggedRoot::GenesisConfigurationHash(genesis_configuration_hash)) => Ok(StoredRoot::GenesisConfigurationHash(genesis_configuration_hash)),
+            Ok(UntaggedRoot::StateSnapshot(state_snapshot)) => Ok(StoredRoot::StateSnapshot(state_snapshot)),
+            Err(e) => Err(format!("{:?}", e))
+        }
+    }
+
+    fn decode_block(&self, record: &[u8], _storage_version: u32) -> Result<StoredBlock, String> {
+        match serde_json::from_slice(record) {
+            Ok(block) => Ok(block),
+            Err(e) => Err(format!("{:?}", e))
+        }
+    }
+}
+
+/// Stores the records encoded with bincode in `blocks.bin`, each prefixed by its length
+/// as 32 bit little endian integer. The file starts with `NRSB` followed by the storage version,
+/// encoded the same way.
+///
+/// Considerably smaller and faster to load than `JsonStorageCodec`, as the
+/// cryptographic proofs of votes are not encoded as decimal numbers.
+pub struct BincodeStorageCodec;
+
+impl BincodeStorageCodec {
+    fn encode_length(length: usize) -> Vec<u8> {
+        let length = length as u32;
+        vec![length as u8, (length >> 8) as u8, (length >> 16) as u8, (length >> 24) as u8]
+    }
+
+    fn decode_length(bytes: &[u8]) -> usize {
+        bytes.iter().rev().fold(0, |length, byte| (length << 8) | *byte as usize)
+    }
+
+    fn frame(payload: Vec<u8>) -> Vec<u8> {
+        let mut record = BincodeStorageCodec::encode_length(payload.len());
+        record.extend(payload);
+
+        record
+    }
+}
+
+impl StorageCodec for BincodeStorageCodec {
+    fn file_name(&self) -> &'static str {
+        BINCODE_FILE_NAME
+    }
+
+    fn header(&self) -> Vec<u8> {
+        let mut header = BINCODE_MAGIC.to_vec();
+        header.extend(BincodeStorageCodec::encode_length(STORAGE_VERSION as usize));
+
+        header
+    }
+
+    fn encode_root(&self, root: &StoredRoot) -> Vec<u8> {
+        BincodeStorageCodec::frame(bincode::serialize(root).unwrap())
+    }
+
+    fn encode_block(&self, block: &StoredBlock) -> Vec<u8> {
+        BincodeStorageCodec::frame(bincode::serialize(block).unwrap())
+    }
+
+    fn split_records<'a>(&self, contents: &'a [u8]) -> Result<(u32, Vec<&'a [u8]>, usize), String> {
+        if contents.is_empty() {
+            return Ok((STORAGE_VERSION, vec![], 0));
+        }
+
+        let header_length = BINCODE_MAGIC.len() + 4;
+        if contents.len() < header_length || &contents[..BINCODE_MAGIC.len()] != BINCODE_MAGIC {
+            return Err("Blocks are not encoded with bincode".to_string());
+        }
+
+        let storage_version = BincodeStorageCodec::decode_length(&contents[BINCODE_MAGIC.len()..header_length]) as u32;
+        if storage_version > STORAGE_VERSION {
+            return Err(format!("Blocks are stored in version {}, but only versions up to {} are supported", storage_version, STORAGE_VERSION));
+        }
+
+        let mut records = vec![];
+        let mut offset = header_length;
+        while offset + 4 <= contents.len() {
+            let end = offset + 4 + BincodeStorageCodec::decode_length(&contents[offset..offset + 4]);
+            if end > contents.len() {
+                break;
+            }
+
+            records.push(&contents[offset + 4..end]);
+            offset = end;
+        }
+
+        Ok((storage_version, records, offset))
+    }
+
+    fn decode_root(&self, record: &[u8], _storage_version: u32) -> Result<StoredRoot, String> {
+        match bincode::deserialize(record) {
+            Ok(root) => Ok(root),
+            Err(e) => Err(format!("{:?}", e))
+        }
+    }
+
+    fn decode_block(&self, record: &[u8], _storage_version: u32) -> Result<StoredBlock, String> {
+        match bincode::deserialize(record) {
+            Ok(block) => Ok(block),
+            Err(e) => Err(format!("{:?}", e))
+        }
+    }
+}
+
+/// What precedes the blocks of a stored chain.
+#[derive(Serialize, Deserialize, Clone)]
+pub enum StoredRoot {
+    /// The hash of the genesis configuration, if the chain starts with the genesis block.
+    GenesisConfigurationHash(String),
+    /// The snapshot the chain starts with, containing the hash of the genesis configuration.
+    StateSnapshot(Box<StoredStateSnapshot>),
+}
+
+impl StoredRoot {
+    /// Returns the root of the given chain.
+    pub fn of(chain: &Chain) -> StoredRoot {
+        match chain.get_state_snapshot() {
+            Some(state_snapshot) => StoredRoot::StateSnapshot(Box::new(StoredStateSnapshot::from(state_snapshot))),
+            None => StoredRoot::GenesisConfigurationHash(chain.genesis_configuration_hash.clone())
+        }
+    }
+
+    /// Recreate the chain from the given blocks, ordered as returned by `Chain::get_ordered_blocks`.
+    pub fn restore(self, blocks: Vec<Block>) -> Result<Option<Chain>, String> {
+        let chain = match self {
+            StoredRoot::GenesisConfigurationHash(genesis_configuration_hash) => Chain::from_ordered_blocks(genesis_configuration_hash, blocks),
+            StoredRoot::StateSnapshot(state_snapshot) => Chain::from_state_snapshot_and_blocks(StateSnapshot::from(*state_snapshot), blocks).map(Some)
+        };
+
+        match chain {
+            Ok(chain) => Ok(chain),
+            Err(e) => Err(format!("{:?}", e))
+        }
+    }
+}
+
+/// The stored form of a `Block`, with its content inlined.
+///
+/// Absent optional fields default to None, so that blocks stored by earlier versions
+/// of this node, which omitted them, are read by `JsonStorageCodec`.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct StoredBlock {
+    pub identifier: BlockHash,
+    pub data: StoredBlockContent,
+    #[serde(default)]
+    pub signature: Option<String>,
+}
+
+/// The stored form of a `BlockContent`.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct StoredBlockContent {
+    pub parent: BlockHash,
+    pub timestamp: u64,
+    pub sealer: Option<SocketAddr>,
+    pub transactions: Vec<StoredTransaction>,
+    #[serde(default)]
+    pub transactions_root: Option<String>,
+}
+
+/// The stored form of a `Transaction`.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct StoredTransaction {
+    pub identifier: String,
+    pub trx_type: StoredTransactionType,
+    pub data: Option<StoredTransactionData>,
+    #[serde(default)]
+    pub metadata: Option<StoredTransactionMetadata>,
+    #[serde(default)]
+    pub sealer_vote: Option<StoredSealerVote>,
+    #[serde(default)]
+    pub phase_change: Option<StoredPhaseChange>,
+    #[serde(default)]
+    pub decryption_share: Option<StoredDecryptionShare>,
+    #[serde(default)]
+    pub dkg_commitment: Option<StoredDkgCommitment>,
+    #[serde(default)]
+    pub election_key: Option<ModInt>,
+    #[serde(default)]
+    pub final_tally: Option<StoredFinalTally>,
+    #[serde(default)]
+    pub voter_registration: Option<StoredVoterRegistration>,
+}
+
+/// The stored form of a `TransactionData`. The encrypted vote and its proofs
+/// are stored in the encoding defined by `crypto_rs`.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct StoredTransactionData {
+    pub voter_idx: usize,
+    pub cipher_text: CipherText,
+    pub membership_proof: MembershipProof,
+    pub cai_proof: CaiProof,
+    #[serde(default)]
+    pub question_id: Option<String>,
+    #[serde(default)]
+    pub credential: Option<StoredEligibilityCredential>,
+}
+
+/// The stored form of a `TransactionType`.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub enum StoredTransactionType {
+    Vote,
+    VoteOpened,
+    VoteClosed,
+    SealerVote,
+    DecryptionShare,
+    DkgCommitment,
+    ElectionKey,
+    FinalTally,
+    VoterRegistered,
+    VoterRevoked,
+}
+
+/// The stored form of a `TransactionMetadata`.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct StoredTransactionMetadata {
+    pub entries: BTreeMap<String, String>,
+    pub digest: String,
+}
+
+/// The stored form of a `SealerVote`.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct StoredSealerVote {
+    pub voter: SocketAddr,
+    pub candidate: SocketAddr,
+    pub authorize: bool,
+    pub public_key: Option<String>,
+    pub cast_at: u64,
+}
+
+/// The stored form of a `PhaseChange`.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct StoredPhaseChange {
+    pub election: String,
+    pub changed_at: u64,
+    pub signature: Option<String>,
+}
+
+/// The stored form of a `DecryptionShare`. The partial decryption and its proof
+/// are stored in the encoding defined by `crypto_rs`.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct StoredDecryptionShare {
+    pub sealer: SocketAddr,
+    pub question_id: Option<String>,
+    pub partial_decryption: ModInt,
+    pub proof: DecryptionProof,
+}
+
+/// The stored form of a `DkgCommitment`. The commitments are stored in the encoding defined by `crypto_rs`.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct StoredDkgCommitment {
+    pub dealer: SocketAddr,
+    pub commitments: Vec<ModInt>,
+}
+
+/// The stored form of a `FinalTally`. The sum and the decryption shares are stored in the encoding defined by `crypto_rs`.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct StoredFinalTally {
+    pub question_id: Option<String>,
+    pub total_votes: usize,
+    pub cipher_text: CipherText,
+    pub decrypted_sum: Option<u64>,
+    pub decryption_shares: Vec<StoredDecryptionShare>,
+}
+
+/// The stored form of an `EligibilityCredential`.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct StoredEligibilityCredential {
+    pub nonce: String,
+    pub signature: String,
+}
+
+/// The stored form of a `VoterRegistration`. The images are stored in the encoding defined by `crypto_rs`.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct StoredVoterRegistration {
+    pub election: String,
+    pub question_id: Option<String>,
+    pub voter_idx: usize,
+    pub image_set: Option<ImageSet>,
+    pub credential: String,
+    pub registered_at: u64,
+    pub signature: Option<String>,
+}
+
+/// The stored form of a `StateSnapshot`.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct StoredStateSnapshot {
+    pub genesis_configuration_hash: String,
+    pub height: Height,
+    pub total_difficulty: u64,
+    pub block: StoredBlock,
+    pub sealers: Vec<SocketAddr>,
+    pub sealer_keys: BTreeMap<SocketAddr, String>,
+    pub pending_votes: Vec<StoredPendingVote>,
+    pub phase: StoredElectionPhase,
+    pub is_voting_opened: bool,
+    pub sum_cipher_text: Option<CipherText>,
+    pub total_votes: usize,
+    pub voters: String,
+    #[serde(default)]
+    pub questions: BTreeMap<String, StoredQuestionSnapshot>,
+    #[serde(default)]
+    pub dkg_commitments: BTreeMap<SocketAddr, Vec<ModInt>>,
+    #[serde(default)]
+    pub election_key: Option<ModInt>,
+    #[serde(default)]
+    pub final_tallies: Vec<StoredFinalTally>,
+    #[serde(default)]
+    pub voter_registrations: Vec<StoredVoterRegistration>,
+}
+
+/// The stored form of a `QuestionSnapshot`.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct StoredQuestionSnapshot {
+    pub sum_cipher_text: Option<CipherText>,
+    pub total_votes: usize,
+    pub voters: String,
+}
+
+/// The stored form of a `PendingVote`.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct StoredPendingVote {
+    pub voter: SocketAddr,
+    pub candidate: SocketAddr,
+    pub authorize: bool,
+    pub public_key: Option<String>,
+}
+
+/// The stored form of an `ElectionPhase`.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub enum StoredElectionPhase {
+    NotOpened,
+    Opened,
+    Closed,
+}
+
+impl<'a> From<&'a Block> for StoredBlock {
+    fn from(block: &'a Block) -> Self {
+        StoredBlock {
+            identifier: block.identifier.clone(),
+            data: StoredBlockContent {
+                parent: block.data.parent.clone(),
+                timestamp: block.data.timestamp,
+                sealer: block.data.sealer,
+                transactions: block.data.transactions.iter().map(StoredTransaction::from).collect(),
+                transactions_root: block.data.transactions_root.clone(),
+            },
+            signature: block.signature.clone(),
+        }
+    }
+}
+
+impl From<StoredBlock> for Block {
+    fn from(block: StoredBlock) -> Self {
+        Block {
+            identifier: block.identifier,
+            data: BlockContent {
+                parent: block.data.parent,
+                timestamp: block.data.timestamp,
+                sealer: block.data.sealer,
+                transactions: block.data.transactions.into_iter().map(Transaction::from).collect(),
+                transactions_root: block.data.transactions_root,
+            },
+            signature: block.signature,
+        }
+    }
+}
+
+impl<'a> From<&'a Transaction> for StoredTransaction {
+    fn from(transaction: &'a Transaction) -> Self {
+        StoredTransaction {
+            identifier: transaction.identifier.clone(),
+            trx_type: match transaction.trx_type {
+                TransactionType::Vote => StoredTransactionType::Vote,
+                TransactionType::VoteOpened => StoredTransactionType::VoteOpened,
+                TransactionType::VoteClosed => StoredTransactionType::VoteClosed,
+                TransactionType::SealerVote => StoredTransactionType::SealerVote,
+                TransactionType::DecryptionShare => StoredTransactionType::DecryptionShare,
+                TransactionType::DkgCommitment => StoredTransactionType::DkgCommitment,
+                TransactionType::ElectionKey => StoredTransactionType::ElectionKey,
+                TransactionType::FinalTally => StoredTransactionType::FinalTally,
+                TransactionType::VoterRegistered => StoredTransactionType::VoterRegistered,
+                TransactionType::VoterRevoked => StoredTransactionType::VoterRevoked
+            },
+            data: transaction.data.as_ref().map(|trx_data| StoredTransactionData {
+                voter_idx: trx_data.voter_idx,
+                cipher_text: trx_data.cipher_text.clone(),
+                membership_proof: trx_data.membership_proof.clone(),
+                cai_proof: trx_data.cai_proof.clone(),
+                question_id: trx_data.question_id.clone(),
+                credential: trx_data.credential.as_ref().map(|credential| StoredEligibilityCredential {
+                    nonce: credential.nonce.clone(),
+                    signature: credential.signature.clone(),
+                }),
+            }),
+            metadata: transaction.metadata.as_ref().map(|metadata| StoredTransactionMetadata {
+                entries: metadata.entries.clone(),
+                digest: metadata.digest.clone(),
+            }),
+            sealer_vote: transaction.sealer_vote.as_ref().map(|sealer_vote| StoredSealerVote {
+                voter: sealer_vote.voter,
+                candidate: sealer_vote.candidate,
+                authorize: sealer_vote.authorize,
+                public_key: sealer_vote.public_key.clone(),
+                cast_at: sealer_vote.cast_at,
+            }),
+            phase_change: transaction.phase_change.as_ref().map(|phase_change| StoredPhaseChange {
+                election: phase_change.election.clone(),
+                changed_at: phase_change.changed_at,
+                signature: phase_change.signature.clone(),
+            }),
+            decryption_share: transaction.decryption_share.as_ref().map(StoredDecryptionShare::from),
+            dkg_commitment: transaction.dkg_commitment.as_ref().map(|dkg_commitment| StoredDkgCommitment {
+                dealer: dkg_commitment.dealer,
+                commitments: dkg_commitment.commitments.clone(),
+            }),
+            election_key: transaction.election_key.clone(),
+            final_tally: transaction.final_tally.as_ref().map(StoredFinalTally::from),
+            voter_registration: transaction.voter_registration.as_ref().map(StoredVoterRegistration::from),
+        }
+    }
+}
+
+impl From<StoredTransaction> for Transaction {
+    fn from(transaction: StoredTransaction) -> Self {
+        Transaction {
+            identifier: transaction.identifier,
+            trx_type: match transaction.trx_type {
+                StoredTransactionType::Vote => TransactionType::Vote,
+                StoredTransactionType::VoteOpened => TransactionType::VoteOpened,
+                StoredTransactionType::VoteClosed => TransactionType::VoteClosed,
+                StoredTransactionType::SealerVote => TransactionType::SealerVote,
+                StoredTransactionType::DecryptionShare => TransactionType::DecryptionShare,
+                StoredTransactionType::DkgCommitment => TransactionType::DkgCommitment,
+                StoredTransactionType::ElectionKey => TransactionType::ElectionKey,
+                StoredTransactionType::FinalTally => TransactionType::FinalTally,
+                StoredTransactionType::VoterRegistered => TransactionType::VoterRegistered,
+                StoredTransactionType::VoterRevoked => TransactionType::VoterRevoked
+            },
+            data: transaction.data.map(|trx_data| TransactionData {
+                voter_idx: trx_data.voter_idx,
+                cipher_text: trx_data.cipher_text,
+                membership_proof: trx_data.membership_proof,
+                cai_proof: trx_data.cai_proof,
+                question_id: trx_data.question_id,
+                credential: trx_data.credential.map(|credential| EligibilityCredential {
+                    nonce: credential.nonce,
+                    signature: credential.signature,
+                }),
+            }),
+            metadata: transaction.metadata.map(|metadata| TransactionMetadata {
+                entries: metadata.entries,
+                digest: metadata.digest,
+            }),
+            sealer_vote: transaction.sealer_vote.map(|sealer_vote| SealerVote {
+                voter: sealer_vote.voter,
+                candidate: sealer_vote.candidate,
+                authorize: sealer_vote.authorize,
+                public_key: sealer_vote.public_key,
+                cast_at: sealer_vote.cast_at,
+            }),
+            phase_change: transaction.phase_change.map(|phase_change| PhaseChange {
+                election: phase_change.election,
+                changed_at: phase_change.changed_at,
+                signature: phase_change.signature,
+            }),
+            decryption_share: transaction.decryption_share.map(DecryptionShare::from),
+            dkg_commitment: transaction.dkg_commitment.map(|dkg_commitment| DkgCommitment {
+                dealer: dkg_commitment.dealer,
+                commitments: dkg_commitment.commitments,
+            }),
+            election_key: transaction.election_key,
+            final_tally: transaction.final_tally.map(FinalTally::from),
+            voter_registration: transaction.voter_registration.map(VoterRegistration::from),
+        }
+    }
+}
+
+impl<'a> From<&'a DecryptionShare> for StoredDecryptionShare {
+    fn from(decryption_share: &'a DecryptionShare) -> Self {
+        StoredDecryptionShare {
+            sealer: decryption_share.sealer,
+            question_id: decryption_share.question_id.clone(),
+            partial_decryption: decryption_share.partial_decryption.clone(),
+            proof: decryption_share.proof.clone(),
+        }
+    }
+}
+
+impl From<StoredDecryptionShare> for DecryptionShare {
+    fn from(decryption_share: StoredDecryptionShare) -> Self {
+        DecryptionShare {
+            sealer: decryption_share.sealer,
+            question_id: decryption_share.question_id,
+            partial_decryption: decryption_share.partial_decryption,
+            proof: decryption_share.proof,
+        }
+    }
+}
+
+impl<'a> From<&'a FinalTally> for StoredFinalTally {
+    fn from(final_tally: &'a FinalTally) -> Self {
+        StoredFinalTally {
+            question_id: final_tally.question_id.clone(),
+            total_votes: final_tally.total_votes,
+            cipher_text: final_tally.cipher_text.clone(),
+            decrypted_sum: final_tally.decrypted_sum,
+            decryption_shares: final_tally.decryption_shares.iter().map(StoredDecryptionShare::from).collect(),
+        }
+    }
+}
+
+impl From<StoredFinalTally> for FinalTally {
+    fn from(final_tally: StoredFinalTally) -> Self {
+        FinalTally {
+            question_id: final_tally.question_id,
+            total_votes: final_tally.total_votes,
+            cipher_text: final_tally.cipher_text,
+            decrypted_sum: final_tally.decrypted_sum,
+            decryption_shares: final_tally.decryption_shares.into_iter().map(DecryptionShare::from).collect(),
+        }
+    }
+}
+
+impl<'a> From<&'a VoterRegistration> for StoredVoterRegistration {
+    fn from(voter_registration: &'a VoterRegistration) -> Self {
+        StoredVoterRegistration {
+            election: voter_registration.election.clone(),
+            question_id: voter_registration.question_id.clone(),
+            voter_idx: voter_registration.voter_idx,
+            image_set: voter_registration.image_set.clone(),
+            credential: voter_registration.credential.clone(),
+            registered_at: voter_registration.registered_at,
+            signature: voter_registration.signature.clone(),
+        }
+    }
+}
+
+impl From<StoredVoterRegistration> for VoterRegistration {
+    fn from(voter_registration: StoredVoterRegistration) -> Self {
+        VoterRegistration {
+            election: voter_registration.election,
+            question_id: voter_registration.question_id,
+            voter_idx: voter_registration.voter_idx,
+            image_set: voter_registration.image_set,
+            credential: voter_registration.credential,
+            registered_at: voter_registration.registered_at,
+            signature: voter_registration.signature,
+        }
+    }
+}
+
+impl<'a> From<&'a StateSnapshot> for StoredStateSnapshot {
+    fn from(state_snapshot: &'a StateSnapshot) -> Self {
+        StoredStateSnapshot {
+            genesis_configuration_hash: state_snapshot.genesis_configuration_hash.clone(),
+            height: state_snapshot.height,
+            total_difficulty: state_snapshot.total_difficulty,
+            block: StoredBlock::from(&state_snapshot.block),
+            sealers: state_snapshot.sealers.clone(),
+            sealer_keys: state_snapshot.sealer_keys.clone(),
+            pending_votes: state_snapshot.pending_votes.iter()
+                .map(|pending_vote| StoredPendingVote {
+                    voter: pending_vote.voter,
+                    candidate: pending_vote.candidate,
+                    authorize: pending_vote.authorize,
+                    public_key: pending_vote.public_key.clone(),
+                })
+                .collect(),
+            phase: match state_snapshot.phase {
+                ElectionPhase::NotOpened => StoredElectionPhase::NotOpened,
+                ElectionPhase::Opened => StoredElectionPhase::Opened,
+                ElectionPhase::Closed => StoredElectionPhase::Closed
+            },
+            is_voting_opened: state_snapshot.is_voting_opened,
+            sum_cipher_text: state_snapshot.sum_cipher_text.clone(),
+            total_votes: state_snapshot.total_votes,
+            voters: state_snapshot.voters.clone(),
+            questions: state_snapshot.questions.iter()
+                .map(|(question_id, question)| (question_id.clone(), StoredQuestionSnapshot {
+                    sum_cipher_text: question.sum_cipher_text.clone(),
+                    total_votes: question.total_votes,
+                    voters: question.voters.clone(),
+                }))
+                .collect(),
+            dkg_commitments: state_snapshot.dkg_commitments.clone(),
+            election_key: state_snapshot.election_key.clone(),
+            final_tallies: state_snapshot.final_tallies.iter().map(StoredFinalTally::from).collect(),
+            voter_registrations: state_snapshot.voter_registrations.iter().map(StoredVoterRegistration::from).collect(),
+        }
+    }
+}
+
+impl From<StoredStateSnapshot> for StateSnapshot {
+    fn from(state_snapshot: StoredStateSnapshot) -> Self {
+        StateSnapshot {
+            genesis_configuration_hash: state_snapshot.genesis_configuration_hash,
+            height: state_snapshot.height,
+            total_difficulty: state_snapshot.total_difficulty,
+            block: Block::from(state_snapshot.block),
+            sealers: state_snapshot.sealers,
+            sealer_keys: state_snapshot.sealer_keys,
+            pending_votes: state_snapshot.pending_votes.into_iter()
+                .map(|pending_vote| PendingVote {
+                    voter: pending_vote.voter,
+                    candidate: pending_vote.candidate,
+                    authorize: pending_vote.authorize,
+                    public_key: pending_vote.public_key,
+                })
+                .collect(),
+            phase: match state_snapshot.phase {
+                StoredElectionPhase::NotOpened => ElectionPhase::NotOpened,
+                StoredElectionPhase::Opened => ElectionPhase::Opened,
+                StoredElectionPhase::Closed => ElectionPhase::Closed
+            },
+            is_voting_opened: state_snapshot.is_voting_opened,
+            sum_cipher_text: state_snapshot.sum_cipher_text,
+            total_votes: state_snapshot.total_votes,
+            voters: state_snapshot.voters,
+            questions: state_snapshot.questions.into_iter()
+                .map(|(question_id, question)| (question_id, QuestionSnapshot {
+                    sum_cipher_text: question.sum_cipher_text,
+                    total_votes: question.total_votes,
+                    voters: question.voters,
+                }))
+                .collect(),
+            dkg_commitments: state_snapshot.dkg_commitments,
+            election_key: state_snapshot.election_key,
+            final_tallies: state_snapshot.final_tallies.into_iter().map(FinalTally::from).collect(),
+            voter_registrations: state_snapshot.voter_registrations.into_iter().map(VoterRegistration::from).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod storage_test {
+
+    use super::*;
+    use ::chain::hasher::Hasher;
+    use crypto_rs::cai::uciv::{CaiProof, ImageSet, PreImageSet};
+    use crypto_rs::el_gamal::encryption::PublicKey;
+    use crypto_rs::el_gamal::membership_proof::MembershipProof;
+    use num::One;
+
+    #[test]
+    fn test_codecs() {
+        let sealer: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let mut opening = Transaction::new_voting_opened(Hasher::default(), "genesis".to_string(), 0);
+        let mut entries = BTreeMap::new();
+        entries.insert("client".to_string(), "1.0".to_string());
+        opening.metadata = Some(TransactionMetadata::new(&opening.identifier, entries));
+        let public_key = PublicKey { p: ModInt::one(), q: ModInt::one(), h: ModInt::one(), g: ModInt::one() };
+        let cipher_text = CipherText { big_h: ModInt::one(), big_g: ModInt::one(), random: ModInt::one() };
+        let vote = Transaction::new_vote(
+            Hasher::default(),
+            0,
+            cipher_text.clone(),
+            MembershipProof::new(public_key.clone(), ModInt::one(), cipher_text.clone(), vec![ModInt::one()]),
+            CaiProof::new(public_key, cipher_text, PreImageSet { pre_images: vec![ModInt::one()] }, ImageSet { images: vec![ModInt::one()] }, 0, vec![ModInt::one()]),
+        ).with_credential(EligibilityCredential { nonce: "00".to_string(), signature: "1".to_string() });
+        let mut question_data = vote.data.clone().unwrap();
+        question_data.credential = None;
+        let question_vote = Transaction::new_question_vote(Hasher::default(), Some("q2".to_string()), 0, question_data.cipher_text.clone(), question_data.membership_proof.clone(), question_data.cai_proof.clone());
+        question_data.question_id = Some("q2".to_string());
+        assert_eq!(Some(question_data), question_vote.data);
+        let decryption_share = Transaction::new_decryption_share(Hasher::default(), DecryptionShare {
+            sealer,
+            question_id: None,
+            partial_decryption: ModInt::one(),
+            proof: DecryptionProof { commitment_g: ModInt::one(), commitment_c: ModInt::one(), response: ModInt::one() },
+        });
+        let dkg_commitment = Transaction::new_dkg_commitment(Hasher::default(), DkgCommitment { dealer: sealer, commitments: vec![ModInt::one(), ModInt::one()] });
+        let election_key = Transaction::new_election_key(Hasher::default(), ModInt::one());
+        let final_tally = Transaction::new_final_tally(Hasher::default(), FinalTally {
+            question_id: Some("q2".to_string()),
+            total_votes: 1,
+            cipher_text: CipherText { big_h: ModInt::one(), big_g: ModInt::one(), random: ModInt::one() },
+            decrypted_sum: Some(1),
+            decryption_shares: vec![decryption_share.decryption_share.clone().unwrap()],
+        });
+        let voter_registration = Transaction::from_voter_registration(Hasher::default(), TransactionType::VoterRegistered, VoterRegistration {
+            election: "genesis".to_string(),
+            question_id: None,
+            voter_idx: 1,
+            image_set: Some(ImageSet { images: vec![ModInt::one(), ModInt::one()] }),
+            credential: "roll-1".to_string(),
+            registered_at: 0,
+            signature: None,
+        });
+        let block = Block::new(Hasher::default(), "parent".into(), Some(sealer), vec![opening, vote, question_vote, decryption_share, dkg_commitment, election_key, final_tally, voter_registration]);
+
+        for encoding in StorageEncoding::all() {
+            let codec = create(encoding);
+            let mut contents = codec.header();
+            contents.extend(codec.encode_root(&StoredRoot::GenesisConfigurationHash("genesis".to_string())));
+            contents.extend(codec.encode_block(&StoredBlock::from(&block)));
+            let complete_length = contents.len();
+            // a block only partially written
+            contents.extend(&codec.encode_block(&StoredBlock::from(&block))[..10]);
+
+            let (storage_version, records, length) = codec.split_records(&contents).unwrap();
+            assert_eq!(STORAGE_VERSION, storage_version);
+            assert_eq!(complete_length, length);
+            assert_eq!(2, records.len());
+            match codec.decode_root(records[0], storage_version) {
+                Ok(StoredRoot::GenesisConfigurationHash(hash)) => assert_eq!("genesis", hash),
+                _ => panic!("Expected the genesis configuration hash for {:?}", encoding)
+            }
+            let decoded_block = Block::from(codec.decode_block(records[1], storage_version).unwrap());
+            assert_eq!(block, decoded_block);
+            assert_eq!(block.data.transactions[1].data, decoded_block.data.transactions[1].data);
+            assert_eq!(block.data.transactions[2].data, decoded_block.data.transactions[2].data);
+            assert_eq!(block.data.transactions[3].decryption_share, decoded_block.data.transactions[3].decryption_share);
+            assert_eq!(block.data.transactions[4].dkg_commitment, decoded_block.data.transactions[4].dkg_commitment);
+            assert_eq!(block.data.transactions[5].election_key, decoded_block.data.transactions[5].election_key);
+            assert_eq!(block.data.transactions[6].final_tally, decoded_block.data.transactions[6].final_tally);
+            assert_eq!(block.data.transactions[7].voter_registration, decoded_block.data.transactions[7].voter_registration);
+        }
+
+        // blocks stored by earlier versions of this node lack the header and the metadata
+        let legacy = format!("\"genesis\"\n{}\n", serde_json::to_string(&Block::new(Hasher::default(), "parent".into(), None, vec![])).unwrap());
+        let (storage_version, records, _) = JsonStorageCodec.split_records(legacy.as_bytes()).unwrap();
+        assert_eq!(1, storage_version);
+        assert_eq!(2, records.len());
+        assert!(JsonStorageCodec.decode_block(records[1], storage_version).is_ok());
+
+        // files of newer versions are rejected
+        let mut header = BINCODE_MAGIC.to_vec();
+        header.extend(BincodeStorageCodec::encode_length(STORAGE_VERSION as usize + 1));
+        assert!(BincodeStorageCodec.split_records(&header).is_err());
+    }
+}
@@ -1,23 +1,51 @@
+use ::chain::hasher::Hasher;
+use ::chain::transaction_registry::TransactionRegistry;
+use ::credential::{EligibilityCredential, RegistrarKey};
+use ::signer::algorithm;
+use ::signer::key::NodeKey;
+use ::threshold::DecryptionProof;
+use crypto_rs::arithmetic::mod_int::ModInt;
 use crypto_rs::cai::uciv::CaiProof;
 use crypto_rs::el_gamal::ciphertext::CipherText;
 use crypto_rs::el_gamal::membership_proof::MembershipProof;
 use crypto_rs::el_gamal::encryption::PublicKey;
-use crypto_rs::arithmetic::mod_int::From;
-use crypto_rs::arithmetic::mod_int::ModInt;
 use crypto_rs::cai::uciv::ImageSet;
-use num::{One, Zero};
-use num::BigInt;
 use std::vec::Vec;
 use bincode;
-use sha1::Sha1;
+use serde_json;
 use std::cmp::{Eq, PartialEq};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::SocketAddr;
 use std::option::Option;
 
+/// The maximum amount of entries in the metadata of a transaction.
+pub const MAX_METADATA_ENTRIES: usize = 8;
+
+/// The maximum length in bytes of a key in the metadata of a transaction.
+pub const MAX_METADATA_KEY_LENGTH: usize = 32;
+
+/// The maximum length in bytes of a value in the metadata of a transaction.
+pub const MAX_METADATA_VALUE_LENGTH: usize = 128;
+
+/// The default maximum size in bytes of a transaction, when encoded as JSON.
+pub const DEFAULT_MAX_TRANSACTION_SIZE: usize = 128 * 1024;
+
+/// The default maximum size in bytes of each proof component of a vote, when encoded with bincode.
+pub const DEFAULT_MAX_PROOF_COMPONENT_SIZE: usize = 16 * 1024;
+
 #[derive(Eq, PartialEq, Hash, Deserialize, Serialize, Clone, Debug)]
 pub enum TransactionType {
     Vote,
     VoteOpened,
-    VoteClosed
+    VoteClosed,
+    SealerVote,
+    DecryptionShare,
+    DkgCommitment,
+    ElectionKey,
+    FinalTally,
+    VoterRegistered,
+    VoterRevoked
 }
 
 #[derive(Eq, PartialEq, Hash, Serialize, Deserialize, Debug, Clone)]
@@ -26,101 +54,684 @@ pub struct TransactionData {
     pub cipher_text: CipherText,
     pub membership_proof: MembershipProof,
     pub cai_proof: CaiProof,
+    /// The identifier of the question the vote answers, as declared in the genesis configuration,
+    /// or None for the question whose voters are declared in `public_uciv.json`. Omitted if absent,
+    /// so that the identifiers of votes on the latter do not change.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub question_id: Option<String>,
+    /// The credential proving the eligibility of the voter, blind-signed by the registrar declared in the genesis
+    /// configuration. Omitted if absent, so that the identifiers of votes without it do not change.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential: Option<EligibilityCredential>,
+}
+
+/// The vote of a sealer on adding a candidate to the sealers or on removing one of them.
+///
+/// The vote only counts if it is included in a block sealed by the voter itself,
+/// so that the sealer vouches for it like the signer of a header does in Clique.
+#[derive(Eq, PartialEq, Hash, Serialize, Deserialize, Debug, Clone)]
+pub struct SealerVote {
+    /// The sealer casting the vote.
+    pub voter: SocketAddr,
+    /// The address of the sealer to add or to remove.
+    pub candidate: SocketAddr,
+    /// True to add the candidate, false to remove it.
+    pub authorize: bool,
+    /// The hex encoded Ed25519 public key the candidate signs its blocks with once added.
+    /// Required to add a candidate if the genesis configuration declares the keys of the sealers.
+    pub public_key: Option<String>,
+    /// The time the vote was cast, in milliseconds since the epoch,
+    /// so that repeated votes on the same candidate have distinct identifiers.
+    pub cast_at: u64,
+}
+
+/// The details of opening or closing the voting, hashed into the identifier of the transaction,
+/// so that each opening and closing has a distinct identifier.
+#[derive(Eq, PartialEq, Hash, Serialize, Deserialize, Debug, Clone)]
+pub struct PhaseChange {
+    /// The hash of the genesis configuration of the election whose voting is opened or closed.
+    pub election: String,
+    /// The time the voting was opened or closed, in milliseconds since the epoch.
+    pub changed_at: u64,
+    /// The signature of the message returned by `get_signed_message` by the election authority,
+    /// encoded along with its algorithm, see `SignatureAlgorithm`. Required if the genesis configuration
+    /// declares an election authority. It is not hashed into the identifier, as it signs the same details.
+    #[serde(default)]
+    pub signature: Option<String>,
+}
+
+impl PhaseChange {
+    /// Create the unsigned details of opening or closing the voting of the given election at the given time.
+    pub fn new(election: String, changed_at: u64) -> PhaseChange {
+        PhaseChange {
+            election,
+            changed_at,
+            signature: None,
+        }
+    }
+
+    /// Returns the message signed by the election authority to open or close the voting with these details,
+    /// depending on the given transaction type. The identifier of the transaction is its digest.
+    pub fn get_signed_message(&self, trx_type: &TransactionType) -> Vec<u8> {
+        bincode::serialize(&(trx_type, &self.election, self.changed_at)).unwrap()
+    }
+
+    /// Sign these details with the given key of the election authority,
+    /// to open or close the voting depending on the given transaction type.
+    pub fn sign(mut self, trx_type: &TransactionType, election_authority: &NodeKey) -> Result<PhaseChange, String> {
+        match election_authority.sign_message(&self.get_signed_message(trx_type)) {
+            Ok(signature) => {
+                self.signature = Some(signature);
+                Ok(self)
+            }
+            Err(e) => Err(e)
+        }
+    }
+
+    /// Check that these details are signed by the election authority with the given encoded public keys,
+    /// to open or close the voting depending on the given transaction type.
+    pub fn verify(&self, trx_type: &TransactionType, election_authority: &str) -> Result<(), String> {
+        match self.signature {
+            Some(ref signature) if algorithm::verify_entries(election_authority, &self.get_signed_message(trx_type), signature) => Ok(()),
+            Some(_) => Err("Signature does not match the election authority".to_string()),
+            None => Err("Opening or closing the voting requires the signature of the election authority".to_string())
+        }
+    }
+}
+
+/// The registration of a voter by the election authority, or the revocation of a registration,
+/// if the feature `voter-registry` is enabled. Registering a voter makes it eligible to vote on a question
+/// with the given images in addition to the voters declared in the genesis configuration, revoking it makes
+/// it ineligible to cast further votes. The details are hashed into the identifier of the transaction.
+#[derive(Eq, PartialEq, Hash, Serialize, Deserialize, Debug, Clone)]
+pub struct VoterRegistration {
+    /// The hash of the genesis configuration of the election the voter is registered for.
+    pub election: String,
+    /// The identifier of the question the voter is registered for, or None for the question
+    /// whose voters are declared in `public_uciv.json`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub question_id: Option<String>,
+    /// The index of the voter, which its votes refer to.
+    pub voter_idx: usize,
+    /// The public UCIV information of the voter, holding an image per voting option.
+    /// Present when registering a voter, absent when revoking it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_set: Option<ImageSet>,
+    /// The credential the election authority identified the voter with, e.g. the digest of its entry
+    /// in the electoral roll, so that each registration can be traced back to the roll when auditing.
+    pub credential: String,
+    /// The time the voter was registered or revoked, in milliseconds since the epoch.
+    pub registered_at: u64,
+    /// The signature of the message returned by `get_signed_message` by the election authority,
+    /// encoded along with its algorithm, see `SignatureAlgorithm`. It is not hashed into the identifier,
+    /// as it signs the same details.
+    #[serde(default)]
+    pub signature: Option<String>,
+}
+
+impl VoterRegistration {
+    /// Returns the message signed by the election authority to register or revoke the voter with these details,
+    /// depending on the given transaction type. The identifier of the transaction is its digest.
+    pub fn get_signed_message(&self, trx_type: &TransactionType) -> Vec<u8> {
+        bincode::serialize(&(trx_type, &self.election, &self.question_id, self.voter_idx, &self.image_set, &self.credential, self.registered_at)).unwrap()
+    }
+
+    /// Sign these details with the given key of the election authority,
+    /// to register or revoke the voter depending on the given transaction type.
+    pub fn sign(mut self, trx_type: &TransactionType, election_authority: &NodeKey) -> Result<VoterRegistration, String> {
+        match election_authority.sign_message(&self.get_signed_message(trx_type)) {
+            Ok(signature) => {
+                self.signature = Some(signature);
+                Ok(self)
+            }
+            Err(e) => Err(e)
+        }
+    }
+
+    /// Check that these details are signed by the election authority with the given encoded public keys,
+    /// to register or revoke the voter depending on the given transaction type.
+    pub fn verify(&self, trx_type: &TransactionType, election_authority: &str) -> Result<(), String> {
+        match self.signature {
+            Some(ref signature) if algorithm::verify_entries(election_authority, &self.get_signed_message(trx_type), signature) => Ok(()),
+            Some(_) => Err("Signature does not match the election authority".to_string()),
+            None => Err("Registering or revoking a voter requires the signature of the election authority".to_string())
+        }
+    }
+}
+
+/// The partial decryption of the final sum of the votes on a question by a single sealer,
+/// published once the voting is closed. A threshold of them decrypts the tally, see `threshold::combine`.
+#[derive(Eq, PartialEq, Hash, Serialize, Deserialize, Debug, Clone)]
+pub struct DecryptionShare {
+    /// The sealer holding the key share the sum is partially decrypted with.
+    pub sealer: SocketAddr,
+    /// The identifier of the question whose votes are decrypted, or None for the question
+    /// whose voters are declared in `public_uciv.json`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub question_id: Option<String>,
+    /// The partial decryption `G^x_i` of the sum of the votes.
+    pub partial_decryption: ModInt,
+    /// The proof that the partial decryption matches the public key share of the sealer.
+    pub proof: DecryptionProof,
+}
+
+/// The commitments of a sealer to the polynomial it deals its contribution to the election key with,
+/// published before the voting is opened, see `threshold::dkg`. Only included in blocks sealed by the dealer itself.
+#[derive(Eq, PartialEq, Hash, Serialize, Deserialize, Debug, Clone)]
+pub struct DkgCommitment {
+    /// The sealer dealing the polynomial.
+    pub dealer: SocketAddr,
+    /// The commitments `g^a_j` to the coefficients of the polynomial, starting with the constant one.
+    pub commitments: Vec<ModInt>,
+}
+
+/// The outcome of the election on a single question, committed on the chain once the voting is closed
+/// and, if the sealers decrypt the tally jointly, once a threshold of them published their decryption shares.
+/// Every node derives the same final tally from the same chain, see `protocol::tally::TallyCalculator`.
+#[derive(Eq, PartialEq, Hash, Serialize, Deserialize, Debug, Clone)]
+pub struct FinalTally {
+    /// The identifier of the question, or None for the question whose voters are declared in `public_uciv.json`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub question_id: Option<String>,
+    /// The amount of votes counted.
+    pub total_votes: usize,
+    /// The homomorphic sum of the votes counted.
+    pub cipher_text: CipherText,
+    /// The decrypted sum of the votes, or None if the sealers do not decrypt the tally jointly.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decrypted_sum: Option<u64>,
+    /// The decryption shares the sum is decrypted with, whose proofs prove the decrypted sum.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub decryption_shares: Vec<DecryptionShare>,
+}
+
+/// Non-secret information attached to a transaction by the client, e.g. the
+/// ballot style or the version of the client, used for troubleshooting.
+///
+/// The metadata is not hashed into the identifier of the transaction, so that
+/// it does not affect the detection of duplicated votes. Instead, it carries a digest
+/// binding it to the identifier, which prevents it from being moved to another
+/// transaction. Once included in a block, it is covered by the identifier of the block.
+#[derive(Eq, PartialEq, Hash, Serialize, Deserialize, Debug, Clone)]
+pub struct TransactionMetadata {
+    pub entries: BTreeMap<String, String>,
+    /// The digest of the transaction identifier and all entries, derived with
+    /// the hash function of the transaction identifier.
+    pub digest: String,
+}
+
+impl TransactionMetadata {
+    /// Create the metadata of the transaction with the given identifier.
+    pub fn new(transaction_identifier: &str, entries: BTreeMap<String, String>) -> TransactionMetadata {
+        let hasher = Hasher::from_identifier(transaction_identifier).unwrap_or_default();
+        let digest = TransactionMetadata::hash_entries(hasher, transaction_identifier, &entries);
+
+        TransactionMetadata {
+            entries,
+            digest,
+        }
+    }
+
+    /// Verify that the metadata belongs to the transaction with the given identifier
+    /// and respects the size limits.
+    /// Returns the reason if it does not.
+    pub fn verify(&self, transaction_identifier: &str) -> Result<(), String> {
+        if self.entries.len() > MAX_METADATA_ENTRIES {
+            return Err(format!("Metadata has {} entries, only {} are allowed", self.entries.len(), MAX_METADATA_ENTRIES));
+        }
+
+        for (key, value) in self.entries.iter() {
+            let is_valid_key = !key.is_empty() && key.chars().all(|character| character.is_ascii_alphanumeric() || character == '_' || character == '-' || character == '.');
+            if !is_valid_key || key.len() > MAX_METADATA_KEY_LENGTH {
+                return Err(format!("Metadata key {:?} must consist of at most {} alphanumeric characters, '_', '-' or '.'", key, MAX_METADATA_KEY_LENGTH));
+            }
+            if value.len() > MAX_METADATA_VALUE_LENGTH || value.chars().any(|character| character.is_control()) {
+                return Err(format!("Metadata value of {:?} must consist of at most {} bytes without control characters", key, MAX_METADATA_VALUE_LENGTH));
+            }
+        }
+
+        let hasher = match Hasher::from_identifier(transaction_identifier) {
+            Some(hasher) => hasher,
+            None => {
+                return Err(format!("Transaction identifier {:?} names an unknown hash function", transaction_identifier));
+            }
+        };
+
+        if !self.digest.eq(&TransactionMetadata::hash_entries(hasher, transaction_identifier, &self.entries)) {
+            return Err("Metadata digest does not match the transaction".to_string());
+        }
+
+        Ok(())
+    }
+
+    fn hash_entries(hasher: Hasher, transaction_identifier: &str, entries: &BTreeMap<String, String>) -> String {
+        // the entries are ordered by their key, hence the encoding is canonical
+        let bytes = bincode::serialize(&(transaction_identifier, entries)).unwrap();
+
+        hasher.digest(&bytes)
+    }
+}
+
+/// Upper bounds on the size of transactions, checked before their proofs are verified,
+/// so that crafted transactions with huge numbers cannot exhaust the time or memory of a node.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct TransactionLimits {
+    /// The maximum size in bytes of a transaction, when encoded as JSON.
+    pub max_transaction_size: usize,
+    /// The maximum size in bytes of the cipher text, the membership proof
+    /// and the cast-as-intended proof of a vote each, when encoded with bincode.
+    pub max_proof_component_size: usize,
+}
+
+impl TransactionLimits {
+    /// Check that the given transaction respects these limits.
+    /// Returns the exceeded limit if it does not.
+    pub fn check(&self, transaction: &Transaction) -> Result<(), LimitViolation> {
+        let size = serde_json::to_string(transaction).unwrap().len();
+        if size > self.max_transaction_size {
+            return Err(LimitViolation::TransactionTooLarge {
+                size,
+                limit: self.max_transaction_size,
+            });
+        }
+
+        if let Some(ref data) = transaction.data {
+            let components = [
+                (ProofComponent::CipherText, bincode::serialized_size(&data.cipher_text).unwrap() as usize),
+                (ProofComponent::MembershipProof, bincode::serialized_size(&data.membership_proof).unwrap() as usize),
+                (ProofComponent::CaiProof, bincode::serialized_size(&data.cai_proof).unwrap() as usize),
+            ];
+
+            for &(component, size) in components.iter() {
+                if size > self.max_proof_component_size {
+                    return Err(LimitViolation::ProofComponentTooLarge {
+                        component,
+                        size,
+                        limit: self.max_proof_component_size,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for TransactionLimits {
+    fn default() -> Self {
+        TransactionLimits {
+            max_transaction_size: DEFAULT_MAX_TRANSACTION_SIZE,
+            max_proof_component_size: DEFAULT_MAX_PROOF_COMPONENT_SIZE,
+        }
+    }
+}
+
+/// A part of a vote whose size is limited by `TransactionLimits`.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum ProofComponent {
+    CipherText,
+    MembershipProof,
+    CaiProof,
+}
+
+impl fmt::Display for ProofComponent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ProofComponent::CipherText => write!(f, "cipher text"),
+            ProofComponent::MembershipProof => write!(f, "membership proof"),
+            ProofComponent::CaiProof => write!(f, "cast-as-intended proof"),
+        }
+    }
+}
+
+/// The limit of `TransactionLimits` exceeded by a transaction.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum LimitViolation {
+    /// The encoded transaction is larger than allowed.
+    TransactionTooLarge {
+        size: usize,
+        limit: usize,
+    },
+    /// A proof component of the vote is larger than allowed.
+    ProofComponentTooLarge {
+        component: ProofComponent,
+        size: usize,
+        limit: usize,
+    },
+}
+
+impl fmt::Display for LimitViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LimitViolation::TransactionTooLarge { size, limit } => write!(f, "Transaction of {} bytes exceeds the limit of {} bytes", size, limit),
+            LimitViolation::ProofComponentTooLarge { component, size, limit } => write!(f, "The {} of {} bytes exceeds the limit of {} bytes", component, size, limit),
+        }
+    }
 }
 
 /// Use Deserialize from Serde, Hash from std::hash
+///
+/// Optional fields are skipped when absent, so that the identifiers
+/// of existing transactions and their blocks are stable.
 #[derive(Hash, Serialize, Deserialize, Debug, Clone)]
 pub struct Transaction {
     pub identifier: String,
     pub trx_type: TransactionType,
-    pub data: Option<TransactionData>
+    pub data: Option<TransactionData>,
+    /// Non-secret information attached by the client, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<TransactionMetadata>,
+    /// The vote of a `SealerVote` transaction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sealer_vote: Option<SealerVote>,
+    /// The details of a `VoteOpened` or `VoteClosed` transaction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phase_change: Option<PhaseChange>,
+    /// The share of a `DecryptionShare` transaction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decryption_share: Option<DecryptionShare>,
+    /// The commitments of a `DkgCommitment` transaction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dkg_commitment: Option<DkgCommitment>,
+    /// The key votes are encrypted with, committed by an `ElectionKey` transaction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub election_key: Option<ModInt>,
+    /// The outcome of a `FinalTally` transaction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub final_tally: Option<FinalTally>,
+    /// The details of a `VoterRegistered` or `VoterRevoked` transaction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voter_registration: Option<VoterRegistration>,
+}
+
+/// Selects the transactions of the canonical chain returned by a query.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct TransactionQuery {
+    /// Only select transactions of this type, if any.
+    pub trx_type: Option<TransactionType>,
+    /// Only select transactions whose metadata contains all of these entries.
+    pub metadata: BTreeMap<String, String>,
+    /// The maximum amount of transactions to return.
+    pub limit: usize,
+}
+
+impl TransactionQuery {
+    /// Returns true, if the given transaction is selected by this query.
+    pub fn matches(&self, transaction: &Transaction) -> bool {
+        if let Some(ref trx_type) = self.trx_type {
+            if !trx_type.eq(&transaction.trx_type) {
+                return false;
+            }
+        }
+
+        self.metadata.iter().all(|(key, value)| {
+            match transaction.metadata {
+                Some(ref metadata) => metadata.entries.get(key) == Some(value),
+                None => false
+            }
+        })
+    }
 }
 
 impl Transaction {
-    pub fn new_voting_opened() -> Transaction {
-        // hash the transaction type as no data is needed
-        let bytes = bincode::serialize(&TransactionType::VoteOpened).unwrap();
-        let digest = Sha1::from(bytes).hexdigest();
+    /// Create the transaction opening the voting of the given election at the given time.
+    ///
+    /// - election: The hash of the genesis configuration of the election.
+    /// - changed_at: The time the voting is opened, in milliseconds since the epoch.
+    pub fn new_voting_opened(hasher: Hasher, election: String, changed_at: u64) -> Transaction {
+        Transaction::from_phase_change(hasher, TransactionType::VoteOpened, PhaseChange::new(election, changed_at))
+    }
+
+    /// Create the transaction closing the voting of the given election at the given time.
+    ///
+    /// - election: The hash of the genesis configuration of the election.
+    /// - changed_at: The time the voting is closed, in milliseconds since the epoch.
+    pub fn new_voting_closed(hasher: Hasher, election: String, changed_at: u64) -> Transaction {
+        Transaction::from_phase_change(hasher, TransactionType::VoteClosed, PhaseChange::new(election, changed_at))
+    }
+
+    /// Create the transaction opening or closing the voting with the given details,
+    /// depending on the given transaction type, e.g. signed by the election authority.
+    pub fn from_phase_change(hasher: Hasher, trx_type: TransactionType, phase_change: PhaseChange) -> Transaction {
+        // hash the details along with the transaction type, so that
+        // opening and closing the voting at the same time differ
+        let digest = hasher.digest(&phase_change.get_signed_message(&trx_type));
 
         Transaction {
             identifier: digest,
-            trx_type: TransactionType::VoteOpened,
-            data: None
+            trx_type,
+            data: None,
+            metadata: None,
+            sealer_vote: None,
+            phase_change: Some(phase_change),
+            decryption_share: None,
+            dkg_commitment: None,
+            election_key: None,
+            final_tally: None,
+            voter_registration: None,
         }
     }
 
-    pub fn new_voting_closed() -> Transaction {
-        // hash the transaction type as no data is needed
-        let bytes = bincode::serialize(&TransactionType::VoteClosed).unwrap();
-        let digest = Sha1::from(bytes).hexdigest();
+    /// Returns true, if the identifier of this `VoteOpened` or `VoteClosed` transaction
+    /// is derived from its details.
+    ///
+    /// Transactions created by earlier versions of this node lack the details,
+    /// their identifier is derived from the transaction type only.
+    pub fn is_valid_phase_change(&self) -> bool {
+        let hasher = match Hasher::from_identifier(&self.identifier) {
+            Some(hasher) => hasher,
+            None => {
+                return false;
+            }
+        };
 
-        Transaction {
-            identifier: digest,
-            trx_type: TransactionType::VoteClosed,
-            data: None
+        let digest = match self.phase_change {
+            Some(ref phase_change) => hasher.digest(&phase_change.get_signed_message(&self.trx_type)),
+            None => hasher.digest(&bincode::serialize(&self.trx_type).unwrap())
+        };
+
+        digest == self.identifier
+    }
+
+    /// Returns true, if the identifier of this `VoterRegistered` or `VoterRevoked` transaction
+    /// is derived from its details.
+    pub fn is_valid_voter_registration(&self) -> bool {
+        match (Hasher::from_identifier(&self.identifier), self.voter_registration.as_ref()) {
+            (Some(hasher), Some(voter_registration)) => hasher.digest(&voter_registration.get_signed_message(&self.trx_type)) == self.identifier,
+            _ => false
         }
     }
 
-    pub fn new_vote(voter_idx: usize, cipher_text: CipherText, membership_proof: MembershipProof, cai_proof: CaiProof) -> Transaction {
+    pub fn new_vote(hasher: Hasher, voter_idx: usize, cipher_text: CipherText, membership_proof: MembershipProof, cai_proof: CaiProof) -> Transaction {
+        Transaction::new_question_vote(hasher, None, voter_idx, cipher_text, membership_proof, cai_proof)
+    }
+
+    /// Create the vote on the question with the given identifier, or on the question
+    /// whose voters are declared in `public_uciv.json` if None.
+    pub fn new_question_vote(hasher: Hasher, question_id: Option<String>, voter_idx: usize, cipher_text: CipherText, membership_proof: MembershipProof, cai_proof: CaiProof) -> Transaction {
         let trx_data = TransactionData {
             voter_idx,
             cipher_text,
             membership_proof,
-            cai_proof
+            cai_proof,
+            question_id,
+            credential: None,
         };
         // we only want to hash the transactions to make sure, that these
         // are not duplicated. We don't care about the references of the block
         let bytes = bincode::serialize(&trx_data).unwrap();
-        let digest = Sha1::from(bytes).hexdigest();
+        let digest = hasher.digest(&bytes);
 
         Transaction {
             identifier: digest,
             trx_type: TransactionType::Vote,
-            data: Some(trx_data)
+            data: Some(trx_data),
+            metadata: None,
+            sealer_vote: None,
+            phase_change: None,
+            decryption_share: None,
+            dkg_commitment: None,
+            election_key: None,
+            final_tally: None,
+            voter_registration: None,
         }
     }
 
+    /// Create the transaction casting the given vote on a sealer.
+    pub fn new_sealer_vote(hasher: Hasher, sealer_vote: SealerVote) -> Transaction {
+        // hash the vote along with the transaction type, so that
+        // its identifier never collides with the one of another type
+        let bytes = bincode::serialize(&(TransactionType::SealerVote, &sealer_vote)).unwrap();
+        let digest = hasher.digest(&bytes);
+
+        Transaction {
+            identifier: digest,
+            trx_type: TransactionType::SealerVote,
+            data: None,
+            metadata: None,
+            sealer_vote: Some(sealer_vote),
+            phase_change: None,
+            decryption_share: None,
+            dkg_commitment: None,
+            election_key: None,
+            final_tally: None,
+            voter_registration: None,
+        }
+    }
+
+    /// Create the transaction publishing the given decryption share of a sealer.
+    pub fn new_decryption_share(hasher: Hasher, decryption_share: DecryptionShare) -> Transaction {
+        let bytes = bincode::serialize(&(TransactionType::DecryptionShare, &decryption_share)).unwrap();
+        let digest = hasher.digest(&bytes);
+
+        Transaction {
+            identifier: digest,
+            trx_type: TransactionType::DecryptionShare,
+            data: None,
+            metadata: None,
+            sealer_vote: None,
+            phase_change: None,
+            decryption_share: Some(decryption_share),
+            dkg_commitment: None,
+            election_key: None,
+            final_tally: None,
+            voter_registration: None,
+        }
+    }
+
+    /// Create the transaction publishing the given commitments of a dealer of the election key.
+    pub fn new_dkg_commitment(hasher: Hasher, dkg_commitment: DkgCommitment) -> Transaction {
+        let bytes = bincode::serialize(&(TransactionType::DkgCommitment, &dkg_commitment)).unwrap();
+        let digest = hasher.digest(&bytes);
+
+        Transaction {
+            identifier: digest,
+            trx_type: TransactionType::DkgCommitment,
+            data: None,
+            metadata: None,
+            sealer_vote: None,
+            phase_change: None,
+            decryption_share: None,
+            dkg_commitment: Some(dkg_commitment),
+            election_key: None,
+            final_tally: None,
+            voter_registration: None,
+        }
+    }
+
+    /// Create the transaction committing the given election key, generated jointly by the sealers.
+    /// Its identifier only depends on the key, so that all sealers publish the same transaction.
+    pub fn new_election_key(hasher: Hasher, election_key: ModInt) -> Transaction {
+        let bytes = bincode::serialize(&(TransactionType::ElectionKey, &election_key)).unwrap();
+        let digest = hasher.digest(&bytes);
+
+        Transaction {
+            identifier: digest,
+            trx_type: TransactionType::ElectionKey,
+            data: None,
+            metadata: None,
+            sealer_vote: None,
+            phase_change: None,
+            decryption_share: None,
+            dkg_commitment: None,
+            election_key: Some(election_key),
+            final_tally: None,
+            voter_registration: None,
+        }
+    }
+
+    /// Create the transaction committing the given final tally of a question.
+    /// Its identifier only depends on the tally, so that all sealers publish the same transaction.
+    pub fn new_final_tally(hasher: Hasher, final_tally: FinalTally) -> Transaction {
+        let bytes = bincode::serialize(&(TransactionType::FinalTally, &final_tally)).unwrap();
+        let digest = hasher.digest(&bytes);
+
+        Transaction {
+            identifier: digest,
+            trx_type: TransactionType::FinalTally,
+            data: None,
+            metadata: None,
+            sealer_vote: None,
+            phase_change: None,
+            decryption_share: None,
+            dkg_commitment: None,
+            election_key: None,
+            final_tally: Some(final_tally),
+            voter_registration: None,
+        }
+    }
+
+    /// Create the transaction registering or revoking a voter with the given details,
+    /// depending on the given transaction type, e.g. signed by the election authority.
+    pub fn from_voter_registration(hasher: Hasher, trx_type: TransactionType, voter_registration: VoterRegistration) -> Transaction {
+        let digest = hasher.digest(&voter_registration.get_signed_message(&trx_type));
+
+        Transaction {
+            identifier: digest,
+            trx_type,
+            data: None,
+            metadata: None,
+            sealer_vote: None,
+            phase_change: None,
+            decryption_share: None,
+            dkg_commitment: None,
+            election_key: None,
+            final_tally: None,
+            voter_registration: Some(voter_registration),
+        }
+    }
+
+    /// Attach the given metadata entries to this transaction,
+    /// replacing any previously attached metadata.
+    pub fn with_metadata(mut self, entries: BTreeMap<String, String>) -> Transaction {
+        self.metadata = Some(TransactionMetadata::new(&self.identifier, entries));
+
+        self
+    }
+
+    /// Attach the given eligibility credential to this vote, deriving its identifier anew
+    /// with the same hash function. Attach it before any metadata, which is bound to the identifier.
+    /// Other transactions are returned unchanged.
+    pub fn with_credential(mut self, credential: EligibilityCredential) -> Transaction {
+        let hasher = Hasher::from_identifier(&self.identifier).unwrap_or_default();
+        if let Some(ref mut trx_data) = self.data {
+            trx_data.credential = Some(credential);
+            self.identifier = hasher.digest(&bincode::serialize(trx_data).unwrap());
+        }
+
+        self
+    }
+
     /// Verify whether the proofs submitted along with the transaction
     /// are valid with respect to the proofs submitted along with it.
+    /// Verification is delegated to the kind registered for the type of this transaction.
     ///
     /// - public_key: The public key used to encrypt the vote
     /// - image_sets: The set of all voters' images
-    pub fn is_valid(&self, public_key: PublicKey, image_sets: Vec<ImageSet>) -> bool {
-        if TransactionType::Vote != self.trx_type {
-            trace!("Considering vote of type {:?} as valid", self.trx_type);
-            return true;
-        }
-
-        let voting_options: Vec<ModInt> = vec![
-            ModInt::from_value(BigInt::one()),
-            ModInt::from_value(BigInt::zero())
-        ];
-
-        trace!("Verifying membership proof...");
-        let is_membership_proof_valid = self.data.clone().unwrap().membership_proof.verify(public_key.clone(), self.data.clone().unwrap().cipher_text.clone(), voting_options.clone());
-        trace!("Is membership proof valid: {:?}", is_membership_proof_valid);
-
-        trace!("Retrieving public UCIV for voter index {}", self.data.clone().unwrap().voter_idx);
-        let image_set_option = image_sets.get(self.data.clone().unwrap().voter_idx as usize);
-        let image_set: ImageSet = match image_set_option {
-            Some(image_set) => {
-                image_set.clone()
-            },
-            None => {
-                error!("Could not find voter_index {} in public UCIV information. Transaction is invalid", self.data.clone().unwrap().voter_idx);
-                return false;
-            }
-        };
-
-        // If the image set has not an equal number of voting options
-        // this is considered a configuration error.
-        assert_eq!(image_set.images.len(), voting_options.len(), "The set of voting options and images of a voter must be equal");
-
-        trace!("Verifying cast-as-intended proof...");
-        let is_cai_proof_valid = self.data.clone().unwrap().cai_proof.verify(public_key, self.data.clone().unwrap().cipher_text.clone(), image_set, voting_options);
-        trace!("Is cast-as-intended proof valid: {:?}", is_cai_proof_valid);
-
-        is_membership_proof_valid && is_cai_proof_valid
+    /// - questions: The set of all voters' images on each further question, by its identifier
+    /// - registrar: The key of the registrar votes must carry a credential of, if any
+    pub fn is_valid(&self, public_key: PublicKey, image_sets: Vec<ImageSet>, questions: &BTreeMap<String, Vec<ImageSet>>, voting_options: &[ModInt], registrar: Option<&RegistrarKey>) -> bool {
+        TransactionRegistry::with_default_kinds().is_valid(self, &public_key, &image_sets, questions, voting_options, registrar)
     }
 }
 
@@ -130,4 +741,115 @@ impl PartialEq for Transaction {
     }
 }
 
-impl Eq for Transaction {}
\ No newline at end of file
+impl Eq for Transaction {}
+
+#[cfg(test)]
+mod transaction_test {
+
+    use super::*;
+
+    #[test]
+    fn test_metadata() {
+        let mut entries = BTreeMap::new();
+        entries.insert("ballot-style".to_string(), "municipal-2".to_string());
+        entries.insert("client_version".to_string(), "0.3.0".to_string());
+
+        let transaction = Transaction::new_voting_opened(Hasher::default(), "genesis".to_string(), 0).with_metadata(entries.clone());
+        let metadata = transaction.metadata.clone().unwrap();
+        assert_eq!(Ok(()), metadata.verify(&transaction.identifier));
+        // the metadata does not affect the identifier
+        assert_eq!(Transaction::new_voting_opened(Hasher::default(), "genesis".to_string(), 0).identifier, transaction.identifier);
+
+        // legacy identifiers are bound by SHA-1 digests
+        let legacy_transaction = Transaction::new_voting_opened(Hasher::Sha1, "genesis".to_string(), 0).with_metadata(entries.clone());
+        assert_eq!(Ok(()), legacy_transaction.metadata.unwrap().verify(&legacy_transaction.identifier));
+
+        // bound to the transaction
+        assert!(metadata.verify(&Transaction::new_voting_closed(Hasher::default(), "genesis".to_string(), 0).identifier).is_err());
+
+        // tampered entries
+        let mut tampered_metadata = metadata.clone();
+        tampered_metadata.entries.insert("ballot-style".to_string(), "municipal-3".to_string());
+        assert!(tampered_metadata.verify(&transaction.identifier).is_err());
+
+        // size limits
+        let mut oversized_entries = entries.clone();
+        oversized_entries.insert("locale".to_string(), "x".repeat(MAX_METADATA_VALUE_LENGTH + 1));
+        assert!(TransactionMetadata::new(&transaction.identifier, oversized_entries).verify(&transaction.identifier).is_err());
+
+        let query = TransactionQuery {
+            trx_type: Some(TransactionType::VoteOpened),
+            metadata: vec![("ballot-style".to_string(), "municipal-2".to_string())].into_iter().collect(),
+            limit: 10,
+        };
+        assert!(query.matches(&transaction));
+        assert!(!query.matches(&Transaction::new_voting_opened(Hasher::default(), "genesis".to_string(), 0)));
+    }
+
+    #[test]
+    fn test_limits() {
+        let transaction = Transaction::new_voting_opened(Hasher::default(), "genesis".to_string(), 0);
+        assert_eq!(Ok(()), TransactionLimits::default().check(&transaction));
+
+        let size = serde_json::to_string(&transaction).unwrap().len();
+        let limits = TransactionLimits {
+            max_transaction_size: size - 1,
+            max_proof_component_size: DEFAULT_MAX_PROOF_COMPONENT_SIZE,
+        };
+        assert_eq!(Err(LimitViolation::TransactionTooLarge { size, limit: size - 1 }), limits.check(&transaction));
+    }
+
+    #[test]
+    fn test_phase_change() {
+        let opening = Transaction::new_voting_opened(Hasher::default(), "genesis".to_string(), 1);
+        assert!(opening.is_valid_phase_change());
+
+        // each opening and closing has a distinct identifier
+        assert!(opening.identifier != Transaction::new_voting_opened(Hasher::default(), "genesis".to_string(), 2).identifier);
+        assert!(opening.identifier != Transaction::new_voting_opened(Hasher::default(), "other-genesis".to_string(), 1).identifier);
+        assert!(opening.identifier != Transaction::new_voting_closed(Hasher::default(), "genesis".to_string(), 1).identifier);
+
+        // the signature is not hashed into the identifier
+        let mut signed_opening = opening.clone();
+        signed_opening.phase_change.as_mut().unwrap().signature = Some("00".to_string());
+        assert!(signed_opening.is_valid_phase_change());
+
+        let mut tampered_opening = opening.clone();
+        tampered_opening.phase_change.as_mut().unwrap().changed_at = 2;
+        assert!(!tampered_opening.is_valid_phase_change());
+
+        // transactions created by earlier versions lack the details
+        let legacy_opening = Transaction {
+            identifier: Hasher::default().digest(&bincode::serialize(&TransactionType::VoteOpened).unwrap()),
+            trx_type: TransactionType::VoteOpened,
+            data: None,
+            metadata: None,
+            sealer_vote: None,
+            phase_change: None,
+            decryption_share: None,
+            dkg_commitment: None,
+            election_key: None,
+            final_tally: None,
+            voter_registration: None,
+        };
+        assert!(legacy_opening.is_valid_phase_change());
+    }
+
+    #[test]
+    fn test_phase_change_signature() {
+        let election_authority = NodeKey::generate();
+        let phase_change = PhaseChange::new("genesis".to_string(), 1);
+        assert!(phase_change.verify(&TransactionType::VoteOpened, &election_authority.public_key).is_err());
+
+        let signed_phase_change = phase_change.sign(&TransactionType::VoteOpened, &election_authority).unwrap();
+        assert_eq!(Ok(()), signed_phase_change.verify(&TransactionType::VoteOpened, &election_authority.public_key));
+        assert!(Transaction::from_phase_change(Hasher::default(), TransactionType::VoteOpened, signed_phase_change.clone()).is_valid_phase_change());
+
+        // the signature neither opens another election nor closes the voting
+        assert!(signed_phase_change.verify(&TransactionType::VoteClosed, &election_authority.public_key).is_err());
+        let mut foreign_phase_change = signed_phase_change.clone();
+        foreign_phase_change.election = "other-genesis".to_string();
+        assert!(foreign_phase_change.verify(&TransactionType::VoteOpened, &election_authority.public_key).is_err());
+        assert!(signed_phase_change.verify(&TransactionType::VoteOpened, &NodeKey::generate().public_key).is_err());
+    }
+}
@@ -0,0 +1,566 @@
+use ::chain::hasher::Hasher;
+use ::chain::transaction::{Transaction, TransactionType, VoterRegistration};
+use ::credential::RegistrarKey;
+use ::signer::key;
+use crypto_rs::arithmetic::mod_int::From;
+use crypto_rs::arithmetic::mod_int::ModInt;
+use crypto_rs::cai::uciv::ImageSet;
+use crypto_rs::el_gamal::additive::Operate;
+use crypto_rs::el_gamal::ciphertext::CipherText;
+use crypto_rs::el_gamal::encryption::PublicKey;
+use num::{BigInt, One, Zero};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Determines which vote counts if a voter votes several times on the same question.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum RevotePolicy {
+    /// The oldest vote of a voter counts, further votes are rejected when submitted.
+    #[default]
+    FirstVoteCounts,
+    /// The newest vote of a voter counts, replacing the previous ones.
+    LastVoteCounts,
+}
+
+/// The running sum of the votes on a single question.
+#[derive(Clone)]
+pub struct QuestionTallyState {
+    pub sum_cipher_text: CipherText,
+    pub total_votes: usize,
+    pub traversed_vote_idx: HashSet<usize>,
+    /// The oldest vote of each voter traversed so far, if the first vote of a voter counts.
+    /// These are only added to the sum once all votes are traversed, see `get_votes`.
+    pub earliest_votes: BTreeMap<usize, CipherText>,
+}
+
+impl QuestionTallyState {
+    /// Returns the amount and the sum of the counted votes, including the oldest vote of each voter in `earliest_votes`.
+    pub fn get_votes(&self) -> (usize, CipherText) {
+        let sum_cipher_text = self.earliest_votes.values()
+            .fold(self.sum_cipher_text.clone(), |sum_cipher_text, cipher_text| sum_cipher_text.operate(cipher_text.clone()));
+
+        (self.total_votes + self.earliest_votes.len(), sum_cipher_text)
+    }
+}
+
+/// The intermediate state of a tally, built up by applying
+/// the transactions of the chain from the newest block to the oldest one.
+pub struct TallyState {
+    pub is_voting_opened: bool,
+    pub is_voting_closed: bool,
+    /// The running sum of the votes on each question, by its identifier,
+    /// where None identifies the question whose voters are declared in `public_uciv.json`.
+    pub questions: BTreeMap<Option<String>, QuestionTallyState>,
+    /// Which vote counts if a voter votes several times on the same question.
+    pub revote_policy: RevotePolicy,
+    zero_cipher_text: CipherText,
+}
+
+impl TallyState {
+    /// Create a new tally state.
+    ///
+    /// - zero_cipher_text: The encryption of zero, acting as neutral element of the sum.
+    /// - revote_policy: Which vote counts if a voter votes several times on the same question.
+    pub fn new(zero_cipher_text: CipherText, revote_policy: RevotePolicy) -> TallyState {
+        TallyState {
+            is_voting_opened: false,
+            is_voting_closed: true,
+            questions: BTreeMap::new(),
+            revote_policy,
+            zero_cipher_text,
+        }
+    }
+
+    /// Returns the running sum of the votes on the question with the given identifier,
+    /// starting with the encryption of zero if no vote on it was applied yet.
+    pub fn question(&mut self, question_id: Option<String>) -> &mut QuestionTallyState {
+        let zero_cipher_text = &self.zero_cipher_text;
+
+        self.questions.entry(question_id).or_insert_with(|| QuestionTallyState {
+            sum_cipher_text: zero_cipher_text.clone(),
+            total_votes: 0,
+            traversed_vote_idx: HashSet::new(),
+            earliest_votes: BTreeMap::new(),
+        })
+    }
+}
+
+/// A transaction kind implements the behaviour specific to a particular `TransactionType`.
+///
+/// Adding a new type of transaction requires a new variant of `TransactionType`
+/// and a corresponding kind registered in the `TransactionRegistry`.
+pub trait TransactionKind: Send + Sync {
+    /// The type of transactions handled by this kind.
+    fn transaction_type(&self) -> TransactionType;
+
+    /// Verify whether the given transaction is valid.
+    ///
+    /// - transaction: The transaction to verify, having the type of this kind.
+    /// - public_key: The public key used to encrypt votes
+    /// - image_sets: The set of all voters' images
+    /// - voting_options: The plaintexts a vote may encrypt, in the order of the images
+    fn is_valid(&self, transaction: &Transaction, public_key: &PublicKey, image_sets: &[ImageSet], voting_options: &[ModInt]) -> bool;
+
+    /// Apply the given transaction to the tally.
+    /// Transactions are applied from the newest block to the oldest one.
+    ///
+    /// - transaction: The transaction to apply, having the type of this kind.
+    /// - tally: The tally to update.
+    fn apply_to_tally(&self, transaction: &Transaction, tally: &mut TallyState);
+}
+
+/// A registry of all known transaction kinds.
+pub struct TransactionRegistry {
+    kinds: HashMap<TransactionType, Box<TransactionKind>>,
+}
+
+impl TransactionRegistry {
+    /// Create a new registry without any registered kinds.
+    pub fn new() -> TransactionRegistry {
+        TransactionRegistry {
+            kinds: HashMap::new()
+        }
+    }
+
+    /// Create a new registry containing the kinds of all
+    /// transaction types known to this node.
+    pub fn with_default_kinds() -> TransactionRegistry {
+        let mut registry = TransactionRegistry::new();
+        registry.register(Box::new(VoteKind {}));
+        registry.register(Box::new(VoteOpenedKind {}));
+        registry.register(Box::new(VoteClosedKind {}));
+        registry.register(Box::new(SealerVoteKind {}));
+        registry.register(Box::new(DecryptionShareKind {}));
+        registry.register(Box::new(DkgCommitmentKind {}));
+        registry.register(Box::new(ElectionKeyKind {}));
+        registry.register(Box::new(FinalTallyKind {}));
+        registry.register(Box::new(VoterRegisteredKind {}));
+        registry.register(Box::new(VoterRevokedKind {}));
+
+        registry
+    }
+
+    /// Register the given kind, replacing any kind
+    /// previously registered for the same transaction type.
+    pub fn register(&mut self, kind: Box<TransactionKind>) {
+        self.kinds.insert(kind.transaction_type(), kind);
+    }
+
+    /// Get the kind registered for the given transaction type.
+    pub fn get(&self, trx_type: &TransactionType) -> Option<&TransactionKind> {
+        self.kinds.get(trx_type).map(|kind| kind.as_ref())
+    }
+
+    /// Verify the given transaction using the kind registered for its type.
+    /// Transactions of an unregistered type or with invalid metadata are invalid,
+    /// as are votes on questions not declared in the genesis configuration.
+    /// If a registrar is declared, votes must carry a credential signed by it for their voter, otherwise none.
+    ///
+    /// - image_sets: The set of all voters' images on the question declared by `public_uciv.json`
+    /// - questions: The set of all voters' images on each further question, by its identifier
+    /// - voting_options: The plaintexts a vote may encrypt, as declared in the genesis configuration
+    /// - registrar: The key of the registrar blind-signing the credentials of the voters, if any
+    pub fn is_valid(&self, transaction: &Transaction, public_key: &PublicKey, image_sets: &[ImageSet], questions: &BTreeMap<String, Vec<ImageSet>>, voting_options: &[ModInt], registrar: Option<&RegistrarKey>) -> bool {
+        if let Some(ref metadata) = transaction.metadata {
+            if let Err(e) = metadata.verify(&transaction.identifier) {
+                warn!("Transaction {:?} has invalid metadata: {}", transaction.identifier, e);
+                return false;
+            }
+        }
+
+        // checked before the proofs, as verifying the signature is cheaper
+        if let Some(trx_data) = transaction.data.as_ref().filter(|_| TransactionType::Vote == transaction.trx_type) {
+            let eligibility = match (registrar, trx_data.credential.as_ref()) {
+                (Some(registrar), Some(credential)) => registrar.verify(&trx_data.question_id, trx_data.voter_idx, credential),
+                (Some(_), None) => Err("Vote lacks the credential of the registrar".to_string()),
+                (None, Some(_)) => Err("Vote carries a credential, but no registrar is declared".to_string()),
+                (None, None) => Ok(())
+            };
+            if let Err(e) = eligibility {
+                warn!("Vote {:?} is not eligible: {}", transaction.identifier, e);
+                return false;
+            }
+        }
+
+        // votes are verified against the voters' images on the question they answer
+        let image_sets = match transaction.data.as_ref().and_then(|trx_data| trx_data.question_id.as_ref()) {
+            Some(question_id) => match questions.get(question_id) {
+                Some(question_image_sets) => question_image_sets,
+                None => {
+                    warn!("Vote {:?} answers the undeclared question {:?}. Transaction is invalid", transaction.identifier, question_id);
+                    return false;
+                }
+            },
+            None => image_sets
+        };
+
+        match self.get(&transaction.trx_type) {
+            Some(kind) => kind.is_valid(transaction, public_key, image_sets, voting_options),
+            None => {
+                warn!("No transaction kind registered for type {:?}. Transaction {:?} is invalid", transaction.trx_type, transaction.identifier);
+                false
+            }
+        }
+    }
+
+    /// Apply the given transaction to the tally using the kind registered for its type.
+    /// Transactions of an unregistered type are skipped.
+    pub fn apply_to_tally(&self, transaction: &Transaction, tally: &mut TallyState) {
+        match self.get(&transaction.trx_type) {
+            Some(kind) => kind.apply_to_tally(transaction, tally),
+            None => {
+                warn!("No transaction kind registered for type {:?}. Skipping transaction {:?}", transaction.trx_type, transaction.identifier);
+            }
+        }
+    }
+}
+
+impl Default for TransactionRegistry {
+    fn default() -> Self {
+        TransactionRegistry::new()
+    }
+}
+
+/// Returns the plaintexts a vote may encrypt by default, in the order of the images of the UCIV information:
+/// one in favour, zero against. Elections with other options declare them in the genesis configuration.
+pub fn voting_options() -> Vec<ModInt> {
+    vec![
+        ModInt::from_value(BigInt::one()),
+        ModInt::from_value(BigInt::zero())
+    ]
+}
+
+/// An encrypted vote along with its proofs.
+pub struct VoteKind {}
+
+impl TransactionKind for VoteKind {
+    fn transaction_type(&self) -> TransactionType {
+        TransactionType::Vote
+    }
+
+    fn is_valid(&self, transaction: &Transaction, public_key: &PublicKey, image_sets: &[ImageSet], voting_options: &[ModInt]) -> bool {
+        let trx_data = match transaction.data {
+            Some(ref trx_data) => trx_data,
+            None => {
+                warn!("Vote {:?} does not contain any data. Transaction is invalid", transaction.identifier);
+                return false;
+            }
+        };
+
+        trace!("Retrieving public UCIV for voter index {}", trx_data.voter_idx);
+        let image_set: ImageSet = match image_sets.get(trx_data.voter_idx) {
+            Some(image_set) => {
+                image_set.clone()
+            },
+            None => {
+                error!("Could not find voter_index {} in public UCIV information. Transaction is invalid", trx_data.voter_idx);
+                return false;
+            }
+        };
+
+        // revoked voters and indices never registered hold no images, see `VoterRegistryVisitor::get_image_sets`
+        if image_set.images.is_empty() {
+            warn!("Voter with index {} is not registered. Transaction is invalid", trx_data.voter_idx);
+            return false;
+        }
+
+        // registrations may hold images for another amount of options than the genesis configuration declares
+        if image_set.images.len() != voting_options.len() {
+            warn!("Voter with index {} holds {} images for {} voting options. Transaction is invalid", trx_data.voter_idx, image_set.images.len(), voting_options.len());
+            return false;
+        }
+
+        trace!("Verifying membership proof...");
+        let is_membership_proof_valid = trx_data.membership_proof.verify(public_key.clone(), trx_data.cipher_text.clone(), voting_options.to_vec());
+        trace!("Is membership proof valid: {:?}", is_membership_proof_valid);
+
+        trace!("Verifying cast-as-intended proof...");
+        let is_cai_proof_valid = trx_data.cai_proof.verify(public_key.clone(), trx_data.cipher_text.clone(), image_set, voting_options.to_vec());
+        trace!("Is cast-as-intended proof valid: {:?}", is_cai_proof_valid);
+
+        is_membership_proof_valid && is_cai_proof_valid
+    }
+
+    fn apply_to_tally(&self, transaction: &Transaction, tally: &mut TallyState) {
+        // chain is traversed bottom up, so check first whether the voting
+        // was closed at the end.
+        if !tally.is_voting_closed {
+            warn!("Skipping to count vote in transaction {:?} as voting was not yet closed", transaction.identifier.clone());
+            return;
+        }
+
+        let trx_data = transaction.data.clone().unwrap();
+        let revote_policy = tally.revote_policy;
+        let question = tally.question(trx_data.question_id.clone());
+        if RevotePolicy::FirstVoteCounts == revote_policy {
+            // an older vote of the same voter is traversed later, replacing this one
+            match question.earliest_votes.insert(trx_data.voter_idx, trx_data.cipher_text) {
+                Some(_) => info!("Voter with index {:?} has voted again later. Counting the older vote in transaction {:?}", trx_data.voter_idx, transaction.identifier.clone()),
+                None => info!("Counting vote in transaction {:?}", transaction.identifier.clone())
+            }
+            return;
+        }
+
+        // check whether we already counted a newer vote for the same voter on the same question
+        if question.traversed_vote_idx.contains(&trx_data.voter_idx) {
+            info!("Voter with index {:?} has voted already. Ignoring transaction {:?}", trx_data.voter_idx, transaction.identifier.clone())
+        } else {
+            info!("Counting vote in transaction {:?}", transaction.identifier.clone());
+            question.sum_cipher_text = question.sum_cipher_text.clone().operate(trx_data.cipher_text);
+            question.total_votes += 1;
+            question.traversed_vote_idx.insert(trx_data.voter_idx);
+        }
+    }
+}
+
+/// Marks the opening of the voting.
+pub struct VoteOpenedKind {}
+
+impl TransactionKind for VoteOpenedKind {
+    fn transaction_type(&self) -> TransactionType {
+        TransactionType::VoteOpened
+    }
+
+    fn is_valid(&self, transaction: &Transaction, _public_key: &PublicKey, _image_sets: &[ImageSet], _voting_options: &[ModInt]) -> bool {
+        transaction.is_valid_phase_change()
+    }
+
+    fn apply_to_tally(&self, transaction: &Transaction, tally: &mut TallyState) {
+        info!("Found open vote transaction {:?}", transaction.identifier.clone());
+        tally.is_voting_opened = true
+    }
+}
+
+/// Marks the closing of the voting.
+pub struct VoteClosedKind {}
+
+impl TransactionKind for VoteClosedKind {
+    fn transaction_type(&self) -> TransactionType {
+        TransactionType::VoteClosed
+    }
+
+    fn is_valid(&self, transaction: &Transaction, _public_key: &PublicKey, _image_sets: &[ImageSet], _voting_options: &[ModInt]) -> bool {
+        transaction.is_valid_phase_change()
+    }
+
+    fn apply_to_tally(&self, transaction: &Transaction, tally: &mut TallyState) {
+        info!("Found close vote transaction {:?}", transaction.identifier.clone());
+        tally.is_voting_closed = true
+    }
+}
+
+/// Casts the vote of a sealer on adding or removing a sealer.
+/// Whether the vote counts depends on the block including it and the sealers at its height,
+/// see `SealerSet`, hence only its form is verified here.
+pub struct SealerVoteKind {}
+
+impl TransactionKind for SealerVoteKind {
+    fn transaction_type(&self) -> TransactionType {
+        TransactionType::SealerVote
+    }
+
+    fn is_valid(&self, transaction: &Transaction, _public_key: &PublicKey, _image_sets: &[ImageSet], _voting_options: &[ModInt]) -> bool {
+        let sealer_vote = match transaction.sealer_vote {
+            Some(ref sealer_vote) => sealer_vote,
+            None => {
+                warn!("Sealer vote {:?} does not contain a vote. Transaction is invalid", transaction.identifier);
+                return false;
+            }
+        };
+
+        if let Some(ref public_key) = sealer_vote.public_key {
+            if !key::is_valid_public_key(public_key) {
+                warn!("Sealer vote {:?} contains a malformed public key. Transaction is invalid", transaction.identifier);
+                return false;
+            }
+        }
+
+        match Hasher::from_identifier(&transaction.identifier) {
+            Some(hasher) => Transaction::new_sealer_vote(hasher, sealer_vote.clone()).identifier == transaction.identifier,
+            None => false
+        }
+    }
+
+    fn apply_to_tally(&self, _transaction: &Transaction, _tally: &mut TallyState) {
+        // votes on sealers do not affect the election
+    }
+}
+
+/// Publishes the partial decryption of the sum of the votes by a sealer.
+/// Its proof depends on the sum of the votes on the chain including it and on the public key
+/// share of the sealer, hence it is verified when the shares are combined, see `threshold::combine`,
+/// and only its form is verified here.
+pub struct DecryptionShareKind {}
+
+impl TransactionKind for DecryptionShareKind {
+    fn transaction_type(&self) -> TransactionType {
+        TransactionType::DecryptionShare
+    }
+
+    fn is_valid(&self, transaction: &Transaction, _public_key: &PublicKey, _image_sets: &[ImageSet], _voting_options: &[ModInt]) -> bool {
+        let decryption_share = match transaction.decryption_share {
+            Some(ref decryption_share) => decryption_share,
+            None => {
+                warn!("Decryption share {:?} does not contain a share. Transaction is invalid", transaction.identifier);
+                return false;
+            }
+        };
+
+        match Hasher::from_identifier(&transaction.identifier) {
+            Some(hasher) => Transaction::new_decryption_share(hasher, decryption_share.clone()).identifier == transaction.identifier,
+            None => false
+        }
+    }
+
+    fn apply_to_tally(&self, _transaction: &Transaction, _tally: &mut TallyState) {
+        // decryption shares do not affect the sum of the votes
+    }
+}
+
+/// Publishes the commitments of a dealer of the election key.
+/// Whether the dealer is a sealer which did not deal before depends on the chain including it,
+/// see `BlockValidator::validate_against_chain`, hence only its form is verified here.
+pub struct DkgCommitmentKind {}
+
+impl TransactionKind for DkgCommitmentKind {
+    fn transaction_type(&self) -> TransactionType {
+        TransactionType::DkgCommitment
+    }
+
+    fn is_valid(&self, transaction: &Transaction, _public_key: &PublicKey, _image_sets: &[ImageSet], _voting_options: &[ModInt]) -> bool {
+        let dkg_commitment = match transaction.dkg_commitment {
+            Some(ref dkg_commitment) => dkg_commitment,
+            None => {
+                warn!("Commitment {:?} does not contain commitments. Transaction is invalid", transaction.identifier);
+                return false;
+            }
+        };
+
+        match Hasher::from_identifier(&transaction.identifier) {
+            Some(hasher) => Transaction::new_dkg_commitment(hasher, dkg_commitment.clone()).identifier == transaction.identifier,
+            None => false
+        }
+    }
+
+    fn apply_to_tally(&self, _transaction: &Transaction, _tally: &mut TallyState) {
+        // the key generation does not affect the sum of the votes
+    }
+}
+
+/// Commits the election key generated jointly by the sealers.
+/// Whether it matches the commitments of all dealers depends on the chain including it,
+/// see `BlockValidator::validate_against_chain`, hence only its form is verified here.
+pub struct ElectionKeyKind {}
+
+impl TransactionKind for ElectionKeyKind {
+    fn transaction_type(&self) -> TransactionType {
+        TransactionType::ElectionKey
+    }
+
+    fn is_valid(&self, transaction: &Transaction, _public_key: &PublicKey, _image_sets: &[ImageSet], _voting_options: &[ModInt]) -> bool {
+        let election_key = match transaction.election_key {
+            Some(ref election_key) => election_key,
+            None => {
+                warn!("Election key {:?} does not contain a key. Transaction is invalid", transaction.identifier);
+                return false;
+            }
+        };
+
+        match Hasher::from_identifier(&transaction.identifier) {
+            Some(hasher) => Transaction::new_election_key(hasher, election_key.clone()).identifier == transaction.identifier,
+            None => false
+        }
+    }
+
+    fn apply_to_tally(&self, _transaction: &Transaction, _tally: &mut TallyState) {
+        // the election key does not affect the sum of the votes
+    }
+}
+
+/// Commits the final tally of a question once the voting is closed.
+/// Whether it matches the votes and decryption shares depends on the chain including it,
+/// see `BlockValidator::validate_against_chain`, hence only its form is verified here.
+pub struct FinalTallyKind {}
+
+impl TransactionKind for FinalTallyKind {
+    fn transaction_type(&self) -> TransactionType {
+        TransactionType::FinalTally
+    }
+
+    fn is_valid(&self, transaction: &Transaction, _public_key: &PublicKey, _image_sets: &[ImageSet], _voting_options: &[ModInt]) -> bool {
+        let final_tally = match transaction.final_tally {
+            Some(ref final_tally) => final_tally,
+            None => {
+                warn!("Final tally {:?} does not contain a tally. Transaction is invalid", transaction.identifier);
+                return false;
+            }
+        };
+
+        match Hasher::from_identifier(&transaction.identifier) {
+            Some(hasher) => Transaction::new_final_tally(hasher, final_tally.clone()).identifier == transaction.identifier,
+            None => false
+        }
+    }
+
+    fn apply_to_tally(&self, _transaction: &Transaction, _tally: &mut TallyState) {
+        // the final tally is derived from the sum of the votes, but does not affect it
+    }
+}
+
+/// Registers a voter by the election authority, if the feature `voter-registry` is enabled.
+/// Whether the voter is registered already and the signature of the election authority
+/// are checked by `BlockValidator`, hence only its form is verified here.
+pub struct VoterRegisteredKind {}
+
+impl TransactionKind for VoterRegisteredKind {
+    fn transaction_type(&self) -> TransactionType {
+        TransactionType::VoterRegistered
+    }
+
+    fn is_valid(&self, transaction: &Transaction, _public_key: &PublicKey, _image_sets: &[ImageSet], voting_options: &[ModInt]) -> bool {
+        let image_set = match transaction.voter_registration {
+            Some(VoterRegistration { image_set: Some(ref image_set), .. }) => image_set,
+            _ => {
+                warn!("Registration {:?} does not contain the images of a voter. Transaction is invalid", transaction.identifier);
+                return false;
+            }
+        };
+
+        // each voter holds an image per voting option
+        if image_set.images.len() != voting_options.len() {
+            warn!("Registration {:?} holds {} images, but there are {} voting options. Transaction is invalid", transaction.identifier, image_set.images.len(), voting_options.len());
+            return false;
+        }
+
+        transaction.is_valid_voter_registration()
+    }
+
+    fn apply_to_tally(&self, _transaction: &Transaction, _tally: &mut TallyState) {
+        // votes are verified against the registered voters when received, but registering does not affect their sum
+    }
+}
+
+/// Revokes the registration of a voter by the election authority, if the feature `voter-registry` is enabled.
+/// Whether the voter is registered and the signature of the election authority
+/// are checked by `BlockValidator`, hence only its form is verified here.
+pub struct VoterRevokedKind {}
+
+impl TransactionKind for VoterRevokedKind {
+    fn transaction_type(&self) -> TransactionType {
+        TransactionType::VoterRevoked
+    }
+
+    fn is_valid(&self, transaction: &Transaction, _public_key: &PublicKey, _image_sets: &[ImageSet], _voting_options: &[ModInt]) -> bool {
+        match transaction.voter_registration {
+            Some(VoterRegistration { image_set: None, .. }) => transaction.is_valid_voter_registration(),
+            _ => {
+                warn!("Revocation {:?} does not name a voter only. Transaction is invalid", transaction.identifier);
+                false
+            }
+        }
+    }
+
+    fn apply_to_tally(&self, _transaction: &Transaction, _tally: &mut TallyState) {
+        // votes cast before the revocation still count
+    }
+}
@@ -0,0 +1,182 @@
+use std::borrow::Borrow;
+use std::fmt;
+use std::ops::{Add, AddAssign, Sub, SubAssign};
+
+/// The height of a block, i.e. its distance to the genesis block,
+/// which has height zero.
+///
+/// Encoded as a plain number, hence interchangeable with heights
+/// encoded by earlier versions of this node.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize, Clone, Copy, Default)]
+pub struct Height(usize);
+
+impl Height {
+    /// The height of the genesis block.
+    pub const GENESIS: Height = Height(0);
+
+    /// Create a new height.
+    pub fn new(height: usize) -> Height {
+        Height(height)
+    }
+
+    /// Returns the height as plain number, e.g. to index a vector.
+    pub fn as_usize(self) -> usize {
+        self.0
+    }
+
+    /// Returns the amount of blocks between this height and the given lower one,
+    /// or zero if the given height is not lower.
+    pub fn distance_to(self, lower: Height) -> usize {
+        self.0.saturating_sub(lower.0)
+    }
+
+    /// Returns the height the given amount of blocks below,
+    /// or the height of the genesis block if there are not as many blocks below.
+    pub fn saturating_sub(self, blocks: usize) -> Height {
+        Height(self.0.saturating_sub(blocks))
+    }
+}
+
+impl From<usize> for Height {
+    fn from(height: usize) -> Height {
+        Height(height)
+    }
+}
+
+impl Add<usize> for Height {
+    type Output = Height;
+
+    fn add(self, blocks: usize) -> Height {
+        Height(self.0 + blocks)
+    }
+}
+
+impl AddAssign<usize> for Height {
+    fn add_assign(&mut self, blocks: usize) {
+        self.0 += blocks;
+    }
+}
+
+impl Sub<usize> for Height {
+    type Output = Height;
+
+    fn sub(self, blocks: usize) -> Height {
+        Height(self.0 - blocks)
+    }
+}
+
+impl SubAssign<usize> for Height {
+    fn sub_assign(&mut self, blocks: usize) {
+        self.0 -= blocks;
+    }
+}
+
+impl fmt::Display for Height {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Debug for Height {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+/// The identifier of a block, i.e. the versioned hex encoded hash of its content.
+///
+/// Encoded as a plain string, hence neither the encoding of blocks
+/// nor their identifiers differ from those of earlier versions of this node.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize, Clone, Default)]
+pub struct BlockHash(String);
+
+impl BlockHash {
+    /// Create a new block hash from its hex encoding.
+    pub fn new(hash: String) -> BlockHash {
+        BlockHash(hash)
+    }
+
+    /// Returns the hex encoding of the hash.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns true, if this is the empty hash referenced as parent by the genesis block.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for BlockHash {
+    fn from(hash: String) -> BlockHash {
+        BlockHash(hash)
+    }
+}
+
+impl<'a> From<&'a str> for BlockHash {
+    fn from(hash: &'a str) -> BlockHash {
+        BlockHash(hash.to_string())
+    }
+}
+
+impl Borrow<str> for BlockHash {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for BlockHash {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl<'a> PartialEq<&'a str> for BlockHash {
+    fn eq(&self, other: &&'a str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl fmt::Display for BlockHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Debug for BlockHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+#[cfg(test)]
+mod types_test {
+
+    use super::*;
+    use serde_json;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_encoding() {
+        // encoded like the plain values used by earlier versions
+        assert_eq!("42", serde_json::to_string(&Height::new(42)).unwrap());
+        assert_eq!("\"ab12\"", serde_json::to_string(&BlockHash::from("ab12")).unwrap());
+
+        let mut blocks = HashMap::new();
+        blocks.insert(BlockHash::from("ab12"), Height::new(1));
+        let encoded_blocks = serde_json::to_string(&blocks).unwrap();
+        assert_eq!("{\"ab12\":1}", encoded_blocks);
+        assert_eq!(blocks, serde_json::from_str(&encoded_blocks).unwrap());
+        assert_eq!(Some(&Height::new(1)), blocks.get("ab12"));
+    }
+
+    #[test]
+    fn test_height_arithmetic() {
+        let height = Height::GENESIS + 5;
+        assert_eq!(Height::new(3), height - 2);
+        assert_eq!(5, height.distance_to(Height::GENESIS));
+        assert_eq!(0, Height::GENESIS.distance_to(height));
+        assert_eq!(Height::GENESIS, height.saturating_sub(7));
+        assert_eq!("5", height.to_string());
+    }
+}
@@ -0,0 +1,189 @@
+use ::archive;
+use ::chain::block::Block;
+use ::chain::transaction::Transaction;
+use serde_json;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// The name of the file in the data directory holding the log.
+pub const WAL_FILE_NAME: &str = "wal.jsonl";
+
+/// A change to the state of a node, logged before it is applied.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub enum WalEntry {
+    /// A block about to be added to the chain.
+    Block(Block),
+    /// A transaction about to be buffered until it is included in a block.
+    Transaction {
+        transaction: Box<Transaction>,
+        /// The local time of arrival, in milliseconds since the epoch.
+        arrived_at: u64,
+    },
+}
+
+/// A write-ahead log, to which blocks and buffered transactions are appended
+/// before they are applied, so that they survive a crash of the node.
+///
+/// The log is replayed on startup. Blocks are only logged until they are persisted
+/// in a `BlockStore`, hence the log is compacted to the transactions still buffered
+/// whenever a block was stored.
+///
+/// Each line of the file holds a JSON encoded entry, which is synced to disk before
+/// it is acknowledged. An entry only partially written due to a crash is discarded
+/// when the log is opened again.
+pub struct WriteAheadLog {
+    /// The path of the file holding the log.
+    path: PathBuf,
+    /// The file opened for appending.
+    file: File,
+}
+
+impl WriteAheadLog {
+    /// Open the log in the given data directory, creating the directory and the log if necessary.
+    /// Returns an error if the data directory is sealed as archive.
+    pub fn open(data_directory: &Path) -> Result<WriteAheadLog, String> {
+        if archive::is_sealed(data_directory) {
+            return Err(format!("Data directory {:?} is sealed as archive, refusing to write to it", data_directory));
+        }
+
+        match fs::create_dir_all(data_directory) {
+            Ok(()) => {}
+            Err(e) => {
+                return Err(format!("Failed to create data directory {:?}: {:?}", data_directory, e));
+            }
+        }
+
+        let path = data_directory.join(WAL_FILE_NAME);
+        let file = match OpenOptions::new().create(true).append(true).open(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                return Err(format!("Failed to open write-ahead log {:?}: {:?}", path, e));
+            }
+        };
+
+        let mut contents = String::new();
+        let read_result = File::open(&path).and_then(|mut file| file.read_to_string(&mut contents));
+        match read_result {
+            Ok(_) => {}
+            Err(e) => {
+                return Err(format!("Failed to read write-ahead log {:?}: {:?}", path, e));
+            }
+        }
+
+        // discard an entry which was only partially written
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            let complete_length = contents.rfind('\n').map_or(0, |index| index + 1);
+            warn!("Discarding {} bytes of a partially written entry in {:?}", contents.len() - complete_length, path);
+
+            match file.set_len(complete_length as u64) {
+                Ok(()) => {}
+                Err(e) => {
+                    return Err(format!("Failed to discard partially written entry in {:?}: {:?}", path, e));
+                }
+            }
+        }
+
+        Ok(WriteAheadLog {
+            path,
+            file,
+        })
+    }
+
+    /// Returns all logged entries in the order they were appended.
+    pub fn replay(&self) -> Result<Vec<WalEntry>, String> {
+        let mut contents = String::new();
+        let read_result = File::open(&self.path).and_then(|mut file| file.read_to_string(&mut contents));
+        match read_result {
+            Ok(_) => {}
+            Err(e) => {
+                return Err(format!("Failed to read write-ahead log {:?}: {:?}", self.path, e));
+            }
+        }
+
+        let mut entries = vec![];
+        for (index, line) in contents.lines().enumerate() {
+            match serde_json::from_str(line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => {
+                    return Err(format!("Failed to parse entry {} of write-ahead log {:?}: {:?}", index, self.path, e));
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Append the given entry and sync it to disk.
+    pub fn append(&mut self, entry: &WalEntry) -> Result<(), String> {
+        let mut line = serde_json::to_string(entry).unwrap();
+        line.push('\n');
+
+        match self.file.write_all(line.as_bytes()).and_then(|_| self.file.sync_data()) {
+            Ok(()) => Ok(()),
+            Err(e) => Err(format!("Failed to append to write-ahead log {:?}: {:?}", self.path, e))
+        }
+    }
+
+    /// Replace all logged entries by the given ones.
+    pub fn compact(&mut self, entries: &[WalEntry]) -> Result<(), String> {
+        let mut contents = String::new();
+        for entry in entries {
+            contents.push_str(&serde_json::to_string(entry).unwrap());
+            contents.push('\n');
+        }
+
+        // replace the file atomically, so that a crash does not lose any entry
+        let temporary_path = self.path.with_extension("tmp");
+        let write_result = File::create(&temporary_path)
+            .and_then(|mut file| file.write_all(contents.as_bytes()).and_then(|_| file.sync_all()))
+            .and_then(|_| fs::rename(&temporary_path, &self.path))
+            .and_then(|_| OpenOptions::new().append(true).open(&self.path));
+
+        match write_result {
+            Ok(file) => {
+                self.file = file;
+                Ok(())
+            }
+            Err(e) => Err(format!("Failed to compact write-ahead log {:?}: {:?}", self.path, e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod write_ahead_log_test {
+
+    use super::*;
+    use ::chain::hasher::Hasher;
+    use ::chain::types::BlockHash;
+    use std::env;
+
+    #[test]
+    fn test_write_ahead_log() {
+        let data_directory = env::temp_dir().join(format!("node_rs_write_ahead_log_test_{}", ::std::process::id()));
+        let _ = fs::remove_dir_all(&data_directory);
+
+        let block_entry = WalEntry::Block(Block::new(Hasher::default(), BlockHash::from("genesis"), None, vec![]));
+        let transaction_entry = WalEntry::Transaction {
+            transaction: Box::new(Transaction::new_voting_opened(Hasher::default(), "genesis".to_string(), 0)),
+            arrived_at: 42,
+        };
+
+        let mut wal = WriteAheadLog::open(&data_directory).unwrap();
+        assert_eq!(Ok(vec![]), wal.replay());
+        assert_eq!(Ok(()), wal.append(&block_entry));
+        assert_eq!(Ok(()), wal.append(&transaction_entry));
+
+        // simulate a crash while appending the next entry
+        let path = data_directory.join(WAL_FILE_NAME);
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(b"{\"Transaction\":").unwrap();
+
+        let mut reopened_wal = WriteAheadLog::open(&data_directory).unwrap();
+        assert_eq!(Ok(vec![block_entry, transaction_entry.clone()]), reopened_wal.replay());
+
+        assert_eq!(Ok(()), reopened_wal.compact(&[transaction_entry.clone()]));
+        assert_eq!(Ok(vec![transaction_entry]), reopened_wal.replay());
+
+        let _ = fs::remove_dir_all(&data_directory);
+    }
+}
@@ -0,0 +1,111 @@
+use ::chain::hasher::Hasher;
+use ::chain::transaction::Transaction;
+use ::chain::transaction_registry;
+use crypto_rs::arithmetic::mod_int::ModInt;
+use crypto_rs::cai::uciv::{CaiProof, ImageSet, PreImageSet};
+use crypto_rs::el_gamal::encryption::{PublicKey, encrypt};
+use crypto_rs::el_gamal::membership_proof::MembershipProof;
+
+pub use ::p2p::client::{admin_request, request, rpc_request};
+pub use ::credential::{BlindedCredential, EligibilityCredential, RegistrarKey};
+
+/// The UCIV information of a single voter: its private pre-images
+/// along with the public images the nodes verify its votes against.
+#[derive(Deserialize, Clone)]
+pub struct VoterUciv {
+    pub pre_image_set: PreImageSet,
+    pub image_set: ImageSet,
+}
+
+/// Build a vote ready to be submitted in a `Message::TransactionPayload`.
+///
+/// The choice is encrypted with the public key of the election and accompanied by
+/// the membership and cast-as-intended proofs, exactly as verified by the nodes.
+/// The identifier of the vote is derived with the default hash function.
+/// If the genesis configuration declares a registrar, attach the credential of the voter
+/// with `Transaction::with_credential` before submitting it.
+///
+/// - `public_key` The public key of the election, as in `public_key.json`.
+/// - `uciv` The UCIV information of the voter.
+/// - `voter_idx` The index of the voter in the public UCIV information.
+/// - `choice` The index of the chosen voting option: 0 in favour, 1 against.
+///
+/// Returns an error if the choice or the UCIV information do not match the voting options.
+pub fn build_vote(public_key: &PublicKey, uciv: &VoterUciv, voter_idx: usize, choice: usize) -> Result<Transaction, String> {
+    build_question_vote(public_key, uciv, None, voter_idx, &transaction_registry::voting_options(), choice)
+}
+
+/// Build a vote on the question with the given identifier, as declared in the genesis configuration,
+/// or on the question whose voters are declared in `public_uciv.json` if None.
+///
+/// The UCIV information and the voter index refer to the voters of that question, the choice is
+/// the index of one of the given voting options, which must match those of the genesis configuration.
+/// See `build_vote`.
+pub fn build_question_vote(public_key: &PublicKey, uciv: &VoterUciv, question_id: Option<String>, voter_idx: usize, voting_options: &[ModInt], choice: usize) -> Result<Transaction, String> {
+    if choice >= voting_options.len() {
+        return Err(format!("Choice {} is not one of the {} voting options", choice, voting_options.len()));
+    }
+    if uciv.image_set.images.len() != voting_options.len() || uciv.pre_image_set.pre_images.len() != voting_options.len() {
+        return Err(format!(
+            "Expected UCIV information for {} voting options but got {} images and {} pre-images",
+            voting_options.len(),
+            uciv.image_set.images.len(),
+            uciv.pre_image_set.pre_images.len()
+        ));
+    }
+
+    let message = voting_options[choice].clone();
+    let cipher_text = encrypt(public_key, message.clone());
+
+    Ok(Transaction::new_question_vote(
+        Hasher::default(),
+        question_id,
+        voter_idx,
+        cipher_text.clone(),
+        MembershipProof::new(public_key.clone(), message, cipher_text.clone(), voting_options.to_vec()),
+        CaiProof::new(public_key.clone(), cipher_text, uciv.pre_image_set.clone(), uciv.image_set.clone(), choice, voting_options.to_vec()),
+    ))
+}
+
+#[cfg(test)]
+mod client_test {
+
+    use super::*;
+    use crypto_rs::arithmetic::mod_int::ModInt;
+    use num::One;
+
+    #[test]
+    fn test_build_vote() {
+        let public_key = PublicKey {
+            p: ModInt::one(),
+            q: ModInt::one(),
+            h: ModInt::one(),
+            g: ModInt::one(),
+        };
+        let uciv = VoterUciv {
+            pre_image_set: PreImageSet { pre_images: vec![ModInt::one(), ModInt::one()] },
+            image_set: ImageSet { images: vec![ModInt::one(), ModInt::one()] },
+        };
+
+        let vote = build_vote(&public_key, &uciv, 3, 1).unwrap();
+        assert_eq!(3, vote.data.unwrap().voter_idx);
+        let question_vote = build_question_vote(&public_key, &uciv, Some("q2".to_string()), 3, &transaction_registry::voting_options(), 1).unwrap();
+        assert_eq!(Some("q2".to_string()), question_vote.data.unwrap().question_id);
+
+        assert!(build_vote(&public_key, &uciv, 3, 2).is_err());
+        let single_option_uciv = VoterUciv {
+            pre_image_set: PreImageSet { pre_images: vec![ModInt::one()] },
+            image_set: ImageSet { images: vec![ModInt::one()] },
+        };
+        assert!(build_vote(&public_key, &single_option_uciv, 3, 0).is_err());
+
+        // elections may declare more than two voting options
+        let three_options = vec![ModInt::one(); 3];
+        let three_option_uciv = VoterUciv {
+            pre_image_set: PreImageSet { pre_images: three_options.clone() },
+            image_set: ImageSet { images: three_options.clone() },
+        };
+        assert!(build_question_vote(&public_key, &three_option_uciv, None, 3, &three_options, 2).is_ok());
+        assert!(build_vote(&public_key, &three_option_uciv, 3, 2).is_err());
+    }
+}
@@ -0,0 +1,111 @@
+use ::config::genesis::Genesis;
+use ::p2p::peers;
+use ::protocol::clique::NODE_VERSION;
+use std::net::SocketAddr;
+
+/// A summary of the configuration a node is started with, printed as a single line of JSON,
+/// so that operators can cross-check that all sealers run an identical configuration.
+///
+/// The hashes are equal on all nodes of the same network, while the sealer index,
+/// the data directory, the features and the addresses are specific to each node.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct StartupBanner {
+    /// The version of the node software.
+    pub node_version: String,
+    /// The version declared in the genesis configuration.
+    pub genesis_version: String,
+    /// The hash of the genesis configuration, also used in the genesis block.
+    pub genesis_hash: String,
+    /// The SHA-1 digest of the public key.
+    pub public_key_fingerprint: String,
+    /// The SHA-1 digest of the public UCIV information.
+    pub public_uciv_hash: String,
+    /// The index of the node among the sealers, or None if it is not a sealer.
+    pub sealer_index: Option<usize>,
+    /// The amount of sealers of the network.
+    pub sealer_count: usize,
+    /// The directory the chain is persisted in, or None if it is kept in memory only.
+    pub data_dir: Option<String>,
+    /// The features compiled in and the optional behaviour enabled on start, e.g. `sign`.
+    pub features: Vec<String>,
+    /// The address on which the node listens for other nodes.
+    pub listen_address: SocketAddr,
+    /// The address under which other nodes know this node.
+    pub advertised_address: SocketAddr,
+    /// The address on which the node listens for RPC connections.
+    pub rpc_listen_address: SocketAddr,
+    /// The address on which the dashboard is served, if any.
+    pub dashboard_address: Option<SocketAddr>,
+}
+
+impl StartupBanner {
+    /// Summarize the configuration of a node, which does not serve the dashboard.
+    ///
+    /// - `genesis` The genesis configuration of the network.
+    /// - `listen_address` The address on which the node listens for other nodes.
+    /// - `advertised_address` The address under which other nodes know this node.
+    /// - `rpc_listen_address` The address on which the node listens for RPC connections.
+    /// - `data_dir` The directory the chain is persisted in, if any.
+    /// - `enabled_options` The optional behaviour enabled on start.
+    pub fn new(genesis: &Genesis, listen_address: SocketAddr, advertised_address: SocketAddr, rpc_listen_address: SocketAddr, data_dir: Option<&str>, enabled_options: &[&str]) -> StartupBanner {
+        let mut features = vec![];
+        if cfg!(feature = "ui") {
+            features.push("ui".to_string());
+        }
+        features.extend(enabled_options.iter().map(|option| option.to_string()));
+
+        StartupBanner {
+            node_version: NODE_VERSION.to_string(),
+            genesis_version: genesis.version.clone(),
+            genesis_hash: genesis.get_configuration_hash(),
+            public_key_fingerprint: genesis.get_public_key_fingerprint(),
+            public_uciv_hash: genesis.get_public_uciv_hash(),
+            sealer_index: genesis.sealer.iter().position(|sealer| peers::is_same_peer(sealer, &advertised_address)),
+            sealer_count: genesis.sealer.len(),
+            data_dir: data_dir.map(|data_dir| data_dir.to_string()),
+            features,
+            listen_address,
+            advertised_address,
+            rpc_listen_address,
+            dashboard_address: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod banner_test {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn test_new() {
+        let genesis = Genesis::new("genesis.json", "public_uciv.json", "public_key.json");
+        let listen_address: SocketAddr = "0.0.0.0:9001".parse().unwrap();
+        let rpc_listen_address: SocketAddr = "127.0.0.1:3001".parse().unwrap();
+
+        // the sealer is identified by its advertised address, regardless of its notation
+        let advertised_address: SocketAddr = format!("[::ffff:{}]:{}", genesis.sealer[1].ip(), genesis.sealer[1].port()).parse().unwrap();
+        let banner = StartupBanner::new(&genesis, listen_address, advertised_address, rpc_listen_address, Some("data"), &["sign"]);
+        assert_eq!(Some(1), banner.sealer_index);
+        assert_eq!(genesis.sealer.len(), banner.sealer_count);
+        assert_eq!(genesis.get_configuration_hash(), banner.genesis_hash);
+        assert_eq!(Some("data".to_string()), banner.data_dir);
+        assert!(banner.features.contains(&"sign".to_string()));
+        assert_eq!(None, banner.dashboard_address);
+
+        // the hashes are equal on all nodes of the network
+        let other_banner = StartupBanner::new(&genesis, genesis.sealer[2], genesis.sealer[2], rpc_listen_address, None, &[]);
+        assert_eq!(Some(2), other_banner.sealer_index);
+        assert_eq!(banner.public_key_fingerprint, other_banner.public_key_fingerprint);
+        assert_eq!(banner.public_uciv_hash, other_banner.public_uciv_hash);
+        assert!(!other_banner.features.contains(&"sign".to_string()));
+
+        let observer_banner = StartupBanner::new(&genesis, listen_address, "127.0.0.1:9999".parse().unwrap(), rpc_listen_address, None, &[]);
+        assert_eq!(None, observer_banner.sealer_index);
+
+        // printed as a single line
+        let encoded = serde_json::to_string(&banner).unwrap();
+        assert!(!encoded.contains('\n'));
+        assert_eq!(banner, serde_json::from_str(&encoded).unwrap());
+    }
+}
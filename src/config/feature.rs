@@ -0,0 +1,70 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// An optional behaviour of the election, enabled in the genesis configuration.
+///
+/// As all sealers must behave the same, the enabled features are part of the hash
+/// of the genesis configuration and compared at the handshake. Nodes not knowing
+/// an enabled feature refuse to read the genesis configuration.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum Feature {
+    /// The last vote of a voter counts instead of the first one.
+    LastVoteCounts,
+    /// Votes are tallied per precinct additionally to the overall tally.
+    PrecinctTallies,
+    /// Votes are submitted as commitments first and revealed later.
+    CommitmentMode,
+    /// The election authority registers and revokes voters on the chain.
+    VoterRegistry,
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Feature::LastVoteCounts => write!(f, "last-vote-counts"),
+            Feature::PrecinctTallies => write!(f, "precinct-tallies"),
+            Feature::CommitmentMode => write!(f, "commitment-mode"),
+            Feature::VoterRegistry => write!(f, "voter-registry"),
+        }
+    }
+}
+
+/// Describes how the features enabled by a peer differ from the own ones,
+/// or returns None if both enable the same features.
+pub fn find_difference(own_features: &BTreeSet<Feature>, peer_features: &BTreeSet<Feature>) -> Option<String> {
+    if own_features == peer_features {
+        return None;
+    }
+
+    let join = |features: Vec<&Feature>| -> String {
+        if features.is_empty() {
+            "none".to_string()
+        } else {
+            features.iter().map(|feature| feature.to_string()).collect::<Vec<String>>().join(", ")
+        }
+    };
+
+    Some(format!(
+        "Only the peer enables {}, only we enable {}",
+        join(peer_features.difference(own_features).collect()),
+        join(own_features.difference(peer_features).collect())
+    ))
+}
+
+#[cfg(test)]
+mod feature_test {
+
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn test_find_difference() {
+        let own_features: BTreeSet<Feature> = serde_json::from_str("[\"last-vote-counts\", \"commitment-mode\"]").unwrap();
+        let peer_features: BTreeSet<Feature> = vec![Feature::CommitmentMode].into_iter().collect();
+
+        assert_eq!(None, find_difference(&own_features, &own_features.clone()));
+        assert_eq!(Some("Only the peer enables none, only we enable last-vote-counts".to_string()), find_difference(&own_features, &peer_features));
+        assert!(serde_json::from_str::<BTreeSet<Feature>>("[\"unknown-feature\"]").is_err());
+    }
+}
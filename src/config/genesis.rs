@@ -1,10 +1,24 @@
+use ::chain::hasher::Hasher;
+use ::chain::sealer_set::{self, SealerSet};
+use ::chain::transaction::TransactionLimits;
+use ::chain::types::Height;
+use ::chain::transaction_registry::{self, RevotePolicy};
+use ::config::feature::Feature;
+use ::credential::RegistrarKey;
+use ::signer::key;
 use std::vec::Vec;
+use std::collections::{BTreeMap, BTreeSet};
+use bincode;
 use serde_json;
+use sha1::Sha1;
 use std::fs::File;
 use std::io::Read;
 use std::net::{SocketAddr};
 use crypto_rs::el_gamal::encryption::PublicKey;
 use crypto_rs::cai::uciv::ImageSet;
+use crypto_rs::arithmetic::mod_int::{From as ModIntFrom, ModInt};
+use native_tls::Certificate;
+use num::BigInt;
 use std::path::Path;
 
 /// Use Deserialize from Serde, Hash from std::hash
@@ -12,14 +26,170 @@ use std::path::Path;
 pub struct GenesisData {
     pub version: String,
     pub clique: CliqueConfig,
-    pub sealer: Vec<SocketAddr>
+    pub sealer: Vec<SocketAddr>,
+    #[serde(default)]
+    pub sealer_keys: BTreeMap<SocketAddr, String>,
+    #[serde(default)]
+    pub features: BTreeSet<Feature>,
+    #[serde(default)]
+    pub transaction_limits: Option<TransactionLimits>,
+    #[serde(default)]
+    pub election_authority: Option<String>,
+    #[serde(default)]
+    pub questions: Vec<QuestionConfig>,
+    #[serde(default)]
+    pub voting_options: Option<Vec<u64>>,
+    #[serde(default)]
+    pub threshold_decryption: Option<ThresholdDecryptionConfig>,
+    #[serde(default)]
+    pub key_generation: Option<KeyGenerationConfig>,
+    #[serde(default)]
+    pub revote_policy: Option<RevotePolicy>,
+    #[serde(default)]
+    pub registrar: Option<RegistrarKey>,
+    #[serde(default)]
+    pub tls_certificates: BTreeMap<SocketAddr, String>,
+    #[serde(default)]
+    pub libp2p_addresses: BTreeMap<SocketAddr, String>
+}
+
+impl GenesisData {
+    /// Check that this configuration is complete and consistent.
+    /// Returns the first violated rule, if any.
+    pub fn check(&self) -> Result<(), String> {
+        if self.version.is_empty() {
+            return Err("Version parameter must be specified".to_string());
+        }
+        if self.clique.block_period == 0 {
+            return Err("Clique block period must be greater than zero".to_string());
+        }
+        if self.sealer.is_empty() {
+            return Err("There must be at least a single sealer".to_string());
+        }
+        if !self.sealer_keys.keys().all(|address| self.sealer.contains(address)) {
+            return Err("Sealer keys must only be declared for sealers".to_string());
+        }
+        if !self.sealer_keys.is_empty() && !self.sealer.iter().all(|address| self.sealer_keys.contains_key(address)) {
+            return Err("If any, sealer keys must be declared for all sealers".to_string());
+        }
+        if let Some(ref election_authority) = self.election_authority {
+            if !key::is_valid_public_key(election_authority) {
+                return Err("Election authority must be an encoded public key".to_string());
+            }
+        }
+        if self.questions.iter().any(|question| question.identifier.is_empty()) {
+            return Err("Questions must have an identifier".to_string());
+        }
+        if self.questions.iter().map(|question| &question.identifier).collect::<BTreeSet<&String>>().len() < self.questions.len() {
+            return Err("Questions must have distinct identifiers".to_string());
+        }
+        if let Some(ref voting_options) = self.voting_options {
+            if voting_options.len() < 2 {
+                return Err("There must be at least two voting options".to_string());
+            }
+            if voting_options.iter().collect::<BTreeSet<&u64>>().len() < voting_options.len() {
+                return Err("Voting options must be distinct".to_string());
+            }
+        }
+
+        if let Some(ref threshold_decryption) = self.threshold_decryption {
+            if threshold_decryption.threshold == 0 || threshold_decryption.threshold > threshold_decryption.public_key_shares.len() {
+                return Err("The decryption threshold must be between one and the amount of public key shares".to_string());
+            }
+            if !threshold_decryption.public_key_shares.keys().all(|address| self.sealer.contains(address)) {
+                return Err("Public key shares must only be declared for sealers".to_string());
+            }
+        }
+        if let Some(ref key_generation) = self.key_generation {
+            if key_generation.threshold == 0 || key_generation.threshold > self.sealer.len() {
+                return Err("The threshold of the key generation must be between one and the amount of sealers".to_string());
+            }
+            if self.threshold_decryption.is_some() {
+                return Err("Public key shares must not be declared if the sealers generate the election key".to_string());
+            }
+        }
+        if Some(RevotePolicy::FirstVoteCounts) == self.revote_policy && self.features.contains(&Feature::LastVoteCounts) {
+            return Err("The revote policy first-vote-counts contradicts the feature last-vote-counts".to_string());
+        }
+        if self.features.contains(&Feature::VoterRegistry) && self.election_authority.is_none() {
+            return Err("The feature voter-registry requires an election authority registering the voters".to_string());
+        }
+        if let Some(Err(e)) = self.registrar.as_ref().map(RegistrarKey::check) {
+            return Err(e);
+        }
+        if !self.tls_certificates.keys().all(|address| self.sealer.contains(address)) {
+            return Err("TLS certificates must only be declared for sealers".to_string());
+        }
+        if !self.libp2p_addresses.keys().all(|address| self.sealer.contains(address)) {
+            return Err("libp2p addresses must only be declared for sealers".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// The configuration of decrypting the tally jointly by the sealers, each holding a share of the private key.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ThresholdDecryptionConfig {
+    /// The amount of decryption shares required to decrypt the tally.
+    pub threshold: usize,
+    /// The public key share `g^x_i` of each sealer holding a key share, by its address. The index `i`
+    /// of a key share is the position of its sealer in the sealers of the configuration, starting at one.
+    pub public_key_shares: BTreeMap<SocketAddr, ModInt>,
+}
+
+/// The configuration of generating the election key jointly by the sealers, see `threshold::dkg`.
+///
+/// Each sealer of the configuration deals a share of its contribution to each other sealer, so that
+/// any `threshold` of them decrypt the tally, while the private key is never held by anyone.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct KeyGenerationConfig {
+    /// The amount of decryption shares required to decrypt the tally.
+    pub threshold: usize,
+}
+
+/// A question asked in addition to the one whose voters are declared in `public_uciv.json`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QuestionConfig {
+    /// The identifier of the question, referenced by the votes answering it.
+    pub identifier: String,
+    /// The file holding the public UCIV information of the voters on the question,
+    /// relative to the directory the node is started in, like `public_uciv.json`.
+    pub public_uciv: String,
 }
 
 /// A configuration element for clique specific values.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CliqueConfig {
     pub block_period: u64,
-    pub signer_limit: usize
+    pub signer_limit: usize,
+    /// The hash function deriving the identifiers of new blocks and transactions,
+    /// SHA-256 if absent. Omitted if absent, so that the hash of configurations
+    /// without it does not change.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_algorithm: Option<Hasher>,
+    /// The height below which blocks, and the transactions they include, may still be identified by
+    /// SHA-1 digests instead of the hash function above, so that a chain started by an earlier version
+    /// of this node stays valid. Omitted if absent, so that the hash of configurations without it does not change.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub legacy_sha1_height: Option<Height>,
+    /// The amount of blocks after which pending votes on sealers are discarded,
+    /// `DEFAULT_VOTE_EPOCH_LENGTH` if absent. Omitted if absent, so that the hash
+    /// of configurations without it does not change.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vote_epoch_length: Option<usize>
+}
+
+impl CliqueConfig {
+    /// Returns the hash function deriving the identifiers of new blocks and transactions.
+    pub fn get_hasher(&self) -> Hasher {
+        self.hash_algorithm.unwrap_or_default()
+    }
+
+    /// Returns the amount of blocks after which pending votes on sealers are discarded.
+    pub fn get_vote_epoch_length(&self) -> usize {
+        self.vote_epoch_length.unwrap_or(sealer_set::DEFAULT_VOTE_EPOCH_LENGTH)
+    }
 }
 
 /// The configuration for the blockchain, usually
@@ -31,7 +201,64 @@ pub struct Genesis {
     pub clique: CliqueConfig,
     pub sealer: Vec<SocketAddr>,
     pub public_key: PublicKey,
-    pub public_uciv: Vec<ImageSet>
+    pub public_uciv: Vec<ImageSet>,
+    /// The hex encoded Ed25519 public key of each sealer. If present, blocks must be
+    /// signed by their sealer. Omitted if empty, so that the hash of configurations
+    /// without keys does not change.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sealer_keys: BTreeMap<SocketAddr, String>,
+    /// The optional behaviours of the election. Omitted if empty, so that the hash
+    /// of configurations without features does not change.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub features: BTreeSet<Feature>,
+    /// The upper bounds on the size of transactions, `TransactionLimits::default()` if absent.
+    /// Omitted if absent, so that the hash of configurations without limits does not change.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction_limits: Option<TransactionLimits>,
+    /// The public key of the election authority, encoded like the keys of the sealers. If present,
+    /// opening and closing the voting must be signed by it, see `PhaseChange`. Omitted if absent,
+    /// so that the hash of configurations without an election authority does not change.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub election_authority: Option<String>,
+    /// The public UCIV information of the voters on each question asked in addition to the one
+    /// of `public_uciv`, by the identifier of the question. Omitted if empty, so that the hash
+    /// of configurations with a single question does not change.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub questions: BTreeMap<String, Vec<ImageSet>>,
+    /// The plaintexts a vote may encrypt, in the order of the images of the UCIV information,
+    /// `transaction_registry::voting_options()` if absent. Omitted if absent, so that the hash
+    /// of configurations with the two default options does not change.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voting_options: Option<Vec<u64>>,
+    /// The configuration of decrypting the tally jointly by the sealers. If present, the sealers publish
+    /// their decryption shares once the voting is closed. Omitted if absent, so that the hash of
+    /// configurations without threshold decryption does not change.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold_decryption: Option<ThresholdDecryptionConfig>,
+    /// The configuration of generating the election key jointly by the sealers. If present, the public key
+    /// only provides the group, while votes are encrypted with the election key committed on the chain.
+    /// Omitted if absent, so that the hash of configurations with a pre-generated key does not change.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_generation: Option<KeyGenerationConfig>,
+    /// Which vote counts if a voter votes several times on the same question, see `get_revote_policy`.
+    /// Omitted if absent, so that the hash of configurations without it does not change.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revote_policy: Option<RevotePolicy>,
+    /// The public key of the registrar blind-signing the eligibility credentials of the voters. If present,
+    /// each vote must carry a credential signed by it, see `EligibilityCredential`. Omitted if absent,
+    /// so that the hash of configurations without a registrar does not change.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registrar: Option<RegistrarKey>,
+    /// The PEM encoded TLS certificate of each sealer, by its address, read from the files declared
+    /// in the genesis file. If present, a sealer is only trusted if it presents its certificate.
+    /// Omitted if empty, so that the hash of configurations without certificates does not change.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tls_certificates: BTreeMap<SocketAddr, String>,
+    /// The libp2p multiaddress of each sealer, by its address, ending in the peer id of the sealer,
+    /// e.g. `/ip4/127.0.0.1/tcp/9100/p2p/<PeerId>`. Required for the sealers exchanging messages over libp2p.
+    /// Omitted if empty, so that the hash of configurations without libp2p addresses does not change.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub libp2p_addresses: BTreeMap<SocketAddr, String>
 }
 
 impl Genesis {
@@ -68,6 +295,82 @@ impl Genesis {
         };
 
         // read the UCIV information from the path provided
+        let public_uciv = Genesis::read_public_uciv(public_uciv_file_name);
+        let questions: BTreeMap<String, Vec<ImageSet>> = genesis_data.questions.iter()
+            .map(|question| (question.identifier.clone(), Genesis::read_public_uciv(&question.public_uciv)))
+            .collect();
+
+        let tls_certificates: BTreeMap<SocketAddr, String> = genesis_data.tls_certificates.iter()
+            .map(|(address, certificate_file_name)| (*address, Genesis::read_tls_certificate(certificate_file_name)))
+            .collect();
+
+        // read public key from path provided
+        let public_key_str_path = "./".to_owned() + public_key_file_name;
+        let public_key_path = Path::new(public_key_str_path.as_str());
+        if ! public_key_path.exists() {
+            panic!("Missing public key file at ./{}", public_key_file_name);
+        }
+
+        trace!("Reading public key from {}", public_key_file_name);
+        let public_key = PublicKey::new(public_key_file_name);
+
+        if let Err(e) = genesis_data.check() {
+            panic!("{}", e);
+        }
+
+        // each voter holds an image per voting option
+        let amount_of_options = genesis_data.voting_options.as_ref().map_or(transaction_registry::voting_options().len(), Vec::len);
+        for image_sets in Some(&public_uciv).into_iter().chain(questions.values()) {
+            if let Some((voter_idx, image_set)) = image_sets.iter().enumerate().find(|&(_, image_set)| image_set.images.len() != amount_of_options) {
+                panic!("The public UCIV information of voter {} holds {} images, but there are {} voting options", voter_idx, image_set.images.len(), amount_of_options);
+            }
+        }
+
+        // TODO: if only one sealer -> what should the signer_limit value be?
+
+        Genesis {
+            version: genesis_data.version,
+            clique: genesis_data.clique,
+            sealer: genesis_data.sealer,
+            public_key,
+            public_uciv,
+            sealer_keys: genesis_data.sealer_keys,
+            features: genesis_data.features,
+            transaction_limits: genesis_data.transaction_limits,
+            election_authority: genesis_data.election_authority,
+            questions,
+            voting_options: genesis_data.voting_options,
+            threshold_decryption: genesis_data.threshold_decryption,
+            key_generation: genesis_data.key_generation,
+            revote_policy: genesis_data.revote_policy,
+            registrar: genesis_data.registrar,
+            tls_certificates,
+            libp2p_addresses: genesis_data.libp2p_addresses
+        }
+    }
+
+    /// Read the PEM encoded TLS certificate of a sealer from the given file.
+    ///
+    /// Panics if the file is missing or does not contain a certificate.
+    fn read_tls_certificate(certificate_file_name: &str) -> String {
+        let mut certificate = String::new();
+        let read_result = File::open("./".to_owned() + certificate_file_name).and_then(|mut file| file.read_to_string(&mut certificate));
+        if let Err(e) = read_result {
+            panic!("Failed to read TLS certificate at ./{}: {:?}", certificate_file_name, e);
+        }
+
+        trace!("Read TLS certificate from {}", certificate_file_name);
+        if let Err(e) = Certificate::from_pem(certificate.as_bytes()) {
+            panic!("Failed to parse TLS certificate at ./{}: {}", certificate_file_name, e);
+        }
+
+        certificate
+    }
+
+    /// Read the public UCIV information of all voters from the given file.
+    ///
+    /// Panics if the file is missing or does not contain UCIV information.
+    fn read_public_uciv(public_uciv_file_name: &str) -> Vec<ImageSet> {
         let uciv_str_path = "./".to_owned() + public_uciv_file_name;
         let uciv_path = Path::new(uciv_str_path.as_str());
         if ! uciv_path.exists() {
@@ -79,39 +382,81 @@ impl Genesis {
         let mut public_uciv_buffer = String::new();
         public_uciv_file.read_to_string(&mut public_uciv_buffer).unwrap();
 
-        let public_uciv: Vec<ImageSet> = match serde_json::from_str(&public_uciv_buffer) {
+        match serde_json::from_str(&public_uciv_buffer) {
             Ok(public_uciv_data) => {
                 public_uciv_data
             }
             Err(e) => {
                 panic!("Failed to transform file {:?} into ImageSet: {:?}", public_uciv_file, e);
             }
-        };
-
-        // read public key from path provided
-        let public_key_str_path = "./".to_owned() + public_key_file_name;
-        let public_key_path = Path::new(public_key_str_path.as_str());
-        if ! public_key_path.exists() {
-            panic!("Missing public key file at ./{}", public_key_file_name);
         }
+    }
 
-        trace!("Reading public key from {}", public_key_file_name);
-        let public_key = PublicKey::new(public_key_file_name);
+    /// Returns a SHA-1 digest of this configuration, which is equal for all nodes
+    /// of the same network.
+    pub fn get_configuration_hash(&self) -> String {
+        let bytes = bincode::serialize(self).unwrap();
 
-        assert!(genesis_data.version.len() > 0, "Version parameter must be specified");
-        assert!(genesis_data.clique.block_period > 0, "Clique block period must be greater than zero");
-        assert!(genesis_data.sealer.len() > 0, "There must be at least a single sealer");
+        Sha1::from(bytes).hexdigest()
+    }
 
-        // TODO: if only one sealer -> what should the signer_limit value be?
+    /// Returns the sealers authorized by this configuration, before any votes on sealers.
+    pub fn get_sealer_set(&self) -> SealerSet {
+        SealerSet::new(self.sealer.clone(), self.sealer_keys.clone(), self.clique.get_vote_epoch_length())
+    }
 
-        Genesis {
-            version: genesis_data.version,
-            clique: genesis_data.clique,
-            sealer: genesis_data.sealer,
-            public_key,
-            public_uciv
+    /// Returns true, if the given optional behaviour of the election is enabled.
+    pub fn is_enabled(&self, feature: Feature) -> bool {
+        self.features.contains(&feature)
+    }
+
+    /// Returns which vote counts if a voter votes several times on the same question.
+    /// If not declared, the last vote counts if the feature `last-vote-counts` is enabled, the first one otherwise.
+    pub fn get_revote_policy(&self) -> RevotePolicy {
+        match self.revote_policy {
+            Some(revote_policy) => revote_policy,
+            None if self.is_enabled(Feature::LastVoteCounts) => RevotePolicy::LastVoteCounts,
+            None => RevotePolicy::FirstVoteCounts
         }
     }
 
+    /// Returns the upper bounds on the size of transactions.
+    pub fn get_transaction_limits(&self) -> TransactionLimits {
+        self.transaction_limits.clone().unwrap_or_default()
+    }
+
+    /// Returns the plaintexts a vote may encrypt, in the order of the images of the UCIV information.
+    pub fn get_voting_options(&self) -> Vec<ModInt> {
+        match self.voting_options {
+            Some(ref voting_options) => voting_options.iter().map(|option| ModInt::from_value(BigInt::from(*option))).collect(),
+            None => transaction_registry::voting_options()
+        }
+    }
+
+    /// Returns the index of the key share held by the given sealer, starting at one,
+    /// along with its public key share, if threshold decryption is configured and the sealer holds a key share.
+    pub fn get_public_key_share(&self, sealer: &SocketAddr) -> Option<(usize, &ModInt)> {
+        match self.threshold_decryption.as_ref().and_then(|threshold_decryption| threshold_decryption.public_key_shares.get(sealer)) {
+            Some(public_key_share) => self.sealer.iter().position(|address| address == sealer).map(|position| (position + 1, public_key_share)),
+            None => None
+        }
+    }
 
+    /// Returns a SHA-1 digest of the public key, identifying it without printing it in full.
+    pub fn get_public_key_fingerprint(&self) -> String {
+        let bytes = bincode::serialize(&self.public_key).unwrap();
+
+        Sha1::from(bytes).hexdigest()
+    }
+
+    /// Returns a SHA-1 digest of the public UCIV information of all voters, on all questions.
+    pub fn get_public_uciv_hash(&self) -> String {
+        let bytes = if self.questions.is_empty() {
+            bincode::serialize(&self.public_uciv).unwrap()
+        } else {
+            bincode::serialize(&(&self.public_uciv, &self.questions)).unwrap()
+        };
+
+        Sha1::from(bytes).hexdigest()
+    }
 }
\ No newline at end of file
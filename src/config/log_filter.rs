@@ -0,0 +1,76 @@
+/// The crate whose modules the filters apply to.
+const CRATE_NAME: &str = "node_rs";
+
+/// The levels a module may be filtered at, from the least to the most verbose.
+const LEVELS: [&str; 6] = ["off", "error", "warn", "info", "debug", "trace"];
+
+/// Returns the directives filtering the log output of this crate, one per module, e.g. `node_rs::p2p=trace`.
+///
+/// The first directive sets the level of the whole crate, turned up by the given verbosity.
+/// It is followed by one directive for each of the comma separated module filters, if any, e.g.
+/// `p2p=trace,protocol::clique=info`. Modules are named relative to the crate.
+///
+/// Returns an error if a module filter is malformed or names an unknown level.
+pub fn parse(verbosity: u64, module_filters: Option<&str>) -> Result<Vec<String>, String> {
+    let crate_level = match verbosity {
+        0 => "info",
+        1 => "debug",
+        _ => "trace"
+    };
+    let mut directives = vec![format!("{}={}", CRATE_NAME, crate_level)];
+
+    let module_filters = match module_filters {
+        Some(module_filters) => module_filters,
+        None => {
+            return Ok(directives);
+        }
+    };
+
+    for module_filter in module_filters.split(',').map(|module_filter| module_filter.trim()).filter(|module_filter| !module_filter.is_empty()) {
+        let mut parts = module_filter.splitn(2, '=');
+        let module = parts.next().unwrap_or("").trim();
+        let level = match parts.next() {
+            Some(level) => level.trim().to_lowercase(),
+            None => {
+                return Err(format!("Module filter {:?} must be of the form <module>=<level>", module_filter));
+            }
+        };
+
+        let is_valid_module = !module.is_empty() && module.split("::").all(|segment| {
+            !segment.is_empty() && segment.chars().all(|character| character.is_ascii_alphanumeric() || character == '_')
+        });
+        if !is_valid_module {
+            return Err(format!("Module filter {:?} names an invalid module {:?}", module_filter, module));
+        }
+        if !LEVELS.contains(&level.as_str()) {
+            return Err(format!("Module filter {:?} names an unknown level {:?}, expected one of {:?}", module_filter, level, LEVELS));
+        }
+
+        if module == CRATE_NAME || module.starts_with(&format!("{}::", CRATE_NAME)) {
+            directives.push(format!("{}={}", module, level));
+        } else {
+            directives.push(format!("{}::{}={}", CRATE_NAME, module, level));
+        }
+    }
+
+    Ok(directives)
+}
+
+#[cfg(test)]
+mod log_filter_test {
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Ok(vec!["node_rs=info".to_string()]), parse(0, None));
+        assert_eq!(
+            Ok(vec!["node_rs=debug".to_string(), "node_rs::p2p=trace".to_string(), "node_rs::protocol::clique=warn".to_string()]),
+            parse(1, Some("p2p=trace, node_rs::protocol::clique=WARN"))
+        );
+
+        assert!(parse(0, Some("p2p")).is_err());
+        assert!(parse(0, Some("p2p=verbose")).is_err());
+        assert!(parse(0, Some("p2p::=info")).is_err());
+    }
+}
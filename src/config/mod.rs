@@ -1 +1,16 @@
-pub mod genesis;
\ No newline at end of file
+pub mod genesis;
+
+/// The optional behaviours of the election, which all sealers must agree on.
+pub mod feature;
+
+/// The configuration of a single node, which may differ per deployment.
+pub mod node;
+
+/// The compatibility of the node with the version declared in the genesis configuration.
+pub mod version;
+
+/// A summary of the configuration a node is started with.
+pub mod banner;
+
+/// The filters of the log output, per module of the node.
+pub mod log_filter;
@@ -0,0 +1,126 @@
+use ::chain::storage::StorageEncoding;
+use ::config::version::VersionPolicy;
+use ::p2p::admin::AdminIdentity;
+use ::p2p::compression::DEFAULT_COMPRESSION_THRESHOLD;
+use ::p2p::memory::MemoryCaps;
+use ::p2p::rate_limit::RateLimits;
+use ::p2p::rpc_token::RpcTokens;
+use ::p2p::tls::TlsContext;
+use ::p2p::transport::TransportKind;
+use ::signer::key::NodeKey;
+use ::threshold::KeyShare;
+#[cfg(feature = "libp2p")]
+use libp2p::identity::Keypair;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// The configuration of a single node.
+///
+/// In contrast to the genesis configuration, which must be equal
+/// for all nodes of a network, this configuration may differ per deployment.
+#[derive(Clone, Debug)]
+pub struct NodeConfig {
+    /// If set, transactions received via RPC are not broadcast immediately
+    /// but collected and gossiped in batches after a random delay of at most
+    /// this amount of milliseconds.
+    pub gossip_max_delay: Option<u64>,
+    /// If true, a dummy message is gossiped whenever a batch is empty,
+    /// so that observers cannot correlate network traffic with voting activity.
+    /// Only has an effect if `gossip_max_delay` is set.
+    pub gossip_padding: bool,
+    /// The address under which other nodes know this node, i.e. the one
+    /// contained in the sealers of the genesis configuration. Required if it differs
+    /// from the listen address, e.g. when listening on `0.0.0.0` or behind a NAT.
+    /// If None, the listen address is used.
+    pub advertised_address: Option<SocketAddr>,
+    /// The amount of workers handling incoming connections concurrently,
+    /// for each of the peer and the RPC listener.
+    pub connection_workers: usize,
+    /// The amount of workers verifying the transactions of blocks received
+    /// from other peers in parallel.
+    pub verification_workers: usize,
+    /// The path of the Unix domain socket of an external signer producing the
+    /// signatures of blocks sealed by this node. Takes precedence over the node key.
+    pub external_signer: Option<PathBuf>,
+    /// The keypair with which this node signs the blocks it seals, unless an external
+    /// signer is configured. If neither is set, blocks are not signed.
+    pub node_key: Option<NodeKey>,
+    /// The policy defining which versions declared in the genesis configuration
+    /// this node is willing to run with.
+    pub version_policy: VersionPolicy,
+    /// The designated admin identity, which may tunnel admin commands over
+    /// the port used for communication between peers. If None, admin commands
+    /// are only accepted on the RPC port.
+    pub admin_identity: Option<AdminIdentity>,
+    /// The share of the private key of the election held by this node, with which it publishes
+    /// its decryption shares once the voting is closed. If None, this node does not take part in decrypting the tally.
+    pub key_share: Option<KeyShare>,
+    /// The tokens accepted on the RPC port, each granting access to the RPC methods
+    /// of its scopes. If None, all RPC methods may be called without a token.
+    pub rpc_tokens: Option<RpcTokens>,
+    /// The transport used to send messages to other peers.
+    pub transport: TransportKind,
+    /// The keypair identifying this node on libp2p, whose peer id must match the one in the libp2p address
+    /// of this node in the genesis configuration. Required if messages are exchanged over libp2p.
+    #[cfg(feature = "libp2p")]
+    pub libp2p_key: Option<Keypair>,
+    /// The size in bytes from which messages to peers negotiating a compression are compressed.
+    /// If None, messages are never compressed.
+    pub compression_threshold: Option<usize>,
+    /// The TLS context the connections to other peers are encrypted with.
+    /// If None, this node connects to other peers in plain text.
+    pub tls: Option<Arc<TlsContext>>,
+    /// The TLS context RPC connections are accepted with. If set, plain text RPC connections
+    /// are refused, so that tokens cannot be observed on the wire.
+    pub rpc_tls: Option<Arc<TlsContext>>,
+    /// The encoding of the blocks persisted in the data directory.
+    /// Blocks stored in another encoding are migrated to it on startup.
+    pub storage_encoding: StorageEncoding,
+    /// The caps of the memory used for caching the chain, buffering transactions
+    /// and receiving messages.
+    pub memory_caps: MemoryCaps,
+    /// The limits on the connections and messages of a single IP address on the peer listener.
+    pub peer_rate_limits: RateLimits,
+    /// The limits on the connections and messages of a single IP address on the RPC listener.
+    pub rpc_rate_limits: RateLimits,
+    /// The directives the log output is filtered with, reported in the status of the node.
+    pub log_filters: Vec<String>,
+    /// The NTP server the local clock is compared against additionally to the peers,
+    /// in the format `<Hostname>[:<Port>]`. If None, the clock is only compared against the peers.
+    pub ntp_server: Option<String>,
+    /// The deviation of the local clock from the clocks of the peers in milliseconds,
+    /// above which a warning is logged.
+    pub clock_warn_threshold: u64,
+}
+
+impl Default for NodeConfig {
+    fn default() -> Self {
+        NodeConfig {
+            gossip_max_delay: None,
+            gossip_padding: false,
+            advertised_address: None,
+            connection_workers: 8,
+            verification_workers: 4,
+            external_signer: None,
+            node_key: None,
+            version_policy: VersionPolicy::Ignore,
+            admin_identity: None,
+            key_share: None,
+            rpc_tokens: None,
+            transport: TransportKind::Tcp,
+            #[cfg(feature = "libp2p")]
+            libp2p_key: None,
+            compression_threshold: Some(DEFAULT_COMPRESSION_THRESHOLD),
+            tls: None,
+            rpc_tls: None,
+            storage_encoding: StorageEncoding::Bincode,
+            memory_caps: MemoryCaps::default(),
+            peer_rate_limits: RateLimits::default(),
+            rpc_rate_limits: RateLimits::default(),
+            log_filters: vec![],
+            ntp_server: None,
+            clock_warn_threshold: 1000,
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Defines which versions declared in the genesis configuration
+/// a node is willing to run with.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum VersionPolicy {
+    /// The declared version must equal the version of the node.
+    Exact,
+    /// The declared version must not be newer than the version of the node
+    /// and must be compatible according to semantic versioning, i.e. have the
+    /// same major version, or the same minor version for major version zero.
+    Compatible,
+    /// The declared version is not checked.
+    Ignore,
+}
+
+impl FromStr for VersionPolicy {
+    type Err = String;
+
+    fn from_str(policy: &str) -> Result<Self, Self::Err> {
+        match policy {
+            "exact" => Ok(VersionPolicy::Exact),
+            "compatible" => Ok(VersionPolicy::Compatible),
+            "ignore" => Ok(VersionPolicy::Ignore),
+            other => Err(format!("Unknown version policy {:?}", other))
+        }
+    }
+}
+
+/// The result of checking the version declared in the genesis
+/// configuration against the version of the node.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct VersionCheck {
+    /// The policy the versions were checked with.
+    pub policy: VersionPolicy,
+    /// The version declared in the genesis configuration.
+    pub genesis_version: String,
+    /// The version of the node.
+    pub node_version: String,
+    /// The reason why the versions are incompatible, or None if they are compatible.
+    pub incompatibility: Option<String>,
+}
+
+impl VersionCheck {
+    /// Check the version declared in the genesis configuration against the version of the node.
+    pub fn new(policy: VersionPolicy, genesis_version: &str, node_version: &str) -> VersionCheck {
+        VersionCheck {
+            policy,
+            genesis_version: genesis_version.to_string(),
+            node_version: node_version.to_string(),
+            incompatibility: find_incompatibility(policy, genesis_version, node_version),
+        }
+    }
+
+    /// Returns true, if the node may run with the genesis configuration.
+    pub fn is_compatible(&self) -> bool {
+        self.incompatibility.is_none()
+    }
+}
+
+/// A version in the format `MAJOR.MINOR.PATCH`, optionally
+/// followed by a pre-release or build suffix, which is ignored.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy)]
+struct Version {
+    major: u64,
+    minor: u64,
+    patch: u64,
+}
+
+impl FromStr for Version {
+    type Err = String;
+
+    fn from_str(version: &str) -> Result<Self, Self::Err> {
+        let core = version.trim().split(&['-', '+'][..]).next().unwrap_or("");
+        let parts: Vec<&str> = core.split('.').collect();
+        if parts.len() != 3 {
+            return Err(format!("Version {:?} is not in the format MAJOR.MINOR.PATCH", version));
+        }
+
+        let mut numbers = vec![];
+        for part in parts {
+            match part.parse::<u64>() {
+                Ok(number) => numbers.push(number),
+                Err(_) => {
+                    return Err(format!("Version {:?} is not in the format MAJOR.MINOR.PATCH", version));
+                }
+            }
+        }
+
+        Ok(Version {
+            major: numbers[0],
+            minor: numbers[1],
+            patch: numbers[2],
+        })
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+fn find_incompatibility(policy: VersionPolicy, genesis_version: &str, node_version: &str) -> Option<String> {
+    if VersionPolicy::Ignore == policy {
+        return None;
+    }
+
+    let genesis_version = match genesis_version.parse::<Version>() {
+        Ok(version) => version,
+        Err(e) => {
+            return Some(e);
+        }
+    };
+    let node_version = match node_version.parse::<Version>() {
+        Ok(version) => version,
+        Err(e) => {
+            return Some(e);
+        }
+    };
+
+    match policy {
+        VersionPolicy::Exact if genesis_version != node_version => {
+            Some(format!("Network requires version {}, but this node runs {}", genesis_version, node_version))
+        }
+        VersionPolicy::Compatible if genesis_version > node_version => {
+            Some(format!("Network requires version {}, which is newer than the version {} of this node", genesis_version, node_version))
+        }
+        VersionPolicy::Compatible if genesis_version.major != node_version.major
+            || (0 == genesis_version.major && genesis_version.minor != node_version.minor) => {
+            Some(format!("Network requires version {}, which is not compatible with the version {} of this node", genesis_version, node_version))
+        }
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod version_test {
+
+    use ::config::version::{VersionCheck, VersionPolicy};
+
+    #[test]
+    fn test_version_policies() {
+        assert!(VersionCheck::new(VersionPolicy::Exact, "1.2.3", "1.2.3").is_compatible());
+        assert!(!VersionCheck::new(VersionPolicy::Exact, "1.2.2", "1.2.3").is_compatible());
+
+        assert!(VersionCheck::new(VersionPolicy::Compatible, "1.1.0", "1.2.3").is_compatible());
+        assert!(VersionCheck::new(VersionPolicy::Compatible, "0.2.0", "0.2.1-beta").is_compatible());
+        assert!(!VersionCheck::new(VersionPolicy::Compatible, "1.3.0", "1.2.3").is_compatible());
+        assert!(!VersionCheck::new(VersionPolicy::Compatible, "1.2.3", "2.0.0").is_compatible());
+        assert!(!VersionCheck::new(VersionPolicy::Compatible, "0.1.0", "0.2.1").is_compatible());
+        assert!(!VersionCheck::new(VersionPolicy::Compatible, "latest", "0.2.1").is_compatible());
+
+        assert!(VersionCheck::new(VersionPolicy::Ignore, "latest", "0.2.1").is_compatible());
+    }
+}
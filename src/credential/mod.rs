@@ -0,0 +1,298 @@
+use ::mac;
+use bincode;
+use num::bigint::Sign;
+use num::{BigInt, Integer, One, Signed, Zero};
+use rand::{thread_rng, Rng};
+use serde_json;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::Read;
+
+/// Separates the messages signed by the registrar from other digests.
+const CREDENTIAL_DOMAIN: &str = "node-rs-eligibility-credential";
+
+/// The amount of random bytes of the nonce of a credential.
+const NONCE_LENGTH: usize = 32;
+
+/// The public exponent of RSA keys generated by common tools, e.g. `openssl genrsa`.
+const DEFAULT_EXPONENT: u64 = 65537;
+
+/// The minimum size in bits of the modulus of the registrar key, below which RSA moduli can be factored.
+pub const MIN_MODULUS_BITS: usize = 2048;
+
+fn default_exponent() -> u64 {
+    DEFAULT_EXPONENT
+}
+
+/// The proof that a voter is eligible to vote for a single voter index, blind-signed by the registrar.
+///
+/// The registrar authenticates the voter and signs the blinded message without learning it,
+/// hence neither the nonce nor the voter index can be linked to the voter the registrar authenticated.
+#[derive(Eq, PartialEq, Hash, Serialize, Deserialize, Debug, Clone)]
+pub struct EligibilityCredential {
+    /// The hex encoded random nonce chosen by the voter.
+    pub nonce: String,
+    /// The hex encoded RSA signature of the registrar on the message returned by `get_message`.
+    pub signature: String,
+}
+
+/// The public RSA key of the registrar blind-signing eligibility credentials, as declared in the genesis configuration.
+#[derive(Eq, PartialEq, Hash, Serialize, Deserialize, Debug, Clone)]
+pub struct RegistrarKey {
+    /// The hex encoded modulus `n`, e.g. as printed by `openssl rsa -noout -modulus`.
+    pub modulus: String,
+    /// The public exponent `e`, 65537 if absent.
+    #[serde(default = "default_exponent")]
+    pub exponent: u64,
+}
+
+/// A credential request of a voter, whose blinded message is sent to the registrar.
+/// The blinding factor must be kept secret, as it links the signature to the request.
+#[derive(Debug, Clone)]
+pub struct BlindedCredential {
+    /// The hex encoded random nonce chosen by the voter.
+    pub nonce: String,
+    /// The message `m * r^e mod n` to be signed by the registrar.
+    pub blinded_message: BigInt,
+    /// The random blinding factor `r`.
+    pub blinding_factor: BigInt,
+}
+
+impl RegistrarKey {
+    /// Returns the modulus, or an error if it is not hex encoded or shorter than `MIN_MODULUS_BITS`.
+    pub fn get_modulus(&self) -> Result<BigInt, String> {
+        match BigInt::parse_bytes(self.modulus.as_bytes(), 16) {
+            Some(ref modulus) if modulus.is_positive() && modulus.bits() >= MIN_MODULUS_BITS => Ok(modulus.clone()),
+            Some(_) => Err(format!("Modulus of the registrar key must have at least {} bits", MIN_MODULUS_BITS)),
+            None => Err("Modulus of the registrar key must be hex encoded".to_string())
+        }
+    }
+
+    /// Check that the modulus is hex encoded and large enough, and the exponent is odd.
+    pub fn check(&self) -> Result<(), String> {
+        if self.exponent < 3 || self.exponent % 2 != 1 {
+            return Err("Exponent of the registrar key must be odd and greater than one".to_string());
+        }
+
+        self.get_modulus().map(|_| ())
+    }
+
+    /// Create a credential request for the voter with the given index on the question with the given identifier,
+    /// or on the question whose voters are declared in `public_uciv.json` if None, blinded with a random factor.
+    pub fn blind(&self, question_id: &Option<String>, voter_idx: usize) -> Result<BlindedCredential, String> {
+        let modulus = match self.get_modulus() {
+            Ok(modulus) => modulus,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+
+        let mut nonce = vec![0u8; NONCE_LENGTH];
+        thread_rng().fill_bytes(&mut nonce);
+        let nonce = mac::encode_hex(&nonce);
+
+        // the blinding factor must be invertible modulo n
+        let mut blinding_factor = random_below(&modulus);
+        while blinding_factor <= BigInt::one() || !blinding_factor.gcd(&modulus).is_one() {
+            blinding_factor = random_below(&modulus);
+        }
+
+        let message = get_message(&modulus, question_id, voter_idx, &nonce);
+        let blinded_message = (message * blinding_factor.modpow(&BigInt::from(self.exponent), &modulus)).mod_floor(&modulus);
+
+        Ok(BlindedCredential {
+            nonce,
+            blinded_message,
+            blinding_factor,
+        })
+    }
+
+    /// Remove the blinding factor of the given request from the given signature of the registrar on its blinded message.
+    /// Returns the credential of the voter, or an error if the signature does not match the request.
+    pub fn unblind(&self, question_id: &Option<String>, voter_idx: usize, blinded_credential: &BlindedCredential, blind_signature: &BigInt) -> Result<EligibilityCredential, String> {
+        let modulus = match self.get_modulus() {
+            Ok(modulus) => modulus,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+
+        let signature = match mod_inverse(&blinded_credential.blinding_factor, &modulus) {
+            Some(inverse) => (blind_signature * inverse).mod_floor(&modulus),
+            None => {
+                return Err("Blinding factor is not invertible".to_string());
+            }
+        };
+
+        let credential = EligibilityCredential {
+            nonce: blinded_credential.nonce.clone(),
+            signature: signature.to_str_radix(16),
+        };
+
+        self.verify(question_id, voter_idx, &credential).map(|_| credential)
+    }
+
+    /// Check that the given credential is signed by the registrar for the voter with the given index
+    /// on the question with the given identifier.
+    pub fn verify(&self, question_id: &Option<String>, voter_idx: usize, credential: &EligibilityCredential) -> Result<(), String> {
+        let modulus = match self.get_modulus() {
+            Ok(modulus) => modulus,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+
+        let signature = match BigInt::parse_bytes(credential.signature.as_bytes(), 16) {
+            Some(ref signature) if signature.is_positive() && signature < &modulus => signature.clone(),
+            _ => {
+                return Err("Signature of the credential is not a hex encoded number below the modulus".to_string());
+            }
+        };
+
+        if signature.modpow(&BigInt::from(self.exponent), &modulus) == get_message(&modulus, question_id, voter_idx, &credential.nonce) {
+            Ok(())
+        } else {
+            Err("Credential is not signed by the registrar".to_string())
+        }
+    }
+}
+
+/// The private RSA key of the registrar, kept off the nodes.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RegistrarPrivateKey {
+    /// The hex encoded modulus `n`.
+    pub modulus: String,
+    /// The hex encoded private exponent `d`.
+    pub private_exponent: String,
+}
+
+impl RegistrarPrivateKey {
+    /// Read the private key from the given JSON file.
+    pub fn from_file(file_name: &str) -> Result<RegistrarPrivateKey, String> {
+        let mut contents = String::new();
+        let read_result = File::open(file_name).and_then(|mut file| file.read_to_string(&mut contents));
+        match read_result {
+            Ok(_) => {}
+            Err(e) => {
+                return Err(format!("Failed to read registrar key {:?}: {:?}", file_name, e));
+            }
+        }
+
+        match serde_json::from_str(&contents) {
+            Ok(private_key) => Ok(private_key),
+            Err(e) => Err(format!("Failed to parse registrar key {:?}: {:?}", file_name, e))
+        }
+    }
+
+    /// Sign the given blinded message of a voter the registrar authenticated, i.e. compute `m'^d mod n`.
+    pub fn sign_blinded(&self, blinded_message: &BigInt) -> Result<BigInt, String> {
+        let (modulus, private_exponent) = match (BigInt::parse_bytes(self.modulus.as_bytes(), 16), BigInt::parse_bytes(self.private_exponent.as_bytes(), 16)) {
+            (Some(modulus), Some(private_exponent)) => (modulus, private_exponent),
+            _ => {
+                return Err("Registrar key must be hex encoded".to_string());
+            }
+        };
+
+        if !blinded_message.is_positive() || blinded_message >= &modulus {
+            return Err("Blinded message must be below the modulus".to_string());
+        }
+
+        Ok(blinded_message.modpow(&private_exponent, &modulus))
+    }
+}
+
+/// Returns the message the registrar signs for the voter with the given index on the question with the given identifier,
+/// i.e. the digest of both along with the nonce, expanded to the size of the given modulus.
+pub fn get_message(modulus: &BigInt, question_id: &Option<String>, voter_idx: usize, nonce: &str) -> BigInt {
+    let bytes = bincode::serialize(&(CREDENTIAL_DOMAIN, question_id, voter_idx as u64, nonce)).unwrap();
+
+    // the surplus of digest bytes makes the bias of the reduction negligible
+    let length = modulus.to_bytes_be().1.len() + 16;
+    let mut digest: Vec<u8> = vec![];
+    let mut counter: u32 = 0;
+    while digest.len() < length {
+        let mut hasher = Sha256::new();
+        hasher.update(counter.to_be_bytes());
+        hasher.update(&bytes);
+        digest.extend_from_slice(&hasher.finalize());
+        counter += 1;
+    }
+
+    BigInt::from_bytes_be(Sign::Plus, &digest[..length]).mod_floor(modulus)
+}
+
+/// Returns the inverse of the given number modulo the given modulus, if both are coprime.
+fn mod_inverse(value: &BigInt, modulus: &BigInt) -> Option<BigInt> {
+    let (mut old_r, mut r) = (value.mod_floor(modulus), modulus.clone());
+    let (mut old_s, mut s) = (BigInt::one(), BigInt::zero());
+    while !r.is_zero() {
+        let quotient = old_r.div_floor(&r);
+        let next_r = &old_r - &quotient * &r;
+        old_r = r;
+        r = next_r;
+        let next_s = &old_s - &quotient * &s;
+        old_s = s;
+        s = next_s;
+    }
+
+    if old_r.is_one() {
+        Some(old_s.mod_floor(modulus))
+    } else {
+        None
+    }
+}
+
+/// Returns a random number below the given bound.
+fn random_below(bound: &BigInt) -> BigInt {
+    let mut bytes = vec![0u8; bound.to_bytes_be().1.len() + 16];
+    thread_rng().fill_bytes(&mut bytes);
+
+    BigInt::from_bytes_be(Sign::Plus, &bytes).mod_floor(bound)
+}
+
+#[cfg(test)]
+mod credential_test {
+
+    use super::*;
+    use num;
+
+    #[test]
+    fn test_blind_signature() {
+        // the product of the Mersenne primes 2^127 - 1 and 2^2203 - 1
+        let p = num::pow(BigInt::from(2), 127) - BigInt::one();
+        let q = num::pow(BigInt::from(2), 2203) - BigInt::one();
+        let modulus = &p * &q;
+        let phi = (&p - BigInt::one()) * (&q - BigInt::one());
+        let private_exponent = mod_inverse(&BigInt::from(DEFAULT_EXPONENT), &phi).unwrap();
+
+        let registrar_key = RegistrarKey { modulus: modulus.to_str_radix(16), exponent: DEFAULT_EXPONENT };
+        let private_key = RegistrarPrivateKey { modulus: modulus.to_str_radix(16), private_exponent: private_exponent.to_str_radix(16) };
+        assert_eq!(Ok(()), registrar_key.check());
+
+        // the registrar signs the blinded message without learning the voter index
+        let blinded_credential = registrar_key.blind(&None, 3).unwrap();
+        assert_ne!(get_message(&modulus, &None, 3, &blinded_credential.nonce), blinded_credential.blinded_message);
+        let blind_signature = private_key.sign_blinded(&blinded_credential.blinded_message).unwrap();
+        let credential = registrar_key.unblind(&None, 3, &blinded_credential, &blind_signature).unwrap();
+        assert_eq!(Ok(()), registrar_key.verify(&None, 3, &credential));
+
+        // the credential is bound to the voter index and the question
+        assert!(registrar_key.verify(&None, 4, &credential).is_err());
+        assert!(registrar_key.verify(&Some("q2".to_string()), 3, &credential).is_err());
+        assert!(registrar_key.unblind(&None, 4, &blinded_credential, &blind_signature).is_err());
+
+        let forged_credential = EligibilityCredential { nonce: credential.nonce.clone(), signature: "1".to_string() };
+        assert!(registrar_key.verify(&None, 3, &forged_credential).is_err());
+        assert!(RegistrarKey { modulus: "xyz".to_string(), exponent: DEFAULT_EXPONENT }.check().is_err());
+    }
+
+    #[test]
+    fn test_modulus_size() {
+        let modulus_of_bits = |bits: usize| (num::pow(BigInt::from(2), bits - 1) + BigInt::one()).to_str_radix(16);
+
+        assert!(RegistrarKey { modulus: modulus_of_bits(MIN_MODULUS_BITS), exponent: DEFAULT_EXPONENT }.get_modulus().is_ok());
+        assert!(RegistrarKey { modulus: modulus_of_bits(MIN_MODULUS_BITS - 1), exponent: DEFAULT_EXPONENT }.get_modulus().is_err());
+        assert!(RegistrarKey { modulus: modulus_of_bits(512), exponent: DEFAULT_EXPONENT }.check().is_err());
+        assert!(RegistrarKey { modulus: format!("-{}", modulus_of_bits(MIN_MODULUS_BITS)), exponent: DEFAULT_EXPONENT }.get_modulus().is_err());
+    }
+}
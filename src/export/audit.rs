@@ -0,0 +1,352 @@
+use ::chain::block::Block;
+use ::chain::block_store::{BlockStore, FileBlockStore};
+use ::chain::chain::Chain;
+use ::chain::chain_visitor::{ElectionPhase, VotingClosedVisitor};
+use ::chain::chain_walker::{ChainWalker, LongestPathWalker};
+use ::chain::transaction::FinalTally;
+use ::chain::types::{BlockHash, Height};
+use ::config::genesis::Genesis;
+use ::protocol::block_validator::BlockValidator;
+use ::protocol::tally::TallyCalculator;
+use serde_json;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The version of the schema of `AuditExport`, increased whenever the meaning of a field changes.
+pub const AUDIT_SCHEMA_VERSION: u32 = 1;
+
+/// A self-contained copy of the bulletin board of an election, i.e. its canonical chain
+/// along with the genesis configuration it was sealed under.
+///
+/// Everything needed to re-verify the election is included: the identifier and signature of each block,
+/// the votes along with their membership and cast-as-intended proofs, and the genesis configuration,
+/// whose hash is recomputed from the bincode encoding of `genesis` with SHA-1.
+#[derive(Serialize, Deserialize)]
+pub struct AuditExport {
+    /// The version of the schema, see `AUDIT_SCHEMA_VERSION`.
+    pub schema_version: u32,
+    /// The time of the export, in seconds since the epoch.
+    pub exported_at: u64,
+    /// The hash of the genesis configuration, identifying the election in the transactions opening and closing the voting.
+    pub genesis_configuration_hash: String,
+    /// The genesis configuration, including the public key and the public UCIV information of all voters.
+    pub genesis: Genesis,
+    /// The height of the head of the canonical chain.
+    pub height: Height,
+    /// The identifier of the head of the canonical chain.
+    pub head_identifier: BlockHash,
+    /// The phase of the election at the head.
+    pub phase: ElectionPhase,
+    /// The blocks of the canonical chain, starting with the genesis block and ending with the head.
+    pub blocks: Vec<Block>,
+    /// The tally of each question at the head, as calculated by the exporting node.
+    pub tallies: Vec<FinalTally>,
+}
+
+impl AuditExport {
+    /// Read an export from the given JSON file.
+    pub fn from_file(file_name: &str) -> Result<AuditExport, String> {
+        let mut contents = String::new();
+        let read_result = File::open(file_name).and_then(|mut file| file.read_to_string(&mut contents));
+        match read_result {
+            Ok(_) => {}
+            Err(e) => {
+                return Err(format!("Failed to read the export {:?}: {:?}", file_name, e));
+            }
+        }
+
+        match serde_json::from_str(&contents) {
+            Ok(audit_export) => Ok(audit_export),
+            Err(e) => Err(format!("Failed to parse the export {:?}: {:?}", file_name, e))
+        }
+    }
+}
+
+/// The outcome of a single check of an `AuditExport`.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct AuditCheck {
+    /// What was checked, e.g. a block along with its height.
+    pub subject: String,
+    /// The reason the check failed, if it did.
+    pub outcome: Result<(), String>,
+}
+
+/// The outcome of all checks of an `AuditExport`, in the order they were performed.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct AuditReport {
+    /// The outcome of each check.
+    pub checks: Vec<AuditCheck>,
+    /// The amount of blocks verified, including the genesis block.
+    pub verified_blocks: usize,
+    /// The amount of transactions verified.
+    pub verified_transactions: usize,
+}
+
+impl AuditReport {
+    /// Returns true, if all checks passed.
+    pub fn is_valid(&self) -> bool {
+        self.checks.iter().all(|check| check.outcome.is_ok())
+    }
+
+    /// Returns the checks which failed.
+    pub fn get_failures(&self) -> Vec<&AuditCheck> {
+        self.checks.iter().filter(|check| check.outcome.is_err()).collect()
+    }
+
+    fn check(&mut self, subject: String, outcome: Result<(), String>) {
+        self.checks.push(AuditCheck {
+            subject,
+            outcome,
+        });
+    }
+}
+
+/// Export the canonical chain stored in the given data directory of a stopped node,
+/// which must be sealed under the given genesis configuration.
+pub fn create(data_directory: &Path, genesis: Genesis) -> Result<AuditExport, String> {
+    match FileBlockStore::open_read_only(data_directory).and_then(|block_store| block_store.load()) {
+        Ok(Some(chain)) => from_chain(&chain, genesis),
+        Ok(None) => Err(format!("Data directory {:?} holds no chain", data_directory)),
+        Err(e) => Err(e)
+    }
+}
+
+/// Export the canonical chain of the given chain, which must be sealed under the given genesis configuration.
+///
+/// Returns an error if the chain belongs to another genesis configuration, or starts with a state snapshot
+/// and hence does not hold the blocks below it.
+pub fn from_chain(chain: &Chain, genesis: Genesis) -> Result<AuditExport, String> {
+    let genesis_configuration_hash = genesis.get_configuration_hash();
+    if genesis_configuration_hash != chain.genesis_configuration_hash {
+        return Err(format!("The chain belongs to the genesis configuration {} but {} is given", chain.genesis_configuration_hash, genesis_configuration_hash));
+    }
+
+    if chain.get_state_snapshot().is_some() {
+        return Err(format!("The chain starts with a state snapshot at height {}, the blocks below are not known", chain.root_height()));
+    }
+
+    let (head, canonical_path) = match chain.head().and_then(|head| chain.get_canonical_path().map(|canonical_path| (head, canonical_path))) {
+        Ok(canonical_chain) => canonical_chain,
+        Err(e) => {
+            return Err(format!("Failed to determine the canonical chain: {:?}", e));
+        }
+    };
+
+    let blocks: Vec<Block> = canonical_path.iter().rev()
+        .map(|block_hash| chain.blocks[block_hash].clone())
+        .collect();
+
+    let mut voting_closed_visitor = VotingClosedVisitor::new();
+    LongestPathWalker::new().walk_chain(chain, &mut voting_closed_visitor);
+    let tallies = TallyCalculator::new(&genesis).calculate(chain, head.height, &head.block);
+
+    Ok(AuditExport {
+        schema_version: AUDIT_SCHEMA_VERSION,
+        exported_at: SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_secs(),
+        genesis_configuration_hash,
+        genesis,
+        height: head.height,
+        head_identifier: head.block.identifier,
+        phase: voting_closed_visitor.get_election_phase(),
+        blocks,
+        tallies,
+    })
+}
+
+/// Verify the given export without connecting to any node, by applying the rules a node applies to blocks it receives:
+///
+/// - The schema is supported and the genesis configuration hash matches the exported genesis configuration.
+/// - The blocks form a chain starting with a genesis block and ending with the exported head.
+/// - The identifier of each block matches its content and the block is sealed, and if required signed, by an authorized sealer.
+/// - All transactions are valid, i.e. the membership and cast-as-intended proofs of all votes verify,
+///   as do the signatures of the election authority and the registrar where required.
+/// - The phase and the tallies match the ones calculated from the blocks.
+///
+/// Each block is checked against the blocks preceding it, so that a single invalid block does not hide the state of the following ones.
+pub fn verify(audit_export: &AuditExport) -> AuditReport {
+    let mut report = AuditReport {
+        checks: vec![],
+        verified_blocks: 0,
+        verified_transactions: 0,
+    };
+
+    let schema_outcome = if audit_export.schema_version <= AUDIT_SCHEMA_VERSION {
+        Ok(())
+    } else {
+        Err(format!("Schema version {} is not supported, at most {} is", audit_export.schema_version, AUDIT_SCHEMA_VERSION))
+    };
+    report.check("Schema version".to_string(), schema_outcome);
+
+    let genesis_configuration_hash = audit_export.genesis.get_configuration_hash();
+    let genesis_outcome = if genesis_configuration_hash == audit_export.genesis_configuration_hash {
+        Ok(())
+    } else {
+        Err(format!("The genesis configuration hashes to {} instead", genesis_configuration_hash))
+    };
+    report.check("Genesis configuration hash".to_string(), genesis_outcome);
+
+    let mut blocks = audit_export.blocks.iter();
+    let genesis_block = match blocks.next() {
+        Some(genesis_block) => genesis_block,
+        None => {
+            report.check("Genesis block".to_string(), Err("The export holds no blocks".to_string()));
+            return report;
+        }
+    };
+
+    let genesis_block_outcome = if !genesis_block.has_valid_identifier() {
+        Err("Identifier does not match the content of the block".to_string())
+    } else if genesis_block.data.sealer.is_some() || !genesis_block.data.transactions.is_empty() {
+        Err("The genesis block must neither name a sealer nor hold transactions".to_string())
+    } else {
+        Ok(())
+    };
+    report.check(format!("Block {} at height {}", genesis_block.identifier, Height::GENESIS), genesis_block_outcome);
+    report.verified_blocks += 1;
+
+    let mut chain = Chain::from_genesis_block(genesis_configuration_hash, genesis_block.clone());
+    match chain.set_sealer_set(Some(audit_export.genesis.get_sealer_set())) {
+        Ok(()) => {}
+        Err(e) => {
+            report.check("Sealers of the genesis configuration".to_string(), Err(format!("{:?}", e)));
+            return report;
+        }
+    }
+
+    let validator = BlockValidator::new(&audit_export.genesis);
+    let mut parent = genesis_block;
+    for (offset, block) in blocks.enumerate() {
+        let subject = format!("Block {} at height {}", block.identifier, Height::new(offset + 1));
+        if block.data.parent != parent.identifier {
+            report.check(subject, Err(format!("Parent {} does not precede the block", block.data.parent)));
+            return report;
+        }
+
+        let outcome = validator.validate(&chain, block).map_err(|rejection| rejection.to_string());
+        report.check(subject, outcome);
+        report.verified_blocks += 1;
+        report.verified_transactions += block.data.transactions.len();
+
+        chain.add_block(block.clone());
+        parent = block;
+    }
+
+    let head_height = Height::new(audit_export.blocks.len() - 1);
+    let head_outcome = if parent.identifier != audit_export.head_identifier {
+        Err(format!("The last block is {}", parent.identifier))
+    } else if head_height != audit_export.height {
+        Err(format!("The chain holds {} blocks", audit_export.blocks.len()))
+    } else {
+        Ok(())
+    };
+    report.check(format!("Head {} at height {}", audit_export.head_identifier, audit_export.height), head_outcome);
+
+    let mut voting_closed_visitor = VotingClosedVisitor::new();
+    LongestPathWalker::new().walk_chain(&chain, &mut voting_closed_visitor);
+    let phase = voting_closed_visitor.get_election_phase();
+    let phase_outcome = if phase == audit_export.phase {
+        Ok(())
+    } else {
+        Err(format!("The blocks put the election in phase {:?}", phase))
+    };
+    report.check(format!("Election phase {:?}", audit_export.phase), phase_outcome);
+
+    let tallies = TallyCalculator::new(&audit_export.genesis).calculate(&chain, head_height, parent);
+    for tally in tallies.iter() {
+        let tally_outcome = match audit_export.tallies.iter().find(|exported_tally| exported_tally.question_id == tally.question_id) {
+            Some(exported_tally) if exported_tally == tally => Ok(()),
+            Some(_) => Err(format!("The blocks hold {} votes summing up to another cipher text or decrypted sum", tally.total_votes)),
+            None => Err("The tally is missing".to_string())
+        };
+        report.check(format!("Tally of question {:?}", tally.question_id), tally_outcome);
+    }
+    if audit_export.tallies.len() != tallies.len() {
+        report.check("Tallies".to_string(), Err(format!("{} tallies are exported for {} questions", audit_export.tallies.len(), tallies.len())));
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod audit_test {
+
+    use super::*;
+    use ::chain::hasher::Hasher;
+    use ::chain::transaction::Transaction;
+    use serde_json;
+
+    #[test]
+    fn test_export_canonical_chain() {
+        let genesis = Genesis::new("genesis.json", "public_uciv.json", "public_key.json");
+        let mut chain = Chain::new(genesis.get_configuration_hash(), Hasher::default());
+        chain.set_sealer_set(Some(genesis.get_sealer_set())).unwrap();
+
+        let mut parent = chain.blocks[&chain.genesis_identifier_hash].clone();
+        let opening = Transaction::new_voting_opened(Hasher::default(), genesis.get_configuration_hash(), 0);
+        let closing = Transaction::new_voting_closed(Hasher::default(), genesis.get_configuration_hash(), 1);
+        for (height, transaction) in vec![opening, closing].into_iter().enumerate() {
+            let mut block = Block::new(Hasher::default(), parent.identifier.clone(), Some(genesis.sealer[height]), vec![transaction]);
+            block.data.timestamp = parent.data.timestamp + 1;
+            block.identifier = block.get_header().unwrap().hash(Hasher::default());
+            assert!(chain.add_block(block.clone()));
+            parent = block;
+        }
+
+        let audit_export = from_chain(&chain, genesis).unwrap();
+        assert_eq!(AUDIT_SCHEMA_VERSION, audit_export.schema_version);
+        assert_eq!(Height::new(2), audit_export.height);
+        assert_eq!(parent.identifier, audit_export.head_identifier);
+        assert_eq!(ElectionPhase::Closed, audit_export.phase);
+        assert_eq!(3, audit_export.blocks.len());
+        assert_eq!(chain.genesis_identifier_hash, audit_export.blocks[0].identifier);
+        assert_eq!(1, audit_export.tallies.len());
+
+        // the genesis configuration hash can be recomputed from the exported configuration
+        let imported: AuditExport = serde_json::from_str(&serde_json::to_string(&audit_export).unwrap()).unwrap();
+        assert_eq!(audit_export.genesis_configuration_hash, imported.genesis.get_configuration_hash());
+        assert_eq!(audit_export.blocks, imported.blocks);
+
+        // the chain must belong to the given genesis configuration
+        let mut other_genesis = Genesis::new("genesis.json", "public_uciv.json", "public_key.json");
+        other_genesis.version = "other".to_string();
+        assert!(from_chain(&chain, other_genesis).is_err());
+    }
+
+    #[test]
+    fn test_verify_export() {
+        let genesis = Genesis::new("genesis.json", "public_uciv.json", "public_key.json");
+        let mut chain = Chain::new(genesis.get_configuration_hash(), Hasher::default());
+        chain.set_sealer_set(Some(genesis.get_sealer_set())).unwrap();
+
+        let mut parent = chain.blocks[&chain.genesis_identifier_hash].clone();
+        let opening = Transaction::new_voting_opened(Hasher::default(), genesis.get_configuration_hash(), 0);
+        let closing = Transaction::new_voting_closed(Hasher::default(), genesis.get_configuration_hash(), 1);
+        for (height, transaction) in vec![opening, closing].into_iter().enumerate() {
+            let mut block = Block::new(Hasher::default(), parent.identifier.clone(), Some(genesis.sealer[height]), vec![transaction]);
+            block.data.timestamp = parent.data.timestamp + 1;
+            block.identifier = block.get_header().unwrap().hash(Hasher::default());
+            assert!(chain.add_block(block.clone()));
+            parent = block;
+        }
+
+        let mut audit_export = from_chain(&chain, genesis).unwrap();
+        let report = verify(&audit_export);
+        assert!(report.is_valid(), "{:?}", report.get_failures());
+        assert_eq!(3, report.verified_blocks);
+        assert_eq!(2, report.verified_transactions);
+
+        // a block modified after sealing is detected, while the following blocks are still checked
+        audit_export.blocks[1].data.timestamp -= 1;
+        let report = verify(&audit_export);
+        assert_eq!(1, report.get_failures().len());
+        assert_eq!(3, report.verified_blocks);
+        assert!(report.get_failures()[0].subject.starts_with(&format!("Block {}", audit_export.blocks[1].identifier)));
+        audit_export.blocks[1].data.timestamp += 1;
+
+        // as is a block removed from the chain
+        audit_export.blocks.remove(1);
+        assert!(!verify(&audit_export).is_valid());
+    }
+}
@@ -0,0 +1,164 @@
+use ::chain::chain_visitor::ElectionPhase;
+use ::export::ElectionResult;
+
+/// Render the given result as an EML 510 (count) document
+/// of a referendum with the options "yes" and "no".
+///
+/// If the tally was not decrypted, only the turnout is included.
+pub fn render(result: &ElectionResult) -> String {
+    let mut selections = String::new();
+    match (result.yes_votes, result.get_no_votes()) {
+        (Some(yes_votes), Some(no_votes)) => {
+            selections.push_str(&render_selection("yes", "Yes", yes_votes));
+            selections.push_str(&render_selection("no", "No", no_votes));
+        }
+        _ => {
+            selections.push_str("            <!-- The tally was not decrypted, hence only the turnout is included -->\n");
+        }
+    }
+
+    let mut remarks = String::new();
+    if ElectionPhase::Closed != result.phase {
+        remarks.push_str("  <!-- Provisional result: The voting was not closed at the time of the export -->\n");
+    }
+
+    format!(
+r#"<?xml version="1.0" encoding="UTF-8"?>
+<EML xmlns="urn:oasis:names:tc:evs:schema:eml" Id="510" SchemaVersion="7.0">
+{remarks}  <TransactionId>{transaction_id}</TransactionId>
+  <IssueDate>{issue_date}</IssueDate>
+  <Count>
+    <EventIdentifier/>
+    <Election>
+      <ElectionIdentifier Id="{election_id}">
+        <ElectionName>{election_name}</ElectionName>
+        <ElectionCategory>Referendum</ElectionCategory>
+      </ElectionIdentifier>
+      <Contests>
+        <Contest>
+          <ContestIdentifier Id="1"/>
+          <TotalVotes>
+{selections}            <Cast>{total_votes}</Cast>
+            <TotalCounted>{total_votes}</TotalCounted>
+          </TotalVotes>
+        </Contest>
+      </Contests>
+    </Election>
+  </Count>
+</EML>
+"#,
+        remarks = remarks,
+        transaction_id = escape(result.head_identifier.as_str()),
+        issue_date = format_timestamp(result.issued_at),
+        election_id = escape(&result.election_identifier),
+        election_name = escape(&result.election_name),
+        selections = selections,
+        total_votes = result.total_votes
+    )
+}
+
+fn render_selection(identifier: &str, name: &str, votes: u64) -> String {
+    format!(
+r#"            <Selection>
+              <ReferendumOptionIdentifier Id="{}">{}</ReferendumOptionIdentifier>
+              <ValidVotes>{}</ValidVotes>
+            </Selection>
+"#,
+        identifier,
+        name,
+        votes
+    )
+}
+
+/// Escape the characters which must not occur literally in XML text or attributes.
+fn escape(value: &str) -> String {
+    value.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Format the given seconds since the epoch as UTC date time, e.g. `2018-07-01T12:00:00Z`.
+fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let seconds_of_day = timestamp % 86400;
+
+    // convert the days since the epoch to a civil date,
+    // see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z / 146097;
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        (seconds_of_day % 3600) / 60,
+        seconds_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod eml_test {
+
+    use super::*;
+    use ::chain::types::{BlockHash, Height};
+    use crypto_rs::arithmetic::mod_int::{From as ModIntFrom, ModInt};
+    use crypto_rs::el_gamal::ciphertext::CipherText;
+    use num::BigInt;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!("Referendum &amp; Initiative", escape("Referendum & Initiative"));
+        assert_eq!("&lt;/ElectionName&gt;&lt;ElectionName&gt;", escape("</ElectionName><ElectionName>"));
+        assert_eq!("&quot;yes&quot; or &apos;no&apos;", escape("\"yes\" or 'no'"));
+        assert_eq!("&amp;amp;", escape("&amp;"));
+        assert_eq!("Abstimmung über", escape("Abstimmung über"));
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!("1970-01-01T00:00:00Z", format_timestamp(0));
+        assert_eq!("2018-07-01T12:00:00Z", format_timestamp(1530446400));
+        // leap days, and the last second of a century which is not a leap year
+        assert_eq!("2000-02-29T23:59:59Z", format_timestamp(951868799));
+        assert_eq!("2024-02-29T00:00:00Z", format_timestamp(1709164800));
+        assert_eq!("2100-02-28T23:59:59Z", format_timestamp(4107542399));
+        assert_eq!("2100-03-01T00:00:00Z", format_timestamp(4107542400));
+    }
+
+    #[test]
+    fn test_render_escapes_values() {
+        let result = ElectionResult {
+            election_identifier: "id\" Injected=\"1".to_string(),
+            election_name: "<Vote> & Count".to_string(),
+            phase: ElectionPhase::Closed,
+            total_votes: 3,
+            yes_votes: Some(2),
+            encrypted_tally: CipherText {
+                big_g: ModInt::from_value_modulus(BigInt::from(1), BigInt::from(23)),
+                big_h: ModInt::from_value_modulus(BigInt::from(2), BigInt::from(23)),
+                random: ModInt::from_value_modulus(BigInt::from(0), BigInt::from(11)),
+            },
+            chain_height: Height::new(4),
+            head_identifier: BlockHash::from("head"),
+            issued_at: 1530446400,
+        };
+
+        let eml = render(&result);
+        assert!(eml.contains(r#"<ElectionIdentifier Id="id&quot; Injected=&quot;1">"#));
+        assert!(eml.contains("<ElectionName>&lt;Vote&gt; &amp; Count</ElectionName>"));
+        assert!(eml.contains("<IssueDate>2018-07-01T12:00:00Z</IssueDate>"));
+        assert!(eml.contains("<ValidVotes>1</ValidVotes>"));
+        assert!(!eml.contains("Provisional"));
+    }
+}
@@ -0,0 +1,129 @@
+use ::chain::chain_visitor::ElectionPhase;
+use ::chain::types::{BlockHash, Height};
+use ::p2p::client;
+use ::p2p::codec::Message;
+use crypto_rs::el_gamal::ciphertext::CipherText;
+use crypto_rs::el_gamal::encryption::{PrivateKey, decrypt};
+use num::ToPrimitive;
+use serde_json;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Rendering of election results in the Election Markup Language (EML).
+pub mod eml;
+
+/// Self-contained exports of the canonical chain for third-party verifiers.
+pub mod audit;
+
+/// Machine-readable exports of the tally of each question.
+pub mod tally;
+
+/// The result of an election as exported to election management systems.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct ElectionResult {
+    /// The identifier of the election, as known to the election management system.
+    pub election_identifier: String,
+    /// The human readable name of the election.
+    pub election_name: String,
+    /// The phase of the election at the time of the export.
+    /// The result is only final if the election is closed.
+    pub phase: ElectionPhase,
+    /// The amount of votes cast, i.e. the turnout.
+    pub total_votes: usize,
+    /// The amount of votes in favour, if the tally was decrypted.
+    pub yes_votes: Option<u64>,
+    /// The homomorphic sum of all votes.
+    pub encrypted_tally: CipherText,
+    /// The height of the chain the tally was calculated on.
+    pub chain_height: Height,
+    /// The identifier of the head of the chain the tally was calculated on.
+    pub head_identifier: BlockHash,
+    /// The time of the export, in seconds since the epoch.
+    pub issued_at: u64,
+}
+
+impl ElectionResult {
+    /// Returns the amount of votes against, if the tally was decrypted.
+    pub fn get_no_votes(&self) -> Option<u64> {
+        self.yes_votes.map(|yes_votes| (self.total_votes as u64).saturating_sub(yes_votes))
+    }
+}
+
+/// The formats an `ElectionResult` can be exported to.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum ExportFormat {
+    /// The Election Markup Language, as specified by OASIS.
+    Eml,
+    /// The `ElectionResult` serialized as JSON.
+    Json,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "eml" => Ok(ExportFormat::Eml),
+            "json" => Ok(ExportFormat::Json),
+            other => Err(format!("Unknown export format {:?}", other))
+        }
+    }
+}
+
+/// Render the given result in the given format.
+pub fn render(result: &ElectionResult, format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Eml => eml::render(result),
+        ExportFormat::Json => serde_json::to_string_pretty(result).unwrap()
+    }
+}
+
+/// Fetch the result of the election from the node listening for RPC connections
+/// on the given address.
+///
+/// - `rpc_address` The RPC listen address of the node.
+/// - `rpc_token` The token granting the `Tally` scope, if the node requires one.
+/// - `election_identifier` The identifier of the election to include in the result.
+/// - `election_name` The name of the election to include in the result.
+/// - `private_key` The private key to decrypt the tally with. If None, only the
+///   encrypted tally and the turnout are included in the result.
+pub fn fetch_result(rpc_address: &SocketAddr, rpc_token: Option<&str>, election_identifier: String, election_name: String, private_key: Option<&PrivateKey>) -> Result<ElectionResult, String> {
+    // both are requested at once, so that the tally belongs to the head of the status
+    let (election_status, tally) = match client::rpc_request(rpc_address, rpc_token, Message::ElectionResultRequest) {
+        Ok(Message::ElectionResultResponse(election_status, tally)) => (election_status, tally),
+        Ok(other) => {
+            return Err(format!("Expected the election status and the tally but got {:?}", other));
+        }
+        Err(e) => {
+            return Err(e);
+        }
+    };
+
+    let yes_votes = match private_key {
+        Some(private_key) => {
+            let decrypted_tally = decrypt(private_key, tally.cipher_text.clone());
+            match decrypted_tally.value.to_u64() {
+                Some(yes_votes) => Some(yes_votes),
+                None => {
+                    return Err(format!("Decrypted tally {:?} is not a valid amount of votes", decrypted_tally.value));
+                }
+            }
+        }
+        None => None
+    };
+
+    let issued_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+
+    Ok(ElectionResult {
+        election_identifier,
+        election_name,
+        phase: election_status.phase,
+        total_votes: tally.total_votes,
+        yes_votes,
+        encrypted_tally: tally.cipher_text,
+        chain_height: election_status.height,
+        head_identifier: election_status.head_identifier,
+        issued_at,
+    })
+}
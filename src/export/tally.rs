@@ -0,0 +1,197 @@
+use ::chain::chain_visitor::ElectionPhase;
+use ::chain::types::{BlockHash, Height};
+use ::export::audit::AuditExport;
+use ::p2p::client;
+use ::p2p::codec::Message;
+use crypto_rs::el_gamal::ciphertext::CipherText;
+use serde_json;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The tally of each question of an election in machine-readable form, for election management systems.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct TallyReport {
+    /// The phase of the election at the head the tally was calculated on.
+    /// The tally is only final if the voting is closed.
+    pub phase: ElectionPhase,
+    /// The height of the head of the chain the tally was calculated on.
+    pub height: Height,
+    /// The identifier of the head of the chain the tally was calculated on.
+    pub head_identifier: BlockHash,
+    /// The tally of each question, starting with the one whose voters are declared in `public_uciv.json`.
+    pub questions: Vec<QuestionResult>,
+}
+
+/// The tally of a single question in a `TallyReport`.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct QuestionResult {
+    /// The identifier of the question, None for the one whose voters are declared in `public_uciv.json`.
+    pub question_id: Option<String>,
+    /// The amount of votes counted on the question, i.e. its turnout.
+    pub total_votes: usize,
+    /// The decrypted sum of the votes, once the sealers decrypted it jointly.
+    pub decrypted_sum: Option<u64>,
+    /// The homomorphic sum of the votes.
+    pub cipher_text: CipherText,
+}
+
+/// The formats a `TallyReport` can be written in.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum TallyFormat {
+    /// One line per question, preceded by a header naming the columns.
+    Csv,
+    /// The `TallyReport` serialized as JSON.
+    Json,
+}
+
+impl TallyFormat {
+    /// Returns the format matching the extension of the given file name, if any.
+    pub fn from_file_name(file_name: &str) -> Option<TallyFormat> {
+        Path::new(file_name).extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| extension.to_lowercase().parse().ok())
+    }
+}
+
+impl FromStr for TallyFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "csv" => Ok(TallyFormat::Csv),
+            "json" => Ok(TallyFormat::Json),
+            other => Err(format!("Unknown tally format {:?}", other))
+        }
+    }
+}
+
+/// Fetch the tally of each question from the node listening for RPC connections on the given address.
+///
+/// - `rpc_address` The RPC listen address of the node.
+/// - `rpc_token` The token granting the `Tally` scope, if the node requires one.
+pub fn fetch(rpc_address: &SocketAddr, rpc_token: Option<&str>) -> Result<TallyReport, String> {
+    // both are requested at once, so that the tally belongs to the head of the status
+    let (election_status, tally) = match client::rpc_request(rpc_address, rpc_token, Message::ElectionResultRequest) {
+        Ok(Message::ElectionResultResponse(election_status, tally)) => (election_status, tally),
+        Ok(other) => {
+            return Err(format!("Expected the election status and the tally but got {:?}", other));
+        }
+        Err(e) => {
+            return Err(e);
+        }
+    };
+
+    let mut questions = vec![QuestionResult {
+        question_id: None,
+        total_votes: tally.total_votes,
+        decrypted_sum: tally.decrypted_sum,
+        cipher_text: tally.cipher_text,
+    }];
+    questions.extend(tally.questions.into_iter().map(|(question_id, question_tally)| QuestionResult {
+        question_id: Some(question_id),
+        total_votes: question_tally.total_votes,
+        decrypted_sum: question_tally.decrypted_sum,
+        cipher_text: question_tally.cipher_text,
+    }));
+
+    Ok(TallyReport {
+        phase: election_status.phase,
+        height: election_status.height,
+        head_identifier: election_status.head_identifier,
+        questions,
+    })
+}
+
+/// Returns the tally of each question at the head of the given exported chain.
+/// The export should be verified with `audit::verify` before, as its tallies are taken as is.
+pub fn from_audit_export(audit_export: &AuditExport) -> TallyReport {
+    TallyReport {
+        phase: audit_export.phase.clone(),
+        height: audit_export.height,
+        head_identifier: audit_export.head_identifier.clone(),
+        questions: audit_export.tallies.iter()
+            .map(|final_tally| QuestionResult {
+                question_id: final_tally.question_id.clone(),
+                total_votes: final_tally.total_votes,
+                decrypted_sum: final_tally.decrypted_sum,
+                cipher_text: final_tally.cipher_text.clone(),
+            })
+            .collect(),
+    }
+}
+
+/// Render the given report in the given format.
+///
+/// The CSV columns are `question_id`, empty for the question whose voters are declared in `public_uciv.json`,
+/// `phase`, `height`, `head_identifier`, `total_votes`, `decrypted_sum`, empty if not decrypted,
+/// and the components `cipher_text_big_g` and `cipher_text_big_h` of the sum in decimal.
+pub fn render(report: &TallyReport, format: TallyFormat) -> String {
+    match format {
+        TallyFormat::Csv => {
+            let mut csv = "question_id,phase,height,head_identifier,total_votes,decrypted_sum,cipher_text_big_g,cipher_text_big_h\n".to_string();
+            for question in report.questions.iter() {
+                csv.push_str(&format!(
+                    "{},{:?},{},{},{},{},{},{}\n",
+                    escape_csv(question.question_id.as_ref().map_or("", String::as_str)),
+                    report.phase,
+                    report.height,
+                    report.head_identifier,
+                    question.total_votes,
+                    question.decrypted_sum.map_or(String::new(), |decrypted_sum| decrypted_sum.to_string()),
+                    question.cipher_text.big_g.value,
+                    question.cipher_text.big_h.value
+                ));
+            }
+
+            csv
+        }
+        TallyFormat::Json => serde_json::to_string_pretty(report).unwrap()
+    }
+}
+
+/// Quote the given field if it contains characters with a special meaning in CSV.
+fn escape_csv(field: &str) -> String {
+    if field.contains(',') || field.contains('"') || field.contains('\n') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tally_test {
+
+    use super::*;
+    use crypto_rs::arithmetic::mod_int::{From as ModIntFrom, ModInt};
+    use num::BigInt;
+
+    #[test]
+    fn test_render_csv() {
+        let cipher_text = |value: u32| CipherText {
+            big_g: ModInt::from_value_modulus(BigInt::from(value), BigInt::from(23)),
+            big_h: ModInt::from_value_modulus(BigInt::from(value + 1), BigInt::from(23)),
+            random: ModInt::from_value_modulus(BigInt::from(0), BigInt::from(11)),
+        };
+        let report = TallyReport {
+            phase: ElectionPhase::Closed,
+            height: Height::new(7),
+            head_identifier: BlockHash::from("head"),
+            questions: vec![
+                QuestionResult { question_id: None, total_votes: 3, decrypted_sum: Some(2), cipher_text: cipher_text(4) },
+                QuestionResult { question_id: Some("q,2".to_string()), total_votes: 1, decrypted_sum: None, cipher_text: cipher_text(9) },
+            ],
+        };
+
+        let csv = render(&report, TallyFormat::Csv);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(3, lines.len());
+        assert_eq!(",Closed,7,head,3,2,4,5", lines[1]);
+        assert_eq!("\"q,2\",Closed,7,head,1,,9,10", lines[2]);
+
+        assert_eq!(report, serde_json::from_str(&render(&report, TallyFormat::Json)).unwrap());
+        assert_eq!(Some(TallyFormat::Csv), TallyFormat::from_file_name("result.CSV"));
+        assert_eq!(Some(TallyFormat::Json), TallyFormat::from_file_name("out/result.json"));
+        assert_eq!(None, TallyFormat::from_file_name("result.xml"));
+    }
+}
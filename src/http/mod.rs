@@ -0,0 +1,152 @@
+use ::p2p::peers;
+use native_tls::TlsConnector;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+/// Split the given URL into its authority, i.e. `host:port`, and its path.
+pub fn parse_url(url: &str) -> Result<(String, String), String> {
+    split_url(url, "http://", 80)
+}
+
+/// Split the given `https://` URL into its authority, i.e. `host:port`, and its path.
+pub fn parse_https_url(url: &str) -> Result<(String, String), String> {
+    split_url(url, "https://", 443)
+}
+
+fn split_url(url: &str, scheme: &str, default_port: u16) -> Result<(String, String), String> {
+    if !url.starts_with(scheme) {
+        return Err(format!("Only {} URLs are supported, got {:?}", scheme, url));
+    }
+
+    let without_scheme = &url[scheme.len()..];
+    let (authority, path) = match without_scheme.find('/') {
+        Some(index) => (&without_scheme[..index], &without_scheme[index..]),
+        None => (without_scheme, "/")
+    };
+
+    if authority.is_empty() {
+        return Err(format!("Missing host in URL {:?}", url));
+    }
+
+    let authority = if authority.contains(':') {
+        authority.to_string()
+    } else {
+        format!("{}:{}", authority, default_port)
+    };
+
+    Ok((authority, path.to_string()))
+}
+
+/// Send the given raw request to the given authority, i.e. `host:port`.
+///
+/// Returns the raw response, read until the server closes the connection
+/// or `max_response_size` bytes were received.
+pub fn send(authority: &str, request: &str, timeout: Duration, max_response_size: u64) -> Result<String, String> {
+    match connect(authority, timeout) {
+        Ok(stream) => exchange(stream, request, max_response_size),
+        Err(e) => Err(e)
+    }
+}
+
+/// Send the given raw request to the given authority, i.e. `host:port`, over TLS.
+/// The certificate of the server must be valid for its host.
+///
+/// Returns the raw response, read until the server closes the connection
+/// or `max_response_size` bytes were received.
+pub fn send_tls(authority: &str, request: &str, timeout: Duration, max_response_size: u64) -> Result<String, String> {
+    let stream = match connect(authority, timeout) {
+        Ok(stream) => stream,
+        Err(e) => {
+            return Err(e);
+        }
+    };
+
+    let connector = match TlsConnector::new() {
+        Ok(connector) => connector,
+        Err(e) => {
+            return Err(format!("Failed to set up TLS: {:?}", e));
+        }
+    };
+
+    let host = authority.rsplitn(2, ':').last().unwrap_or(authority);
+    match connector.connect(host, stream) {
+        Ok(tls_stream) => exchange(tls_stream, request, max_response_size),
+        Err(e) => Err(format!("Failed to establish TLS with {}: {:?}", host, e))
+    }
+}
+
+/// Connect to the given authority, i.e. `host:port`, with the given timeout
+/// applied to connecting, reading and writing.
+fn connect(authority: &str, timeout: Duration) -> Result<TcpStream, String> {
+    let address = match peers::resolve(authority) {
+        Some(address) => address,
+        None => {
+            return Err(format!("Failed to resolve {}", authority));
+        }
+    };
+
+    let stream = match TcpStream::connect_timeout(&address, timeout) {
+        Ok(stream) => stream,
+        Err(e) => {
+            return Err(format!("Failed to connect: {:?}", e));
+        }
+    };
+
+    match stream.set_read_timeout(Some(timeout)).and_then(|_| stream.set_write_timeout(Some(timeout))) {
+        Ok(()) => Ok(stream),
+        Err(e) => Err(format!("Failed to configure connection: {:?}", e))
+    }
+}
+
+/// Send the given raw request on the given stream and read the response.
+fn exchange<S: Read + Write>(mut stream: S, request: &str, max_response_size: u64) -> Result<String, String> {
+    let mut response = String::new();
+    let result = stream.write_all(request.as_bytes())
+        .and_then(|_| stream.flush())
+        .and_then(|_| Read::by_ref(&mut stream).take(max_response_size).read_to_string(&mut response));
+    match result {
+        Ok(_) => Ok(response),
+        // the status line may be complete even if the server closed the connection early
+        Err(_) if !response.is_empty() => Ok(response),
+        Err(e) => Err(format!("Failed to send request: {:?}", e))
+    }
+}
+
+/// Returns the status code of the given raw response,
+/// or None if it does not start with a valid status line.
+pub fn get_status(response: &str) -> Option<u16> {
+    response.split_whitespace().nth(1).and_then(|status| status.parse::<u16>().ok())
+}
+
+/// Returns the body of the given raw response, i.e. everything after the headers.
+pub fn get_body(response: &str) -> &str {
+    match response.find("\r\n\r\n") {
+        Some(index) => &response[index + 4..],
+        None => ""
+    }
+}
+
+#[cfg(test)]
+mod http_test {
+
+    use super::{get_body, get_status, parse_https_url, parse_url};
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(Ok(("localhost:8080".to_string(), "/hooks/node".to_string())), parse_url("http://localhost:8080/hooks/node"));
+        assert_eq!(Ok(("example.org:80".to_string(), "/".to_string())), parse_url("http://example.org"));
+        assert!(parse_url("https://example.org/").is_err());
+        assert!(parse_url("http:///hooks").is_err());
+        assert_eq!(Ok(("example.org:443".to_string(), "/checkpoints".to_string())), parse_https_url("https://example.org/checkpoints"));
+        assert!(parse_https_url("http://example.org/").is_err());
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let response = "HTTP/1.0 200 OK\r\nContent-Type: text/xml\r\n\r\n<root/>";
+        assert_eq!(Some(200), get_status(response));
+        assert_eq!("<root/>", get_body(response));
+        assert_eq!(None, get_status("garbage"));
+    }
+}
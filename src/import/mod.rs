@@ -0,0 +1,248 @@
+use ::chain::transaction::{Transaction, TransactionType};
+use ::mac::{constant_time_eq, decode_hex, encode_hex, hmac_sha1};
+use ::p2p::client;
+use ::p2p::codec::Message;
+use ::protocol::clique::TransactionInclusion;
+use serde_json;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Read;
+use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The secret shared between an offline client producing batches of ballots
+/// and the operator importing them, used to sign and verify the batches.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BatchKey {
+    /// The name of the key, e.g. the precinct it is issued to.
+    pub name: String,
+    /// The hex encoded secret used to sign batches.
+    pub secret: String,
+}
+
+impl BatchKey {
+    /// Read the batch key from the given JSON file.
+    pub fn from_file(file_name: &str) -> Result<BatchKey, String> {
+        let mut contents = String::new();
+        let read_result = File::open(file_name).and_then(|mut file| file.read_to_string(&mut contents));
+        match read_result {
+            Ok(_) => {}
+            Err(e) => {
+                return Err(format!("Failed to read batch key {:?}: {:?}", file_name, e));
+            }
+        }
+
+        let key: BatchKey = match serde_json::from_str(&contents) {
+            Ok(key) => key,
+            Err(e) => {
+                return Err(format!("Failed to parse batch key {:?}: {:?}", file_name, e));
+            }
+        };
+
+        match decode_hex(&key.secret) {
+            Some(ref secret) if secret.len() >= 16 => Ok(key),
+            _ => Err(format!("The secret of batch key {:?} must be hex encoded and at least 16 bytes long", file_name))
+        }
+    }
+
+    /// Returns the hex encoded signature of the given content.
+    fn sign(&self, content: &str) -> String {
+        let secret = decode_hex(&self.secret).unwrap_or_default();
+
+        encode_hex(&hmac_sha1(&secret, content.as_bytes()))
+    }
+}
+
+/// A batch of encrypted ballots collected offline, e.g. by a precinct
+/// without connectivity, signed by the offline client which produced it.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BallotBatch {
+    /// The precinct or the offline client which collected the ballots.
+    pub origin: String,
+    /// The time the batch was closed at, in seconds since the epoch.
+    pub created_at: u64,
+    /// The vote transactions of the batch.
+    pub transactions: Vec<Transaction>,
+    /// The hex encoded HMAC-SHA1 of the origin, the time of creation and the transactions,
+    /// keyed with the secret of a `BatchKey`.
+    pub signature: String,
+}
+
+impl BallotBatch {
+    /// Create a new batch of the given transactions, signed with the given key.
+    pub fn new(origin: String, created_at: u64, transactions: Vec<Transaction>, key: &BatchKey) -> BallotBatch {
+        let mut batch = BallotBatch {
+            origin,
+            created_at,
+            transactions,
+            signature: String::new(),
+        };
+        batch.signature = key.sign(&batch.get_signed_content());
+
+        batch
+    }
+
+    /// Read a batch from the given JSON file.
+    pub fn from_file(file_name: &str) -> Result<BallotBatch, String> {
+        let mut contents = String::new();
+        let read_result = File::open(file_name).and_then(|mut file| file.read_to_string(&mut contents));
+        match read_result {
+            Ok(_) => {}
+            Err(e) => {
+                return Err(format!("Failed to read ballot batch {:?}: {:?}", file_name, e));
+            }
+        }
+
+        match serde_json::from_str(&contents) {
+            Ok(batch) => Ok(batch),
+            Err(e) => Err(format!("Failed to parse ballot batch {:?}: {:?}", file_name, e))
+        }
+    }
+
+    /// Check that the batch is signed with the given key, consists
+    /// of votes only and does not contain any vote twice.
+    pub fn verify(&self, key: &BatchKey) -> Result<(), String> {
+        if !constant_time_eq(&self.signature, &key.sign(&self.get_signed_content())) {
+            return Err(format!("The batch of {:?} is not signed with the batch key {:?}", self.origin, key.name));
+        }
+
+        let mut identifiers = HashSet::new();
+        for transaction in self.transactions.iter() {
+            if TransactionType::Vote != transaction.trx_type {
+                return Err(format!("Transaction {} of the batch is not a vote", transaction.identifier));
+            }
+            if !identifiers.insert(&transaction.identifier) {
+                return Err(format!("Vote {} is contained twice in the batch", transaction.identifier));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the content covered by the signature.
+    fn get_signed_content(&self) -> String {
+        serde_json::to_string(&(&self.origin, self.created_at, &self.transactions)).unwrap()
+    }
+}
+
+/// The outcome of importing a single ballot.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub enum BallotOutcome {
+    /// The node accepted the ballot for inclusion in a block.
+    Accepted,
+    /// The ballot was already included in the canonical chain, e.g. by an earlier import.
+    AlreadyIncluded(TransactionInclusion),
+    /// The node rejected the ballot for the given reason, e.g. due to invalid proofs.
+    Rejected(String),
+    /// The ballot could not be submitted, e.g. because the node was not reachable.
+    Failed(String),
+}
+
+/// The outcome of a single ballot of an imported batch.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct BallotReport {
+    /// The identifier of the vote transaction.
+    pub transaction_identifier: String,
+    pub outcome: BallotOutcome,
+}
+
+/// Reports the outcome of importing a batch of ballots.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct ImportReport {
+    /// The origin of the imported batch.
+    pub origin: String,
+    /// The time the imported batch was closed at, in seconds since the epoch.
+    pub created_at: u64,
+    /// The time of the import, in seconds since the epoch.
+    pub imported_at: u64,
+    /// The outcome of each ballot, in the order of the batch.
+    pub ballots: Vec<BallotReport>,
+}
+
+impl ImportReport {
+    /// Returns the amount of ballots which were accepted or already included.
+    pub fn get_amount_imported(&self) -> usize {
+        let mut amount_imported = 0;
+        for ballot in self.ballots.iter() {
+            match ballot.outcome {
+                BallotOutcome::Accepted | BallotOutcome::AlreadyIncluded(_) => amount_imported += 1,
+                BallotOutcome::Rejected(_) | BallotOutcome::Failed(_) => {}
+            }
+        }
+
+        amount_imported
+    }
+}
+
+/// Submit the ballots of the given batch one by one to the node listening for
+/// RPC connections on the given address, as if they were cast by online clients.
+///
+/// The batch must be verified before. Failing to submit a ballot does not stop the import,
+/// hence the report lists the outcome of each ballot. As submissions are idempotent,
+/// a batch may be imported again to retry the ballots which could not be submitted.
+///
+/// - `rpc_address` The RPC listen address of the node.
+/// - `rpc_token` The token granting the `Submit` scope, if the node requires one.
+/// - `batch` The batch to import.
+pub fn import_batch(rpc_address: &SocketAddr, rpc_token: Option<&str>, batch: &BallotBatch) -> ImportReport {
+    let mut ballots = vec![];
+
+    for transaction in batch.transactions.iter() {
+        let outcome = match client::rpc_request(rpc_address, rpc_token, Message::TransactionPayload(transaction.clone())) {
+            Ok(Message::TransactionAccept(_)) => BallotOutcome::Accepted,
+            Ok(Message::TransactionIncluded(inclusion)) => BallotOutcome::AlreadyIncluded(inclusion),
+            Ok(Message::TransactionReject(_, reason)) => BallotOutcome::Rejected(reason),
+            Ok(other) => BallotOutcome::Failed(format!("Got unexpected response {:?}", other)),
+            Err(e) => BallotOutcome::Failed(e)
+        };
+
+        ballots.push(BallotReport {
+            transaction_identifier: transaction.identifier.clone(),
+            outcome,
+        });
+    }
+
+    ImportReport {
+        origin: batch.origin.clone(),
+        created_at: batch.created_at,
+        imported_at: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+        ballots,
+    }
+}
+
+#[cfg(test)]
+mod import_test {
+
+    use super::*;
+
+    #[test]
+    fn test_verify_batch() {
+        let key = BatchKey {
+            name: "precinct-1".to_string(),
+            secret: "00112233445566778899aabbccddeeff".to_string(),
+        };
+        let vote = Transaction {
+            identifier: "vote-1".to_string(),
+            trx_type: TransactionType::Vote,
+            data: None,
+            metadata: None,
+            sealer_vote: None,
+            phase_change: None,
+            decryption_share: None,
+            dkg_commitment: None,
+            election_key: None,
+            final_tally: None,
+            voter_registration: None,
+        };
+
+        let batch = BallotBatch::new("precinct-1".to_string(), 100, vec![vote.clone()], &key);
+        assert!(batch.verify(&key).is_ok());
+
+        let mut tampered_batch = batch.clone();
+        tampered_batch.created_at = 200;
+        assert!(tampered_batch.verify(&key).is_err());
+
+        let duplicated_batch = BallotBatch::new("precinct-1".to_string(), 100, vec![vote.clone(), vote], &key);
+        assert!(duplicated_batch.verify(&key).is_err());
+    }
+}
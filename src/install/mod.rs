@@ -0,0 +1,314 @@
+use ::config::genesis::GenesisData;
+use serde_json;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The name of the systemd unit.
+pub const SYSTEMD_UNIT_NAME: &str = "node_rs.service";
+
+/// The label of the launchd job.
+pub const LAUNCHD_LABEL: &str = "org.provotum.node_rs";
+
+/// The files a node reads from its working directory on startup.
+pub const REQUIRED_FILES: [&str; 3] = ["genesis.json", "public_key.json", "public_uciv.json"];
+
+/// The service managers a node can be installed with.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum ServiceManager {
+    /// systemd, as used by most Linux distributions.
+    Systemd,
+    /// launchd, as used by macOS.
+    Launchd,
+}
+
+impl ServiceManager {
+    /// Returns the path a system wide service is installed to.
+    pub fn get_default_path(&self) -> PathBuf {
+        match *self {
+            ServiceManager::Systemd => Path::new("/etc/systemd/system").join(SYSTEMD_UNIT_NAME),
+            ServiceManager::Launchd => Path::new("/Library/LaunchDaemons").join(format!("{}.plist", LAUNCHD_LABEL)),
+        }
+    }
+
+    /// Returns the command activating the service written to the given path.
+    pub fn get_activation_hint(&self, path: &Path) -> String {
+        match *self {
+            ServiceManager::Systemd => format!("systemctl daemon-reload && systemctl enable --now {}", SYSTEMD_UNIT_NAME),
+            ServiceManager::Launchd => format!("launchctl load -w {}", path.display()),
+        }
+    }
+}
+
+impl FromStr for ServiceManager {
+    type Err = String;
+
+    fn from_str(service_manager: &str) -> Result<Self, Self::Err> {
+        match service_manager {
+            "systemd" => Ok(ServiceManager::Systemd),
+            "launchd" => Ok(ServiceManager::Launchd),
+            _ => Err(format!("Unknown service manager {:?}, expected systemd or launchd", service_manager))
+        }
+    }
+}
+
+/// Describes how the service runs a node.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct ServiceSpec {
+    /// The absolute path of the node_rs binary.
+    pub binary: PathBuf,
+    /// The absolute path of the directory holding the `REQUIRED_FILES`, in which the node is started.
+    pub working_dir: PathBuf,
+    /// The absolute path of the directory the node persists its data in.
+    pub data_dir: PathBuf,
+    /// The user running the node, or None to run it as the user of the service manager.
+    pub user: Option<String>,
+    /// The arguments of the `start` subcommand, except for the data directory.
+    pub start_args: Vec<String>,
+}
+
+impl ServiceSpec {
+    /// Returns the arguments passed to the binary, starting with the subcommand.
+    pub fn get_arguments(&self) -> Vec<String> {
+        let mut arguments = vec!["start".to_string()];
+        arguments.extend(self.start_args.iter().cloned());
+        arguments.push("--data-dir".to_string());
+        arguments.push(self.data_dir.display().to_string());
+
+        arguments
+    }
+
+    /// Render the service definition for the given service manager.
+    pub fn render(&self, service_manager: ServiceManager) -> String {
+        match service_manager {
+            ServiceManager::Systemd => self.render_systemd_unit(),
+            ServiceManager::Launchd => self.render_launchd_plist(),
+        }
+    }
+
+    fn render_systemd_unit(&self) -> String {
+        let command_line: Vec<String> = Some(self.binary.display().to_string()).into_iter()
+            .chain(self.get_arguments())
+            .map(|argument| quote_systemd_argument(&argument))
+            .collect();
+
+        let mut unit = String::new();
+        unit.push_str("[Unit]\n");
+        unit.push_str("Description=node_rs e-voting blockchain node\n");
+        unit.push_str("Wants=network-online.target\n");
+        unit.push_str("After=network-online.target\n");
+        unit.push_str("\n[Service]\n");
+        unit.push_str("Type=simple\n");
+        if let Some(ref user) = self.user {
+            unit.push_str(&format!("User={}\n", user));
+        }
+        unit.push_str(&format!("WorkingDirectory={}\n", quote_systemd_argument(&self.working_dir.display().to_string())));
+        unit.push_str(&format!("ExecStart={}\n", command_line.join(" ")));
+        // the node shuts down gracefully on SIGTERM
+        unit.push_str("KillSignal=SIGTERM\n");
+        unit.push_str("TimeoutStopSec=30\n");
+        unit.push_str("Restart=on-failure\n");
+        unit.push_str("RestartSec=5\n");
+        unit.push_str("\n[Install]\n");
+        unit.push_str("WantedBy=multi-user.target\n");
+
+        unit
+    }
+
+    fn render_launchd_plist(&self) -> String {
+        let log_file = escape_xml(&self.data_dir.join("node_rs.log").display().to_string());
+
+        let mut plist = String::new();
+        plist.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        plist.push_str("<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n");
+        plist.push_str("<plist version=\"1.0\">\n<dict>\n");
+        plist.push_str(&format!("    <key>Label</key>\n    <string>{}</string>\n", LAUNCHD_LABEL));
+        plist.push_str("    <key>ProgramArguments</key>\n    <array>\n");
+        plist.push_str(&format!("        <string>{}</string>\n", escape_xml(&self.binary.display().to_string())));
+        for argument in self.get_arguments() {
+            plist.push_str(&format!("        <string>{}</string>\n", escape_xml(&argument)));
+        }
+        plist.push_str("    </array>\n");
+        plist.push_str(&format!("    <key>WorkingDirectory</key>\n    <string>{}</string>\n", escape_xml(&self.working_dir.display().to_string())));
+        if let Some(ref user) = self.user {
+            plist.push_str(&format!("    <key>UserName</key>\n    <string>{}</string>\n", escape_xml(user)));
+        }
+        plist.push_str("    <key>RunAtLoad</key>\n    <true/>\n");
+        // restart the node unless it exited successfully, e.g. after a graceful shutdown
+        plist.push_str("    <key>KeepAlive</key>\n    <dict>\n        <key>SuccessfulExit</key>\n        <false/>\n    </dict>\n");
+        plist.push_str(&format!("    <key>StandardOutPath</key>\n    <string>{}</string>\n", log_file));
+        plist.push_str(&format!("    <key>StandardErrorPath</key>\n    <string>{}</string>\n", log_file));
+        plist.push_str("</dict>\n</plist>\n");
+
+        plist
+    }
+}
+
+/// Check that the given service can start a node, i.e. that the binary exists and
+/// the working directory holds all `REQUIRED_FILES` along with a consistent genesis configuration.
+/// Returns the first problem found, if any.
+pub fn validate(spec: &ServiceSpec) -> Result<(), String> {
+    if !spec.binary.is_file() {
+        return Err(format!("Binary {:?} does not exist", spec.binary));
+    }
+
+    for required_file in REQUIRED_FILES.iter() {
+        if !spec.working_dir.join(required_file).is_file() {
+            return Err(format!("Working directory {:?} does not contain {}", spec.working_dir, required_file));
+        }
+    }
+
+    let mut contents = String::new();
+    let genesis_path = spec.working_dir.join("genesis.json");
+    match File::open(&genesis_path).and_then(|mut file| file.read_to_string(&mut contents)) {
+        Ok(_) => {}
+        Err(e) => {
+            return Err(format!("Failed to read {:?}: {}", genesis_path, e));
+        }
+    }
+
+    let genesis_data: GenesisData = match serde_json::from_str(&contents) {
+        Ok(genesis_data) => genesis_data,
+        Err(e) => {
+            return Err(format!("Failed to parse {:?}: {}", genesis_path, e));
+        }
+    };
+
+    match genesis_data.check() {
+        Ok(()) => Ok(()),
+        Err(e) => Err(format!("Invalid genesis configuration {:?}: {}", genesis_path, e))
+    }
+}
+
+/// Create the data directory of the given service, if it does not exist yet, and restrict it to its owner,
+/// as it holds the chain and the audit log. If the service runs as another user, it is handed over to that user.
+pub fn prepare_data_dir(spec: &ServiceSpec) -> Result<(), String> {
+    match fs::create_dir_all(&spec.data_dir) {
+        Ok(()) => {}
+        Err(e) => {
+            return Err(format!("Failed to create data directory {:?}: {}", spec.data_dir, e));
+        }
+    }
+
+    #[cfg(unix)]
+    {
+        match fs::set_permissions(&spec.data_dir, fs::Permissions::from_mode(0o700)) {
+            Ok(()) => {}
+            Err(e) => {
+                return Err(format!("Failed to restrict the permissions of data directory {:?}: {}", spec.data_dir, e));
+            }
+        }
+
+        if let Some(ref user) = spec.user {
+            return change_owner(&spec.data_dir, user);
+        }
+    }
+
+    Ok(())
+}
+
+/// Make the given user the owner of the given path.
+#[cfg(unix)]
+fn change_owner(path: &Path, user: &str) -> Result<(), String> {
+    use libc;
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let (c_user, c_path) = match (CString::new(user), CString::new(path.as_os_str().as_bytes())) {
+        (Ok(c_user), Ok(c_path)) => (c_user, c_path),
+        _ => {
+            return Err(format!("User {:?} or path {:?} contains a null byte", user, path));
+        }
+    };
+
+    let result = unsafe {
+        let passwd = libc::getpwnam(c_user.as_ptr());
+        if passwd.is_null() {
+            return Err(format!("Unknown user {:?}", user));
+        }
+
+        libc::chown(c_path.as_ptr(), (*passwd).pw_uid, (*passwd).pw_gid)
+    };
+
+    if result != 0 {
+        return Err(format!("Failed to hand data directory {:?} over to user {:?}, which requires root privileges", path, user));
+    }
+
+    Ok(())
+}
+
+/// Write the given service definition to the given path, readable by everyone
+/// as it contains no secrets. An existing file is only replaced if requested.
+pub fn write_service_definition(path: &Path, service_definition: &str, replace: bool) -> Result<(), String> {
+    if path.exists() && !replace {
+        return Err(format!("Refusing to overwrite the existing service definition at {:?}", path));
+    }
+
+    let result = File::create(path)
+        .and_then(|mut file| file.write_all(service_definition.as_bytes()));
+    match result {
+        Ok(()) => {}
+        Err(e) => {
+            return Err(format!("Failed to write the service definition to {:?}: {}", path, e));
+        }
+    }
+
+    #[cfg(unix)]
+    {
+        if let Err(e) = fs::set_permissions(path, fs::Permissions::from_mode(0o644)) {
+            return Err(format!("Failed to set the permissions of {:?}: {}", path, e));
+        }
+    }
+
+    Ok(())
+}
+
+/// Quote the given argument for a command line of a systemd unit, if required.
+fn quote_systemd_argument(argument: &str) -> String {
+    // systemd expands specifiers starting with a percent sign
+    let escaped = argument.replace('%', "%%");
+    if !escaped.is_empty() && !escaped.chars().any(|character| character.is_whitespace() || character == '"' || character == '\'' || character == '\\') {
+        return escaped;
+    }
+
+    format!("\"{}\"", escaped.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Escape the given text for the content of an XML element.
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod install_test {
+
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let spec = ServiceSpec {
+            binary: PathBuf::from("/usr/local/bin/node_rs"),
+            working_dir: PathBuf::from("/etc/node_rs"),
+            data_dir: PathBuf::from("/var/lib/node rs"),
+            user: Some("node_rs".to_string()),
+            start_args: vec!["0.0.0.0:9000".to_string(), "0.0.0.0:3000".to_string(), "--sign".to_string(), "--webhooks".to_string(), "hooks & alerts.json".to_string()],
+        };
+
+        let unit = spec.render(ServiceManager::Systemd);
+        assert!(unit.contains("ExecStart=/usr/local/bin/node_rs start 0.0.0.0:9000 0.0.0.0:3000 --sign --webhooks \"hooks & alerts.json\" --data-dir \"/var/lib/node rs\"\n"));
+        assert!(unit.contains("User=node_rs\n"));
+        assert!(unit.contains("WorkingDirectory=/etc/node_rs\n"));
+
+        let plist = spec.render(ServiceManager::Launchd);
+        assert!(plist.contains("<string>hooks &amp; alerts.json</string>"));
+        assert!(plist.contains("<string>/var/lib/node rs/node_rs.log</string>"));
+
+        assert_eq!("100%%", quote_systemd_argument("100%"));
+        assert_eq!("\"say \\\"yes\\\"\"", quote_systemd_argument("say \"yes\""));
+        assert_eq!(Ok(ServiceManager::Launchd), "launchd".parse::<ServiceManager>());
+        assert!("upstart".parse::<ServiceManager>().is_err());
+    }
+}
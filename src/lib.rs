@@ -8,13 +8,16 @@
 //! Run a node of a permissioned e-voting blockchain
 //!
 //! USAGE:
-//!     node_rs [FLAGS] [SUBCOMMAND]
+//!     node_rs [FLAGS] [OPTIONS] [SUBCOMMAND]
 //!
 //! FLAGS:
 //!     -h, --help         Prints help information
 //!     -V, --version      Prints version information
 //!     -v, --verbosity    Turn up the verbosity of the log output
 //!
+//! OPTIONS:
+//!         --log <FILTERS>    Comma separated levels of single modules, overriding the verbosity, e.g. p2p=trace,protocol=info
+//!
 //! SUBCOMMANDS:
 //!     help     Prints this message or the help of the given subcommand(s)
 //!     start    Start a new node
@@ -55,8 +58,67 @@
 //!    protocol as initially proposed to the Ethereum blockchain as
 //!    Proof-of-Authority [sybil control mechanism](https://twitter.com/el33th4xor/status/1006931658338177024?s=12).
 //!     * `block_period`: This is the period until a new block is generated
-//!     * `signer_limit`: How many epochs a node must wait until its his turn again to mint a new block
-//! * `sealer`: A set of IPv4 addresses of nodes which form the network.
+//!     * `signer_limit`: How many of the sealers following the leader of a block may seal it as co-leaders
+//!     * `hash_algorithm` (optional): The hash function deriving the identifiers of new blocks and transactions,
+//!       one of `sha256` (default), `blake2s` or `sha1`. Identifiers are prefixed with the name of the hash function,
+//!       e.g. `sha256:<hex digest>`, while identifiers without prefix are SHA-1 digests derived by earlier versions
+//!       of this node. New blocks and transactions must be identified by this hash function only.
+//!     * `legacy_sha1_height` (optional): The height below which blocks, and the transactions they include, may still be
+//!       identified by SHA-1, so that chains sealed by earlier versions remain valid. Absent by default, i.e. SHA-1
+//!       identifiers are only accepted if `hash_algorithm` is `sha1`.
+//!     * `vote_epoch_length` (optional): The amount of blocks after which pending votes on sealers are discarded,
+//!       30000 by default, see [Sealer Voting](#sealer-voting).
+//! * `sealer`: A set of IPv4 addresses of nodes which form the network initially.
+//! * `sealer_keys` (optional): The hex encoded Ed25519 public key of each sealer, keyed by its address.
+//!   If present, blocks must be signed by their sealer, see [Block Signatures](#block-signatures).
+//!   Without them, nodes only seal blocks if passed `--allow-unsigned-blocks`.
+//! * `features` (optional): The optional behaviours of the election, any of `last-vote-counts`, `precinct-tallies`,
+//!   `commitment-mode` and `voter-registry`. Nodes not knowing an enabled feature refuse to start. As the features are part of
+//!   the hash of the configuration, all sealers must enable the same ones.
+//! * `transaction_limits` (optional): Upper bounds on the size of transactions, checked before any proof is verified,
+//!   so that crafted votes with huge numbers cannot exhaust the time or memory of a node.
+//!     * `max_transaction_size`: The maximum size in bytes of a transaction encoded as JSON, 131072 by default.
+//!     * `max_proof_component_size`: The maximum size in bytes of the cipher text, the membership proof and the
+//!       cast-as-intended proof of a vote each, encoded with bincode, 16384 by default.
+//!
+//!   Oversized transactions are rejected on submission with a `TransactionReject` naming the exceeded limit,
+//!   and blocks including them are rejected.
+//! * `election_authority` (optional): The public key of the election authority, as printed by `keygen`.
+//!   If present, opening and closing the voting must be signed by it, see [Admin Channel](#admin-channel).
+//!   Required by the feature `voter-registry`, see [Registering Voters](#registering-voters).
+//! * `questions` (optional): Further questions asked in the same election, each with its own `identifier`
+//!   and the file holding the public UCIV of its voters, e.g. `{"identifier":"q2","public_uciv":"public_uciv_q2.json"}`.
+//!   Votes name the question they answer by its `question_id`, while votes without it answer the question
+//!   whose voters are declared in `public_uciv.json`. Each voter may vote once on each question, and the
+//!   tally lists the votes on each further question separately under `questions`.
+//! * `voting_options` (optional): The plaintexts a vote may encrypt, `[1, 0]` (in favour, against) by default.
+//!   The UCIV information of each voter must hold an image per option, in the same order. As votes are summed up,
+//!   elections with more than two choices encode them so that the decrypted sum can be split into the amount of
+//!   each choice, e.g. `[1, 1000, 1000000]` for three choices and less than 1000 voters.
+//! * `threshold_decryption` (optional): Lets the sealers decrypt the tally jointly instead of a single holder
+//!   of the private key, e.g. `{"threshold":2,"public_key_shares":{"127.0.0.1:3000":{...},...}}`. Each sealer
+//!   listed holds a share of the private key, whose public key share is declared here, and passes it to `start`
+//!   with `--key-share key_share.json`. Once the voting is closed, each of them publishes a `DecryptionShare`
+//!   transaction holding its partial decryption of the summed votes along with a proof of its correctness.
+//!   As soon as `threshold` valid shares are on the chain, the tally holds the plaintext sum as `decrypted_sum`,
+//!   for the main question and each further one.
+//! * `key_generation` (optional): Lets the sealers generate the election key jointly at startup instead of
+//!   distributing a pre-generated one, e.g. `{"threshold":2}`. The public key then only provides the group.
+//!   Each sealer deals a random contribution, publishes its commitments in a `DkgCommitment` transaction in a
+//!   block it seals itself, and sends the share of each other sealer to it directly. Once the commitments of
+//!   all sealers are on the chain, the election key is committed in an `ElectionKey` transaction, the voting
+//!   may be opened and the key is reported as `election_key` by `election-status`. Any `threshold` of the sealers
+//!   decrypt the tally as with `threshold_decryption`, which must not be declared along. There is no complaint
+//!   phase: a sealer not receiving a valid share of each other sealer can not take part in the decryption.
+//!   Pass `--data-dir` so that a sealer keeps its contribution across restarts.
+//! * `revote_policy` (optional): Which vote counts if a voter votes several times on the same question, either
+//!   `first-vote-counts` or `last-vote-counts`. Defaults to `last-vote-counts` if the feature of the same name is
+//!   enabled and to `first-vote-counts` otherwise, which must not be declared along with the feature.
+//! * `registrar` (optional): The RSA public key of the registrar blind-signing the eligibility credentials of the voters,
+//!   e.g. `{"modulus": "<hex encoded modulus>", "exponent": 65537}`, with a modulus of at least 2048 bits.
+//!   If present, each vote must carry a credential signed by it, see [Eligibility Credentials](#eligibility-credentials).
+//! * `tls_certificates` (optional): The file of the PEM encoded TLS certificate of each sealer, keyed by its address,
+//!   relative to the directory the node is started in. See [TLS](#tls).
 //!
 //! *Note: In order to let multiple nodes build a network successfully, this
 //! configuration must be equal, as its hash is used in the Genesis block.
@@ -85,6 +147,233 @@
 //! in the same directory as the binary is invoked. As the public key,
 //! this information can be generated using [generator_rs](https://github.com/provotum/generator-rs).
 //!
+//! ### Startup modes
+//!
+//! A node can be started in one of the following modes:
+//! * `--fresh`: Start with a chain containing only the genesis block. A data directory which already holds
+//!   blocks is refused instead of continuing its chain. Without either flag, a node continues with the chain stored
+//!   in its data directory, if any, without synchronizing with the peers.
+//! * `-r`, `--resume`: Fetch the blocks missing in the own chain from all peers defined in `genesis.json`
+//!   and continue on the heaviest chain. Blocks are requested in ranges of heights above the own head,
+//!   or the whole chain is copied from peers not supporting block ranges.
+//!   The peers are ranked by the round trip time of their answer to a head query, and consecutive ranges are
+//!   fetched in parallel from up to four of the fastest ones. A range a peer does not send within 10 seconds
+//!   is requested from the next fastest peer instead, so a stalled peer does not stall the catch-up.
+//!   With `--fast-sync`, a node joining late in a large election first adopts a snapshot of the finalized chain
+//!   and only fetches the blocks above it, see Fast Sync.
+//!
+//! Independently of the mode, passing `--data-dir <DIRECTORY>` persists each block in `<DIRECTORY>/blocks.bin`
+//! as soon as it is added to the chain. On the next start with the same directory, unless started with `--fresh`,
+//! the stored chain is restored before synchronizing with the peers. Each restored block, as well as each block
+//! replayed from the log of a crash, is validated like a block received from a peer, and a stored chain holding an invalid block is refused.
+//! Without this option, the chain is kept in memory only and lost on restart.
+//! Blocks are stored in a compact bincode encoding whose version is declared in the header of the file, and which
+//! changes independently of the messages exchanged with peers. Pass `--storage-encoding json` to store them as one
+//! JSON document per line in `<DIRECTORY>/blocks.jsonl` instead. Blocks stored in the other encoding, e.g. by earlier
+//! versions of this node, are migrated on startup. Likewise, files of an earlier storage version are rewritten
+//! in the current version on startup.
+//! Blocks and the transactions in the mempool are also appended to the write-ahead log `<DIRECTORY>/wal.jsonl`
+//! before they are applied. After a crash, the log is replayed on startup, so that no buffered vote is lost.
+//!
+//! Connections to the peer port are only handled if their IP address is the one of a sealer authorized on the
+//! canonical chain, of a candidate of a pending sealer vote, or of the local host. RPC connections are not restricted by their source.
+//!
+//! Sources submitting 5 transactions with invalid proofs are banned, i.e. their connections are refused, for
+//! 10 minutes, whereas each further ban lasts twice as long, up to a week. With `--data-dir`, bans survive restarts
+//! in `<DIRECTORY>/offenders.json` and each ban is appended to the audit log `<DIRECTORY>/audit.jsonl`
+//! for the operator, which should be covered by a retention policy as it contains IP addresses.
+//!
+//! On `SIGINT` or `SIGTERM`, a node shuts down gracefully: it stops accepting new connections, finishes the
+//! connections being handled as well as pending gossip within the deadline given by `--shutdown-deadline <SECONDS>`
+//! (default 10), stops sealing and its other periodic tasks, waiting for them within the same deadline, hands its buffered
+//! transactions over to the other peers and only then closes the block store. Tasks not finished by the deadline, e.g. relay
+//! connections waiting for messages, are named in a warning and abandoned.
+//!
+//! Messages to other peers are sent over plain TCP, opening a connection per message. The transport is selected
+//! with `--transport <TRANSPORT>`, where `tcp` is the default. Further transports can be added by implementing
+//! `p2p::transport::Transport`.
+//!
+//! When built with the `libp2p` feature (`cargo build --features libp2p`), sealers may exchange messages over libp2p
+//! instead with `--transport libp2p`. Blocks and transactions are then published with gossipsub on a topic per genesis
+//! configuration, while all other messages, e.g. the ones synchronizing the chain, are exchanged with request-response.
+//! Each sealer declares its libp2p address, ending in its peer id, in the genesis configuration:
+//!
+//! ```json
+//! "libp2p_addresses": {
+//!   "127.0.0.1:9000": "/ip4/127.0.0.1/tcp/9100/p2p/12D3KooW..."
+//! }
+//! ```
+//!
+//! The keypair identifying a node is read from `--libp2p-key <FILE>`, which is generated and its peer id logged if the
+//! file does not exist. A node refuses to start if its peer id differs from the declared one, and drops messages of peers
+//! not declared. The TCP listener keeps accepting connections, e.g. of clients and of nodes not running libp2p.
+//!
+//! On small machines, the memory of a node can be capped with `--chain-cache-cap`, `--mempool-cap` and
+//! `--in-flight-cap`, each in megabytes. Copies of the chain served to synchronizing peers are then only cached
+//! while they fit, transactions are rejected while the buffer of pending transactions is full, and reading
+//! messages from peers and clients is delayed while too many bytes are being received, slowing down their senders.
+//!
+//! To keep a single host from flooding a node, the connections and messages of each IP address can be limited with
+//! `--max-peer-connections` and `--peer-message-rate` for the peer listener, and `--max-rpc-connections` and
+//! `--rpc-message-rate` for the RPC listener. The former cap the connections open at once, the latter the messages
+//! per second, which may be sent in a burst after a pause. Connections exceeding a limit are closed without a response
+//! and counted in the metrics. Polling stations behind a shared address should be given a limit fitting all of them.
+//!
+//! ### Epochs
+//! Sealing follows a schedule of epochs: in each epoch, every sealer leads the sealing of one block,
+//! in the order of the sealers in `genesis.json`, followed by the sealers voted in. With three sealers, the first epoch holds the blocks at heights
+//! 1 to 3, the second one those at heights 4 to 6, and so on, whereas the genesis block does not belong to any epoch.
+//! If a leader misses its slot, one of its co-leaders seals the block instead. Before sealing out of turn, a co-leader
+//! asks the leader for the head of its chain with a `HeadQuery`, waiting at most 300 milliseconds, and skips
+//! the height if the leader already sealed a block at it. The statistics of each epoch,
+//! i.e. its height range, the amount of blocks and transactions, and the blocks sealed and slots missed by each sealer,
+//! are returned for an `EpochStatisticsRequest` RPC message or by the `epoch-statistics` admin command.
+//! The election status includes the `current_epoch`, which the next block belongs to.
+//!
+//! As in the Clique protocol, a block sealed by the leader of its height weighs 2, one sealed out of turn by a co-leader 1.
+//! The canonical chain is the branch of the greatest total difficulty, i.e. the sum of the weights of its blocks,
+//! so that a block of the leader wins over competing blocks of its co-leaders. Among equally heavy branches, the shorter one wins.
+//!
+//! A sealed block is sent to up to 16 peers in parallel, waiting at most 10 seconds for each of them to accept it,
+//! so that an unreachable peer does not delay the propagation of the block to the others. Blocks sealed while a peer
+//! is still busy with the previous one are not queued up for it; only the latest is sent once the peer is done.
+//!
+//! ### Block Validation
+//! Before a block received from another node is added to the chain, it must satisfy all of the following rules,
+//! otherwise it is answered with a `BlockReject` stating the violated rule: its identifier matches its content,
+//! it is sealed (and if keys are declared, signed) by a sealer authorized by its parent, all sealer votes it includes
+//! are cast by its sealer, its parent is already part of the chain,
+//! its timestamp is after the one of its parent and at most 15 seconds ahead of the own clock, its sealer is the leader
+//! or one of the `signer_limit` co-leaders of its height, and all of its transactions respect the `transaction_limits`
+//! and are valid.
+//!
+//! ### Clock Check
+//! As blocks with timestamps too far ahead are rejected, a sealer with a wrong clock silently loses its slots.
+//! Hence, after the handshake and every 10 minutes, a node asks each peer for its time and takes the median deviation,
+//! optionally including the NTP server passed with `--ntp-server pool.ntp.org`. A warning is logged if the deviation
+//! exceeds `--clock-warn-threshold` (in milliseconds, defaults to 1000). If it exceeds the 15 seconds tolerated for
+//! timestamps, the node refuses to seal blocks until its clock is fixed. The deviation is reported as `clock_offset`
+//! in the node status and checked by `probe`.
+//!
+//! ### Version Policy
+//! The `version` declared in `genesis.json` can be checked against the version of `node_rs`
+//! by passing `--version-policy` to `start`: `exact` requires both to be equal, `compatible` requires
+//! them to be compatible according to semantic versioning and `ignore` (default) skips the check.
+//! Nodes refuse to start if the declared version is not accepted. The result of the check
+//! is included in the status reported to peers.
+//!
+//! ### Dashboard
+//!
+//! When built with the `ui` feature (`cargo build --features ui`), a node can serve
+//! a dashboard showing the phase of the election, the turnout, the height of the chain
+//! and the amount of blocks sealed by each sealer. Pass `--dashboard <ADDRESS>` to `start`
+//! and open the given address in a browser. The underlying data is available as JSON at `/api/status`,
+//! the statistics of each epoch at `/api/epochs`.
+//!
+//! Responses carry an `ETag`, so that clients revalidating with `If-None-Match` get a `304 Not Modified`
+//! without a body as long as the data did not change. Responses of at least 1 KiB are compressed with brotli
+//! or gzip, whichever is accepted by the client, in the order given by `--dashboard-compression` (default `br,gzip`).
+//! Pass `--dashboard-compression none` to disable compression, e.g. if a reverse proxy compresses instead.
+//! Up to 4 connections are served at the same time. Clients not sending their request or not reading
+//! the response within 5 seconds are disconnected.
+//!
+//! ### External Signer
+//! To keep the key of a sealer off the host exposed to the network, blocks can be signed by an external
+//! process, e.g. one with access to a hardware security module. Pass `--external-signer <SOCKET>` along with `-s`
+//! to `start`. For each block, the node connects to the Unix domain socket and sends a single line
+//! `{"block_identifier": "<identifier>"}`, to which the signer responds with a single line
+//! `{"signature": "<signature>", "error": null}`. If the signer is unreachable or refuses to sign,
+//! the node skips its slot and includes the transactions in its next block instead.
+//!
+//! ### Block Signatures
+//! To prevent peers from injecting blocks on behalf of a sealer, declare the public key of each sealer
+//! in the `sealer_keys` of `genesis.json`. Pass `--node-key <FILE>` along with `-s` to `start`, so that the node
+//! signs the identifier of each block it seals with the Ed25519 keypair in the given file. If the file does not exist,
+//! a new keypair is generated and its public key is logged, to be declared in the genesis configuration.
+//! Alternatively, an external signer may produce the signatures. Once keys are declared, blocks which are unsigned,
+//! sealed by a sealer without key or whose signature does not match the key of their sealer are rejected,
+//! as are copies of chains containing such blocks. As the signature covers the identifier of the block only,
+//! blocks whose identifier does not match their content are rejected as well.
+//! A node refuses to seal blocks if the genesis configuration declares no `sealer_keys`, as any peer could then seal
+//! blocks on behalf of a sealer, unless `--allow-unsigned-blocks` is passed along with `-s`, e.g. on a test network.
+//! It also refuses to seal blocks if keys are declared, but neither a node key nor an external signer is given.
+//!
+//! To keep the secret key encrypted at rest, generate the node identity with `node_rs keygen keystore.json --address 127.0.0.1:9000`
+//! instead. It prompts for a passphrase, writes the keypair encrypted with a key derived from it by scrypt to the given
+//! keystore, and prints the entry to add to the `sealer_keys`. Then pass `--keystore keystore.json` to `start` in place of `--node-key`.
+//! Both commands read the passphrase from `NODE_RS_KEYSTORE_PASSPHRASE` if set, e.g. when run by a service manager.
+//!
+//! Keys and signatures name their algorithm, e.g. `ecdsa-p256:02ab...`, except Ed25519 ones, which are plain hex.
+//! A sealer may declare keys of several algorithms separated by commas, in which case its blocks must carry a valid
+//! signature for each key of an algorithm the verifying node supports, e.g. as returned by an external signer. This way, a further algorithm, e.g. a post-quantum
+//! one, is introduced alongside the existing keys without changing the format of the chain. Besides Ed25519, builds with
+//! the `ecdsa-p256` feature support ECDSA on the P-256 curve, whose keypairs are generated with `keygen --algorithm ecdsa-p256`.
+//!
+//! ### Admin Channel
+//! Deployments which cannot expose the RPC port may tunnel admin commands over the port used for
+//! communication between peers. Create an admin identity `admin.json` such as
+//! ```json
+//! {
+//!   "name": "admin",
+//!   "secret": "<at least 16 random bytes, hex encoded, e.g. from openssl rand -hex 32>",
+//!   "capabilities": ["ManageElection", "ReadResults", "ReadStatus"]
+//! }
+//! ```
+//! and pass `--admin-identity admin.json` to `start`. Using the same file, run commands such as
+//! `node_rs admin 127.0.0.1:9000 open-vote --identity admin.json`. Requests and responses are
+//! authenticated with a HMAC of the shared secret and may not be replayed, and each command requires
+//! the respective capability. They are not encrypted, hence the secret must never be shared with other peers
+//! and the tally should only be requested over a trusted network.
+//!
+//! If the genesis configuration declares an `election_authority`, unsigned `OpenVote` and `CloseVote` messages
+//! are rejected, as are blocks including transactions opening or closing the voting without its signature.
+//! Generate the key of the authority with `node_rs keygen authority.json`, declare the printed public key and pass
+//! `--authority-keystore authority.json` to `admin open-vote` or `admin close-vote`. The command then signs
+//! the election, i.e. the hash of the genesis configuration requested from the node, along with the current time,
+//! and sends a `SignedOpenVote` or `SignedCloseVote` instead. This requires the `ReadStatus` capability as well.
+//!
+//! ### RPC Tokens
+//! By default, any client reaching the RPC port may call all RPC methods. To restrict them, create a file `rpc_tokens.json` such as
+//! ```json
+//! [
+//!   {"name": "polling-station-1", "token": "<at least 16 random bytes, hex encoded>", "scopes": ["Submit"]},
+//!   {"name": "auditor", "token": "<at least 16 random bytes, hex encoded>", "scopes": ["Read"]}
+//! ]
+//! ```
+//! and pass `--rpc-tokens rpc_tokens.json` to `start`. Each RPC request must then present a token granting the scope
+//! of its method: `Submit` for transactions and join requests, `Read` for the chain, transactions and status information,
+//! `Tally` for the final tally, alone or along with the status of the election, and `Admin` for opening and closing the voting, which also grants all other scopes.
+//! Clients wrap their request in `Message::RpcAuthorized(token, request)`, the commands of `node_rs` accept `--rpc-token <TOKEN>`.
+//! Tokens are sent in plain text, hence the RPC port should only be reachable over a trusted network, unless it is encrypted.
+//!
+//! To encrypt the RPC port, pass `--rpc-tls-certificate <FILE>` and `--rpc-tls-key <FILE>` to `start`, PEM encoded as for
+//! [TLS](#tls) between nodes. The node then refuses RPC connections without TLS. The commands of `node_rs` connect with TLS
+//! when passed `--rpc-tls-ca <FILE>` before the command, trusting certificates issued by the given authority for the IP address
+//! of the node, or `--rpc-tls` to trust the authorities of the system. Rust clients call `node_rs::p2p::client::use_rpc_tls` once.
+//! Client certificates are not verified, clients authenticate with their token.
+//!
+//! ### NAT Traversal
+//! Sealers behind a home router may pass `--upnp` to `start`, which maps the port of the advertised address
+//! on the router to the listen address via UPnP and renews the mapping periodically. If the router does not
+//! support UPnP, pass `--relay` instead. The node then keeps a connection open to each other peer, over which
+//! these peers relay blocks and other messages to it. Peers only accept such a connection from the IP address
+//! the sealer is listed under in the genesis configuration, i.e. the public address of its router.
+//!
+//! ### TLS
+//! Pass `--tls-certificate <FILE>` and `--tls-key <FILE>` to `start`, both PEM encoded with the key in PKCS#8,
+//! so that the node connects to the other peers over TLS and presents the certificate to peers connecting to it.
+//! A peer is trusted if it presents the certificate declared for it in the `tls_certificates` of `genesis.json`,
+//! or else a certificate for its IP address issued by the authority passed with `--tls-ca <FILE>`, by default
+//! one trusted by the system. This holds for the peers a node connects to as well as for the ones connecting to it,
+//! which must present their certificate. Connections without TLS are refused, unless `--tls-allow-plain` is passed,
+//! e.g. while the sealers switch to TLS one at a time. Such connections are neither encrypted nor authenticated.
+//! Commands connecting to the port for peers, e.g. `probe` and `admin`, use TLS if passed `--peer-tls-certificate <FILE>`
+//! and `--peer-tls-key <FILE>` before the command, e.g. the ones of the node, and `--peer-tls-ca <FILE>` to trust
+//! certificates issued by the given authority instead of the ones of the system.
+//! Connections are established with the TLS library and trust store of the platform, as for the beacon.
+//! As these libraries cannot request the certificate of peers connecting to the node, those are accepted with OpenSSL.
+//! The RPC port is encrypted separately, see [RPC Tokens](#rpc-tokens).
+//!
 //! ## Running a permissioned Voting network
 //!
 //! Now, once you have met the requirements stated above,
@@ -96,19 +385,22 @@
 //!
 //! **This example requires to have `genesis.json` setup as in the above example.**
 //!
-//! 1. Start your first node by running `node_rs -v start -s 127.0.0.1:9000 127.0.0.1:3000`.
+//! 1. Start your first node by running `node_rs -v start -s --allow-unsigned-blocks 127.0.0.1:9000 127.0.0.1:3000`.
 //!    The flag `-v` will let you output debug information, increase the
 //!    verbosity using `-vv` to also show more detailed statements.
-//!    `-s` tells the node to start minting blocks.
+//!    To turn up single modules only, pass their levels, e.g. `node_rs --log p2p=trace,protocol=warn start ...`.
+//!    The active filters are reported in the status of the node and by `node_rs probe`.
+//!    `-s` tells the node to start minting blocks. As the example `genesis.json` declares no `sealer_keys`,
+//!    `--allow-unsigned-blocks` acknowledges that its blocks are not signed, see [Block Signatures](#block-signatures).
 //!    Provide as first argument the first IP address of the `sealer` key
 //!    of `genesis.json`. Specify as second argument any IPv4 address
 //!    on which the node will listen for RPC connections of a client.
 //! 2. **Important**: Let the node mint the first block until you start
 //!    a further one!
 //!
-//!    Start your second node, this time by adding the flags `-p` to the
-//!    command, yielding `node_rs -v start -s -p 127.0.0.1:9001 127.0.0.1:3001`.
-//!    `-p` will tell the node to first obtain a copy of the already running
+//!    Start your second node, this time by adding the flag `-r` (`--resume`) to the
+//!    command, yielding `node_rs -v start -s --allow-unsigned-blocks -r 127.0.0.1:9001 127.0.0.1:3001`.
+//!    `-r` will tell the node to first obtain a copy of the already running
 //!    nodes. If their canonical chain are longer, they will replace
 //!    the chain of the node you've just started.
 //!    **Note**: You will likely some warning output, telling you that
@@ -119,15 +411,395 @@
 //!    a further one!
 //!
 //!    Eventually, after the first two nodes have exchanged their initial blocks,
-//!    you are ready to start the third one. For that, run `node_rs -v start -s -p 127.0.0.1:9002 127.0.0.1:3002`.
+//!    you are ready to start the third one. For that, run `node_rs -v start -s --allow-unsigned-blocks -r 127.0.0.1:9002 127.0.0.1:3002`.
 //!
 //!
 //! That's it, now you should see new blocks being minted every `block_period` seconds.
 //!
+//! ### Diagnosing Peers
+//! On start, a node sends a handshake with the hash of its genesis configuration to each other peer.
+//! A peer running a different genesis configuration answers with `GenesisMismatch` instead of accepting it,
+//! both nodes log an error naming the hashes, and the starting node no longer exchanges messages with that peer.
+//! Likewise, a peer enabling different `features` answers with `FeatureMismatch`, and both nodes log which features differ.
+//! The handshake also lists the codecs the node decodes. The peer answers with `HandshakeAcceptCodec` naming the most compact
+//! codec both decode, e.g. `cbor`, in which both nodes encode their requests to each other from then on. Responses are encoded
+//! in the codec of the request. Nodes of earlier versions only decode JSON, which is therefore still used for them, for RPC clients and relays.
+//! Unless started with `--compression-threshold none`, a node also announces that it decompresses `gzip`. Messages between two such nodes
+//! are preceded by a short envelope indicating whether the message is compressed, which it is from 64 KiB on, or the size in KiB given
+//! with `--compression-threshold`, e.g. chains and blocks during synchronization. Responses are only compressed if the envelope of the request accepts it.
+//! If two nodes refuse to synchronize, run `node_rs probe 127.0.0.1:9001` in the directory
+//! of one of them. It connects to the given peer and checks whether it is reachable,
+//! uses the same codec, node version and genesis configuration.
+//!
+//! Each node answers a `RequestTally` from another peer with its tally as `RequestTallyPayload`,
+//! without requiring a token as the tally is derived from the chain available to all peers.
+//! To cross-check that all sealers compute the same sum, run `node_rs compare-tally` in the directory of a node,
+//! or pass the listen addresses of the nodes to compare, e.g. `node_rs compare-tally 127.0.0.1:9000 127.0.0.1:9001`.
+//! It requests the tally from each of them, names the nodes deviating from the majority or not responding, and exits
+//! with a non-zero code unless all tallies are equal. Nodes may briefly differ while blocks are sealed,
+//! hence the comparison is only conclusive once the voting is closed. A node receiving a `RequestTallyPayload`
+//! compares it with its own tally and logs a warning if they differ.
+//!
+//! Each node tracks its connectivity to the peers it sends requests to. A `PeerStatusRequest` RPC message, or
+//! `node_rs admin 127.0.0.1:9000 peers`, returns for each other current sealer when it last answered a request,
+//! the amount of requests failed since along with the last error, and the time connecting to it took, i.e. a single round trip.
+//! Sealers this node did not contact yet are listed as never seen. Peers do not answer the request, as it reveals which sealers are unreachable.
+//! To keep the connectivity current while no blocks are exchanged, each node sends a `Ping` heartbeat to the other sealers twice per block period.
+//! A warning is logged once a sealer did not answer any request for more than a block period, and a notice once it answers again.
+//! Until then, the sealer is reported as `silent` in the peer status.
+//!
+//! On start, each node prints a summary of its configuration as a single line of JSON to stdout, e.g.
+//! ```json
+//! {"node_version":"0.2.1","genesis_version":"0.1.0","genesis_hash":"628b10e0...","public_key_fingerprint":"0d0e4793...","public_uciv_hash":"b853bc10...","sealer_index":1,"sealer_count":3,"data_dir":null,"features":["sign"],"listen_address":"127.0.0.1:9001","advertised_address":"127.0.0.1:9001","rpc_listen_address":"127.0.0.1:3001","dashboard_address":null}
+//! ```
+//! The `genesis_hash`, `public_key_fingerprint` and `public_uciv_hash` must be equal on all sealers.
+//!
+//! ### Metrics
+//! If a node falls behind, its thread pools may be saturated. A `MetricsRequest` RPC message, or
+//! `node_rs admin 127.0.0.1:9000 metrics`, returns for each pool its busy workers, the amount of waiting tasks
+//! and the age of the oldest one, along with histograms of the time tasks waited and ran per task name, e.g. `sign`
+//! or `connection`. A warning is logged once more than 64 tasks wait for the workers of a pool for 10 seconds.
+//! A task panicking, e.g. on a broken connection, is logged as an error and counted as `failed` for its name,
+//! while its worker keeps executing further tasks. Only if it poisoned the lock of the protocol, i.e. panicked while holding it,
+//! the node aborts, as the state of the protocol may be inconsistent. Incoming connections stalling for 10 seconds while sending their request,
+//! including the TLS handshake, or receiving the response are dropped, so that they release their `connection` worker.
+//! The metrics further include the blocks pruned from the chain, see Abandoned Branches, and the connections
+//! dropped by the rate limits of each listener.
+//!
+//! ### Selftest
+//! Before election day, validate a build and its host by running `node_rs selftest --votes 10`
+//! in a directory holding the genesis configuration along with test key material, i.e.
+//! `private_key.json` and `private_uciv.json` belonging to `public_key.json` and `public_uciv.json`.
+//! It starts an ephemeral network of three sealers within the process, opens the voting, casts
+//! the given amount of votes with random choices, closes the voting and decrypts the tally of each node.
+//! The selftest exits with a non-zero code, if any step times out or a tally does not match the votes cast.
+//!
+//! ### Installing as a Service
+//! In production, run the node as a service of the init system instead of a shell. In the directory holding the
+//! genesis configuration along with `public_key.json` and `public_uciv.json`, run e.g.
+//! `sudo node_rs install --systemd --data-dir /var/lib/node_rs --user node_rs -- 0.0.0.0:9000 0.0.0.0:3000 --sign`
+//! to write a systemd unit to `/etc/systemd/system/node_rs.service`, or pass `--launchd` to write a property list to
+//! `/Library/LaunchDaemons/org.provotum.node_rs.plist`. The arguments after `--` are passed to `node_rs start`.
+//! Before writing the definition, `install` checks them along with the genesis configuration, and then
+//! creates the data directory, accessible only to the given user. An existing definition is kept unless `--force`
+//! is passed, and `--output` writes it to another file. The service restarts the node if it fails and stops it with
+//! `SIGTERM`. Since it cannot prompt for the passphrase of a keystore, provide it in `NODE_RS_KEYSTORE_PASSPHRASE`,
+//! e.g. with an `Environment=` line of a systemd drop-in.
+//!
+//! ### Joining as Sealer
+//! A node which wants to become a sealer first copies the genesis configuration of the network,
+//! then runs `node_rs join-request 127.0.0.1:3000 --address 127.0.0.1:9004` against the RPC address
+//! of any existing node, passing the address under which it would be listed among the sealers.
+//! This generates a candidate identity at `candidate.json`, unless one already exists, and submits its
+//! join request, which is relayed to all other nodes. Authorities list the pending requests with
+//! `node_rs join-request 127.0.0.1:3000 --list`. The candidate seals blocks once the sealers voted it in.
+//! Nodes keep at most 100 pending requests, of which at most 3 may list candidates under the same IP address.
+//! A request is dropped three days after it was issued, so a candidate still waiting for the vote submits it again.
+//!
+//! ### Sealer Voting
+//! As in the Clique protocol, the sealers add and remove sealers at runtime by voting. Run
+//! `node_rs vote-sealer 127.0.0.1:3000 127.0.0.1:9004 --public-key <KEY>` against the RPC address of a sealer
+//! to let it vote on adding the candidate, or pass `--remove` instead of the key to vote on removing a sealer.
+//! The public key of the candidate is only required if `sealer_keys` are declared. Over RPC, this requires a token
+//! of scope Admin, over the admin channel the capability `ManageSealers`. The sealer keeps the resulting
+//! `SealerVote` transaction until it seals its next block, as a vote only counts in a block sealed by the voter.
+//!
+//! Once more than half of the sealers voted to add the same candidate, with the same key, or to remove the same sealer,
+//! the change takes effect from the following block on: an added sealer leads the sealing after the existing ones,
+//! the votes of a removed sealer are discarded. Votes not reaching a majority are discarded at every height
+//! divisible by `vote_epoch_length`. Since the sealers at each height follow from the chain, nodes on different
+//! branches may temporarily disagree on them, and a restarted node derives them again from its blocks.
+//! The sealers authorized on the canonical chain are reported as `authorized_sealers` in the node status.
+//!
+//! ### Finality
+//! A block of the canonical chain is final once more than half of the sealers authorized to build on it
+//! sealed blocks on top of it. The head never moves to a branch reverting a final block, and chains received
+//! from peers which do not contain it are not adopted, so that final blocks are irreversible. The height of the
+//! finalized head is reported as `finalized_height` in the election status, and `TransactionIncluded` states
+//! whether the block including the transaction is `finalized`.
+//!
+//! Applications embedding a node may register a listener with `Node::on_head_changed`, which is called with the
+//! previous and the new head whenever the head of the canonical chain changes. Reorganizations, i.e. changes after
+//! which the previous head is no longer part of the canonical chain, are additionally logged.
+//!
+//! Before sealing a block, a sealer asks each other sealer for its head. If more than half of all sealers announce
+//! heads on other branches than its own head, e.g. after a network partition healed, it pauses sealing instead of
+//! deepening the fork on its minority branch, and resynchronizes with the conflicting sealers until its head agrees
+//! with them again. Sealers not answering in time do not count as conflicting. Pausing and resuming are logged and,
+//! with `--data-dir`, appended to the audit log `<DIRECTORY>/audit.jsonl`.
+
+//! ### Abandoned Branches
+//! Once the tip of a fork falls 6 blocks behind the head of the canonical chain, the node abandons
+//! the branch, puts its transactions which are not part of the canonical chain back into the buffer
+//! and announces the tip to its peers. Peers only take note of an announced branch if their own
+//! fork choice abandons it as well, and blocks of abandoned branches are not deleted, so that
+//! announcements cannot be used to censor blocks. The tips are listed in the election status as
+//! `abandoned_branches`, allowing explorers to flag orphaned blocks.
+//!
+//! Only branches which forked off below the finalized height are deleted, as they can never become canonical.
+//! Every minute, the node prunes them from its chain and its block store to reclaim memory. The amount of passes,
+//! blocks and bytes pruned since the start is reported as `pruning` in the metrics.
+//!
+//! History can be paged through by height, without knowing the identifiers of the blocks: a
+//! `BlockByNumberRequest` RPC message returns the block of the canonical chain at the given height,
+//! a `BlocksAtHeightRequest` the blocks at that height on all branches, including forks.
+//!
+//! ### Fast Sync
+//! A `SnapshotRequest` RPC message returns a snapshot of the canonical chain up to the finalized block at the
+//! given height, or up to the finalized head if none is given. It contains the block along with its height and
+//! total difficulty, the authorized sealers, the phase of the election and the running tally, i.e. the homomorphic
+//! sum of the counted votes and the voters having voted, encoded as bitmap. All nodes derive the same snapshot for
+//! the same block. Started with `--resume --fast-sync`, a node asks each peer for the snapshot at its finalized head,
+//! requests the snapshots at the highest height finalized by more than half of the peers and adopts the snapshot
+//! if more than half of the peers agree on it. Its chain then starts with the block of the snapshot, and only the
+//! blocks above are fetched. If the peers do not agree, all blocks are synchronized as usual.
+//!
+//! A node started from a snapshot knows no blocks below it: it serves neither these blocks nor inclusion proofs
+//! for them, statistics only cover the blocks above, and a voter voting both below and above the snapshot is
+//! counted twice, as the replaced vote is only contained in the sum of the snapshot. The snapshot is persisted
+//! in place of the genesis configuration hash, preceding the stored blocks.
+//!
 //!
 //! ## Submitting Data to the Voting blockchain
 //! In order to submit votes to the chain,
 //! please refer to the readme of [client_rs](https://github.com/provotum/client-rs).
+//!
+//! Rust clients may depend on `node_rs` with the `client` feature instead, so that votes are constructed
+//! exactly as the nodes verify them: `node_rs::client::build_vote(&public_key, &uciv, voter_idx, choice)`
+//! encrypts the chosen voting option (0 in favour, 1 against) and attaches its membership and cast-as-intended
+//! proofs, given the pre-images and images of the voter. The resulting transaction is submitted with
+//! `node_rs::client::rpc_request` in a `TransactionPayload`. Votes on a further question of the election are
+//! built with `node_rs::client::build_question_vote`, naming the identifier of the question and the voting
+//! options declared in the genesis configuration.
+//!
+//! Votes may carry up to 8 entries of non-secret `metadata`, e.g. the ballot style or the version
+//! of the client, to ease troubleshooting. Keys consist of at most 32 alphanumeric characters,
+//! `_`, `-` or `.`, values of at most 128 bytes. The metadata is not part of the identifier of the
+//! vote, but bound to it by a digest and stored on-chain along with it. Transactions of the canonical
+//! chain can be filtered by their type and metadata entries with a `QueryTransactions` RPC message.
+//!
+//! Submissions are idempotent, so clients may safely retry them after a timeout: a transaction submitted again
+//! is accepted again while it waits to be included in a block, and answered with `TransactionIncluded`, naming
+//! the block including it and its confirmations, once it is part of the canonical chain.
+//! Under the `first-vote-counts` revote policy, each voter votes once: a vote whose voter already has a vote on the
+//! canonical chain or waiting in the mempool is answered with a `TransactionReject` stating that the voter has already
+//! voted, before its proofs are verified. Should several votes of a voter still end up on the chain, e.g. on forks
+//! merging, the oldest one counts. Under the `last-vote-counts` policy, voters may vote again and only their newest
+//! vote counts, where a vote later in the same block is newer.
+//! Votes are only accepted while the voting is open on the canonical chain. The voting is opened once and closed
+//! once, hence an `OpenVote` or `CloseVote` not matching the phase of the election, e.g. closing a voting which is
+//! not opened yet, is answered with a `TransactionReject` stating the phase instead of `OpenVoteAccept` or `CloseVoteAccept`.
+//! The transaction opening or closing the voting carries the hash of the genesis configuration and the time it was
+//! created at, from which its identifier is derived, and is forwarded as such to the other nodes. While one is pending,
+//! another `OpenVote` or `CloseVote` respectively is rejected.
+//!
+//! Once the voting is closed, the leader of the next block commits the outcome of each question in a `FinalTally`
+//! transaction: the amount and the homomorphic sum of the counted votes and, if the sealers decrypt the tally jointly,
+//! the decrypted sum along with the decryption shares proving it, hence it is only committed once a threshold of valid
+//! shares is on the chain. Every node calculates the final tallies from its own chain and rejects blocks including
+//! tallies which do not match, so that the outcome itself is part of the auditable chain rather than only an RPC response.
+//! Only the first final tally of each question is committed.
+//!
+//! Voters can verify that their encrypted ballot is committed on the chain without downloading it by sending
+//! an `InclusionProofRequest` with the identifier of their vote. The response holds the inclusion of the vote,
+//! the header of the block including it and the Merkle path from the vote to the `transactions_root` of the header.
+//! The vote is committed, if hashing the header yields the identifier of the block and the path leads from the vote
+//! to the root, as checked by `InclusionProof::verify`. Blocks sealed by earlier versions of this node do not commit
+//! to a root of their transactions, so no proof is returned for votes included in them.
+//!
+//! If a reorganization reverts the block including a vote, the node adds the vote to its buffer again, so that
+//! it is included on the new branch. A `ReceiptRequest` with the identifier of a vote returns its state: `included`
+//! along with its inclusion, `pending` while waiting for its first inclusion, `pending-reinsertion` while waiting to be
+//! included again, `dropped` along with the reason if it cannot be included again, e.g. as the voting is closed on the
+//! new branch, or `unknown`. Applications embedding a node are notified about reverted and re-included votes by
+//! registering a listener with `Node::on_receipt_changed`.
+//!
+//! ### Eligibility Credentials
+//! If the genesis configuration declares a `registrar`, voters prove their eligibility with a credential blind-signed
+//! by the registrar in addition to their cast-as-intended proof. The voter requests a credential for its voter index on a
+//! question with `RegistrarKey::blind`, which chooses a random nonce and blinds the digest of the question, the voter index
+//! and the nonce with a random factor. The registrar authenticates the voter out of band and signs the blinded message with
+//! `RegistrarPrivateKey::sign_blinded`, without learning the voter index it signs. `RegistrarKey::unblind` removes
+//! the blinding factor, yielding the credential, which is attached to the vote with `Transaction::with_credential`
+//! before any metadata. Thus, the registrar knows who obtained a credential, but cannot link any vote or voter index to them.
+//!
+//! Nodes verify the credential against the registrar key when verifying the proofs of the vote, on submission and
+//! in blocks. Votes lacking a valid credential, or carrying one although no registrar is declared, are invalid.
+//! As a credential is bound to a single voter index, each voter still votes once on each question as described above.
+//!
+//! ### Inspecting Pending Transactions
+//! Every node keeps the transactions it receives in its mempool until a block including them is added to its chain,
+//! regardless of whether it currently leads the sealing. Whichever sealer leads next assembles its block from its
+//! mempool, and the transactions of a block which is not sealed remain pending, so that no vote is discarded.
+//! A transaction submitted over RPC is broadcast once by the receiving node. Additionally, every node announces
+//! the identifiers of its pending transactions to the sealers every 10 seconds with a `MempoolAnnouncement`.
+//! Each sealer answers with the identifiers it neither has pending nor included, whose transactions are then
+//! sent to it in `PendingTransactions` messages of at most 1 MiB. Thus, transactions reach the leader even if
+//! the broadcast was lost or the leader restarted, and a restarted node passes on the transactions replayed from
+//! its write-ahead log. Sealer votes are not gossiped, as they only count in a block sealed by the voter.
+//!
+//! Operators can list the transactions a node received but did not include in a block yet with
+//! `node_rs mempool 127.0.0.1:3000`, which sends a `MempoolRequest` RPC message. Each transaction is summarized
+//! by its identifier, type, time of arrival and size, while its payload, i.e. the encrypted vote and its proofs, is redacted.
+//! Pass `--payloads` to print the whole transactions instead, which requires a token of scope `Admin` if the node
+//! requires tokens. Over the admin channel, summaries require `ReadStatus`, payloads `ManageElection`
+//! (`node_rs admin 127.0.0.1:9000 mempool` requests the summaries). Peers only ever receive summaries.
+//!
+//! ### Importing Offline Ballots
+//! Precincts collecting encrypted ballots offline hand them over as a batch file produced by an offline client,
+//! holding the `origin` of the ballots, the time it was closed at, the vote transactions and a `signature`.
+//! The signature is the hex encoded HMAC-SHA1 over the JSON of `[origin, created_at, transactions]`,
+//! keyed with a secret shared with the precinct in a batch key file such as
+//! `{"name": "precinct-1", "secret": "<at least 16 hex encoded bytes>"}`. Import a batch by running
+//! `node_rs import-ballots --batch-key precinct-1.json -o report.json batch.json 127.0.0.1:3000`
+//! against the RPC address of any node. Batches with an invalid signature, other transactions than votes
+//! or duplicate votes are refused as a whole. Otherwise, each ballot is submitted like a vote cast online,
+//! and the import report lists whether it was `Accepted`, `AlreadyIncluded`, `Rejected` or `Failed`.
+//! As submissions are idempotent, a batch may be imported again to retry failed ballots. Note that ballots
+//! with invalid proofs count towards a ban of the importing host.
+//!
+//! ### Registering Voters
+//! With the feature `voter-registry` enabled, the election authority registers voters on the chain in addition to those
+//! declared in `public_uciv.json` and the files of the questions, e.g. late registrations, and revokes voters no longer eligible,
+//! so that every change of the electoral roll is auditable. Register a voter by running
+//! `node_rs register-voter --authority-keystore authority.json --images images.json --credential <credential> 127.0.0.1:3000 42`
+//! in the directory of the genesis configuration, where `images.json` holds the image set of the voter with index 42 and the
+//! credential identifies the voter in the electoral roll, e.g. the digest of its entry. Pass `--question` to register the
+//! voter for a further question, or `--revoke` instead of `--images` to revoke the voter.
+//!
+//! The transaction is signed by the election authority and carries the hash of the genesis configuration, the index,
+//! image set and credential of the voter and the time it was created at, from which its identifier is derived.
+//! Registering a voter who is already eligible, revoking one who is not, a second registration of the same voter
+//! while one is pending and any registration once the voting is closed are answered with a `TransactionReject`.
+//! Registrations take effect once included in a block: votes of a registered voter are accepted from then on,
+//! a revocation is rejected while a vote of the voter is pending, and votes cast before a revocation remain counted.
+//! Blocks including registrations which are not signed by the election authority or not permitted on their branch,
+//! or votes of voters not eligible on their branch, are rejected.
+//!
+//! ### Following the Turnout
+//! Dashboards showing the participation in real time subscribe to the turnout with a `TurnoutSubscribe` RPC message,
+//! which requires a token of scope `Read` if the node requires tokens. Instead of closing the connection after responding,
+//! the node sends the current turnout and then pushes another one whenever the head of its canonical chain changes,
+//! each as a `TurnoutUpdate` message on its own line. A turnout holds the height and identifier of the head,
+//! the phase of the election and the amount of votes counted on each question, but neither their sum nor the decrypted result.
+//! Subscribers not reading their updates within 5 seconds are dropped, as are further subscribers once 256 are connected.
+//! Run `node_rs turnout 127.0.0.1:3000` to print each update as a line of JSON, e.g.
+//! ```json
+//! {"height":42,"head_identifier":"8e2c6a1f...","phase":"Opened","total_votes":17}
+//! ```
+//!
+//! ## Exporting the Result
+//! Once the voting is closed, the result can be exported to election management systems
+//! in the Election Markup Language (EML) by running
+//! `node_rs export-results --private-key private_key.json -o result.xml 127.0.0.1:3000`
+//! against the RPC address of any node. Use `--format json` to export the result as JSON instead.
+//! Without a private key, only the turnout is exported. The status of the election and the tally are fetched in a single
+//! `ElectionResultRequest`, so that both belong to the same head of the chain. It requires a token granting the `Tally` scope.
+//!
+//! The tally of each question, along with its turnout and the homomorphic sum of its votes, is exported in
+//! machine-readable form by running `node_rs tally -o result.csv 127.0.0.1:3000`. The format follows the extension
+//! of the output file, `csv` or `json`, or is chosen with `--format`. The CSV holds a line per question with the columns
+//! `question_id`, `phase`, `height`, `head_identifier`, `total_votes`, `decrypted_sum` and the components
+//! `cipher_text_big_g` and `cipher_text_big_h` of the sum in decimal. Columns of absent values are left empty.
+//! Run `node_rs tally --chain-export audit.json -o result.json` to take the tally from an exported chain instead,
+//! which is verified before, see [Exporting the Bulletin Board](#exporting-the-bulletin-board).
+//!
+//! ## Retention of Off-Chain Data
+//! Off-chain artifacts which may be linked to voters, such as audit logs containing IP addresses,
+//! can be deleted once the voting is closed. Define them in a retention policy, e.g. `retention.json`:
+//!
+//! ```json
+//! {
+//!   "paths": ["logs/"],
+//!   "max_age_secs": 86400,
+//!   "interval_secs": 3600
+//! }
+//! ```
+//!
+//! Pass `--retention-policy retention.json` to `start` to apply it periodically after the voting is closed,
+//! or run `node_rs purge --node 127.0.0.1:3000` to apply it once. Files are overwritten before being deleted.
+//! The chain itself and the files defining it are never deleted, nor are the files of the data directory given with
+//! `--data-dir` except for the audit log. A policy covering any other file inside the data directory is refused.
+//!
+//! ## Sealing the Archive
+//! Once the voting is closed, the data directory of a stopped node can be frozen for legal retention by running
+//! `node_rs seal-archive --keystore keystore.json data/`. This writes `archive-manifest.json`, listing the SHA-256 digest
+//! of each file in the directory along with the head of the chain, signed with the node identity, and makes all files read-only.
+//! Nodes refuse to write into a sealed directory afterwards, hence it can no longer be passed to `start`.
+//! Run `node_rs seal-archive --verify data/` to detect any file modified, removed or added since sealing.
+//!
+//! ## Exporting the Bulletin Board
+//! Third parties can verify an election without running a node from an export of its canonical chain,
+//! written by running `node_rs audit-export -o audit.json data/` against the data directory of a stopped node,
+//! in the directory holding its genesis configuration. The export is a JSON document of the following fields:
+//!
+//! - `schema_version`: The version of this schema, currently `1`.
+//! - `exported_at`: The time of the export, in seconds since the epoch.
+//! - `genesis_configuration_hash`: The hex encoded SHA-1 digest of the bincode encoding of `genesis`, identifying the election.
+//! - `genesis`: The genesis configuration, including the public key and the public UCIV information of all voters.
+//! - `height`, `head_identifier`: The height and the identifier of the head of the canonical chain.
+//! - `phase`: The phase of the election at the head, i.e. `NotOpened`, `Opened` or `Closed`.
+//! - `blocks`: The blocks of the canonical chain, from the genesis block to the head, each holding its `identifier`,
+//!   its `signature` by the sealer, and its `data` including the transactions along with their proofs.
+//! - `tallies`: The tally of each question at the head, as calculated by the exporting node.
+//!
+//! Chains starting with a state snapshot can not be exported, as the blocks below the snapshot are not known.
+//!
+//! Auditors verify an export by running `node_rs audit audit.json`, without connecting to any node.
+//! It recomputes the genesis configuration hash and checks each block as a node receiving it would:
+//! its identifier, its sealer and signature, and all its transactions including the membership and
+//! cast-as-intended proofs of the votes. Finally, the phase and the tallies are recalculated from the blocks.
+//! The outcome of each check is printed, and the command exits with a non-zero code if any of them failed.
+//!
+//! ## Notifying External Systems
+//! Instead of polling a node, downstream systems such as registries can be notified about finalized blocks
+//! and the opening and closing of the voting. Define the HTTP endpoints in e.g. `webhooks.json`:
+//!
+//! ```json
+//! {
+//!   "endpoints": [
+//!     { "url": "http://127.0.0.1:8080/hooks/node", "secret": "<shared secret>" }
+//!   ],
+//!   "spool_directory": "webhooks/",
+//!   "confirmations": 6,
+//!   "interval_secs": 10
+//! }
+//! ```
+//!
+//! and pass `--webhooks webhooks.json` to `start`. A block is final once it is followed by `confirmations` blocks
+//! on the canonical chain. Each event is posted as JSON, along with the header `X-Node-Rs-Signature: sha1=<HMAC-SHA1 of the body>`
+//! computed with the secret of the endpoint. Events are persisted in the spool directory until their endpoint responds with
+//! a `2xx` status, and are delivered at least once and in order, even across restarts. Endpoints must therefore deduplicate
+//! events by their `identifier`.
+//!
+//! ## Publishing Checkpoints
+//! So that external observers can detect a retroactively rewritten chain, sealers can periodically publish the finalized
+//! head of the canonical chain to a beacon. Define the beacon in e.g. `beacon.json`, either as `https://` endpoint:
+//!
+//! ```json
+//! {
+//!   "publisher": { "Https": { "url": "https://beacon.example.org/checkpoints" } },
+//!   "confirmations": 6,
+//!   "interval_secs": 60
+//! }
+//! ```
+//!
+//! or as TXT record, published by a command such as a script calling `nsupdate`:
+//!
+//! ```json
+//! {
+//!   "publisher": { "DnsTxt": { "record": "_checkpoint.vote.example.org", "command": "/usr/local/bin/publish-txt" } },
+//!   "confirmations": 6,
+//!   "interval_secs": 60
+//! }
+//! ```
+//!
+//! and pass `--beacon beacon.json` to `start` along with a node key, keystore or external signer. Whenever the block followed by
+//! `confirmations` blocks advanced, the node publishes a checkpoint naming the hash of the genesis configuration, the height
+//! and identifier of that block, the time and its own address, signed with its node identity. The checkpoint is posted as JSON
+//! to the endpoint, or passed as JSON to the command along with the name of the record. Observers verify it with the public key
+//! of the sealer declared in `sealer_keys`, see `Checkpoint::verify`, and raise an alarm once a block at a published height
+//! differs from the published identifier.
 
 #![crate_type = "lib"]
 #![crate_name = "node_rs"]
@@ -141,17 +813,39 @@ extern crate uuid;
 extern crate serde_derive;
 extern crate serde;
 extern crate serde_json;
+extern crate serde_cbor;
 
 #[macro_use]
 extern crate log;
 extern crate pretty_env_logger;
 
 extern crate sha1;
+extern crate sha2;
+extern crate blake2;
 extern crate bincode;
 
 extern crate num;
 extern crate crypto_rs;
 
+extern crate libc;
+extern crate ed25519_dalek;
+extern crate chacha20poly1305;
+extern crate scrypt;
+extern crate native_tls;
+extern crate openssl;
+extern crate flate2;
+
+#[cfg(feature = "ui")]
+extern crate brotli;
+#[cfg(feature = "ecdsa-p256")]
+extern crate p256;
+#[cfg(feature = "libp2p")]
+extern crate libp2p;
+#[cfg(feature = "libp2p")]
+extern crate tokio;
+#[cfg(feature = "libp2p")]
+extern crate futures03;
+
 /// Holds all functionality related to the blockchain itself.
 pub mod chain;
 
@@ -162,4 +856,60 @@ pub mod config;
 pub mod p2p;
 
 /// Holds all functionality related to the protocol used to communicate blocks and transactions.
-pub mod protocol;
\ No newline at end of file
+pub mod protocol;
+
+/// Holds all functionality related to exporting the results of an election.
+pub mod export;
+
+/// Holds all functionality related to importing ballots collected offline.
+pub mod import;
+
+/// Holds all functionality related to signing blocks, e.g. by external signers.
+pub mod signer;
+
+/// Holds all functionality related to the retention of off-chain data.
+pub mod retention;
+
+/// Holds all functionality related to authenticating messages with a shared secret.
+pub mod mac;
+
+/// Holds all functionality related to notifying external systems about the chain.
+pub mod webhook;
+
+/// Holds all functionality related to sending plain HTTP and HTTPS requests.
+pub mod http;
+
+/// Holds all functionality related to publishing signed checkpoints of the chain to external beacons.
+pub mod beacon;
+
+/// Holds all functionality related to onboarding new sealers, e.g. join requests.
+pub mod onboarding;
+
+/// Holds all functionality related to shutting down the node gracefully.
+pub mod shutdown;
+
+/// Holds all functionality related to validating a build by running an election on an ephemeral network.
+pub mod selftest;
+
+/// Holds all functionality related to collecting metrics about the internals of the node, e.g. its thread pools.
+pub mod metrics;
+
+/// Holds all functionality related to installing a node as a service, e.g. with systemd.
+pub mod install;
+
+/// Holds all functionality related to sealing the data directory as tamper-evident archive once the election is certified.
+pub mod archive;
+
+/// Holds all functionality related to decrypting the tally jointly by a threshold of the sealers.
+pub mod threshold;
+
+/// Holds all functionality related to proving the eligibility of voters by credentials blind-signed by the registrar.
+pub mod credential;
+
+/// An embedded dashboard showing the state of the election.
+#[cfg(feature = "ui")]
+pub mod ui;
+
+/// Helpers for clients constructing transactions exactly as the nodes verify them.
+#[cfg(feature = "client")]
+pub mod client;
\ No newline at end of file
@@ -0,0 +1,63 @@
+use sha1::Sha1;
+
+/// The block size of SHA-1 in bytes, as required to compute a HMAC.
+const SHA1_BLOCK_SIZE: usize = 64;
+
+/// Compute the HMAC-SHA1 of the given message as defined in RFC 2104.
+pub fn hmac_sha1(key: &[u8], message: &[u8]) -> [u8; 20] {
+    let mut block_key = [0u8; SHA1_BLOCK_SIZE];
+    if key.len() > SHA1_BLOCK_SIZE {
+        block_key[..20].copy_from_slice(&Sha1::from(key).digest().bytes());
+    } else {
+        block_key[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha1::new();
+    inner.update(&block_key.iter().map(|byte| byte ^ 0x36).collect::<Vec<u8>>());
+    inner.update(message);
+
+    let mut outer = Sha1::new();
+    outer.update(&block_key.iter().map(|byte| byte ^ 0x5c).collect::<Vec<u8>>());
+    outer.update(&inner.digest().bytes());
+
+    outer.digest().bytes()
+}
+
+/// Compare both strings in time independent of the position of the first difference.
+pub fn constant_time_eq(first: &str, second: &str) -> bool {
+    if first.len() != second.len() {
+        return false;
+    }
+
+    first.bytes().zip(second.bytes()).fold(0u8, |difference, (a, b)| difference | (a ^ b)) == 0
+}
+
+/// Encode the given bytes as lowercase hex string.
+pub fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Decode the given hex string. Returns None if it is not valid hex.
+pub fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 == 1 {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| hex.get(index..index + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect()
+}
+
+#[cfg(test)]
+mod mac_test {
+
+    use super::*;
+
+    #[test]
+    fn test_hmac_sha1() {
+        // test case 2 of RFC 2202
+        let mac = hmac_sha1(b"Jefe", b"what do ya want for nothing?");
+        assert_eq!("effcdf6ae5eb2fa2d27416d5f184df9c259a7c79", encode_hex(&mac));
+    }
+}
@@ -1,20 +1,70 @@
 extern crate clap;
+extern crate crypto_rs;
 extern crate env_logger;
 extern crate futures;
 #[macro_use]
 extern crate log;
 extern crate node_rs;
 extern crate pretty_env_logger;
+extern crate rpassword;
+extern crate serde_json;
 
-use clap::{App, Arg, SubCommand};
+use clap::{App, Arg, ArgGroup, SubCommand};
+use crypto_rs::cai::uciv::ImageSet;
+use crypto_rs::el_gamal::encryption::PrivateKey;
 use env_logger::Target;
+use node_rs::archive;
+use node_rs::beacon::BeaconConfig;
+use node_rs::config::banner::StartupBanner;
 use node_rs::config::genesis::Genesis;
+use node_rs::config::log_filter;
+use node_rs::config::node::NodeConfig;
+use node_rs::config::version::{VersionCheck, VersionPolicy};
+use node_rs::export::{self, audit, ExportFormat};
+use node_rs::export::tally::{self, TallyFormat};
+use node_rs::import::{self, BallotBatch, BatchKey};
+use node_rs::install::{self, ServiceManager, ServiceSpec};
+use node_rs::onboarding::{CandidateIdentity, JoinRequest};
+use node_rs::p2p::admin::AdminIdentity;
 use node_rs::p2p::node::Node;
+use node_rs::p2p::client;
+use node_rs::p2p::codec::Message;
+use node_rs::p2p::memory::MemoryCaps;
+use node_rs::p2p::peers;
+use node_rs::p2p::probe::{self, ProbeOutcome};
+use node_rs::p2p::rate_limit::RateLimits;
+use node_rs::p2p::rpc_token::RpcTokens;
+use node_rs::p2p::tally_comparison;
+use node_rs::p2p::tls::{self, TlsConfig, TlsContext};
+use node_rs::p2p::transport::TransportKind;
+#[cfg(feature = "libp2p")]
+use node_rs::p2p::libp2p_transport;
+use node_rs::protocol::clique::NODE_VERSION;
+use node_rs::chain::chain_visitor::ElectionPhase;
+use node_rs::chain::storage::StorageEncoding;
+use node_rs::chain::transaction::{PhaseChange, Transaction, TransactionType, VoterRegistration};
+use node_rs::retention::RetentionPolicy;
+use node_rs::selftest;
+use node_rs::shutdown;
+use node_rs::signer::algorithm::SignatureAlgorithm;
+use node_rs::signer::key::NodeKey;
+use node_rs::signer::keystore::Keystore;
+use node_rs::threshold::KeyShare;
+use node_rs::webhook::WebhookConfig;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::Write;
 use std::net::SocketAddr;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 fn main() {
-    let matches = App::new("node_rs")
+    let signature_algorithms: Vec<&str> = SignatureAlgorithm::supported().iter().map(|algorithm| algorithm.identifier()).collect();
+
+    let app = App::new("node_rs")
         .version("0.1.0")
         .author("Raphael Matile <raphael.matile@gmail.com>")
         .about("Run a node of a permissioned e-voting blockchain")
@@ -24,59 +74,925 @@ fn main() {
             .long("verbosity")
             .multiple(true)
         )
+        .arg(Arg::with_name("log")
+            .long("log")
+            .takes_value(true)
+            .value_name("FILTERS")
+            .help("Comma separated levels of single modules, overriding the verbosity, e.g. p2p=trace,protocol=info")
+        )
+        .arg(Arg::with_name("rpc_tls")
+            .long("rpc-tls")
+            .help("Connect to the RPC address of nodes with TLS, trusting the certificate authorities of the system")
+        )
+        .arg(Arg::with_name("rpc_tls_ca")
+            .long("rpc-tls-ca")
+            .takes_value(true)
+            .value_name("FILE")
+            .help("Connect to the RPC address of nodes with TLS, trusting their certificates issued by the given PEM encoded certificate authority")
+        )
+        .arg(Arg::with_name("peer_tls_certificate")
+            .long("peer-tls-certificate")
+            .takes_value(true)
+            .value_name("FILE")
+            .requires("peer_tls_key")
+            .help("Connect to the address of nodes for peers with TLS, e.g. for probe and admin, presenting the given PEM encoded certificate. Required if the nodes use TLS")
+        )
+        .arg(Arg::with_name("peer_tls_key")
+            .long("peer-tls-key")
+            .takes_value(true)
+            .value_name("FILE")
+            .requires("peer_tls_certificate")
+            .help("The PEM encoded PKCS#8 private key of the certificate passed with --peer-tls-certificate")
+        )
+        .arg(Arg::with_name("peer_tls_ca")
+            .long("peer-tls-ca")
+            .takes_value(true)
+            .value_name("FILE")
+            .requires("peer_tls_certificate")
+            .help("Trust the certificates of nodes issued by the given PEM encoded certificate authority when connecting with --peer-tls-certificate. Defaults to the authorities trusted by the system")
+        )
         .subcommand(
             SubCommand::with_name("start")
                 .about("Start a new node")
                 .arg(Arg::with_name("listen_address")
                     .required(true)
                     .takes_value(true)
-                    .index(1)
-                    .help("The address on which the started node should listen for incoming connections of other nodes. In the format <IPv4>:<Port>")
+                    .index(1)
+                    .help("The address on which the started node should listen for incoming connections of other nodes. In the format <IPv4|Hostname>:<Port>")
+                )
+                .arg(Arg::with_name("rpc_listen_address")
+                    .required(true)
+                    .takes_value(true)
+                    .index(2)
+                    .help("The address on which the started node should listen for RPC connections from clients. In the format <IPv4|Hostname>:<Port>")
+                )
+                .arg(Arg::with_name("advertise_address")
+                    .long("advertise-address")
+                    .takes_value(true)
+                    .value_name("ADDRESS")
+                    .help("The address under which other nodes know this node, as listed in the genesis configuration. Defaults to the listen address. In the format <IPv4|Hostname>:<Port>")
+                )
+                .arg(Arg::with_name("data_dir")
+                    .long("data-dir")
+                    .takes_value(true)
+                    .value_name("DIRECTORY")
+                    .help("Persist the chain and the bans of misbehaving sources in the given directory and restore them from there on startup. Otherwise, both are kept in memory only")
+                )
+                .arg(Arg::with_name("storage_encoding")
+                    .long("storage-encoding")
+                    .takes_value(true)
+                    .value_name("ENCODING")
+                    .possible_values(&["json", "bincode"])
+                    .default_value("bincode")
+                    .help("The encoding of the blocks persisted in the data directory. Blocks stored in the other encoding are migrated on startup")
+                )
+                .arg(Arg::with_name("upnp")
+                    .long("upnp")
+                    .help("Map the port of the advertised address to the listen address on the gateway of the local network via UPnP")
+                )
+                .arg(Arg::with_name("relay")
+                    .long("relay")
+                    .help("Keep a connection open to each other peer over which it relays messages to this node. Use if this node cannot accept incoming connections")
+                )
+                .arg(Arg::with_name("resume")
+                    .short("r")
+                    .long("resume")
+                    .conflicts_with("fresh")
+                    .help("Resume the chain from the peers defined in the genesis block, replacing the own chain if theirs is longer")
+                )
+                .arg(Arg::with_name("fast_sync")
+                    .long("fast-sync")
+                    .requires("resume")
+                    .help("When resuming, start from a snapshot of the finalized chain on which the majority of the peers agrees instead of replaying all blocks")
+                )
+                .arg(Arg::with_name("fresh")
+                    .long("fresh")
+                    .help("Start from the genesis block without synchronizing with other peers. Refuses a data directory which already holds blocks")
+                )
+                .arg(Arg::with_name("ping")
+                    .short("p")
+                    .long("ping")
+                    .hidden(true)
+                    .conflicts_with("fresh")
+                    .help("Deprecated alias of --resume")
+                )
+                .arg(Arg::with_name("gossip_delay")
+                    .long("gossip-delay")
+                    .takes_value(true)
+                    .value_name("MILLISECONDS")
+                    .help("Gossip transactions received via RPC in batches after a random delay of at most the given milliseconds instead of broadcasting them immediately")
+                )
+                .arg(Arg::with_name("gossip_padding")
+                    .long("gossip-padding")
+                    .requires("gossip_delay")
+                    .help("Gossip a dummy message whenever there are no transactions to gossip")
+                )
+                .arg(Arg::with_name("connection_workers")
+                    .long("connection-workers")
+                    .takes_value(true)
+                    .value_name("AMOUNT")
+                    .validator(validate_worker_amount)
+                    .help("The amount of connections handled concurrently by each listener. Defaults to 8")
+                )
+                .arg(Arg::with_name("shutdown_deadline")
+                    .long("shutdown-deadline")
+                    .takes_value(true)
+                    .value_name("SECONDS")
+                    .help("The time to finish the connections being handled when the node is shut down by SIGINT or SIGTERM. Defaults to 10")
+                )
+                .arg(Arg::with_name("chain_cache_cap")
+                    .long("chain-cache-cap")
+                    .takes_value(true)
+                    .value_name("MEGABYTES")
+                    .help("The memory used to cache copies of the chain served to synchronizing peers. Larger copies are created anew for each request")
+                )
+                .arg(Arg::with_name("mempool_cap")
+                    .long("mempool-cap")
+                    .takes_value(true)
+                    .value_name("MEGABYTES")
+                    .help("The memory used to buffer transactions until they are included in a block. Further transactions are rejected while the buffer is full")
+                )
+                .arg(Arg::with_name("in_flight_cap")
+                    .long("in-flight-cap")
+                    .takes_value(true)
+                    .value_name("MEGABYTES")
+                    .help("The memory used by messages being received. Reading further messages is delayed while the cap is reached")
+                )
+                .arg(Arg::with_name("max_peer_connections")
+                    .long("max-peer-connections")
+                    .takes_value(true)
+                    .value_name("AMOUNT")
+                    .help("The maximum amount of connections a single IP address may have open at once on the peer listener. Unlimited by default")
+                )
+                .arg(Arg::with_name("peer_message_rate")
+                    .long("peer-message-rate")
+                    .takes_value(true)
+                    .value_name("MESSAGES")
+                    .help("The maximum amount of messages a single IP address may send per second to the peer listener. Unlimited by default")
+                )
+                .arg(Arg::with_name("max_rpc_connections")
+                    .long("max-rpc-connections")
+                    .takes_value(true)
+                    .value_name("AMOUNT")
+                    .help("The maximum amount of connections a single IP address may have open at once on the RPC listener. Unlimited by default")
+                )
+                .arg(Arg::with_name("rpc_message_rate")
+                    .long("rpc-message-rate")
+                    .takes_value(true)
+                    .value_name("MESSAGES")
+                    .help("The maximum amount of messages a single IP address may send per second to the RPC listener. Unlimited by default")
+                )
+                .arg(Arg::with_name("verification_workers")
+                    .long("verification-workers")
+                    .takes_value(true)
+                    .value_name("AMOUNT")
+                    .validator(validate_worker_amount)
+                    .help("The amount of workers verifying the transactions of received blocks in parallel. Defaults to 4")
+                )
+                .arg(Arg::with_name("dashboard")
+                    .long("dashboard")
+                    .takes_value(true)
+                    .value_name("ADDRESS")
+                    .help("Serve the election dashboard on the given address. Requires node_rs to be built with the ui feature. In the format <IPv4|Hostname>:<Port>")
+                )
+                .arg(Arg::with_name("dashboard_compression")
+                    .long("dashboard-compression")
+                    .takes_value(true)
+                    .value_name("ENCODINGS")
+                    .default_value("br,gzip")
+                    .help("The encodings to compress responses of the dashboard with, in the order of preference, or none to disable compression")
+                )
+                .arg(Arg::with_name("retention_policy")
+                    .long("retention-policy")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("Periodically delete the off-chain data defined in the given retention policy once the voting is closed")
+                )
+                .arg(Arg::with_name("webhooks")
+                    .long("webhooks")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("Notify the HTTP endpoints defined in the given configuration about finalized blocks and the lifecycle of the election")
+                )
+                .arg(Arg::with_name("beacon")
+                    .long("beacon")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("Periodically publish the signed finalized head to the beacon defined in the given configuration")
+                )
+                .arg(Arg::with_name("external_signer")
+                    .long("external-signer")
+                    .takes_value(true)
+                    .value_name("SOCKET")
+                    .requires("sign")
+                    .help("Let the external signer listening on the given Unix domain socket sign sealed blocks. The own slot is skipped if it is unreachable")
+                )
+                .arg(Arg::with_name("node_key")
+                    .long("node-key")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .requires("sign")
+                    .conflicts_with("external_signer")
+                    .help("Sign sealed blocks with the Ed25519 keypair in the given file. A new keypair is generated if the file does not exist")
+                )
+                .arg(Arg::with_name("keystore")
+                    .long("keystore")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .requires("sign")
+                    .conflicts_with_all(&["external_signer", "node_key"])
+                    .help("Sign sealed blocks with the node identity in the given keystore, as generated by keygen. The passphrase is read from NODE_RS_KEYSTORE_PASSPHRASE or prompted for")
+                )
+                .arg(Arg::with_name("allow_unsigned_blocks")
+                    .long("allow-unsigned-blocks")
+                    .requires("sign")
+                    .help("Seal blocks even though the genesis configuration declares no sealer keys, in which case any peer may seal blocks on behalf of a sealer. Only meant for test networks")
+                )
+                .arg(Arg::with_name("version_policy")
+                    .long("version-policy")
+                    .takes_value(true)
+                    .possible_values(&["exact", "compatible", "ignore"])
+                    .default_value("ignore")
+                    .help("Refuse to join the network if the version declared in the genesis configuration is not exactly the version of this node, not compatible with it, or ignore the declared version")
+                )
+                .arg(Arg::with_name("admin_identity")
+                    .long("admin-identity")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("Accept admin commands of the given identity on the listen address, additionally to the RPC address")
+                )
+                .arg(Arg::with_name("key_share")
+                    .long("key-share")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("Publish decryption shares of the tally with the given share of the private key of the election once the voting is closed")
+                )
+                .arg(Arg::with_name("transport")
+                    .long("transport")
+                    .takes_value(true)
+                    .value_name("TRANSPORT")
+                    .default_value("tcp")
+                    .possible_values(&["tcp", "libp2p"])
+                    .help("The transport used to send messages to other peers. The libp2p transport requires the feature libp2p and the libp2p addresses of the sealers in the genesis configuration")
+                )
+                .arg(Arg::with_name("libp2p_key")
+                    .long("libp2p-key")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("Identify this node on libp2p with the keypair in the given file, whose peer id must be the one of its libp2p address in the genesis configuration. A new keypair is generated if the file does not exist")
+                )
+                .arg(Arg::with_name("rpc_tokens")
+                    .long("rpc-tokens")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("Require each RPC request to present one of the given tokens, granting the scope of the requested method")
+                )
+                .arg(Arg::with_name("rpc_tls_certificate")
+                    .long("rpc-tls-certificate")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .requires("rpc_tls_key")
+                    .help("Accept RPC connections only with TLS, presenting the given PEM encoded certificate")
+                )
+                .arg(Arg::with_name("rpc_tls_key")
+                    .long("rpc-tls-key")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .requires("rpc_tls_certificate")
+                    .help("The PEM encoded PKCS#8 private key of the RPC TLS certificate")
+                )
+                .arg(Arg::with_name("ntp_server")
+                    .long("ntp-server")
+                    .takes_value(true)
+                    .value_name("SERVER")
+                    .help("Compare the local clock against the given NTP server additionally to the peers. In the format <IPv4|Hostname>[:<Port>]")
+                )
+                .arg(Arg::with_name("clock_warn_threshold")
+                    .long("clock-warn-threshold")
+                    .takes_value(true)
+                    .value_name("MILLISECONDS")
+                    .help("Warn if the local clock deviates from the clocks of the peers by more than the given milliseconds. Defaults to 1000")
+                )
+                .arg(Arg::with_name("compression_threshold")
+                    .long("compression-threshold")
+                    .takes_value(true)
+                    .value_name("KILOBYTES")
+                    .help("Compress messages to other peers from the given size on, e.g. chains and blocks, or none to disable compression. Defaults to 64")
+                )
+                .arg(Arg::with_name("tls_certificate")
+                    .long("tls-certificate")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .requires("tls_key")
+                    .help("Encrypt the connections to other peers with TLS, presenting the given PEM encoded certificate")
+                )
+                .arg(Arg::with_name("tls_key")
+                    .long("tls-key")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .requires("tls_certificate")
+                    .help("The PEM encoded PKCS#8 private key of the TLS certificate")
+                )
+                .arg(Arg::with_name("tls_ca")
+                    .long("tls-ca")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .requires("tls_certificate")
+                    .help("Trust the TLS certificates of peers issued by the given PEM encoded certificate authority, unless the genesis configuration declares their certificate. Defaults to the authorities trusted by the system")
+                )
+                .arg(Arg::with_name("tls_allow_plain")
+                    .long("tls-allow-plain")
+                    .requires("tls_certificate")
+                    .help("Also accept connections of peers and commands like probe without TLS, which are neither encrypted nor authenticated, e.g. while the sealers switch to TLS one at a time")
+                )
+                .arg(Arg::with_name("sign")
+                    .short("s")
+                    .long("sign")
+                    .help("Sign blocks after starting the node")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("probe")
+                .about("Check whether a peer is compatible with this node, e.g. when two nodes refuse to sync")
+                .arg(Arg::with_name("address")
+                    .required(true)
+                    .takes_value(true)
+                    .index(1)
+                    .help("The address of the peer. In the format <IPv4|Hostname>:<Port>")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("compare-tally")
+                .about("Request the tally from several nodes over their listen addresses and check that all compute the same sum")
+                .arg(Arg::with_name("address")
+                    .takes_value(true)
+                    .multiple(true)
+                    .index(1)
+                    .help("The addresses of the nodes. In the format <IPv4|Hostname>:<Port>. Defaults to the sealers of the genesis configuration in the current directory")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("admin")
+                .about("Run an admin command on a node over its listen address, e.g. if its RPC address is not reachable")
+                .arg(Arg::with_name("address")
+                    .required(true)
+                    .takes_value(true)
+                    .index(1)
+                    .help("The address on which the node listens for incoming connections of other nodes. In the format <IPv4|Hostname>:<Port>")
+                )
+                .arg(Arg::with_name("command")
+                    .required(true)
+                    .takes_value(true)
+                    .index(2)
+                    .possible_values(&["open-vote", "close-vote", "tally", "status", "election-status", "sealer-statistics", "epoch-statistics", "mempool", "metrics", "peers"])
+                    .help("The command to run")
+                )
+                .arg(Arg::with_name("identity")
+                    .long("identity")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .default_value("admin.json")
+                    .help("The admin identity designated by the node")
+                )
+                .arg(Arg::with_name("authority_keystore")
+                    .long("authority-keystore")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("Sign opening or closing the voting with the election authority in the given keystore, as generated by keygen. Required if the genesis configuration declares an election authority. The passphrase is read from NODE_RS_KEYSTORE_PASSPHRASE or prompted for")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("join-request")
+                .about("Request to join the network as sealer, or list the pending requests of all candidates")
+                .arg(Arg::with_name("rpc_address")
+                    .required(true)
+                    .takes_value(true)
+                    .index(1)
+                    .help("The address on which an existing node listens for RPC connections. In the format <IPv4|Hostname>:<Port>")
+                )
+                .arg(Arg::with_name("address")
+                    .long("address")
+                    .takes_value(true)
+                    .value_name("ADDRESS")
+                    .required_unless("list")
+                    .help("The address under which the candidate would be listed among the sealers. In the format <IPv4|Hostname>:<Port>")
+                )
+                .arg(Arg::with_name("identity")
+                    .long("identity")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .default_value("candidate.json")
+                    .help("The identity of the candidate. Generated if it does not exist yet")
+                )
+                .arg(Arg::with_name("list")
+                    .long("list")
+                    .conflicts_with("address")
+                    .help("List the pending join requests instead of submitting one")
+                )
+                .arg(Arg::with_name("rpc_token")
+                    .long("rpc-token")
+                    .takes_value(true)
+                    .value_name("TOKEN")
+                    .help("The token to present, if the node requires one for RPC requests")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("mempool")
+                .about("List the transactions a node received but did not include in a block yet")
+                .arg(Arg::with_name("rpc_address")
+                    .required(true)
+                    .takes_value(true)
+                    .index(1)
+                    .help("The address on which the node listens for RPC connections. In the format <IPv4|Hostname>:<Port>")
+                )
+                .arg(Arg::with_name("payloads")
+                    .long("payloads")
+                    .help("Print the whole transactions instead of their summaries. Requires a token of scope Admin, if the node requires tokens")
+                )
+                .arg(Arg::with_name("rpc_token")
+                    .long("rpc-token")
+                    .takes_value(true)
+                    .value_name("TOKEN")
+                    .help("The token to present, if the node requires one for RPC requests")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("turnout")
+                .about("Follow the amount of votes counted by a node, printing it as JSON whenever a new block arrives")
+                .arg(Arg::with_name("rpc_address")
+                    .required(true)
+                    .takes_value(true)
+                    .index(1)
+                    .help("The address on which the node listens for RPC connections. In the format <IPv4|Hostname>:<Port>")
+                )
+                .arg(Arg::with_name("rpc_token")
+                    .long("rpc-token")
+                    .takes_value(true)
+                    .value_name("TOKEN")
+                    .help("The token to present, if the node requires one for RPC requests")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("vote-sealer")
+                .about("Ask a sealer to vote on adding a candidate to the sealers, or on removing a sealer")
+                .arg(Arg::with_name("rpc_address")
+                    .required(true)
+                    .takes_value(true)
+                    .index(1)
+                    .help("The address on which the voting sealer listens for RPC connections. In the format <IPv4|Hostname>:<Port>")
+                )
+                .arg(Arg::with_name("candidate")
+                    .required(true)
+                    .takes_value(true)
+                    .index(2)
+                    .help("The address of the sealer to add or to remove. In the format <IPv4|Hostname>:<Port>")
+                )
+                .arg(Arg::with_name("remove")
+                    .long("remove")
+                    .help("Vote on removing the sealer instead of adding it")
+                )
+                .arg(Arg::with_name("public_key")
+                    .long("public-key")
+                    .takes_value(true)
+                    .value_name("KEY")
+                    .conflicts_with("remove")
+                    .help("The hex encoded public key of the candidate's node key. Required if blocks must be signed")
+                )
+                .arg(Arg::with_name("rpc_token")
+                    .long("rpc-token")
+                    .takes_value(true)
+                    .value_name("TOKEN")
+                    .help("The token to present, if the node requires one for RPC requests. Requires the scope Admin")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("register-voter")
+                .about("Register a voter on the chain, or revoke it, signed by the election authority. Requires the genesis configuration in the current directory and the feature voter-registry")
+                .arg(Arg::with_name("rpc_address")
+                    .required(true)
+                    .takes_value(true)
+                    .index(1)
+                    .help("The address on which a node listens for RPC connections. In the format <IPv4|Hostname>:<Port>")
+                )
+                .arg(Arg::with_name("voter_idx")
+                    .required(true)
+                    .takes_value(true)
+                    .index(2)
+                    .help("The index of the voter, which its votes refer to")
+                )
+                .arg(Arg::with_name("images")
+                    .long("images")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .required_unless("revoke")
+                    .conflicts_with("revoke")
+                    .help("The JSON encoded image set of the voter, holding an image per voting option")
+                )
+                .arg(Arg::with_name("credential")
+                    .long("credential")
+                    .takes_value(true)
+                    .value_name("CREDENTIAL")
+                    .required(true)
+                    .help("The credential the voter is identified with, e.g. the digest of its entry in the electoral roll")
+                )
+                .arg(Arg::with_name("question")
+                    .long("question")
+                    .takes_value(true)
+                    .value_name("QUESTION")
+                    .help("The identifier of the question to register the voter for. Defaults to the one whose voters are declared in public_uciv.json")
+                )
+                .arg(Arg::with_name("revoke")
+                    .long("revoke")
+                    .help("Revoke the voter instead of registering it. Votes it cast before remain counted")
+                )
+                .arg(Arg::with_name("authority_keystore")
+                    .long("authority-keystore")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .required(true)
+                    .help("Sign the registration with the election authority in the given keystore, as generated by keygen. The passphrase is read from NODE_RS_KEYSTORE_PASSPHRASE or prompted for")
+                )
+                .arg(Arg::with_name("rpc_token")
+                    .long("rpc-token")
+                    .takes_value(true)
+                    .value_name("TOKEN")
+                    .help("The token to present, if the node requires one for RPC requests")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("purge")
+                .about("Securely delete the off-chain data defined in a retention policy. Never touches the chain")
+                .arg(Arg::with_name("policy")
+                    .long("policy")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .default_value("retention.json")
+                    .help("The retention policy defining the data to delete")
+                )
+                .arg(Arg::with_name("node")
+                    .long("node")
+                    .takes_value(true)
+                    .value_name("RPC_ADDRESS")
+                    .required_unless("force")
+                    .help("The RPC address of a node used to verify that the voting is closed before deleting any data")
+                )
+                .arg(Arg::with_name("data_dir")
+                    .long("data-dir")
+                    .takes_value(true)
+                    .value_name("DIRECTORY")
+                    .help("The data directory of the node. Its files are never deleted except for the audit log, and a policy covering any other of them is refused")
+                )
+                .arg(Arg::with_name("force")
+                    .long("force")
+                    .conflicts_with("node")
+                    .help("Delete the data without verifying that the voting is closed")
+                )
+                .arg(Arg::with_name("rpc_token")
+                    .long("rpc-token")
+                    .takes_value(true)
+                    .value_name("TOKEN")
+                    .help("The token to present, if the node requires one for RPC requests")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("seal-archive")
+                .about("Seal the data directory of a stopped node as tamper-evident archive once the voting is closed: sign a manifest of all its files and make them read-only")
+                .arg(Arg::with_name("data_dir")
+                    .takes_value(true)
+                    .index(1)
+                    .required(true)
+                    .help("The data directory of the node")
+                )
+                .arg(Arg::with_name("node_key")
+                    .long("node-key")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .conflicts_with("keystore")
+                    .help("The file holding the node identity keypair to sign the manifest with")
+                )
+                .arg(Arg::with_name("keystore")
+                    .long("keystore")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("The encrypted keystore holding the node identity keypair to sign the manifest with. The passphrase is read from NODE_RS_KEYSTORE_PASSPHRASE or prompted for")
+                )
+                .group(ArgGroup::with_name("identity")
+                    .args(&["node_key", "keystore"])
+                )
+                .arg(Arg::with_name("verify")
+                    .long("verify")
+                    .conflicts_with("identity")
+                    .help("Verify that an already sealed data directory was not tampered with instead")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("export-results")
+                .about("Export the result of the election from a running node")
+                .arg(Arg::with_name("rpc_address")
+                    .required(true)
+                    .takes_value(true)
+                    .index(1)
+                    .help("The address on which the node listens for RPC connections. In the format <IPv4|Hostname>:<Port>")
+                )
+                .arg(Arg::with_name("private_key")
+                    .long("private-key")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("The private key to decrypt the tally with. If omitted, only the turnout is exported")
+                )
+                .arg(Arg::with_name("format")
+                    .long("format")
+                    .takes_value(true)
+                    .possible_values(&["eml", "json"])
+                    .default_value("eml")
+                    .help("The format to export the result in")
+                )
+                .arg(Arg::with_name("election_id")
+                    .long("election-id")
+                    .takes_value(true)
+                    .default_value("1")
+                    .help("The identifier of the election, as known to the election management system")
+                )
+                .arg(Arg::with_name("election_name")
+                    .long("election-name")
+                    .takes_value(true)
+                    .default_value("Referendum")
+                    .help("The name of the election")
+                )
+                .arg(Arg::with_name("output")
+                    .short("o")
+                    .long("output")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("The file to write the result to. If omitted, the result is written to stdout")
+                )
+                .arg(Arg::with_name("rpc_token")
+                    .long("rpc-token")
+                    .takes_value(true)
+                    .value_name("TOKEN")
+                    .help("The token to present, if the node requires one for RPC requests")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("audit-export")
+                .about("Export the canonical chain of a stopped node as self-contained archive for third-party verifiers. Run in the directory holding the genesis configuration")
+                .arg(Arg::with_name("data_dir")
+                    .takes_value(true)
+                    .index(1)
+                    .required(true)
+                    .help("The data directory of the node")
+                )
+                .arg(Arg::with_name("output")
+                    .short("o")
+                    .long("output")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("The file to write the archive to. If omitted, the archive is written to stdout")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("audit")
+                .about("Verify an exported chain without connecting to any node: all block identifiers, sealer signatures and proofs of the votes. Exits with a non-zero code if any check fails")
+                .arg(Arg::with_name("chain_export")
+                    .takes_value(true)
+                    .index(1)
+                    .required(true)
+                    .help("The file written by audit-export")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("tally")
+                .about("Export the tally of each question along with its turnout and the sum of its votes as CSV or JSON, from a running node or an exported chain")
+                .arg(Arg::with_name("rpc_address")
+                    .takes_value(true)
+                    .index(1)
+                    .help("The address on which the node listens for RPC connections. In the format <IPv4|Hostname>:<Port>")
+                )
+                .arg(Arg::with_name("chain_export")
+                    .long("chain-export")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("The file written by audit-export to take the tally from instead. It is verified before")
+                )
+                .group(ArgGroup::with_name("source")
+                    .args(&["rpc_address", "chain_export"])
+                    .required(true)
+                )
+                .arg(Arg::with_name("output")
+                    .short("o")
+                    .long("output")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("The file to write the tally to, in the format matching its extension. If omitted, the tally is written to stdout")
+                )
+                .arg(Arg::with_name("format")
+                    .long("format")
+                    .takes_value(true)
+                    .possible_values(&["csv", "json"])
+                    .help("The format to write the tally in, overriding the extension of the output file. Defaults to csv")
+                )
+                .arg(Arg::with_name("rpc_token")
+                    .long("rpc-token")
+                    .takes_value(true)
+                    .value_name("TOKEN")
+                    .help("The token to present, if the node requires one for RPC requests")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("import-ballots")
+                .about("Import a signed batch of ballots collected offline into a running node")
+                .arg(Arg::with_name("batch_file")
+                    .required(true)
+                    .takes_value(true)
+                    .index(1)
+                    .help("The file holding the signed batch of ballots")
+                )
+                .arg(Arg::with_name("rpc_address")
+                    .required(true)
+                    .takes_value(true)
+                    .index(2)
+                    .help("The address on which the node listens for RPC connections. In the format <IPv4|Hostname>:<Port>")
+                )
+                .arg(Arg::with_name("batch_key")
+                    .long("batch-key")
+                    .required(true)
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("The key shared with the offline client, used to verify the signature of the batch")
+                )
+                .arg(Arg::with_name("output")
+                    .short("o")
+                    .long("output")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("The file to write the import report to. If omitted, the report is written to stdout")
+                )
+                .arg(Arg::with_name("rpc_token")
+                    .long("rpc-token")
+                    .takes_value(true)
+                    .value_name("TOKEN")
+                    .help("The token to present, if the node requires one for RPC requests")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("keygen")
+                .about("Generate a node identity keypair, store it in an encrypted keystore and print its public key to be declared in the genesis configuration")
+                .arg(Arg::with_name("keystore")
+                    .takes_value(true)
+                    .index(1)
+                    .default_value("keystore.json")
+                    .help("The file to write the keystore to. An existing file is never overwritten")
+                )
+                .arg(Arg::with_name("address")
+                    .long("address")
+                    .takes_value(true)
+                    .value_name("ADDRESS")
+                    .help("The address of the node as listed among the sealers, to print the entry of sealer_keys for. In the format <IPv4|Hostname>:<Port>")
+                )
+                .arg(Arg::with_name("algorithm")
+                    .long("algorithm")
+                    .takes_value(true)
+                    .value_name("ALGORITHM")
+                    .possible_values(&signature_algorithms)
+                    .default_value("ed25519")
+                    .help("The algorithm of the keypair")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("selftest")
+                .about("Run an election on an ephemeral network of three nodes within this process and verify its tally. Exits with a non-zero code on failure")
+                .arg(Arg::with_name("private_key")
+                    .long("private-key")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .default_value("private_key.json")
+                    .help("The private key belonging to public_key.json, used to decrypt the tally")
+                )
+                .arg(Arg::with_name("private_uciv")
+                    .long("private-uciv")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .default_value("private_uciv.json")
+                    .help("The private UCIV information belonging to public_uciv.json, used to cast the votes")
+                )
+                .arg(Arg::with_name("votes")
+                    .long("votes")
+                    .takes_value(true)
+                    .value_name("AMOUNT")
+                    .default_value("10")
+                    .help("The amount of votes to cast, at most one per voter")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("install")
+                .about("Write a service definition starting a node with the given arguments of start, after validating them along with the configuration in the working directory")
+                .arg(Arg::with_name("systemd")
+                    .long("systemd")
+                    .help("Write a systemd unit, by default to /etc/systemd/system/node_rs.service")
+                )
+                .arg(Arg::with_name("launchd")
+                    .long("launchd")
+                    .help("Write a launchd property list, by default to /Library/LaunchDaemons/org.provotum.node_rs.plist")
+                )
+                .group(ArgGroup::with_name("service_manager")
+                    .args(&["systemd", "launchd"])
+                    .required(true)
+                )
+                .arg(Arg::with_name("data_dir")
+                    .long("data-dir")
+                    .takes_value(true)
+                    .value_name("DIRECTORY")
+                    .required(true)
+                    .help("The data directory of the node. Created if missing and restricted to the user running the node")
+                )
+                .arg(Arg::with_name("working_dir")
+                    .long("working-dir")
+                    .takes_value(true)
+                    .value_name("DIRECTORY")
+                    .default_value(".")
+                    .help("The directory holding genesis.json, public_key.json and public_uciv.json, in which the node is started")
                 )
-                .arg(Arg::with_name("rpc_listen_address")
-                    .required(true)
+                .arg(Arg::with_name("user")
+                    .long("user")
                     .takes_value(true)
-                    .index(2)
-                    .help("The address on which the started node should listen for RPC connections from clients. In the format <IPv4>:<Port>")
+                    .value_name("USER")
+                    .help("The user running the node. Defaults to the user of the service manager")
                 )
-                .arg(Arg::with_name("ping")
-                    .short("p")
-                    .long("ping")
-                    .help("Ping all nodes defined in the genesis block")
+                .arg(Arg::with_name("binary")
+                    .long("binary")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("The node_rs binary started by the service. Defaults to this binary")
                 )
-                .arg(Arg::with_name("sign")
-                    .short("s")
-                    .long("sign")
-                    .help("Sign blocks after starting the node")
+                .arg(Arg::with_name("output")
+                    .short("o")
+                    .long("output")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("The file to write the service definition to, instead of the default location of the service manager")
                 )
-        )
-        .get_matches();
+                .arg(Arg::with_name("force")
+                    .long("force")
+                    .help("Replace an existing service definition")
+                )
+                .arg(Arg::with_name("start_args")
+                    .multiple(true)
+                    .last(true)
+                    .value_name("START_ARGS")
+                    .help("The arguments of start, following --, e.g. -- 0.0.0.0:9000 0.0.0.0:3000 --sign")
+                )
+        );
+    let matches = app.clone().get_matches();
 
-    let log_filter;
-    match matches.occurrences_of("verbosity") {
-        0 => { log_filter = "node_rs=info" }
-        1 => { log_filter = "node_rs=debug" }
-        2 => { log_filter = "node_rs=trace" }
-        _ => { log_filter = "node_rs=trace" }
-    }
+    let log_filters = match log_filter::parse(matches.occurrences_of("verbosity"), matches.value_of("log")) {
+        Ok(log_filters) => log_filters,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let log_filter = log_filters.join(",");
 
     // init logger
     pretty_env_logger::formatted_builder().unwrap()
         //let's just set some random stuff.. for more see
         //https://docs.rs/env_logger/0.5.0-rc.1/env_logger/struct.Builder.html
         .target(Target::Stdout)
-        .parse(log_filter)
+        .parse(&log_filter)
         .init();
 
+    if matches.is_present("rpc_tls") || matches.is_present("rpc_tls_ca") {
+        match tls::create_connector(matches.value_of("rpc_tls_ca").map(Path::new)) {
+            Ok(connector) => client::use_rpc_tls(connector),
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let (Some(certificate), Some(key)) = (matches.value_of("peer_tls_certificate"), matches.value_of("peer_tls_key")) {
+        match tls::create_peer_connector(matches.value_of("peer_tls_ca").map(Path::new), Path::new(certificate), Path::new(key)) {
+            Ok(connector) => client::use_peer_tls(connector),
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
 
     match matches.subcommand_name() {
         Some("start") => {
             let subcommand_matches = matches.subcommand_matches("start").unwrap();
 
-            let listen_address: SocketAddr = subcommand_matches.value_of("listen_address").unwrap().parse::<SocketAddr>().unwrap();
-            let rpc_listen_address: SocketAddr = subcommand_matches.value_of("rpc_listen_address").unwrap().parse::<SocketAddr>().unwrap();
+            let listen_address: SocketAddr = peers::resolve(subcommand_matches.value_of("listen_address").unwrap()).expect("Invalid listen address");
+            let rpc_listen_address: SocketAddr = peers::resolve(subcommand_matches.value_of("rpc_listen_address").unwrap()).expect("Invalid RPC listen address");
+            let advertised_address: Option<SocketAddr> = subcommand_matches.value_of("advertise_address").map(|address| peers::resolve(address).expect("Invalid advertised address"));
 
-            let has_ping: bool = subcommand_matches.is_present("ping");
+            if subcommand_matches.is_present("ping") {
+                warn!("The flag -p/--ping is deprecated, use -r/--resume instead");
+            }
+            let has_resume: bool = subcommand_matches.is_present("resume") || subcommand_matches.is_present("ping");
             let has_sign: bool = subcommand_matches.is_present("sign");
 
             // get configuration
@@ -98,19 +1014,1007 @@ fn main() {
                 std::process::exit(1);
             }
 
+            let mut node_config = NodeConfig::default();
+            node_config.gossip_max_delay = subcommand_matches.value_of("gossip_delay").map(|delay| parse_option_value::<u64>(delay, "gossip delay"));
+            node_config.gossip_padding = subcommand_matches.is_present("gossip_padding");
+            node_config.advertised_address = advertised_address;
+            node_config.log_filters = log_filters.clone();
+            node_config.ntp_server = subcommand_matches.value_of("ntp_server").map(String::from);
+            if let Some(clock_warn_threshold) = subcommand_matches.value_of("clock_warn_threshold") {
+                node_config.clock_warn_threshold = parse_option_value::<u64>(clock_warn_threshold, "clock warn threshold");
+            }
+            match subcommand_matches.value_of("compression_threshold") {
+                Some("none") => node_config.compression_threshold = None,
+                Some(kilobytes) => node_config.compression_threshold = Some(parse_option_value::<usize>(kilobytes, "compression threshold") * 1024),
+                None => {}
+            }
+            node_config.external_signer = subcommand_matches.value_of("external_signer").map(PathBuf::from);
+            if let Some(node_key_file) = subcommand_matches.value_of("node_key") {
+                match NodeKey::load_or_generate(node_key_file) {
+                    Ok(node_key) => {
+                        info!("Signing sealed blocks with public key {}", node_key.public_key);
+                        node_config.node_key = Some(node_key);
+                    }
+                    Err(e) => {
+                        error!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            if let Some(keystore_file) = subcommand_matches.value_of("keystore") {
+                let unseal_result = Keystore::from_file(keystore_file)
+                    .and_then(|keystore| keystore.unseal(&read_passphrase(false)));
+                match unseal_result {
+                    Ok(node_key) => {
+                        info!("Signing sealed blocks with public key {}", node_key.public_key);
+                        node_config.node_key = Some(node_key);
+                    }
+                    Err(e) => {
+                        error!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            if let Some(connection_workers) = subcommand_matches.value_of("connection_workers") {
+                node_config.connection_workers = match parse_worker_amount(connection_workers) {
+                    Ok(connection_workers) => connection_workers,
+                    Err(e) => clap::Error::with_description(&e, clap::ErrorKind::InvalidValue).exit()
+                };
+            }
+            if let Some(verification_workers) = subcommand_matches.value_of("verification_workers") {
+                node_config.verification_workers = match parse_worker_amount(verification_workers) {
+                    Ok(verification_workers) => verification_workers,
+                    Err(e) => clap::Error::with_description(&e, clap::ErrorKind::InvalidValue).exit()
+                };
+            }
+
+            let parse_memory_cap = |name: &str| subcommand_matches.value_of(name).map(|megabytes| parse_option_value::<usize>(megabytes, "memory cap") * 1024 * 1024);
+            node_config.memory_caps = MemoryCaps {
+                chain_cache: parse_memory_cap("chain_cache_cap"),
+                mempool: parse_memory_cap("mempool_cap"),
+                in_flight_messages: parse_memory_cap("in_flight_cap"),
+            };
+
+            let parse_rate_limits = |connections_name: &str, rate_name: &str| RateLimits {
+                max_connections: subcommand_matches.value_of(connections_name).map(|amount| parse_option_value::<usize>(amount, "maximum amount of connections")),
+                messages_per_second: subcommand_matches.value_of(rate_name).map(|messages| parse_option_value::<u32>(messages, "message rate")),
+            };
+            node_config.peer_rate_limits = parse_rate_limits("max_peer_connections", "peer_message_rate");
+            node_config.rpc_rate_limits = parse_rate_limits("max_rpc_connections", "rpc_message_rate");
+
+            node_config.version_policy = subcommand_matches.value_of("version_policy").unwrap().parse::<VersionPolicy>().unwrap();
+            node_config.transport = match subcommand_matches.value_of("transport").unwrap().parse::<TransportKind>() {
+                Ok(transport) => transport,
+                Err(e) => {
+                    error!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            node_config.storage_encoding = subcommand_matches.value_of("storage_encoding").unwrap().parse::<StorageEncoding>().unwrap();
+
+            if let Some(admin_identity_file) = subcommand_matches.value_of("admin_identity") {
+                match AdminIdentity::from_file(admin_identity_file) {
+                    Ok(admin_identity) => node_config.admin_identity = Some(admin_identity),
+                    Err(e) => {
+                        error!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+
+            if let Some(key_share_file) = subcommand_matches.value_of("key_share") {
+                match KeyShare::from_file(key_share_file) {
+                    Ok(key_share) => node_config.key_share = Some(key_share),
+                    Err(e) => {
+                        error!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+
+            if let Some(rpc_tokens_file) = subcommand_matches.value_of("rpc_tokens") {
+                match RpcTokens::from_file(rpc_tokens_file) {
+                    Ok(rpc_tokens) => node_config.rpc_tokens = Some(rpc_tokens),
+                    Err(e) => {
+                        error!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+
+            if let (Some(certificate), Some(key)) = (subcommand_matches.value_of("rpc_tls_certificate"), subcommand_matches.value_of("rpc_tls_key")) {
+                let rpc_tls_config = TlsConfig {
+                    certificate: PathBuf::from(certificate),
+                    key: PathBuf::from(key),
+                    ca_file: None,
+                    // clients authenticate with their token
+                    authenticate_clients: false,
+                    allow_plain: false,
+                };
+                match TlsContext::new(&rpc_tls_config, &BTreeMap::new()) {
+                    Ok(rpc_tls) => node_config.rpc_tls = Some(Arc::new(rpc_tls)),
+                    Err(e) => {
+                        error!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+                if node_config.rpc_tokens.is_none() {
+                    warn!("RPC connections are encrypted, but any client may call all RPC methods. Pass --rpc-tokens to restrict them");
+                }
+            }
+
             let genesis = Genesis::new("genesis.json", "public_uciv.json", "public_key.json");
-            let mut node = Node::new(listen_address, rpc_listen_address, genesis);
+
+            if let (Some(certificate), Some(key)) = (subcommand_matches.value_of("tls_certificate"), subcommand_matches.value_of("tls_key")) {
+                let tls_config = TlsConfig {
+                    certificate: PathBuf::from(certificate),
+                    key: PathBuf::from(key),
+                    ca_file: subcommand_matches.value_of("tls_ca").map(PathBuf::from),
+                    authenticate_clients: true,
+                    allow_plain: subcommand_matches.is_present("tls_allow_plain"),
+                };
+                match TlsContext::new(&tls_config, &genesis.tls_certificates) {
+                    Ok(tls) => node_config.tls = Some(Arc::new(tls)),
+                    Err(e) => {
+                        error!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+
+            let version_check = VersionCheck::new(node_config.version_policy, &genesis.version, NODE_VERSION);
+            if let Some(incompatibility) = version_check.incompatibility {
+                error!("Refusing to join network: {}", incompatibility);
+                std::process::exit(1);
+            }
+
+            if has_sign && genesis.sealer_keys.is_empty() && !subcommand_matches.is_present("allow_unsigned_blocks") {
+                error!("Refusing to seal blocks as the genesis configuration declares no sealer_keys, hence any peer could seal blocks on behalf of a sealer. Declare the public key of each sealer, or pass --allow-unsigned-blocks on a test network");
+                std::process::exit(1);
+            }
+            if has_sign && !genesis.sealer_keys.is_empty() && node_config.node_key.is_none() && node_config.external_signer.is_none() {
+                error!("Refusing to seal blocks as the genesis configuration requires them to be signed, but neither --node-key, --keystore nor --external-signer is given");
+                std::process::exit(1);
+            }
+
+            #[cfg(feature = "libp2p")]
+            if TransportKind::Libp2p == node_config.transport {
+                let own_address = advertised_address.unwrap_or(listen_address);
+                let key_result = match subcommand_matches.value_of("libp2p_key") {
+                    Some(libp2p_key_file) => libp2p_transport::load_or_generate_key(libp2p_key_file),
+                    None => Err("The libp2p transport requires --libp2p-key".to_string())
+                };
+                match key_result.and_then(|keypair| libp2p_transport::check_addresses(&genesis.libp2p_addresses, own_address, &keypair).map(|_| keypair)) {
+                    Ok(keypair) => node_config.libp2p_key = Some(keypair),
+                    Err(e) => {
+                        error!("Refusing to join network: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+
+            let shutdown_deadline = Duration::from_secs(subcommand_matches.value_of("shutdown_deadline").map_or(10, |deadline| parse_option_value::<u64>(deadline, "shutdown deadline")));
+            shutdown::install_signal_handlers();
+
+            let enabled_options: Vec<&str> = ["sign", "allow_unsigned_blocks", "resume", "fast_sync", "relay", "upnp", "gossip_padding", "external_signer", "node_key", "keystore", "admin_identity", "key_share", "rpc_tokens", "rpc_tls_certificate", "tls_certificate", "tls_allow_plain", "retention_policy", "webhooks", "beacon"].iter()
+                .cloned()
+                .filter(|option| subcommand_matches.is_present(option))
+                .collect();
+            let mut startup_banner = StartupBanner::new(&genesis, listen_address, advertised_address.unwrap_or(listen_address), rpc_listen_address, subcommand_matches.value_of("data_dir"), &enabled_options);
+            startup_banner.dashboard_address = subcommand_matches.value_of("dashboard").map(|address| peers::resolve(address).expect("Invalid dashboard address"));
+            println!("{}", serde_json::to_string(&startup_banner).unwrap());
+
+            let mut node = Node::new(listen_address, rpc_listen_address, genesis, node_config);
+
+            let mut is_restored = false;
+            if let Some(data_directory) = subcommand_matches.value_of("data_dir") {
+                let is_fresh = subcommand_matches.is_present("fresh");
+                let persist_result = node.persist_blocks(Path::new(data_directory), is_fresh)
+                    .and_then(|has_restored| node.persist_bans(Path::new(data_directory)).map(|_| has_restored))
+                    .and_then(|has_restored| node.persist_key_generation(Path::new(data_directory)).map(|_| has_restored));
+                match persist_result {
+                    Ok(has_restored) => {
+                        is_restored = has_restored;
+                    }
+                    Err(e) if is_fresh => {
+                        error!("{}. Remove the data directory to start fresh, or omit --fresh to continue with the stored chain", e);
+                        std::process::exit(1);
+                    }
+                    Err(e) => {
+                        error!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
 
             node.listen();
             node.listen_rpc();
 
-            if has_ping {
+            if subcommand_matches.is_present("upnp") {
+                match node.map_port() {
+                    Ok(()) => {}
+                    Err(e) => {
+                        warn!("{}. Peers may not be able to connect to this node, consider passing --relay", e);
+                    }
+                }
+            }
+            if subcommand_matches.is_present("relay") {
+                node.receive_over_relays();
+            }
+
+            node.gossip();
+            node.announce_abandoned_branches();
+
+            match subcommand_matches.value_of("retention_policy") {
+                Some(retention_policy_file) => {
+                    // the data directory holds the blocks, which are never deleted
+                    let data_directory = subcommand_matches.value_of("data_dir");
+                    match RetentionPolicy::from_file(retention_policy_file).and_then(|retention_policy| protect_data_directory(retention_policy, data_directory)) {
+                        Ok(retention_policy) => node.enforce_retention_policy(retention_policy),
+                        Err(e) => {
+                            error!("{}", e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                None => {}
+            }
+
+            match subcommand_matches.value_of("webhooks") {
+                Some(webhook_config_file) => {
+                    let dispatch_result = WebhookConfig::from_file(webhook_config_file)
+                        .and_then(|webhook_config| node.dispatch_webhooks(webhook_config));
+                    match dispatch_result {
+                        Ok(()) => {}
+                        Err(e) => {
+                            error!("{}", e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                None => {}
+            }
+
+            match subcommand_matches.value_of("beacon") {
+                Some(beacon_config_file) => {
+                    let publish_result = BeaconConfig::from_file(beacon_config_file)
+                        .and_then(|beacon_config| node.publish_checkpoints(beacon_config));
+                    match publish_result {
+                        Ok(()) => {}
+                        Err(e) => {
+                            error!("{}", e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                None => {}
+            }
+
+            match subcommand_matches.value_of("dashboard") {
+                Some(dashboard_address) => {
+                    let dashboard_address: SocketAddr = peers::resolve(dashboard_address).expect("Invalid dashboard address");
+                    serve_dashboard(&node, dashboard_address, subcommand_matches.value_of("dashboard_compression").unwrap());
+                }
+                None => {}
+            }
+
+            node.handshake();
+            node.check_clock();
+            node.monitor_clock();
+            node.send_heartbeats();
+
+            if has_resume {
+                if subcommand_matches.is_present("fast_sync") {
+                    match node.sync_from_snapshot() {
+                        Ok(height) => info!("Fast sync: Adopted the snapshot of the peers at height {}", height),
+                        Err(e) => warn!("Fast sync: Synchronizing all blocks instead, as no snapshot was adopted: {}", e)
+                    }
+                }
+                info!("Resuming: Synchronizing chain with the peers defined in the genesis configuration");
                 node.request_chain_copy();
+            } else if is_restored {
+                info!("Continuing with the stored chain without synchronizing with the peers");
+            } else {
+                info!("Starting with a fresh chain containing only the genesis block");
             }
 
             if has_sign {
                 node.sign();
             }
+
+            shutdown::wait_until_requested();
+            node.shutdown(shutdown_deadline);
+            info!("Shut down");
+            std::process::exit(0);
+        }
+        Some("probe") => {
+            let subcommand_matches = matches.subcommand_matches("probe").unwrap();
+            let address: SocketAddr = peers::resolve(subcommand_matches.value_of("address").unwrap()).expect("Invalid address");
+
+            // compare with the own genesis configuration, if there is one
+            let has_genesis = Path::new("genesis.json").exists() && Path::new("public_key.json").exists() && Path::new("public_uciv.json").exists();
+            let genesis = if has_genesis {
+                Some(Genesis::new("genesis.json", "public_uciv.json", "public_key.json"))
+            } else {
+                None
+            };
+
+            let report = probe::probe(&address, genesis.as_ref());
+            for check in report.checks.iter() {
+                let outcome = match check.outcome {
+                    ProbeOutcome::Passed => " OK ",
+                    ProbeOutcome::Warning => "WARN",
+                    ProbeOutcome::Failed => "FAIL",
+                };
+                println!("[{}] {}: {}", outcome, check.name, check.message);
+            }
+
+            if report.is_compatible() {
+                println!("Peer at {} is compatible with this node", address);
+            } else {
+                println!("Peer at {} is not compatible with this node or unreachable", address);
+                std::process::exit(1);
+            }
+        }
+        Some("compare-tally") => {
+            let subcommand_matches = matches.subcommand_matches("compare-tally").unwrap();
+
+            let addresses: Vec<SocketAddr> = match subcommand_matches.values_of("address") {
+                Some(addresses) => addresses.map(|address| peers::resolve(address).expect("Invalid address")).collect(),
+                None => {
+                    if !Path::new("genesis.json").exists() || !Path::new("public_key.json").exists() || !Path::new("public_uciv.json").exists() {
+                        error!("No addresses given and no genesis configuration found in the current directory");
+                        std::process::exit(1);
+                    }
+
+                    Genesis::new("genesis.json", "public_uciv.json", "public_key.json").sealer
+                }
+            };
+
+            let comparison = tally_comparison::compare(&addresses);
+            for &(address, ref tally) in comparison.tallies.iter() {
+                match *tally {
+                    Ok(ref tally) => println!("{}: {} votes, decrypted sum {:?}, {} further questions", address, tally.total_votes, tally.decrypted_sum, tally.questions.len()),
+                    Err(ref e) => println!("{}: {}", address, e)
+                }
+            }
+
+            if comparison.is_consistent() {
+                println!("All {} nodes compute the same tally", addresses.len());
+            } else {
+                for address in comparison.get_deviating_nodes() {
+                    println!("Node at {} computes a tally differing from most nodes", address);
+                }
+                for address in comparison.get_unreachable_nodes() {
+                    println!("Node at {} did not report its tally", address);
+                }
+                std::process::exit(1);
+            }
+        }
+        Some("admin") => {
+            let subcommand_matches = matches.subcommand_matches("admin").unwrap();
+            let address: SocketAddr = peers::resolve(subcommand_matches.value_of("address").unwrap()).expect("Invalid address");
+
+            let identity = match AdminIdentity::from_file(subcommand_matches.value_of("identity").unwrap()) {
+                Ok(identity) => identity,
+                Err(e) => {
+                    error!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            let command = match (subcommand_matches.value_of("command").unwrap(), subcommand_matches.value_of("authority_keystore")) {
+                ("open-vote", Some(authority_keystore)) => Message::SignedOpenVote(sign_phase_change(&address, &identity, authority_keystore, TransactionType::VoteOpened)),
+                ("close-vote", Some(authority_keystore)) => Message::SignedCloseVote(sign_phase_change(&address, &identity, authority_keystore, TransactionType::VoteClosed)),
+                ("open-vote", None) => Message::OpenVote,
+                ("close-vote", None) => Message::CloseVote,
+                ("tally", _) => Message::RequestTally,
+                ("status", _) => Message::StatusRequest,
+                ("election-status", _) => Message::ElectionStatusRequest,
+                ("epoch-statistics", _) => Message::EpochStatisticsRequest,
+                ("mempool", _) => Message::MempoolRequest { include_payloads: false },
+                ("metrics", _) => Message::MetricsRequest,
+                ("peers", _) => Message::PeerStatusRequest,
+                _ => Message::SealerStatisticsRequest
+            };
+
+            match client::admin_request(&address, &identity, command) {
+                Ok(Message::OpenVoteAccept) => println!("Voting opened"),
+                Ok(Message::CloseVoteAccept) => println!("Voting closed"),
+                Ok(Message::TransactionReject(_, reason)) => {
+                    error!("Admin command rejected: {}", reason);
+                    std::process::exit(1);
+                }
+                Ok(response) => println!("{:?}", response),
+                Err(e) => {
+                    error!("Admin command failed: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some("join-request") => {
+            let subcommand_matches = matches.subcommand_matches("join-request").unwrap();
+            let rpc_address: SocketAddr = peers::resolve(subcommand_matches.value_of("rpc_address").unwrap()).expect("Invalid RPC address");
+
+            if subcommand_matches.is_present("list") {
+                match client::rpc_request(&rpc_address, subcommand_matches.value_of("rpc_token"), Message::PendingJoinRequests) {
+                    Ok(Message::PendingJoinRequestsResponse(join_requests)) => {
+                        for join_request in join_requests.iter() {
+                            println!("{} {} (node version {})", join_request.identifier, join_request.address, join_request.node_version);
+                        }
+                        println!("{} pending join request(s)", join_requests.len());
+                    }
+                    Ok(other) => {
+                        error!("Got unexpected response {:?}", other);
+                        std::process::exit(1);
+                    }
+                    Err(e) => {
+                        error!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+
+                return;
+            }
+
+            let address: SocketAddr = peers::resolve(subcommand_matches.value_of("address").unwrap()).expect("Invalid address");
+
+            // the candidate must run with the genesis configuration of the network to join
+            let has_genesis = Path::new("genesis.json").exists() && Path::new("public_key.json").exists() && Path::new("public_uciv.json").exists();
+            if !has_genesis {
+                error!("Genesis configuration of the network to join not found in the current directory");
+                std::process::exit(1);
+            }
+            let genesis = Genesis::new("genesis.json", "public_uciv.json", "public_key.json");
+
+            let identity = match CandidateIdentity::load_or_generate(subcommand_matches.value_of("identity").unwrap()) {
+                Ok(identity) => identity,
+                Err(e) => {
+                    error!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            let join_request = JoinRequest::new(&identity, address, genesis.get_configuration_hash(), NODE_VERSION);
+            match client::rpc_request(&rpc_address, subcommand_matches.value_of("rpc_token"), Message::JoinRequest(join_request)) {
+                Ok(Message::JoinRequestAccept(identifier)) => {
+                    println!("Submitted join request of candidate {}. Awaiting the decision of the authorities", identifier);
+                }
+                Ok(Message::JoinRequestReject(identifier, reason)) => {
+                    error!("Join request of candidate {} was rejected: {}", identifier, reason);
+                    std::process::exit(1);
+                }
+                Ok(other) => {
+                    error!("Got unexpected response {:?}", other);
+                    std::process::exit(1);
+                }
+                Err(e) => {
+                    error!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some("mempool") => {
+            let subcommand_matches = matches.subcommand_matches("mempool").unwrap();
+            let rpc_address: SocketAddr = peers::resolve(subcommand_matches.value_of("rpc_address").unwrap()).expect("Invalid RPC address");
+            let include_payloads = subcommand_matches.is_present("payloads");
+
+            match client::rpc_request(&rpc_address, subcommand_matches.value_of("rpc_token"), Message::MempoolRequest { include_payloads }) {
+                Ok(Message::MempoolResponse(entries)) => {
+                    for entry in entries.iter() {
+                        match entry.payload {
+                            Some(ref payload) => println!("{}", serde_json::to_string(payload).unwrap()),
+                            None => println!("{} {:?} arrived at {} ({} bytes)", entry.identifier, entry.trx_type, entry.arrived_at, entry.size)
+                        }
+                    }
+                    println!("{} pending transaction(s)", entries.len());
+                }
+                Ok(other) => {
+                    error!("Got unexpected response {:?}", other);
+                    std::process::exit(1);
+                }
+                Err(e) => {
+                    error!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some("turnout") => {
+            let subcommand_matches = matches.subcommand_matches("turnout").unwrap();
+            let rpc_address: SocketAddr = peers::resolve(subcommand_matches.value_of("rpc_address").unwrap()).expect("Invalid RPC address");
+
+            let subscription_result = client::subscribe_turnout(&rpc_address, subcommand_matches.value_of("rpc_token"), |turnout| {
+                println!("{}", serde_json::to_string(&turnout).unwrap());
+            });
+            match subscription_result {
+                Ok(()) => info!("Node at {} closed the subscription", rpc_address),
+                Err(e) => {
+                    error!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some("vote-sealer") => {
+            let subcommand_matches = matches.subcommand_matches("vote-sealer").unwrap();
+            let rpc_address: SocketAddr = peers::resolve(subcommand_matches.value_of("rpc_address").unwrap()).expect("Invalid RPC address");
+            let candidate: SocketAddr = peers::resolve(subcommand_matches.value_of("candidate").unwrap()).expect("Invalid address of the candidate");
+            let proposal = Message::SealerVoteProposal {
+                candidate,
+                authorize: !subcommand_matches.is_present("remove"),
+                public_key: subcommand_matches.value_of("public_key").map(|public_key| public_key.to_string()),
+            };
+
+            match client::rpc_request(&rpc_address, subcommand_matches.value_of("rpc_token"), proposal) {
+                Ok(Message::TransactionAccept(identifier)) => {
+                    println!("Vote cast in transaction {}, counting once the sealer seals its next block", identifier);
+                }
+                Ok(Message::SealerVoteReject(reason)) => {
+                    error!("Sealer refused to vote: {}", reason);
+                    std::process::exit(1);
+                }
+                Ok(other) => {
+                    error!("Got unexpected response {:?}", other);
+                    std::process::exit(1);
+                }
+                Err(e) => {
+                    error!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some("register-voter") => {
+            let subcommand_matches = matches.subcommand_matches("register-voter").unwrap();
+            let rpc_address: SocketAddr = peers::resolve(subcommand_matches.value_of("rpc_address").unwrap()).expect("Invalid RPC address");
+            let voter_idx: usize = parse_option_value(subcommand_matches.value_of("voter_idx").unwrap(), "voter index");
+
+            // the registration belongs to the election of the genesis configuration
+            let has_genesis = Path::new("genesis.json").exists() && Path::new("public_key.json").exists() && Path::new("public_uciv.json").exists();
+            if !has_genesis {
+                error!("Genesis configuration of the election not found in the current directory");
+                std::process::exit(1);
+            }
+            let genesis = Genesis::new("genesis.json", "public_uciv.json", "public_key.json");
+
+            let image_set: Option<ImageSet> = subcommand_matches.value_of("images").map(|images| {
+                let file = File::open(images).expect("Failed to open the image set");
+                serde_json::from_reader(file).expect("Failed to parse the image set")
+            });
+
+            let trx_type = match image_set {
+                Some(_) => TransactionType::VoterRegistered,
+                None => TransactionType::VoterRevoked
+            };
+
+            let unseal_result = Keystore::from_file(subcommand_matches.value_of("authority_keystore").unwrap())
+                .and_then(|keystore| keystore.unseal(&read_passphrase(false)));
+            let election_authority = match unseal_result {
+                Ok(election_authority) => election_authority,
+                Err(e) => {
+                    error!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            let since_the_epoch = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards");
+            let voter_registration = VoterRegistration {
+                election: genesis.get_configuration_hash(),
+                question_id: subcommand_matches.value_of("question").map(|question_id| question_id.to_string()),
+                voter_idx,
+                image_set,
+                credential: subcommand_matches.value_of("credential").unwrap().to_string(),
+                registered_at: since_the_epoch.as_secs() * 1000 + u64::from(since_the_epoch.subsec_nanos()) / 1_000_000,
+                signature: None,
+            };
+            let transaction = match voter_registration.sign(&trx_type, &election_authority) {
+                Ok(voter_registration) => Transaction::from_voter_registration(genesis.clique.get_hasher(), trx_type, voter_registration),
+                Err(e) => {
+                    error!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            match client::rpc_request(&rpc_address, subcommand_matches.value_of("rpc_token"), Message::TransactionPayload(transaction)) {
+                Ok(Message::TransactionAccept(identifier)) => {
+                    println!("Submitted transaction {}, taking effect once it is included in a block", identifier);
+                }
+                Ok(Message::TransactionReject(identifier, reason)) => {
+                    error!("Transaction {} was rejected: {}", identifier, reason);
+                    std::process::exit(1);
+                }
+                Ok(other) => {
+                    error!("Got unexpected response {:?}", other);
+                    std::process::exit(1);
+                }
+                Err(e) => {
+                    error!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some("purge") => {
+            let subcommand_matches = matches.subcommand_matches("purge").unwrap();
+
+            let retention_policy = match RetentionPolicy::from_file(subcommand_matches.value_of("policy").unwrap()).and_then(|retention_policy| protect_data_directory(retention_policy, subcommand_matches.value_of("data_dir"))) {
+                Ok(retention_policy) => retention_policy,
+                Err(e) => {
+                    error!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            match subcommand_matches.value_of("node") {
+                Some(rpc_address) => {
+                    let rpc_address: SocketAddr = peers::resolve(rpc_address).expect("Invalid RPC address");
+                    match client::rpc_request(&rpc_address, subcommand_matches.value_of("rpc_token"), Message::ElectionStatusRequest) {
+                        Ok(Message::ElectionStatusResponse(ref election_status)) if ElectionPhase::Closed == election_status.phase => {}
+                        Ok(Message::ElectionStatusResponse(election_status)) => {
+                            error!("Not purging any data as the voting is not closed but {:?}", election_status.phase);
+                            std::process::exit(1);
+                        }
+                        Ok(other) => {
+                            error!("Expected the election status but got {:?}", other);
+                            std::process::exit(1);
+                        }
+                        Err(e) => {
+                            error!("Failed to verify that the voting is closed: {}", e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                None => {
+                    warn!("Purging data without verifying that the voting is closed");
+                }
+            }
+
+            let report = retention_policy.purge();
+            for path in report.deleted_files.iter() {
+                info!("Securely deleted {:?}", path);
+            }
+            for &(ref path, ref reason) in report.failed_files.iter() {
+                error!("Failed to delete {:?}: {}", path, reason);
+            }
+
+            if !report.failed_files.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        Some("export-results") => {
+            let subcommand_matches = matches.subcommand_matches("export-results").unwrap();
+
+            let rpc_address: SocketAddr = peers::resolve(subcommand_matches.value_of("rpc_address").unwrap()).expect("Invalid RPC address");
+            let format: ExportFormat = subcommand_matches.value_of("format").unwrap().parse::<ExportFormat>().unwrap();
+            let election_identifier = subcommand_matches.value_of("election_id").unwrap().to_string();
+            let election_name = subcommand_matches.value_of("election_name").unwrap().to_string();
+
+            let private_key = match subcommand_matches.value_of("private_key") {
+                Some(private_key_file) => {
+                    if !Path::new(private_key_file).exists() {
+                        error!("Private key not found at '{}'", private_key_file);
+                        std::process::exit(1);
+                    }
+
+                    Some(PrivateKey::new(private_key_file))
+                }
+                None => None
+            };
+
+            let result = match export::fetch_result(&rpc_address, subcommand_matches.value_of("rpc_token"), election_identifier, election_name, private_key.as_ref()) {
+                Ok(result) => result,
+                Err(e) => {
+                    error!("Failed to fetch the result of the election: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            if result.yes_votes.is_none() {
+                warn!("No private key given. Only the turnout is exported");
+            }
+
+            let rendered_result = export::render(&result, format);
+            match subcommand_matches.value_of("output") {
+                Some(output_file) => {
+                    let mut file = File::create(output_file).unwrap();
+                    file.write_all(rendered_result.as_bytes()).unwrap();
+                    info!("Exported the result of the election to {}", output_file);
+                }
+                None => {
+                    println!("{}", rendered_result);
+                }
+            }
+        }
+        Some("audit-export") => {
+            let subcommand_matches = matches.subcommand_matches("audit-export").unwrap();
+            let data_directory = Path::new(subcommand_matches.value_of("data_dir").unwrap());
+
+            if !Path::new("genesis.json").exists() || !Path::new("public_key.json").exists() || !Path::new("public_uciv.json").exists() {
+                error!("No genesis configuration found in the current directory");
+                std::process::exit(1);
+            }
+            let genesis = Genesis::new("genesis.json", "public_uciv.json", "public_key.json");
+
+            let audit_export = match audit::create(data_directory, genesis) {
+                Ok(audit_export) => audit_export,
+                Err(e) => {
+                    error!("Failed to export the chain: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            if ElectionPhase::Closed != audit_export.phase {
+                warn!("The voting is not closed yet. The archive may be incomplete");
+            }
+
+            let rendered_export = serde_json::to_string_pretty(&audit_export).unwrap();
+            match subcommand_matches.value_of("output") {
+                Some(output_file) => {
+                    let mut file = File::create(output_file).unwrap();
+                    file.write_all(rendered_export.as_bytes()).unwrap();
+                    info!("Exported {} blocks up to {} at height {} to {}", audit_export.blocks.len(), audit_export.head_identifier, audit_export.height, output_file);
+                }
+                None => {
+                    println!("{}", rendered_export);
+                }
+            }
+        }
+        Some("audit") => {
+            let subcommand_matches = matches.subcommand_matches("audit").unwrap();
+
+            let audit_export = match audit::AuditExport::from_file(subcommand_matches.value_of("chain_export").unwrap()) {
+                Ok(audit_export) => audit_export,
+                Err(e) => {
+                    error!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            let report = audit::verify(&audit_export);
+            for check in report.checks.iter() {
+                match check.outcome {
+                    Ok(()) => println!("ok      {}", check.subject),
+                    Err(ref e) => println!("FAILED  {}: {}", check.subject, e)
+                }
+            }
+
+            let failures = report.get_failures().len();
+            println!("Verified {} blocks with {} transactions: {} of {} checks failed", report.verified_blocks, report.verified_transactions, failures, report.checks.len());
+            if failures > 0 {
+                std::process::exit(1);
+            }
+        }
+        Some("tally") => {
+            let subcommand_matches = matches.subcommand_matches("tally").unwrap();
+
+            let report_result = match subcommand_matches.value_of("chain_export") {
+                Some(chain_export_file) => audit::AuditExport::from_file(chain_export_file).and_then(|audit_export| {
+                    let audit_report = audit::verify(&audit_export);
+                    match audit_report.get_failures().first() {
+                        Some(failure) => Err(format!("The exported chain does not verify: {}: {}", failure.subject, failure.outcome.clone().unwrap_err())),
+                        None => Ok(tally::from_audit_export(&audit_export))
+                    }
+                }),
+                None => {
+                    let rpc_address: SocketAddr = peers::resolve(subcommand_matches.value_of("rpc_address").unwrap()).expect("Invalid RPC address");
+                    tally::fetch(&rpc_address, subcommand_matches.value_of("rpc_token"))
+                }
+            };
+
+            let report = match report_result {
+                Ok(report) => report,
+                Err(e) => {
+                    error!("Failed to obtain the tally: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            if ElectionPhase::Closed != report.phase {
+                warn!("The voting is not closed yet. The tally is provisional");
+            }
+
+            let format = match (subcommand_matches.value_of("format"), subcommand_matches.value_of("output")) {
+                (Some(format), _) => format.parse::<TallyFormat>().unwrap(),
+                (None, Some(output_file)) => TallyFormat::from_file_name(output_file).unwrap_or(TallyFormat::Csv),
+                (None, None) => TallyFormat::Csv
+            };
+
+            let rendered_report = tally::render(&report, format);
+            match subcommand_matches.value_of("output") {
+                Some(output_file) => {
+                    let mut file = File::create(output_file).unwrap();
+                    file.write_all(rendered_report.as_bytes()).unwrap();
+                    info!("Exported the tally of {} questions to {}", report.questions.len(), output_file);
+                }
+                None => {
+                    print!("{}", rendered_report);
+                }
+            }
+        }
+        Some("import-ballots") => {
+            let subcommand_matches = matches.subcommand_matches("import-ballots").unwrap();
+
+            let rpc_address: SocketAddr = peers::resolve(subcommand_matches.value_of("rpc_address").unwrap()).expect("Invalid RPC address");
+
+            let batch_key = match BatchKey::from_file(subcommand_matches.value_of("batch_key").unwrap()) {
+                Ok(batch_key) => batch_key,
+                Err(e) => {
+                    error!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            let batch = match BallotBatch::from_file(subcommand_matches.value_of("batch_file").unwrap()) {
+                Ok(batch) => batch,
+                Err(e) => {
+                    error!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            match batch.verify(&batch_key) {
+                Ok(()) => {}
+                Err(e) => {
+                    error!("Refusing to import the batch: {}", e);
+                    std::process::exit(1);
+                }
+            }
+
+            info!("Importing {} ballots collected by {}", batch.transactions.len(), batch.origin);
+            let report = import::import_batch(&rpc_address, subcommand_matches.value_of("rpc_token"), &batch);
+            info!("Imported {} of {} ballots", report.get_amount_imported(), report.ballots.len());
+
+            let rendered_report = serde_json::to_string_pretty(&report).unwrap();
+            match subcommand_matches.value_of("output") {
+                Some(output_file) => {
+                    let mut file = File::create(output_file).unwrap();
+                    file.write_all(rendered_report.as_bytes()).unwrap();
+                    info!("Wrote the import report to {}", output_file);
+                }
+                None => {
+                    println!("{}", rendered_report);
+                }
+            }
+
+            if report.get_amount_imported() < report.ballots.len() {
+                std::process::exit(1);
+            }
+        }
+        Some("seal-archive") => {
+            let subcommand_matches = matches.subcommand_matches("seal-archive").unwrap();
+            let data_directory = Path::new(subcommand_matches.value_of("data_dir").unwrap());
+
+            let is_verify = subcommand_matches.is_present("verify");
+            let seal_result = if is_verify {
+                archive::verify(data_directory)
+            } else {
+                let node_key_result = match (subcommand_matches.value_of("node_key"), subcommand_matches.value_of("keystore")) {
+                    (Some(node_key_file), _) => NodeKey::from_file(node_key_file),
+                    (None, Some(keystore_file)) => Keystore::from_file(keystore_file).and_then(|keystore| keystore.unseal(&read_passphrase(false))),
+                    (None, None) => Err("Either --node-key or --keystore is required to seal the archive".to_string())
+                };
+
+                node_key_result.and_then(|node_key| archive::seal(data_directory, &node_key))
+            };
+
+            match seal_result {
+                Ok(manifest) => {
+                    let outcome = if is_verify { "is intact" } else { "sealed" };
+                    println!("Archive of {} files at height {} with head {} {}, signed by {}", manifest.files.len(), manifest.height, manifest.head_identifier, outcome, manifest.public_key);
+                }
+                Err(e) => {
+                    error!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some("keygen") => {
+            let subcommand_matches = matches.subcommand_matches("keygen").unwrap();
+            let keystore_file = subcommand_matches.value_of("keystore").unwrap();
+
+            if Path::new(keystore_file).exists() {
+                error!("Refusing to overwrite the existing keystore at '{}'", keystore_file);
+                std::process::exit(1);
+            }
+
+            let algorithm: SignatureAlgorithm = subcommand_matches.value_of("algorithm").unwrap().parse().unwrap();
+            let node_key = NodeKey::generate_with(algorithm);
+            let keystore = Keystore::seal(&node_key, &read_passphrase(true));
+            match keystore.write_to_file(keystore_file) {
+                Ok(()) => info!("Wrote the keystore to {}", keystore_file),
+                Err(e) => {
+                    error!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+
+            match subcommand_matches.value_of("address") {
+                Some(address) => {
+                    let address: SocketAddr = peers::resolve(address).expect("Invalid address");
+                    println!("\"{}\": \"{}\"", address, keystore.public_key);
+                }
+                None => println!("{}", keystore.public_key)
+            }
+        }
+        Some("selftest") => {
+            let subcommand_matches = matches.subcommand_matches("selftest").unwrap();
+            let amount_of_votes = parse_option_value::<usize>(subcommand_matches.value_of("votes").unwrap(), "amount of votes");
+
+            let private_key_file = subcommand_matches.value_of("private_key").unwrap();
+            if !Path::new(private_key_file).exists() {
+                error!("Private key not found at '{}'", private_key_file);
+                std::process::exit(1);
+            }
+            let private_key = PrivateKey::new(private_key_file);
+
+            let pre_image_sets = match selftest::load_pre_image_sets(subcommand_matches.value_of("private_uciv").unwrap()) {
+                Ok(pre_image_sets) => pre_image_sets,
+                Err(e) => {
+                    error!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            match selftest::run("genesis.json", "public_uciv.json", "public_key.json", &private_key, &pre_image_sets, amount_of_votes) {
+                Ok(report) => {
+                    println!("Selftest passed: {} votes with {} in favour tallied by all nodes within {}s", report.total_votes, report.yes_votes, report.duration);
+                    std::process::exit(0);
+                }
+                Err(e) => {
+                    error!("Selftest failed: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some("install") => {
+            let subcommand_matches = matches.subcommand_matches("install").unwrap();
+            let service_manager = if subcommand_matches.is_present("launchd") { ServiceManager::Launchd } else { ServiceManager::Systemd };
+            let start_args: Vec<String> = subcommand_matches.values_of("start_args").map(|values| values.map(String::from).collect()).unwrap_or_default();
+
+            let current_dir = std::env::current_dir().expect("Failed to determine the current directory");
+            let binary = match subcommand_matches.value_of("binary") {
+                Some(binary) => current_dir.join(binary),
+                None => std::env::current_exe().expect("Failed to determine the path of this binary")
+            };
+            let working_dir = current_dir.join(subcommand_matches.value_of("working_dir").unwrap());
+            let spec = ServiceSpec {
+                binary,
+                working_dir: working_dir.canonicalize().unwrap_or(working_dir),
+                data_dir: current_dir.join(subcommand_matches.value_of("data_dir").unwrap()),
+                user: subcommand_matches.value_of("user").map(String::from),
+                start_args,
+            };
+
+            // the arguments must be accepted by start as they are written to the service definition
+            let start_command_line = vec!["node_rs".to_string()].into_iter().chain(spec.get_arguments());
+            if let Err(e) = app.get_matches_from_safe(start_command_line) {
+                error!("Invalid arguments of start: {}", e.message);
+                std::process::exit(1);
+            }
+
+            if let Err(e) = install::validate(&spec) {
+                error!("{}", e);
+                std::process::exit(1);
+            }
+
+            if spec.start_args.iter().any(|argument| argument == "--keystore") {
+                warn!("The service cannot prompt for the passphrase of the keystore, provide it in NODE_RS_KEYSTORE_PASSPHRASE");
+            }
+
+            if let Err(e) = install::prepare_data_dir(&spec) {
+                error!("{}", e);
+                std::process::exit(1);
+            }
+
+            let output = subcommand_matches.value_of("output").map(|output| current_dir.join(output)).unwrap_or_else(|| service_manager.get_default_path());
+            match install::write_service_definition(&output, &spec.render(service_manager), subcommand_matches.is_present("force")) {
+                Ok(()) => {
+                    info!("Wrote the service definition to {}", output.display());
+                    println!("{}", service_manager.get_activation_hint(&output));
+                }
+                Err(e) => {
+                    error!("{}", e);
+                    std::process::exit(1);
+                }
+            }
         }
         Some(&_) | None => {
             // an unspecified or no command was used
@@ -118,3 +2022,110 @@ fn main() {
         }
     }
 }
+
+/// Sign opening or closing the voting of the election of the node at the given address now,
+/// with the election authority in the given keystore. Exits if the details cannot be signed.
+fn sign_phase_change(address: &SocketAddr, identity: &AdminIdentity, authority_keystore: &str, trx_type: TransactionType) -> PhaseChange {
+    let unseal_result = Keystore::from_file(authority_keystore)
+        .and_then(|keystore| keystore.unseal(&read_passphrase(false)));
+    let election_authority = match unseal_result {
+        Ok(election_authority) => election_authority,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // the signature covers the election, i.e. the hash of the genesis configuration of the node
+    let election = match client::admin_request(address, identity, Message::StatusRequest) {
+        Ok(Message::StatusResponse(status)) => status.genesis_configuration_hash,
+        Ok(response) => {
+            error!("Expected the status of the node but got {:?}", response);
+            std::process::exit(1);
+        }
+        Err(e) => {
+            error!("Failed to request the election of the node: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let since_the_epoch = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards");
+    let changed_at = since_the_epoch.as_secs() * 1000 + u64::from(since_the_epoch.subsec_nanos()) / 1_000_000;
+    match PhaseChange::new(election, changed_at).sign(&trx_type, &election_authority) {
+        Ok(phase_change) => phase_change,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Read the passphrase of a keystore from the environment variable `NODE_RS_KEYSTORE_PASSPHRASE`,
+/// or prompt for it, repeating the prompt for confirmation if requested.
+fn read_passphrase(confirm: bool) -> String {
+    if let Ok(passphrase) = std::env::var("NODE_RS_KEYSTORE_PASSPHRASE") {
+        return passphrase;
+    }
+
+    let passphrase = rpassword::prompt_password("Keystore passphrase: ").expect("Failed to read the passphrase");
+    if confirm {
+        let confirmation = rpassword::prompt_password("Repeat the passphrase: ").expect("Failed to read the passphrase");
+        if passphrase != confirmation {
+            error!("The passphrases do not match");
+            std::process::exit(1);
+        }
+    }
+
+    passphrase
+}
+
+/// Parse the given amount of workers, which must be at least one.
+fn parse_worker_amount(amount: &str) -> Result<usize, String> {
+    match amount.parse::<usize>() {
+        Ok(0) => Err("The amount of workers must be at least 1".to_string()),
+        Ok(amount) => Ok(amount),
+        Err(e) => Err(format!("Invalid amount of workers {}: {}", amount, e))
+    }
+}
+
+/// Parse the given value of a command line option, exiting with a usage error if it is invalid.
+fn parse_option_value<T>(value: &str, name: &str) -> T
+    where
+        T: FromStr,
+        T::Err: Display
+{
+    match value.parse::<T>() {
+        Ok(parsed) => parsed,
+        Err(e) => clap::Error::with_description(&format!("Invalid {} {}: {}", name, value, e), clap::ErrorKind::InvalidValue).exit()
+    }
+}
+
+/// Reject an invalid amount of workers when the arguments are parsed.
+fn validate_worker_amount(amount: String) -> Result<(), String> {
+    parse_worker_amount(&amount).map(|_| ())
+}
+
+/// Protect the files in the given data directory, if any, from being deleted by the given retention policy.
+/// Returns an error if the policy covers any of them.
+fn protect_data_directory(mut retention_policy: RetentionPolicy, data_directory: Option<&str>) -> Result<RetentionPolicy, String> {
+    match data_directory {
+        Some(data_directory) => retention_policy.protect_directory(Path::new(data_directory)).map(|_| retention_policy),
+        None => Ok(retention_policy)
+    }
+}
+
+#[cfg(feature = "ui")]
+fn serve_dashboard(node: &Node, dashboard_address: SocketAddr, compression: &str) {
+    match node_rs::ui::compression::ContentEncoding::parse_list(compression) {
+        Ok(compression) => node.serve_dashboard(dashboard_address, compression),
+        Err(e) => {
+            error!("Invalid dashboard compression: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(not(feature = "ui"))]
+fn serve_dashboard(_node: &Node, _dashboard_address: SocketAddr, _compression: &str) {
+    error!("Cannot serve the dashboard as node_rs was built without the ui feature");
+}
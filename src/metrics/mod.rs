@@ -0,0 +1,228 @@
+use ::p2p::rate_limit::RateLimiter;
+use ::p2p::thread::ThreadPoolStats;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+/// The upper bounds in milliseconds of the buckets of duration histograms.
+/// Longer durations are counted in an additional overflow bucket.
+pub const DURATION_BUCKETS: [u64; 6] = [1, 10, 100, 1000, 10_000, 60_000];
+
+/// Returns the given duration in whole milliseconds.
+pub fn as_millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + u64::from(duration.subsec_nanos()) / 1_000_000
+}
+
+/// Counts durations in buckets bounded by `DURATION_BUCKETS`.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct Histogram {
+    /// The amount of durations per bucket, whereas the last bucket counts
+    /// the durations exceeding the greatest bound.
+    pub counts: Vec<u64>,
+    /// The sum of all durations in milliseconds.
+    pub sum_millis: u64,
+}
+
+impl Histogram {
+    /// Create a histogram without any durations.
+    pub fn new() -> Histogram {
+        Histogram {
+            counts: vec![0; DURATION_BUCKETS.len() + 1],
+            sum_millis: 0,
+        }
+    }
+
+    /// Count the given duration in its bucket.
+    pub fn record(&mut self, duration: Duration) {
+        let millis = as_millis(duration);
+        let bucket = DURATION_BUCKETS.iter()
+            .position(|bound| millis <= *bound)
+            .unwrap_or(DURATION_BUCKETS.len());
+
+        self.counts[bucket] += 1;
+        self.sum_millis += millis;
+    }
+
+    /// Returns the amount of recorded durations.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram::new()
+    }
+}
+
+/// The activity of the tasks of the same name executed by a thread pool.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct TaskMetrics {
+    /// The name of the tasks, e.g. `sign`.
+    pub name: String,
+    /// The amount of tasks currently executed by a worker.
+    pub running: usize,
+    /// The amount of tasks which finished, including the failed ones.
+    pub completed: u64,
+    /// The amount of tasks which panicked. The worker executing a panicking task keeps running.
+    #[serde(default)]
+    pub failed: u64,
+    /// The durations of the finished tasks.
+    pub durations: Histogram,
+}
+
+/// A snapshot of the workers and the queue of a thread pool.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct ThreadPoolMetrics {
+    /// The name of the pool, e.g. `connections`.
+    pub name: String,
+    /// The amount of workers of the pool.
+    pub workers: usize,
+    /// The amount of workers currently executing a task.
+    pub busy_workers: usize,
+    /// The amount of tasks waiting for a worker.
+    pub queue_depth: usize,
+    /// The time in milliseconds the oldest waiting task was submitted ago, zero if none is waiting.
+    pub oldest_queued_millis: u64,
+    /// The time tasks waited for a worker.
+    pub queue_waits: Histogram,
+    /// The activity per task name, ordered by name.
+    pub tasks: Vec<TaskMetrics>,
+}
+
+/// The blocks removed from the chain as their branches can no longer become canonical.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PruningMetrics {
+    /// The amount of passes which removed any blocks.
+    pub passes: u64,
+    /// The amount of removed blocks.
+    pub blocks: u64,
+    /// The size of the removed blocks in bytes, when encoded as JSON.
+    pub bytes: u64,
+}
+
+/// The traffic dropped by the rate limiter of a listener.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct RateLimitMetrics {
+    /// The name of the listener, e.g. `rpc`.
+    pub name: String,
+    /// The amount of connections dropped as their address had too many connections open.
+    pub dropped_connections: u64,
+    /// The amount of connections dropped as their address sent too many messages.
+    pub dropped_messages: u64,
+    /// The amount of addresses currently tracked.
+    pub tracked_sources: usize,
+}
+
+/// The metrics of a node, as returned by a `MetricsRequest`.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct Metrics {
+    /// The thread pools of the node, in the order they were started.
+    pub thread_pools: Vec<ThreadPoolMetrics>,
+    /// The stale branches pruned from the chain since the node started.
+    #[serde(default)]
+    pub pruning: PruningMetrics,
+    /// The traffic dropped by the rate limiters of the listeners.
+    #[serde(default)]
+    pub rate_limits: Vec<RateLimitMetrics>,
+}
+
+/// Collects the metrics of the components of a node, e.g. its thread pools.
+///
+/// Components are only referenced weakly, so that those which are dropped
+/// disappear from the metrics.
+pub struct MetricsRegistry {
+    thread_pools: Mutex<Vec<Weak<ThreadPoolStats>>>,
+    pruning: Mutex<PruningMetrics>,
+    rate_limiters: Mutex<Vec<Weak<RateLimiter>>>,
+}
+
+impl MetricsRegistry {
+    /// Create a registry without any components.
+    pub fn new() -> MetricsRegistry {
+        MetricsRegistry {
+            thread_pools: Mutex::new(vec![]),
+            pruning: Mutex::new(PruningMetrics::default()),
+            rate_limiters: Mutex::new(vec![]),
+        }
+    }
+
+    /// Include the given statistics of a thread pool in the metrics, as long as the pool exists.
+    pub fn register_thread_pool(&self, stats: &Arc<ThreadPoolStats>) {
+        let mut thread_pools = self.thread_pools.lock().unwrap();
+        thread_pools.retain(|thread_pool| thread_pool.upgrade().is_some());
+        thread_pools.push(Arc::downgrade(stats));
+    }
+
+    /// Include the traffic dropped by the given rate limiter in the metrics, as long as the limiter exists.
+    pub fn register_rate_limiter(&self, rate_limiter: &Arc<RateLimiter>) {
+        let mut rate_limiters = self.rate_limiters.lock().unwrap();
+        rate_limiters.retain(|rate_limiter| rate_limiter.upgrade().is_some());
+        rate_limiters.push(Arc::downgrade(rate_limiter));
+    }
+
+    /// Take note of a pass pruning the given amount of blocks of the given size in bytes from the chain.
+    pub fn record_pruning(&self, blocks: usize, bytes: usize) {
+        let mut pruning = self.pruning.lock().unwrap();
+        pruning.passes += 1;
+        pruning.blocks += blocks as u64;
+        pruning.bytes += bytes as u64;
+    }
+
+    /// Returns a snapshot of the metrics of all registered components which still exist.
+    pub fn snapshot(&self) -> Metrics {
+        Metrics {
+            thread_pools: self.thread_pools.lock().unwrap().iter()
+                .filter_map(|thread_pool| thread_pool.upgrade())
+                .map(|thread_pool| thread_pool.snapshot())
+                .collect(),
+            pruning: self.pruning.lock().unwrap().clone(),
+            rate_limits: self.rate_limiters.lock().unwrap().iter()
+                .filter_map(|rate_limiter| rate_limiter.upgrade())
+                .map(|rate_limiter| rate_limiter.snapshot())
+                .collect(),
+        }
+    }
+}
+
+impl Default for MetricsRegistry {
+    fn default() -> Self {
+        MetricsRegistry::new()
+    }
+}
+
+#[cfg(test)]
+mod metrics_test {
+
+    use super::*;
+    use ::p2p::thread::ThreadPool;
+    use std::thread;
+
+    #[test]
+    fn test_thread_pool_metrics() {
+        let mut histogram = Histogram::new();
+        histogram.record(Duration::from_millis(5));
+        histogram.record(Duration::from_secs(120));
+        assert_eq!(vec![0, 1, 0, 0, 0, 0, 1], histogram.counts);
+        assert_eq!(120_005, histogram.sum_millis);
+
+        let registry = MetricsRegistry::new();
+        let thread_pool = ThreadPool::new("test", 1);
+        registry.register_thread_pool(thread_pool.get_stats());
+
+        thread_pool.execute("first", || {});
+        thread_pool.execute("second", || {});
+        thread_pool.execute("second", || {});
+        while registry.snapshot().thread_pools[0].tasks.iter().map(|task| task.completed).sum::<u64>() < 3 {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let metrics = registry.snapshot();
+        assert_eq!("test", metrics.thread_pools[0].name);
+        assert_eq!(0, metrics.thread_pools[0].queue_depth);
+        assert_eq!(3, metrics.thread_pools[0].queue_waits.count());
+        assert_eq!(vec!["first".to_string(), "second".to_string()], metrics.thread_pools[0].tasks.iter().map(|task| task.name.clone()).collect::<Vec<String>>());
+
+        drop(thread_pool);
+        assert!(registry.snapshot().thread_pools.is_empty());
+    }
+}
@@ -0,0 +1,119 @@
+use rand::{thread_rng, Rng};
+use serde_json;
+use sha1::Sha1;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The length in bytes of the secret of a candidate identity.
+const SECRET_LENGTH: usize = 32;
+
+/// The identity of a node which wants to become a sealer.
+///
+/// Only the identifier, i.e. the SHA-1 digest of the secret, is published.
+/// The secret stays with the candidate, so that it is later able to prove
+/// that it issued the join request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CandidateIdentity {
+    /// The hex encoded SHA-1 digest of the secret.
+    pub identifier: String,
+    /// The hex encoded secret of the candidate.
+    pub secret: String,
+}
+
+impl CandidateIdentity {
+    /// Generate a new identity from a random secret.
+    pub fn generate() -> CandidateIdentity {
+        let mut secret = [0u8; SECRET_LENGTH];
+        thread_rng().fill_bytes(&mut secret);
+
+        CandidateIdentity {
+            identifier: Sha1::from(&secret[..]).hexdigest(),
+            secret: secret.iter().map(|byte| format!("{:02x}", byte)).collect(),
+        }
+    }
+
+    /// Read the identity from the given JSON file.
+    pub fn from_file(file_name: &str) -> Result<CandidateIdentity, String> {
+        let mut contents = String::new();
+        let read_result = File::open(file_name).and_then(|mut file| file.read_to_string(&mut contents));
+        match read_result {
+            Ok(_) => {}
+            Err(e) => {
+                return Err(format!("Failed to read candidate identity {:?}: {:?}", file_name, e));
+            }
+        }
+
+        match serde_json::from_str(&contents) {
+            Ok(identity) => Ok(identity),
+            Err(e) => Err(format!("Failed to parse candidate identity {:?}: {:?}", file_name, e))
+        }
+    }
+
+    /// Read the identity from the given JSON file,
+    /// or generate a new one and store it there if the file does not exist.
+    ///
+    /// A generated identity is never overwritten, so that the same
+    /// candidate is identified equally on subsequent join requests.
+    pub fn load_or_generate(file_name: &str) -> Result<CandidateIdentity, String> {
+        if Path::new(file_name).exists() {
+            return CandidateIdentity::from_file(file_name);
+        }
+
+        let identity = CandidateIdentity::generate();
+        let write_result = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(file_name)
+            .and_then(|mut file| file.write_all(serde_json::to_string_pretty(&identity).unwrap().as_bytes()));
+        match write_result {
+            Ok(()) => {
+                info!("Generated candidate identity {} at {:?}", identity.identifier, file_name);
+                Ok(identity)
+            }
+            Err(e) => Err(format!("Failed to write candidate identity {:?}: {:?}", file_name, e))
+        }
+    }
+}
+
+/// The payload a candidate submits to an existing node in order to
+/// join the network as sealer. Accepted join requests are kept by all
+/// nodes until the authorities decide on them.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct JoinRequest {
+    /// The identifier of the candidate identity.
+    pub identifier: String,
+    /// The address under which the candidate would be listed among the sealers.
+    pub address: SocketAddr,
+    /// The hash of the genesis configuration the candidate runs with.
+    pub genesis_configuration_hash: String,
+    /// The version of the node software of the candidate.
+    pub node_version: String,
+    /// The time of issuing the request in seconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+impl JoinRequest {
+    /// Create the join request of the given candidate.
+    ///
+    /// - `identity` The identity of the candidate.
+    /// - `address` The address under which the candidate would be listed among the sealers.
+    /// - `genesis_configuration_hash` The hash of the genesis configuration of the network to join.
+    /// - `node_version` The version of the node software of the candidate.
+    pub fn new(identity: &CandidateIdentity, address: SocketAddr, genesis_configuration_hash: String, node_version: &str) -> JoinRequest {
+        JoinRequest {
+            identifier: identity.identifier.clone(),
+            address,
+            genesis_configuration_hash,
+            node_version: node_version.to_string(),
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0),
+        }
+    }
+
+    /// Returns true, if the identifier is a well-formed SHA-1 digest.
+    pub fn has_valid_identifier(&self) -> bool {
+        self.identifier.len() == 40 && self.identifier.chars().all(|character| character.is_ascii_hexdigit())
+    }
+}
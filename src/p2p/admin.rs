@@ -0,0 +1,313 @@
+use ::mac::{constant_time_eq, decode_hex, encode_hex, hmac_sha1};
+use ::p2p::codec::Message;
+use rand::{thread_rng, Rng};
+use serde_json;
+use std::cmp;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The maximum amount of seconds the timestamp of an admin request
+/// may differ from the clock of the node. Older requests are rejected,
+/// so that nonces only need to be remembered for this amount of time.
+pub const MAX_REQUEST_AGE_SECS: u64 = 60;
+
+/// The capabilities an admin identity may be granted.
+/// Each admin command requires exactly one of them.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Clone, Copy, Debug)]
+pub enum AdminCapability {
+    /// Open and close the voting, and inspect the payloads of pending transactions.
+    ManageElection,
+    /// Request the final tally of the election.
+    ReadResults,
+    /// Request statistics, pending join requests, pending transactions and the status of the node and the election.
+    ReadStatus,
+    /// Vote on adding or removing sealers.
+    ManageSealers,
+}
+
+impl AdminCapability {
+    /// Returns the capability required to run the given command,
+    /// or None if the command cannot be run over the admin channel.
+    pub fn required_by(command: &Message) -> Option<AdminCapability> {
+        match *command {
+            Message::OpenVote | Message::CloseVote | Message::SignedOpenVote(_) | Message::SignedCloseVote(_) | Message::MempoolRequest { include_payloads: true } => Some(AdminCapability::ManageElection),
+            Message::RequestTally | Message::ElectionResultRequest => Some(AdminCapability::ReadResults),
+            Message::SealerVoteProposal { .. } => Some(AdminCapability::ManageSealers),
+            Message::SealerStatisticsRequest | Message::EpochStatisticsRequest | Message::ElectionStatusRequest | Message::StatusRequest | Message::MetricsRequest | Message::PeerStatusRequest | Message::PendingJoinRequests | Message::MempoolRequest { include_payloads: false } => Some(AdminCapability::ReadStatus),
+            _ => None
+        }
+    }
+}
+
+/// The designated admin identity, which is allowed to tunnel
+/// admin commands over the port used for communication between peers.
+///
+/// The secret is shared between the node and the admin and must
+/// never be distributed to other peers.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AdminIdentity {
+    /// The name of the admin.
+    pub name: String,
+    /// The hex encoded secret used to authenticate requests and responses.
+    pub secret: String,
+    /// The capabilities granted to the admin.
+    pub capabilities: Vec<AdminCapability>,
+}
+
+impl AdminIdentity {
+    /// Read the admin identity from the given JSON file.
+    pub fn from_file(file_name: &str) -> Result<AdminIdentity, String> {
+        let mut contents = String::new();
+        let read_result = File::open(file_name).and_then(|mut file| file.read_to_string(&mut contents));
+        match read_result {
+            Ok(_) => {}
+            Err(e) => {
+                return Err(format!("Failed to read admin identity {:?}: {:?}", file_name, e));
+            }
+        }
+
+        let identity: AdminIdentity = match serde_json::from_str(&contents) {
+            Ok(identity) => identity,
+            Err(e) => {
+                return Err(format!("Failed to parse admin identity {:?}: {:?}", file_name, e));
+            }
+        };
+
+        match decode_hex(&identity.secret) {
+            Some(ref secret) if secret.len() >= 16 => Ok(identity),
+            _ => Err(format!("The secret of admin identity {:?} must be hex encoded and at least 16 bytes long", file_name))
+        }
+    }
+
+    /// Returns true, if this identity is granted the given capability.
+    pub fn has_capability(&self, capability: AdminCapability) -> bool {
+        self.capabilities.contains(&capability)
+    }
+
+    fn authenticate(&self, payload: &str) -> String {
+        let secret = decode_hex(&self.secret).unwrap_or_default();
+        encode_hex(&hmac_sha1(&secret, payload.as_bytes()))
+    }
+}
+
+/// A command authenticated by the admin identity.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Clone, Debug)]
+pub struct AdminRequest {
+    /// The name of the admin identity issuing the command.
+    pub identity: String,
+    /// A random value which must not be reused, preventing replays of the request.
+    pub nonce: String,
+    /// The time of issuing the request in seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The command to run.
+    pub command: Box<Message>,
+    /// The hex encoded HMAC-SHA1 of all other fields.
+    pub mac: String,
+}
+
+impl AdminRequest {
+    /// Create a new request running the given command, authenticated by the given identity.
+    pub fn new(identity: &AdminIdentity, command: Message) -> AdminRequest {
+        let nonce: String = thread_rng().gen_ascii_chars().take(32).collect();
+
+        let mut request = AdminRequest {
+            identity: identity.name.clone(),
+            nonce,
+            timestamp: now_secs(),
+            command: Box::new(command),
+            mac: String::new(),
+        };
+        request.mac = identity.authenticate(&request.payload());
+
+        request
+    }
+
+    fn payload(&self) -> String {
+        format!("request\n{}\n{}\n{}\n{}", self.identity, self.nonce, self.timestamp, serde_json::to_string(&self.command).unwrap_or_default())
+    }
+}
+
+/// The response of the node to an `AdminRequest`, authenticated
+/// by the same identity so that the admin is able to verify its origin.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Clone, Debug)]
+pub struct AdminResponse {
+    /// The nonce of the corresponding request.
+    pub nonce: String,
+    /// The response to the command, or `Message::None` if it was rejected.
+    pub response: Box<Message>,
+    /// The reason why the request was rejected, if it was.
+    pub error: Option<String>,
+    /// The hex encoded HMAC-SHA1 of all other fields.
+    pub mac: String,
+}
+
+impl AdminResponse {
+    /// Verify the response to the given request.
+    ///
+    /// Returns the response to the command if the response is authentic
+    /// and the command succeeded, an error otherwise.
+    pub fn verify(self, identity: &AdminIdentity, request: &AdminRequest) -> Result<Message, String> {
+        if !self.nonce.eq(&request.nonce) {
+            return Err("Response does not belong to the request".to_string());
+        }
+
+        if !constant_time_eq(&identity.authenticate(&self.payload()), &self.mac) {
+            return Err("Response is not authentic".to_string());
+        }
+
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(*self.response)
+        }
+    }
+
+    fn payload(&self) -> String {
+        format!("response\n{}\n{}\n{}", self.nonce, serde_json::to_string(&self.response).unwrap_or_default(), self.error.clone().unwrap_or_default())
+    }
+}
+
+/// The node side of the admin channel, verifying incoming requests.
+#[derive(Clone, Debug)]
+pub struct AdminChannel {
+    /// The designated admin identity.
+    identity: AdminIdentity,
+    /// The nonces of accepted requests along with their timestamps.
+    /// Pruned once the requests would be rejected anyway due to their age.
+    seen_nonces: HashMap<String, u64>,
+}
+
+impl AdminChannel {
+    /// Create a new channel accepting requests of the given identity.
+    pub fn new(identity: AdminIdentity) -> AdminChannel {
+        AdminChannel {
+            identity,
+            seen_nonces: HashMap::new(),
+        }
+    }
+
+    /// Verify the given request and check the capabilities of its issuer.
+    ///
+    /// Returns the command to run if the request is authentic,
+    /// fresh and permitted, the reason of the rejection otherwise.
+    pub fn authorize(&mut self, request: &AdminRequest) -> Result<Message, String> {
+        if !self.identity.name.eq(&request.identity) {
+            return Err(format!("Unknown admin identity {:?}", request.identity));
+        }
+
+        if !constant_time_eq(&self.identity.authenticate(&request.payload()), &request.mac) {
+            return Err("Request is not authentic".to_string());
+        }
+
+        let now = now_secs();
+        let age = cmp::max(now, request.timestamp) - cmp::min(now, request.timestamp);
+        if age > MAX_REQUEST_AGE_SECS {
+            return Err(format!("Request timestamp differs by {} seconds from the clock of the node", age));
+        }
+
+        self.seen_nonces.retain(|_, timestamp| *timestamp + MAX_REQUEST_AGE_SECS >= now);
+        if self.seen_nonces.contains_key(&request.nonce) {
+            return Err("Request was already handled".to_string());
+        }
+
+        let capability = match AdminCapability::required_by(&request.command) {
+            Some(capability) => capability,
+            None => {
+                return Err(format!("{:?} is not an admin command", request.command));
+            }
+        };
+
+        if !self.identity.has_capability(capability) {
+            return Err(format!("Admin identity {:?} lacks the capability {:?}", self.identity.name, capability));
+        }
+
+        self.seen_nonces.insert(request.nonce.clone(), request.timestamp);
+
+        Ok(*request.command.clone())
+    }
+
+    /// Create the authenticated response to the request with the given nonce.
+    pub fn respond(&self, nonce: String, result: Result<Message, String>) -> AdminResponse {
+        let (response, error) = match result {
+            Ok(response) => (response, None),
+            Err(error) => (Message::None, Some(error))
+        };
+
+        let mut admin_response = AdminResponse {
+            nonce,
+            response: Box::new(response),
+            error,
+            mac: String::new(),
+        };
+        admin_response.mac = self.identity.authenticate(&admin_response.payload());
+
+        admin_response
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod admin_test {
+
+    use super::*;
+
+    fn identity(capabilities: Vec<AdminCapability>) -> AdminIdentity {
+        AdminIdentity {
+            name: "admin".to_string(),
+            secret: "000102030405060708090a0b0c0d0e0f".to_string(),
+            capabilities,
+        }
+    }
+
+    #[test]
+    fn test_authorize() {
+        let identity = identity(vec![AdminCapability::ManageElection]);
+        let mut channel = AdminChannel::new(identity.clone());
+
+        let request = AdminRequest::new(&identity, Message::OpenVote);
+        assert_eq!(Ok(Message::OpenVote), channel.authorize(&request));
+
+        // replays are rejected
+        assert!(channel.authorize(&request).is_err());
+
+        // missing capability
+        let request = AdminRequest::new(&identity, Message::RequestTally);
+        assert!(channel.authorize(&request).is_err());
+
+        // not an admin command
+        let request = AdminRequest::new(&identity, Message::ChainRequest);
+        assert!(channel.authorize(&request).is_err());
+
+        // tampered command
+        let mut request = AdminRequest::new(&identity, Message::OpenVote);
+        request.command = Box::new(Message::CloseVote);
+        assert!(channel.authorize(&request).is_err());
+
+        // foreign secret
+        let mut foreign_identity = identity.clone();
+        foreign_identity.secret = "0f0e0d0c0b0a09080706050403020100".to_string();
+        let request = AdminRequest::new(&foreign_identity, Message::OpenVote);
+        assert!(channel.authorize(&request).is_err());
+    }
+
+    #[test]
+    fn test_verify_response() {
+        let identity = identity(vec![AdminCapability::ManageElection]);
+        let channel = AdminChannel::new(identity.clone());
+
+        let request = AdminRequest::new(&identity, Message::OpenVote);
+        let response = channel.respond(request.nonce.clone(), Ok(Message::OpenVoteAccept));
+        assert_eq!(Ok(Message::OpenVoteAccept), response.clone().verify(&identity, &request));
+
+        let mut tampered_response = response.clone();
+        tampered_response.response = Box::new(Message::CloseVoteAccept);
+        assert!(tampered_response.verify(&identity, &request).is_err());
+
+        let other_request = AdminRequest::new(&identity, Message::OpenVote);
+        assert!(response.verify(&identity, &other_request).is_err());
+    }
+}
@@ -0,0 +1,53 @@
+use serde::Serialize;
+use serde_json;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The name of the file in the data directory to which notices for the operator are appended.
+pub const AUDIT_LOG_FILE_NAME: &str = "audit.jsonl";
+
+/// An append-only log of notices for the operator, e.g. about banned sources,
+/// each stored as a JSON encoded line.
+#[derive(Default, Clone)]
+pub struct AuditLog {
+    /// The path of the log, or None if notices are not persisted.
+    path: Option<PathBuf>,
+}
+
+impl AuditLog {
+    /// Create an audit log which discards all notices.
+    pub fn new() -> AuditLog {
+        AuditLog {
+            path: None,
+        }
+    }
+
+    /// Open the audit log in the given data directory, which must exist.
+    /// The file is created once the first notice is appended.
+    pub fn open(data_directory: &Path) -> AuditLog {
+        AuditLog {
+            path: Some(data_directory.join(AUDIT_LOG_FILE_NAME)),
+        }
+    }
+
+    /// Append the given notice to the log and sync it to disk, if the log is persisted.
+    pub fn append<T: Serialize>(&self, notice: &T) -> Result<(), String> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => {
+                return Ok(());
+            }
+        };
+
+        let mut line = serde_json::to_string(notice).unwrap();
+        line.push('\n');
+
+        let append_result = OpenOptions::new().create(true).append(true).open(path)
+            .and_then(|mut file| file.write_all(line.as_bytes()).and_then(|_| file.sync_data()));
+        match append_result {
+            Ok(()) => Ok(()),
+            Err(e) => Err(format!("Failed to append to audit log {:?}: {:?}", path, e))
+        }
+    }
+}
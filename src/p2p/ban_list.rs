@@ -0,0 +1,221 @@
+use ::p2p::audit::AuditLog;
+use serde_json;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+/// The amount of transactions failing proof verification after which their source is banned.
+pub const INVALID_PROOF_THRESHOLD: usize = 5;
+
+/// The duration in seconds of the first ban of a source.
+/// Each further ban of the same source lasts twice as long as the previous one.
+const INITIAL_BAN_DURATION: u64 = 10 * 60;
+
+/// The maximum duration of a ban in seconds.
+const MAX_BAN_DURATION: u64 = 7 * 24 * 60 * 60;
+
+/// The name of the file in the data directory holding the records of all offenders.
+pub const OFFENDERS_FILE_NAME: &str = "offenders.json";
+
+/// The misbehaviour of a single source submitting transactions.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone, Default)]
+pub struct OffenderRecord {
+    /// The amount of transactions failing proof verification since the source was banned the last time.
+    pub invalid_proofs: usize,
+    /// The amount of times the source was banned.
+    pub bans: u32,
+    /// The time the last ban ends at, in seconds since the epoch.
+    pub banned_until: Option<u64>,
+}
+
+/// Notifies the operator about a source which was banned.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct BanNotice {
+    /// The address of the banned source.
+    pub source: IpAddr,
+    /// The amount of times the source was banned, including this ban.
+    pub bans: u32,
+    /// The time the source was banned at, in seconds since the epoch.
+    pub banned_at: u64,
+    /// The time the ban ends at, in seconds since the epoch.
+    pub banned_until: u64,
+}
+
+/// Bans sources which repeatedly submit transactions failing proof verification.
+///
+/// A source is banned once `INVALID_PROOF_THRESHOLD` of its transactions failed,
+/// whereas each ban lasts twice as long as the previous one of the same source.
+/// If opened in a data directory, the records of all offenders survive restarts
+/// and each ban is appended to an audit log for the operator.
+#[derive(Default)]
+pub struct BanList {
+    /// The directory holding the records, or None if kept in memory only.
+    data_directory: Option<PathBuf>,
+    /// The log each ban is appended to.
+    audit_log: AuditLog,
+    offenders: HashMap<IpAddr, OffenderRecord>,
+}
+
+impl BanList {
+    /// Create a new ban list, which is kept in memory only.
+    pub fn new() -> BanList {
+        BanList {
+            data_directory: None,
+            audit_log: AuditLog::new(),
+            offenders: HashMap::new(),
+        }
+    }
+
+    /// Open the ban list persisted in the given data directory, creating the directory if necessary.
+    pub fn open(data_directory: &Path) -> Result<BanList, String> {
+        match fs::create_dir_all(data_directory) {
+            Ok(()) => {}
+            Err(e) => {
+                return Err(format!("Failed to create data directory {:?}: {:?}", data_directory, e));
+            }
+        }
+
+        let path = data_directory.join(OFFENDERS_FILE_NAME);
+        let mut contents = String::new();
+        let offenders = match File::open(&path).and_then(|mut file| file.read_to_string(&mut contents)) {
+            Ok(_) => match serde_json::from_str(&contents) {
+                Ok(offenders) => offenders,
+                Err(e) => {
+                    return Err(format!("Failed to parse offenders {:?}: {:?}", path, e));
+                }
+            },
+            Err(_) => HashMap::new()
+        };
+
+        Ok(BanList {
+            data_directory: Some(data_directory.to_path_buf()),
+            audit_log: AuditLog::open(data_directory),
+            offenders,
+        })
+    }
+
+    /// Returns true, if the given source is banned at the given time.
+    pub fn is_banned(&self, source: &IpAddr, now: u64) -> bool {
+        match self.offenders.get(source) {
+            Some(&OffenderRecord { banned_until: Some(banned_until), .. }) => now < banned_until,
+            _ => false
+        }
+    }
+
+    /// Returns the records of all offenders.
+    pub fn get_offenders(&self) -> &HashMap<IpAddr, OffenderRecord> {
+        &self.offenders
+    }
+
+    /// Take note of a transaction failing proof verification submitted by the given source.
+    ///
+    /// Returns a notice for the operator, if the source is banned as a consequence.
+    /// The notice is appended to the audit log as well.
+    pub fn record_invalid_proof(&mut self, source: IpAddr, now: u64) -> Option<BanNotice> {
+        let notice = {
+            let record = self.offenders.entry(source).or_default();
+            record.invalid_proofs += 1;
+
+            if record.invalid_proofs < INVALID_PROOF_THRESHOLD {
+                None
+            } else {
+                let duration = INITIAL_BAN_DURATION.checked_shl(record.bans).unwrap_or(MAX_BAN_DURATION).min(MAX_BAN_DURATION);
+                record.invalid_proofs = 0;
+                record.bans += 1;
+                record.banned_until = Some(now + duration);
+
+                Some(BanNotice {
+                    source,
+                    bans: record.bans,
+                    banned_at: now,
+                    banned_until: now + duration,
+                })
+            }
+        };
+
+        if let Some(ref notice) = notice {
+            warn!("Banned {} for {} seconds after {} transactions with invalid proofs. Banned {} times so far", notice.source, notice.banned_until - notice.banned_at, INVALID_PROOF_THRESHOLD, notice.bans);
+            match self.audit_log.append(notice) {
+                Ok(()) => {}
+                Err(e) => {
+                    error!("{}", e);
+                }
+            }
+        }
+
+        match self.persist() {
+            Ok(()) => {}
+            Err(e) => {
+                error!("{}", e);
+            }
+        }
+
+        notice
+    }
+
+    /// Write the records of all offenders to the data directory, if any.
+    fn persist(&self) -> Result<(), String> {
+        let data_directory = match self.data_directory {
+            Some(ref data_directory) => data_directory,
+            None => {
+                return Ok(());
+            }
+        };
+
+        // replace the file atomically, so that a crash does not lose any record
+        let path = data_directory.join(OFFENDERS_FILE_NAME);
+        let temporary_path = path.with_extension("tmp");
+        let contents = serde_json::to_string(&self.offenders).unwrap();
+        let write_result = File::create(&temporary_path)
+            .and_then(|mut file| file.write_all(contents.as_bytes()).and_then(|_| file.sync_all()))
+            .and_then(|_| fs::rename(&temporary_path, &path));
+
+        match write_result {
+            Ok(()) => Ok(()),
+            Err(e) => Err(format!("Failed to persist offenders to {:?}: {:?}", path, e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod ban_list_test {
+
+    use super::*;
+    use ::p2p::audit::AUDIT_LOG_FILE_NAME;
+    use std::env;
+
+    #[test]
+    fn test_ban_list() {
+        let data_directory = env::temp_dir().join(format!("node_rs_ban_list_test_{}", ::std::process::id()));
+        let _ = fs::remove_dir_all(&data_directory);
+        let source: IpAddr = "192.0.2.1".parse().unwrap();
+
+        let mut ban_list = BanList::open(&data_directory).unwrap();
+        for _ in 1..INVALID_PROOF_THRESHOLD {
+            assert_eq!(None, ban_list.record_invalid_proof(source, 100));
+        }
+        assert!(!ban_list.is_banned(&source, 100));
+
+        let notice = ban_list.record_invalid_proof(source, 100).unwrap();
+        assert_eq!(100 + INITIAL_BAN_DURATION, notice.banned_until);
+        assert!(ban_list.is_banned(&source, 100));
+        assert!(!ban_list.is_banned(&source, notice.banned_until));
+
+        // bans survive restarts and escalate
+        let mut reopened_ban_list = BanList::open(&data_directory).unwrap();
+        assert!(reopened_ban_list.is_banned(&source, 100));
+        for _ in 1..INVALID_PROOF_THRESHOLD {
+            reopened_ban_list.record_invalid_proof(source, 1000);
+        }
+        let notice = reopened_ban_list.record_invalid_proof(source, 1000).unwrap();
+        assert_eq!(2, notice.bans);
+        assert_eq!(1000 + 2 * INITIAL_BAN_DURATION, notice.banned_until);
+
+        let audit_log = fs::read_to_string(data_directory.join(AUDIT_LOG_FILE_NAME)).unwrap();
+        assert_eq!(2, audit_log.lines().count());
+
+        let _ = fs::remove_dir_all(&data_directory);
+    }
+}
@@ -0,0 +1,144 @@
+use ::chain::types::Height;
+use ::p2p::peers;
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// The maximum amount of peers block ranges are fetched from in parallel during catch-up.
+pub const MAX_PARALLEL_FETCHES: usize = 4;
+
+/// The time in milliseconds to wait for a peer to announce its head before catching up.
+pub const PROBE_TIMEOUT: u64 = 2000;
+
+/// The time in milliseconds after which a peer not sending a requested block range is considered stalled,
+/// upon which the range is requested from the next peer.
+pub const STALL_TIMEOUT: u64 = 10000;
+
+/// The round trip times of requests to other peers, by which the fastest ones are preferred,
+/// e.g. to fetch blocks from.
+///
+/// Round trip times are smoothed as in TCP, so that a single slow response does not
+/// demote a peer. Peers which failed to respond in time are ranked last until they respond again.
+#[derive(Debug, Default)]
+pub struct PeerLatencies {
+    /// The smoothed round trip time of each peer.
+    round_trip_times: HashMap<SocketAddr, Duration>,
+    /// The peers which did not respond in time since their last response.
+    stalled_peers: HashSet<SocketAddr>,
+}
+
+impl PeerLatencies {
+    pub fn new() -> PeerLatencies {
+        PeerLatencies::default()
+    }
+
+    /// Take note of a response of the given peer received after the given time.
+    pub fn record(&mut self, peer: &SocketAddr, round_trip_time: Duration) {
+        let peer = peers::normalize(peer);
+        let smoothed_round_trip_time = match self.round_trip_times.get(&peer) {
+            Some(previous) => *previous * 7 / 8 + round_trip_time / 8,
+            None => round_trip_time
+        };
+
+        self.round_trip_times.insert(peer, smoothed_round_trip_time);
+        self.stalled_peers.remove(&peer);
+    }
+
+    /// Take note that the given peer did not respond in time.
+    pub fn record_stall(&mut self, peer: &SocketAddr) {
+        self.stalled_peers.insert(peers::normalize(peer));
+    }
+
+    /// Returns the smoothed round trip time of the given peer, or None if it never responded.
+    pub fn get_round_trip_time(&self, peer: &SocketAddr) -> Option<Duration> {
+        self.round_trip_times.get(&peers::normalize(peer)).cloned()
+    }
+
+    /// Returns true, if the given peer did not respond in time since its last response.
+    pub fn is_stalled(&self, peer: &SocketAddr) -> bool {
+        self.stalled_peers.contains(&peers::normalize(peer))
+    }
+
+    /// Returns the given peers ordered by their round trip time, followed by the peers
+    /// which never responded and finally the stalled ones.
+    pub fn rank(&self, peers: &[SocketAddr]) -> Vec<SocketAddr> {
+        let mut ranked_peers = peers.to_vec();
+        ranked_peers.sort_by_key(|peer| (self.is_stalled(peer), self.get_round_trip_time(peer).is_none(), self.get_round_trip_time(peer)));
+
+        ranked_peers
+    }
+}
+
+/// Split the heights from `from_height` up to `to_height`, inclusive, into ranges of at most `range_size` blocks.
+pub fn stripe(from_height: Height, to_height: Height, range_size: usize) -> Vec<(Height, Height)> {
+    let mut ranges = vec![];
+    let mut range_start = from_height;
+    while range_start <= to_height {
+        let range_end = cmp::min(to_height, range_start + range_size - 1);
+        ranges.push((range_start, range_end));
+        range_start = range_end + 1;
+    }
+
+    ranges
+}
+
+/// Assign each of the given ranges to one of the given peers, ranked by their round trip time
+/// along with the heights of their heads.
+///
+/// The ranges are spread over the fastest `max_peers` peers whose heads reach the start of a range,
+/// in turn. Returns None for a range, if no peer reaches it.
+pub fn assign(ranges: &[(Height, Height)], ranked_heads: &[(SocketAddr, Height)], max_peers: usize) -> Vec<Option<SocketAddr>> {
+    ranges.iter()
+        .enumerate()
+        .map(|(index, &(from_height, _))| {
+            let candidates: Vec<SocketAddr> = ranked_heads.iter()
+                .filter(|&&(_, head_height)| head_height >= from_height)
+                .map(|&(peer, _)| peer)
+                .take(max_peers)
+                .collect();
+
+            if candidates.is_empty() {
+                None
+            } else {
+                Some(candidates[index % candidates.len()])
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod block_fetch_test {
+
+    use super::*;
+
+    #[test]
+    fn test_assign_striped_ranges() {
+        let fast: SocketAddr = "127.0.0.1:9001".parse().unwrap();
+        let slow: SocketAddr = "127.0.0.1:9002".parse().unwrap();
+        let stalled: SocketAddr = "127.0.0.1:9003".parse().unwrap();
+        let silent: SocketAddr = "127.0.0.1:9004".parse().unwrap();
+
+        let mut latencies = PeerLatencies::new();
+        latencies.record(&slow, Duration::from_millis(80));
+        latencies.record(&fast, Duration::from_millis(10));
+        latencies.record(&stalled, Duration::from_millis(1));
+        latencies.record_stall(&stalled);
+        assert_eq!(vec![fast, slow, silent, stalled], latencies.rank(&[stalled, silent, slow, fast]));
+
+        // a response clears the stall, its round trip time is smoothed
+        latencies.record(&stalled, Duration::from_millis(9));
+        assert_eq!(Some(Duration::from_millis(2)), latencies.get_round_trip_time(&stalled));
+        assert!(!latencies.is_stalled(&stalled));
+
+        let ranges = stripe(Height::new(5), Height::new(29), 10);
+        assert_eq!(vec![(Height::new(5), Height::new(14)), (Height::new(15), Height::new(24)), (Height::new(25), Height::new(29))], ranges);
+        assert!(stripe(Height::new(5), Height::new(4), 10).is_empty());
+
+        // the slow peer only reaches the first range, the fast one all of them
+        assert_eq!(vec![Some(fast), Some(fast), Some(fast)], assign(&ranges, &[(fast, Height::new(30)), (slow, Height::new(10))], 2));
+        assert_eq!(vec![Some(fast), Some(slow), Some(fast)], assign(&ranges, &[(fast, Height::new(30)), (slow, Height::new(30))], 2));
+        assert_eq!(vec![Some(fast), Some(fast), Some(fast)], assign(&ranges, &[(fast, Height::new(30)), (slow, Height::new(30))], 1));
+        assert_eq!(vec![Some(slow), None, None], assign(&ranges, &[(slow, Height::new(10))], 2));
+    }
+}
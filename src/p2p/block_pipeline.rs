@@ -0,0 +1,237 @@
+use ::chain::block::Block;
+use ::chain::transaction::Transaction;
+use ::p2p::codec::Message;
+use ::metrics::MetricsRegistry;
+use ::p2p::thread::ThreadPool;
+use ::protocol::block_validator::{BlockRejection, BlockValidator};
+use ::protocol::clique::CliqueProtocol;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+
+/// The amount of blocks each stage of the pipeline buffers
+/// before blocking the previous stage.
+pub const PIPELINE_CAPACITY: usize = 16;
+
+/// A block travelling through the pipeline, along with
+/// the channel on which its submitter awaits the response.
+struct PipelineJob {
+    block: Block,
+    response_sender: Sender<Message>,
+}
+
+/// Processes blocks received from other peers in stages connected by bounded channels:
+///
+/// 1. Verify the integrity of the block, i.e. its identifier and the voters of its sealer votes.
+/// 2. Verify the proofs of all contained transactions in parallel.
+/// 3. Verify the block against the chain, i.e. its parent, timestamp, sealer and signature, and insert it.
+///
+/// Only the last stage acquires the lock of the protocol, so that a burst of blocks,
+/// e.g. during catch-up, does not stall the RPC and sign loops. As each stage handles
+/// the blocks one after another, they are inserted in the order they were submitted.
+pub struct BlockPipeline {
+    /// The sending half of the first stage. Must be dropped before
+    /// the thread pool, as the stages only terminate once their input is closed.
+    sender: SyncSender<PipelineJob>,
+    /// The threads running the stages. Only held to wait for
+    /// the stages to terminate once the pipeline is dropped.
+    _stage_pool: ThreadPool,
+}
+
+impl BlockPipeline {
+    /// Create a new pipeline and start its stages.
+    ///
+    /// - `protocol` The protocol into whose chain verified blocks are inserted.
+    /// - `validator` The validator enforcing the rules of the genesis configuration.
+    /// - `verification_workers` The amount of workers verifying transaction proofs in parallel.
+    /// - `metrics` The registry the thread pools of the stages are reported in.
+    ///
+    /// # Panics
+    ///
+    /// The `new` function will panic if the amount of verification workers is zero,
+    /// rather than the stage verifying the proofs failing once it is started.
+    pub fn new(protocol: Arc<Mutex<CliqueProtocol>>, validator: BlockValidator, verification_workers: usize, metrics: &Arc<MetricsRegistry>) -> BlockPipeline {
+        assert!(verification_workers > 0, "The amount of verification workers must be at least 1");
+
+        let (sender, integrity_receiver) = mpsc::sync_channel(PIPELINE_CAPACITY);
+        let (proof_sender, proof_receiver) = mpsc::sync_channel(PIPELINE_CAPACITY);
+        let (insertion_sender, insertion_receiver) = mpsc::sync_channel(PIPELINE_CAPACITY);
+
+        let stage_pool = ThreadPool::new("block-pipeline", 3);
+        stage_pool.abort_on_poisoned("protocol", &protocol);
+        metrics.register_thread_pool(stage_pool.get_stats());
+        let validator = Arc::new(validator);
+
+        let cloned_validator = Arc::clone(&validator);
+        stage_pool.execute("verify-integrity", move || {
+            BlockPipeline::verify_integrity(integrity_receiver, proof_sender, &cloned_validator);
+        });
+        let cloned_metrics = Arc::clone(metrics);
+        stage_pool.execute("verify-proofs", move || {
+            BlockPipeline::verify_proofs(proof_receiver, insertion_sender, &validator, verification_workers, &cloned_metrics);
+        });
+        stage_pool.execute("insert", move || {
+            BlockPipeline::insert(insertion_receiver, &protocol);
+        });
+
+        BlockPipeline {
+            sender,
+            _stage_pool: stage_pool,
+        }
+    }
+
+    /// Submit the given block and wait until it is processed.
+    /// Blocks while the pipeline is full.
+    ///
+    /// Returns the response to the peer which sent the block.
+    pub fn submit(&self, block: Block) -> Message {
+        let (response_sender, response_receiver) = mpsc::channel();
+
+        let job = PipelineJob {
+            block,
+            response_sender,
+        };
+
+        match self.sender.send(job) {
+            Ok(()) => {}
+            Err(e) => {
+                warn!("Failed to submit block to pipeline: {:?}", e);
+                return Message::None;
+            }
+        }
+
+        response_receiver.recv().unwrap_or(Message::None)
+    }
+
+    fn verify_integrity(receiver: Receiver<PipelineJob>, sender: SyncSender<PipelineJob>, validator: &BlockValidator) {
+        for job in receiver.iter() {
+            if let Err(rejection) = validator.validate_integrity(&job.block) {
+                BlockPipeline::reject(job, rejection);
+                continue;
+            }
+
+            if sender.send(job).is_err() {
+                return;
+            }
+        }
+    }
+
+    fn verify_proofs(receiver: Receiver<PipelineJob>, sender: SyncSender<PipelineJob>, validator: &Arc<BlockValidator>, verification_workers: usize, metrics: &MetricsRegistry) {
+        let verification_pool = ThreadPool::new("verification", verification_workers);
+        metrics.register_thread_pool(verification_pool.get_stats());
+
+        for job in receiver.iter() {
+            let transactions = &job.block.data.transactions;
+            let chunk_size = transactions.len().div_ceil(verification_workers);
+
+            let (result_sender, result_receiver) = mpsc::channel();
+            let mut amount_chunks = 0;
+            for chunk in transactions.chunks(chunk_size.max(1)) {
+                let chunk: Vec<Transaction> = chunk.to_vec();
+                let cloned_validator = Arc::clone(validator);
+                let cloned_result_sender = result_sender.clone();
+                amount_chunks += 1;
+
+                verification_pool.execute("verify-transactions", move || {
+                    // the receiver is gone if another chunk was already found invalid
+                    let _ = cloned_result_sender.send(cloned_validator.validate_transactions(&chunk));
+                });
+            }
+
+            let rejection = result_receiver.iter()
+                .take(amount_chunks)
+                .find(|result| result.is_err());
+
+            match rejection {
+                Some(Err(rejection)) => {
+                    BlockPipeline::reject(job, rejection);
+                }
+                _ => {
+                    if sender.send(job).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    fn insert(receiver: Receiver<PipelineJob>, protocol: &Arc<Mutex<CliqueProtocol>>) {
+        for job in receiver.iter() {
+            let response = protocol.lock().unwrap().add_verified_block(job.block);
+
+            // the submitter may have given up waiting
+            let _ = job.response_sender.send(response);
+        }
+    }
+
+    fn reject(job: PipelineJob, rejection: BlockRejection) {
+        warn!("Rejecting block {:?}: {}", job.block.identifier, rejection);
+
+        let _ = job.response_sender.send(Message::BlockReject(job.block.identifier, rejection.to_string()));
+    }
+}
+
+#[cfg(test)]
+mod block_pipeline_test {
+
+    use super::*;
+    use ::chain::hasher::Hasher;
+    use ::config::genesis::Genesis;
+    use ::config::version::VersionPolicy;
+    use std::net::SocketAddr;
+
+    fn new_pipeline(verification_workers: usize) -> (Arc<Mutex<CliqueProtocol>>, BlockPipeline) {
+        let genesis = Genesis::new("genesis.json", "public_uciv.json", "public_key.json");
+        let validator = BlockValidator::new(&genesis);
+        let protocol = Arc::new(Mutex::new(CliqueProtocol::new(genesis.sealer[0], genesis, VersionPolicy::Ignore)));
+        let pipeline = BlockPipeline::new(Arc::clone(&protocol), validator, verification_workers, &Arc::new(MetricsRegistry::new()));
+
+        (protocol, pipeline)
+    }
+
+    fn new_block(parent: &Block, sealer: SocketAddr, transactions: Vec<Transaction>) -> Block {
+        let mut block = Block::new(Hasher::default(), parent.identifier.clone(), Some(sealer), transactions);
+        block.data.timestamp = parent.data.timestamp + 1;
+        block.identifier = block.get_header().unwrap().hash(Hasher::default());
+
+        block
+    }
+
+    #[test]
+    fn test_submit() {
+        let (protocol, pipeline) = new_pipeline(2);
+        let (genesis_block, sealers) = {
+            let locked_protocol = protocol.lock().unwrap();
+            (locked_protocol.get_head().unwrap().block, locked_protocol.get_sealer_set().get_sealers().to_vec())
+        };
+
+        let block = new_block(&genesis_block, sealers[0], vec![]);
+        assert_eq!(Message::BlockAccept, pipeline.submit(block.clone()));
+        assert_eq!(block, protocol.lock().unwrap().get_head().unwrap().block);
+        assert_eq!(Message::BlockDuplicated, pipeline.submit(block.clone()));
+
+        // each stage rejects the blocks violating its rules, in the order they were submitted
+        let mut tampered_block = new_block(&block, sealers[1], vec![]);
+        tampered_block.data.timestamp += 1;
+        match pipeline.submit(tampered_block.clone()) {
+            Message::BlockReject(identifier, reason) => {
+                assert_eq!(tampered_block.identifier, identifier);
+                assert_eq!(BlockRejection::InvalidIdentifier.to_string(), reason);
+            }
+            other => panic!("Expected the block to be rejected, got {:?}", other)
+        }
+
+        let unknown_sealer: SocketAddr = "127.0.0.1:9999".parse().unwrap();
+        let foreign_block = new_block(&block, unknown_sealer, vec![]);
+        match pipeline.submit(foreign_block) {
+            Message::BlockReject(_, reason) => assert_eq!(BlockRejection::UnknownSealer(Some(unknown_sealer)).to_string(), reason),
+            other => panic!("Expected the block to be rejected, got {:?}", other)
+        }
+        assert_eq!(block, protocol.lock().unwrap().get_head().unwrap().block);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least 1")]
+    fn test_new_rejects_zero_verification_workers() {
+        new_pipeline(0);
+    }
+}
@@ -0,0 +1,200 @@
+use ::p2p::admin::{AdminIdentity, AdminRequest};
+use ::p2p::codec::{Codec, JsonCodec, Message, MAX_MESSAGE_SIZE};
+use ::p2p::tls::{self, PeerStream};
+use ::protocol::clique::Turnout;
+use native_tls::TlsConnector;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::sync::Mutex;
+
+/// The connector RPC connections are encrypted with, if the nodes serve RPC over TLS.
+static RPC_TLS_CONNECTOR: Mutex<Option<TlsConnector>> = Mutex::new(None);
+
+/// Encrypt the connections of all subsequent RPC requests and subscriptions with TLS,
+/// trusting the certificates of the nodes issued by the given connector's authorities.
+/// Requests to the port used for communication between peers are not affected.
+pub fn use_rpc_tls(connector: TlsConnector) {
+    *RPC_TLS_CONNECTOR.lock().unwrap() = Some(connector);
+}
+
+/// The connector connections to the address of nodes for peers are encrypted with, if the nodes use TLS.
+static PEER_TLS_CONNECTOR: Mutex<Option<TlsConnector>> = Mutex::new(None);
+
+/// Encrypt the connections of all subsequent requests to the address of nodes for peers with TLS,
+/// e.g. of `probe` and `admin_request`, authenticating with the certificate presented by the given connector.
+/// Nodes using TLS refuse such requests without it, unless they allow plain connections.
+pub fn use_peer_tls(connector: TlsConnector) {
+    *PEER_TLS_CONNECTOR.lock().unwrap() = Some(connector);
+}
+
+/// Connect to the RPC address of a node, with TLS if configured by `use_rpc_tls`.
+fn connect_rpc(address: &SocketAddr) -> Result<PeerStream, String> {
+    let stream = match TcpStream::connect(address) {
+        Ok(stream) => stream,
+        Err(e) => {
+            return Err(format!("Failed to connect to {:?}: {:?}", address, e));
+        }
+    };
+
+    match *RPC_TLS_CONNECTOR.lock().unwrap() {
+        Some(ref connector) => tls::connect(connector, address, stream),
+        None => Ok(PeerStream::Plain(stream))
+    }
+}
+
+/// Send the given message to a node and wait for its response.
+///
+/// As for communication between nodes, the message is written
+/// to a new connection which is then closed for writing, signalling
+/// the end of the message to the node. The response is read until EOF.
+/// The connection is encrypted with TLS if configured by `use_peer_tls`.
+///
+/// - `address` The address of the node, usually its RPC listen address.
+/// - `message` The message to send.
+///
+/// Returns an error describing the failure if the node is not reachable
+/// or does not respond.
+pub fn request(address: &SocketAddr, message: Message) -> Result<Message, String> {
+    let stream = match TcpStream::connect(address) {
+        Ok(stream) => stream,
+        Err(e) => {
+            return Err(format!("Failed to connect to {:?}: {:?}", address, e));
+        }
+    };
+
+    let stream = match *PEER_TLS_CONNECTOR.lock().unwrap() {
+        Some(ref connector) => tls::connect(connector, address, stream),
+        None => Ok(PeerStream::Plain(stream))
+    };
+    match stream {
+        Ok(stream) => exchange(stream, address, message),
+        Err(e) => Err(e)
+    }
+}
+
+/// Write the given message to the given connection to the given address
+/// and read the response until the node closes the connection.
+fn exchange(mut stream: PeerStream, address: &SocketAddr, message: Message) -> Result<Message, String> {
+    let write_result = stream.write_all(&JsonCodec::encode(message))
+        .and_then(|_| stream.flush())
+        .and_then(|_| stream.shutdown(Shutdown::Write));
+    match write_result {
+        Ok(()) => {}
+        Err(e) => {
+            return Err(format!("Failed to send request to {:?}: {:?}", address, e));
+        }
+    }
+
+    let mut buffer = vec![];
+    let read_result = Read::by_ref(&mut stream).take(MAX_MESSAGE_SIZE as u64 + 1).read_to_end(&mut buffer);
+    match read_result {
+        Ok(0) => Err(format!("Got no response from {:?}", address)),
+        Ok(_) => Ok(JsonCodec::decode(&buffer)),
+        Err(e) => Err(format!("Failed to read response from {:?}: {:?}", address, e))
+    }
+}
+
+/// Send the given message to the RPC address of a node, presenting the given token if any.
+/// The connection is encrypted with TLS if configured by `use_rpc_tls`.
+///
+/// - `address` The RPC address of the node.
+/// - `rpc_token` The token granting the scope of the message. Required if the node restricts its RPC methods.
+/// - `message` The message to send.
+///
+/// Returns an error describing the failure if the node is not reachable,
+/// does not respond or refuses the token.
+pub fn rpc_request(address: &SocketAddr, rpc_token: Option<&str>, message: Message) -> Result<Message, String> {
+    let message = match rpc_token {
+        Some(rpc_token) => Message::RpcAuthorized(rpc_token.to_string(), Box::new(message)),
+        None => message
+    };
+
+    let response = match connect_rpc(address) {
+        Ok(stream) => exchange(stream, address, message),
+        Err(e) => Err(e)
+    };
+    match response {
+        Ok(Message::RpcUnauthorized(reason)) => Err(format!("Node at {:?} refused the request: {}", address, reason)),
+        result => result
+    }
+}
+
+/// Tunnel the given admin command over the peer protocol of a node.
+///
+/// - `address` The address on which the node listens for connections of other nodes.
+/// - `identity` The admin identity authenticating the command. Must be the one
+///   designated by the node.
+/// - `command` The command to run.
+///
+/// Returns the response to the command if it was run by the node, an error
+/// describing the failure or the reason of the rejection otherwise.
+pub fn admin_request(address: &SocketAddr, identity: &AdminIdentity, command: Message) -> Result<Message, String> {
+    let request = AdminRequest::new(identity, command);
+
+    match self::request(address, Message::AdminRequest(request.clone())) {
+        Ok(Message::AdminResponse(response)) => response.verify(identity, &request),
+        Ok(Message::None) => Err(format!("Node at {:?} does not accept admin commands", address)),
+        Ok(other) => Err(format!("Got unexpected response {:?} from {:?}", other, address)),
+        Err(e) => Err(e)
+    }
+}
+
+/// Subscribe to the turnout at the RPC address of a node, presenting the given token if any.
+///
+/// The node pushes the current turnout and then an update whenever the head of its
+/// canonical chain changes, each of which is passed to `on_update`.
+///
+/// - `address` The RPC address of the node.
+/// - `rpc_token` The token granting the `Read` scope. Required if the node restricts its RPC methods.
+/// - `on_update` Called with each turnout received.
+///
+/// Returns once the node closes the connection, or an error describing the failure.
+pub fn subscribe_turnout<F>(address: &SocketAddr, rpc_token: Option<&str>, mut on_update: F) -> Result<(), String> where F: FnMut(Turnout) {
+    let message = match rpc_token {
+        Some(rpc_token) => Message::RpcAuthorized(rpc_token.to_string(), Box::new(Message::TurnoutSubscribe)),
+        None => Message::TurnoutSubscribe
+    };
+
+    let mut stream = match connect_rpc(address) {
+        Ok(stream) => stream,
+        Err(e) => {
+            return Err(e);
+        }
+    };
+
+    let write_result = stream.write_all(&JsonCodec::encode(message))
+        .and_then(|_| stream.flush())
+        .and_then(|_| stream.shutdown(Shutdown::Write));
+    match write_result {
+        Ok(()) => {}
+        Err(e) => {
+            return Err(format!("Failed to send subscription to {:?}: {:?}", address, e));
+        }
+    }
+
+    let mut reader = BufReader::new(stream);
+    loop {
+        let mut line = String::new();
+        let read_result = Read::by_ref(&mut reader).take(MAX_MESSAGE_SIZE as u64 + 1).read_line(&mut line);
+        match read_result {
+            Ok(0) => {
+                return Ok(());
+            }
+            Ok(_) => {}
+            Err(e) => {
+                return Err(format!("Failed to read update from {:?}: {:?}", address, e));
+            }
+        }
+
+        // unauthorized subscriptions are answered with a single response without line break
+        match JsonCodec::decode(line.trim().as_bytes()) {
+            Message::TurnoutUpdate(turnout) => on_update(turnout),
+            Message::RpcUnauthorized(reason) => {
+                return Err(format!("Node at {:?} refused the subscription: {}", address, reason));
+            }
+            other => {
+                return Err(format!("Got unexpected response {:?} from {:?}", other, address));
+            }
+        }
+    }
+}
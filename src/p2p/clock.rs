@@ -0,0 +1,149 @@
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The port NTP servers listen on, if the configured server does not specify one.
+const NTP_PORT: u16 = 123;
+
+/// The size in bytes of an SNTP request and response.
+const NTP_PACKET_SIZE: usize = 48;
+
+/// The seconds between the start of the NTP era in 1900 and the Unix epoch.
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+
+/// Returns the local time in milliseconds since the epoch.
+pub fn now_millis() -> u64 {
+    let since_the_epoch = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards");
+
+    since_the_epoch.as_secs() * 1000 + u64::from(since_the_epoch.subsec_nanos()) / 1_000_000
+}
+
+/// Returns the amount of milliseconds the clock of a peer is ahead of the local one,
+/// negative if it is behind.
+///
+/// The peer is assumed to have read its clock halfway between sending the request
+/// and receiving the response, so that the round trip time cancels out.
+///
+/// - `sent_at` The local time the request was sent at.
+/// - `peer_time` The time the peer answered with.
+/// - `received_at` The local time the response was received at.
+pub fn estimate_offset(sent_at: u64, peer_time: u64, received_at: u64) -> i64 {
+    let local_time = sent_at + received_at.saturating_sub(sent_at) / 2;
+
+    peer_time as i64 - local_time as i64
+}
+
+/// Returns the median of the given offsets, or None if there are none.
+/// Robust against a minority of peers whose clocks are off as well.
+pub fn median_offset(offsets: &[i64]) -> Option<i64> {
+    if offsets.is_empty() {
+        return None;
+    }
+
+    let mut sorted_offsets = offsets.to_vec();
+    sorted_offsets.sort();
+    let middle = sorted_offsets.len() / 2;
+    if sorted_offsets.len().is_multiple_of(2) {
+        Some((sorted_offsets[middle - 1] + sorted_offsets[middle]) / 2)
+    } else {
+        Some(sorted_offsets[middle])
+    }
+}
+
+/// Query the given NTP server for its time using SNTP.
+///
+/// Returns the amount of milliseconds the clock of the server is ahead of the local one,
+/// or the reason why the server could not be queried.
+///
+/// - `server` The host name or address of the server, optionally followed by a port.
+/// - `timeout` The maximum time to wait for the answer of the server.
+pub fn query_ntp(server: &str, timeout: Duration) -> Result<i64, String> {
+    let server_address = match (server, NTP_PORT).to_socket_addrs().or_else(|_| server.to_socket_addrs()) {
+        Ok(mut addresses) => match addresses.next() {
+            Some(address) => address,
+            None => {
+                return Err(format!("NTP server {} does not resolve to any address", server));
+            }
+        },
+        Err(e) => {
+            return Err(format!("Failed to resolve NTP server {}: {}", server, e));
+        }
+    };
+
+    let bind_address = if server_address.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    let socket = match UdpSocket::bind(bind_address) {
+        Ok(socket) => socket,
+        Err(e) => {
+            return Err(format!("Failed to bind socket for querying NTP server {}: {}", server, e));
+        }
+    };
+    if let Err(e) = socket.set_read_timeout(Some(timeout)) {
+        return Err(format!("Failed to set timeout for querying NTP server {}: {}", server, e));
+    }
+
+    // leap indicator 0, version 3, mode 3 (client)
+    let mut request = [0u8; NTP_PACKET_SIZE];
+    request[0] = 0x1B;
+
+    let sent_at = now_millis();
+    if let Err(e) = socket.send_to(&request, server_address) {
+        return Err(format!("Failed to send request to NTP server {}: {}", server, e));
+    }
+
+    let mut response = [0u8; NTP_PACKET_SIZE];
+    let length = match socket.recv_from(&mut response) {
+        Ok((length, _)) => length,
+        Err(e) => {
+            return Err(format!("NTP server {} did not answer: {}", server, e));
+        }
+    };
+    let received_at = now_millis();
+
+    match parse_ntp_response(&response[..length]) {
+        Ok(server_time) => Ok(estimate_offset(sent_at, server_time, received_at)),
+        Err(e) => Err(format!("Invalid response of NTP server {}: {}", server, e))
+    }
+}
+
+/// Returns the transmit timestamp of the given SNTP response in milliseconds since the epoch.
+pub fn parse_ntp_response(response: &[u8]) -> Result<u64, String> {
+    if response.len() < NTP_PACKET_SIZE {
+        return Err(format!("Expected {} bytes but got {}", NTP_PACKET_SIZE, response.len()));
+    }
+    // mode 4 (server)
+    if response[0] & 0x07 != 4 {
+        return Err(format!("Unexpected mode {}", response[0] & 0x07));
+    }
+
+    let seconds = response[40..44].iter().fold(0u64, |value, byte| (value << 8) | u64::from(*byte));
+    let fraction = response[44..48].iter().fold(0u64, |value, byte| (value << 8) | u64::from(*byte));
+    if seconds < NTP_UNIX_OFFSET {
+        return Err(format!("Transmit timestamp {} lies before the epoch", seconds));
+    }
+
+    Ok((seconds - NTP_UNIX_OFFSET) * 1000 + ((fraction * 1000) >> 32))
+}
+
+#[cfg(test)]
+mod clock_test {
+
+    use super::*;
+
+    #[test]
+    fn test_offset() {
+        // the peer answered 100ms into a round trip of 200ms, 50ms ahead
+        assert_eq!(50, estimate_offset(1000, 1150, 1200));
+        assert_eq!(-1100, estimate_offset(1000, 0, 1200));
+
+        assert_eq!(None, median_offset(&[]));
+        assert_eq!(Some(20), median_offset(&[20, -5000, 30]));
+        assert_eq!(Some(25), median_offset(&[20, 30]));
+
+        let mut response = [0u8; NTP_PACKET_SIZE];
+        response[0] = 0x1C;
+        // one and a half seconds after the epoch
+        response[40..44].copy_from_slice(&[0x83, 0xAA, 0x7E, 0x81]);
+        response[44] = 0x80;
+        assert_eq!(Ok(1500), parse_ntp_response(&response));
+        assert!(parse_ntp_response(&response[..40]).is_err());
+    }
+}
@@ -1,9 +1,35 @@
 use ::chain::block::Block;
 use ::chain::chain::Chain;
-use ::chain::transaction::Transaction;
-use ::protocol::clique::Tally;
+use ::chain::epoch::EpochStatistics;
+use ::chain::snapshot::StateSnapshot;
+use ::chain::transaction::{PhaseChange, Transaction, TransactionQuery};
+use ::chain::types::{BlockHash, Height};
+use ::config::feature::Feature;
+use ::metrics::Metrics;
+use ::onboarding::JoinRequest;
+use ::p2p::admin::{AdminRequest, AdminResponse};
+use ::p2p::health::PeerHealth;
+use ::protocol::clique::{BlockRange, ChainChunk, ChainChunkToken, ElectionStatus, Handshake, InclusionProof, NodeStatus, SealerStatistics, Tally, TransactionInclusion, Turnout};
+use ::protocol::mempool::MempoolEntry;
+use ::protocol::receipt::ReceiptStatus;
+use ::threshold::dkg::DealtShare;
+use serde_cbor;
 use serde_json;
-use std::str;
+use std::collections::BTreeSet;
+use std::net::SocketAddr;
+use std::str::{self, FromStr};
+
+/// The maximum size in bytes of an encoded message.
+/// Larger messages are rejected without being decoded.
+pub const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// The targeted maximum size in bytes of the blocks contained in a single `ChainChunk`.
+/// Only exceeded by chunks consisting of a single block.
+pub const MAX_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// The bytes each message encoded with `CborCodec` starts with, i.e. the CBOR self-describe tag.
+/// Distinguishes such messages from JSON ones, which start with a quote or a brace.
+pub const CBOR_PREFIX: [u8; 3] = [0xd9, 0xd9, 0xf7];
 
 /// Messages used to communicate information between nodes.
 #[derive(Eq, PartialEq, Deserialize, Serialize, Clone, Debug)]
@@ -12,32 +38,161 @@ pub enum Message {
     Pong,
     TransactionPayload(Transaction),
     TransactionAccept(String),
-    BlockRequest(String),
+    /// The identifier of the rejected transaction along with the reason of the rejection.
+    TransactionReject(String, String),
+    /// Responds to a transaction which is already included in the canonical chain,
+    /// e.g. because a client submitted it again after a timeout.
+    TransactionIncluded(TransactionInclusion),
+    BlockRequest(BlockHash),
     BlockPayload(Block),
     BlockAccept,
     BlockDuplicated,
+    /// The identifier of the rejected block along with the reason of the rejection.
+    BlockReject(BlockHash, String),
     ChainRequest,
-    ChainResponse(Chain),
+    ChainResponse(Box<Chain>),
     ChainAccept,
+    ChainChunkRequest(Option<ChainChunkToken>),
+    ChainChunkResponse(ChainChunk),
+    /// Requests a summary of the canonical chain up to the finalized block at the given height,
+    /// or up to the finalized head if None, so that a node joins without fetching all blocks.
+    SnapshotRequest(Option<Height>),
+    /// None if the requested block is not finalized or not known to the sender.
+    SnapshotResponse(Option<StateSnapshot>),
+    /// Requests the blocks of the canonical chain between both heights, inclusive,
+    /// so that a node only needs to fetch the blocks it is missing.
+    BlockRangeRequest { from_height: Height, to_height: Height },
+    BlockRangeResponse(BlockRange),
+    /// Requests the block of the canonical chain at the given height, e.g. to page through its history.
+    BlockByNumberRequest(Height),
+    BlockByNumberResponse(Option<Block>),
+    /// Requests the blocks at the given height on all branches, including those of forks.
+    BlocksAtHeightRequest(Height),
+    BlocksAtHeightResponse(Vec<Block>),
+    /// Asks a peer for the head of its canonical chain, e.g. whether the in-turn leader already sealed a block.
+    HeadQuery,
+    /// The height and the identifier of the head of the canonical chain of the sender.
+    HeadQueryResponse(Height, BlockHash),
     OpenVote,
     OpenVoteAccept,
     CloseVote,
     CloseVoteAccept,
+    /// Opens the voting with the given details, signed by the election authority.
+    /// Required instead of `OpenVote` if the genesis configuration declares an election authority.
+    SignedOpenVote(PhaseChange),
+    /// Closes the voting with the given details, signed by the election authority.
+    /// Required instead of `CloseVote` if the genesis configuration declares an election authority.
+    SignedCloseVote(PhaseChange),
     RequestTally,
     RequestTallyPayload(Tally),
+    /// Subscribes an RPC client to the turnout. The node keeps the connection open
+    /// and pushes a `TurnoutUpdate` whenever the head of its canonical chain changes.
+    TurnoutSubscribe,
+    /// The amount of votes counted on the canonical chain, without their sum.
+    TurnoutUpdate(Turnout),
     FindTransaction(String),
     FindTransactionResponse(Option<Transaction>),
+    /// Requests the proof that the transaction with the given identifier is included in the canonical chain.
+    InclusionProofRequest(String),
+    InclusionProofResponse(Option<InclusionProof>),
+    /// Requests the state of the transaction with the given identifier, including whether
+    /// its block was reverted by a reorganization.
+    ReceiptRequest(String),
+    ReceiptResponse(ReceiptStatus),
+    QueryTransactions(TransactionQuery),
+    /// The selected transactions of the canonical chain, from the newest to the oldest.
+    QueryTransactionsResponse(Vec<Transaction>),
+    SealerStatisticsRequest,
+    SealerStatisticsResponse(SealerStatistics),
+    EpochStatisticsRequest,
+    /// The statistics of each epoch of the canonical chain, from the oldest to the newest.
+    EpochStatisticsResponse(Vec<EpochStatistics>),
+    ElectionStatusRequest,
+    ElectionStatusResponse(ElectionStatus),
+    StatusRequest,
+    StatusResponse(NodeStatus),
+    /// Requests the activity of the thread pools of the node.
+    MetricsRequest,
+    MetricsResponse(Metrics),
+    /// Sent to each peer on start to introduce the sender.
+    Handshake(Handshake),
+    HandshakeAccept,
+    /// Asks a peer for its local time, to detect whether the own clock is off.
+    TimeRequest,
+    /// The local time of the sender in milliseconds since the epoch.
+    TimeResponse(u64),
+    /// The hash of the genesis configuration of the responding node, which differs from the one of the handshake.
+    GenesisMismatch(String),
+    /// The optional behaviours of the election enabled by the responding node, which differ from the ones of the handshake.
+    FeatureMismatch(BTreeSet<Feature>),
+    /// A command of the admin identity, tunneled over the port used for communication between peers.
+    AdminRequest(AdminRequest),
+    AdminResponse(AdminResponse),
+    /// An RPC request along with the token authorizing it, required if the node restricts its RPC methods.
+    RpcAuthorized(String, Box<Message>),
+    /// The reason why an RPC request was refused due to a missing or insufficient token.
+    RpcUnauthorized(String),
+    JoinRequest(JoinRequest),
+    JoinRequestAccept(String),
+    /// The identifier of the rejected candidate along with the reason of the rejection.
+    JoinRequestReject(String, String),
+    PendingJoinRequests,
+    PendingJoinRequestsResponse(Vec<JoinRequest>),
+    /// Requests the summaries of the transactions not yet included in a block.
+    /// Their payloads are only included for admins.
+    MempoolRequest { include_payloads: bool },
+    MempoolResponse(Vec<MempoolEntry>),
+    /// The identifiers of the transactions pending at the sender, gossiped periodically to the sealers.
+    /// Answered with the identifiers among them which the receiver neither has pending nor included.
+    MempoolAnnouncement(Vec<String>),
+    MempoolAnnouncementResponse(Vec<String>),
+    /// The pending transactions requested by a `MempoolAnnouncementResponse`,
+    /// answered with the amount of transactions the receiver added to its mempool.
+    PendingTransactions(Vec<Transaction>),
+    PendingTransactionsAccept(usize),
+    /// Asks a sealer to vote on adding the candidate to the sealers, or on removing it.
+    /// The public key of the candidate is required to add it if blocks must be signed.
+    /// Accepted with the identifier of the resulting `TransactionType::SealerVote` transaction.
+    SealerVoteProposal { candidate: SocketAddr, authorize: bool, public_key: Option<String> },
+    /// The reason the sealer refuses to cast the proposed vote.
+    SealerVoteReject(String),
+    /// The share of the contribution of a dealer to the election key, sent by the dealer directly to its recipient.
+    /// Answered with `DkgShareAccept` once the recipient verified it against the commitments of the dealer on the chain.
+    DkgShare(DealtShare),
+    DkgShareAccept,
+    /// The reason the recipient refuses the share.
+    DkgShareReject(String),
+    /// The identifier of the tip of a branch which the sender abandoned by fork choice.
+    /// Receivers only take note of it if their own fork choice agrees.
+    BranchAbandoned(BlockHash),
+    /// Sent by a peer unable to accept incoming connections on a connection it keeps open,
+    /// along with the address it is known under. Messages to it are relayed over this connection.
+    RelayRegister(SocketAddr),
+    Padding(String),
     None,
+    /// Accepts a handshake announcing the codecs the sender decodes, along with the name of the codec
+    /// the responding node chose among them, see `negotiate`. Handshakes of nodes of earlier versions,
+    /// which do not announce any codecs, are accepted with `HandshakeAccept`.
+    HandshakeAcceptCodec(String),
+    /// Requests the connectivity of the node to each other sealer, answered only to RPC clients and admins.
+    PeerStatusRequest,
+    PeerStatusResponse(Vec<PeerHealth>),
+    /// Requests the election status along with the tally, both calculated on the same head of the canonical chain,
+    /// e.g. to export the result of the election. Requires the same scope as `RequestTally`.
+    ElectionResultRequest,
+    ElectionResultResponse(ElectionStatus, Tally),
 }
 
 
 /// A codec is able to encode as well decode a particular `Message`
-/// into a corresponding `String` representation.
+/// into a corresponding byte representation.
 pub trait Codec {
-    /// Encode the given message into a string.
-    fn encode(message: Message) -> String;
-    /// Decode the given string into a message.
-    fn decode(message: String) -> Message;
+    /// The name of the codec, which must be equal for nodes to understand each other.
+    fn name() -> &'static str;
+    /// Encode the given message into bytes.
+    fn encode(message: Message) -> Vec<u8>;
+    /// Decode the given bytes into a message.
+    fn decode(message: &[u8]) -> Message;
 }
 
 /// JsonCodec is able to encode and decode a particular
@@ -45,36 +200,206 @@ pub trait Codec {
 pub struct JsonCodec;
 
 impl Codec for JsonCodec {
+    fn name() -> &'static str {
+        "json"
+    }
+
     /// Encode the given message into a JSON string.
     /// If the message cannot be encoded, an empty string will be returned.
-    fn encode(message: Message) -> String {
-        let result = serde_json::to_string(&message);
+    fn encode(message: Message) -> Vec<u8> {
+        let result = serde_json::to_vec(&message);
 
         match result {
-            Ok(json_message) => {
-                return json_message;
-            }
+            Ok(json_message) => json_message,
             Err(e) => {
                 warn!("Failed to encode {:?} to json: {:?}. Will return an empty message", message, e);
-                return String::new();
+                vec![]
             }
         }
     }
 
     /// Decode the given JSON string into a corresponding Message.
-    /// Will return a `Message::None` if the string cannot be decoded.
-    fn decode(json_string: String) -> Message {
-        let result = serde_json::from_str(&json_string.as_str());
+    /// Will return a `Message::None` if the string cannot be decoded
+    /// or exceeds `MAX_MESSAGE_SIZE`.
+    fn decode(json_string: &[u8]) -> Message {
+        if json_string.len() > MAX_MESSAGE_SIZE {
+            warn!("Refusing to decode message of {} bytes exceeding the maximum of {} bytes.", json_string.len(), MAX_MESSAGE_SIZE);
+            return Message::None;
+        }
+
+        let result = serde_json::from_slice(json_string);
 
         match result {
             Ok(message) => {
                 return message;
             }
             Err(e) => {
-                warn!("Failed to decode {:?} into a message: {:?}. Will return error.", json_string, e);
+                warn!("Failed to decode {:?} into a message: {:?}. Will return error.", String::from_utf8_lossy(json_string), e);
                 return Message::None;
             }
         }
     }
 }
 
+/// CborCodec encodes a `Message` in the Concise Binary Object Representation (CBOR),
+/// prefixed by `CBOR_PREFIX`.
+///
+/// Considerably more compact than `JsonCodec` for chains and blocks, as the
+/// cryptographic proofs of votes are not encoded as decimal numbers.
+/// Only used towards peers which announced to decode it in their handshake, see `negotiate`.
+pub struct CborCodec;
+
+impl Codec for CborCodec {
+    fn name() -> &'static str {
+        "cbor"
+    }
+
+    /// Encode the given message into CBOR.
+    /// If the message cannot be encoded, an empty message will be returned.
+    fn encode(message: Message) -> Vec<u8> {
+        match serde_cbor::to_vec(&message) {
+            Ok(cbor_message) => {
+                let mut encoded_message = CBOR_PREFIX.to_vec();
+                encoded_message.extend_from_slice(&cbor_message);
+
+                encoded_message
+            }
+            Err(e) => {
+                warn!("Failed to encode {:?} to cbor: {:?}. Will return an empty message", message, e);
+                vec![]
+            }
+        }
+    }
+
+    /// Decode the given CBOR into a corresponding Message.
+    /// Will return a `Message::None` if the bytes cannot be decoded,
+    /// lack the prefix or exceed `MAX_MESSAGE_SIZE`.
+    fn decode(cbor_message: &[u8]) -> Message {
+        if cbor_message.len() > MAX_MESSAGE_SIZE {
+            warn!("Refusing to decode message of {} bytes exceeding the maximum of {} bytes.", cbor_message.len(), MAX_MESSAGE_SIZE);
+            return Message::None;
+        }
+
+        if !cbor_message.starts_with(&CBOR_PREFIX) {
+            warn!("Refusing to decode message lacking the cbor prefix");
+            return Message::None;
+        }
+
+        match serde_cbor::from_slice(&cbor_message[CBOR_PREFIX.len()..]) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("Failed to decode {} bytes of cbor into a message: {:?}", cbor_message.len(), e);
+                Message::None
+            }
+        }
+    }
+}
+
+/// The codecs messages are exchanged in.
+#[derive(Eq, PartialEq, Hash, Clone, Copy, Debug)]
+pub enum CodecKind {
+    /// See `JsonCodec`, which all nodes understand.
+    Json,
+    /// See `CborCodec`.
+    Cbor,
+}
+
+impl CodecKind {
+    /// Returns all codecs this node decodes, the most compact first.
+    pub fn supported() -> Vec<CodecKind> {
+        vec![CodecKind::Cbor, CodecKind::Json]
+    }
+
+    /// Returns the names of all codecs this node decodes, the most compact first.
+    pub fn supported_names() -> Vec<String> {
+        CodecKind::supported().iter().map(|codec| codec.name().to_string()).collect()
+    }
+
+    /// Returns the codec the given encoded message is encoded in.
+    pub fn detect(encoded_message: &[u8]) -> CodecKind {
+        if encoded_message.starts_with(&CBOR_PREFIX) {
+            CodecKind::Cbor
+        } else {
+            CodecKind::Json
+        }
+    }
+
+    /// Returns the name of the codec, see `Codec::name`.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            CodecKind::Json => JsonCodec::name(),
+            CodecKind::Cbor => CborCodec::name()
+        }
+    }
+
+    /// Encode the given message with this codec.
+    pub fn encode(&self, message: Message) -> Vec<u8> {
+        match *self {
+            CodecKind::Json => JsonCodec::encode(message),
+            CodecKind::Cbor => CborCodec::encode(message)
+        }
+    }
+
+    /// Decode the given message with this codec.
+    pub fn decode(&self, message: &[u8]) -> Message {
+        match *self {
+            CodecKind::Json => JsonCodec::decode(message),
+            CodecKind::Cbor => CborCodec::decode(message)
+        }
+    }
+}
+
+impl FromStr for CodecKind {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match CodecKind::supported().into_iter().find(|codec| codec.name() == name) {
+            Some(codec) => Ok(codec),
+            None => Err(format!("Unknown codec {:?}", name))
+        }
+    }
+}
+
+/// Decode the given message in the codec it is encoded in.
+/// Returns the message along with the codec, in which it should be responded to.
+pub fn decode(encoded_message: &[u8]) -> (Message, CodecKind) {
+    let codec = CodecKind::detect(encoded_message);
+
+    (codec.decode(encoded_message), codec)
+}
+
+/// Returns the most compact codec among the given names of the codecs a peer decodes,
+/// which this node decodes as well. Falls back to `CodecKind::Json`, which all nodes decode.
+pub fn negotiate(peer_codecs: &[String]) -> CodecKind {
+    CodecKind::supported().into_iter()
+        .find(|codec| peer_codecs.iter().any(|peer_codec| peer_codec == codec.name()))
+        .unwrap_or(CodecKind::Json)
+}
+
+#[cfg(test)]
+mod codec_test {
+
+    use super::*;
+    use ::chain::hasher::Hasher;
+    use ::chain::transaction::Transaction;
+
+    #[test]
+    fn test_negotiate_codec() {
+        let block = Block::new(Hasher::default(), BlockHash::default(), None, vec![Transaction::new_voting_opened(Hasher::default(), "genesis".to_string(), 0)]);
+        let message = Message::BlockPayload(block);
+
+        let json_message = JsonCodec::encode(message.clone());
+        let cbor_message = CborCodec::encode(message.clone());
+        assert!(cbor_message.len() < json_message.len());
+
+        // the codec of a message is detected by its prefix
+        assert_eq!((message.clone(), CodecKind::Json), decode(&json_message));
+        assert_eq!((message.clone(), CodecKind::Cbor), decode(&cbor_message));
+        assert_eq!(Message::None, CborCodec::decode(&json_message));
+
+        assert_eq!(CodecKind::Cbor, negotiate(&CodecKind::supported_names()));
+        assert_eq!(CodecKind::Json, negotiate(&["json".to_string()]));
+        assert_eq!(CodecKind::Json, negotiate(&["msgpack".to_string()]));
+        assert_eq!(Ok(CodecKind::Cbor), "cbor".parse::<CodecKind>());
+    }
+}
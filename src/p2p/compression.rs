@@ -0,0 +1,186 @@
+use ::p2p::codec::MAX_MESSAGE_SIZE;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use std::borrow::Cow;
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+/// Messages smaller than this amount of bytes are not compressed, unless configured otherwise.
+pub const DEFAULT_COMPRESSION_THRESHOLD: usize = 64 * 1024;
+
+/// The first byte of an enveloped message. Distinguishes enveloped messages
+/// from plain ones, which start with the prefix of a codec, a quote or a brace.
+///
+/// The marker is followed by the identifier of the compression of the message
+/// and the identifier of the compression the sender accepts for the response,
+/// each of which is zero for none.
+pub const ENVELOPE_MARKER: u8 = 0xfe;
+
+/// The length of the envelope preceding the message.
+const ENVELOPE_LENGTH: usize = 3;
+
+/// A compression along with the size in bytes from which messages are compressed with it.
+pub type CompressionSetting = (CompressionKind, usize);
+
+/// A compression of messages exchanged between nodes.
+#[derive(Eq, PartialEq, Hash, Debug, Clone, Copy)]
+pub enum CompressionKind {
+    Gzip,
+}
+
+impl CompressionKind {
+    /// Returns all compressions this node decompresses, in the order of preference.
+    pub fn supported() -> Vec<CompressionKind> {
+        vec![CompressionKind::Gzip]
+    }
+
+    /// Returns the names of all compressions this node decompresses, in the order of preference.
+    pub fn supported_names() -> Vec<String> {
+        CompressionKind::supported().iter().map(|compression| compression.name().to_string()).collect()
+    }
+
+    /// Returns the first own compression among the given names of the compressions
+    /// a peer decompresses, or None if messages to the peer must not be compressed.
+    pub fn negotiate(peer_compressions: &[String]) -> Option<CompressionKind> {
+        CompressionKind::supported().into_iter()
+            .find(|compression| peer_compressions.iter().any(|peer_compression| peer_compression == compression.name()))
+    }
+
+    /// Returns the name of the compression, as announced in handshakes.
+    pub fn name(self) -> &'static str {
+        match self {
+            CompressionKind::Gzip => "gzip",
+        }
+    }
+
+    /// Returns the identifier of the compression in the envelope of a message.
+    fn get_identifier(self) -> u8 {
+        match self {
+            CompressionKind::Gzip => 1,
+        }
+    }
+
+    fn from_identifier(identifier: u8) -> Result<Option<CompressionKind>, String> {
+        match identifier {
+            0 => Ok(None),
+            1 => Ok(Some(CompressionKind::Gzip)),
+            other => Err(format!("Unknown compression {} in envelope", other))
+        }
+    }
+
+    /// Compress the given encoded message.
+    pub fn compress(self, encoded_message: &[u8]) -> Vec<u8> {
+        match self {
+            CompressionKind::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(encoded_message).unwrap();
+
+                encoder.finish().unwrap()
+            }
+        }
+    }
+
+    /// Decompress the given compressed message.
+    /// Returns an error if it is corrupt or decompresses to more than `MAX_MESSAGE_SIZE` bytes.
+    pub fn decompress(self, compressed_message: &[u8]) -> Result<Vec<u8>, String> {
+        let mut encoded_message = vec![];
+        let read_result = match self {
+            CompressionKind::Gzip => GzDecoder::new(compressed_message).take(MAX_MESSAGE_SIZE as u64 + 1).read_to_end(&mut encoded_message)
+        };
+
+        match read_result {
+            Ok(length) if length > MAX_MESSAGE_SIZE => Err(format!("Compressed message exceeds the maximum of {} bytes", MAX_MESSAGE_SIZE)),
+            Ok(_) => Ok(encoded_message),
+            Err(e) => Err(format!("Failed to decompress message: {:?}", e))
+        }
+    }
+}
+
+impl FromStr for CompressionKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<CompressionKind, String> {
+        match s {
+            "gzip" => Ok(CompressionKind::Gzip),
+            _ => Err(format!("Unknown compression {:?}, expected gzip", s))
+        }
+    }
+}
+
+/// Envelope the given encoded message for a peer accepting the given compression.
+///
+/// The message is compressed if it has at least `threshold` bytes. Either way, the envelope
+/// announces that the response may be compressed with the given compression as well.
+pub fn seal(encoded_message: Vec<u8>, compression: CompressionKind, threshold: usize) -> Vec<u8> {
+    let (compressed_with, payload) = if encoded_message.len() >= threshold {
+        (compression.get_identifier(), compression.compress(&encoded_message))
+    } else {
+        (0, encoded_message)
+    };
+
+    let mut enveloped_message = Vec::with_capacity(ENVELOPE_LENGTH + payload.len());
+    enveloped_message.push(ENVELOPE_MARKER);
+    enveloped_message.push(compressed_with);
+    enveloped_message.push(compression.get_identifier());
+    enveloped_message.extend_from_slice(&payload);
+
+    enveloped_message
+}
+
+/// Remove the envelope from the given message, if any, and decompress it if necessary.
+///
+/// Returns the encoded message along with the compression the sender accepts for the response,
+/// or an error if the envelope is corrupt.
+pub fn open<'a>(message: &'a [u8]) -> Result<(Cow<'a, [u8]>, Option<CompressionKind>), String> {
+    if message.first() != Some(&ENVELOPE_MARKER) {
+        return Ok((Cow::Borrowed(message), None));
+    }
+
+    if message.len() < ENVELOPE_LENGTH {
+        return Err("Envelope of message is truncated".to_string());
+    }
+
+    let (compressed_with, accepted_compression) = match (CompressionKind::from_identifier(message[1]), CompressionKind::from_identifier(message[2])) {
+        (Ok(compressed_with), Ok(accepted_compression)) => (compressed_with, accepted_compression),
+        (Err(e), _) | (_, Err(e)) => {
+            return Err(e);
+        }
+    };
+
+    let payload = &message[ENVELOPE_LENGTH..];
+    match compressed_with {
+        Some(compression) => compression.decompress(payload).map(|encoded_message| (Cow::Owned(encoded_message), accepted_compression)),
+        None => Ok((Cow::Borrowed(payload), accepted_compression))
+    }
+}
+
+#[cfg(test)]
+mod compression_test {
+
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open() {
+        let large_message = vec![b'7'; 4096];
+
+        let sealed_message = seal(large_message.clone(), CompressionKind::Gzip, 1024);
+        assert!(sealed_message.len() < large_message.len());
+        assert_eq!((Cow::Owned(large_message.clone()), Some(CompressionKind::Gzip)), open(&sealed_message).unwrap());
+
+        // small messages are only enveloped to announce the accepted compression
+        let sealed_message = seal(b"\"Ping\"".to_vec(), CompressionKind::Gzip, 1024);
+        assert_eq!(3 + 6, sealed_message.len());
+        assert_eq!((Cow::Borrowed(&b"\"Ping\""[..]), Some(CompressionKind::Gzip)), open(&sealed_message).unwrap());
+
+        // plain messages are passed through
+        assert_eq!((Cow::Borrowed(&large_message[..]), None), open(&large_message).unwrap());
+
+        assert!(open(&[ENVELOPE_MARKER, 1]).is_err());
+        assert!(open(&[ENVELOPE_MARKER, 7, 0, 0]).is_err());
+        assert!(open(&[ENVELOPE_MARKER, 1, 0, 0]).is_err());
+
+        assert_eq!(Some(CompressionKind::Gzip), CompressionKind::negotiate(&["snappy".to_string(), "gzip".to_string()]));
+        assert_eq!(None, CompressionKind::negotiate(&[]));
+    }
+}
@@ -0,0 +1,74 @@
+use ::chain::types::{BlockHash, Height};
+use std::net::SocketAddr;
+
+/// How the head announced by another sealer relates to the own canonical chain.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum HeadRelation {
+    /// The announced head is on the own canonical chain, or the canonical chain of the sealer contains the own head.
+    Agrees,
+    /// The announced head is on another branch than the own head.
+    Conflicts,
+    /// The sealer did not announce its head in time.
+    Unknown,
+}
+
+/// Notifies the operator that this node stopped or resumed sealing blocks
+/// because its head is on a branch most sealers do not follow.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub enum DivergenceNotice {
+    /// Sealing was paused to resynchronize with the other sealers instead of deepening the fork.
+    SealingPaused {
+        /// The height of the own head.
+        head_height: Height,
+        /// The own head, on the minority branch.
+        head: BlockHash,
+        /// The sealers whose heads conflict with the own head.
+        conflicting_sealers: Vec<SocketAddr>,
+        /// The amount of all sealers, including this node.
+        sealers: usize,
+        /// The time sealing was paused at, in seconds since the epoch.
+        paused_at: u64,
+    },
+    /// Sealing was resumed as the own head no longer conflicts with most sealers.
+    SealingResumed {
+        /// The height of the own head.
+        head_height: Height,
+        /// The own head, e.g. on the branch of the other sealers after resynchronizing.
+        head: BlockHash,
+        /// The time sealing was resumed at, in seconds since the epoch.
+        resumed_at: u64,
+    },
+}
+
+/// Returns true, if the own head is on a minority branch, i.e. more than half of the
+/// given amount of sealers, including this node, announced conflicting heads.
+///
+/// Sealers not announcing their head in time do not count as conflicting, so that a node
+/// does not stop sealing only because other sealers are unreachable.
+pub fn is_minority_branch(relations: &[HeadRelation], sealers: usize) -> bool {
+    let conflicting_sealers = relations.iter()
+        .filter(|relation| **relation == HeadRelation::Conflicts)
+        .count();
+
+    conflicting_sealers * 2 > sealers
+}
+
+#[cfg(test)]
+mod divergence_test {
+
+    use super::*;
+
+    #[test]
+    fn test_is_minority_branch() {
+        // two of three sealers conflict
+        assert!(is_minority_branch(&[HeadRelation::Conflicts, HeadRelation::Conflicts], 3));
+        assert!(!is_minority_branch(&[HeadRelation::Conflicts, HeadRelation::Agrees], 3));
+
+        // unreachable sealers are no quorum
+        assert!(!is_minority_branch(&[HeadRelation::Unknown, HeadRelation::Unknown], 3));
+        assert!(!is_minority_branch(&[HeadRelation::Conflicts, HeadRelation::Unknown, HeadRelation::Unknown], 4));
+
+        // a sealer on its own cannot be outvoted
+        assert!(!is_minority_branch(&[], 1));
+    }
+}
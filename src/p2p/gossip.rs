@@ -0,0 +1,124 @@
+use ::p2p::codec::{Codec, JsonCodec, Message};
+use rand::{thread_rng, Rng};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// The length of padding messages used as long as no real message was gossiped yet.
+const DEFAULT_PADDING_LENGTH: usize = 1024;
+
+/// Collects messages which should be gossiped to other peers
+/// in order to broadcast them in batches.
+///
+/// Broadcasting messages received via RPC immediately allows an observer
+/// of the network traffic to correlate it with the activity of voters.
+/// Batches are therefore sent after a random delay, in random order and,
+/// if configured, padded with dummy messages.
+pub struct GossipQueue {
+    /// The upper bound of the random delay between two batches in milliseconds.
+    max_delay: u64,
+    /// Whether empty batches should be padded with a dummy message.
+    has_padding: bool,
+    /// Messages waiting to be gossiped along with the length
+    /// of the largest encoded message seen so far.
+    pending: Mutex<(Vec<Message>, usize)>,
+}
+
+impl GossipQueue {
+    /// Create a new queue.
+    ///
+    /// - `max_delay`: The upper bound of the random delay between two batches in milliseconds.
+    /// - `has_padding`: Whether empty batches should be padded with a dummy message.
+    pub fn new(max_delay: u64, has_padding: bool) -> GossipQueue {
+        GossipQueue {
+            max_delay,
+            has_padding,
+            pending: Mutex::new((vec![], DEFAULT_PADDING_LENGTH)),
+        }
+    }
+
+    /// Enqueue a message to be gossiped with the next batch.
+    pub fn push(&self, message: Message) {
+        let mut pending = self.pending.lock().unwrap();
+
+        let encoded_length = JsonCodec::encode(message.clone()).len();
+        if encoded_length > pending.1 {
+            pending.1 = encoded_length;
+        }
+
+        pending.0.push(message);
+    }
+
+    /// Returns true, if no message is waiting to be gossiped.
+    pub fn is_empty(&self) -> bool {
+        self.pending.lock().unwrap().0.is_empty()
+    }
+
+    /// Returns the random delay to wait before sending the next batch.
+    pub fn next_delay(&self) -> Duration {
+        let delay = thread_rng().gen_range(0, self.max_delay + 1);
+
+        Duration::from_millis(delay)
+    }
+
+    /// Take all pending messages in random order.
+    /// If there are none but padding is enabled, a single dummy message
+    /// of the size of the largest message seen so far is returned instead.
+    pub fn take_batch(&self) -> Vec<Message> {
+        let mut pending = self.pending.lock().unwrap();
+        let mut batch: Vec<Message> = pending.0.drain(..).collect();
+
+        let mut rng = thread_rng();
+        if batch.is_empty() && self.has_padding {
+            let padding: String = rng.gen_ascii_chars().take(pending.1).collect();
+            batch.push(Message::Padding(padding));
+        }
+
+        rng.shuffle(&mut batch);
+
+        batch
+    }
+}
+
+#[cfg(test)]
+mod gossip_test {
+    use super::*;
+
+    #[test]
+    fn test_take_batch() {
+        let queue = GossipQueue::new(10, false);
+        assert!(queue.take_batch().is_empty());
+        assert!(queue.next_delay() <= Duration::from_millis(10));
+
+        // all pending messages are taken at once
+        queue.push(Message::TransactionAccept("first".to_string()));
+        queue.push(Message::TransactionAccept("second".to_string()));
+        assert!(!queue.is_empty());
+        let mut batch = queue.take_batch();
+        batch.sort_by_key(|message| format!("{:?}", message));
+        assert_eq!(vec![Message::TransactionAccept("first".to_string()), Message::TransactionAccept("second".to_string())], batch);
+        assert!(queue.is_empty());
+        assert!(queue.take_batch().is_empty());
+    }
+
+    #[test]
+    fn test_take_padded_batch() {
+        let queue = GossipQueue::new(10, true);
+
+        // empty batches are padded, at first with the default length
+        match queue.take_batch().as_slice() {
+            [Message::Padding(padding)] => assert_eq!(DEFAULT_PADDING_LENGTH, padding.len()),
+            batch => panic!("Expected a single padding message, got {:?}", batch)
+        }
+
+        // batches holding messages are not padded
+        let message = Message::TransactionAccept("x".repeat(2 * DEFAULT_PADDING_LENGTH));
+        queue.push(message.clone());
+        assert_eq!(vec![message.clone()], queue.take_batch());
+
+        // later padding is sized like the largest message seen so far
+        match queue.take_batch().as_slice() {
+            [Message::Padding(padding)] => assert_eq!(JsonCodec::encode(message).len(), padding.len()),
+            batch => panic!("Expected a single padding message, got {:?}", batch)
+        }
+    }
+}
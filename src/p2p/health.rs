@@ -0,0 +1,148 @@
+use ::p2p::peers;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The connectivity of this node to a single peer, as observed by the requests sent to it.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct PeerHealth {
+    /// The address of the peer.
+    pub address: SocketAddr,
+    /// The seconds since the epoch at which the peer last answered a request.
+    /// None, if it never answered since this node started.
+    pub last_seen: Option<u64>,
+    /// The amount of requests to the peer failed since it last answered one.
+    pub consecutive_failures: u32,
+    /// The milliseconds it took to connect to the peer on its last answered request,
+    /// i.e. a single round trip independent of the size of the exchanged messages.
+    pub round_trip_millis: Option<u64>,
+    /// The reason the last failed request to the peer failed, if it did not answer since.
+    pub last_error: Option<String>,
+    /// True, if the heartbeats found the peer silent for more than a block period.
+    #[serde(default)]
+    pub silent: bool,
+}
+
+impl PeerHealth {
+    fn new(address: SocketAddr) -> PeerHealth {
+        PeerHealth {
+            address,
+            last_seen: None,
+            consecutive_failures: 0,
+            round_trip_millis: None,
+            last_error: None,
+            silent: false,
+        }
+    }
+
+    /// Returns true, if the peer did not answer a request for more than the given amount of seconds
+    /// before the given time, counting from the given time at which contacting it started if it never answered.
+    pub fn is_silent(&self, started_at: u64, now: u64, max_silence: u64) -> bool {
+        now.saturating_sub(self.last_seen.unwrap_or(started_at)) > max_silence
+    }
+}
+
+/// Tracks the connectivity of this node to each peer it sends requests to,
+/// so that operators can see which sealers are actually reachable.
+pub struct PeerHealthTracker {
+    peers: Mutex<BTreeMap<SocketAddr, PeerHealth>>,
+}
+
+impl PeerHealthTracker {
+    pub fn new() -> PeerHealthTracker {
+        PeerHealthTracker {
+            peers: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Record that the given peer answered a request, after connecting to it took the given time.
+    pub fn record_success(&self, peer_addr: &SocketAddr, round_trip: Duration) {
+        let since_the_epoch = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards");
+        let address = peers::normalize(peer_addr);
+        let mut peers = self.peers.lock().unwrap();
+        let health = peers.entry(address).or_insert_with(|| PeerHealth::new(address));
+        health.last_seen = Some(since_the_epoch.as_secs());
+        health.consecutive_failures = 0;
+        health.round_trip_millis = Some(round_trip.as_millis() as u64);
+        health.last_error = None;
+    }
+
+    /// Record that a request to the given peer failed for the given reason.
+    pub fn record_failure(&self, peer_addr: &SocketAddr, error: &str) {
+        let address = peers::normalize(peer_addr);
+        let mut peers = self.peers.lock().unwrap();
+        let health = peers.entry(address).or_insert_with(|| PeerHealth::new(address));
+        health.consecutive_failures = health.consecutive_failures.saturating_add(1);
+        health.last_error = Some(error.to_string());
+    }
+
+    /// Mark the given peer as silent or not, e.g. by the heartbeats.
+    /// Returns true, if this changed the mark of the peer.
+    pub fn mark_silent(&self, peer_addr: &SocketAddr, silent: bool) -> bool {
+        let address = peers::normalize(peer_addr);
+        let mut peers = self.peers.lock().unwrap();
+        let health = peers.entry(address).or_insert_with(|| PeerHealth::new(address));
+        let is_changed = health.silent != silent;
+        health.silent = silent;
+
+        is_changed
+    }
+
+    /// Returns the connectivity to each of the given peers, in the given order.
+    /// Peers no request was sent to yet are reported as never seen.
+    pub fn get_status(&self, peer_addrs: &[SocketAddr]) -> Vec<PeerHealth> {
+        let peers = self.peers.lock().unwrap();
+        peer_addrs.iter()
+            .map(|peer_addr| {
+                let address = peers::normalize(peer_addr);
+                peers.get(&address).cloned().unwrap_or_else(|| PeerHealth::new(address))
+            })
+            .collect()
+    }
+}
+
+impl Default for PeerHealthTracker {
+    fn default() -> Self {
+        PeerHealthTracker::new()
+    }
+}
+
+#[cfg(test)]
+mod health_test {
+
+    use super::*;
+
+    #[test]
+    fn test_peer_health() {
+        let tracker = PeerHealthTracker::new();
+        let reachable: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let unreachable: SocketAddr = "127.0.0.1:9001".parse().unwrap();
+        let unknown: SocketAddr = "127.0.0.1:9002".parse().unwrap();
+
+        tracker.record_failure(&reachable, "Connection refused");
+        tracker.record_success(&reachable, Duration::from_millis(12));
+        tracker.record_failure(&unreachable, "Connection refused");
+        tracker.record_failure(&unreachable, "Connection timed out");
+
+        let status = tracker.get_status(&[reachable, unreachable, unknown]);
+        assert_eq!(3, status.len());
+
+        assert!(status[0].last_seen.is_some());
+        assert_eq!(0, status[0].consecutive_failures);
+        assert_eq!(Some(12), status[0].round_trip_millis);
+        assert_eq!(None, status[0].last_error);
+
+        assert_eq!(None, status[1].last_seen);
+        assert_eq!(2, status[1].consecutive_failures);
+        assert_eq!(Some("Connection timed out".to_string()), status[1].last_error);
+
+        assert_eq!(PeerHealth::new(unknown), status[2]);
+
+        let last_seen = status[0].last_seen.unwrap();
+        assert!(!status[0].is_silent(0, last_seen + 5, 5));
+        assert!(status[0].is_silent(0, last_seen + 6, 5));
+        assert!(!status[2].is_silent(100, 105, 5));
+        assert!(status[2].is_silent(100, 106, 5));
+    }
+}
@@ -0,0 +1,612 @@
+use ::mac::{decode_hex, encode_hex};
+use ::p2p::codec::{CodecKind, Message, MAX_MESSAGE_SIZE};
+use ::p2p::compression::CompressionKind;
+use ::p2p::health::PeerHealthTracker;
+use ::p2p::peers;
+use ::p2p::transport::{IncomingMessage, Responder, Transport};
+use futures03::channel::mpsc::{self as async_mpsc, UnboundedReceiver, UnboundedSender};
+use futures03::future;
+use futures03::stream::StreamExt;
+use libp2p::{gossipsub, noise, tcp, yamux, Multiaddr, PeerId, StreamProtocol, Swarm, SwarmBuilder};
+use libp2p::identity::Keypair;
+use libp2p::multiaddr::Protocol;
+use libp2p::request_response::{self, json, OutboundRequestId, ProtocolSupport, ResponseChannel};
+use libp2p::swarm::{DialError, NetworkBehaviour, SwarmEvent};
+use serde_json;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::OpenOptions;
+use std::fs;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{mpsc, Arc, Mutex};
+use std::task::{Context, Poll};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio;
+
+/// The protocol requests and their responses are exchanged with.
+const REQUEST_PROTOCOL: &str = "/node-rs/request/1";
+
+/// The time in seconds after which a request without response fails.
+const REQUEST_TIMEOUT: u64 = 60;
+
+/// The time in seconds after which connections without any requests are closed.
+const IDLE_CONNECTION_TIMEOUT: u64 = 120;
+
+/// Receives the response to a request, or the reason it failed.
+type ResponseSender = mpsc::Sender<Result<Message, String>>;
+
+/// The address and the libp2p address of each other sealer, by its peer id.
+type SealerPeers = HashMap<PeerId, (SocketAddr, Multiaddr)>;
+
+/// Read the libp2p keypair from the given file, holding its hex encoded protobuf encoding,
+/// or generate a new Ed25519 keypair and store it there if the file does not exist.
+///
+/// A generated keypair is never overwritten, so that the peer id
+/// declared in the genesis configuration stays valid.
+pub fn load_or_generate_key(file_name: &str) -> Result<Keypair, String> {
+    if Path::new(file_name).exists() {
+        let encoded_keypair = match fs::read_to_string(file_name) {
+            Ok(encoded_keypair) => encoded_keypair,
+            Err(e) => {
+                return Err(format!("Failed to read libp2p key {:?}: {:?}", file_name, e));
+            }
+        };
+
+        return match decode_hex(encoded_keypair.trim()).map(|bytes| Keypair::from_protobuf_encoding(&bytes)) {
+            Some(Ok(keypair)) => Ok(keypair),
+            _ => Err(format!("libp2p key {:?} does not hold a hex encoded keypair", file_name))
+        };
+    }
+
+    let keypair = Keypair::generate_ed25519();
+    let encoded_keypair = match keypair.to_protobuf_encoding() {
+        Ok(bytes) => encode_hex(&bytes),
+        Err(e) => {
+            return Err(format!("Failed to encode libp2p key: {}", e));
+        }
+    };
+    let write_result = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(file_name)
+        .and_then(|mut file| file.write_all(encoded_keypair.as_bytes()));
+    match write_result {
+        Ok(()) => {
+            info!("Generated libp2p key with peer id {} at {:?}", keypair.public().to_peer_id(), file_name);
+            Ok(keypair)
+        }
+        Err(e) => Err(format!("Failed to write libp2p key {:?}: {:?}", file_name, e))
+    }
+}
+
+/// Check that the given libp2p addresses of the sealers each end in a peer id,
+/// and that the one of the given own address is identified by the given keypair.
+pub fn check_addresses(libp2p_addresses: &BTreeMap<SocketAddr, String>, own_address: SocketAddr, keypair: &Keypair) -> Result<(), String> {
+    let peer_ids = match parse_addresses(libp2p_addresses) {
+        Ok(peer_ids) => peer_ids,
+        Err(e) => {
+            return Err(e);
+        }
+    };
+
+    match peer_ids.get(&peers::normalize(&own_address)) {
+        Some((peer_id, _)) if *peer_id == keypair.public().to_peer_id() => Ok(()),
+        Some((peer_id, _)) => Err(format!("The libp2p key identifies peer {}, but the genesis configuration declares {} for {}", keypair.public().to_peer_id(), peer_id, own_address)),
+        None => Err(format!("The genesis configuration declares no libp2p address for {}", own_address))
+    }
+}
+
+/// Parse the given libp2p addresses of the sealers into their peer id and the address
+/// without the peer id, by the normalized address of the sealer.
+fn parse_addresses(libp2p_addresses: &BTreeMap<SocketAddr, String>) -> Result<HashMap<SocketAddr, (PeerId, Multiaddr)>, String> {
+    let mut peer_ids = HashMap::new();
+    for (address, libp2p_address) in libp2p_addresses.iter() {
+        let mut multiaddr = match libp2p_address.parse::<Multiaddr>() {
+            Ok(multiaddr) => multiaddr,
+            Err(e) => {
+                return Err(format!("Invalid libp2p address {:?} of {}: {}", libp2p_address, address, e));
+            }
+        };
+
+        match multiaddr.pop() {
+            Some(Protocol::P2p(peer_id)) => {
+                peer_ids.insert(peers::normalize(address), (peer_id, multiaddr));
+            }
+            _ => {
+                return Err(format!("The libp2p address {:?} of {} does not end in a peer id", libp2p_address, address));
+            }
+        }
+    }
+
+    Ok(peer_ids)
+}
+
+/// The behaviours of the swarm: gossipsub for published messages
+/// and request-response for all other messages.
+#[derive(NetworkBehaviour)]
+struct Behaviour {
+    gossipsub: gossipsub::Behaviour,
+    request_response: json::Behaviour<Message, Message>,
+}
+
+/// The commands handed over to the thread running the swarm.
+enum Command {
+    /// Send the request to the peer and reply with its response.
+    Request(PeerId, Message, ResponseSender),
+    /// Respond to a request of a peer.
+    Respond(ResponseChannel<Message>, Message),
+    /// Publish the message to all peers subscribed to the topic of the network.
+    Publish(Message),
+}
+
+/// Exchanges messages with the other sealers over libp2p. Published messages,
+/// i.e. blocks and transactions, are disseminated with gossipsub on a topic per
+/// genesis configuration. All other messages, e.g. the ones synchronizing the chain,
+/// are exchanged with request-response, whose requests are limited to 1 MB.
+///
+/// Connections are encrypted with noise and authenticated by the peer ids declared
+/// in the genesis configuration. Messages of undeclared peers are dropped.
+/// The swarm runs on a dedicated thread. Messages received by it are handed over
+/// to the node, see `Transport::take_incoming`.
+pub struct Libp2pTransport {
+    /// The commands to the thread running the swarm.
+    commands: UnboundedSender<Command>,
+    /// The peer id of each sealer with a libp2p address, by its normalized address.
+    peer_ids: HashMap<SocketAddr, PeerId>,
+    /// The messages received by the swarm, until taken by the node.
+    incoming_messages: Mutex<Option<mpsc::Receiver<IncomingMessage>>>,
+    /// The connectivity to each peer, updated by each request.
+    peer_health: Arc<PeerHealthTracker>,
+}
+
+impl Libp2pTransport {
+    /// Start a swarm identified by the given keypair, listening on the libp2p address of the given own address
+    /// among the given libp2p addresses of the sealers and subscribing to the given topic.
+    /// The outcome of each request is recorded in the given tracker.
+    pub fn new(keypair: Keypair, own_address: SocketAddr, libp2p_addresses: &BTreeMap<SocketAddr, String>, topic: &str, peer_health: &Arc<PeerHealthTracker>) -> Result<Libp2pTransport, String> {
+        match check_addresses(libp2p_addresses, own_address, &keypair) {
+            Ok(()) => {}
+            Err(e) => {
+                return Err(e);
+            }
+        }
+        let addresses = match parse_addresses(libp2p_addresses) {
+            Ok(addresses) => addresses,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+
+        let (command_sender, command_receiver) = async_mpsc::unbounded();
+        let (incoming_sender, incoming_receiver) = mpsc::channel();
+        let (started_sender, started_receiver) = mpsc::channel();
+        let cloned_addresses = addresses.clone();
+        let cloned_command_sender = command_sender.clone();
+        let cloned_topic = topic.to_string();
+        let spawn_result = thread::Builder::new().name("libp2p".to_string()).spawn(move || {
+            let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+                Ok(runtime) => runtime,
+                Err(e) => {
+                    let _ = started_sender.send(Err(format!("Failed to create runtime: {:?}", e)));
+                    return;
+                }
+            };
+
+            // the swarm must be created within the runtime it is driven by
+            let start_result = {
+                let _runtime_guard = runtime.enter();
+                start(keypair, own_address, &cloned_addresses, &cloned_topic)
+            };
+            let mut event_loop = match start_result {
+                Ok((swarm, peers)) => EventLoop {
+                    swarm,
+                    commands: command_receiver,
+                    command_sender: cloned_command_sender,
+                    incoming_sender,
+                    topic: gossipsub::IdentTopic::new(cloned_topic),
+                    peers,
+                    queued_requests: HashMap::new(),
+                    pending_requests: HashMap::new(),
+                },
+                Err(e) => {
+                    let _ = started_sender.send(Err(e));
+                    return;
+                }
+            };
+            let _ = started_sender.send(Ok(()));
+
+            runtime.block_on(future::poll_fn(|context| event_loop.poll(context)));
+        });
+        match spawn_result {
+            Ok(_) => {}
+            Err(e) => {
+                return Err(format!("Failed to spawn the libp2p thread: {:?}", e));
+            }
+        }
+
+        match started_receiver.recv() {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                return Err(e);
+            }
+            Err(_) => {
+                return Err("The libp2p thread stopped while starting".to_string());
+            }
+        }
+
+        Ok(Libp2pTransport {
+            commands: command_sender,
+            peer_ids: addresses.into_iter().map(|(address, (peer_id, _))| (address, peer_id)).collect(),
+            incoming_messages: Mutex::new(Some(incoming_receiver)),
+            peer_health: Arc::clone(peer_health),
+        })
+    }
+
+    /// Send the given request to the given peer and wait for its response, at most the given time if any.
+    fn exchange(&self, peer_addr: &SocketAddr, message: Message, timeout: Option<Duration>) -> Result<Message, String> {
+        let peer_id = match self.peer_ids.get(&peers::normalize(peer_addr)) {
+            Some(peer_id) => *peer_id,
+            None => {
+                return Err(format!("The genesis configuration declares no libp2p address for {}", peer_addr));
+            }
+        };
+
+        let started_at = Instant::now();
+        let (response_sender, response_receiver) = mpsc::channel();
+        if self.commands.unbounded_send(Command::Request(peer_id, message, response_sender)).is_err() {
+            return Err("The libp2p thread has stopped".to_string());
+        }
+
+        // the swarm fails requests without response after `REQUEST_TIMEOUT`
+        let response = match timeout {
+            Some(timeout) => response_receiver.recv_timeout(timeout).unwrap_or_else(|_| Err("Timed out waiting for the response".to_string())),
+            None => response_receiver.recv().unwrap_or_else(|_| Err("The libp2p thread has stopped".to_string()))
+        };
+        match response {
+            Ok(response) => {
+                self.peer_health.record_success(peer_addr, started_at.elapsed());
+                Ok(response)
+            }
+            Err(e) => {
+                self.peer_health.record_failure(peer_addr, &e);
+                Err(e)
+            }
+        }
+    }
+}
+
+impl Transport for Libp2pTransport {
+    fn request(&self, peer_addr: &SocketAddr, message: Message) -> Result<Message, String> {
+        self.exchange(peer_addr, message, None)
+    }
+
+    fn request_with_timeout(&self, peer_addr: &SocketAddr, message: Message, timeout: Duration) -> Result<Message, String> {
+        self.exchange(peer_addr, message, Some(timeout))
+    }
+
+    fn use_codec(&self, _peer_addr: &SocketAddr, _codec: CodecKind) {
+        // messages are always encoded as JSON by request-response
+    }
+
+    fn use_compression(&self, _peer_addr: &SocketAddr, _compression: Option<CompressionKind>) {
+        // messages are never compressed, as their size is bounded by request-response and gossipsub
+    }
+
+    /// Publish the given message with gossipsub to all peers subscribed to the topic
+    /// of the network, independently of the given peers.
+    fn publish(&self, _peer_addrs: &[SocketAddr], message: Message) {
+        if self.commands.unbounded_send(Command::Publish(message)).is_err() {
+            warn!("Failed to publish message as the libp2p thread has stopped");
+        }
+    }
+
+    fn is_publish_subscribe(&self) -> bool {
+        true
+    }
+
+    fn take_incoming(&self) -> Option<mpsc::Receiver<IncomingMessage>> {
+        self.incoming_messages.lock().unwrap().take()
+    }
+}
+
+/// Create the swarm, listen on the libp2p address of the given own address and dial all other sealers.
+/// Returns the swarm and the address and the libp2p address of each other sealer by its peer id.
+fn start(keypair: Keypair, own_address: SocketAddr, addresses: &HashMap<SocketAddr, (PeerId, Multiaddr)>, topic: &str) -> Result<(Swarm<Behaviour>, SealerPeers), String> {
+    let mut swarm = match create_swarm(keypair, topic) {
+        Ok(swarm) => swarm,
+        Err(e) => {
+            return Err(e);
+        }
+    };
+
+    let own_peer_id = *swarm.local_peer_id();
+    let mut peers = HashMap::new();
+    for (address, &(peer_id, ref multiaddr)) in addresses.iter() {
+        if peer_id == own_peer_id {
+            if let Err(e) = swarm.listen_on(multiaddr.clone()) {
+                return Err(format!("Failed to listen on {}: {:?}", multiaddr, e));
+            }
+            continue;
+        }
+
+        peers.insert(peer_id, (*address, multiaddr.clone()));
+        swarm.add_peer_address(peer_id, multiaddr.clone());
+        // gossipsub keeps connections to explicit peers open and forwards all messages to them
+        swarm.behaviour_mut().gossipsub.add_explicit_peer(&peer_id);
+        if let Err(e) = swarm.dial(peer_id) {
+            debug!("Failed to dial {} at {}: {}", address, multiaddr, e);
+        }
+    }
+    info!("Exchanging messages over libp2p as peer {} for {}", own_peer_id, own_address);
+
+    Ok((swarm, peers))
+}
+
+/// Create a swarm identified by the given keypair, subscribed to the given topic.
+fn create_swarm(keypair: Keypair, topic: &str) -> Result<Swarm<Behaviour>, String> {
+    let gossipsub_config = match gossipsub::ConfigBuilder::default().max_transmit_size(MAX_MESSAGE_SIZE).build() {
+        Ok(gossipsub_config) => gossipsub_config,
+        Err(e) => {
+            return Err(format!("Invalid gossipsub configuration: {}", e));
+        }
+    };
+    let mut gossipsub = match gossipsub::Behaviour::new(gossipsub::MessageAuthenticity::Signed(keypair.clone()), gossipsub_config) {
+        Ok(gossipsub) => gossipsub,
+        Err(e) => {
+            return Err(format!("Failed to create gossipsub: {}", e));
+        }
+    };
+    if let Err(e) = gossipsub.subscribe(&gossipsub::IdentTopic::new(topic)) {
+        return Err(format!("Failed to subscribe to {}: {:?}", topic, e));
+    }
+    let request_response = json::Behaviour::new(
+        vec![(StreamProtocol::new(REQUEST_PROTOCOL), ProtocolSupport::Full)],
+        request_response::Config::default().with_request_timeout(Duration::from_secs(REQUEST_TIMEOUT)),
+    );
+
+    let builder = match SwarmBuilder::with_existing_identity(keypair).with_tokio().with_tcp(tcp::Config::default(), noise::Config::new, yamux::Config::default) {
+        Ok(builder) => builder,
+        Err(e) => {
+            return Err(format!("Failed to create the TCP transport: {:?}", e));
+        }
+    };
+    let builder = match builder.with_behaviour(move |_| Behaviour { gossipsub, request_response }) {
+        Ok(builder) => builder,
+        Err(e) => {
+            return Err(format!("Failed to create the behaviour: {:?}", e));
+        }
+    };
+
+    Ok(builder.with_swarm_config(|config| config.with_idle_connection_timeout(Duration::from_secs(IDLE_CONNECTION_TIMEOUT))).build())
+}
+
+/// Drives the swarm, executing the commands of the transport
+/// and handing over received messages to the node.
+struct EventLoop {
+    swarm: Swarm<Behaviour>,
+    commands: UnboundedReceiver<Command>,
+    /// Passed to the responders of incoming requests.
+    command_sender: UnboundedSender<Command>,
+    incoming_sender: mpsc::Sender<IncomingMessage>,
+    topic: gossipsub::IdentTopic,
+    /// The address and the libp2p address of each other sealer, by its peer id.
+    peers: SealerPeers,
+    /// The requests to each peer being dialed, sent once connected to it.
+    queued_requests: HashMap<PeerId, Vec<(Message, ResponseSender)>>,
+    /// The senders waiting for the response to each request.
+    pending_requests: HashMap<OutboundRequestId, ResponseSender>,
+}
+
+impl EventLoop {
+    /// Execute all pending commands and handle all events of the swarm.
+    /// Ready once the transport is dropped.
+    fn poll(&mut self, context: &mut Context) -> Poll<()> {
+        loop {
+            match self.commands.poll_next_unpin(context) {
+                Poll::Ready(Some(command)) => {
+                    self.execute(command);
+                    continue;
+                }
+                Poll::Ready(None) => {
+                    return Poll::Ready(());
+                }
+                Poll::Pending => {}
+            }
+
+            match self.swarm.poll_next_unpin(context) {
+                Poll::Ready(Some(event)) => self.handle_event(event),
+                Poll::Ready(None) => {
+                    return Poll::Ready(());
+                }
+                Poll::Pending => {
+                    return Poll::Pending;
+                }
+            }
+        }
+    }
+
+    /// Let the swarm know the libp2p address of the given peer, if it is a sealer. Addresses
+    /// are forgotten by the swarm once dialing them fails, e.g. while the peer is restarting.
+    fn remember_address(&mut self, peer_id: PeerId) {
+        if let Some((_, multiaddr)) = self.peers.get(&peer_id) {
+            self.swarm.add_peer_address(peer_id, multiaddr.clone());
+        }
+    }
+
+    fn send_request(&mut self, peer_id: PeerId, message: Message, response_sender: ResponseSender) {
+        let request_id = self.swarm.behaviour_mut().request_response.send_request(&peer_id, message);
+        self.pending_requests.insert(request_id, response_sender);
+    }
+
+    fn execute(&mut self, command: Command) {
+        match command {
+            Command::Request(peer_id, message, response_sender) => {
+                if self.swarm.is_connected(&peer_id) {
+                    self.send_request(peer_id, message, response_sender);
+                    return;
+                }
+
+                // request-response fails requests to a peer which is already being dialed,
+                // hence they are queued until the connection is established
+                self.remember_address(peer_id);
+                match self.swarm.dial(peer_id) {
+                    Ok(()) | Err(DialError::DialPeerConditionFalse(_)) => {
+                        self.queued_requests.entry(peer_id).or_default().push((message, response_sender));
+                    }
+                    Err(e) => {
+                        let _ = response_sender.send(Err(format!("Failed to dial peer: {}", e)));
+                    }
+                }
+            }
+            Command::Respond(channel, response) => {
+                if self.swarm.behaviour_mut().request_response.send_response(channel, response).is_err() {
+                    debug!("Failed to respond as the connection to the requester is closed");
+                }
+            }
+            Command::Publish(message) => {
+                let data = serde_json::to_vec(&message).unwrap();
+                if let Err(e) = self.swarm.behaviour_mut().gossipsub.publish(self.topic.clone(), data) {
+                    warn!("Failed to publish message: {:?}", e);
+                }
+            }
+        }
+    }
+
+    fn handle_event(&mut self, event: SwarmEvent<BehaviourEvent>) {
+        match event {
+            SwarmEvent::Behaviour(BehaviourEvent::RequestResponse(request_response::Event::Message { peer, message })) => match message {
+                request_response::Message::Request { request, channel, .. } => {
+                    let source = match self.peers.get(&peer) {
+                        Some(&(source, _)) => source,
+                        None => {
+                            debug!("Dropping request of undeclared peer {}", peer);
+                            return;
+                        }
+                    };
+                    let command_sender = self.command_sender.clone();
+                    let responder = Responder::new(Box::new(move |response| {
+                        let _ = command_sender.unbounded_send(Command::Respond(channel, response));
+                    }));
+                    let _ = self.incoming_sender.send(IncomingMessage { source, message: request, responder: Some(responder) });
+                }
+                request_response::Message::Response { request_id, response } => {
+                    if let Some(response_sender) = self.pending_requests.remove(&request_id) {
+                        let _ = response_sender.send(Ok(response));
+                    }
+                }
+            },
+            SwarmEvent::Behaviour(BehaviourEvent::RequestResponse(request_response::Event::OutboundFailure { request_id, error, .. })) => {
+                if let Some(response_sender) = self.pending_requests.remove(&request_id) {
+                    let _ = response_sender.send(Err(format!("Request failed: {}", error)));
+                }
+            }
+            SwarmEvent::Behaviour(BehaviourEvent::RequestResponse(request_response::Event::InboundFailure { peer, error, .. })) => {
+                debug!("Failed to handle request of {}: {}", peer, error);
+            }
+            SwarmEvent::Behaviour(BehaviourEvent::Gossipsub(gossipsub::Event::Message { message, .. })) => {
+                // the message is signed by its author, which may differ from the peer forwarding it
+                let source = match message.source.and_then(|author| self.peers.get(&author).map(|&(source, _)| source)) {
+                    Some(source) => source,
+                    None => {
+                        debug!("Dropping message published by undeclared peer {:?}", message.source);
+                        return;
+                    }
+                };
+                match serde_json::from_slice::<Message>(&message.data) {
+                    Ok(published_message) => {
+                        let _ = self.incoming_sender.send(IncomingMessage { source, message: published_message, responder: None });
+                    }
+                    Err(e) => {
+                        debug!("Dropping malformed message published by {}: {:?}", source, e);
+                    }
+                }
+            }
+            SwarmEvent::NewListenAddr { address, .. } => {
+                info!("Listening for incoming libp2p connections on {}", address);
+            }
+            SwarmEvent::ConnectionEstablished { peer_id, .. } => {
+                trace!("Connected to {} over libp2p", peer_id);
+                for (message, response_sender) in self.queued_requests.remove(&peer_id).unwrap_or_default() {
+                    self.send_request(peer_id, message, response_sender);
+                }
+            }
+            SwarmEvent::OutgoingConnectionError { peer_id: Some(peer_id), error, .. } => {
+                debug!("Failed to connect to {} over libp2p: {}", peer_id, error);
+                for (_, response_sender) in self.queued_requests.remove(&peer_id).unwrap_or_default() {
+                    let _ = response_sender.send(Err(format!("Failed to connect: {}", error)));
+                }
+                self.remember_address(peer_id);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod libp2p_transport_test {
+    use ::p2p::codec::Message;
+    use ::p2p::health::PeerHealthTracker;
+    use ::p2p::libp2p_transport::Libp2pTransport;
+    use ::p2p::transport::Transport;
+    use libp2p::identity::Keypair;
+    use std::collections::BTreeMap;
+    use std::net::SocketAddr;
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_request_and_publish() {
+        let first_address: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let second_address: SocketAddr = "127.0.0.1:9001".parse().unwrap();
+        let first_key = Keypair::generate_ed25519();
+        let second_key = Keypair::generate_ed25519();
+        let mut libp2p_addresses = BTreeMap::new();
+        libp2p_addresses.insert(first_address, format!("/ip4/127.0.0.1/tcp/19300/p2p/{}", first_key.public().to_peer_id()));
+        libp2p_addresses.insert(second_address, format!("/ip4/127.0.0.1/tcp/19301/p2p/{}", second_key.public().to_peer_id()));
+
+        let peer_health = Arc::new(PeerHealthTracker::new());
+        // the second listens before the first dials it
+        let second = Libp2pTransport::new(second_key, second_address, &libp2p_addresses, "test", &peer_health).unwrap();
+        let first = Libp2pTransport::new(first_key, first_address, &libp2p_addresses, "test", &peer_health).unwrap();
+        let incoming = second.take_incoming().unwrap();
+        assert!(second.take_incoming().is_none());
+
+        let (published_sender, published_receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for incoming_message in incoming.iter() {
+                assert_eq!(first_address, incoming_message.source);
+                match incoming_message.responder {
+                    Some(responder) => responder.respond(Message::Pong),
+                    None => published_sender.send(incoming_message.message).unwrap()
+                }
+            }
+        });
+
+        assert_eq!(Message::Pong, first.request_with_timeout(&second_address, Message::Ping, Duration::from_secs(10)).unwrap());
+        assert!(first.request(&"127.0.0.1:9002".parse().unwrap(), Message::Ping).is_err());
+
+        // the subscription of the second is announced asynchronously
+        let published_message = (0..20).filter_map(|_| {
+            first.publish(&[], Message::Ping);
+            published_receiver.recv_timeout(Duration::from_millis(500)).ok()
+        }).next();
+        assert_eq!(Some(Message::Ping), published_message);
+    }
+
+    #[test]
+    fn test_rejects_foreign_key() {
+        let own_address: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let declared_key = Keypair::generate_ed25519();
+        let mut libp2p_addresses = BTreeMap::new();
+        libp2p_addresses.insert(own_address, format!("/ip4/127.0.0.1/tcp/19302/p2p/{}", declared_key.public().to_peer_id()));
+
+        let peer_health = Arc::new(PeerHealthTracker::new());
+        assert!(Libp2pTransport::new(Keypair::generate_ed25519(), own_address, &libp2p_addresses, "test", &peer_health).is_err());
+
+        libp2p_addresses.insert(own_address, "/ip4/127.0.0.1/tcp/19302".to_string());
+        assert!(Libp2pTransport::new(declared_key, own_address, &libp2p_addresses, "test", &peer_health).is_err());
+    }
+}
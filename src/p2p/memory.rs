@@ -0,0 +1,214 @@
+use std::io::{ErrorKind, Read};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The time in milliseconds to wait for other reservations to be released
+/// before giving up on a reservation exceeding the cap.
+const BACKPRESSURE_TIMEOUT: u64 = 5000;
+
+/// The interval in milliseconds in which a waiting reservation is retried.
+const BACKPRESSURE_POLL_INTERVAL: u64 = 10;
+
+/// The amount of bytes read at once from a connection.
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The caps in bytes of the memory a node may use for different purposes.
+/// None, if the respective memory is not capped.
+#[derive(Eq, PartialEq, Clone, Copy, Debug, Default)]
+pub struct MemoryCaps {
+    /// The copies of the chain cached to serve requests of peers synchronizing with this node.
+    /// Snapshots exceeding the cap are not cached but encoded for each request.
+    pub chain_cache: Option<usize>,
+    /// The transactions waiting to be included in a block.
+    /// Further transactions are rejected until the buffer drains.
+    pub mempool: Option<usize>,
+    /// The messages being received from peers and clients.
+    /// Reading further messages is delayed until others are handled.
+    pub in_flight_messages: Option<usize>,
+}
+
+/// Accounts the memory used for a single purpose against its cap.
+pub struct MemoryBudget {
+    /// The purpose of the memory, used in log messages.
+    name: &'static str,
+    /// The cap in bytes, or None if the memory is not capped.
+    cap: Option<usize>,
+    /// The amount of bytes currently reserved.
+    used: AtomicUsize,
+}
+
+impl MemoryBudget {
+    /// Create a new budget with the given cap in bytes.
+    pub fn new(name: &'static str, cap: Option<usize>) -> MemoryBudget {
+        MemoryBudget {
+            name,
+            cap,
+            used: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the amount of bytes currently reserved.
+    pub fn get_used(&self) -> usize {
+        self.used.load(Ordering::SeqCst)
+    }
+
+    /// Returns the cap in bytes, or None if the memory is not capped.
+    pub fn get_cap(&self) -> Option<usize> {
+        self.cap
+    }
+
+    /// Reserve the given amount of bytes, if they fit into the cap.
+    /// Returns false, without reserving anything, otherwise.
+    pub fn try_reserve(&self, bytes: usize) -> bool {
+        let cap = match self.cap {
+            Some(cap) => cap,
+            None => {
+                self.used.fetch_add(bytes, Ordering::SeqCst);
+                return true;
+            }
+        };
+
+        let mut used = self.used.load(Ordering::SeqCst);
+        loop {
+            if used + bytes > cap {
+                return false;
+            }
+
+            match self.used.compare_exchange(used, used + bytes, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) => {
+                    return true;
+                }
+                Err(previously_used) => {
+                    used = previously_used;
+                }
+            }
+        }
+    }
+
+    /// Release the given amount of bytes reserved before.
+    pub fn release(&self, bytes: usize) {
+        self.used.fetch_sub(bytes, Ordering::SeqCst);
+    }
+}
+
+/// Memory reserved in a budget until the reservation is dropped.
+pub struct MemoryReservation {
+    budget: Arc<MemoryBudget>,
+    bytes: usize,
+}
+
+impl MemoryReservation {
+    /// Create an empty reservation in the given budget.
+    pub fn new(budget: &Arc<MemoryBudget>) -> MemoryReservation {
+        MemoryReservation {
+            budget: Arc::clone(budget),
+            bytes: 0,
+        }
+    }
+
+    /// Returns the amount of bytes reserved.
+    pub fn get_bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Reserve the given amount of bytes in addition.
+    ///
+    /// If they do not fit into the cap, waits for other reservations to be released
+    /// for at most `BACKPRESSURE_TIMEOUT` milliseconds. Returns an error if they still
+    /// do not fit, or if the reservation as a whole would exceed the cap.
+    pub fn grow(&mut self, bytes: usize) -> Result<(), String> {
+        if let Some(cap) = self.budget.get_cap() {
+            if self.bytes + bytes > cap {
+                return Err(format!("{} bytes exceed the cap of {} bytes for {}", self.bytes + bytes, cap, self.budget.name));
+            }
+        }
+
+        let started_at = Instant::now();
+        while !self.budget.try_reserve(bytes) {
+            if started_at.elapsed() >= Duration::from_millis(BACKPRESSURE_TIMEOUT) {
+                return Err(format!("Cap of {:?} bytes for {} reached, {} bytes in use", self.budget.get_cap(), self.budget.name, self.budget.get_used()));
+            }
+
+            thread::sleep(Duration::from_millis(BACKPRESSURE_POLL_INTERVAL));
+        }
+        self.bytes += bytes;
+
+        Ok(())
+    }
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        self.budget.release(self.bytes);
+    }
+}
+
+/// Read a message from the given reader until EOF, but at most `limit` bytes.
+///
+/// The bytes read are reserved in the given budget chunk by chunk. While the budget
+/// is exhausted, reading pauses, which in turn slows down the sender of the message.
+/// The returned reservation must be kept until the message is handled.
+///
+/// Returns an error if reading fails or the budget is not released within the backpressure timeout.
+pub fn read_message(reader: &mut Read, limit: usize, budget: &Arc<MemoryBudget>) -> Result<(Vec<u8>, MemoryReservation), String> {
+    let mut reservation = MemoryReservation::new(budget);
+    let mut buffer: Vec<u8> = vec![];
+    let mut chunk = vec![0; READ_CHUNK_SIZE];
+
+    while buffer.len() < limit {
+        let amount_to_read = READ_CHUNK_SIZE.min(limit - buffer.len());
+        let amount_read = match reader.read(&mut chunk[..amount_to_read]) {
+            Ok(0) => break,
+            Ok(amount_read) => amount_read,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => {
+                return Err(format!("Failed to read message: {:?}", e));
+            }
+        };
+
+        match reservation.grow(amount_read) {
+            Ok(()) => {}
+            Err(e) => {
+                return Err(e);
+            }
+        }
+        buffer.extend_from_slice(&chunk[..amount_read]);
+    }
+
+    Ok((buffer, reservation))
+}
+
+#[cfg(test)]
+mod memory_test {
+
+    use super::*;
+
+    #[test]
+    fn test_memory_budget() {
+        let budget = Arc::new(MemoryBudget::new("test", Some(10)));
+
+        let mut reservation = MemoryReservation::new(&budget);
+        assert_eq!(Ok(()), reservation.grow(6));
+        assert!(!budget.try_reserve(5));
+        assert!(budget.try_reserve(4));
+        assert_eq!(10, budget.get_used());
+        budget.release(4);
+
+        // a single reservation can never exceed the cap
+        assert!(reservation.grow(5).is_err());
+        assert_eq!(6, budget.get_used());
+
+        drop(reservation);
+        assert_eq!(0, budget.get_used());
+
+        let message_budget = Arc::new(MemoryBudget::new("test messages", Some(14)));
+        let mut message: &[u8] = b"{\"Ping\":null}";
+        let (read, reservation) = read_message(&mut message, 1024, &message_budget).unwrap();
+        assert_eq!(b"{\"Ping\":null}".to_vec(), read);
+        assert_eq!(read.len(), reservation.get_bytes());
+        drop(reservation);
+        assert!(read_message(&mut &b"too long message"[..], 1024, &message_budget).is_err());
+    }
+}
@@ -5,4 +5,77 @@ pub mod thread;
 pub mod node;
 
 /// The codec definition used to send information between nodes.
-pub mod codec;
\ No newline at end of file
+pub mod codec;
+
+/// Compression of large messages exchanged between nodes.
+pub mod compression;
+
+/// Caches of encoded responses shared among concurrent requesters.
+pub mod response_cache;
+
+/// Accounting of the memory used by a node against configurable caps.
+pub mod memory;
+
+/// Batched gossiping of messages to other peers.
+pub mod gossip;
+
+/// The set of peers of a node and the normalization of their addresses.
+pub mod peers;
+
+/// A client to send requests to a node.
+pub mod client;
+
+/// Diagnosis of the compatibility of peers.
+pub mod probe;
+
+/// Authenticated admin commands tunneled over the peer protocol.
+pub mod admin;
+
+/// Scoped tokens restricting access to the RPC methods of a node.
+pub mod rpc_token;
+
+/// The staged verification and insertion of blocks received from other peers.
+pub mod block_pipeline;
+
+/// Traversal of NATs via UPnP port mappings.
+pub mod nat;
+
+/// Connections kept open by peers unable to accept incoming connections.
+pub mod relay;
+
+/// The transports over which messages are sent to other peers.
+pub mod transport;
+
+/// A transport exchanging messages with other peers over libp2p.
+#[cfg(feature = "libp2p")]
+pub mod libp2p_transport;
+
+/// Encryption of the connections between nodes with TLS.
+pub mod tls;
+
+/// Persistent bans of sources submitting transactions with invalid proofs.
+pub mod ban_list;
+
+/// Per-address limits on the connections and messages accepted by the listeners.
+pub mod rate_limit;
+
+/// Tracking of the connectivity to each peer.
+pub mod health;
+
+/// Comparison of the local clock against the clocks of peers and NTP servers.
+pub mod clock;
+
+/// An append-only log of notices for the operator.
+pub mod audit;
+
+/// Detection of heads conflicting with the heads of most other sealers, upon which sealing is paused.
+pub mod divergence;
+
+/// Latency-aware fetching of block ranges from several peers in parallel during catch-up.
+pub mod block_fetch;
+
+/// Subscriptions of RPC clients to updates pushed by the node.
+pub mod subscription;
+
+/// Cross-checking the tallies computed by several nodes.
+pub mod tally_comparison;
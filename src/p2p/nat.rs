@@ -0,0 +1,287 @@
+use ::http;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::time::Duration;
+
+/// The multicast address on which gateways answer discovery requests.
+const SSDP_ADDRESS: &str = "239.255.255.250:1900";
+
+/// The maximum time to wait for the gateway to answer a request.
+const GATEWAY_TIMEOUT: u64 = 3;
+
+/// The maximum size in bytes of a response of the gateway.
+const MAX_GATEWAY_RESPONSE_SIZE: u64 = 64 * 1024;
+
+/// The amount of seconds a port mapping is requested for.
+/// Mappings must be renewed before they expire.
+pub const PORT_MAPPING_LEASE_SECS: u64 = 3600;
+
+/// The services of a gateway able to map ports, in the order of preference.
+const WAN_CONNECTION_SERVICES: [&str; 2] = [
+    "urn:schemas-upnp-org:service:WANIPConnection:1",
+    "urn:schemas-upnp-org:service:WANPPPConnection:1",
+];
+
+/// A TCP port mapping on an Internet Gateway Device, created via UPnP,
+/// forwarding connections from outside the local network to this node.
+#[derive(Clone, Debug)]
+pub struct PortMapping {
+    /// The URL on which the gateway accepts commands.
+    control_url: String,
+    /// The service of the gateway handling the mapping.
+    service_type: String,
+    /// The address of this node in the local network.
+    local_address: SocketAddr,
+    /// The port on the gateway forwarded to the local address.
+    external_port: u16,
+}
+
+impl PortMapping {
+    /// Discover the gateway of the local network and forward the given external port
+    /// to the port of the given local address.
+    ///
+    /// - `local_port` The port this node listens on. Connections are forwarded to the
+    ///   address of the interface facing the gateway.
+    /// - `external_port` The port on the gateway, i.e. the port of the advertised address.
+    pub fn create(local_port: u16, external_port: u16) -> Result<PortMapping, String> {
+        let location = match discover_gateway() {
+            Ok(location) => location,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+
+        let (control_url, service_type) = match find_control_url(&location) {
+            Ok(control) => control,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+
+        let (gateway_authority, _) = match http::parse_url(&control_url) {
+            Ok(parsed_url) => parsed_url,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+        let local_ip = match find_local_ip(&gateway_authority) {
+            Ok(local_ip) => local_ip,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+
+        let port_mapping = PortMapping {
+            control_url,
+            service_type,
+            local_address: SocketAddr::new(local_ip, local_port),
+            external_port,
+        };
+
+        match port_mapping.renew() {
+            Ok(()) => Ok(port_mapping),
+            Err(e) => Err(e)
+        }
+    }
+
+    /// Request the mapping again, extending its lease by `PORT_MAPPING_LEASE_SECS`.
+    pub fn renew(&self) -> Result<(), String> {
+        let arguments = format!(
+            "<NewRemoteHost></NewRemoteHost><NewExternalPort>{}</NewExternalPort><NewProtocol>TCP</NewProtocol>\
+             <NewInternalPort>{}</NewInternalPort><NewInternalClient>{}</NewInternalClient><NewEnabled>1</NewEnabled>\
+             <NewPortMappingDescription>node_rs</NewPortMappingDescription><NewLeaseDuration>{}</NewLeaseDuration>",
+            self.external_port,
+            self.local_address.port(),
+            self.local_address.ip(),
+            PORT_MAPPING_LEASE_SECS
+        );
+
+        match self.invoke("AddPortMapping", &arguments) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Failed to map port {} to {}: {}", self.external_port, self.local_address, e))
+        }
+    }
+
+    /// Returns the IP address of the gateway on the side of the internet.
+    pub fn get_external_ip(&self) -> Result<IpAddr, String> {
+        let body = match self.invoke("GetExternalIPAddress", "") {
+            Ok(body) => body,
+            Err(e) => {
+                return Err(format!("Failed to get external IP address: {}", e));
+            }
+        };
+
+        match extract_element(&body, "NewExternalIPAddress").and_then(|ip| ip.trim().parse().ok()) {
+            Some(external_ip) => Ok(external_ip),
+            None => Err("Gateway did not report a valid external IP address".to_string())
+        }
+    }
+
+    /// Returns the port on the gateway forwarded to this node.
+    pub fn get_external_port(&self) -> u16 {
+        self.external_port
+    }
+
+    /// Invoke the given action of the service of the gateway.
+    /// Returns the body of the response.
+    fn invoke(&self, action: &str, arguments: &str) -> Result<String, String> {
+        let (authority, path) = match http::parse_url(&self.control_url) {
+            Ok(parsed_url) => parsed_url,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+
+        let body = format!(
+            "<?xml version=\"1.0\"?>\
+             <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+             <s:Body><u:{action} xmlns:u=\"{service}\">{arguments}</u:{action}></s:Body></s:Envelope>",
+            action = action,
+            service = self.service_type,
+            arguments = arguments
+        );
+        // HTTP/1.0 prevents the gateway from responding in chunks
+        let request = format!(
+            "POST {} HTTP/1.0\r\nHost: {}\r\nContent-Type: text/xml; charset=\"utf-8\"\r\nContent-Length: {}\r\nSOAPAction: \"{}#{}\"\r\n\r\n{}",
+            path,
+            authority,
+            body.len(),
+            self.service_type,
+            action,
+            body
+        );
+
+        let response = match http::send(&authority, &request, Duration::from_secs(GATEWAY_TIMEOUT), MAX_GATEWAY_RESPONSE_SIZE) {
+            Ok(response) => response,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+
+        match http::get_status(&response) {
+            Some(200) => Ok(http::get_body(&response).to_string()),
+            Some(status) => Err(format!("Gateway responded with status {}", status)),
+            None => Err("Gateway sent no valid HTTP response".to_string())
+        }
+    }
+}
+
+/// Search the local network for an Internet Gateway Device using SSDP.
+/// Returns the URL of the description of the first gateway answering.
+fn discover_gateway() -> Result<String, String> {
+    let request = format!(
+        "M-SEARCH * HTTP/1.1\r\nHOST: {}\r\nST: urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\n\r\n",
+        SSDP_ADDRESS
+    );
+
+    let mut buffer = [0u8; 2048];
+    let result = UdpSocket::bind("0.0.0.0:0")
+        .and_then(|socket| socket.set_read_timeout(Some(Duration::from_secs(GATEWAY_TIMEOUT))).map(|_| socket))
+        .and_then(|socket| socket.send_to(request.as_bytes(), SSDP_ADDRESS).map(|_| socket))
+        .and_then(|socket| socket.recv_from(&mut buffer));
+    let amount_bytes_received = match result {
+        Ok((amount_bytes_received, _)) => amount_bytes_received,
+        Err(e) => {
+            return Err(format!("No UPnP gateway found: {:?}", e));
+        }
+    };
+
+    let response = String::from_utf8_lossy(&buffer[..amount_bytes_received]);
+    let location = response.lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(2, ':');
+            match (parts.next(), parts.next()) {
+                (Some(name), Some(value)) if name.trim().eq_ignore_ascii_case("location") => Some(value.trim().to_string()),
+                _ => None
+            }
+        })
+        .next();
+
+    match location {
+        Some(location) => Ok(location),
+        None => Err("UPnP gateway did not announce the location of its description".to_string())
+    }
+}
+
+/// Fetch the description of the gateway at the given location and find the
+/// control URL of a service able to map ports, along with the type of the service.
+fn find_control_url(location: &str) -> Result<(String, String), String> {
+    let (authority, path) = match http::parse_url(location) {
+        Ok(parsed_url) => parsed_url,
+        Err(e) => {
+            return Err(format!("Unsupported location of the gateway description: {}", e));
+        }
+    };
+
+    let request = format!("GET {} HTTP/1.0\r\nHost: {}\r\n\r\n", path, authority);
+    let response = match http::send(&authority, &request, Duration::from_secs(GATEWAY_TIMEOUT), MAX_GATEWAY_RESPONSE_SIZE) {
+        Ok(response) => response,
+        Err(e) => {
+            return Err(format!("Failed to fetch the gateway description: {}", e));
+        }
+    };
+    let description = http::get_body(&response);
+
+    for service_type in WAN_CONNECTION_SERVICES.iter() {
+        let service_index = match description.find(service_type) {
+            Some(index) => index,
+            None => continue
+        };
+
+        if let Some(control_url) = extract_element(&description[service_index..], "controlURL") {
+            let control_url = control_url.trim();
+            let control_url = if control_url.starts_with("http://") {
+                control_url.to_string()
+            } else if control_url.starts_with('/') {
+                format!("http://{}{}", authority, control_url)
+            } else {
+                format!("http://{}/{}", authority, control_url)
+            };
+
+            return Ok((control_url, service_type.to_string()));
+        }
+    }
+
+    Err("Gateway does not offer a service to map ports".to_string())
+}
+
+/// Returns the address of the local interface used to reach the given authority.
+fn find_local_ip(authority: &str) -> Result<IpAddr, String> {
+    // connecting a UDP socket does not send anything, but selects the interface
+    let result = UdpSocket::bind("0.0.0.0:0")
+        .and_then(|socket| socket.connect(authority).map(|_| socket))
+        .and_then(|socket| socket.local_addr());
+
+    match result {
+        Ok(local_address) => Ok(local_address.ip()),
+        Err(e) => Err(format!("Failed to determine the local address facing the gateway: {:?}", e))
+    }
+}
+
+/// Returns the text of the first element with the given name.
+fn extract_element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let start_tag = format!("<{}>", name);
+    let content_start = match xml.find(&start_tag) {
+        Some(index) => index + start_tag.len(),
+        None => {
+            return None;
+        }
+    };
+
+    xml[content_start..].find("</").map(|content_length| &xml[content_start..content_start + content_length])
+}
+
+#[cfg(test)]
+mod nat_test {
+
+    use super::extract_element;
+
+    #[test]
+    fn test_extract_element() {
+        let body = "<s:Body><u:GetExternalIPAddressResponse><NewExternalIPAddress>203.0.113.7</NewExternalIPAddress></u:GetExternalIPAddressResponse></s:Body>";
+        assert_eq!(Some("203.0.113.7"), extract_element(body, "NewExternalIPAddress"));
+
+        let description = "<service><serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType><controlURL>/ctl/IPConn</controlURL></service>";
+        assert_eq!(Some("/ctl/IPConn"), extract_element(description, "controlURL"));
+        assert_eq!(None, extract_element(description, "eventSubURL"));
+    }
+}
@@ -1,15 +1,150 @@
+use ::beacon::{BeaconConfig, Checkpoint};
+use ::config::feature;
 use ::config::genesis::Genesis;
-use ::p2p::codec::{Codec, JsonCodec, Message};
+use ::config::node::NodeConfig;
+use ::chain::block_store::FileBlockStore;
+use ::chain::chain::{Chain, HeadInfo};
+use ::chain::sealer_set::SealerSet;
+use ::chain::snapshot::StateSnapshot;
+use ::chain::storage::StorageEncoding;
+use ::chain::types::{BlockHash, Height};
+use ::chain::write_ahead_log::WriteAheadLog;
+use ::metrics::MetricsRegistry;
+use ::p2p::audit::AuditLog;
+use ::p2p::ban_list::BanList;
+use ::p2p::block_fetch::{self, PeerLatencies, MAX_PARALLEL_FETCHES, PROBE_TIMEOUT, STALL_TIMEOUT};
+use ::p2p::block_pipeline::BlockPipeline;
+use ::p2p::clock;
+use ::p2p::codec::{self, Codec, CodecKind, JsonCodec, Message, MAX_MESSAGE_SIZE};
+use ::p2p::compression::{self, CompressionKind, CompressionSetting};
+use ::p2p::divergence::{self, DivergenceNotice, HeadRelation};
+use ::p2p::gossip::GossipQueue;
+use ::p2p::health::PeerHealthTracker;
+use ::p2p::memory::{self, MemoryBudget, MemoryReservation};
+use ::p2p::nat::{PortMapping, PORT_MAPPING_LEASE_SECS};
+use ::p2p::peers::{self, PeerSet};
+use ::p2p::rate_limit::{ConnectionPermit, RateLimiter};
+use ::p2p::relay::RelaySet;
+use ::p2p::response_cache::ChainResponseCache;
+use ::p2p::rpc_token::RpcTokens;
+use ::p2p::subscription::TurnoutSubscriptions;
 use ::p2p::thread::ThreadPool;
-use ::protocol::clique::{CliqueProtocol, ProtocolHandler};
-use std::{thread, time};
-use std::collections::HashSet;
-use std::io::ErrorKind;
+use ::p2p::tls::{PeerStream, TlsContext};
+use ::p2p::transport::{self, IncomingMessage, Transport};
+use ::protocol::block_validator::{BlockValidator, MAX_TIMESTAMP_DRIFT};
+use ::protocol::clique::{BlockRange, CliqueProtocol, ProtocolHandler, INVALID_TRANSACTION_REASON, MAX_BLOCK_RANGE};
+use ::protocol::receipt::ReceiptStatus;
+use ::retention::RetentionPolicy;
+use ::signer::{Signer, UnixSocketSigner};
+use ::threshold::dkg::{DealtShare, KeyGeneration};
+use ::webhook::{WebhookConfig, WebhookDispatcher};
+#[cfg(feature = "ui")]
+use ::ui::Dashboard;
+#[cfg(feature = "ui")]
+use ::ui::compression::ContentEncoding;
+use serde_json;
+use std::{cmp, thread, time};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, ErrorKind};
 use std::io::Read;
 use std::io::Write;
-use std::iter::FromIterator;
 use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// The time in milliseconds to wait for an external signer
+/// before skipping the own slot.
+const EXTERNAL_SIGNER_TIMEOUT: u64 = 500;
+
+/// The time in milliseconds a co-leader waits for the in-turn leader
+/// to announce its head, before sealing an out-of-turn block.
+/// Also the time a sealer waits for each other sealer to announce its head before sealing.
+const HEAD_QUERY_TIMEOUT: u64 = 300;
+
+/// The time in milliseconds to wait for a peer to answer the handshake.
+const HANDSHAKE_TIMEOUT: u64 = 2000;
+
+/// The time in milliseconds an incoming connection may stall while sending its request,
+/// including the TLS handshake, or while receiving the response, before it is dropped.
+const INCOMING_CONNECTION_TIMEOUT: u64 = 10000;
+
+/// The time in milliseconds to wait for a peer or an NTP server to tell its time.
+const CLOCK_QUERY_TIMEOUT: u64 = 1000;
+
+/// The interval in seconds in which the local clock is compared against the clocks of the peers.
+const CLOCK_CHECK_INTERVAL: u64 = 600;
+
+/// The interval in seconds in which branches forked off below the finalized height are pruned from the chain.
+const BRANCH_PRUNE_INTERVAL: u64 = 60;
+
+/// The maximum amount of bytes of blocks fetched from a peer chunk by chunk,
+/// so that a peer cannot exhaust the memory by sending chunks endlessly.
+const MAX_FETCHED_CHAIN_SIZE: usize = 512 * 1024 * 1024;
+
+/// The interval in seconds in which the pending transactions are announced to the sealers.
+const MEMPOOL_GOSSIP_INTERVAL: u64 = 10;
+
+/// The time in milliseconds to wait for a sealer to answer a heartbeat.
+const HEARTBEAT_TIMEOUT: u64 = 1000;
+
+/// The time in milliseconds to wait for a peer to accept a block broadcast by this node,
+/// which it answers once it verified the block.
+const BROADCAST_TIMEOUT: u64 = 10000;
+
+/// The amount of peers a sealed block is sent to in parallel.
+const MAX_PARALLEL_BROADCASTS: usize = 16;
+
+/// The time in seconds to wait before reconnecting a broken relay connection.
+const RELAY_RECONNECT_DELAY: u64 = 5;
+
+/// The interval in milliseconds in which the head is checked for changes to push to the subscribers of the turnout.
+const TURNOUT_POLL_INTERVAL: u64 = 500;
+
+/// The interval in milliseconds in which the shutdown checks
+/// whether all connections were drained.
+const DRAIN_POLL_INTERVAL: u64 = 50;
+
+/// Counts an incoming connection as in flight until it is dropped,
+/// i.e. until it is handled completely or its worker panicked.
+struct InFlightConnection(Arc<AtomicUsize>);
+
+impl InFlightConnection {
+    fn new(counter: &Arc<AtomicUsize>) -> InFlightConnection {
+        counter.fetch_add(1, Ordering::SeqCst);
+
+        InFlightConnection(Arc::clone(counter))
+    }
+}
+
+impl Drop for InFlightConnection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// The message to broadcast to each peer once its broadcast in flight is done, if any.
+/// Peers without an entry have no broadcast in flight.
+type PendingBroadcasts = Mutex<HashMap<SocketAddr, Option<Message>>>;
+
+/// The handles of a node shared by the workers handling incoming connections and RPC connections.
+#[derive(Clone)]
+struct ConnectionContext {
+    /// The protocol handling the requests.
+    clique_protocol_handler: Arc<Mutex<CliqueProtocol>>,
+    /// The cache sharing the responses to chain requests among all requesters.
+    chain_response_cache: Arc<ChainResponseCache>,
+    /// Enqueues the messages which must be broadcast as a consequence of a request.
+    broadcast_sender: Sender<Message>,
+    /// Accounts the requests until they are handled.
+    in_flight_messages: Arc<MemoryBudget>,
+    /// Accounts transactions with invalid proofs to their source.
+    ban_list: Arc<Mutex<BanList>>,
+    /// The size from which responses are compressed, if the requester accepts it.
+    compression_threshold: Option<usize>,
+}
 
 /// Forms a node in the blockchain.
 ///
@@ -31,12 +166,26 @@ pub struct Node {
     rpc_listen_address: SocketAddr,
 
     /// A fixed set of peers to which this node should connect
-    /// and broadcast messages to.
+    /// and broadcast messages to. Knows about the own address
+    /// of this node so that it never connects to itself.
     ///
     /// As this set is used among different threads, a
     /// atomic reference counter (ARC) and a Mutex are used
     /// to avoid concurrent overwrites.
-    peers: Arc<Mutex<HashSet<SocketAddr>>>,
+    peers: Arc<Mutex<PeerSet>>,
+
+    /// The connections kept open by peers unable to accept incoming
+    /// connections, over which messages to them are relayed.
+    relays: Arc<RelaySet>,
+
+    /// The transport used to send messages to other peers.
+    transport: Arc<Transport>,
+
+    /// The connectivity to each peer, as recorded by the transport and the heartbeats.
+    peer_health: Arc<PeerHealthTracker>,
+
+    /// The round trip times of the peers, by which the fastest ones are preferred to fetch blocks from.
+    latencies: Arc<Mutex<PeerLatencies>>,
 
     /// A protocol handling incoming messages to some
     /// specified behaviour.
@@ -45,6 +194,94 @@ pub struct Node {
     /// atomic reference counter (ARC) and a Mutex are used
     /// to avoid concurrent overwrites.
     protocol: Arc<Mutex<CliqueProtocol>>,
+
+    /// The pipeline verifying blocks received from other peers
+    /// before inserting them into the chain of the protocol.
+    block_pipeline: Arc<BlockPipeline>,
+
+    /// A cache of the encoded chain, shared among all
+    /// requesters of a copy of the chain.
+    chain_response_cache: Arc<ChainResponseCache>,
+
+    /// A queue of messages to be gossiped in batches to other peers.
+    /// None, if messages should be broadcast immediately.
+    gossip_queue: Option<Arc<GossipQueue>>,
+
+    /// The amount of workers handling incoming connections, per listener.
+    connection_workers: usize,
+
+    /// The tokens authorizing RPC requests.
+    /// None, if RPC requests do not require a token.
+    rpc_tokens: Option<Arc<RpcTokens>>,
+
+    /// The connections of RPC clients subscribed to the turnout.
+    turnout_subscriptions: Arc<TurnoutSubscriptions>,
+
+    /// The sending half of the queue of messages to be broadcast to other peers.
+    broadcast_sender: Sender<Message>,
+
+    /// The receiving half of the queue of messages to be broadcast to other peers.
+    /// Taken by the thread sending these messages.
+    broadcast_receiver: Mutex<Option<Receiver<Message>>>,
+
+    /// The signer producing signatures of blocks sealed by this node.
+    /// None, if blocks are not signed.
+    signer: Option<Arc<Signer>>,
+
+    /// Whether the listeners still accept incoming connections.
+    /// Cleared once the node shuts down.
+    accepting_connections: Arc<AtomicBool>,
+
+    /// Whether the periodic tasks of the node, e.g. sealing or heartbeats, keep running.
+    /// Cleared once the node shuts down, after the connections were drained.
+    is_running: Arc<AtomicBool>,
+
+    /// The address the dashboard listens on, woken up on shutdown. None, if no dashboard is served.
+    dashboard_address: Mutex<Option<SocketAddr>>,
+
+    /// The amount of incoming connections currently handled by the listeners.
+    in_flight_connections: Arc<AtomicUsize>,
+
+    /// The memory used by messages being received from peers and clients.
+    in_flight_messages: Arc<MemoryBudget>,
+
+    /// The sources banned for submitting transactions with invalid proofs,
+    /// whose connections are refused.
+    ban_list: Arc<Mutex<BanList>>,
+
+    /// The limits on the connections and messages of a single address on the peer listener.
+    peer_rate_limiter: Arc<RateLimiter>,
+
+    /// The limits on the connections and messages of a single address on the RPC listener.
+    rpc_rate_limiter: Arc<RateLimiter>,
+
+    /// The log notices for the operator are appended to, e.g. when sealing is paused.
+    audit_log: Arc<Mutex<AuditLog>>,
+
+    /// The NTP server the local clock is compared against additionally to the peers.
+    /// None, if the clock is only compared against the peers.
+    ntp_server: Option<String>,
+
+    /// The deviation of the local clock in milliseconds above which a warning is logged.
+    clock_warn_threshold: u64,
+
+    /// The size in bytes from which messages to peers negotiating a compression are compressed.
+    /// None, if messages are never compressed.
+    compression_threshold: Option<usize>,
+
+    /// The TLS context the connections between nodes are encrypted with.
+    /// None, if this node connects to other peers in plain text.
+    tls: Option<Arc<TlsContext>>,
+
+    /// The TLS context RPC connections are accepted with.
+    /// None, if RPC connections are accepted in plain text.
+    rpc_tls: Option<Arc<TlsContext>>,
+
+    /// The registry the thread pools of this node are reported in.
+    metrics: Arc<MetricsRegistry>,
+
+    /// The encoding of the blocks persisted in the data directory.
+    storage_encoding: StorageEncoding,
 }
 
 impl Node {
@@ -53,361 +290,2667 @@ impl Node {
     /// - `listen_addr` The address on which the node listens for incoming messages.
     /// - `rpc_listen_address` The address on which the node listens for incoming RPC messages.
     /// - `genesis` The genesis configuration which defines the behaviour of this node.
-    ///             Must be equal for all nodes which should connect to the same network.
-    pub fn new(listen_address: SocketAddr, rpc_listen_address: SocketAddr, genesis: Genesis) -> Node {
+    ///   Must be equal for all nodes which should connect to the same network.
+    /// - `node_config` The configuration specific to this node. If it specifies an advertised
+    ///   address, this one is used to identify the node among the sealers of the
+    ///   genesis configuration instead of `listen_address`.
+    pub fn new(listen_address: SocketAddr, rpc_listen_address: SocketAddr, genesis: Genesis, node_config: NodeConfig) -> Node {
+        let gossip_queue = node_config.gossip_max_delay.map(|max_delay| {
+            Arc::new(GossipQueue::new(max_delay, node_config.gossip_padding))
+        });
+        let own_address = node_config.advertised_address.unwrap_or(listen_address);
+        let peers = PeerSet::new(own_address, genesis.sealer.clone());
+        let relays = Arc::new(RelaySet::new(peers.clone()));
+        let block_validator = BlockValidator::new(&genesis);
+        let requires_signatures = !genesis.sealer_keys.is_empty();
+        let metrics = Arc::new(MetricsRegistry::new());
+        let in_flight_messages = Arc::new(MemoryBudget::new("in-flight messages", node_config.memory_caps.in_flight_messages));
+        let peer_health = Arc::new(PeerHealthTracker::new());
+        let transport = transport::create(&node_config, own_address, &genesis, &in_flight_messages, &peer_health);
+        let mut protocol = CliqueProtocol::new(own_address, genesis, node_config.version_policy);
+        protocol.set_metrics_registry(Arc::clone(&metrics));
+        protocol.set_peer_health(Arc::clone(&peer_health));
+        if let Some(admin_identity) = node_config.admin_identity.clone() {
+            protocol.set_admin_identity(admin_identity);
+        }
+        if let Some(key_share) = node_config.key_share.clone() {
+            protocol.set_key_share(key_share);
+        }
+        if let Some(mempool_cap) = node_config.memory_caps.mempool {
+            protocol.set_mempool_cap(mempool_cap);
+        }
+        protocol.set_log_filters(node_config.log_filters.clone());
+        let protocol = Arc::new(Mutex::new(protocol));
+        let block_pipeline = Arc::new(BlockPipeline::new(Arc::clone(&protocol), block_validator, node_config.verification_workers, &metrics));
+        let (broadcast_sender, broadcast_receiver) = mpsc::channel();
+        let signer: Option<Arc<Signer>> = match (node_config.external_signer.clone(), node_config.node_key.clone()) {
+            (Some(socket_path), _) => Some(Arc::new(UnixSocketSigner::new(socket_path, time::Duration::from_millis(EXTERNAL_SIGNER_TIMEOUT)))),
+            (None, Some(node_key)) => Some(Arc::new(node_key)),
+            (None, None) => None
+        };
+        if requires_signatures && signer.is_none() {
+            warn!("The genesis configuration requires blocks to be signed, but neither a node key nor an external signer is configured. Blocks sealed by this node will be rejected by its peers");
+        }
+        let peer_rate_limiter = Arc::new(RateLimiter::new("peer", node_config.peer_rate_limits));
+        metrics.register_rate_limiter(&peer_rate_limiter);
+        let rpc_rate_limiter = Arc::new(RateLimiter::new("rpc", node_config.rpc_rate_limits));
+        metrics.register_rate_limiter(&rpc_rate_limiter);
+
+        // each long running task started by the node, e.g. `listen` or `heartbeat`, occupies one worker
+        let thread_pool = ThreadPool::new("node", 14);
+        thread_pool.abort_on_poisoned("protocol", &protocol);
+        metrics.register_thread_pool(thread_pool.get_stats());
+
         Node {
-            thread_pool: ThreadPool::new(4),
+            thread_pool,
             listen_address: listen_address.clone(),
             rpc_listen_address: rpc_listen_address.clone(),
-            peers: Arc::new(Mutex::new(HashSet::from_iter(genesis.sealer.iter().cloned()))),
-            protocol: Arc::new(Mutex::new(CliqueProtocol::new(listen_address, genesis))),
+            peers: Arc::new(Mutex::new(peers)),
+            relays,
+            transport,
+            peer_health,
+            latencies: Arc::new(Mutex::new(PeerLatencies::new())),
+            protocol,
+            block_pipeline,
+            chain_response_cache: Arc::new(ChainResponseCache::new(node_config.memory_caps.chain_cache)),
+            gossip_queue,
+            connection_workers: node_config.connection_workers,
+            rpc_tokens: node_config.rpc_tokens.clone().map(Arc::new),
+            turnout_subscriptions: Arc::new(TurnoutSubscriptions::new()),
+            broadcast_sender,
+            broadcast_receiver: Mutex::new(Some(broadcast_receiver)),
+            signer,
+            accepting_connections: Arc::new(AtomicBool::new(true)),
+            is_running: Arc::new(AtomicBool::new(true)),
+            dashboard_address: Mutex::new(None),
+            in_flight_connections: Arc::new(AtomicUsize::new(0)),
+            in_flight_messages,
+            ban_list: Arc::new(Mutex::new(BanList::new())),
+            peer_rate_limiter,
+            rpc_rate_limiter,
+            audit_log: Arc::new(Mutex::new(AuditLog::new())),
+            ntp_server: node_config.ntp_server.clone(),
+            clock_warn_threshold: node_config.clock_warn_threshold,
+            compression_threshold: node_config.compression_threshold,
+            tls: node_config.tls.clone(),
+            rpc_tls: node_config.rpc_tls.clone(),
+            metrics,
+            storage_encoding: node_config.storage_encoding,
         }
     }
 
     /// Start a listener on the bootstrap address.
     ///
-    /// Read all bytes until EOF (when underlying socket is closed) from the given stream
-    /// and return a message back to the incoming sender.
-    /// Then close the stream in order to signal EOF for the receiving node.
+    /// Each incoming connection is handled on a dedicated worker,
+    /// so that slow peers do not delay connections of others.
+    /// If TLS is configured, connections starting a TLS handshake are accepted with TLS,
+    /// others in plain text, e.g. the ones of `probe`.
+    /// Messages received by the transport itself, e.g. over libp2p, are handled as well.
     pub fn listen(&self) {
+        if let Some(incoming_messages) = self.transport.take_incoming() {
+            self.listen_transport(incoming_messages);
+        }
+
         let listener = TcpListener::bind(&self.listen_address).unwrap();
         info!("Listening for incoming connections on {:?}", listener.local_addr());
         // clone the mutex of the chain
-        let clique_protocol_handler = Arc::clone(&self.protocol);
+        let context = self.get_connection_context();
+        let block_pipeline = Arc::clone(&self.block_pipeline);
+        let relays = Arc::clone(&self.relays);
+        let connection_workers = self.connection_workers;
+        let accepting_connections = Arc::clone(&self.accepting_connections);
+        let in_flight_connections = Arc::clone(&self.in_flight_connections);
+        let transport = Arc::clone(&self.transport);
+        let tls = self.tls.clone();
+        let rate_limiter = Arc::clone(&self.peer_rate_limiter);
+        let metrics = Arc::clone(&self.metrics);
+
+        self.thread_pool.execute("listen", move || {
+            let connection_pool = ThreadPool::new("connections", connection_workers);
+            connection_pool.abort_on_poisoned("protocol", &context.clique_protocol_handler);
+            metrics.register_thread_pool(connection_pool.get_stats());
 
-        self.thread_pool.execute(move || {
             for stream in listener.incoming() {
-                let mut cloned_stream = stream.unwrap().try_clone().unwrap();
-                let cloned_clique_protocol_handler = Arc::clone(&clique_protocol_handler);
+                if !accepting_connections.load(Ordering::SeqCst) {
+                    info!("Stopped accepting incoming connections");
+                    break;
+                }
+
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        trace!("Failed to accept incoming connection: {:?}", e);
+                        continue;
+                    }
+                };
+
+                if Node::is_banned(&context.ban_list, &stream) {
+                    trace!("Refusing incoming connection from banned {:?}", stream.peer_addr());
+                    continue;
+                }
+
+                if !Node::set_incoming_timeouts(&stream) {
+                    continue;
+                }
 
-                trace!("Got incoming stream on {:?} from {:?}", cloned_stream.local_addr(), cloned_stream.peer_addr());
-
-                // TODO: Drop connection if not from authorized node
-
-                let mut buffer_str = String::new();
-                let result = cloned_stream.read_to_string(&mut buffer_str);
-                match result {
-                    Ok(amount_bytes_received) => {
-                        if 0 == amount_bytes_received {
-                            trace!("No bytes received on incoming connection. Dropping connection without response");
-                            let shutdown_result = cloned_stream.shutdown(Shutdown::Both);
-                            match shutdown_result {
-                                Ok(()) => {}
-                                Err(e) => {
-                                    trace!("Failed to shutdown incoming connection: {:?}", e);
-                                }
+                let connection_permit = match Node::admit(&rate_limiter, &stream) {
+                    Some(connection_permit) => connection_permit,
+                    None => {
+                        continue;
+                    }
+                };
+
+                let cloned_context = context.clone();
+                let cloned_block_pipeline = Arc::clone(&block_pipeline);
+                let cloned_relays = Arc::clone(&relays);
+                let cloned_transport = Arc::clone(&transport);
+                let cloned_tls = tls.clone();
+                let in_flight_connection = InFlightConnection::new(&in_flight_connections);
+
+                connection_pool.execute("connection", move || {
+                    let _in_flight_connection = in_flight_connection;
+                    let _connection_permit = connection_permit;
+                    // the handshake is done by the worker, so that slow peers do not delay the listener
+                    let stream = match cloned_tls {
+                        Some(ref tls) => match tls.accept(stream) {
+                            Ok(stream) => stream,
+                            Err(e) => {
+                                debug!("Dropping incoming connection: {}", e);
+                                return;
                             }
+                        },
+                        None => PeerStream::Plain(stream)
+                    };
+                    Node::handle_incoming_connection(stream, &cloned_context, &cloned_block_pipeline, &cloned_relays, &*cloned_transport);
+                });
+            }
+        });
+    }
 
-                            return;
-                        }
+    /// Handle the given messages received by the transport itself, each on a dedicated worker like
+    /// the connections accepted by `listen`. Admin commands and relay registrations are only
+    /// accepted by the listener, as they require a connection to the node.
+    fn listen_transport(&self, incoming_messages: Receiver<IncomingMessage>) {
+        let clique_protocol_handler = Arc::clone(&self.protocol);
+        let chain_response_cache = Arc::clone(&self.chain_response_cache);
+        let block_pipeline = Arc::clone(&self.block_pipeline);
+        let connection_workers = self.connection_workers;
+        let accepting_connections = Arc::clone(&self.accepting_connections);
+        let in_flight_connections = Arc::clone(&self.in_flight_connections);
+        let metrics = Arc::clone(&self.metrics);
+
+        self.thread_pool.execute("listen-transport", move || {
+            let message_pool = ThreadPool::new("transport-messages", connection_workers);
+            message_pool.abort_on_poisoned("protocol", &clique_protocol_handler);
+            metrics.register_thread_pool(message_pool.get_stats());
+
+            for incoming_message in incoming_messages.iter() {
+                if !accepting_connections.load(Ordering::SeqCst) {
+                    info!("Stopped accepting messages of the transport");
+                    break;
+                }
+
+                let cloned_clique_protocol_handler = Arc::clone(&clique_protocol_handler);
+                let cloned_chain_response_cache = Arc::clone(&chain_response_cache);
+                let cloned_block_pipeline = Arc::clone(&block_pipeline);
+                let in_flight_connection = InFlightConnection::new(&in_flight_connections);
+
+                message_pool.execute("transport-message", move || {
+                    let _in_flight_connection = in_flight_connection;
+                    let IncomingMessage { source, message, responder } = incoming_message;
+                    trace!("Got message {:?} from {:?} over the transport", message, source);
+                    let response = match message {
+                        Message::ChainChunkRequest(token) => Message::ChainChunkResponse(cloned_chain_response_cache.get_chain_chunk(&cloned_clique_protocol_handler, token)),
+                        Message::BlockRangeRequest { from_height, to_height } => Message::BlockRangeResponse(cloned_chain_response_cache.get_block_range(&cloned_clique_protocol_handler, from_height, to_height)),
+                        // verify the block without holding the lock of the protocol
+                        Message::BlockPayload(block) => cloned_block_pipeline.submit(block),
+                        Message::AdminRequest(_) | Message::RelayRegister(_) => Message::None,
+                        message => cloned_clique_protocol_handler.lock().unwrap().handle(message)
+                    };
+                    if let Some(responder) = responder {
+                        responder.respond(response);
                     }
-                    Err(e) => {
-                        trace!("Failed to read bytes from incoming connection: {:?}", e);
+                });
+            }
+        });
+    }
 
-                        return;
+    /// Read all bytes until EOF (when underlying socket is closed) from the given stream
+    /// and return a message back to the incoming sender.
+    /// Then close the stream in order to signal EOF for the receiving node.
+    ///
+    /// Connections from sources which are neither sealers nor candidates of the canonical chain
+    /// nor the local host are dropped. Blocks are handed over to the given pipeline. Messages which
+    /// must be broadcast as a consequence of an admin command are enqueued on the sender of the context.
+    /// Connections of peers registering as relay are kept open in the given relay set.
+    /// The message is accounted in the budget of the context until it is handled.
+    /// Transactions with invalid proofs are accounted to their source in the ban list of the context.
+    /// The response is encoded with the codec of the request and compressed from the threshold of the context on,
+    /// if the requester accepts it. The codec and the compression negotiated in the handshake
+    /// of a peer are used by the given transport for the requests to the peer.
+    fn handle_incoming_connection(mut stream: PeerStream, context: &ConnectionContext, block_pipeline: &BlockPipeline, relays: &RelaySet, transport: &Transport) {
+        trace!("Got incoming stream on {:?} from {:?}", stream.local_addr(), stream.peer_addr());
+        let ConnectionContext { ref clique_protocol_handler, ref chain_response_cache, ref broadcast_sender, ref in_flight_messages, ref ban_list, compression_threshold } = *context;
+
+        let source = match stream.peer_addr() {
+            Ok(source) => source,
+            Err(e) => {
+                trace!("Dropping incoming connection of unknown source: {:?}", e);
+                return;
+            }
+        };
+        let sealer_set = clique_protocol_handler.lock().unwrap().get_sealer_set();
+        if !Node::is_authorized_source(&sealer_set, &source) {
+            debug!("Dropping incoming connection from {:?}, which is not an authorized node", source);
+            return;
+        }
+
+        let (buffer, mut reservation) = match Node::read_incoming_message(&mut stream, in_flight_messages) {
+            Some(message) => message,
+            None => {
+                return;
+            }
+        };
+
+        let (encoded_request, response_compression) = match Node::open_incoming_message(&stream, &buffer, &mut reservation, compression_threshold) {
+            Some(opened) => opened,
+            None => {
+                return;
+            }
+        };
+
+        let (request, codec) = codec::decode(&encoded_request);
+        trace!("Got request message {:?} from {:?}", request.clone(), stream.peer_addr());
+        let encoded_response = match request {
+            Message::ChainRequest => {
+                // share the serialized chain among all requesters
+                trace!("Sending chain response to {:?}", stream.peer_addr());
+                chain_response_cache.get_encoded_chain_response(clique_protocol_handler, codec, response_compression)
+            }
+            Message::ChainChunkRequest(token) => {
+                // serve all chunks from the same snapshot of the chain
+                let chunk = chain_response_cache.get_chain_chunk(clique_protocol_handler, token);
+                trace!("Sending chain chunk of {} blocks to {:?}", chunk.blocks.len(), stream.peer_addr());
+                Arc::new(Node::encode_response(Message::ChainChunkResponse(chunk), codec, response_compression))
+            }
+            Message::BlockRangeRequest { from_height, to_height } => {
+                // serve all ranges from the same snapshot of the canonical chain
+                let block_range = chain_response_cache.get_block_range(clique_protocol_handler, from_height, to_height);
+                trace!("Sending {} blocks from height {} to {:?}", block_range.blocks.len(), from_height, stream.peer_addr());
+                Arc::new(Node::encode_response(Message::BlockRangeResponse(block_range), codec, response_compression))
+            }
+            Message::BlockPayload(block) => {
+                // verify the block without holding the lock of the protocol
+                let response = block_pipeline.submit(block);
+                trace!("Sending response message {:?} to {:?}", response.clone(), stream.peer_addr());
+                Arc::new(Node::encode_response(response, codec, response_compression))
+            }
+            Message::AdminRequest(admin_request) => {
+                let (response, broadcast_response) = clique_protocol_handler.lock().unwrap().handle_admin_request(admin_request);
+
+                if Message::None != broadcast_response {
+                    trace!("Enqueue admin command message {:?} for broadcasting", broadcast_response.clone());
+                    match broadcast_sender.send(broadcast_response) {
+                        Ok(()) => {}
+                        Err(e) => {
+                            warn!("Failed to enqueue message for broadcasting: {:?}", e);
+                        }
                     }
                 }
 
-                let request = JsonCodec::decode(buffer_str);
-                trace!("Got request message {:?} from {:?}", request.clone(), cloned_stream.peer_addr());
-                let response = cloned_clique_protocol_handler.lock().unwrap().handle(request);
-                trace!("Sending response message {:?} to {:?}", response.clone(), cloned_stream.peer_addr());
-                let encoded_response = JsonCodec::encode(response);
-
-                // send some data back
-                let mut stream_clone = cloned_stream.try_clone().unwrap();
-                stream_clone.write_all(&encoded_response.into_bytes()).unwrap();
-                stream_clone.flush().unwrap();
+                Arc::new(Node::encode_response(response, codec, response_compression))
+            }
+            Message::RelayRegister(address) => {
+                let remote_address = stream.peer_addr();
+                // once registered, relayed messages are sent over the stream instead of a response
+                if let Err(e) = relays.register(address, stream) {
+                    warn!("Rejecting relay registration of {:?}: {}", remote_address, e);
+                }
 
-                let shutdown_result = stream_clone.shutdown(Shutdown::Read);
-                match shutdown_result {
-                    Ok(()) => {}
-                    // happens when the peer already closed the connection
-                    Err(ref e) if e.kind() == ErrorKind::NotConnected => {}
-                    Err(e) => { trace!("Could not shutdown incoming connection: {:?}", e) }
+                return;
+            }
+            Message::Handshake(handshake) => {
+                let peer_addr = handshake.address;
+                // the envelope of the response announces the compression chosen among the ones of the peer
+                let negotiated_compression = compression_threshold.and_then(|threshold| {
+                    CompressionKind::negotiate(&handshake.compressions).map(|compression| (compression, threshold))
+                });
+                let response = clique_protocol_handler.lock().unwrap().handle(Message::Handshake(handshake));
+                match response {
+                    Message::HandshakeAcceptCodec(ref name) => {
+                        // the negotiated codec is always one supported by this node
+                        if let Ok(negotiated_codec) = name.parse() {
+                            transport.use_codec(&peer_addr, negotiated_codec);
+                        }
+                        transport.use_compression(&peer_addr, negotiated_compression.map(|(compression, _)| compression));
+                    }
+                    // the peer may have been restarted with a version not announcing any codecs
+                    Message::HandshakeAccept => {
+                        transport.use_codec(&peer_addr, CodecKind::Json);
+                        transport.use_compression(&peer_addr, negotiated_compression.map(|(compression, _)| compression));
+                    }
+                    _ => {}
                 }
+                trace!("Sending response message {:?} to {:?}", response.clone(), stream.peer_addr());
+                Arc::new(Node::encode_response(response, codec, negotiated_compression))
             }
-        });
+            _ => {
+                let response = clique_protocol_handler.lock().unwrap().handle(request);
+                Node::account_response(ban_list, &stream, &response);
+                trace!("Sending response message {:?} to {:?}", response.clone(), stream.peer_addr());
+                Arc::new(Node::encode_response(response, codec, response_compression))
+            }
+        };
+
+        Node::write_response(&mut stream, &encoded_response);
     }
 
     /// Start to listen for incoming RPC connections, i.e. connections from an end-user client.
     /// Compared to `pub fn listen(&self)`, incoming messages may be handled a bit differently.
+    ///
+    /// Each incoming connection is handled on a dedicated worker, so that
+    /// multiple clients may submit their transactions concurrently. Messages which
+    /// must be broadcast to other peers are handed over to the thread started by
+    /// `pub fn gossip(&self)` instead of being sent by the worker itself.
+    /// If TLS is configured for RPC, connections not starting a TLS handshake are refused.
     pub fn listen_rpc(&self) {
         let rpc_listener = TcpListener::bind(&self.rpc_listen_address).unwrap();
         info!("Listening for incoming RPC connections on {:?}", rpc_listener.local_addr());
 
-        let cloned_clique_protocol_handler = Arc::clone(&self.protocol);
-        let known_peers = Arc::clone(&self.peers);
-        let own_address = self.listen_address.clone();
+        let context = self.get_connection_context();
+        let rpc_tokens = self.rpc_tokens.clone();
+        let rpc_tls = self.rpc_tls.clone();
+        let rate_limiter = Arc::clone(&self.rpc_rate_limiter);
+        let turnout_subscriptions = Arc::clone(&self.turnout_subscriptions);
+        let connection_workers = self.connection_workers;
+        let accepting_connections = Arc::clone(&self.accepting_connections);
+        let in_flight_connections = Arc::clone(&self.in_flight_connections);
+        let metrics = Arc::clone(&self.metrics);
+
+        self.thread_pool.execute("listen-rpc", move || {
+            let connection_pool = ThreadPool::new("rpc-connections", connection_workers);
+            connection_pool.abort_on_poisoned("protocol", &context.clique_protocol_handler);
+            metrics.register_thread_pool(connection_pool.get_stats());
+
+            // updates are pushed by a dedicated worker, so that subscriptions do not occupy connection workers
+            let turnout_pool = ThreadPool::new("turnout", 1);
+            metrics.register_thread_pool(turnout_pool.get_stats());
+            let cloned_clique_protocol_handler = Arc::clone(&context.clique_protocol_handler);
+            let cloned_turnout_subscriptions = Arc::clone(&turnout_subscriptions);
+            let cloned_accepting_connections = Arc::clone(&accepting_connections);
+            turnout_pool.execute("turnout", move || {
+                Node::publish_turnout(&cloned_clique_protocol_handler, &cloned_turnout_subscriptions, &cloned_accepting_connections);
+            });
 
-        self.thread_pool.execute(move || {
             for incoming_stream in rpc_listener.incoming() {
-                let mut stream = incoming_stream.unwrap();
-
-                trace!("Handling incoming RPC stream on {:?} from {:?}", stream.local_addr(), stream.peer_addr());
-
-                let mut buffer_str = String::new();
-                let result = stream.read_to_string(&mut buffer_str);
-                match result {
-                    Ok(amount_bytes_received) => {
-                        if 0 == amount_bytes_received {
-                            trace!("No bytes received on incoming connection. Dropping connection without response");
-                            let shutdown_result = stream.shutdown(Shutdown::Both);
-                            match shutdown_result {
-                                Ok(()) => {}
-                                Err(e) => {
-                                    trace!("Failed to shutdown incoming connection: {:?}", e);
-                                }
-                            }
+                if !accepting_connections.load(Ordering::SeqCst) {
+                    info!("Stopped accepting incoming RPC connections");
+                    break;
+                }
 
-                            return;
-                        }
-                    }
+                let stream = match incoming_stream {
+                    Ok(stream) => stream,
                     Err(e) => {
-                        trace!("Failed to read bytes from incoming connection: {:?}", e);
-
-                        return;
+                        trace!("Failed to accept incoming RPC connection: {:?}", e);
+                        continue;
                     }
+                };
+
+                if Node::is_banned(&context.ban_list, &stream) {
+                    trace!("Refusing incoming RPC connection from banned {:?}", stream.peer_addr());
+                    continue;
                 }
 
-                let request = JsonCodec::decode(buffer_str);
-                trace!("Got RPC request message {:?} from {:?}", request.clone(), stream.peer_addr());
-                let needs_response = cloned_clique_protocol_handler.lock().unwrap().handle_rpc(request);
+                if !Node::set_incoming_timeouts(&stream) {
+                    continue;
+                }
 
-                match needs_response {
+                let connection_permit = match Node::admit(&rate_limiter, &stream) {
+                    Some(connection_permit) => connection_permit,
                     None => {
-                        trace!("RPC does not require any response. Closing stream to {:?}", stream.peer_addr());
-                        let shutdown_result = stream.shutdown(Shutdown::Both);
-                        match shutdown_result {
-                            Ok(()) => {}
-                            // happens when the peer already closed the connection
-                            Err(ref e) if e.kind() == ErrorKind::NotConnected => {}
-                            Err(e) => { trace!("Could not shutdown incoming RPC connection to {:?}: {:?}", stream.peer_addr(), e) }
-                        }
+                        continue;
                     }
-                    Some((response, broadcast_response)) => {
-                        trace!("Sending RPC response message {:?} to {:?}", response.clone(), stream.peer_addr());
-                        let encoded_response = JsonCodec::encode(response);
-
-                        // send some data back
-                        let mut stream_clone = stream.try_clone().unwrap();
-                        stream_clone.write_all(&encoded_response.into_bytes()).unwrap();
-                        stream_clone.flush().unwrap();
-
-                        let shutdown_result = stream_clone.shutdown(Shutdown::Read);
-                        match shutdown_result {
-                            Ok(()) => {}
-                            // happens when the peer already closed the connection
-                            Err(ref e) if e.kind() == ErrorKind::NotConnected => {}
-                            Err(e) => { trace!("Could not shutdown incoming connection: {:?}", e) }
-                        }
+                };
 
-                        // now broadcast the message to all other peers
-                        trace!("Broadcast RPC handler message {:?}", broadcast_response.clone());
-                        for peer_addr in known_peers.lock().unwrap().iter() {
-                            if own_address.eq(peer_addr) {
-                                // avoid connecting to ourselves
-                                continue;
-                            }
+                let cloned_context = context.clone();
+                let cloned_rpc_tokens = rpc_tokens.clone();
+                let cloned_turnout_subscriptions = Arc::clone(&turnout_subscriptions);
+                let cloned_rpc_tls = rpc_tls.clone();
+                let in_flight_connection = InFlightConnection::new(&in_flight_connections);
 
-                            let stream = TcpStream::connect(&peer_addr);
-
-                            match stream {
-                                Ok(mut stream) => {
-                                    Node::handle_outgoing_connection(&mut stream, broadcast_response.clone());
-                                }
-                                Err(e) => {
-                                    warn!("Failed to connect to {:?} due to {:?}", peer_addr, e);
-                                }
+                connection_pool.execute("rpc-connection", move || {
+                    let _in_flight_connection = in_flight_connection;
+                    let _connection_permit = connection_permit;
+                    let stream = match cloned_rpc_tls {
+                        // plain text connections are refused
+                        Some(ref rpc_tls) => match rpc_tls.accept(stream) {
+                            Ok(stream) => stream,
+                            Err(e) => {
+                                debug!("Dropping incoming RPC connection: {}", e);
+                                return;
                             }
-                        }
-                    }
-                }
+                        },
+                        None => PeerStream::Plain(stream)
+                    };
+                    Node::handle_incoming_rpc_connection(stream, &cloned_context, &cloned_rpc_tokens, &cloned_turnout_subscriptions);
+                });
             }
         });
     }
 
-    /// Send a request for a copy of the blockchain to all known nodes.
-    pub fn request_chain_copy(&mut self) {
-        // create a reference which we can share across threads
-        let peers = Arc::clone(&self.peers);
+    /// Push the turnout to its subscribers whenever the head of the canonical chain changes,
+    /// until the node stops accepting connections.
+    fn publish_turnout(clique_protocol_handler: &Arc<Mutex<CliqueProtocol>>, turnout_subscriptions: &TurnoutSubscriptions, accepting_connections: &AtomicBool) {
+        let mut published_head = None;
+        while accepting_connections.load(Ordering::SeqCst) {
+            thread::sleep(time::Duration::from_millis(TURNOUT_POLL_INTERVAL));
 
-        for peer_addr in peers.lock().unwrap().iter() {
-            if self.listen_address.eq(peer_addr) {
-                // avoid connecting to ourselves
+            // subscribers receive the current turnout when subscribing
+            if turnout_subscriptions.is_empty() {
+                published_head = None;
                 continue;
             }
 
-            let stream = TcpStream::connect(&peer_addr);
-            let protocol = Arc::clone(&self.protocol);
-
-            match stream {
-                Ok(mut stream) => {
-                    trace!("Successfully connected to {:?}", stream.peer_addr());
-
-                    // request the chain of the other node
-                    let response = Node::handle_outgoing_connection(&mut stream, Message::ChainRequest);
-                    match response {
-                        Some(message) => {
-                            protocol.lock().unwrap().handle(message);
-                        },
-                        None => {
-                            // noop
-                        }
-                    }
-                }
-                Err(e) => {
-                    warn!("Failed to connect to {:?} due to {:?}", peer_addr, e);
-                }
+            let head = clique_protocol_handler.lock().unwrap().get_head().map(|head| head.block.identifier);
+            if head == published_head {
+                continue;
             }
+
+            let turnout = clique_protocol_handler.lock().unwrap().get_turnout();
+            trace!("Push turnout of {} votes at height {} to subscribers", turnout.total_votes, turnout.height);
+            turnout_subscriptions.publish(&turnout);
+            published_head = head;
         }
     }
 
-    /// Start the main loop to sign (aka. mint) blocks in the network.
-    pub fn sign(&mut self) {
-        let clique_protocol_handler = Arc::clone(&self.protocol);
-        // create a reference which we can share across threads
-        let peers = Arc::clone(&self.peers);
-        let own_address = self.listen_address.clone();
+    /// Handle a single RPC connection and enqueue any message which
+    /// must be broadcast to other peers as a consequence.
+    ///
+    /// If tokens are given, the request is refused unless it presents
+    /// a token granting the scope of its method. The request is accounted in the
+    /// budget of the context until it is handled. Transactions with invalid proofs are
+    /// accounted to their source in the ban list of the context.
+    /// Connections subscribing to the turnout are kept open in the given subscriptions.
+    /// Responses are compressed from the threshold of the context on, if the client accepts it.
+    fn handle_incoming_rpc_connection(mut stream: PeerStream, context: &ConnectionContext, rpc_tokens: &Option<Arc<RpcTokens>>, turnout_subscriptions: &TurnoutSubscriptions) {
+        trace!("Handling incoming RPC stream on {:?} from {:?}", stream.local_addr(), stream.peer_addr());
+        let ConnectionContext { ref clique_protocol_handler, ref chain_response_cache, ref broadcast_sender, ref in_flight_messages, ref ban_list, compression_threshold } = *context;
 
-        self.thread_pool.execute(move || {
-            let mut has_logged_signed_recently = false;
+        let (buffer, mut reservation) = match Node::read_incoming_message(&mut stream, in_flight_messages) {
+            Some(message) => message,
+            None => {
+                return;
+            }
+        };
 
-            loop {
-                // start with waiting
-                thread::sleep(time::Duration::from_millis(1000));
+        let (encoded_request, response_compression) = match Node::open_incoming_message(&stream, &buffer, &mut reservation, compression_threshold) {
+            Some(opened) => opened,
+            None => {
+                return;
+            }
+        };
 
-                // check whether we have to do something
-                let is_leader = clique_protocol_handler.lock().unwrap().is_leader();
-                let is_co_leader = clique_protocol_handler.lock().unwrap().is_co_leader();
-                if ! is_leader  && ! is_co_leader {
-                    // any transactions a node may have must now be reset
-                    clique_protocol_handler.lock().unwrap().reset_transaction_buffer();
+        let (request, codec) = codec::decode(&encoded_request);
+        trace!("Got RPC request message {:?} from {:?}", request.clone(), stream.peer_addr());
 
-                    // this is just to reduce log output spamming
-                    if ! has_logged_signed_recently {
-                        debug!("Signed recently, must wait for others...");
-                        has_logged_signed_recently = true;
+        let request = match (rpc_tokens, request) {
+            (Some(rpc_tokens), request) => {
+                match rpc_tokens.authorize(request) {
+                    Ok(method) => method,
+                    Err(reason) => {
+                        warn!("Refusing RPC request from {:?}: {}", stream.peer_addr(), reason);
+                        let encoded_response = Node::encode_response(Message::RpcUnauthorized(reason), codec, response_compression);
+                        Node::write_response(&mut stream, &encoded_response);
+
+                        return;
                     }
-                    continue;
                 }
-                // reset so that we get notified again...
-                has_logged_signed_recently = false;
+            }
+            // tokens are not required, but clients may present them nevertheless
+            (None, Message::RpcAuthorized(_, method)) => *method,
+            (None, request) => request
+        };
 
-                if !clique_protocol_handler.lock().unwrap().is_block_period_over() {
-                    continue;
-                }
+        if Message::TurnoutSubscribe == request {
+            let remote_address = stream.peer_addr();
+            let turnout = clique_protocol_handler.lock().unwrap().get_turnout();
+            // once subscribed, updates are pushed over the stream instead of a single response
+            if let Err(e) = turnout_subscriptions.subscribe(stream, turnout) {
+                warn!("Refusing turnout subscription of {:?}: {}", remote_address, e);
+            }
 
-                let current_block = clique_protocol_handler.lock().unwrap().create_current_block_and_reset_transaction_buffer();
+            return;
+        }
 
-                // check whether we are a co-leader and must wait to sign the block
-                // for some time...
-                if clique_protocol_handler.lock().unwrap().is_co_leader() {
-                    debug!("I am co-leader and therefore adding wiggle before signing block {:?}", current_block.identifier.clone());
-                    // add some "wiggle" time to let leader nodes announce their blocks first
-                    thread::sleep(time::Duration::from_millis(1000));
-                }
+        if Message::ChainRequest == request {
+            // share the serialized chain among all requesters
+            trace!("Sending RPC chain response to {:?}", stream.peer_addr());
+            let encoded_response = chain_response_cache.get_encoded_chain_response(clique_protocol_handler, codec, response_compression);
+            Node::write_response(&mut stream, &encoded_response);
 
-                info!("Signing block {:?}", current_block.identifier.clone());
-                let block_to_broadcast = clique_protocol_handler.lock().unwrap().sign(current_block);
+            return;
+        }
 
-                match block_to_broadcast {
-                    None => {
-                        // noop
-                    }
-                    Some(block) => {
-                        info!("Broadcasting block {:?}", block.identifier.clone());
-                        let cloned_peers = Arc::clone(&peers);
-                        // broadcast new block
-                        for peer_addr in cloned_peers.lock().unwrap().iter() {
-                            if own_address.clone().eq(peer_addr) {
-                                // avoid connecting to ourselves
-                                continue;
-                            }
+        if let Message::BlockRangeRequest { from_height, to_height } = request {
+            // serve all ranges from the same snapshot of the canonical chain
+            let block_range = chain_response_cache.get_block_range(clique_protocol_handler, from_height, to_height);
+            trace!("Sending RPC block range of {} blocks to {:?}", block_range.blocks.len(), stream.peer_addr());
+            Node::write_response(&mut stream, &Node::encode_response(Message::BlockRangeResponse(block_range), codec, response_compression));
 
-                            let stream = TcpStream::connect(&peer_addr);
+            return;
+        }
 
-                            match stream {
-                                Ok(mut stream) => {
-                                    trace!("Successfully connected to {:?}", stream.peer_addr());
+        let needs_response = clique_protocol_handler.lock().unwrap().handle_rpc(request);
 
-                                    Node::handle_outgoing_connection(&mut stream, Message::BlockPayload(block.clone()));
-                                }
-                                Err(e) => {
-                                    warn!("Failed to connect to {:?} due to {:?}", peer_addr, e);
-                                }
-                            }
-                        }
-                    }
+        match needs_response {
+            None => {
+                trace!("RPC does not require any response. Closing stream to {:?}", stream.peer_addr());
+                let shutdown_result = stream.shutdown(Shutdown::Both);
+                match shutdown_result {
+                    Ok(()) => {}
+                    // happens when the peer already closed the connection
+                    Err(ref e) if e.kind() == ErrorKind::NotConnected => {}
+                    Err(e) => { trace!("Could not shutdown incoming RPC connection to {:?}: {:?}", stream.peer_addr(), e) }
                 }
+            }
+            Some((response, broadcast_response)) => {
+                Node::account_response(ban_list, &stream, &response);
+                trace!("Sending RPC response message {:?} to {:?}", response.clone(), stream.peer_addr());
+                let encoded_response = Node::encode_response(response, codec, response_compression);
+                Node::write_response(&mut stream, &encoded_response);
 
-                // Scenario is as follows:
-                // - I am co-leader
-                // - I receive a transaction -> add transaction to buffer
-                // - I receive a block from leader containing that transaction
-                // - I'm the leader now, and still have the transaction im my buffer
-                // - I create a block containing that transaction again.
-                // => two different blocks with the same transaction in them
-
-                // Idea on how to prevent this here instead while receiving the block:
-                // Check whether we have the contained transactions of the block already in our buffer
-                // eventually, reset the transaction buffer again as we might have received new
-                // transactions while we were signing. Since we might not be a a co-leader
-                // or leader again -> remove them.
+                if Message::None == broadcast_response {
+                    return;
+                }
 
-                // => This will cause data loss in the following case:
-                // - we receive a transaction during our signing process as co-leader
-                // - of course, this transaction is not contained in the block
-                // - resetting the transaction buffer will also cause the new one to be removed
-                // - we are missing a transaction as leader.
+                // now broadcast the message to all other peers
+                trace!("Enqueue RPC handler message {:?} for broadcasting", broadcast_response.clone());
+                match broadcast_sender.send(broadcast_response) {
+                    Ok(()) => {}
+                    Err(e) => {
+                        warn!("Failed to enqueue message for broadcasting: {:?}", e);
+                    }
+                }
             }
-        });
+        }
     }
 
-    fn handle_outgoing_connection(stream: &mut TcpStream, message: Message) -> Option<Message> {
-        let request = JsonCodec::encode(message);
+    /// Admit the given incoming connection with the given rate limiter.
+    /// Returns None if the connection must be dropped as its source exceeds a limit.
+    fn admit(rate_limiter: &Arc<RateLimiter>, stream: &TcpStream) -> Option<ConnectionPermit> {
+        let peer_addr = match stream.peer_addr() {
+            Ok(peer_addr) => peer_addr,
+            Err(e) => {
+                trace!("Failed to determine the source of incoming connection: {:?}", e);
+                return None;
+            }
+        };
 
-        stream.write_all(&request.into_bytes()).unwrap();
-        stream.flush().unwrap();
-        let shutdown_result = stream.shutdown(Shutdown::Write);
-        match shutdown_result {
-            Ok(()) => {}
+        match RateLimiter::admit(rate_limiter, peer_addr.ip(), time::Instant::now()) {
+            Ok(connection_permit) => Some(connection_permit),
             Err(e) => {
-                trace!("Could not shutdown outgoing write connection: {:?}", e);
+                trace!("Dropping incoming connection: {}", e);
+                None
+            }
+        }
+    }
 
-                return None;
+    /// Bound the time to wait for the remote end of the given accepted connection when reading from
+    /// and writing to it, so that a stalled client does not occupy a connection worker indefinitely.
+    /// The timeouts also apply to the TLS handshake. Returns false if they cannot be set.
+    fn set_incoming_timeouts(stream: &TcpStream) -> bool {
+        let timeout = Some(time::Duration::from_millis(INCOMING_CONNECTION_TIMEOUT));
+        match stream.set_read_timeout(timeout).and_then(|_| stream.set_write_timeout(timeout)) {
+            Ok(()) => true,
+            Err(e) => {
+                trace!("Dropping incoming connection from {:?} as its timeouts cannot be set: {:?}", stream.peer_addr(), e);
+                false
             }
         }
+    }
 
-        // wait for some incoming data on the same stream
-        let mut buffer_str = String::new();
-        let read_result = stream.try_clone().unwrap().read_to_string(&mut buffer_str);
+    /// Returns true, if the given source of an incoming connection is an authorized node: one of the given
+    /// sealers, a candidate of their pending votes, or the local host, e.g. issuing admin commands.
+    /// Sources are identified by their IP address, as they connect from an arbitrary port.
+    fn is_authorized_source(sealer_set: &SealerSet, source: &SocketAddr) -> bool {
+        let source_ip = peers::normalize(source).ip();
+        if source_ip.is_loopback() {
+            return true;
+        }
 
-        match read_result {
-            Ok(amount_bytes_received) => {
-                if 0 == amount_bytes_received {
-                    trace!("No bytes received on outgoing connection. Dropping connection without response");
-                    let shutdown_result = stream.shutdown(Shutdown::Both);
-                    match shutdown_result {
-                        Ok(()) => {}
-                        Err(e) => {
-                            trace!("Failed to shutdown incoming connection: {:?}", e);
-                        }
+        let candidates = sealer_set.get_pending_votes().iter().map(|pending_vote| &pending_vote.candidate);
+        sealer_set.get_sealers().iter()
+            .chain(candidates)
+            .any(|node| peers::normalize(node).ip() == source_ip)
+    }
+
+    /// Returns the handles shared by the workers handling incoming connections.
+    fn get_connection_context(&self) -> ConnectionContext {
+        ConnectionContext {
+            clique_protocol_handler: Arc::clone(&self.protocol),
+            chain_response_cache: Arc::clone(&self.chain_response_cache),
+            broadcast_sender: self.broadcast_sender.clone(),
+            in_flight_messages: Arc::clone(&self.in_flight_messages),
+            ban_list: Arc::clone(&self.ban_list),
+            compression_threshold: self.compression_threshold,
+        }
+    }
+
+    /// Returns true, if the source of the given stream is banned.
+    fn is_banned(ban_list: &Mutex<BanList>, stream: &TcpStream) -> bool {
+        match stream.peer_addr() {
+            Ok(peer_addr) => ban_list.lock().unwrap().is_banned(&peer_addr.ip(), Node::now()),
+            Err(_) => false
+        }
+    }
+
+    /// Ban the source of the given stream if the given response rejects
+    /// a transaction with invalid proofs too often.
+    fn account_response(ban_list: &Mutex<BanList>, stream: &PeerStream, response: &Message) {
+        match (response, stream.peer_addr()) {
+            (Message::TransactionReject(_, reason), Ok(peer_addr)) if reason == INVALID_TRANSACTION_REASON => {
+                ban_list.lock().unwrap().record_invalid_proof(peer_addr.ip(), Node::now());
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the current time in seconds since the epoch.
+    fn now() -> u64 {
+        time::SystemTime::now().duration_since(time::UNIX_EPOCH).expect("Time went backwards").as_secs()
+    }
+
+    /// Read a message from the given stream until EOF, reserving its size in the given budget.
+    /// The returned reservation must be kept until the message is handled.
+    ///
+    /// Returns None, and closes the stream if necessary, if nothing could be read.
+    fn read_incoming_message(stream: &mut PeerStream, in_flight_messages: &Arc<MemoryBudget>) -> Option<(Vec<u8>, MemoryReservation)> {
+        let result = memory::read_message(stream, MAX_MESSAGE_SIZE + 1, in_flight_messages);
+        let (buffer, reservation) = match result {
+            Ok((buffer, reservation)) => {
+                if buffer.is_empty() {
+                    trace!("No bytes received on incoming connection. Dropping connection without response");
+                    let shutdown_result = stream.shutdown(Shutdown::Both);
+                    match shutdown_result {
+                        Ok(()) => {}
+                        Err(e) => {
+                            trace!("Failed to shutdown incoming connection: {:?}", e);
+                        }
                     }
 
                     return None;
                 }
+
+                (buffer, reservation)
+            }
+            Err(e) => {
+                debug!("Failed to read bytes from incoming connection: {}", e);
+
+                return None;
             }
+        };
+
+        Some((buffer, reservation))
+    }
+
+    /// Remove the envelope of the given message read from the given stream, accounting it in the given
+    /// reservation once decompressed. Returns the encoded message along with the compression and the threshold
+    /// to compress the response with, if the sender accepts a compression and compression is enabled.
+    ///
+    /// Returns None if the envelope is corrupt or the decompressed message exceeds the budget.
+    fn open_incoming_message<'a>(stream: &PeerStream, buffer: &'a [u8], reservation: &mut MemoryReservation, compression_threshold: Option<usize>) -> Option<(Cow<'a, [u8]>, Option<CompressionSetting>)> {
+        let (encoded_message, accepted_compression) = match compression::open(buffer) {
+            Ok(opened) => opened,
             Err(e) => {
-                trace!("Failed to read bytes from incoming connection: {:?}", e);
+                debug!("Dropping message from {:?}: {}", stream.peer_addr(), e);
+                return None;
+            }
+        };
 
+        if let Cow::Owned(ref decompressed_message) = encoded_message {
+            if let Err(e) = reservation.grow(decompressed_message.len()) {
+                debug!("Dropping decompressed message from {:?}: {}", stream.peer_addr(), e);
                 return None;
             }
         }
 
-        let response = JsonCodec::decode(buffer_str);
-        trace!("Got response from outgoing stream: {:?}", response);
+        let response_compression = match (accepted_compression, compression_threshold) {
+            (Some(compression), Some(threshold)) => Some((compression, threshold)),
+            _ => None
+        };
+
+        Some((encoded_message, response_compression))
+    }
+
+    /// Encode the given response with the given codec and compress it with the given compression
+    /// if it has at least the given amount of bytes.
+    fn encode_response(response: Message, codec: CodecKind, compression: Option<CompressionSetting>) -> Vec<u8> {
+        match compression {
+            Some((compression, threshold)) => compression::seal(codec.encode(response), compression, threshold),
+            None => codec.encode(response)
+        }
+    }
+
+    /// Send the given response back on the stream and close it afterwards.
+    fn write_response(stream: &mut PeerStream, encoded_response: &[u8]) {
+        let write_result = stream.write_all(encoded_response).and_then(|_| stream.flush());
+        match write_result {
+            Ok(()) => {}
+            Err(e) => {
+                trace!("Failed to send response to {:?}: {:?}", stream.peer_addr(), e);
+                return;
+            }
+        }
+
+        // closing the connection for writing signals the end of the response, over TLS as well
+        let shutdown_result = stream.shutdown(Shutdown::Both);
+        match shutdown_result {
+            Ok(()) => {}
+            // happens when the peer already closed the connection
+            Err(ref e) if e.kind() == ErrorKind::NotConnected => {}
+            Err(e) => { trace!("Could not shutdown incoming connection: {:?}", e) }
+        }
+    }
+
+    /// Start to send the messages collected from RPC connections to other peers.
+    ///
+    /// Messages are broadcast as soon as they are received, unless
+    /// gossiping is configured, in which case they are sent in batches.
+    pub fn gossip(&self) {
+        let broadcast_receiver = match self.broadcast_receiver.lock().unwrap().take() {
+            Some(receiver) => receiver,
+            None => {
+                warn!("Already sending messages to other peers");
+                return;
+            }
+        };
+
+        let known_peers = Arc::clone(&self.peers);
+        let relays = Arc::clone(&self.relays);
+        let transport = Arc::clone(&self.transport);
+        let gossip_queue = self.gossip_queue.clone();
+        let is_running = Arc::clone(&self.is_running);
+
+        self.thread_pool.execute("gossip", move || {
+            match gossip_queue {
+                None => {
+                    while is_running.load(Ordering::SeqCst) {
+                        let message = match broadcast_receiver.recv_timeout(time::Duration::from_millis(DRAIN_POLL_INTERVAL)) {
+                            Ok(message) => message,
+                            Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                            // the receiver fails only once all senders are gone
+                            Err(mpsc::RecvTimeoutError::Disconnected) => break
+                        };
+                        trace!("Broadcast RPC handler message {:?}", message.clone());
+                        Node::broadcast(&known_peers, &relays, &*transport, message);
+                    }
+                }
+                Some(queue) => {
+                    while Node::sleep_while_running(&is_running, queue.next_delay()) {
+
+                        for message in broadcast_receiver.try_iter() {
+                            queue.push(message);
+                        }
+
+                        for message in queue.take_batch() {
+                            trace!("Gossip message {:?}", message.clone());
+                            Node::broadcast(&known_peers, &relays, &*transport, message);
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    /// Start to periodically abandon branches which fell behind the canonical chain
+    /// and announce them to all other peers, once per block period.
+    /// Every `BRANCH_PRUNE_INTERVAL` seconds, branches which forked off below the finalized height are pruned.
+    /// Every `MEMPOOL_GOSSIP_INTERVAL` seconds, the pending transactions are gossiped to the sealers,
+    /// and the shares of the election key dealt by this sealer are sent to their recipients.
+    pub fn announce_abandoned_branches(&self) {
+        let clique_protocol_handler = Arc::clone(&self.protocol);
+        let known_peers = Arc::clone(&self.peers);
+        let relays = Arc::clone(&self.relays);
+        let transport = Arc::clone(&self.transport);
+        let is_running = Arc::clone(&self.is_running);
+
+        self.thread_pool.execute("abandon-branches", move || {
+            let mut last_pruning = time::Instant::now();
+            let mut last_mempool_gossip = time::Instant::now();
+            let mut delivered_shares = HashSet::new();
+            loop {
+                let block_period = clique_protocol_handler.lock().unwrap().get_block_period();
+                if !Node::sleep_while_running(&is_running, time::Duration::from_secs(block_period.max(1))) {
+                    break;
+                }
+
+                let abandoned_tips = clique_protocol_handler.lock().unwrap().abandon_stale_branches();
+                for tip in abandoned_tips {
+                    trace!("Announce abandoned branch {}", tip);
+                    Node::broadcast(&known_peers, &relays, &*transport, Message::BranchAbandoned(tip));
+                }
+
+                if last_pruning.elapsed() >= time::Duration::from_secs(BRANCH_PRUNE_INTERVAL) {
+                    clique_protocol_handler.lock().unwrap().prune_stale_branches();
+                    last_pruning = time::Instant::now();
+                }
+
+                if last_mempool_gossip.elapsed() >= time::Duration::from_secs(MEMPOOL_GOSSIP_INTERVAL) {
+                    Node::gossip_mempool(&clique_protocol_handler, &known_peers, &*transport);
+                    Node::deal_key_shares(&clique_protocol_handler, &*transport, &mut delivered_shares);
+                    last_mempool_gossip = time::Instant::now();
+                }
+            }
+        });
+    }
+
+    /// Start to serve the election dashboard on the given address,
+    /// compressing responses with the given encodings in the order of preference.
+    #[cfg(feature = "ui")]
+    pub fn serve_dashboard(&self, dashboard_address: SocketAddr, compression: Vec<ContentEncoding>) {
+        let dashboard = Dashboard::new(dashboard_address, Arc::clone(&self.protocol), compression);
+        let is_running = Arc::clone(&self.is_running);
+        *self.dashboard_address.lock().unwrap() = Some(dashboard_address);
+
+        self.thread_pool.execute("dashboard", move || {
+            dashboard.listen(&is_running);
+        });
+    }
+
+    /// Start to periodically apply the given retention policy
+    /// once the voting is closed on the canonical chain.
+    pub fn enforce_retention_policy(&self, retention_policy: RetentionPolicy) {
+        let clique_protocol_handler = Arc::clone(&self.protocol);
+        let is_running = Arc::clone(&self.is_running);
+
+        self.thread_pool.execute("retention", move || {
+            while Node::sleep_while_running(&is_running, time::Duration::from_secs(retention_policy.interval_secs)) {
+
+                if !clique_protocol_handler.lock().unwrap().is_voting_closed() {
+                    trace!("Voting is not closed yet. Not applying the retention policy");
+                    continue;
+                }
+
+                let report = retention_policy.purge();
+                if !report.deleted_files.is_empty() {
+                    info!("Securely deleted {} files according to the retention policy", report.deleted_files.len());
+                }
+                for (path, reason) in report.failed_files.iter() {
+                    warn!("Failed to delete {:?} according to the retention policy: {}", path, reason);
+                }
+            }
+        });
+    }
+
+    /// Start to periodically notify the endpoints of the given configuration
+    /// about finalized blocks of the canonical chain and the lifecycle of the election.
+    ///
+    /// Returns an error if the spool directory of the configuration cannot be set up.
+    pub fn dispatch_webhooks(&self, webhook_config: WebhookConfig) -> Result<(), String> {
+        let clique_protocol_handler = Arc::clone(&self.protocol);
+        let interval = time::Duration::from_secs(webhook_config.interval_secs);
+        let mut dispatcher = match WebhookDispatcher::new(webhook_config) {
+            Ok(dispatcher) => dispatcher,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+
+        let is_running = Arc::clone(&self.is_running);
+
+        self.thread_pool.execute("webhooks", move || {
+            while Node::sleep_while_running(&is_running, interval) {
+
+                let finalized_blocks = clique_protocol_handler.lock().unwrap().get_finalized_blocks(dispatcher.get_confirmations(), dispatcher.get_cursor());
+                match dispatcher.enqueue(&finalized_blocks) {
+                    Ok(()) => {}
+                    Err(e) => {
+                        warn!("{}", e);
+                    }
+                }
+
+                dispatcher.deliver();
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Start to periodically publish the finalized head of the canonical chain, signed by
+    /// the signer of this node, to the beacon of the given configuration.
+    /// A checkpoint is only published if the finalized head advanced since the last one.
+    ///
+    /// Returns an error if this node has no signer.
+    pub fn publish_checkpoints(&self, beacon_config: BeaconConfig) -> Result<(), String> {
+        let signer = match self.signer.clone() {
+            Some(signer) => signer,
+            None => {
+                return Err("Publishing checkpoints requires a node key, a keystore or an external signer".to_string());
+            }
+        };
+        let clique_protocol_handler = Arc::clone(&self.protocol);
+        let own_address = self.peers.lock().unwrap().own_address();
+        let interval = time::Duration::from_secs(beacon_config.interval_secs);
+        let is_running = Arc::clone(&self.is_running);
+
+        self.thread_pool.execute("checkpoints", move || {
+            let mut published_height: Option<Height> = None;
+            while Node::sleep_while_running(&is_running, interval) {
+
+                let (genesis_configuration_hash, finalized_head) = {
+                    let protocol = clique_protocol_handler.lock().unwrap();
+                    (protocol.get_genesis_configuration_hash(), protocol.get_finalized_head(beacon_config.confirmations))
+                };
+                let (height, block) = match finalized_head {
+                    Some((height, _)) if published_height.is_some_and(|published_height| height <= published_height) => continue,
+                    Some(finalized_head) => finalized_head,
+                    None => continue
+                };
+
+                let published_at = time::SystemTime::now().duration_since(time::UNIX_EPOCH).expect("Time went backwards").as_secs();
+                let publish_result = Checkpoint::new(genesis_configuration_hash, height, block.identifier, published_at, own_address, &*signer)
+                    .and_then(|checkpoint| beacon_config.publisher.publish(&checkpoint));
+                match publish_result {
+                    Ok(()) => {
+                        info!("Published checkpoint at height {}", height);
+                        published_height = Some(height);
+                    }
+                    Err(e) => {
+                        warn!("Failed to publish checkpoint at height {}: {}. Will retry", height, e);
+                    }
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Persist the bans of sources submitting transactions with invalid proofs
+    /// in the given data directory, restoring the bans stored there if any.
+    /// Each ban, as well as each pause of sealing, is appended to the audit log in the same directory.
+    ///
+    /// Returns an error if the stored bans cannot be restored.
+    pub fn persist_bans(&self, data_directory: &Path) -> Result<(), String> {
+        match BanList::open(data_directory) {
+            Ok(ban_list) => {
+                *self.ban_list.lock().unwrap() = ban_list;
+                *self.audit_log.lock().unwrap() = AuditLog::open(data_directory);
+                Ok(())
+            }
+            Err(e) => Err(e)
+        }
+    }
+
+    /// Persist the progress of this sealer in generating the election key jointly in the given data directory,
+    /// continuing the key generation persisted there if any, so that its dealing survives restarts.
+    ///
+    /// Returns an error if the stored progress cannot be restored.
+    pub fn persist_key_generation(&self, data_directory: &Path) -> Result<(), String> {
+        match KeyGeneration::open(data_directory) {
+            Ok(key_generation) => {
+                self.protocol.lock().unwrap().set_key_generation(key_generation);
+                Ok(())
+            }
+            Err(e) => Err(e)
+        }
+    }
+
+    /// Persist the chain in the given data directory, restoring and validating the chain stored there if any.
+    /// If `is_fresh` is set, a data directory already holding blocks is refused instead.
+    /// Blocks are stored in the configured storage encoding, migrating those stored in another one.
+    /// Blocks and buffered transactions are logged there before they are applied, and
+    /// the log left by a crash is replayed.
+    /// Must be called before the node starts to listen for incoming connections.
+    ///
+    /// Returns whether a stored chain was restored, or an error if the stored chain or the log cannot be restored.
+    pub fn persist_blocks(&self, data_directory: &Path, is_fresh: bool) -> Result<bool, String> {
+        let block_store = match FileBlockStore::open(data_directory, self.storage_encoding) {
+            Ok(block_store) => block_store,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+        let write_ahead_log = match WriteAheadLog::open(data_directory) {
+            Ok(write_ahead_log) => write_ahead_log,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+
+        let mut protocol = self.protocol.lock().unwrap();
+        let is_restored = match protocol.set_block_store(Box::new(block_store), is_fresh) {
+            Ok(is_restored) => is_restored,
+            Err(e) => {
+                return Err(format!("Failed to restore the chain from {}: {}", data_directory.display(), e));
+            }
+        };
+
+        protocol.set_write_ahead_log(write_ahead_log).map(|_| is_restored)
+    }
+
+    /// Shut down the node gracefully.
+    ///
+    /// Stops accepting incoming connections and waits for the connections being handled,
+    /// as well as for the messages waiting to be gossiped, until the given deadline.
+    /// Then stops the periodic tasks, e.g. sealing, and waits for the workers running them
+    /// until the same deadline. Relay connections blocked on reading are abandoned at the deadline.
+    /// Finally hands the buffered transactions over to the other peers, so that the next
+    /// leader may include them, and closes the block store.
+    pub fn shutdown(&self, drain_deadline: time::Duration) {
+        info!("Shutting down, draining connections for at most {:?}", drain_deadline);
+        self.accepting_connections.store(false, Ordering::SeqCst);
+
+        // wake up the listeners, which are blocked until the next connection arrives
+        for address in [self.listen_address, self.rpc_listen_address].iter() {
+            let _ = TcpStream::connect_timeout(&peers::normalize(address), time::Duration::from_millis(DRAIN_POLL_INTERVAL));
+        }
+
+        let started_at = time::Instant::now();
+        loop {
+            let in_flight_connections = self.in_flight_connections.load(Ordering::SeqCst);
+            let is_gossip_pending = match self.gossip_queue {
+                Some(ref queue) => !queue.is_empty(),
+                None => false
+            };
+            if in_flight_connections == 0 && !is_gossip_pending {
+                debug!("Drained all connections after {:?}", started_at.elapsed());
+                break;
+            }
+
+            if started_at.elapsed() >= drain_deadline {
+                warn!("Abandoning {} connections and pending gossip not finished within {:?}", in_flight_connections, drain_deadline);
+                break;
+            }
+
+            thread::sleep(time::Duration::from_millis(DRAIN_POLL_INTERVAL));
+        }
+
+        self.is_running.store(false, Ordering::SeqCst);
+        if let Some(dashboard_address) = *self.dashboard_address.lock().unwrap() {
+            let _ = TcpStream::connect_timeout(&peers::normalize(&dashboard_address), time::Duration::from_millis(DRAIN_POLL_INTERVAL));
+        }
+
+        let remaining_time = drain_deadline.checked_sub(started_at.elapsed()).unwrap_or_default();
+        let running_tasks = self.thread_pool.wait_until_idle(remaining_time);
+        if running_tasks.is_empty() {
+            debug!("Stopped all tasks after {:?}", started_at.elapsed());
+        } else {
+            warn!("Abandoning tasks not finished within {:?}: {}", drain_deadline, running_tasks.join(", "));
+        }
+
+        let pending_transactions = self.protocol.lock().unwrap().get_pending_transactions();
+        if !pending_transactions.is_empty() {
+            info!("Handing {} buffered transactions over to other peers", pending_transactions.len());
+            for transaction in pending_transactions {
+                Node::broadcast(&self.peers, &self.relays, &*self.transport, Message::TransactionPayload(transaction));
+            }
+        }
+
+        self.protocol.lock().unwrap().close_block_store();
+    }
+
+    /// Map the port of the advertised address on the gateway of the local network
+    /// to the listen address via UPnP, and keep renewing the mapping.
+    ///
+    /// Returns an error if no gateway is found or it refuses the mapping.
+    pub fn map_port(&self) -> Result<(), String> {
+        let external_port = self.peers.lock().unwrap().own_address().port();
+        let port_mapping = match PortMapping::create(self.listen_address.port(), external_port) {
+            Ok(port_mapping) => port_mapping,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+
+        match port_mapping.get_external_ip() {
+            Ok(external_ip) => info!("Mapped port {} of the gateway with external address {} to this node", external_port, external_ip),
+            Err(e) => info!("Mapped port {} of the gateway to this node. {}", external_port, e)
+        }
+
+        let is_running = Arc::clone(&self.is_running);
+
+        self.thread_pool.execute("port-mapping", move || {
+            // renew well before the lease expires
+            while Node::sleep_while_running(&is_running, time::Duration::from_secs(PORT_MAPPING_LEASE_SECS / 2)) {
+
+                match port_mapping.renew() {
+                    Ok(()) => trace!("Renewed mapping of port {}", port_mapping.get_external_port()),
+                    Err(e) => warn!("{}", e)
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Keep a connection open to each other peer over which it relays messages to this node,
+    /// for nodes which are unable to accept incoming connections. Broken connections are
+    /// re-established after `RELAY_RECONNECT_DELAY` seconds.
+    pub fn receive_over_relays(&self) {
+        let clique_protocol_handler = Arc::clone(&self.protocol);
+        let block_pipeline = Arc::clone(&self.block_pipeline);
+        let (own_address, other_peers) = {
+            let peers = self.peers.lock().unwrap();
+            (peers.own_address(), peers.others())
+        };
+        let tls = self.tls.clone();
+        let metrics = Arc::clone(&self.metrics);
+        let is_running = Arc::clone(&self.is_running);
+
+        self.thread_pool.execute("relays", move || {
+            let relay_pool = ThreadPool::new("relays", other_peers.len().max(1));
+            metrics.register_thread_pool(relay_pool.get_stats());
+
+            for peer_addr in other_peers {
+                let cloned_clique_protocol_handler = Arc::clone(&clique_protocol_handler);
+                let cloned_block_pipeline = Arc::clone(&block_pipeline);
+                let cloned_tls = tls.clone();
+                let cloned_is_running = Arc::clone(&is_running);
+
+                relay_pool.execute("relay", move || {
+                    loop {
+                        Node::receive_over_relay(&peer_addr, own_address, &cloned_clique_protocol_handler, &cloned_block_pipeline, &cloned_tls);
+                        if !Node::sleep_while_running(&cloned_is_running, time::Duration::from_secs(RELAY_RECONNECT_DELAY)) {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+    }
+
+    /// Register as relay at the given peer and handle the relayed messages until the connection breaks.
+    /// The connection is encrypted with the given TLS context, if any.
+    fn receive_over_relay(peer_addr: &SocketAddr, own_address: SocketAddr, clique_protocol_handler: &Arc<Mutex<CliqueProtocol>>, block_pipeline: &BlockPipeline, tls: &Option<Arc<TlsContext>>) {
+        let registration = JsonCodec::encode(Message::RelayRegister(own_address));
+        let stream = TcpStream::connect(peer_addr)
+            .map_err(|e| format!("{:?}", e))
+            .and_then(|stream| match *tls {
+                Some(ref tls) => tls.connect(peer_addr, stream),
+                None => Ok(PeerStream::Plain(stream))
+            })
+            .and_then(|mut stream| {
+                stream.write_all(&registration)
+                    .and_then(|_| stream.shutdown(Shutdown::Write))
+                    .map(|_| stream)
+                    .map_err(|e| format!("{:?}", e))
+            });
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                debug!("Failed to register as relay at {:?}: {}", peer_addr, e);
+                return;
+            }
+        };
+
+        debug!("Receiving messages relayed by {:?}", peer_addr);
+        let mut reader = BufReader::new(stream);
+        loop {
+            let mut line = String::new();
+            let read_result = Read::by_ref(&mut reader).take(MAX_MESSAGE_SIZE as u64 + 1).read_line(&mut line);
+            match read_result {
+                Ok(0) => {
+                    debug!("Relay connection to {:?} was closed", peer_addr);
+                    return;
+                }
+                Ok(_) if !line.ends_with('\n') => {
+                    debug!("Relay connection to {:?} was closed amid a message or the message exceeds the maximum size", peer_addr);
+                    return;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    debug!("Failed to read from relay connection to {:?}: {:?}", peer_addr, e);
+                    return;
+                }
+            }
+
+            let message = JsonCodec::decode(line.trim().as_bytes());
+            trace!("Got relayed message {:?} from {:?}", message, peer_addr);
+
+            // relayed messages are not responded to
+            let _ = match message {
+                Message::BlockPayload(block) => block_pipeline.submit(block),
+                message => clique_protocol_handler.lock().unwrap().handle(message)
+            };
+        }
+    }
+
+    /// Introduce this node to all other peers with the hash of its genesis configuration
+    /// and the features of the election it enables.
+    ///
+    /// Peers running a different genesis configuration answer with `GenesisMismatch`, peers enabling
+    /// different features with `FeatureMismatch`. Both are removed from the set of peers,
+    /// so that neither blocks nor chains are exchanged with them.
+    /// Unreachable peers are kept, as they may still be starting.
+    /// Requests to peers choosing a codec among the ones announced are encoded with it afterwards.
+    /// Unless compression is disabled, the handshake also announces the compressions this node decompresses.
+    pub fn handshake(&self) {
+        let own_address = self.peers.lock().unwrap().own_address();
+        let mut handshake = self.protocol.lock().unwrap().create_handshake(own_address);
+        if self.compression_threshold.is_some() {
+            handshake.compressions = CompressionKind::supported_names();
+        }
+        let other_peers = self.peers.lock().unwrap().others();
+
+        for peer_addr in other_peers.iter() {
+            match self.transport.request_with_timeout(peer_addr, Message::Handshake(handshake.clone()), time::Duration::from_millis(HANDSHAKE_TIMEOUT)) {
+                Ok(Message::HandshakeAccept) => {
+                    debug!("Peer {:?} accepted the handshake", peer_addr);
+                    self.transport.use_codec(peer_addr, CodecKind::Json);
+                }
+                Ok(Message::HandshakeAcceptCodec(name)) => {
+                    debug!("Peer {:?} accepted the handshake using the {} codec", peer_addr, name);
+                    match name.parse() {
+                        Ok(negotiated_codec) => self.transport.use_codec(peer_addr, negotiated_codec),
+                        Err(e) => {
+                            warn!("Peer {:?} chose a codec we do not support: {}", peer_addr, e);
+                        }
+                    }
+                }
+                Ok(Message::GenesisMismatch(genesis_configuration_hash)) => {
+                    error!("Peer {:?} runs genesis configuration {} instead of {}. Not exchanging any messages with it", peer_addr, genesis_configuration_hash, handshake.genesis_configuration_hash);
+                    self.peers.lock().unwrap().remove(peer_addr);
+                }
+                Ok(Message::FeatureMismatch(features)) => {
+                    let difference = feature::find_difference(&handshake.features, &features).unwrap_or_default();
+                    error!("Peer {:?} enables different features than we do: {}. Not exchanging any messages with it", peer_addr, difference);
+                    self.peers.lock().unwrap().remove(peer_addr);
+                }
+                Ok(other) => {
+                    debug!("Peer {:?} answered the handshake with {:?}, it may not support handshakes", peer_addr, other);
+                }
+                Err(e) => {
+                    debug!("Failed to reach {:?} for the handshake: {}", peer_addr, e);
+                }
+            }
+        }
+    }
+
+    /// Call the given listener with the previous and the new head whenever the head
+    /// of the canonical chain changes, see `CliqueProtocol::on_head_changed`.
+    pub fn on_head_changed<F>(&self, listener: F)
+        where
+            F: Fn(&HeadInfo, &HeadInfo) + Send + 'static
+    {
+        self.protocol.lock().unwrap().on_head_changed(listener);
+    }
+
+    /// Call the given listener with the identifier and the new receipt of a transaction whenever its
+    /// block is reverted by a reorganization, see `CliqueProtocol::on_receipt_changed`.
+    pub fn on_receipt_changed<F>(&self, listener: F)
+        where
+            F: Fn(&str, &ReceiptStatus) + Send + 'static
+    {
+        self.protocol.lock().unwrap().on_receipt_changed(listener);
+    }
+
+    /// Compare the local clock against the clocks of all other peers and the NTP server, if configured.
+    ///
+    /// The median of the offsets is taken, so that a minority of peers with wrong clocks does not matter.
+    /// A warning is logged if it exceeds the configured threshold. If it exceeds the drift tolerated
+    /// for the timestamps of blocks, this node refuses to seal blocks until its clock is fixed.
+    pub fn check_clock(&self) {
+        Node::compare_clock(&self.protocol, &self.peers, &*self.transport, self.ntp_server.as_deref(), self.clock_warn_threshold);
+    }
+
+    /// Start to periodically compare the local clock against the clocks of the peers, see `check_clock`.
+    pub fn monitor_clock(&self) {
+        let clique_protocol_handler = Arc::clone(&self.protocol);
+        let peers = Arc::clone(&self.peers);
+        let transport = Arc::clone(&self.transport);
+        let ntp_server = self.ntp_server.clone();
+        let clock_warn_threshold = self.clock_warn_threshold;
+        let is_running = Arc::clone(&self.is_running);
+
+        self.thread_pool.execute("clock", move || {
+            while Node::sleep_while_running(&is_running, time::Duration::from_secs(CLOCK_CHECK_INTERVAL)) {
+
+                Node::compare_clock(&clique_protocol_handler, &peers, &*transport, ntp_server.as_deref(), clock_warn_threshold);
+            }
+        });
+    }
+
+    /// Start to periodically send a `Ping` to each other sealer, twice per block period,
+    /// so that the connectivity to all sealers is tracked even while no blocks are exchanged.
+    pub fn send_heartbeats(&self) {
+        let clique_protocol_handler = Arc::clone(&self.protocol);
+        let transport = Arc::clone(&self.transport);
+        let peer_health = Arc::clone(&self.peer_health);
+        let is_running = Arc::clone(&self.is_running);
+
+        self.thread_pool.execute("heartbeat", move || {
+            let started_at = clock::now_millis() / 1000;
+            loop {
+                let block_period = clique_protocol_handler.lock().unwrap().get_block_period().max(1);
+                if !Node::sleep_while_running(&is_running, time::Duration::from_millis(block_period * 500)) {
+                    break;
+                }
+
+                Node::send_heartbeat(&clique_protocol_handler, &*transport, &peer_health, started_at, block_period);
+            }
+        });
+    }
+
+    /// Send a `Ping` to each other sealer and mark the sealers in the given peer-health table which did not answer
+    /// any request for more than the given block period, counting from the given start of the heartbeats if they never did.
+    /// A warning is logged once a sealer becomes silent, and a notice once it answers again.
+    ///
+    /// Returns the sealers which became silent.
+    fn send_heartbeat(clique_protocol_handler: &Mutex<CliqueProtocol>, transport: &Transport, peer_health: &PeerHealthTracker, started_at: u64, block_period: u64) -> Vec<SocketAddr> {
+        let sealers: Vec<SocketAddr> = clique_protocol_handler.lock().unwrap().get_peer_status().iter()
+            .map(|health| health.address)
+            .collect();
+        for sealer in sealers.iter() {
+            // the transport records the outcome in the connectivity of the sealer
+            match transport.request_with_timeout(sealer, Message::Ping, time::Duration::from_millis(HEARTBEAT_TIMEOUT)) {
+                Ok(Message::Pong) => trace!("Sealer {:?} answered the heartbeat", sealer),
+                Ok(other) => debug!("Sealer {:?} answered the heartbeat with {:?}", sealer, other),
+                Err(e) => debug!("Sealer {:?} did not answer the heartbeat: {}", sealer, e)
+            }
+        }
+
+        let now = clock::now_millis() / 1000;
+        let mut silent_sealers = vec![];
+        for health in clique_protocol_handler.lock().unwrap().get_peer_status() {
+            let is_silent = health.is_silent(started_at, now, block_period);
+            if !peer_health.mark_silent(&health.address, is_silent) {
+                continue;
+            }
+
+            if is_silent {
+                warn!("Sealer {:?} has been silent for more than the block period of {}s: {}", health.address, block_period, health.last_error.unwrap_or_else(|| "No error".to_string()));
+                silent_sealers.push(health.address);
+            } else {
+                info!("Sealer {:?} is reachable again", health.address);
+            }
+        }
 
-        return Some(response);
+        silent_sealers
     }
-}
\ No newline at end of file
+
+    fn compare_clock(clique_protocol_handler: &Arc<Mutex<CliqueProtocol>>, peers: &Arc<Mutex<PeerSet>>, transport: &Transport, ntp_server: Option<&str>, clock_warn_threshold: u64) {
+        let timeout = time::Duration::from_millis(CLOCK_QUERY_TIMEOUT);
+        let other_peers = peers.lock().unwrap().others();
+
+        let mut offsets = vec![];
+        for peer_addr in other_peers.iter() {
+            let sent_at = clock::now_millis();
+            match transport.request_with_timeout(peer_addr, Message::TimeRequest, timeout) {
+                Ok(Message::TimeResponse(peer_time)) => {
+                    let offset = clock::estimate_offset(sent_at, peer_time, clock::now_millis());
+                    trace!("Clock of peer {:?} is {}ms ahead", peer_addr, offset);
+                    offsets.push(offset);
+                }
+                Ok(other) => {
+                    debug!("Peer {:?} answered the time request with {:?}, it may not support time requests", peer_addr, other);
+                }
+                Err(e) => {
+                    debug!("Failed to ask {:?} for its time: {}", peer_addr, e);
+                }
+            }
+        }
+
+        if let Some(ntp_server) = ntp_server {
+            match clock::query_ntp(ntp_server, timeout) {
+                Ok(offset) => {
+                    trace!("Clock of NTP server {} is {}ms ahead", ntp_server, offset);
+                    offsets.push(offset);
+                }
+                Err(e) => {
+                    warn!("{}", e);
+                }
+            }
+        }
+
+        let offset = match clock::median_offset(&offsets) {
+            Some(offset) => offset,
+            None => {
+                debug!("No peer told its time. Not checking the local clock");
+                return;
+            }
+        };
+
+        clique_protocol_handler.lock().unwrap().set_clock_offset(offset);
+        if offset.unsigned_abs() > MAX_TIMESTAMP_DRIFT * 1000 {
+            error!("Local clock deviates by {}ms from the clocks of {} peers, more than the {}s tolerated for blocks. Refusing to seal blocks until it is fixed", -offset, offsets.len(), MAX_TIMESTAMP_DRIFT);
+        } else if offset.unsigned_abs() > clock_warn_threshold {
+            warn!("Local clock deviates by {}ms from the clocks of {} peers", -offset, offsets.len());
+        } else {
+            debug!("Local clock deviates by {}ms from the clocks of {} peers", -offset, offsets.len());
+        }
+    }
+
+    /// Send a request for a copy of the blockchain to all known nodes.
+    ///
+    /// The missing blocks are first fetched from the fastest peers in parallel, see `sync_striped_block_ranges`.
+    /// Then, starting with the fastest peer, the chain of each node is synchronized range by range,
+    /// or fetched chunk by chunk and replaces the own one, if it is longer.
+    pub fn request_chain_copy(&mut self) {
+        // create a reference which we can share across threads
+        let peers = Arc::clone(&self.peers);
+
+        let other_peers = peers.lock().unwrap().others();
+
+        let ranked_heads = Node::probe_heads(&*self.transport, &other_peers, &self.latencies);
+        match Node::sync_striped_block_ranges(&self.transport, &ranked_heads, &self.protocol, &self.latencies, &self.metrics) {
+            Ok(amount_added_blocks) => {
+                info!("Synchronized {} missing blocks with the {} fastest peers", amount_added_blocks, cmp::min(ranked_heads.len(), MAX_PARALLEL_FETCHES));
+            }
+            Err(e) => {
+                debug!("Synchronizing with each peer in turn as fetching block ranges in parallel failed: {}", e);
+            }
+        }
+
+        let ranked_peers = self.latencies.lock().unwrap().rank(&other_peers);
+        for peer_addr in ranked_peers.iter() {
+            match Node::sync_block_ranges(&*self.transport, peer_addr, &self.protocol) {
+                Ok(amount_added_blocks) => {
+                    info!("Synchronized {} missing blocks with {:?}", amount_added_blocks, peer_addr);
+                    continue;
+                }
+                Err(e) => {
+                    debug!("Falling back to a copy of the whole chain of {:?} as synchronizing block ranges failed: {}", peer_addr, e);
+                }
+            }
+
+            match Node::fetch_chain(&*self.transport, peer_addr) {
+                Some(chain) => {
+                    self.protocol.lock().unwrap().replace_chain(chain);
+                }
+                None => {
+                    warn!("Failed to obtain a copy of the chain of {:?}", peer_addr);
+                }
+            }
+        }
+    }
+
+    /// Replace the own chain by a snapshot of the finalized chain on which more than half of the other peers agree,
+    /// so that a node joining late does not need to replay all blocks. The blocks above the snapshot are
+    /// fetched by `request_chain_copy` afterwards.
+    ///
+    /// Each peer is asked for a snapshot at its finalized head first. The snapshot is then taken at the
+    /// highest height finalized by more than half of the peers, so that all of them can serve it.
+    ///
+    /// Returns the height of the adopted snapshot, or an error if not enough peers agree on a snapshot
+    /// or the own chain is already as high.
+    pub fn sync_from_snapshot(&mut self) -> Result<Height, String> {
+        let other_peers = self.peers.lock().unwrap().others();
+        let required_agreement = other_peers.len() / 2 + 1;
+
+        let mut finalized_heights = vec![];
+        for peer_addr in other_peers.iter() {
+            match self.transport.request(peer_addr, Message::SnapshotRequest(None)) {
+                Ok(Message::SnapshotResponse(Some(state_snapshot))) => finalized_heights.push(state_snapshot.height),
+                Ok(Message::SnapshotResponse(None)) => {
+                    debug!("Peer {:?} did not finalize any block yet", peer_addr);
+                }
+                Ok(other) => {
+                    debug!("Peer {:?} answered the snapshot request with {:?}, it may not support snapshots", peer_addr, other);
+                }
+                Err(e) => {
+                    debug!("Failed to request a snapshot of {:?}: {}", peer_addr, e);
+                }
+            }
+        }
+        if finalized_heights.len() < required_agreement {
+            return Err(format!("Only {} of {} peers serve a snapshot, but {} are required", finalized_heights.len(), other_peers.len(), required_agreement));
+        }
+
+        // the highest height finalized by enough peers
+        finalized_heights.sort_unstable_by(|first, second| second.cmp(first));
+        let height = finalized_heights[required_agreement - 1];
+
+        let mut state_snapshots: Vec<(StateSnapshot, usize)> = vec![];
+        for peer_addr in other_peers.iter() {
+            match self.transport.request(peer_addr, Message::SnapshotRequest(Some(height))) {
+                Ok(Message::SnapshotResponse(Some(state_snapshot))) => {
+                    match state_snapshots.iter_mut().find(|(known_snapshot, _)| *known_snapshot == state_snapshot) {
+                        Some(entry) => entry.1 += 1,
+                        None => state_snapshots.push((state_snapshot, 1))
+                    }
+                }
+                Ok(other) => {
+                    debug!("Peer {:?} did not serve a snapshot at height {}: {:?}", peer_addr, height, other);
+                }
+                Err(e) => {
+                    debug!("Failed to request a snapshot at height {} of {:?}: {}", height, peer_addr, e);
+                }
+            }
+        }
+
+        let state_snapshot = match state_snapshots.into_iter().find(|(_, agreement)| *agreement >= required_agreement) {
+            Some((state_snapshot, _)) => state_snapshot,
+            None => {
+                return Err(format!("Less than {} of {} peers agree on the snapshot at height {}", required_agreement, other_peers.len(), height));
+            }
+        };
+
+        match self.protocol.lock().unwrap().adopt_state_snapshot(state_snapshot) {
+            Ok(()) => Ok(height),
+            Err(e) => Err(e)
+        }
+    }
+
+    /// Ask each of the given peers for its head, taking note of its round trip time.
+    /// Returns the peers which answered in time along with the heights of their heads, the fastest first.
+    fn probe_heads(transport: &Transport, other_peers: &[SocketAddr], latencies: &Mutex<PeerLatencies>) -> Vec<(SocketAddr, Height)> {
+        let timeout = time::Duration::from_millis(PROBE_TIMEOUT);
+        let mut heads = vec![];
+        for peer_addr in other_peers.iter() {
+            let started_at = time::Instant::now();
+            match transport.request_with_timeout(peer_addr, Message::HeadQuery, timeout) {
+                Ok(Message::HeadQueryResponse(height, _)) => {
+                    latencies.lock().unwrap().record(peer_addr, started_at.elapsed());
+                    heads.push((*peer_addr, height));
+                }
+                Ok(other) => {
+                    debug!("Peer {:?} answered the head query with {:?}", peer_addr, other);
+                }
+                Err(e) => {
+                    debug!("Peer {:?} did not announce its head in time: {}", peer_addr, e);
+                    latencies.lock().unwrap().record_stall(peer_addr);
+                }
+            }
+        }
+
+        let latencies = latencies.lock().unwrap();
+        heads.sort_by_key(|&(peer_addr, _)| latencies.get_round_trip_time(&peer_addr));
+
+        heads
+    }
+
+    /// Fetch the blocks missing in the own chain up to the highest of the given heads, ranked by the round trip
+    /// time of their peers, by requesting consecutive block ranges from up to `MAX_PARALLEL_FETCHES` peers in parallel.
+    ///
+    /// A range which a peer fails to send within `STALL_TIMEOUT` is requested from the next fastest peer instead.
+    /// Returns the amount of added blocks, or an error if no peer sends a range or the ranges do not extend
+    /// the own chain, e.g. as it forked, in which case the peers are synchronized with one by one.
+    fn sync_striped_block_ranges(transport: &Arc<Transport>, ranked_heads: &[(SocketAddr, Height)], clique_protocol_handler: &Arc<Mutex<CliqueProtocol>>, latencies: &Arc<Mutex<PeerLatencies>>, metrics: &MetricsRegistry) -> Result<usize, String> {
+        let (genesis_configuration_hash, own_height) = {
+            let protocol = clique_protocol_handler.lock().unwrap();
+            match protocol.get_head() {
+                Some(head) => (protocol.get_genesis_configuration_hash(), head.height),
+                None => {
+                    return Err("The head of the own chain cannot be determined".to_string());
+                }
+            }
+        };
+
+        let target_height = match ranked_heads.iter().map(|&(_, head_height)| head_height).max() {
+            Some(target_height) => target_height,
+            None => {
+                return Err("No peer announced its head".to_string());
+            }
+        };
+
+        let fetch_pool = ThreadPool::new("block-fetch", MAX_PARALLEL_FETCHES);
+        metrics.register_thread_pool(fetch_pool.get_stats());
+
+        let mut candidates = ranked_heads.to_vec();
+        let mut next_height = own_height + 1;
+        let mut amount_added_blocks = 0;
+        while next_height <= target_height {
+            let window_end = cmp::min(target_height, next_height + MAX_PARALLEL_FETCHES * MAX_BLOCK_RANGE - 1);
+            let ranges = block_fetch::stripe(next_height, window_end, MAX_BLOCK_RANGE);
+            let block_ranges = match Node::fetch_block_ranges(&fetch_pool, transport, &ranges, &mut candidates, latencies, &genesis_configuration_hash) {
+                Ok(block_ranges) => block_ranges,
+                Err(e) => {
+                    return Err(e);
+                }
+            };
+
+            // a range ends early if its blocks exceed the size of a chunk, in which case
+            // the following ranges do not extend the added blocks and are requested again
+            let window_start = next_height;
+            for block_range in block_ranges {
+                if block_range.from_height != next_height {
+                    break;
+                }
+
+                next_height = block_range.from_height + block_range.blocks.len();
+                match clique_protocol_handler.lock().unwrap().add_block_range(block_range.blocks) {
+                    Ok(amount_added_range_blocks) => {
+                        amount_added_blocks += amount_added_range_blocks;
+                    }
+                    Err(e) => {
+                        return Err(e);
+                    }
+                }
+            }
+
+            if next_height == window_start {
+                return Err(format!("No peer sent the blocks from height {}", window_start));
+            }
+            trace!("Fetched blocks from height {} up to height {} of {}", window_start, next_height - 1, target_height);
+        }
+
+        Ok(amount_added_blocks)
+    }
+
+    /// Request the given ranges in parallel from the given candidates, ranked by the round trip times of their peers
+    /// along with the heights of their heads. A candidate failing to send a range is removed and the range
+    /// requested from the next one.
+    ///
+    /// Returns the block ranges in the order of the given ranges, or an error if no candidate sends one of them.
+    fn fetch_block_ranges(fetch_pool: &ThreadPool, transport: &Arc<Transport>, ranges: &[(Height, Height)], candidates: &mut Vec<(SocketAddr, Height)>, latencies: &Mutex<PeerLatencies>, genesis_configuration_hash: &str) -> Result<Vec<BlockRange>, String> {
+        let mut block_ranges: Vec<Option<BlockRange>> = ranges.iter().map(|_| None).collect();
+        let mut missing_ranges: Vec<usize> = (0..ranges.len()).collect();
+
+        while !missing_ranges.is_empty() {
+            let requested_ranges: Vec<(Height, Height)> = missing_ranges.iter().map(|index| ranges[*index]).collect();
+            let assignments = block_fetch::assign(&requested_ranges, candidates, MAX_PARALLEL_FETCHES);
+
+            let (sender, receiver) = mpsc::channel();
+            for (index, assignment) in missing_ranges.iter().zip(assignments) {
+                let (from_height, to_height) = ranges[*index];
+                let peer_addr = match assignment {
+                    Some(peer_addr) => peer_addr,
+                    None => {
+                        return Err(format!("No peer sent the blocks from height {} up to height {}", from_height, to_height));
+                    }
+                };
+
+                let index = *index;
+                let cloned_transport = Arc::clone(transport);
+                let cloned_sender = sender.clone();
+                fetch_pool.execute("block-range", move || {
+                    let started_at = time::Instant::now();
+                    let request = Message::BlockRangeRequest { from_height, to_height };
+                    let response = cloned_transport.request_with_timeout(&peer_addr, request, time::Duration::from_millis(STALL_TIMEOUT));
+                    let _ = cloned_sender.send((index, peer_addr, started_at.elapsed(), response));
+                });
+            }
+            drop(sender);
+
+            let mut failed_peers = vec![];
+            for (index, peer_addr, round_trip_time, response) in receiver.iter() {
+                match response {
+                    Ok(Message::BlockRangeResponse(ref block_range)) if block_range.genesis_configuration_hash != genesis_configuration_hash => {
+                        warn!("Peer {:?} sent blocks of genesis configuration {}", peer_addr, block_range.genesis_configuration_hash);
+                    }
+                    Ok(Message::BlockRangeResponse(ref block_range)) if block_range.blocks.is_empty() => {
+                        debug!("Peer {:?} sent no blocks from height {}", peer_addr, block_range.from_height);
+                    }
+                    Ok(Message::BlockRangeResponse(block_range)) => {
+                        latencies.lock().unwrap().record(&peer_addr, round_trip_time);
+                        block_ranges[index] = Some(block_range);
+                        continue;
+                    }
+                    Ok(other) => {
+                        debug!("Expected a block range from {:?} but got {:?}", peer_addr, other);
+                    }
+                    Err(e) => {
+                        warn!("Peer {:?} stalled sending blocks from height {}: {}", peer_addr, ranges[index].0, e);
+                        latencies.lock().unwrap().record_stall(&peer_addr);
+                    }
+                }
+
+                failed_peers.push(peer_addr);
+            }
+
+            candidates.retain(|&(peer_addr, _)| !failed_peers.contains(&peer_addr));
+            missing_ranges.retain(|index| block_ranges[*index].is_none());
+        }
+
+        Ok(block_ranges.into_iter().map(Option::unwrap).collect())
+    }
+
+    /// Fetch only the blocks of the canonical chain of the given peer which are missing
+    /// in the own chain, range by range, and add them to the own chain.
+    ///
+    /// Requests start above the own head. If the first block of a range does not extend
+    /// the own chain, both chains forked below, in which case ranges starting at
+    /// increasingly lower heights are requested until the fork is found.
+    ///
+    /// Returns the amount of added blocks, or an error if the peer is not reachable,
+    /// does not support block ranges or sends blocks not extending the own chain.
+    fn sync_block_ranges(transport: &Transport, peer_addr: &SocketAddr, clique_protocol_handler: &Arc<Mutex<CliqueProtocol>>) -> Result<usize, String> {
+        let (genesis_configuration_hash, own_height, root_height) = {
+            let protocol = clique_protocol_handler.lock().unwrap();
+            let own_height = match protocol.get_head() {
+                Some(head) => head.height,
+                None => {
+                    return Err("The head of the own chain cannot be determined".to_string());
+                }
+            };
+
+            (protocol.get_genesis_configuration_hash(), own_height, protocol.get_root_height())
+        };
+
+        let mut from_height = own_height + 1;
+        let mut step_back = 1;
+        let mut amount_added_blocks = 0;
+
+        loop {
+            let request = Message::BlockRangeRequest {
+                from_height,
+                to_height: from_height + MAX_BLOCK_RANGE - 1,
+            };
+            let block_range = match transport.request(peer_addr, request) {
+                Ok(Message::BlockRangeResponse(block_range)) => block_range,
+                Ok(other) => {
+                    return Err(format!("Expected a block range but got {:?}", other));
+                }
+                Err(e) => {
+                    return Err(e);
+                }
+            };
+
+            if !block_range.genesis_configuration_hash.eq(&genesis_configuration_hash) {
+                return Err(format!("Chain belongs to genesis configuration {}", block_range.genesis_configuration_hash));
+            }
+
+            let is_forked = match block_range.blocks.first() {
+                Some(first_block) => !clique_protocol_handler.lock().unwrap().has_parent_of_block(first_block),
+                // the own chain is at least as long as the one of the peer
+                None => {
+                    return Ok(amount_added_blocks);
+                }
+            };
+
+            if is_forked {
+                if from_height <= root_height + 1 {
+                    return Err("Chain does not share the first block of the own chain".to_string());
+                }
+
+                from_height -= cmp::min(step_back, from_height.distance_to(root_height + 1));
+                step_back *= 2;
+                trace!("Chain of {:?} forked below height {}, requesting blocks from height {}", peer_addr, block_range.from_height, from_height);
+                continue;
+            }
+
+            let amount_received_blocks = block_range.blocks.len();
+            trace!("Received {} blocks from height {} of {:?}", amount_received_blocks, block_range.from_height, peer_addr);
+            match clique_protocol_handler.lock().unwrap().add_block_range(block_range.blocks) {
+                Ok(amount_added_range_blocks) => {
+                    amount_added_blocks += amount_added_range_blocks;
+                }
+                Err(e) => {
+                    return Err(e);
+                }
+            }
+
+            from_height = block_range.from_height + amount_received_blocks;
+            if from_height > block_range.head_height {
+                return Ok(amount_added_blocks);
+            }
+        }
+    }
+
+    /// Fetch the chain of the given peer by requesting it chunk by chunk
+    /// and reassembling the chunks into a chain.
+    ///
+    /// Returns None if the peer is not reachable, responds with invalid chunks
+    /// or sends more than `MAX_FETCHED_CHAIN_SIZE` bytes of blocks.
+    fn fetch_chain(transport: &Transport, peer_addr: &SocketAddr) -> Option<Chain> {
+        let mut chain: Option<Chain> = None;
+        let mut token = None;
+        let mut fetched_size = 0;
+
+        loop {
+            let chunk = match transport.request(peer_addr, Message::ChainChunkRequest(token)) {
+                Ok(Message::ChainChunkResponse(chunk)) => chunk,
+                Ok(other) => {
+                    warn!("Expected a chain chunk from {:?} but got {:?}", peer_addr, other);
+                    return None;
+                }
+                Err(e) => {
+                    warn!("Failed to request a chain chunk from {:?}: {}", peer_addr, e);
+                    return None;
+                }
+            };
+
+            trace!("Received chain chunk of {} blocks from {:?}", chunk.blocks.len(), peer_addr);
+
+            fetched_size += chunk.blocks.iter().map(|block| serde_json::to_string(block).unwrap().len()).sum::<usize>();
+            if fetched_size > MAX_FETCHED_CHAIN_SIZE {
+                warn!("Chain of {:?} exceeds {} bytes. Not fetching it", peer_addr, MAX_FETCHED_CHAIN_SIZE);
+                return None;
+            }
+
+            for block in chunk.blocks {
+                let mut reassembled_chain = match chain.take() {
+                    Some(reassembled_chain) => reassembled_chain,
+                    // the very first block is the genesis block
+                    None => Chain::from_genesis_block(chunk.genesis_configuration_hash.clone(), block.clone())
+                };
+
+                // chunks continue after the last block sent, yet a block sent twice does no harm
+                if !reassembled_chain.blocks.contains_key(&block.identifier) {
+                    if !reassembled_chain.has_parent_of_block(&block) {
+                        warn!("Received block {:?} from {:?} whose parent is unknown", block.identifier, peer_addr);
+                        return None;
+                    }
+
+                    reassembled_chain.add_block(block);
+                }
+
+                chain = Some(reassembled_chain);
+            }
+
+            match chunk.next {
+                Some(next_token) => {
+                    token = Some(next_token);
+                }
+                None => {
+                    return chain;
+                }
+            }
+        }
+    }
+
+    /// Start the main loop to sign (aka. mint) blocks in the network.
+    pub fn sign(&mut self) {
+        let clique_protocol_handler = Arc::clone(&self.protocol);
+        // create a reference which we can share across threads
+        let peers = Arc::clone(&self.peers);
+        let relays = Arc::clone(&self.relays);
+        let transport = Arc::clone(&self.transport);
+        let signer = self.signer.clone();
+        let audit_log = Arc::clone(&self.audit_log);
+        let metrics = Arc::clone(&self.metrics);
+        let is_running = Arc::clone(&self.is_running);
+
+        self.thread_pool.execute("sign", move || {
+            let broadcast_pool = ThreadPool::new("broadcast", MAX_PARALLEL_BROADCASTS);
+            metrics.register_thread_pool(broadcast_pool.get_stats());
+            let pending_broadcasts: Arc<PendingBroadcasts> = Arc::new(Mutex::new(HashMap::new()));
+            let mut has_logged_signed_recently = false;
+            let mut has_logged_clock_skew = false;
+            let mut is_sealing_paused = false;
+
+            // start with waiting
+            while Node::sleep_while_running(&is_running, time::Duration::from_millis(1000)) {
+
+                // exchange blocks with the sealers voted in as well
+                let sealers = clique_protocol_handler.lock().unwrap().get_sealer_set().get_sealers().to_vec();
+                for sealer in sealers {
+                    if peers.lock().unwrap().add(sealer) {
+                        info!("Added sealer {} to the peers", sealer);
+                    }
+                }
+
+                // check whether we have to do something
+                let is_leader = clique_protocol_handler.lock().unwrap().is_leader();
+                let is_co_leader = clique_protocol_handler.lock().unwrap().is_co_leader();
+                if ! is_leader  && ! is_co_leader {
+                    // this is just to reduce log output spamming
+                    if ! has_logged_signed_recently {
+                        debug!("Signed recently, must wait for others...");
+                        has_logged_signed_recently = true;
+                    }
+                    continue;
+                }
+                // reset so that we get notified again...
+                has_logged_signed_recently = false;
+
+                if !clique_protocol_handler.lock().unwrap().is_block_period_over() {
+                    continue;
+                }
+
+                // blocks with timestamps off by too much are rejected by the peers anyway
+                if !clique_protocol_handler.lock().unwrap().is_clock_sane() {
+                    if !has_logged_clock_skew {
+                        warn!("Not sealing blocks as the local clock deviates too much from the clocks of the peers");
+                        has_logged_clock_skew = true;
+                    }
+                    continue;
+                }
+                has_logged_clock_skew = false;
+
+                // rather resynchronize than deepen a fork most sealers do not follow
+                let relations = Node::relate_sealer_heads(&clique_protocol_handler, &peers, &*transport);
+                let amount_sealers = clique_protocol_handler.lock().unwrap().get_sealer_set().get_sealers().len();
+                let relation_kinds: Vec<HeadRelation> = relations.iter().map(|(_, relation)| *relation).collect();
+                if divergence::is_minority_branch(&relation_kinds, amount_sealers) {
+                    let conflicting_sealers: Vec<SocketAddr> = relations.iter()
+                        .filter(|(_, relation)| *relation == HeadRelation::Conflicts)
+                        .map(|(sealer, _)| *sealer)
+                        .collect();
+
+                    if !is_sealing_paused {
+                        is_sealing_paused = true;
+                        Node::pause_sealing(&clique_protocol_handler, &audit_log, conflicting_sealers.clone(), amount_sealers);
+                    }
+
+                    for sealer in conflicting_sealers.iter() {
+                        match Node::sync_block_ranges(&*transport, sealer, &clique_protocol_handler) {
+                            Ok(amount_added_blocks) => {
+                                info!("Resynchronized {} blocks with sealer {:?}", amount_added_blocks, sealer);
+                                break;
+                            }
+                            Err(e) => {
+                                debug!("Failed to resynchronize with sealer {:?}: {}", sealer, e);
+                            }
+                        }
+                    }
+                    continue;
+                }
+                if is_sealing_paused {
+                    is_sealing_paused = false;
+                    Node::resume_sealing(&clique_protocol_handler, &audit_log);
+                }
+
+                let sealing_height = match clique_protocol_handler.lock().unwrap().get_head() {
+                    Some(head) => head.height + 1,
+                    None => {
+                        continue;
+                    }
+                };
+
+                clique_protocol_handler.lock().unwrap().publish_key_generation();
+                clique_protocol_handler.lock().unwrap().publish_decryption_shares();
+                clique_protocol_handler.lock().unwrap().publish_final_tally();
+
+                let current_block = match clique_protocol_handler.lock().unwrap().create_current_block() {
+                    Some(block) => block,
+                    None => {
+                        continue;
+                    }
+                };
+
+                // check whether we are a co-leader and must wait to sign the block
+                // for some time...
+                if clique_protocol_handler.lock().unwrap().is_co_leader() {
+                    debug!("I am co-leader and therefore adding wiggle before signing block {:?}", current_block.identifier.clone());
+                    // add some "wiggle" time to let leader nodes announce their blocks first
+                    thread::sleep(time::Duration::from_millis(1000));
+
+                    // only seal out of turn, if the leader did not already seal a block at this height
+                    if Node::has_leader_sealed(&clique_protocol_handler, &*transport, sealing_height) {
+                        info!("Leader already sealed a block at height {}, skipping out-of-turn block {:?}", sealing_height, current_block.identifier.clone());
+                        continue;
+                    }
+                }
+
+                let current_block = match signer {
+                    None => current_block,
+                    Some(ref signer) => {
+                        match signer.sign(&current_block.identifier) {
+                            Ok(signature) => {
+                                let mut signed_block = current_block;
+                                signed_block.signature = Some(signature);
+                                signed_block
+                            }
+                            Err(e) => {
+                                // rather skip our slot than sealing a block without signature
+                                warn!("Skipping block {:?} as it could not be signed: {}", current_block.identifier.clone(), e);
+                                continue;
+                            }
+                        }
+                    }
+                };
+
+                info!("Signing block {:?}", current_block.identifier.clone());
+                let block_to_broadcast = clique_protocol_handler.lock().unwrap().sign(current_block);
+
+                match block_to_broadcast {
+                    None => {
+                        // noop
+                    }
+                    Some(block) => {
+                        info!("Broadcasting block {:?}", block.identifier.clone());
+                        Node::broadcast_in_parallel(&broadcast_pool, &pending_broadcasts, &peers, &relays, &transport, Message::BlockPayload(block));
+                    }
+                }
+            }
+        });
+    }
+
+    /// Ask each other sealer for its head and relate it to the own canonical chain.
+    /// A sealer announcing a head above the own head is asked for its block at the height of the own head.
+    fn relate_sealer_heads(protocol: &Arc<Mutex<CliqueProtocol>>, peers: &Arc<Mutex<PeerSet>>, transport: &Transport) -> Vec<(SocketAddr, HeadRelation)> {
+        let (own_head, other_sealers) = {
+            let protocol = protocol.lock().unwrap();
+            let own_head = match protocol.get_head() {
+                Some(head) => head,
+                None => {
+                    return vec![];
+                }
+            };
+
+            let peers = peers.lock().unwrap();
+            let other_sealers: Vec<SocketAddr> = protocol.get_sealer_set().get_sealers().iter()
+                .filter(|sealer| !peers.is_own_address(sealer))
+                .cloned()
+                .collect();
+
+            (own_head, other_sealers)
+        };
+
+        other_sealers.into_iter()
+            .map(|sealer| {
+                let relation = Node::relate_sealer_head(protocol, transport, &sealer, own_head.height, &own_head.block.identifier);
+                (sealer, relation)
+            })
+            .collect()
+    }
+
+    /// Relate the head of the given sealer to the own head at the given height.
+    fn relate_sealer_head(protocol: &Arc<Mutex<CliqueProtocol>>, transport: &Transport, sealer: &SocketAddr, own_height: Height, own_head: &BlockHash) -> HeadRelation {
+        let timeout = time::Duration::from_millis(HEAD_QUERY_TIMEOUT);
+        let (height, head) = match transport.request_with_timeout(&peers::normalize(sealer), Message::HeadQuery, timeout) {
+            Ok(Message::HeadQueryResponse(height, head)) => (height, head),
+            Ok(other) => {
+                debug!("Sealer {} answered the head query with {:?}", sealer, other);
+                return HeadRelation::Unknown;
+            }
+            Err(e) => {
+                debug!("Sealer {} did not announce its head in time: {}", sealer, e);
+                return HeadRelation::Unknown;
+            }
+        };
+
+        if height <= own_height {
+            let canonical_block = protocol.lock().unwrap().get_block_by_number(height).map(|block| block.identifier);
+            return if canonical_block == Some(head) { HeadRelation::Agrees } else { HeadRelation::Conflicts };
+        }
+
+        // the sealer is ahead, hence its chain must contain the own head
+        match transport.request_with_timeout(&peers::normalize(sealer), Message::BlockByNumberRequest(own_height), timeout) {
+            Ok(Message::BlockByNumberResponse(Some(block))) => {
+                if block.identifier == *own_head { HeadRelation::Agrees } else { HeadRelation::Conflicts }
+            }
+            Ok(other) => {
+                debug!("Sealer {} answered the request for its block at height {} with {:?}", sealer, own_height, other);
+                HeadRelation::Unknown
+            }
+            Err(e) => {
+                debug!("Sealer {} did not send its block at height {} in time: {}", sealer, own_height, e);
+                HeadRelation::Unknown
+            }
+        }
+    }
+
+    /// Take note that sealing is paused, as the given sealers announced heads conflicting with the own head.
+    fn pause_sealing(protocol: &Arc<Mutex<CliqueProtocol>>, audit_log: &Mutex<AuditLog>, conflicting_sealers: Vec<SocketAddr>, amount_sealers: usize) {
+        let head = match protocol.lock().unwrap().get_head() {
+            Some(head) => head,
+            None => {
+                return;
+            }
+        };
+
+        warn!("Pausing sealing to resynchronize, as {} of {} sealers announced heads conflicting with the own head {:?} at height {}: {:?}", conflicting_sealers.len(), amount_sealers, head.block.identifier, head.height, conflicting_sealers);
+        let notice = DivergenceNotice::SealingPaused {
+            head_height: head.height,
+            head: head.block.identifier,
+            conflicting_sealers,
+            sealers: amount_sealers,
+            paused_at: Node::now(),
+        };
+        if let Err(e) = audit_log.lock().unwrap().append(&notice) {
+            error!("{}", e);
+        }
+    }
+
+    /// Take note that sealing is resumed, as the own head no longer conflicts with most sealers.
+    fn resume_sealing(protocol: &Arc<Mutex<CliqueProtocol>>, audit_log: &Mutex<AuditLog>) {
+        let head = match protocol.lock().unwrap().get_head() {
+            Some(head) => head,
+            None => {
+                return;
+            }
+        };
+
+        info!("Resuming sealing on the head {:?} at height {}", head.block.identifier, head.height);
+        let notice = DivergenceNotice::SealingResumed {
+            head_height: head.height,
+            head: head.block.identifier,
+            resumed_at: Node::now(),
+        };
+        if let Err(e) = audit_log.lock().unwrap().append(&notice) {
+            error!("{}", e);
+        }
+    }
+
+    /// Returns true, if the in-turn leader of the given height announces a head at or above it,
+    /// i.e. an out-of-turn block at the height would only compete with the block of the leader.
+    /// A leader not answering within `HEAD_QUERY_TIMEOUT` is assumed to not have sealed.
+    fn has_leader_sealed(protocol: &Arc<Mutex<CliqueProtocol>>, transport: &Transport, height: Height) -> bool {
+        let leader = match protocol.lock().unwrap().get_epoch_schedule().get_leader(height) {
+            Some(leader) => leader,
+            None => {
+                return false;
+            }
+        };
+
+        match transport.request_with_timeout(&peers::normalize(&leader), Message::HeadQuery, time::Duration::from_millis(HEAD_QUERY_TIMEOUT)) {
+            Ok(Message::HeadQueryResponse(leader_height, _)) => {
+                trace!("Leader {} announced its head at height {}", leader, leader_height);
+                leader_height >= height
+            }
+            Ok(other) => {
+                debug!("Leader {} answered the head query with {:?}", leader, other);
+                false
+            }
+            Err(e) => {
+                debug!("Leader {} did not announce its head in time: {}", leader, e);
+                false
+            }
+        }
+    }
+
+    /// Announce the identifiers of the pending transactions to all other sealers and send each of them
+    /// the transactions it does not know yet. Thus, transactions submitted to any node reach the leader,
+    /// even if their initial broadcast was lost or the leader restarted in the meantime.
+    fn gossip_mempool(protocol: &Arc<Mutex<CliqueProtocol>>, peers: &Arc<Mutex<PeerSet>>, transport: &Transport) {
+        let (announcement, other_sealers) = {
+            let protocol = protocol.lock().unwrap();
+            let announcement = protocol.get_mempool_announcement();
+            if announcement.is_empty() {
+                return;
+            }
+
+            let peers = peers.lock().unwrap();
+            let other_sealers: Vec<SocketAddr> = protocol.get_sealer_set().get_sealers().iter()
+                .filter(|sealer| !peers.is_own_address(sealer))
+                .cloned()
+                .collect();
+
+            (announcement, other_sealers)
+        };
+
+        for sealer in other_sealers {
+            let unknown_transactions = match transport.request(&peers::normalize(&sealer), Message::MempoolAnnouncement(announcement.clone())) {
+                Ok(Message::MempoolAnnouncementResponse(unknown_transactions)) => unknown_transactions,
+                Ok(other) => {
+                    debug!("Sealer {} answered the mempool announcement with {:?}", sealer, other);
+                    continue;
+                }
+                Err(e) => {
+                    debug!("Failed to announce the mempool to sealer {}: {}", sealer, e);
+                    continue;
+                }
+            };
+
+            if unknown_transactions.is_empty() {
+                continue;
+            }
+
+            let batches = protocol.lock().unwrap().get_pending_transaction_batches(&unknown_transactions);
+            for batch in batches {
+                match transport.request(&peers::normalize(&sealer), Message::PendingTransactions(batch)) {
+                    Ok(Message::PendingTransactionsAccept(amount_added)) => {
+                        trace!("Sealer {} added {} gossiped transactions to its mempool", sealer, amount_added);
+                    }
+                    Ok(other) => {
+                        debug!("Sealer {} answered the gossiped transactions with {:?}", sealer, other);
+                    }
+                    Err(e) => {
+                        debug!("Failed to gossip pending transactions to sealer {}: {}", sealer, e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Send the shares of the election key dealt by this sealer to their recipients, except to those
+    /// in the given set, which already accepted theirs. Recipients accepting their share are added to the set.
+    fn deal_key_shares(protocol: &Arc<Mutex<CliqueProtocol>>, transport: &Transport, delivered_shares: &mut HashSet<SocketAddr>) {
+        let dealt_shares: Vec<DealtShare> = protocol.lock().unwrap().get_dealt_shares().into_iter()
+            .filter(|dealt_share| !delivered_shares.contains(&dealt_share.recipient))
+            .collect();
+
+        for dealt_share in dealt_shares {
+            let recipient = dealt_share.recipient;
+            match transport.request(&peers::normalize(&recipient), Message::DkgShare(dealt_share)) {
+                Ok(Message::DkgShareAccept) => {
+                    info!("Sealer {} accepted its share of the election key", recipient);
+                    delivered_shares.insert(recipient);
+                }
+                Ok(Message::DkgShareReject(reason)) => {
+                    debug!("Sealer {} refused its share of the election key: {}", recipient, reason);
+                }
+                Ok(other) => {
+                    debug!("Sealer {} answered its share of the election key with {:?}", recipient, other);
+                }
+                Err(e) => {
+                    debug!("Failed to send the share of the election key to sealer {}: {}", recipient, e);
+                }
+            }
+        }
+    }
+
+    /// Send the given message to all known peers except ourselves.
+    /// Peers which registered a relay connection receive the message over it,
+    /// all others via the given transport.
+    /// Sleep for the given time, but wake up early once the node shuts down.
+    /// Returns false, if the node shuts down.
+    fn sleep_while_running(is_running: &AtomicBool, duration: time::Duration) -> bool {
+        let started_at = time::Instant::now();
+        while is_running.load(Ordering::SeqCst) {
+            let elapsed = started_at.elapsed();
+            if elapsed >= duration {
+                return true;
+            }
+
+            thread::sleep(cmp::min(duration - elapsed, time::Duration::from_millis(DRAIN_POLL_INTERVAL)));
+        }
+
+        false
+    }
+
+    fn broadcast(peers: &Arc<Mutex<PeerSet>>, relays: &RelaySet, transport: &Transport, message: Message) {
+        // release the lock before connecting to the peers
+        let other_peers = peers.lock().unwrap().others();
+
+        let mut unrelayed_peers = vec![];
+        for peer_addr in other_peers.into_iter() {
+            if relays.send(&peer_addr, message.clone()) {
+                trace!("Relayed message to {:?}", peer_addr);
+                continue;
+            }
+
+            unrelayed_peers.push(peer_addr);
+        }
+
+        transport.publish(&unrelayed_peers, message);
+    }
+
+    /// Broadcast the given message like `broadcast`, but send it to each peer not connected over a relay
+    /// on a worker of the given pool, waiting at most `BROADCAST_TIMEOUT` for each of them.
+    /// Returns without waiting for the responses, so that sealing is not delayed by slow or unreachable peers,
+    /// and each peer receives the message independently of the others.
+    /// A peer with a broadcast in flight only receives the latest of the messages broadcast in the meantime,
+    /// once the one in flight is done, so that an unreachable peer occupies at most one worker.
+    fn broadcast_in_parallel(broadcast_pool: &ThreadPool, pending_broadcasts: &Arc<PendingBroadcasts>, peers: &Arc<Mutex<PeerSet>>, relays: &RelaySet, transport: &Arc<Transport>, message: Message) {
+        // the transport already publishes to all peers at once, e.g. over gossipsub
+        if transport.is_publish_subscribe() {
+            Node::broadcast(peers, relays, &**transport, message);
+            return;
+        }
+
+        // release the lock before connecting to the peers
+        let other_peers = peers.lock().unwrap().others();
+
+        for peer_addr in other_peers.into_iter() {
+            if relays.send(&peer_addr, message.clone()) {
+                trace!("Relayed message to {:?}", peer_addr);
+                continue;
+            }
+
+            {
+                let mut pending_broadcasts = pending_broadcasts.lock().unwrap();
+                if let Some(pending_message) = pending_broadcasts.get_mut(&peer_addr) {
+                    trace!("Replacing message pending for {:?}, which is still busy with a broadcast", peer_addr);
+                    *pending_message = Some(message.clone());
+                    continue;
+                }
+                pending_broadcasts.insert(peer_addr, None);
+            }
+
+            let cloned_pending_broadcasts = Arc::clone(pending_broadcasts);
+            let cloned_transport = Arc::clone(transport);
+            let mut cloned_message = message.clone();
+            broadcast_pool.execute("broadcast", move || {
+                loop {
+                    if let Err(e) = cloned_transport.request_with_timeout(&peer_addr, cloned_message, time::Duration::from_millis(BROADCAST_TIMEOUT)) {
+                        warn!("Failed to send message to {:?}: {}", peer_addr, e);
+                    }
+
+                    let mut pending_broadcasts = cloned_pending_broadcasts.lock().unwrap();
+                    match pending_broadcasts.get_mut(&peer_addr).and_then(|pending_message| pending_message.take()) {
+                        Some(pending_message) => cloned_message = pending_message,
+                        None => {
+                            pending_broadcasts.remove(&peer_addr);
+                            break;
+                        }
+                    }
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod node_test {
+
+    use super::*;
+    use ::chain::block::Block;
+    use ::chain::hasher::Hasher;
+    use ::chain::sealer_set::{PendingVote, DEFAULT_VOTE_EPOCH_LENGTH};
+    use ::config::version::VersionPolicy;
+    use std::collections::BTreeMap;
+
+    /// Answers block range requests from the chain of a single peer, recording the requested heights.
+    struct BlockRangeTransport {
+        peer: Mutex<CliqueProtocol>,
+        requested_heights: Mutex<Vec<Height>>,
+    }
+
+    impl Transport for BlockRangeTransport {
+        fn request(&self, _peer_addr: &SocketAddr, message: Message) -> Result<Message, String> {
+            match message {
+                Message::BlockRangeRequest { from_height, to_height } => {
+                    self.requested_heights.lock().unwrap().push(from_height);
+                    Ok(Message::BlockRangeResponse(self.peer.lock().unwrap().get_block_range(from_height, to_height)))
+                }
+                other => Err(format!("Unexpected request {:?}", other))
+            }
+        }
+
+        fn request_with_timeout(&self, peer_addr: &SocketAddr, message: Message, _timeout: time::Duration) -> Result<Message, String> {
+            self.request(peer_addr, message)
+        }
+
+        fn use_codec(&self, _peer_addr: &SocketAddr, _codec: CodecKind) {}
+
+        fn use_compression(&self, _peer_addr: &SocketAddr, _compression: Option<CompressionKind>) {}
+    }
+
+    /// Records the messages sent to any peer, answering each request only once released.
+    struct BlockingTransport {
+        sent_messages: Mutex<Vec<Message>>,
+        releases: Mutex<Receiver<()>>,
+    }
+
+    impl Transport for BlockingTransport {
+        fn request(&self, _peer_addr: &SocketAddr, message: Message) -> Result<Message, String> {
+            self.sent_messages.lock().unwrap().push(message);
+            match self.releases.lock().unwrap().recv() {
+                Ok(()) => Ok(Message::BlockAccept),
+                Err(e) => Err(format!("{:?}", e))
+            }
+        }
+
+        fn request_with_timeout(&self, peer_addr: &SocketAddr, message: Message, _timeout: time::Duration) -> Result<Message, String> {
+            self.request(peer_addr, message)
+        }
+
+        fn use_codec(&self, _peer_addr: &SocketAddr, _codec: CodecKind) {}
+
+        fn use_compression(&self, _peer_addr: &SocketAddr, _compression: Option<CompressionKind>) {}
+    }
+
+    /// Answers each request with the protocol of a single peer, whatever the address of the request.
+    struct PeerTransport {
+        peer: Mutex<CliqueProtocol>,
+    }
+
+    impl Transport for PeerTransport {
+        fn request(&self, _peer_addr: &SocketAddr, message: Message) -> Result<Message, String> {
+            Ok(self.peer.lock().unwrap().handle(message))
+        }
+
+        fn request_with_timeout(&self, peer_addr: &SocketAddr, message: Message, _timeout: time::Duration) -> Result<Message, String> {
+            self.request(peer_addr, message)
+        }
+
+        fn use_codec(&self, _peer_addr: &SocketAddr, _codec: CodecKind) {}
+
+        fn use_compression(&self, _peer_addr: &SocketAddr, _compression: Option<CompressionKind>) {}
+    }
+
+    /// Fails each request as if no peer was reachable, recording the failure like `TcpTransport`.
+    struct UnreachableTransport {
+        peer_health: Arc<PeerHealthTracker>,
+    }
+
+    impl Transport for UnreachableTransport {
+        fn request(&self, peer_addr: &SocketAddr, _message: Message) -> Result<Message, String> {
+            self.peer_health.record_failure(peer_addr, "Connection refused");
+            Err("Connection refused".to_string())
+        }
+
+        fn request_with_timeout(&self, peer_addr: &SocketAddr, message: Message, _timeout: time::Duration) -> Result<Message, String> {
+            self.request(peer_addr, message)
+        }
+
+        fn use_codec(&self, _peer_addr: &SocketAddr, _codec: CodecKind) {}
+
+        fn use_compression(&self, _peer_addr: &SocketAddr, _compression: Option<CompressionKind>) {}
+    }
+
+    fn new_genesis() -> Genesis {
+        Genesis::new("genesis.json", "public_uciv.json", "public_key.json")
+    }
+
+    fn new_protocol() -> CliqueProtocol {
+        let genesis = new_genesis();
+        CliqueProtocol::new(genesis.sealer[0], genesis, VersionPolicy::Ignore)
+    }
+
+    /// Create the given amount of protocols sharing the same genesis block,
+    /// which is stamped with the time the protocol is created.
+    fn new_protocols(amount: usize) -> Vec<CliqueProtocol> {
+        loop {
+            let protocols: Vec<CliqueProtocol> = (0..amount).map(|_| new_protocol()).collect();
+            let genesis_block = protocols[0].get_block_by_number(Height::GENESIS);
+            if protocols.iter().all(|protocol| protocol.get_block_by_number(Height::GENESIS) == genesis_block) {
+                return protocols;
+            }
+        }
+    }
+
+    /// Seal the given amount of blocks on top of the given parent, by the given sealers in turn.
+    fn new_blocks(parent: &Block, sealers: &[SocketAddr], amount: usize) -> Vec<Block> {
+        let mut blocks: Vec<Block> = vec![];
+        for index in 0..amount {
+            let parent = blocks.last().unwrap_or(parent).clone();
+            let mut block = Block::new(Hasher::default(), parent.identifier.clone(), Some(sealers[index % sealers.len()]), vec![]);
+            block.data.timestamp = parent.data.timestamp + 1;
+            block.identifier = block.get_header().unwrap().hash(Hasher::default());
+            blocks.push(block);
+        }
+
+        blocks
+    }
+
+    #[test]
+    fn test_sync_block_ranges() {
+        let mut protocols = new_protocols(3);
+        let (peer, mut own_protocol, mut finalized_protocol) = (protocols.remove(0), protocols.remove(0), protocols.remove(0));
+        let sealers = new_genesis().sealer;
+        let genesis_block = peer.get_block_by_number(Height::GENESIS).unwrap();
+        let peer_addr = sealers[1];
+
+        // both chains share two blocks, the chain of the peer then continues with a different branch of ten blocks
+        let shared_blocks = new_blocks(&genesis_block, &sealers, 2);
+        let peer_blocks = new_blocks(&shared_blocks[1], &[sealers[2], sealers[0], sealers[1]], 10);
+        // sealed by a single sealer only, the own branch is not finalized
+        let own_blocks = new_blocks(&shared_blocks[1], &[sealers[0]], 4);
+        let transport = BlockRangeTransport {
+            peer: Mutex::new(peer),
+            requested_heights: Mutex::new(vec![]),
+        };
+        {
+            let mut peer = transport.peer.lock().unwrap();
+            assert_eq!(Ok(12), peer.add_block_range(shared_blocks.iter().chain(peer_blocks.iter()).cloned().collect()));
+        }
+
+        assert_eq!(Ok(6), own_protocol.add_block_range(shared_blocks.iter().chain(own_blocks.iter()).cloned().collect()));
+        let own_protocol = Arc::new(Mutex::new(own_protocol));
+
+        // the fork below the own head is found by stepping back twice as far on each request
+        assert_eq!(Ok(10), Node::sync_block_ranges(&transport, &peer_addr, &own_protocol));
+        assert_eq!(vec![Height::new(7), Height::new(6), Height::new(4), Height::new(1)], *transport.requested_heights.lock().unwrap());
+        assert_eq!(Some(peer_blocks[9].clone()), own_protocol.lock().unwrap().get_head().map(|head| head.block));
+
+        // once both chains are equal, no more blocks are added
+        transport.requested_heights.lock().unwrap().clear();
+        assert_eq!(Ok(0), Node::sync_block_ranges(&transport, &peer_addr, &own_protocol));
+        assert_eq!(vec![Height::new(13)], *transport.requested_heights.lock().unwrap());
+
+        // a chain starting with a snapshot does not step back below its root
+        let finalized_blocks = new_blocks(&shared_blocks[1], &[sealers[0], sealers[2], sealers[1]], 4);
+        assert_eq!(Ok(6), finalized_protocol.add_block_range(shared_blocks.iter().chain(finalized_blocks.iter()).cloned().collect()));
+        let mut snapshot_protocol = new_protocol();
+        assert_eq!(Ok(()), snapshot_protocol.adopt_state_snapshot(finalized_protocol.get_state_snapshot(Some(Height::new(3))).unwrap()));
+        assert_eq!(Ok(3), snapshot_protocol.add_block_range(finalized_blocks[1..].to_vec()));
+        let snapshot_protocol = Arc::new(Mutex::new(snapshot_protocol));
+        transport.requested_heights.lock().unwrap().clear();
+        assert!(Node::sync_block_ranges(&transport, &peer_addr, &snapshot_protocol).is_err());
+        assert_eq!(vec![Height::new(7), Height::new(6), Height::new(4)], *transport.requested_heights.lock().unwrap());
+        assert_eq!(Height::new(6), snapshot_protocol.lock().unwrap().get_head().unwrap().height);
+    }
+
+    #[test]
+    fn test_is_authorized_source() {
+        let sealers: Vec<SocketAddr> = vec!["10.0.0.1:9000".parse().unwrap(), "10.0.0.2:9000".parse().unwrap()];
+        let pending_vote = PendingVote { voter: sealers[0], candidate: "10.0.0.3:9000".parse().unwrap(), authorize: true, public_key: None };
+        let sealer_set = SealerSet::restore(sealers, BTreeMap::new(), vec![pending_vote], DEFAULT_VOTE_EPOCH_LENGTH);
+
+        // sealers and candidates connect from an arbitrary port
+        assert!(Node::is_authorized_source(&sealer_set, &"10.0.0.2:53124".parse().unwrap()));
+        assert!(Node::is_authorized_source(&sealer_set, &"10.0.0.3:53124".parse().unwrap()));
+        assert!(Node::is_authorized_source(&sealer_set, &"127.0.0.1:53124".parse().unwrap()));
+        assert!(!Node::is_authorized_source(&sealer_set, &"10.0.0.4:9000".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_broadcast_in_parallel_coalesces_pending_messages() {
+        let own_address: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let peer_addr: SocketAddr = "127.0.0.1:9001".parse().unwrap();
+        let peers = Arc::new(Mutex::new(PeerSet::new(own_address, vec![own_address, peer_addr])));
+        let relays = RelaySet::new(PeerSet::new(own_address, vec![]));
+        let (release_sender, releases) = mpsc::channel();
+        let blocking_transport = Arc::new(BlockingTransport { sent_messages: Mutex::new(vec![]), releases: Mutex::new(releases) });
+        let transport: Arc<Transport> = blocking_transport.clone();
+        let broadcast_pool = ThreadPool::new("broadcast", 2);
+        let pending_broadcasts: Arc<PendingBroadcasts> = Arc::new(Mutex::new(HashMap::new()));
+        let wait_for = |condition: &Fn() -> bool| {
+            while !condition() {
+                thread::sleep(time::Duration::from_millis(10));
+            }
+        };
+
+        Node::broadcast_in_parallel(&broadcast_pool, &pending_broadcasts, &peers, &relays, &transport, Message::Ping);
+        wait_for(&|| blocking_transport.sent_messages.lock().unwrap().len() == 1);
+
+        // messages broadcast while the peer is busy replace each other instead of queueing up
+        Node::broadcast_in_parallel(&broadcast_pool, &pending_broadcasts, &peers, &relays, &transport, Message::HeadQuery);
+        Node::broadcast_in_parallel(&broadcast_pool, &pending_broadcasts, &peers, &relays, &transport, Message::ChainRequest);
+        assert_eq!(Some(&Some(Message::ChainRequest)), pending_broadcasts.lock().unwrap().get(&peer_addr));
+
+        release_sender.send(()).unwrap();
+        release_sender.send(()).unwrap();
+        wait_for(&|| pending_broadcasts.lock().unwrap().is_empty());
+        assert_eq!(vec![Message::Ping, Message::ChainRequest], *blocking_transport.sent_messages.lock().unwrap());
+    }
+
+    #[test]
+    fn test_send_heartbeat_marks_silent_sealers() {
+        let peer_health = Arc::new(PeerHealthTracker::new());
+        let mut protocol = new_protocol();
+        protocol.set_peer_health(Arc::clone(&peer_health));
+        let protocol = Mutex::new(protocol);
+        let transport = UnreachableTransport { peer_health: Arc::clone(&peer_health) };
+        let other_sealers: Vec<SocketAddr> = new_genesis().sealer.into_iter().skip(1).collect();
+        let block_period = 5;
+        let now = clock::now_millis() / 1000;
+
+        // sealers are not silent within the first block period of the heartbeats
+        assert!(Node::send_heartbeat(&protocol, &transport, &peer_health, now, block_period).is_empty());
+        assert!(protocol.lock().unwrap().get_peer_status().iter().all(|health| !health.silent));
+
+        // a sealer which did not answer for more than a block period is warned about once
+        let started_at = now - block_period - 1;
+        assert_eq!(other_sealers, Node::send_heartbeat(&protocol, &transport, &peer_health, started_at, block_period));
+        assert!(Node::send_heartbeat(&protocol, &transport, &peer_health, started_at, block_period).is_empty());
+        let status = protocol.lock().unwrap().get_peer_status();
+        assert!(status.iter().all(|health| health.silent));
+        assert_eq!(Some("Connection refused".to_string()), status[0].last_error);
+
+        // and is no longer marked once it answers again
+        peer_health.record_success(&other_sealers[0], time::Duration::from_millis(1));
+        assert!(Node::send_heartbeat(&protocol, &transport, &peer_health, started_at, block_period).is_empty());
+        let status = protocol.lock().unwrap().get_peer_status();
+        assert_eq!(vec![false, true], status.iter().map(|health| health.silent).collect::<Vec<bool>>());
+    }
+
+    #[test]
+    fn test_has_leader_sealed() {
+        let mut protocols = new_protocols(2);
+        let (leader, own_protocol) = (protocols.remove(0), Arc::new(Mutex::new(protocols.remove(0))));
+        let sealers = new_genesis().sealer;
+        let genesis_block = leader.get_block_by_number(Height::GENESIS).unwrap();
+        let blocks = new_blocks(&genesis_block, &sealers, 2);
+        let transport = PeerTransport { peer: Mutex::new(leader) };
+
+        // the leader of the second height did not seal yet
+        assert_eq!(Ok(1), transport.peer.lock().unwrap().add_block_range(blocks[..1].to_vec()));
+        assert!(!Node::has_leader_sealed(&own_protocol, &transport, Height::new(2)));
+
+        // once it did, a co-leader skips the height
+        assert_eq!(Ok(1), transport.peer.lock().unwrap().add_block_range(blocks[1..].to_vec()));
+        assert!(Node::has_leader_sealed(&own_protocol, &transport, Height::new(2)));
+        assert!(!Node::has_leader_sealed(&own_protocol, &transport, Height::new(3)));
+
+        // an unreachable leader is assumed to not have sealed
+        let unreachable_transport = UnreachableTransport { peer_health: Arc::new(PeerHealthTracker::new()) };
+        assert!(!Node::has_leader_sealed(&own_protocol, &unreachable_transport, Height::new(2)));
+    }
+
+    /// Create a node listening for RPC connections only, on a free local port.
+    fn new_rpc_node() -> (Node, SocketAddr) {
+        let genesis = new_genesis();
+        let mut node_config = NodeConfig::default();
+        node_config.advertised_address = Some(genesis.sealer[0]);
+        let listen_address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let rpc_address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+
+        let node = Node::new(listen_address, rpc_address, genesis, node_config);
+        node.listen_rpc();
+
+        (node, rpc_address)
+    }
+
+    /// Request the status of the node listening for RPC connections on the given address.
+    fn request_status(address: &SocketAddr) -> Result<Message, String> {
+        let mut stream = match TcpStream::connect(address) {
+            Ok(stream) => stream,
+            Err(e) => {
+                return Err(format!("{:?}", e));
+            }
+        };
+
+        let mut response = vec![];
+        let result = stream.write_all(&JsonCodec::encode(Message::StatusRequest))
+            .and_then(|_| stream.shutdown(Shutdown::Write))
+            .and_then(|_| stream.read_to_end(&mut response));
+        match result {
+            Ok(_) => Ok(JsonCodec::decode(&response)),
+            Err(e) => Err(format!("{:?}", e))
+        }
+    }
+
+    #[test]
+    fn test_shutdown() {
+        let (node, rpc_address) = new_rpc_node();
+        match request_status(&rpc_address) {
+            Ok(Message::StatusResponse(_)) => {}
+            response => panic!("Expected the status of the node, got {:?}", response)
+        }
+
+        // without connections in flight, the node shuts down without waiting for the deadline
+        let deadline = time::Duration::from_secs(5);
+        let started_at = time::Instant::now();
+        node.shutdown(deadline);
+        assert!(started_at.elapsed() < deadline);
+        assert!(!node.accepting_connections.load(Ordering::SeqCst));
+        assert!(request_status(&rpc_address).is_err());
+    }
+
+    #[test]
+    fn test_shutdown_waits_for_connections_until_deadline() {
+        let (node, rpc_address) = new_rpc_node();
+
+        // a client which never finishes its request keeps its connection in flight
+        let stalled_client = TcpStream::connect(rpc_address).unwrap();
+        for _ in 0..100 {
+            if node.in_flight_connections.load(Ordering::SeqCst) > 0 {
+                break;
+            }
+            thread::sleep(time::Duration::from_millis(10));
+        }
+        assert_eq!(1, node.in_flight_connections.load(Ordering::SeqCst));
+
+        let deadline = time::Duration::from_millis(500);
+        let started_at = time::Instant::now();
+        node.shutdown(deadline);
+        assert!(started_at.elapsed() >= deadline);
+        assert!(started_at.elapsed() < time::Duration::from_millis(INCOMING_CONNECTION_TIMEOUT));
+        drop(stalled_client);
+    }
+}
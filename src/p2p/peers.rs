@@ -0,0 +1,156 @@
+use std::collections::HashSet;
+use std::iter::FromIterator;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+
+/// Normalize the given socket address so that different notations
+/// of the same peer compare equal:
+///
+/// - IPv4-mapped IPv6 addresses (e.g. `[::ffff:127.0.0.1]:9000`) are converted to IPv4.
+/// - Unspecified addresses (e.g. `0.0.0.0:9000`) are converted to the loopback address
+///   of the same family, as this is how a node listening on all interfaces is reached locally.
+pub fn normalize(address: &SocketAddr) -> SocketAddr {
+    let ip = match address.ip() {
+        IpAddr::V6(ip) => {
+            match ip.to_ipv4() {
+                // to_ipv4 also converts IPv4-compatible addresses such as ::1,
+                // therefore only accept IPv4-mapped ones
+                Some(ipv4) if ip.segments()[5] == 0xffff => IpAddr::V4(ipv4),
+                _ => IpAddr::V6(ip)
+            }
+        }
+        ip => ip
+    };
+
+    let ip = match ip {
+        IpAddr::V4(ref ipv4) if ipv4.is_unspecified() => IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+        IpAddr::V6(ref ipv6) if ipv6.is_unspecified() => IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)),
+        ip => ip
+    };
+
+    SocketAddr::new(ip, address.port())
+}
+
+/// Returns true, if both addresses identify the same peer after normalization.
+pub fn is_same_peer(first: &SocketAddr, second: &SocketAddr) -> bool {
+    normalize(first).eq(&normalize(second))
+}
+
+/// Resolve the given address, which may also contain a hostname
+/// such as `localhost:9000`, to a socket address.
+/// IPv4 addresses are preferred if the hostname resolves to multiple addresses.
+///
+/// Returns None if the address cannot be resolved.
+pub fn resolve(address: &str) -> Option<SocketAddr> {
+    let addresses: Vec<SocketAddr> = match address.to_socket_addrs() {
+        Ok(addresses) => addresses.collect(),
+        Err(e) => {
+            warn!("Failed to resolve address {:?}: {:?}", address, e);
+            return None;
+        }
+    };
+
+    addresses.iter()
+        .find(|address| address.is_ipv4())
+        .or(addresses.first())
+        .cloned()
+}
+
+/// The set of peers a node connects and broadcasts messages to.
+///
+/// All addresses are normalized, so that the node is able to reliably
+/// exclude itself from the set of peers, regardless of the notation
+/// used for its own address.
+#[derive(Clone, Debug)]
+pub struct PeerSet {
+    /// The normalized address under which other peers know this node.
+    own_address: SocketAddr,
+    /// The normalized addresses of all peers, possibly including our own one.
+    peers: HashSet<SocketAddr>,
+}
+
+impl PeerSet {
+    /// Create a new set of peers.
+    ///
+    /// - `own_address` The address under which other peers know this node, i.e. the one
+    ///   contained in the genesis configuration.
+    /// - `peers` The addresses of all peers. May contain the own address.
+    pub fn new(own_address: SocketAddr, peers: Vec<SocketAddr>) -> Self {
+        PeerSet {
+            own_address: normalize(&own_address),
+            peers: HashSet::from_iter(peers.iter().map(normalize)),
+        }
+    }
+
+    /// Returns the normalized address under which other peers know this node.
+    pub fn own_address(&self) -> SocketAddr {
+        self.own_address
+    }
+
+    /// Returns true, if the given address identifies this node.
+    pub fn is_own_address(&self, address: &SocketAddr) -> bool {
+        self.own_address.eq(&normalize(address))
+    }
+
+    /// Add the given peer, e.g. a sealer added by the votes of the sealers.
+    /// Returns true, if the peer was not part of the set yet.
+    pub fn add(&mut self, address: SocketAddr) -> bool {
+        self.peers.insert(normalize(&address))
+    }
+
+    /// Remove the given peer, so that no more messages are exchanged with it.
+    /// Returns true, if the peer was part of the set.
+    pub fn remove(&mut self, address: &SocketAddr) -> bool {
+        self.peers.remove(&normalize(address))
+    }
+
+    /// Returns the addresses of all peers except ourselves.
+    pub fn others(&self) -> Vec<SocketAddr> {
+        self.peers.iter()
+            .filter(|peer| !self.own_address.eq(peer))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod peers_test {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let address: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+
+        assert_eq!(address, normalize(&"[::ffff:127.0.0.1]:9000".parse().unwrap()));
+        assert_eq!(address, normalize(&"0.0.0.0:9000".parse().unwrap()));
+        assert_eq!("[::1]:9000".parse::<SocketAddr>().unwrap(), normalize(&"[::]:9000".parse().unwrap()));
+        // IPv4-compatible addresses are no IPv4 addresses
+        assert_eq!("[::1]:9000".parse::<SocketAddr>().unwrap(), normalize(&"[::1]:9000".parse().unwrap()));
+
+        assert!(is_same_peer(&address, &"[::ffff:127.0.0.1]:9000".parse().unwrap()));
+        assert!(!is_same_peer(&address, &"127.0.0.1:9001".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_others() {
+        let peers = vec!["127.0.0.1:9000".parse().unwrap(), "127.0.0.1:9001".parse().unwrap()];
+
+        // the own address is excluded regardless of its notation
+        let mut peer_set = PeerSet::new("0.0.0.0:9000".parse().unwrap(), peers.clone());
+        assert_eq!(vec!["127.0.0.1:9001".parse::<SocketAddr>().unwrap()], peer_set.others());
+        assert!(peer_set.is_own_address(&"[::ffff:127.0.0.1]:9000".parse().unwrap()));
+        assert!(!peer_set.is_own_address(&"127.0.0.1:9001".parse().unwrap()));
+
+        // added peers are normalized as well
+        assert!(peer_set.add("[::ffff:127.0.0.1]:9002".parse().unwrap()));
+        assert!(!peer_set.add("127.0.0.1:9002".parse().unwrap()));
+        let mut others = peer_set.others();
+        others.sort();
+        assert_eq!(vec!["127.0.0.1:9001".parse::<SocketAddr>().unwrap(), "127.0.0.1:9002".parse().unwrap()], others);
+        assert!(peer_set.remove(&"127.0.0.1:9001".parse().unwrap()));
+        assert_eq!(vec!["127.0.0.1:9002".parse::<SocketAddr>().unwrap()], peer_set.others());
+
+        // a node advertising another address than the one listed among the peers does not exclude itself
+        let peer_set = PeerSet::new("127.0.0.1:9100".parse().unwrap(), peers);
+        assert_eq!(2, peer_set.others().len());
+    }
+}
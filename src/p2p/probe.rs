@@ -0,0 +1,222 @@
+use ::config::feature;
+use ::config::genesis::Genesis;
+use ::p2p::client;
+use ::p2p::codec::{self, Codec, CodecKind, JsonCodec, Message};
+use ::protocol::block_validator::MAX_TIMESTAMP_DRIFT;
+use ::protocol::clique::{NodeStatus, NODE_VERSION};
+use std::net::SocketAddr;
+
+/// The outcome of a single check of a probe.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum ProbeOutcome {
+    /// The peer is compatible in this respect.
+    Passed,
+    /// The peer differs in a way which may cause problems.
+    Warning,
+    /// The peer is not compatible.
+    Failed,
+}
+
+/// A single check performed when probing a peer.
+#[derive(Debug, Clone)]
+pub struct ProbeCheck {
+    pub name: String,
+    pub outcome: ProbeOutcome,
+    pub message: String,
+}
+
+/// The diagnosis of probing a peer.
+#[derive(Debug, Clone)]
+pub struct ProbeReport {
+    /// The checks in the order they were performed.
+    pub checks: Vec<ProbeCheck>,
+    /// The status reported by the peer, if it could be obtained.
+    pub status: Option<NodeStatus>,
+}
+
+impl ProbeReport {
+    fn new() -> ProbeReport {
+        ProbeReport {
+            checks: vec![],
+            status: None,
+        }
+    }
+
+    fn add(&mut self, name: &str, outcome: ProbeOutcome, message: String) {
+        self.checks.push(ProbeCheck {
+            name: name.to_string(),
+            outcome,
+            message,
+        });
+    }
+
+    /// Returns true, if no check failed.
+    pub fn is_compatible(&self) -> bool {
+        self.checks.iter().all(|check| ProbeOutcome::Failed != check.outcome)
+    }
+}
+
+/// Probe the peer listening on the given address for its compatibility with this node.
+///
+/// - `address` The address of the peer.
+/// - `genesis` The own genesis configuration to compare the one of the peer with.
+///   If None, the genesis configuration is not compared.
+pub fn probe(address: &SocketAddr, genesis: Option<&Genesis>) -> ProbeReport {
+    let mut report = ProbeReport::new();
+
+    // a ping verifies that the peer is reachable and understands our codec
+    match client::request(address, Message::Ping) {
+        Ok(Message::Pong) => {
+            report.add("connection", ProbeOutcome::Passed, format!("Peer at {} is reachable", address));
+        }
+        Ok(other) => {
+            report.add("connection", ProbeOutcome::Failed, format!("Peer responded to a ping with {:?} instead of a pong. It may not use the {} codec", other, JsonCodec::name()));
+            return report;
+        }
+        Err(e) => {
+            report.add("connection", ProbeOutcome::Failed, e);
+            return report;
+        }
+    }
+
+    let status = match client::request(address, Message::StatusRequest) {
+        Ok(Message::StatusResponse(status)) => status,
+        Ok(other) => {
+            report.add("status", ProbeOutcome::Failed, format!("Peer responded to a status request with {:?}. It is probably running an older version", other));
+            return report;
+        }
+        Err(e) => {
+            report.add("status", ProbeOutcome::Failed, e);
+            return report;
+        }
+    };
+    report.add("status", ProbeOutcome::Passed, format!("Peer is at height {} with head {:?}", status.height, status.head_identifier));
+    if !status.log_filters.is_empty() {
+        report.add("log filters", ProbeOutcome::Passed, format!("Peer filters its log output with {}", status.log_filters.join(",")));
+    }
+
+    if let Some(clock_offset) = status.clock_offset {
+        if clock_offset.unsigned_abs() > MAX_TIMESTAMP_DRIFT * 1000 {
+            report.add("clock", ProbeOutcome::Warning, format!("Clock of peer deviates by {}ms from the clocks of its peers. It refuses to seal blocks", -clock_offset));
+        } else {
+            report.add("clock", ProbeOutcome::Passed, format!("Clock of peer deviates by {}ms from the clocks of its peers", -clock_offset));
+        }
+    }
+
+    if JsonCodec::name() != status.codec {
+        report.add("codec", ProbeOutcome::Failed, format!("Peer uses the {} codec, but we use {}", status.codec, JsonCodec::name()));
+    } else if status.codecs.is_empty() {
+        report.add("codec", ProbeOutcome::Passed, format!("Peer uses the {} codec", status.codec));
+    } else {
+        let negotiated_codec = codec::negotiate(&status.codecs);
+        let outcome = if CodecKind::Json == negotiated_codec && status.codecs.len() > 1 { ProbeOutcome::Warning } else { ProbeOutcome::Passed };
+        report.add("codec", outcome, format!("Peer decodes the codecs {}, we would exchange messages in {}", status.codecs.join(","), negotiated_codec.name()));
+    }
+
+    if NODE_VERSION == status.node_version {
+        report.add("node version", ProbeOutcome::Passed, format!("Peer runs version {}", status.node_version));
+    } else {
+        report.add("node version", ProbeOutcome::Warning, format!("Peer runs version {}, but we run {}", status.node_version, NODE_VERSION));
+    }
+
+    match genesis {
+        Some(genesis) => {
+            if genesis.version == status.genesis_version {
+                report.add("genesis version", ProbeOutcome::Passed, format!("Peer uses genesis version {}", status.genesis_version));
+            } else {
+                report.add("genesis version", ProbeOutcome::Failed, format!("Peer uses genesis version {}, but we use {}", status.genesis_version, genesis.version));
+            }
+
+            let genesis_configuration_hash = genesis.get_configuration_hash();
+            if genesis_configuration_hash == status.genesis_configuration_hash {
+                report.add("genesis configuration", ProbeOutcome::Passed, format!("Peer uses the same genesis configuration {}", genesis_configuration_hash));
+            } else {
+                let mut message = format!("Peer uses genesis configuration {}, but we use {}", status.genesis_configuration_hash, genesis_configuration_hash);
+                if genesis.sealer != status.sealer {
+                    message.push_str(&format!(". Sealers differ: {:?} vs. {:?}", status.sealer, genesis.sealer));
+                }
+                report.add("genesis configuration", ProbeOutcome::Failed, message);
+            }
+
+            match feature::find_difference(&genesis.features, &status.features) {
+                None => report.add("features", ProbeOutcome::Passed, format!("Peer enables the same {} features", status.features.len())),
+                Some(difference) => report.add("features", ProbeOutcome::Failed, difference)
+            }
+        }
+        None => {
+            report.add("genesis configuration", ProbeOutcome::Warning, "No own genesis configuration found to compare with".to_string());
+        }
+    }
+
+    report.status = Some(status);
+
+    report
+}
+
+#[cfg(test)]
+mod probe_test {
+    use super::*;
+    use ::config::version::VersionPolicy;
+    use ::protocol::clique::{CliqueProtocol, ProtocolHandler};
+    use std::io::{Read, Write};
+    use std::net::{Shutdown, TcpListener};
+    use std::thread;
+
+    /// Answer the given amount of requests as a peer running the given genesis configuration.
+    fn serve(genesis: Genesis, requests: usize) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        thread::spawn(move || {
+            let mut peer = CliqueProtocol::new(genesis.sealer[1], genesis, VersionPolicy::Ignore);
+            for stream in listener.incoming().take(requests) {
+                let mut stream = stream.unwrap();
+                let mut request = vec![];
+                stream.read_to_end(&mut request).unwrap();
+                stream.write_all(&JsonCodec::encode(peer.handle(JsonCodec::decode(&request)))).unwrap();
+                stream.shutdown(Shutdown::Both).unwrap();
+            }
+        });
+
+        address
+    }
+
+    fn new_genesis() -> Genesis {
+        Genesis::new("genesis.json", "public_uciv.json", "public_key.json")
+    }
+
+    fn outcome_of(report: &ProbeReport, name: &str) -> Option<ProbeOutcome> {
+        report.checks.iter().find(|check| name == check.name).map(|check| check.outcome.clone())
+    }
+
+    #[test]
+    fn test_probe() {
+        let genesis = new_genesis();
+
+        let report = probe(&serve(new_genesis(), 2), Some(&genesis));
+        assert!(report.is_compatible());
+        assert_eq!(Some(ProbeOutcome::Passed), outcome_of(&report, "genesis configuration"));
+        assert_eq!(Some(genesis.get_configuration_hash()), report.status.map(|status| status.genesis_configuration_hash));
+
+        // without an own genesis configuration, the one of the peer cannot be compared
+        let report = probe(&serve(new_genesis(), 2), None);
+        assert!(report.is_compatible());
+        assert_eq!(Some(ProbeOutcome::Warning), outcome_of(&report, "genesis configuration"));
+
+        // a peer running another genesis configuration is not compatible
+        let mut other_genesis = new_genesis();
+        other_genesis.version = format!("{}-other", genesis.version);
+        let report = probe(&serve(other_genesis, 2), Some(&genesis));
+        assert!(!report.is_compatible());
+        assert_eq!(Some(ProbeOutcome::Failed), outcome_of(&report, "genesis version"));
+        assert_eq!(Some(ProbeOutcome::Failed), outcome_of(&report, "genesis configuration"));
+
+        // an unreachable peer fails the first check only
+        let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let report = probe(&address, Some(&genesis));
+        assert!(!report.is_compatible());
+        assert_eq!(1, report.checks.len());
+        assert_eq!(Some(ProbeOutcome::Failed), outcome_of(&report, "connection"));
+        assert!(report.status.is_none());
+    }
+}
@@ -0,0 +1,186 @@
+use ::metrics::RateLimitMetrics;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+/// The amount of tracked addresses above which idle ones are forgotten.
+const MAX_TRACKED_SOURCES: usize = 4096;
+
+/// The limits on the traffic of a single IP address on a listener.
+/// None, if the respective traffic is not limited.
+#[derive(Eq, PartialEq, Clone, Copy, Debug, Default)]
+pub struct RateLimits {
+    /// The maximum amount of connections of a single address open at once.
+    pub max_connections: Option<usize>,
+    /// The maximum amount of messages a single address may send per second, which it may
+    /// send in a burst after being idle. As each incoming connection carries a single message,
+    /// this limits the rate at which connections are accepted.
+    pub messages_per_second: Option<u32>,
+}
+
+/// The traffic of a single IP address.
+struct Source {
+    /// The amount of connections currently open.
+    open_connections: usize,
+    /// The amount of messages the source may send right away.
+    allowance: f64,
+    /// The time the allowance was last updated at.
+    updated_at: Instant,
+}
+
+impl Source {
+    /// Returns true, if the source has neither open connections nor any spent allowance.
+    fn is_idle(&self, limits: &RateLimits, now: Instant) -> bool {
+        if 0 != self.open_connections {
+            return false;
+        }
+
+        match limits.messages_per_second {
+            Some(rate) => self.allowance + now.duration_since(self.updated_at).as_secs_f64() * f64::from(rate) >= f64::from(rate),
+            None => true
+        }
+    }
+}
+
+/// Limits the connections and messages each IP address may open and send on a listener,
+/// so that a single client cannot exhaust the workers and the lock of the protocol.
+///
+/// Connections exceeding a limit are dropped without a response, and counted in the metrics.
+pub struct RateLimiter {
+    /// The listener the limits apply to, e.g. `peer` or `rpc`.
+    name: &'static str,
+    limits: RateLimits,
+    sources: Mutex<HashMap<IpAddr, Source>>,
+    dropped_connections: AtomicUsize,
+    dropped_messages: AtomicUsize,
+}
+
+impl RateLimiter {
+    /// Create a new limiter of the listener with the given name, enforcing the given limits.
+    pub fn new(name: &'static str, limits: RateLimits) -> RateLimiter {
+        RateLimiter {
+            name,
+            limits,
+            sources: Mutex::new(HashMap::new()),
+            dropped_connections: AtomicUsize::new(0),
+            dropped_messages: AtomicUsize::new(0),
+        }
+    }
+
+    /// Admit a new connection of the given address at the given time.
+    ///
+    /// Returns a permit counting the connection as open until it is dropped,
+    /// or the limit the address exceeds, in which case the connection must be dropped.
+    pub fn admit(limiter: &Arc<RateLimiter>, ip: IpAddr, now: Instant) -> Result<ConnectionPermit, String> {
+        let limits = limiter.limits;
+        let mut sources = limiter.sources.lock().unwrap();
+        if sources.len() > MAX_TRACKED_SOURCES {
+            sources.retain(|_, source| !source.is_idle(&limits, now));
+        }
+
+        let source = sources.entry(ip).or_insert_with(|| Source {
+            open_connections: 0,
+            allowance: f64::from(limits.messages_per_second.unwrap_or(0)),
+            updated_at: now,
+        });
+
+        if let Some(max_connections) = limits.max_connections {
+            if source.open_connections >= max_connections {
+                limiter.dropped_connections.fetch_add(1, Ordering::SeqCst);
+                return Err(format!("{} exceeds the limit of {} open connections", ip, max_connections));
+            }
+        }
+
+        if let Some(messages_per_second) = limits.messages_per_second {
+            let rate = f64::from(messages_per_second);
+            source.allowance = rate.min(source.allowance + now.duration_since(source.updated_at).as_secs_f64() * rate);
+            source.updated_at = now;
+            if source.allowance < 1.0 {
+                limiter.dropped_messages.fetch_add(1, Ordering::SeqCst);
+                return Err(format!("{} exceeds the limit of {} messages per second", ip, messages_per_second));
+            }
+            source.allowance -= 1.0;
+        }
+
+        source.open_connections += 1;
+
+        Ok(ConnectionPermit {
+            limiter: Arc::clone(limiter),
+            ip,
+        })
+    }
+
+    /// Returns the traffic dropped by this limiter so far.
+    pub fn snapshot(&self) -> RateLimitMetrics {
+        RateLimitMetrics {
+            name: self.name.to_string(),
+            dropped_connections: self.dropped_connections.load(Ordering::SeqCst) as u64,
+            dropped_messages: self.dropped_messages.load(Ordering::SeqCst) as u64,
+            tracked_sources: self.sources.lock().unwrap().len(),
+        }
+    }
+}
+
+/// Counts a connection admitted by a `RateLimiter` as open until it is dropped.
+pub struct ConnectionPermit {
+    limiter: Arc<RateLimiter>,
+    ip: IpAddr,
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        let limits = self.limiter.limits;
+        let mut sources = self.limiter.sources.lock().unwrap();
+        let is_idle = match sources.get_mut(&self.ip) {
+            Some(source) => {
+                source.open_connections -= 1;
+                source.is_idle(&limits, Instant::now())
+            }
+            None => false
+        };
+
+        if is_idle {
+            sources.remove(&self.ip);
+        }
+    }
+}
+
+#[cfg(test)]
+mod rate_limit_test {
+
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_rate_limits() {
+        let limiter = Arc::new(RateLimiter::new("test", RateLimits { max_connections: Some(2), messages_per_second: Some(3) }));
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let other_ip: IpAddr = "10.0.0.2".parse().unwrap();
+        let start = Instant::now();
+
+        // at most two connections are open at once
+        let first = RateLimiter::admit(&limiter, ip, start).unwrap();
+        let second = RateLimiter::admit(&limiter, ip, start).unwrap();
+        assert!(RateLimiter::admit(&limiter, ip, start).is_err());
+        assert!(RateLimiter::admit(&limiter, other_ip, start).is_ok());
+        drop(first);
+
+        // the burst of three messages is spent, until the allowance is refilled
+        assert!(RateLimiter::admit(&limiter, ip, start).is_ok());
+        assert!(RateLimiter::admit(&limiter, ip, start + Duration::from_millis(100)).is_err());
+        drop(second);
+        assert!(RateLimiter::admit(&limiter, ip, start + Duration::from_millis(400)).is_ok());
+
+        let metrics = limiter.snapshot();
+        assert_eq!(1, metrics.dropped_connections);
+        assert_eq!(1, metrics.dropped_messages);
+
+        // unlimited listeners admit everything
+        let unlimited = Arc::new(RateLimiter::new("unlimited", RateLimits::default()));
+        let permits: Vec<ConnectionPermit> = (0..100).map(|_| RateLimiter::admit(&unlimited, ip, start).unwrap()).collect();
+        drop(permits);
+        assert_eq!(0, unlimited.snapshot().tracked_sources);
+    }
+}
@@ -0,0 +1,101 @@
+use ::p2p::codec::{Codec, JsonCodec, Message};
+use ::p2p::peers::{self, PeerSet};
+use ::p2p::tls::PeerStream;
+use std::collections::HashMap;
+use std::io::Write;
+use std::net::{Shutdown, SocketAddr};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// The maximum time to wait for a relayed message to be written to the connection.
+const RELAY_WRITE_TIMEOUT: u64 = 5;
+
+/// The connections kept open by peers which are unable to accept incoming
+/// connections, e.g. sealers behind a NAT without port mapping.
+///
+/// Such a peer registers itself with a `Message::RelayRegister` on a connection
+/// it opened on its own. Instead of connecting to the peer, messages are then
+/// pushed over this connection, one JSON encoded message per line.
+/// The peer does not respond to relayed messages.
+pub struct RelaySet {
+    /// The peers allowed to register, i.e. the sealers of the genesis configuration.
+    peers: PeerSet,
+    /// The registered connections by the normalized address of their peer.
+    connections: Mutex<HashMap<SocketAddr, PeerStream>>,
+}
+
+impl RelaySet {
+    /// Create a new, empty relay set accepting registrations of the given peers.
+    pub fn new(peers: PeerSet) -> RelaySet {
+        RelaySet {
+            peers,
+            connections: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Keep the given connection to relay messages to the peer with the given address.
+    /// Replaces any connection the peer registered previously.
+    ///
+    /// Only sealers may register, and only from the IP address they are known under,
+    /// so that other hosts are not able to intercept the messages of a sealer.
+    pub fn register(&self, address: SocketAddr, stream: PeerStream) -> Result<(), String> {
+        let address = peers::normalize(&address);
+
+        if !self.peers.others().contains(&address) {
+            return Err(format!("{} is not a sealer of the genesis configuration", address));
+        }
+
+        let remote_address = match stream.peer_addr() {
+            Ok(remote_address) => peers::normalize(&remote_address),
+            Err(e) => {
+                return Err(format!("Failed to determine the remote address: {:?}", e));
+            }
+        };
+        if remote_address.ip() != address.ip() {
+            return Err(format!("Connection from {} cannot register as relay for {}", remote_address, address));
+        }
+
+        match stream.set_write_timeout(Some(Duration::from_secs(RELAY_WRITE_TIMEOUT))) {
+            Ok(()) => {}
+            Err(e) => {
+                return Err(format!("Failed to configure relay connection: {:?}", e));
+            }
+        }
+
+        info!("Relaying messages to {} over the connection from {}", address, remote_address);
+        if let Some(mut previous_stream) = self.connections.lock().unwrap().insert(address, stream) {
+            let _ = previous_stream.shutdown(Shutdown::Both);
+        }
+
+        Ok(())
+    }
+
+    /// Push the given message to the peer with the given address over its registered connection.
+    ///
+    /// Returns false if the peer did not register a connection or the connection broke,
+    /// in which case it is dropped and the message must be sent directly.
+    pub fn send(&self, address: &SocketAddr, message: Message) -> bool {
+        let address = peers::normalize(address);
+        let mut connections = self.connections.lock().unwrap();
+
+        let write_result = match connections.get_mut(&address) {
+            Some(stream) => {
+                let mut encoded_message = JsonCodec::encode(message);
+                encoded_message.push(b'\n');
+                stream.write_all(&encoded_message).and_then(|_| stream.flush())
+            }
+            None => {
+                return false;
+            }
+        };
+
+        match write_result {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("Dropping relay connection of {} as it broke: {:?}", address, e);
+                connections.remove(&address);
+                false
+            }
+        }
+    }
+}
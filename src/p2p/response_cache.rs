@@ -0,0 +1,307 @@
+use ::chain::block::Block;
+use ::chain::types::{BlockHash, Height};
+use ::p2p::codec::{CodecKind, Message};
+use ::p2p::compression::{self, CompressionKind, CompressionSetting};
+use ::p2p::memory::MemoryBudget;
+use ::protocol::clique::{BlockRange, ChainChunk, ChainChunkToken, CliqueProtocol, ProtocolHandler};
+use serde_json;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Blocks collected from a snapshot of the chain, along with the snapshot key of the
+/// chain and the amount of bytes accounted for them.
+type CachedBlocks<T> = Option<((usize, BlockHash), Arc<T>, usize)>;
+
+/// A `ChainResponse` encoded with each codec and compression requested, along with the snapshot key of the chain.
+type CachedResponse = Option<((usize, BlockHash), HashMap<(CodecKind, Option<CompressionKind>), Arc<Vec<u8>>>)>;
+
+/// Caches the encoded response to a `Message::ChainRequest`, and the blocks
+/// from which chain chunks and block ranges are served.
+///
+/// Serializing the whole chain is expensive. Requesters asking for the chain
+/// while it did not change in the meantime therefore share a single snapshot
+/// and its serialization instead of encoding the same chain over and over again.
+/// Likewise, requesters of chunks and ranges share the blocks collected once per snapshot.
+///
+/// Snapshots which do not fit into the memory cap of the cache are not cached,
+/// but created anew for each request instead.
+pub struct ChainResponseCache {
+    /// The snapshot key of the chain along with its `ChainResponse` encoded with each codec and compression requested.
+    cached_response: Mutex<CachedResponse>,
+    /// The blocks of the chain ordered by height, from which chunks of the chain are served.
+    cached_blocks: Mutex<CachedBlocks<Vec<(Height, Block)>>>,
+    /// The blocks of the canonical chain ordered by height, from which block ranges are served.
+    cached_canonical_blocks: Mutex<CachedBlocks<Vec<Block>>>,
+    /// The memory used by the cached snapshots.
+    memory: MemoryBudget,
+}
+
+impl ChainResponseCache {
+    /// Create a new, empty cache, which uses at most the given amount of bytes if any.
+    pub fn new(memory_cap: Option<usize>) -> ChainResponseCache {
+        ChainResponseCache {
+            cached_response: Mutex::new(None),
+            cached_blocks: Mutex::new(None),
+            cached_canonical_blocks: Mutex::new(None),
+            memory: MemoryBudget::new("the chain cache", memory_cap),
+        }
+    }
+
+    /// Returns the `ChainResponse` of the chain currently held by the given protocol, encoded with the given codec.
+    ///
+    /// The response is only encoded if the chain changed since the last invocation with the same codec and compression.
+    /// Concurrent invocations wait for the one encoding the response and reuse its result.
+    ///
+    /// - `protocol`: The protocol holding the chain to respond with.
+    /// - `codec`: The codec the requester encoded its request with.
+    /// - `compression`: The compression the requester accepts along with the size from which to compress the response, if any.
+    pub fn get_encoded_chain_response(&self, protocol: &Mutex<CliqueProtocol>, codec: CodecKind, compression: Option<CompressionSetting>) -> Arc<Vec<u8>> {
+        let encoding = (codec, compression.map(|(compression, _)| compression));
+
+        // keep the cache locked while encoding so that identical
+        // requests arriving in the meantime are coalesced into this one
+        let mut cached_response = self.cached_response.lock().unwrap();
+
+        // only hold the protocol lock while creating the snapshot,
+        // encoding happens afterwards
+        let (snapshot_key, response) = {
+            let mut locked_protocol = protocol.lock().unwrap();
+            let snapshot_key = locked_protocol.get_chain_snapshot_key();
+
+            if let Some((ref cached_key, ref encoded_responses)) = *cached_response {
+                if let (true, Some(encoded_response)) = (cached_key.eq(&snapshot_key), encoded_responses.get(&encoding)) {
+                    trace!("Reusing cached {} chain response for snapshot {:?}", codec.name(), snapshot_key);
+                    return Arc::clone(encoded_response);
+                }
+            }
+
+            (snapshot_key, locked_protocol.handle(Message::ChainRequest))
+        };
+
+        trace!("Encoding {} chain response for snapshot {:?}", codec.name(), snapshot_key);
+        let encoded_response = match compression {
+            Some((compression, threshold)) => Arc::new(compression::seal(codec.encode(response), compression, threshold)),
+            None => Arc::new(codec.encode(response))
+        };
+        let is_outdated = match *cached_response {
+            Some((ref cached_key, _)) => !cached_key.eq(&snapshot_key),
+            None => true
+        };
+        if is_outdated {
+            if let Some((_, outdated_responses)) = cached_response.take() {
+                self.memory.release(outdated_responses.values().map(|outdated_response| outdated_response.len()).sum());
+            }
+            *cached_response = Some((snapshot_key.clone(), HashMap::new()));
+        }
+
+        if self.memory.try_reserve(encoded_response.len()) {
+            if let Some((_, ref mut encoded_responses)) = *cached_response {
+                encoded_responses.insert(encoding, Arc::clone(&encoded_response));
+            }
+        } else {
+            debug!("Not caching chain response of {} bytes for snapshot {:?} as it exceeds the cap of the chain cache", encoded_response.len(), snapshot_key);
+        }
+
+        encoded_response
+    }
+
+    /// Returns the chunk of the chain held by the given protocol following the given token,
+    /// or the first chunk if the token is None.
+    ///
+    /// The blocks of the chain are only collected if the chain changed since the last invocation.
+    ///
+    /// - `protocol`: The protocol holding the chain to respond with.
+    /// - `token`: The token identifying the requested chunk.
+    pub fn get_chain_chunk(&self, protocol: &Mutex<CliqueProtocol>, token: Option<ChainChunkToken>) -> ChainChunk {
+        let mut cached_blocks = self.cached_blocks.lock().unwrap();
+
+        let (genesis_configuration_hash, blocks_by_height) = {
+            let locked_protocol = protocol.lock().unwrap();
+            let blocks_by_height = self.reuse_or_collect(&mut cached_blocks, &locked_protocol, "blocks by height", |protocol| protocol.get_blocks_by_height(), |entry| &entry.1);
+
+            (locked_protocol.get_genesis_configuration_hash(), blocks_by_height)
+        };
+
+        ChainChunk::new(genesis_configuration_hash, &blocks_by_height, token.as_ref())
+    }
+
+    /// Returns the blocks of the canonical chain held by the given protocol from `from_height`
+    /// up to and including `to_height`. See `BlockRange::new`.
+    ///
+    /// The blocks of the canonical chain are only collected if the chain changed since the last invocation.
+    /// Concurrent invocations wait for the one collecting the blocks and reuse its result.
+    ///
+    /// - `protocol`: The protocol holding the chain to respond with.
+    /// - `from_height`: The height of the first requested block.
+    /// - `to_height`: The height of the last requested block.
+    pub fn get_block_range(&self, protocol: &Mutex<CliqueProtocol>, from_height: Height, to_height: Height) -> BlockRange {
+        let mut cached_canonical_blocks = self.cached_canonical_blocks.lock().unwrap();
+
+        let (genesis_configuration_hash, root_height, canonical_blocks) = {
+            let locked_protocol = protocol.lock().unwrap();
+            let canonical_blocks = self.reuse_or_collect(&mut cached_canonical_blocks, &locked_protocol, "canonical blocks", |protocol| protocol.get_canonical_blocks(), |block| block);
+
+            (locked_protocol.get_genesis_configuration_hash(), locked_protocol.get_root_height(), canonical_blocks)
+        };
+
+        BlockRange::new(genesis_configuration_hash, root_height, &canonical_blocks, from_height, to_height)
+    }
+
+    /// Returns the cached blocks if they were collected from the current snapshot of the chain held
+    /// by the given protocol. Otherwise, collects them anew and caches them if they fit into the memory cap.
+    ///
+    /// - `cached`: The cached blocks, replaced if outdated.
+    /// - `protocol`: The locked protocol holding the chain.
+    /// - `name`: The name of the blocks in the log.
+    /// - `collect`: Collects the blocks from the protocol.
+    /// - `block_of`: Returns the block of a collected entry, to account for its size.
+    fn reuse_or_collect<T, C, B>(&self, cached: &mut CachedBlocks<Vec<T>>, protocol: &CliqueProtocol, name: &str, collect: C, block_of: B) -> Arc<Vec<T>>
+        where
+            C: FnOnce(&CliqueProtocol) -> Vec<T>,
+            B: Fn(&T) -> &Block
+    {
+        let snapshot_key = protocol.get_chain_snapshot_key();
+
+        if let Some((ref cached_key, ref blocks, _)) = *cached {
+            if cached_key.eq(&snapshot_key) {
+                return Arc::clone(blocks);
+            }
+        }
+
+        trace!("Collecting the {} for snapshot {:?}", name, snapshot_key);
+        let blocks = Arc::new(collect(protocol));
+        if let Some((_, _, outdated_size)) = cached.take() {
+            self.memory.release(outdated_size);
+        }
+
+        // the size of the blocks is only needed to enforce a cap
+        let size = match self.memory.get_cap() {
+            Some(_) => blocks.iter().map(|entry| serde_json::to_string(block_of(entry)).unwrap().len()).sum(),
+            None => 0
+        };
+        if self.memory.try_reserve(size) {
+            *cached = Some((snapshot_key, Arc::clone(&blocks), size));
+        } else {
+            debug!("Not caching {} bytes of {} for snapshot {:?} as they exceed the cap of the chain cache", size, name, snapshot_key);
+        }
+
+        blocks
+    }
+}
+
+#[cfg(test)]
+mod response_cache_test {
+
+    use super::*;
+    use ::chain::hasher::Hasher;
+    use ::config::genesis::Genesis;
+    use ::config::version::VersionPolicy;
+    use std::net::SocketAddr;
+
+    fn new_protocol() -> (CliqueProtocol, Vec<SocketAddr>) {
+        let genesis = Genesis::new("genesis.json", "public_uciv.json", "public_key.json");
+        let sealers = genesis.sealer.clone();
+        (CliqueProtocol::new(sealers[0], genesis, VersionPolicy::Ignore), sealers)
+    }
+
+    fn new_block(parent: &Block, sealer: SocketAddr) -> Block {
+        let mut block = Block::new(Hasher::default(), parent.identifier.clone(), Some(sealer), vec![]);
+        block.data.timestamp = parent.data.timestamp + 1;
+        block.identifier = block.get_header().unwrap().hash(Hasher::default());
+
+        block
+    }
+
+    fn get_cached_key<T>(cached: &Mutex<CachedBlocks<T>>) -> Option<(usize, BlockHash)> {
+        cached.lock().unwrap().as_ref().map(|entry| entry.0.clone())
+    }
+
+    fn get_cached_blocks<T>(cached: &Mutex<CachedBlocks<T>>) -> Arc<T> {
+        Arc::clone(&cached.lock().unwrap().as_ref().unwrap().1)
+    }
+
+    #[test]
+    fn test_chain_chunk_invalidation() {
+        let (protocol, sealers) = new_protocol();
+        let genesis_block = protocol.get_block_by_number(Height::GENESIS).unwrap();
+        let first_block = new_block(&genesis_block, sealers[0]);
+        let protocol = Mutex::new(protocol);
+        assert_eq!(Ok(1), protocol.lock().unwrap().add_block_range(vec![first_block.clone()]));
+
+        let cache = ChainResponseCache::new(Some(1024 * 1024));
+        let chunk = cache.get_chain_chunk(&protocol, None);
+        assert_eq!(vec![genesis_block.clone(), first_block.clone()], chunk.blocks);
+        let blocks = get_cached_blocks(&cache.cached_blocks);
+        let used_memory = cache.memory.get_used();
+        assert!(used_memory > 0);
+
+        // chunks of the same snapshot share the collected blocks
+        assert_eq!(chunk, cache.get_chain_chunk(&protocol, None));
+        assert!(Arc::ptr_eq(&blocks, &get_cached_blocks(&cache.cached_blocks)));
+        assert_eq!(used_memory, cache.memory.get_used());
+
+        // a block on a side branch changes the length of the chain, but not its head
+        let side_block = new_block(&genesis_block, sealers[1]);
+        let head_hash = protocol.lock().unwrap().get_chain_snapshot_key().1;
+        assert_eq!(Ok(1), protocol.lock().unwrap().add_block_range(vec![side_block.clone()]));
+        assert_eq!(head_hash, protocol.lock().unwrap().get_chain_snapshot_key().1);
+        assert!(cache.get_chain_chunk(&protocol, None).blocks.contains(&side_block));
+        assert!(!Arc::ptr_eq(&blocks, &get_cached_blocks(&cache.cached_blocks)));
+        assert_eq!(Some((3, head_hash)), get_cached_key(&cache.cached_blocks));
+
+        // extending the canonical chain changes its head
+        let second_block = new_block(&first_block, sealers[1]);
+        assert_eq!(Ok(1), protocol.lock().unwrap().add_block_range(vec![second_block.clone()]));
+        assert!(cache.get_chain_chunk(&protocol, None).blocks.contains(&second_block));
+        assert_eq!(Some((4, second_block.identifier.clone())), get_cached_key(&cache.cached_blocks));
+        assert!(cache.memory.get_used() > used_memory);
+
+        // blocks exceeding the cap are not cached
+        let capped_cache = ChainResponseCache::new(Some(1));
+        assert_eq!(4, capped_cache.get_chain_chunk(&protocol, None).blocks.len());
+        assert_eq!(None, get_cached_key(&capped_cache.cached_blocks));
+        assert_eq!(0, capped_cache.memory.get_used());
+    }
+
+    #[test]
+    fn test_block_range_coalescing() {
+        let (protocol, sealers) = new_protocol();
+        let genesis_block = protocol.get_block_by_number(Height::GENESIS).unwrap();
+        let first_block = new_block(&genesis_block, sealers[0]);
+        let protocol = Mutex::new(protocol);
+        assert_eq!(Ok(1), protocol.lock().unwrap().add_block_range(vec![first_block.clone()]));
+
+        let cache = ChainResponseCache::new(None);
+        let block_range = cache.get_block_range(&protocol, Height::new(1), Height::new(5));
+        assert_eq!(protocol.lock().unwrap().get_block_range(Height::new(1), Height::new(5)), block_range);
+        assert_eq!(vec![first_block.clone()], block_range.blocks);
+        let canonical_blocks = get_cached_blocks(&cache.cached_canonical_blocks);
+
+        // ranges of the same snapshot share the canonical blocks
+        assert_eq!(vec![genesis_block.clone(), first_block.clone()], cache.get_block_range(&protocol, Height::GENESIS, Height::new(1)).blocks);
+        assert!(Arc::ptr_eq(&canonical_blocks, &get_cached_blocks(&cache.cached_canonical_blocks)));
+
+        let second_block = new_block(&first_block, sealers[1]);
+        assert_eq!(Ok(1), protocol.lock().unwrap().add_block_range(vec![second_block.clone()]));
+        let block_range = cache.get_block_range(&protocol, Height::new(1), Height::new(5));
+        assert_eq!(vec![first_block.clone(), second_block.clone()], block_range.blocks);
+        assert_eq!(Height::new(2), block_range.head_height);
+        assert!(!Arc::ptr_eq(&canonical_blocks, &get_cached_blocks(&cache.cached_canonical_blocks)));
+    }
+
+    #[test]
+    fn test_encoded_chain_response_invalidation() {
+        let (protocol, sealers) = new_protocol();
+        let genesis_block = protocol.get_block_by_number(Height::GENESIS).unwrap();
+        let protocol = Mutex::new(protocol);
+
+        let cache = ChainResponseCache::new(None);
+        let encoded_response = cache.get_encoded_chain_response(&protocol, CodecKind::Json, None);
+        assert!(Arc::ptr_eq(&encoded_response, &cache.get_encoded_chain_response(&protocol, CodecKind::Json, None)));
+
+        assert_eq!(Ok(1), protocol.lock().unwrap().add_block_range(vec![new_block(&genesis_block, sealers[0])]));
+        let updated_response = cache.get_encoded_chain_response(&protocol, CodecKind::Json, None);
+        assert!(!Arc::ptr_eq(&encoded_response, &updated_response));
+        assert_ne!(encoded_response, updated_response);
+    }
+}
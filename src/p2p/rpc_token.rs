@@ -0,0 +1,185 @@
+use ::mac::{constant_time_eq, decode_hex};
+use ::p2p::codec::Message;
+use serde_json;
+use std::fs::File;
+use std::io::Read;
+
+/// The scopes an RPC token may be granted.
+/// Each RPC method requires exactly one of them.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Clone, Copy, Debug)]
+pub enum RpcScope {
+    /// Submit transactions and join requests, e.g. by polling-station gateways.
+    Submit,
+    /// Read the chain, its transactions, the redacted pending transactions and the status of the node and the election, e.g. by auditors.
+    Read,
+    /// Request the final tally of the election.
+    Tally,
+    /// Open and close the voting and inspect the payloads of pending transactions. Grants all other scopes as well.
+    Admin,
+}
+
+impl RpcScope {
+    /// Returns the scope required to call the given RPC method.
+    /// Methods which are not meant to be called via RPC require `RpcScope::Admin`.
+    pub fn required_by(method: &Message) -> RpcScope {
+        match *method {
+            Message::TransactionPayload(_) | Message::JoinRequest(_) => RpcScope::Submit,
+            Message::Ping
+            | Message::ChainRequest
+            | Message::ChainChunkRequest(_)
+            | Message::SnapshotRequest(_)
+            | Message::BlockRangeRequest { .. }
+            | Message::BlockByNumberRequest(_)
+            | Message::BlocksAtHeightRequest(_)
+            | Message::HeadQuery
+            | Message::FindTransaction(_)
+            | Message::InclusionProofRequest(_)
+            | Message::ReceiptRequest(_)
+            | Message::QueryTransactions(_)
+            | Message::SealerStatisticsRequest
+            | Message::EpochStatisticsRequest
+            | Message::ElectionStatusRequest
+            | Message::TurnoutSubscribe
+            | Message::StatusRequest
+            | Message::MetricsRequest
+            | Message::PeerStatusRequest
+            | Message::PendingJoinRequests
+            | Message::MempoolRequest { include_payloads: false } => RpcScope::Read,
+            Message::RequestTally | Message::ElectionResultRequest => RpcScope::Tally,
+            _ => RpcScope::Admin
+        }
+    }
+}
+
+/// A bearer token granting access to the RPC methods of its scopes.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RpcToken {
+    /// The name of the holder of the token, e.g. a polling station.
+    pub name: String,
+    /// The hex encoded token presented by the holder.
+    pub token: String,
+    /// The scopes granted to the holder.
+    pub scopes: Vec<RpcScope>,
+}
+
+impl RpcToken {
+    /// Returns true, if this token grants the given scope.
+    pub fn has_scope(&self, scope: RpcScope) -> bool {
+        self.scopes.contains(&RpcScope::Admin) || self.scopes.contains(&scope)
+    }
+}
+
+/// The tokens accepted on the RPC port. If configured,
+/// each RPC request must present a token granting the scope of its method.
+#[derive(Clone, Debug)]
+pub struct RpcTokens {
+    tokens: Vec<RpcToken>,
+}
+
+impl RpcTokens {
+    /// Create a new set accepting the given tokens.
+    pub fn new(tokens: Vec<RpcToken>) -> RpcTokens {
+        RpcTokens {
+            tokens,
+        }
+    }
+
+    /// Read the accepted tokens from the given JSON file.
+    pub fn from_file(file_name: &str) -> Result<RpcTokens, String> {
+        let mut contents = String::new();
+        let read_result = File::open(file_name).and_then(|mut file| file.read_to_string(&mut contents));
+        match read_result {
+            Ok(_) => {}
+            Err(e) => {
+                return Err(format!("Failed to read RPC tokens {:?}: {:?}", file_name, e));
+            }
+        }
+
+        let tokens: Vec<RpcToken> = match serde_json::from_str(&contents) {
+            Ok(tokens) => tokens,
+            Err(e) => {
+                return Err(format!("Failed to parse RPC tokens {:?}: {:?}", file_name, e));
+            }
+        };
+
+        for token in tokens.iter() {
+            match decode_hex(&token.token) {
+                Some(ref secret) if secret.len() >= 16 => {}
+                _ => {
+                    return Err(format!("The token of {:?} in {:?} must be hex encoded and at least 16 bytes long", token.name, file_name));
+                }
+            }
+        }
+
+        Ok(RpcTokens::new(tokens))
+    }
+
+    /// Check that the given request presents a token granting the scope of its method.
+    ///
+    /// Returns the method to call if it is permitted,
+    /// the reason of the rejection otherwise.
+    pub fn authorize(&self, request: Message) -> Result<Message, String> {
+        let (presented_token, method) = match request {
+            Message::RpcAuthorized(presented_token, method) => (presented_token, *method),
+            _ => {
+                return Err("A token is required to call RPC methods of this node".to_string());
+            }
+        };
+
+        let token = match self.tokens.iter().find(|token| constant_time_eq(&token.token, &presented_token)) {
+            Some(token) => token,
+            None => {
+                return Err("Unknown token".to_string());
+            }
+        };
+
+        let scope = RpcScope::required_by(&method);
+        if !token.has_scope(scope) {
+            return Err(format!("Token of {:?} lacks the scope {:?}", token.name, scope));
+        }
+
+        Ok(method)
+    }
+}
+
+#[cfg(test)]
+mod rpc_token_test {
+
+    use super::*;
+
+    #[test]
+    fn test_authorize() {
+        let tokens = RpcTokens::new(vec![
+            RpcToken {
+                name: "polling-station".to_string(),
+                token: "000102030405060708090a0b0c0d0e0f".to_string(),
+                scopes: vec![RpcScope::Submit],
+            },
+            RpcToken {
+                name: "electoral-board".to_string(),
+                token: "0f0e0d0c0b0a09080706050403020100".to_string(),
+                scopes: vec![RpcScope::Admin],
+            },
+        ]);
+
+        let authorized = |token: &str, method: Message| Message::RpcAuthorized(token.to_string(), Box::new(method));
+
+        assert_eq!(Ok(Message::PendingJoinRequests), tokens.authorize(authorized("0f0e0d0c0b0a09080706050403020100", Message::PendingJoinRequests)));
+        assert_eq!(Ok(Message::OpenVote), tokens.authorize(authorized("0f0e0d0c0b0a09080706050403020100", Message::OpenVote)));
+
+        // missing scope
+        assert!(tokens.authorize(authorized("000102030405060708090a0b0c0d0e0f", Message::StatusRequest)).is_err());
+        assert!(tokens.authorize(authorized("000102030405060708090a0b0c0d0e0f", Message::RequestTally)).is_err());
+
+        // the status is revealed along with the tally, which requires the scope of the latter
+        assert_eq!(RpcScope::Tally, RpcScope::required_by(&Message::ElectionResultRequest));
+
+        // the payloads of pending transactions are only revealed to admins
+        assert_eq!(RpcScope::Read, RpcScope::required_by(&Message::MempoolRequest { include_payloads: false }));
+        assert_eq!(RpcScope::Admin, RpcScope::required_by(&Message::MempoolRequest { include_payloads: true }));
+
+        // unknown or missing token
+        assert!(tokens.authorize(authorized("00000000000000000000000000000000", Message::StatusRequest)).is_err());
+        assert!(tokens.authorize(Message::StatusRequest).is_err());
+    }
+}
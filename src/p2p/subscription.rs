@@ -0,0 +1,136 @@
+use ::p2p::codec::{Codec, JsonCodec, Message};
+use ::p2p::tls::PeerStream;
+use ::protocol::clique::Turnout;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// The maximum amount of clients subscribed to the turnout at once.
+pub const MAX_TURNOUT_SUBSCRIBERS: usize = 256;
+
+/// The maximum time to wait for an update to be written to a subscriber.
+const SUBSCRIPTION_WRITE_TIMEOUT: u64 = 5;
+
+/// The connections of RPC clients subscribed to the turnout with a `Message::TurnoutSubscribe`.
+///
+/// Instead of closing the connection after responding, the node keeps it open
+/// and pushes a `Message::TurnoutUpdate` whenever the head of its canonical chain changes,
+/// one JSON encoded message per line. Subscribers which do not read their updates in time are dropped.
+pub struct TurnoutSubscriptions {
+    connections: Mutex<Vec<PeerStream>>,
+}
+
+impl TurnoutSubscriptions {
+    /// Create a new set without any subscribers.
+    pub fn new() -> TurnoutSubscriptions {
+        TurnoutSubscriptions {
+            connections: Mutex::new(vec![]),
+        }
+    }
+
+    /// Returns true, if no client is subscribed.
+    pub fn is_empty(&self) -> bool {
+        self.connections.lock().unwrap().is_empty()
+    }
+
+    /// Keep the given connection to push updates of the turnout to,
+    /// starting with the given current one.
+    pub fn subscribe(&self, mut stream: PeerStream, turnout: Turnout) -> Result<(), String> {
+        let mut connections = self.connections.lock().unwrap();
+        if connections.len() >= MAX_TURNOUT_SUBSCRIBERS {
+            return Err(format!("Already {} clients are subscribed", MAX_TURNOUT_SUBSCRIBERS));
+        }
+
+        let write_result = stream.set_write_timeout(Some(Duration::from_secs(SUBSCRIPTION_WRITE_TIMEOUT)))
+            .and_then(|_| TurnoutSubscriptions::write_update(&mut stream, turnout));
+        match write_result {
+            Ok(()) => {}
+            Err(e) => {
+                return Err(format!("Failed to send the current turnout: {:?}", e));
+            }
+        }
+
+        debug!("Client {:?} subscribed to the turnout", stream.peer_addr());
+        connections.push(stream);
+
+        Ok(())
+    }
+
+    /// Push the given turnout to all subscribers, dropping those whose connection broke.
+    pub fn publish(&self, turnout: &Turnout) {
+        let mut connections = self.connections.lock().unwrap();
+
+        connections.retain_mut(|stream| {
+            match TurnoutSubscriptions::write_update(stream, turnout.clone()) {
+                Ok(()) => true,
+                Err(e) => {
+                    debug!("Dropping turnout subscription of {:?} as its connection broke: {:?}", stream.peer_addr(), e);
+                    false
+                }
+            }
+        });
+    }
+
+    fn write_update<W: Write>(stream: &mut W, turnout: Turnout) -> ::std::io::Result<()> {
+        let mut encoded_message = JsonCodec::encode(Message::TurnoutUpdate(turnout));
+        encoded_message.push(b'\n');
+
+        stream.write_all(&encoded_message).and_then(|_| stream.flush())
+    }
+}
+
+impl Default for TurnoutSubscriptions {
+    fn default() -> Self {
+        TurnoutSubscriptions::new()
+    }
+}
+
+#[cfg(test)]
+mod subscription_test {
+
+    use super::*;
+    use ::chain::chain_visitor::ElectionPhase;
+    use ::chain::types::{BlockHash, Height};
+    use std::collections::BTreeMap;
+    use std::io::{BufRead, BufReader};
+    use std::net::{TcpListener, TcpStream};
+
+    #[test]
+    fn test_turnout_subscription() {
+        let turnout = |total_votes: usize| Turnout {
+            height: Height::GENESIS,
+            head_identifier: BlockHash::default(),
+            phase: ElectionPhase::Opened,
+            total_votes,
+            questions: BTreeMap::new(),
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+
+        let subscriptions = TurnoutSubscriptions::new();
+        assert!(subscriptions.is_empty());
+        subscriptions.subscribe(PeerStream::Plain(stream), turnout(1)).unwrap();
+        subscriptions.publish(&turnout(2));
+
+        // each update is pushed on its own line
+        let mut reader = BufReader::new(client);
+        for total_votes in 1..3 {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            assert_eq!(Message::TurnoutUpdate(turnout(total_votes)), JsonCodec::decode(line.trim().as_bytes()));
+        }
+
+        // subscribers closing their connection are dropped eventually
+        drop(reader);
+        for _ in 0..100 {
+            subscriptions.publish(&turnout(3));
+            if subscriptions.is_empty() {
+                break;
+            }
+            ::std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(subscriptions.is_empty());
+    }
+}
@@ -0,0 +1,122 @@
+use ::p2p::client;
+use ::p2p::codec::Message;
+use ::protocol::clique::Tally;
+use std::net::SocketAddr;
+
+/// The tallies reported by several nodes, e.g. all sealers of the genesis configuration.
+#[derive(Debug, Clone)]
+pub struct TallyComparison {
+    /// The tally reported by each node in the order requested, or the reason it could not be obtained.
+    pub tallies: Vec<(SocketAddr, Result<Tally, String>)>,
+}
+
+impl TallyComparison {
+    /// Returns the tally reported by most nodes, or None if no node reported one.
+    /// Of equally common tallies, the one reported first is returned.
+    pub fn get_majority_tally(&self) -> Option<&Tally> {
+        let reported_tallies: Vec<&Tally> = self.tallies.iter()
+            .filter_map(|(_, tally)| tally.as_ref().ok())
+            .collect();
+
+        let mut majority_tally: Option<(&Tally, usize)> = None;
+        for tally in reported_tallies.iter() {
+            let count = reported_tallies.iter().filter(|other| *other == tally).count();
+            if majority_tally.map(|(_, majority_count)| count > majority_count) != Some(false) {
+                majority_tally = Some((tally, count));
+            }
+        }
+
+        majority_tally.map(|(tally, _)| tally)
+    }
+
+    /// Returns the nodes which reported a tally other than the one reported by most nodes.
+    pub fn get_deviating_nodes(&self) -> Vec<SocketAddr> {
+        let majority_tally = self.get_majority_tally();
+
+        self.tallies.iter()
+            .filter(|&(_, tally)| tally.as_ref().ok().map(|tally| Some(tally) != majority_tally) == Some(true))
+            .map(|&(address, _)| address)
+            .collect()
+    }
+
+    /// Returns the nodes which did not report a tally.
+    pub fn get_unreachable_nodes(&self) -> Vec<SocketAddr> {
+        self.tallies.iter()
+            .filter(|&(_, tally)| tally.is_err())
+            .map(|&(address, _)| address)
+            .collect()
+    }
+
+    /// Returns true, if all nodes reported the same tally.
+    pub fn is_consistent(&self) -> bool {
+        self.get_majority_tally().is_some() && self.get_deviating_nodes().is_empty() && self.get_unreachable_nodes().is_empty()
+    }
+}
+
+/// Request the tally from each node listening on the given addresses over the peer protocol.
+///
+/// As the sum of the votes is deterministic, all nodes on the same head report the same tally.
+/// While blocks are sealed, nodes may briefly report tallies of different heads, hence the comparison
+/// is only conclusive once the voting is closed.
+pub fn compare(addresses: &[SocketAddr]) -> TallyComparison {
+    let tallies = addresses.iter()
+        .map(|address| {
+            let tally = match client::request(address, Message::RequestTally) {
+                Ok(Message::RequestTallyPayload(tally)) => Ok(tally),
+                Ok(other) => Err(format!("Peer responded to a tally request with {:?}. It is probably running an older version", other)),
+                Err(e) => Err(e)
+            };
+
+            (*address, tally)
+        })
+        .collect();
+
+    TallyComparison { tallies }
+}
+
+#[cfg(test)]
+mod tally_comparison_test {
+
+    use super::*;
+    use crypto_rs::arithmetic::mod_int::{From as ModIntFrom, ModInt};
+    use crypto_rs::el_gamal::ciphertext::CipherText;
+    use num::BigInt;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_tally_comparison() {
+        let tally = |big_h: u64| Tally {
+            total_votes: 2,
+            cipher_text: CipherText {
+                big_g: ModInt::from_value_modulus(BigInt::from(4), BigInt::from(23)),
+                big_h: ModInt::from_value_modulus(BigInt::from(big_h), BigInt::from(23)),
+                random: ModInt::from_value_modulus(BigInt::from(0), BigInt::from(11)),
+            },
+            decrypted_sum: None,
+            questions: BTreeMap::new(),
+        };
+        let addresses: Vec<SocketAddr> = (0..4).map(|port| format!("127.0.0.1:{}", 3000 + port).parse().unwrap()).collect();
+
+        let comparison = TallyComparison {
+            tallies: vec![(addresses[0], Ok(tally(9))), (addresses[1], Ok(tally(9))), (addresses[2], Ok(tally(9)))],
+        };
+        assert!(comparison.is_consistent());
+        assert_eq!(Some(&tally(9)), comparison.get_majority_tally());
+
+        // a single sealer summing up another cipher text is outvoted
+        let comparison = TallyComparison {
+            tallies: vec![(addresses[0], Ok(tally(5))), (addresses[1], Ok(tally(9))), (addresses[2], Ok(tally(9))), (addresses[3], Err("unreachable".to_string()))],
+        };
+        assert!(!comparison.is_consistent());
+        assert_eq!(Some(&tally(9)), comparison.get_majority_tally());
+        assert_eq!(vec![addresses[0]], comparison.get_deviating_nodes());
+        assert_eq!(vec![addresses[3]], comparison.get_unreachable_nodes());
+
+        // without any tally, there is nothing to compare
+        let comparison = TallyComparison {
+            tallies: vec![(addresses[0], Err("unreachable".to_string()))],
+        };
+        assert!(!comparison.is_consistent());
+        assert_eq!(None, comparison.get_majority_tally());
+    }
+}
@@ -1,17 +1,43 @@
+use ::metrics::{self, Histogram, TaskMetrics, ThreadPoolMetrics};
+use std::cmp;
+use std::collections::{BTreeMap, VecDeque};
 use std::thread;
 use std::sync::mpsc;
-use std::sync::Arc;
+use std::process;
+use std::sync::{Arc, Weak};
 use std::sync::Mutex;
 use std::ops::Drop;
+use std::panic::{self, AssertUnwindSafe};
+use std::any::Any;
+use std::time::{Duration, Instant};
+
+/// The amount of waiting tasks above which the queue of a pool is considered congested.
+const QUEUE_WARN_DEPTH: usize = 64;
+
+/// The time in seconds the queue of a pool must stay congested before a warning is logged.
+const QUEUE_WARN_PERIOD: u64 = 10;
+
+/// The interval in milliseconds in which a pool is checked for running tasks while waiting for it to become idle.
+const IDLE_POLL_INTERVAL: u64 = 50;
 
 enum Message {
-    NewJob(Job),
+    NewJob(NamedJob),
     Terminate,
 }
 
 pub struct ThreadPool {
     workers: Vec<Worker>,
     sender: mpsc::Sender<Message>,
+    stats: Arc<ThreadPoolStats>,
+    critical_locks: Arc<Mutex<Vec<CriticalLock>>>,
+}
+
+/// A lock shared with the tasks of a pool, whose state may be inconsistent once it is poisoned.
+struct CriticalLock {
+    /// The name of the lock, e.g. `protocol`.
+    name: String,
+    /// Returns whether the lock is poisoned. False once the lock is dropped.
+    is_poisoned: Box<Fn() -> bool + Send>,
 }
 
 trait FnBox {
@@ -26,41 +52,129 @@ impl<F: FnOnce()> FnBox for F {
 
 type Job = Box<FnBox + Send + 'static>;
 
+/// A job along with the name of its task and the time it was submitted.
+struct NamedJob {
+    name: String,
+    submitted_at: Instant,
+    job: Job,
+}
+
 impl ThreadPool {
     /// Create a new ThreadPool.
     ///
+    /// The name identifies the pool in the metrics and the log.
     /// The size is the number of threads in the pool.
     ///
     /// # Panics
     ///
     /// The `new` function will panic if the size is zero.
-    pub fn new(size: usize) -> ThreadPool {
+    pub fn new(name: &str, size: usize) -> ThreadPool {
         assert!(size > 0);
 
         let (sender, receiver) = mpsc::channel();
 
         let receiver = Arc::new(Mutex::new(receiver));
+        let stats = Arc::new(ThreadPoolStats::new(name, size));
+        let critical_locks = Arc::new(Mutex::new(vec![]));
 
         let mut workers = Vec::with_capacity(size);
 
         for id in 0..size {
-            workers.push(Worker::new(id, Arc::clone(&receiver)));
+            workers.push(Worker::new(id, Arc::clone(&receiver), Arc::clone(&stats), Arc::clone(&critical_locks)));
         }
 
         ThreadPool {
             workers,
             sender,
+            stats,
+            critical_locks,
         }
     }
 
-    pub fn execute<F>(&self, f: F)
+    /// Abort the process as soon as a task of this pool panics while the given lock is poisoned,
+    /// e.g. as the task panicked while holding it. Otherwise, the process would stay alive,
+    /// although the state guarded by the lock may be inconsistent and each further attempt to acquire it fails.
+    /// The name identifies the lock in the log.
+    pub fn abort_on_poisoned<T: Send + 'static>(&self, name: &str, lock: &Arc<Mutex<T>>) {
+        let lock: Weak<Mutex<T>> = Arc::downgrade(lock);
+
+        self.critical_locks.lock().unwrap().push(CriticalLock {
+            name: name.to_string(),
+            is_poisoned: Box::new(move || match lock.upgrade() {
+                Some(lock) => lock.is_poisoned(),
+                None => false
+            }),
+        });
+    }
+
+    /// Returns the name of the first lock passed to `abort_on_poisoned` which is poisoned, if any.
+    pub fn find_poisoned_lock(&self) -> Option<String> {
+        find_poisoned_lock(&self.critical_locks)
+    }
+
+    /// Execute the given task on the next free worker.
+    /// The name identifies the task in the metrics, e.g. `sign`.
+    pub fn execute<F>(&self, name: &str, f: F)
         where
             F: FnOnce() + Send + 'static
     {
-        let job = Box::new(f);
+        let job = NamedJob {
+            name: name.to_string(),
+            submitted_at: Instant::now(),
+            job: Box::new(f),
+        };
 
+        self.stats.on_submit(job.submitted_at);
         self.sender.send(Message::NewJob(job)).unwrap();
     }
+
+    /// Execute the given task on the next free worker like `execute`,
+    /// returning a handle to wait for the result of the task.
+    pub fn submit<F, T>(&self, name: &str, f: F) -> TaskHandle<T>
+        where
+            F: FnOnce() -> T + Send + 'static,
+            T: Send + 'static
+    {
+        let (sender, receiver) = mpsc::channel();
+        self.execute(name, move || {
+            match panic::catch_unwind(AssertUnwindSafe(f)) {
+                Ok(result) => {
+                    let _ = sender.send(Ok(result));
+                }
+                Err(payload) => {
+                    let _ = sender.send(Err(panic_message(&*payload)));
+                    // let the worker account the failure
+                    panic::resume_unwind(payload);
+                }
+            }
+        });
+
+        TaskHandle {
+            name: name.to_string(),
+            receiver,
+        }
+    }
+
+    /// Returns the statistics of the workers and the queue of this pool.
+    pub fn get_stats(&self) -> &Arc<ThreadPoolStats> {
+        &self.stats
+    }
+
+    /// Wait at most the given time until all submitted tasks finished, e.g. on shutdown.
+    ///
+    /// Returns the names of the tasks still running or waiting at the deadline,
+    /// or none if the pool is idle.
+    pub fn wait_until_idle(&self, deadline: Duration) -> Vec<String> {
+        let started_at = Instant::now();
+        loop {
+            let running_tasks = self.stats.get_pending_tasks();
+            if running_tasks.is_empty() || started_at.elapsed() >= deadline {
+                return running_tasks;
+            }
+
+            thread::sleep(cmp::min(deadline - started_at.elapsed(), Duration::from_millis(IDLE_POLL_INTERVAL)));
+        }
+    }
 }
 
 impl Drop for ThreadPool {
@@ -81,13 +195,164 @@ impl Drop for ThreadPool {
     }
 }
 
+/// A handle to the result of a task submitted to a `ThreadPool`.
+pub struct TaskHandle<T> {
+    name: String,
+    receiver: mpsc::Receiver<Result<T, String>>,
+}
+
+impl<T> TaskHandle<T> {
+    /// Block until the task finished and return its result,
+    /// or the message it panicked with.
+    pub fn join(self) -> Result<T, String> {
+        match self.receiver.recv() {
+            Ok(result) => result,
+            Err(_) => Err(format!("Task {} was dropped before it finished", self.name))
+        }
+    }
+}
+
+/// Returns the message of the given panic payload, if it is a string.
+fn panic_message(payload: &(Any + Send)) -> String {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => match payload.downcast_ref::<String>() {
+            Some(message) => message.clone(),
+            None => "Unknown panic".to_string()
+        }
+    }
+}
+
+/// Tracks what the workers of a pool are doing and how many tasks wait for them.
+pub struct ThreadPoolStats {
+    name: String,
+    workers: usize,
+    state: Mutex<ThreadPoolState>,
+}
+
+struct ThreadPoolState {
+    /// The submission times of the waiting tasks, from the oldest to the newest.
+    queued: VecDeque<Instant>,
+    busy_workers: usize,
+    queue_waits: Histogram,
+    tasks: BTreeMap<String, TaskMetrics>,
+    /// The time since which the queue is congested, if it is.
+    congested_since: Option<Instant>,
+    /// Whether a warning was logged for the current congestion.
+    has_warned: bool,
+}
+
+impl ThreadPoolStats {
+    fn new(name: &str, workers: usize) -> ThreadPoolStats {
+        ThreadPoolStats {
+            name: name.to_string(),
+            workers,
+            state: Mutex::new(ThreadPoolState {
+                queued: VecDeque::new(),
+                busy_workers: 0,
+                queue_waits: Histogram::new(),
+                tasks: BTreeMap::new(),
+                congested_since: None,
+                has_warned: false,
+            }),
+        }
+    }
+
+    /// Returns a snapshot of the workers and the queue of the pool.
+    pub fn snapshot(&self) -> ThreadPoolMetrics {
+        let state = self.state.lock().unwrap();
+
+        ThreadPoolMetrics {
+            name: self.name.clone(),
+            workers: self.workers,
+            busy_workers: state.busy_workers,
+            queue_depth: state.queued.len(),
+            oldest_queued_millis: state.queued.front().map_or(0, |submitted_at| metrics::as_millis(submitted_at.elapsed())),
+            queue_waits: state.queue_waits.clone(),
+            tasks: state.tasks.values().cloned().collect(),
+        }
+    }
+
+    /// Returns the names of the running tasks, followed by a placeholder for each waiting task.
+    fn get_pending_tasks(&self) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        let mut pending_tasks: Vec<String> = state.tasks.values()
+            .filter(|task| task.running > 0)
+            .map(|task| task.name.clone())
+            .collect();
+        if !state.queued.is_empty() {
+            pending_tasks.push(format!("{} waiting", state.queued.len()));
+        }
+
+        pending_tasks
+    }
+
+    fn on_submit(&self, submitted_at: Instant) {
+        let mut state = self.state.lock().unwrap();
+        state.queued.push_back(submitted_at);
+        self.watch_queue(&mut state);
+    }
+
+    fn on_start(&self, job: &NamedJob) {
+        let mut state = self.state.lock().unwrap();
+        state.queued.pop_front();
+        state.queue_waits.record(job.submitted_at.elapsed());
+        state.busy_workers += 1;
+        state.tasks.entry(job.name.clone())
+            .or_insert_with(|| TaskMetrics {
+                name: job.name.clone(),
+                running: 0,
+                completed: 0,
+                failed: 0,
+                durations: Histogram::new(),
+            })
+            .running += 1;
+        self.watch_queue(&mut state);
+    }
+
+    fn on_finish(&self, name: &str, duration: Duration, has_failed: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.busy_workers -= 1;
+        if let Some(task) = state.tasks.get_mut(name) {
+            task.running -= 1;
+            task.completed += 1;
+            if has_failed {
+                task.failed += 1;
+            }
+            task.durations.record(duration);
+        }
+    }
+
+    /// Log a warning once the queue stays congested for longer than `QUEUE_WARN_PERIOD`.
+    fn watch_queue(&self, state: &mut ThreadPoolState) {
+        if state.queued.len() <= QUEUE_WARN_DEPTH {
+            state.congested_since = None;
+            state.has_warned = false;
+            return;
+        }
+
+        let congested_since = *state.congested_since.get_or_insert_with(Instant::now);
+        if !state.has_warned && congested_since.elapsed() >= Duration::from_secs(QUEUE_WARN_PERIOD) {
+            warn!("More than {} tasks have been waiting for the {} workers of thread pool {} for {}s", QUEUE_WARN_DEPTH, self.workers, self.name, QUEUE_WARN_PERIOD);
+            state.has_warned = true;
+        }
+    }
+}
+
+/// Returns the name of the first of the given locks which is poisoned, if any.
+fn find_poisoned_lock(critical_locks: &Mutex<Vec<CriticalLock>>) -> Option<String> {
+    critical_locks.lock().unwrap().iter()
+        .find(|critical_lock| (critical_lock.is_poisoned)())
+        .map(|critical_lock| critical_lock.name.clone())
+}
+
 struct Worker {
     id: usize,
     thread: Option<thread::JoinHandle<()>>,
 }
 
 impl Worker {
-    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Message>>>) -> Worker {
+    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Message>>>, stats: Arc<ThreadPoolStats>, critical_locks: Arc<Mutex<Vec<CriticalLock>>>) -> Worker {
 
         let thread = thread::spawn(move || {
             loop {
@@ -95,9 +360,22 @@ impl Worker {
 
                 match message {
                     Message::NewJob(job) => {
-                        trace!("Worker {} got a job; executing.", id);
+                        trace!("Worker {} of {} got task {}; executing.", id, stats.name, job.name);
 
-                        job.call_box();
+                        stats.on_start(&job);
+                        let started_at = Instant::now();
+                        // a panicking task must neither kill the worker nor leave it accounted as busy
+                        let NamedJob { name, job, .. } = job;
+                        let result = panic::catch_unwind(AssertUnwindSafe(move || job.call_box()));
+                        if let Err(ref payload) = result {
+                            error!("Task {} on worker {} of {} panicked: {}", name, id, stats.name, panic_message(&**payload));
+
+                            if let Some(lock_name) = find_poisoned_lock(&critical_locks) {
+                                error!("Aborting, as the {} lock is poisoned and the state it guards may be inconsistent", lock_name);
+                                process::abort();
+                            }
+                        }
+                        stats.on_finish(&name, started_at.elapsed(), result.is_err());
                     },
                     Message::Terminate => {
                         trace!("Worker {} was told to terminate.", id);
@@ -113,4 +391,60 @@ impl Worker {
             thread: Some(thread),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod thread_test {
+
+    use super::*;
+
+    #[test]
+    fn test_wait_until_idle() {
+        let pool = ThreadPool::new("test", 2);
+        assert!(pool.wait_until_idle(Duration::from_millis(0)).is_empty());
+
+        pool.execute("short", || thread::sleep(Duration::from_millis(50)));
+        pool.execute("long", || thread::sleep(Duration::from_millis(1000)));
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(vec!["long".to_string()], pool.wait_until_idle(Duration::from_millis(300)));
+    }
+
+    #[test]
+    fn test_panicking_task() {
+        let pool = ThreadPool::new("test", 1);
+
+        // the only worker survives a panicking task
+        pool.execute("broken", || panic!("broken stream"));
+        assert_eq!(Ok(42), pool.submit("answer", || 42).join());
+        assert_eq!(Err("Failed to decode".to_string()), pool.submit("decode", || -> u8 { panic!("Failed to decode") }).join());
+        assert!(pool.wait_until_idle(Duration::from_millis(500)).is_empty());
+
+        let metrics = pool.get_stats().snapshot();
+        assert_eq!(0, metrics.busy_workers);
+        let failed: Vec<(String, u64)> = metrics.tasks.iter().map(|task| (task.name.clone(), task.failed)).collect();
+        assert_eq!(vec![("answer".to_string(), 0), ("broken".to_string(), 1), ("decode".to_string(), 1)], failed);
+    }
+
+    #[test]
+    fn test_find_poisoned_lock() {
+        let pool = ThreadPool::new("test", 1);
+        let healthy_lock = Arc::new(Mutex::new(0));
+        let poisoned_lock = Arc::new(Mutex::new(0));
+        pool.abort_on_poisoned("healthy", &healthy_lock);
+        pool.abort_on_poisoned("poisoned", &poisoned_lock);
+        assert_eq!(None, pool.find_poisoned_lock());
+
+        // poisoned outside the pool, as a panicking task of the pool would abort the tests
+        let cloned_poisoned_lock = Arc::clone(&poisoned_lock);
+        let _ = thread::spawn(move || {
+            let _guard = cloned_poisoned_lock.lock().unwrap();
+            panic!("broken state");
+        }).join();
+        assert!(poisoned_lock.is_poisoned());
+        assert_eq!(Some("poisoned".to_string()), pool.find_poisoned_lock());
+
+        // a dropped lock is not checked anymore
+        drop(poisoned_lock);
+        assert_eq!(None, pool.find_poisoned_lock());
+    }
+}
@@ -0,0 +1,314 @@
+use ::config::genesis::Genesis;
+use ::config::node::NodeConfig;
+use ::p2p::codec::{self, CodecKind, Message, MAX_MESSAGE_SIZE};
+use ::p2p::compression::{self, CompressionKind};
+use ::p2p::health::PeerHealthTracker;
+#[cfg(feature = "libp2p")]
+use ::p2p::libp2p_transport::Libp2pTransport;
+use ::p2p::memory::{self, MemoryBudget};
+use ::p2p::peers;
+use ::p2p::tls::{PeerStream, TlsContext};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::Write;
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+
+/// The transports available to exchange messages with other peers.
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum TransportKind {
+    /// A new TCP connection per message, see `TcpTransport`.
+    Tcp,
+    /// Gossipsub for published messages and request-response for others, see `Libp2pTransport`.
+    #[cfg(feature = "libp2p")]
+    Libp2p,
+}
+
+impl FromStr for TransportKind {
+    type Err = String;
+
+    fn from_str(kind: &str) -> Result<Self, Self::Err> {
+        match kind {
+            "tcp" => Ok(TransportKind::Tcp),
+            #[cfg(feature = "libp2p")]
+            "libp2p" => Ok(TransportKind::Libp2p),
+            #[cfg(not(feature = "libp2p"))]
+            "libp2p" => Err("The libp2p transport is not available in this build, enable the feature libp2p".to_string()),
+            other => Err(format!("Unknown transport {:?}", other))
+        }
+    }
+}
+
+/// Exchanges messages with other peers.
+///
+/// A transport only defines how messages reach a peer. Incoming messages
+/// are accepted by the listeners of the node, independently of the transport,
+/// unless the transport receives them itself, see `take_incoming`.
+pub trait Transport: Send + Sync {
+    /// Send the given request to the given peer and wait for its response,
+    /// e.g. to synchronize the chain.
+    fn request(&self, peer_addr: &SocketAddr, message: Message) -> Result<Message, String>;
+
+    /// Send the given request to the given peer and wait at most the given time for its response,
+    /// e.g. for hints which are only useful if they arrive in time.
+    fn request_with_timeout(&self, peer_addr: &SocketAddr, message: Message, timeout: Duration) -> Result<Message, String>;
+
+    /// Encode the requests to the given peer with the given codec, as negotiated in the handshake with it.
+    /// Requests to peers without a negotiated codec are encoded with `CodecKind::Json`.
+    fn use_codec(&self, peer_addr: &SocketAddr, codec: CodecKind);
+
+    /// Compress large requests to the given peer with the given compression, as negotiated in the handshake with it.
+    /// If None, requests to the peer are not compressed.
+    fn use_compression(&self, peer_addr: &SocketAddr, compression: Option<CompressionKind>);
+
+    /// Publish the given message, e.g. a block or a transaction, to all given peers.
+    /// Responses are discarded.
+    fn publish(&self, peer_addrs: &[SocketAddr], message: Message) {
+        for peer_addr in peer_addrs.iter() {
+            if let Err(e) = self.request(peer_addr, message.clone()) {
+                warn!("Failed to send message to {:?}: {}", peer_addr, e);
+            }
+        }
+    }
+
+    /// Whether `publish` delivers the message to all peers at once without waiting for
+    /// any of them, e.g. over gossipsub, instead of sending it to each peer in turn.
+    fn is_publish_subscribe(&self) -> bool {
+        false
+    }
+
+    /// Returns the messages received by the transport itself, e.g. over libp2p, which
+    /// must be handled by the node. None, if incoming messages are accepted by the
+    /// listeners of the node or if they have already been taken.
+    fn take_incoming(&self) -> Option<Receiver<IncomingMessage>> {
+        None
+    }
+}
+
+/// A message received by a transport itself, see `Transport::take_incoming`.
+pub struct IncomingMessage {
+    /// The peer the message was received from.
+    pub source: SocketAddr,
+    /// The received message.
+    pub message: Message,
+    /// Sends the response back to the peer. None, if the peer expects no response, e.g. for a published block.
+    pub responder: Option<Responder>,
+}
+
+/// Sends the response to an incoming message back to its peer.
+pub struct Responder(Box<FnOnce(Message) + Send>);
+
+impl Responder {
+    /// Create a new responder sending the response with the given function.
+    pub fn new(send_response: Box<FnOnce(Message) + Send>) -> Responder {
+        Responder(send_response)
+    }
+
+    /// Send the given response back to the peer.
+    pub fn respond(self, response: Message) {
+        (self.0)(response);
+    }
+}
+
+/// Create the transport configured in the given node configuration for the node with the given address
+/// among the sealers of the given genesis configuration, accounting the responses being received in the given budget.
+/// The outcome of each request is recorded in the given tracker.
+#[cfg_attr(not(feature = "libp2p"), allow(unused_variables))]
+pub fn create(node_config: &NodeConfig, own_address: SocketAddr, genesis: &Genesis, in_flight_messages: &Arc<MemoryBudget>, peer_health: &Arc<PeerHealthTracker>) -> Arc<Transport> {
+    match node_config.transport {
+        TransportKind::Tcp => Arc::new(TcpTransport::new(in_flight_messages, node_config.compression_threshold, node_config.tls.clone(), peer_health)),
+        #[cfg(feature = "libp2p")]
+        TransportKind::Libp2p => {
+            // the key and the addresses are checked on startup
+            let keypair = node_config.libp2p_key.clone().expect("The libp2p transport requires a libp2p key");
+            let topic = format!("node-rs/{}", genesis.get_configuration_hash());
+            match Libp2pTransport::new(keypair, own_address, &genesis.libp2p_addresses, &topic, peer_health) {
+                Ok(transport) => Arc::new(transport),
+                Err(e) => panic!("Failed to start the libp2p transport: {}", e)
+            }
+        }
+    }
+}
+
+/// Opens a new TCP connection for each message. The message is written to the
+/// connection which is then closed for writing, signalling the end of the message
+/// to the peer. The response is read until the peer closes the connection.
+pub struct TcpTransport {
+    /// The memory used by responses being received.
+    in_flight_messages: Arc<MemoryBudget>,
+    /// The codec negotiated with each peer.
+    codecs: Mutex<HashMap<SocketAddr, CodecKind>>,
+    /// The compression negotiated with each peer.
+    compressions: Mutex<HashMap<SocketAddr, CompressionKind>>,
+    /// The size in bytes from which requests are compressed. If None, requests are never compressed.
+    compression_threshold: Option<usize>,
+    /// The context to establish TLS on each connection with. If None, connections are plain text.
+    tls: Option<Arc<TlsContext>>,
+    /// The connectivity to each peer, updated by each request.
+    peer_health: Arc<PeerHealthTracker>,
+}
+
+impl TcpTransport {
+    /// Create a new transport accounting the responses being received in the given budget.
+    /// Requests of at least the given amount of bytes are compressed for peers negotiating a compression, if any.
+    /// Connections are encrypted with the given TLS context, if any.
+    /// The outcome of each request is recorded in the given tracker.
+    pub fn new(in_flight_messages: &Arc<MemoryBudget>, compression_threshold: Option<usize>, tls: Option<Arc<TlsContext>>, peer_health: &Arc<PeerHealthTracker>) -> TcpTransport {
+        TcpTransport {
+            in_flight_messages: Arc::clone(in_flight_messages),
+            codecs: Mutex::new(HashMap::new()),
+            compressions: Mutex::new(HashMap::new()),
+            compression_threshold,
+            tls,
+            peer_health: Arc::clone(peer_health),
+        }
+    }
+
+    /// Record the outcome of a request to the given peer, which took the given time to connect to it.
+    fn record_health(&self, peer_addr: &SocketAddr, connect_duration: Duration, result: Result<Message, String>) -> Result<Message, String> {
+        match result {
+            Ok(response) => {
+                self.peer_health.record_success(peer_addr, connect_duration);
+                Ok(response)
+            }
+            Err(e) => {
+                self.peer_health.record_failure(peer_addr, &e);
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns the codec negotiated with the given peer.
+    fn get_codec(&self, peer_addr: &SocketAddr) -> CodecKind {
+        self.codecs.lock().unwrap().get(&peers::normalize(peer_addr)).cloned().unwrap_or(CodecKind::Json)
+    }
+
+    /// Send the given request over the given connection, encoded with the codec and compressed with the compression
+    /// negotiated with the given peer, and read the response until the peer closes it.
+    /// If TLS is configured, it is established on the connection first.
+    ///
+    /// A peer accepting a compression in the envelope of its response, e.g. of the handshake,
+    /// gets compressed requests from then on.
+    fn exchange(&self, stream: TcpStream, peer_addr: &SocketAddr, message: Message) -> Result<Message, String> {
+        trace!("Successfully connected to {:?}", stream.peer_addr());
+
+        let mut stream = match self.tls {
+            Some(ref tls) => match tls.connect(peer_addr, stream) {
+                Ok(stream) => stream,
+                Err(e) => {
+                    return Err(e);
+                }
+            },
+            None => PeerStream::Plain(stream)
+        };
+
+        let codec = self.get_codec(peer_addr);
+        let compression = self.compressions.lock().unwrap().get(&peers::normalize(peer_addr)).cloned();
+        let request = match (compression, self.compression_threshold) {
+            (Some(compression), Some(threshold)) => compression::seal(codec.encode(message), compression, threshold),
+            _ => codec.encode(message)
+        };
+        let write_result = stream.write_all(&request)
+            .and_then(|_| stream.flush())
+            .and_then(|_| stream.shutdown(Shutdown::Write));
+        match write_result {
+            Ok(()) => {}
+            Err(e) => {
+                return Err(format!("Failed to send request: {:?}", e));
+            }
+        }
+
+        // wait for some incoming data on the same stream
+        match memory::read_message(&mut stream, MAX_MESSAGE_SIZE + 1, &self.in_flight_messages) {
+            Ok((ref buffer, _)) if buffer.is_empty() => {
+                let _ = stream.shutdown(Shutdown::Both);
+                Err("No bytes received on outgoing connection".to_string())
+            }
+            Ok((buffer, mut reservation)) => {
+                let (encoded_response, accepted_compression) = match compression::open(&buffer) {
+                    Ok(opened) => opened,
+                    Err(e) => {
+                        return Err(format!("Failed to open response: {}", e));
+                    }
+                };
+                // account the decompressed response as well
+                let grow_result = match encoded_response {
+                    Cow::Owned(ref decompressed_response) => reservation.grow(decompressed_response.len()),
+                    Cow::Borrowed(_) => Ok(())
+                };
+                match grow_result {
+                    Ok(()) => {}
+                    Err(e) => {
+                        return Err(e);
+                    }
+                }
+                if let (Some(accepted_compression), Some(_), None) = (accepted_compression, self.compression_threshold, compression) {
+                    self.use_compression(peer_addr, Some(accepted_compression));
+                }
+
+                let (response, _) = codec::decode(&encoded_response);
+                trace!("Got response from outgoing stream: {:?}", response);
+
+                Ok(response)
+            }
+            Err(e) => Err(format!("Failed to read response: {}", e))
+        }
+    }
+}
+
+impl Transport for TcpTransport {
+    fn request(&self, peer_addr: &SocketAddr, message: Message) -> Result<Message, String> {
+        let started_at = Instant::now();
+        let stream = match TcpStream::connect(peer_addr) {
+            Ok(stream) => stream,
+            Err(e) => {
+                return self.record_health(peer_addr, started_at.elapsed(), Err(format!("Failed to connect due to {:?}", e)));
+            }
+        };
+        let connect_duration = started_at.elapsed();
+
+        let result = self.exchange(stream, peer_addr, message);
+        self.record_health(peer_addr, connect_duration, result)
+    }
+
+    fn request_with_timeout(&self, peer_addr: &SocketAddr, message: Message, timeout: Duration) -> Result<Message, String> {
+        let started_at = Instant::now();
+        let stream = match TcpStream::connect_timeout(peer_addr, timeout) {
+            Ok(stream) => stream,
+            Err(e) => {
+                return self.record_health(peer_addr, started_at.elapsed(), Err(format!("Failed to connect due to {:?}", e)));
+            }
+        };
+        let connect_duration = started_at.elapsed();
+
+        // the timeout bounds each read and write rather than the whole exchange,
+        // which suffices for the small messages sent with a timeout
+        let timeout_result = stream.set_read_timeout(Some(timeout)).and_then(|_| stream.set_write_timeout(Some(timeout)));
+        let result = match timeout_result {
+            Ok(()) => self.exchange(stream, peer_addr, message),
+            Err(e) => Err(format!("Failed to set the timeout due to {:?}", e))
+        };
+        self.record_health(peer_addr, connect_duration, result)
+    }
+
+    fn use_codec(&self, peer_addr: &SocketAddr, codec: CodecKind) {
+        debug!("Using the {} codec for requests to {}", codec.name(), peer_addr);
+        self.codecs.lock().unwrap().insert(peers::normalize(peer_addr), codec);
+    }
+
+    fn use_compression(&self, peer_addr: &SocketAddr, compression: Option<CompressionKind>) {
+        let mut compressions = self.compressions.lock().unwrap();
+        match compression {
+            Some(compression) => {
+                debug!("Compressing large requests to {} with {}", peer_addr, compression.name());
+                compressions.insert(peers::normalize(peer_addr), compression);
+            }
+            None => {
+                compressions.remove(&peers::normalize(peer_addr));
+            }
+        }
+    }
+}
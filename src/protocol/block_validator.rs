@@ -0,0 +1,826 @@
+use ::chain::block::Block;
+use ::chain::chain::Chain;
+use ::chain::chain_visitor::{ElectionPhase, FinalTallyVisitor, KeyGenerationVisitor, VoterRegistryVisitor, VotingClosedVisitor};
+use ::chain::chain_walker::LongestPathWalker;
+use ::chain::hasher::Hasher;
+use ::chain::transaction::{LimitViolation, Transaction, TransactionLimits, TransactionType};
+use ::chain::transaction_registry::TransactionRegistry;
+use ::chain::types::{BlockHash, Height};
+use ::config::feature::Feature;
+use ::config::genesis::{Genesis, KeyGenerationConfig};
+use ::credential::RegistrarKey;
+use ::p2p::peers;
+use ::protocol::tally::TallyCalculator;
+use ::signer::key;
+use ::threshold::dkg;
+use crypto_rs::arithmetic::mod_int::ModInt;
+use crypto_rs::cai::uciv::ImageSet;
+use crypto_rs::el_gamal::encryption::PublicKey;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The amount of seconds the timestamp of a block may lie ahead of the own clock,
+/// so that sealers whose clocks are slightly off do not reject each other's blocks.
+pub const MAX_TIMESTAMP_DRIFT: u64 = 15;
+
+/// The reason a block received from another node is not accepted.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum BlockRejection {
+    /// The identifier does not match the content of the block.
+    InvalidIdentifier,
+    /// The block does not name a sealer authorized by its parent.
+    UnknownSealer(Option<SocketAddr>),
+    /// The block is not signed, or not by the public key declared for its sealer.
+    InvalidSignature(String),
+    /// The parent of the block is not contained in the chain.
+    UnknownParent(BlockHash),
+    /// The block is not younger than its parent.
+    TimestampNotIncreasing {
+        timestamp: u64,
+        parent_timestamp: u64,
+    },
+    /// The block is younger than the own clock allows for.
+    TimestampInFuture {
+        timestamp: u64,
+        now: u64,
+    },
+    /// The sealer is neither the leader nor a co-leader of the height of the block.
+    SealerNotInTurn {
+        sealer: SocketAddr,
+        height: Height,
+    },
+    /// The transaction with the given identifier is invalid.
+    InvalidTransaction(String),
+    /// The transaction with the given identifier exceeds the limits on the size of transactions.
+    OversizedTransaction(String, LimitViolation),
+    /// The sealer vote with the given identifier is cast by another sealer than the one of the block.
+    ForeignSealerVote(String),
+    /// The transaction with the given identifier opens or closes the voting without the signature of the election authority.
+    UnauthorizedPhaseChange(String),
+    /// The commitments of the key generation with the given identifier are published by another dealer than the sealer of the block.
+    ForeignDkgCommitment(String),
+    /// The transaction with the given identifier registers or revokes a voter without the signature of the election authority.
+    UnauthorizedVoterRegistration(String),
+    /// The block, its transactions root or the transaction with the given identifier is not identified by the hash function of the genesis configuration.
+    UnexpectedHasher(String),
+}
+
+impl fmt::Display for BlockRejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BlockRejection::InvalidIdentifier => write!(f, "Identifier does not match the content of the block"),
+            BlockRejection::UnknownSealer(ref sealer) => write!(f, "{:?} is not an authorized sealer", sealer),
+            BlockRejection::InvalidSignature(ref reason) => write!(f, "{}", reason),
+            BlockRejection::UnknownParent(ref parent) => write!(f, "Parent {} is unknown", parent),
+            BlockRejection::TimestampNotIncreasing { timestamp, parent_timestamp } => write!(f, "Timestamp {} is not after the timestamp {} of the parent", timestamp, parent_timestamp),
+            BlockRejection::TimestampInFuture { timestamp, now } => write!(f, "Timestamp {} lies more than {} seconds after {}", timestamp, MAX_TIMESTAMP_DRIFT, now),
+            BlockRejection::SealerNotInTurn { ref sealer, height } => write!(f, "Sealer {} is not in turn at height {}", sealer, height),
+            BlockRejection::InvalidTransaction(ref identifier) => write!(f, "Transaction {} is invalid", identifier),
+            BlockRejection::OversizedTransaction(ref identifier, ref violation) => write!(f, "Transaction {} is oversized: {}", identifier, violation),
+            BlockRejection::ForeignSealerVote(ref identifier) => write!(f, "Sealer vote {} is not cast by the sealer of the block", identifier),
+            BlockRejection::UnauthorizedPhaseChange(ref identifier) => write!(f, "Transaction {} opens or closes the voting without the signature of the election authority", identifier),
+            BlockRejection::ForeignDkgCommitment(ref identifier) => write!(f, "Commitments {} are not published by the sealer of the block", identifier),
+            BlockRejection::UnauthorizedVoterRegistration(ref identifier) => write!(f, "Transaction {} registers or revokes a voter without the signature of the election authority", identifier),
+            BlockRejection::UnexpectedHasher(ref identifier) => write!(f, "Identifier {} is not derived with the hash function of the genesis configuration", identifier),
+        }
+    }
+}
+
+/// Enforces the rules a block received from another node must satisfy before it is added to the chain:
+///
+/// - Its identifier matches its content and all sealer votes and commitments of the key generation it includes are cast by its sealer.
+///   If the genesis configuration declares an election authority, all transactions opening or closing the voting are signed by it,
+///   as are all transactions registering or revoking voters.
+/// - Its parent is contained in the chain and it is younger than its parent, but not from the future.
+/// - It, its transactions root and its transactions are identified by the hash function of the genesis configuration,
+///   or by SHA-1 if it lies below the height up to which the genesis configuration accepts legacy identifiers.
+/// - It is sealed, and if required signed, by a sealer authorized by its parent,
+///   which is the leader or one of the co-leaders of its height.
+/// - All its transactions are valid. If the sealers generate the election key jointly, the commitments and the
+///   election key it includes are valid on the branch of its parent, and its votes are encrypted with the election key
+///   committed on that branch, which must also be committed before the voting is opened.
+///   Each final tally it includes is the first one of its question on the branch of its parent, the voting is closed on that branch
+///   and the tally matches the one calculated from it. If the feature `voter-registry` is enabled, each voter it registers is not
+///   eligible yet and each voter it revokes is eligible on that branch, and its votes are cast by eligible voters.
+///
+/// The rules not depending on the chain may be checked without holding the lock of the protocol.
+#[derive(Clone)]
+pub struct BlockValidator {
+    /// The amount of sealers following the leader which may seal a block in its place.
+    signer_limit: usize,
+    /// The public key used to encrypt votes.
+    public_key: PublicKey,
+    /// The set of all voters' images.
+    public_uciv: Vec<ImageSet>,
+    /// The set of all voters' images on each further question, by its identifier.
+    questions: BTreeMap<String, Vec<ImageSet>>,
+    /// The plaintexts a vote may encrypt.
+    voting_options: Vec<ModInt>,
+    /// The upper bounds on the size of transactions.
+    transaction_limits: TransactionLimits,
+    /// The encoded public key of the election authority, if any.
+    election_authority: Option<String>,
+    /// The sealers of the genesis configuration, which deal the election key if it is generated jointly.
+    dealers: Vec<SocketAddr>,
+    /// The configuration of generating the election key jointly by the sealers, if any.
+    key_generation: Option<KeyGenerationConfig>,
+    /// The calculator of the final tallies, which blocks must commit unaltered.
+    tally_calculator: TallyCalculator,
+    /// Whether the election authority registers and revokes voters on the chain.
+    voter_registry: bool,
+    /// The key of the registrar votes must carry a credential of, if any.
+    registrar: Option<RegistrarKey>,
+    /// The hash function blocks and transactions must be identified by.
+    hasher: Hasher,
+    /// The height below which blocks and transactions may still be identified by SHA-1, if any.
+    legacy_sha1_height: Option<Height>,
+}
+
+impl BlockValidator {
+    /// Create a validator enforcing the rules of the given genesis configuration.
+    pub fn new(genesis: &Genesis) -> BlockValidator {
+        BlockValidator {
+            signer_limit: genesis.clique.signer_limit,
+            public_key: genesis.public_key.clone(),
+            public_uciv: genesis.public_uciv.clone(),
+            questions: genesis.questions.clone(),
+            voting_options: genesis.get_voting_options(),
+            transaction_limits: genesis.get_transaction_limits(),
+            election_authority: genesis.election_authority.clone(),
+            dealers: genesis.sealer.clone(),
+            key_generation: genesis.key_generation.clone(),
+            tally_calculator: TallyCalculator::new(genesis),
+            voter_registry: genesis.is_enabled(Feature::VoterRegistry),
+            registrar: genesis.registrar.clone(),
+            hasher: genesis.clique.get_hasher(),
+            legacy_sha1_height: genesis.clique.legacy_sha1_height,
+        }
+    }
+
+    /// Check all rules for the given block, which should extend the given chain.
+    pub fn validate(&self, chain: &Chain, block: &Block) -> Result<(), BlockRejection> {
+        match self.validate_integrity(block) {
+            Ok(()) => {}
+            Err(rejection) => {
+                return Err(rejection);
+            }
+        }
+
+        match self.validate_against_chain(chain, block) {
+            Ok(()) => {}
+            Err(rejection) => {
+                return Err(rejection);
+            }
+        }
+
+        self.validate_transactions(&block.data.transactions)
+    }
+
+    /// Check that the identifier of the given block matches its content, that it names
+    /// its sealer, that all sealer votes and commitments of the key generation it includes are cast by its sealer and that
+    /// the election authority, if any, signed all transactions opening or closing the voting and registering or revoking voters.
+    pub fn validate_integrity(&self, block: &Block) -> Result<(), BlockRejection> {
+        if !block.has_valid_identifier() {
+            return Err(BlockRejection::InvalidIdentifier);
+        }
+
+        let sealer = match block.data.sealer {
+            Some(ref sealer) => sealer,
+            None => {
+                return Err(BlockRejection::UnknownSealer(None));
+            }
+        };
+
+        let foreign_sealer_vote = block.data.transactions.iter()
+            .filter(|transaction| TransactionType::SealerVote == transaction.trx_type)
+            .find(|transaction| match transaction.sealer_vote {
+                Some(ref sealer_vote) => !peers::is_same_peer(&sealer_vote.voter, sealer),
+                None => false
+            });
+        if let Some(transaction) = foreign_sealer_vote {
+            return Err(BlockRejection::ForeignSealerVote(transaction.identifier.clone()));
+        }
+
+        let foreign_dkg_commitment = block.data.transactions.iter()
+            .filter(|transaction| TransactionType::DkgCommitment == transaction.trx_type)
+            .find(|transaction| match transaction.dkg_commitment {
+                Some(ref dkg_commitment) => !peers::is_same_peer(&dkg_commitment.dealer, sealer),
+                None => false
+            });
+        if let Some(transaction) = foreign_dkg_commitment {
+            return Err(BlockRejection::ForeignDkgCommitment(transaction.identifier.clone()));
+        }
+
+        if let Some(transaction) = block.data.transactions.iter().find(|transaction| self.check_phase_change_signature(transaction).is_err()) {
+            return Err(BlockRejection::UnauthorizedPhaseChange(transaction.identifier.clone()));
+        }
+
+        match block.data.transactions.iter().find(|transaction| self.check_voter_registration_signature(transaction).is_err()) {
+            Some(transaction) => Err(BlockRejection::UnauthorizedVoterRegistration(transaction.identifier.clone())),
+            None => Ok(())
+        }
+    }
+
+    /// Returns whether the given identifier of a block at the given height, of its transactions root or
+    /// of one of its transactions is derived with the hash function of the genesis configuration,
+    /// or with SHA-1 if the height lies below the one up to which legacy identifiers are accepted.
+    pub fn is_expected_hasher(&self, identifier: &str, height: Height) -> bool {
+        match (Hasher::from_identifier(identifier), self.legacy_sha1_height) {
+            (Some(hasher), _) if hasher == self.hasher => true,
+            (Some(Hasher::Sha1), Some(legacy_sha1_height)) => height < legacy_sha1_height,
+            _ => false
+        }
+    }
+
+    /// Returns the identifier of the given block at the given height, of its transactions root or of the first of
+    /// its transactions which is not derived with the expected hash function, if any.
+    fn find_unexpected_hasher(&self, block: &Block, height: Height) -> Option<String> {
+        if !self.is_expected_hasher(block.identifier.as_str(), height) {
+            return Some(block.identifier.to_string());
+        }
+
+        if let Some(ref transactions_root) = block.data.transactions_root {
+            if !self.is_expected_hasher(transactions_root, height) {
+                return Some(transactions_root.clone());
+            }
+        }
+
+        block.data.transactions.iter()
+            .find(|transaction| !self.is_expected_hasher(&transaction.identifier, height))
+            .map(|transaction| transaction.identifier.clone())
+    }
+
+    /// Check that the given transaction is signed by the election authority, if it opens or closes the voting
+    /// and the genesis configuration declares an election authority. Other transactions always pass.
+    /// Returns the reason if the signature is missing or invalid.
+    pub fn check_phase_change_signature(&self, transaction: &Transaction) -> Result<(), String> {
+        let election_authority = match self.election_authority {
+            Some(ref election_authority) if TransactionType::VoteOpened == transaction.trx_type || TransactionType::VoteClosed == transaction.trx_type => election_authority,
+            _ => {
+                return Ok(());
+            }
+        };
+
+        match transaction.phase_change {
+            Some(ref phase_change) => phase_change.verify(&transaction.trx_type, election_authority),
+            None => Err("Opening or closing the voting requires the signature of the election authority".to_string())
+        }
+    }
+
+    /// Check that the given transaction is signed by the election authority, if it registers or revokes a voter.
+    /// Other transactions always pass. Returns the reason if the signature is missing or invalid,
+    /// or the genesis configuration declares no election authority.
+    pub fn check_voter_registration_signature(&self, transaction: &Transaction) -> Result<(), String> {
+        match transaction.trx_type {
+            TransactionType::VoterRegistered | TransactionType::VoterRevoked => {}
+            _ => {
+                return Ok(());
+            }
+        }
+
+        match (self.election_authority.as_ref(), transaction.voter_registration.as_ref()) {
+            (Some(election_authority), Some(voter_registration)) => voter_registration.verify(&transaction.trx_type, election_authority),
+            (None, _) => Err("Voters are only registered by the election authority, which is not declared".to_string()),
+            (_, None) => Err("Transaction does not name a voter".to_string())
+        }
+    }
+
+    /// Check that the voter registered by the given transaction is not eligible yet, or that the voter revoked by it is eligible,
+    /// given the registrations on a branch of the chain, and that its question is declared in the genesis configuration.
+    /// Other transactions always pass. Returns the reason if the transaction is not permitted, e.g. as the feature
+    /// `voter-registry` is not enabled.
+    pub fn check_voter_registration(&self, voter_registry_visitor: &VoterRegistryVisitor, transaction: &Transaction) -> Result<(), String> {
+        let voter_registration = match transaction.trx_type {
+            TransactionType::VoterRegistered | TransactionType::VoterRevoked if !self.voter_registry => {
+                return Err("Voters are declared in the genesis configuration".to_string());
+            }
+            TransactionType::VoterRegistered | TransactionType::VoterRevoked => match transaction.voter_registration {
+                Some(ref voter_registration) => voter_registration,
+                None => {
+                    return Err("Transaction does not name a voter".to_string());
+                }
+            },
+            _ => {
+                return Ok(());
+            }
+        };
+
+        let image_sets = match voter_registration.question_id {
+            Some(ref question_id) => match self.questions.get(question_id) {
+                Some(image_sets) => image_sets,
+                None => {
+                    return Err(format!("Question {} is not declared", question_id));
+                }
+            },
+            None => &self.public_uciv
+        };
+
+        let is_eligible = voter_registry_visitor.is_eligible(&voter_registration.question_id, voter_registration.voter_idx, image_sets);
+        match transaction.trx_type {
+            TransactionType::VoterRegistered if is_eligible => Err(format!("Voter {} is already registered", voter_registration.voter_idx)),
+            TransactionType::VoterRevoked if !is_eligible => Err(format!("Voter {} is not registered", voter_registration.voter_idx)),
+            _ => Ok(())
+        }
+    }
+
+    /// Check that the given transactions are valid, i.e. they respect the limits on their size and their proofs verify.
+    /// The sizes of all transactions are checked before any proof is verified.
+    /// If the sealers generate the election key jointly or the feature `voter-registry` is enabled,
+    /// the proofs of votes are verified against the chain instead.
+    pub fn validate_transactions(&self, transactions: &[Transaction]) -> Result<(), BlockRejection> {
+        for transaction in transactions {
+            if let Err(violation) = self.transaction_limits.check(transaction) {
+                return Err(BlockRejection::OversizedTransaction(transaction.identifier.clone(), violation));
+            }
+        }
+
+        let registry = TransactionRegistry::with_default_kinds();
+
+        let is_verified_against_chain = |transaction: &Transaction| (self.key_generation.is_some() || self.voter_registry) && TransactionType::Vote == transaction.trx_type;
+
+        match transactions.iter().find(|transaction| !is_verified_against_chain(transaction) && !registry.is_valid(transaction, &self.public_key, &self.public_uciv, &self.questions, &self.voting_options, self.registrar.as_ref())) {
+            Some(transaction) => Err(BlockRejection::InvalidTransaction(transaction.identifier.clone())),
+            None => Ok(())
+        }
+    }
+
+    /// Check that the parent of the given block is contained in the given chain,
+    /// that the block is younger than its parent but not from the future, and that
+    /// it is sealed, and if required signed, by a sealer authorized by its parent and in turn at its height.
+    /// Checks the transactions depending on the key generation, the registered voters and the final tallies on the branch of its parent, too.
+    pub fn validate_against_chain(&self, chain: &Chain, block: &Block) -> Result<(), BlockRejection> {
+        let parent = match chain.blocks.get(&block.data.parent) {
+            Some(parent) => parent,
+            None => {
+                return Err(BlockRejection::UnknownParent(block.data.parent.clone()));
+            }
+        };
+
+        if block.data.timestamp <= parent.data.timestamp {
+            return Err(BlockRejection::TimestampNotIncreasing {
+                timestamp: block.data.timestamp,
+                parent_timestamp: parent.data.timestamp,
+            });
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_secs();
+        if block.data.timestamp > now + MAX_TIMESTAMP_DRIFT {
+            return Err(BlockRejection::TimestampInFuture {
+                timestamp: block.data.timestamp,
+                now,
+            });
+        }
+
+        let parent_height = match chain.height_of(&parent.identifier) {
+            Ok(Some(parent_height)) => parent_height,
+            Ok(None) | Err(_) => {
+                return Err(BlockRejection::UnknownParent(parent.identifier.clone()));
+            }
+        };
+        let height = parent_height + 1;
+
+        if let Some(identifier) = self.find_unexpected_hasher(block, height) {
+            return Err(BlockRejection::UnexpectedHasher(identifier));
+        }
+
+        let sealer = match block.data.sealer {
+            Some(sealer) => sealer,
+            None => {
+                return Err(BlockRejection::UnknownSealer(None));
+            }
+        };
+        let sealer_set = match chain.get_sealer_set(&parent.identifier) {
+            Ok(Some(sealer_set)) => sealer_set,
+            Ok(None) | Err(_) => {
+                return Err(BlockRejection::UnknownSealer(Some(sealer)));
+            }
+        };
+        let sealer_index = match sealer_set.index_of(&sealer) {
+            Some(sealer_index) => sealer_index,
+            None => {
+                return Err(BlockRejection::UnknownSealer(Some(sealer)));
+            }
+        };
+
+        if sealer_set.requires_signatures() {
+            if let Err(reason) = key::verify_block_signature(sealer_set.get_sealer_keys(), block) {
+                return Err(BlockRejection::InvalidSignature(reason));
+            }
+        }
+
+        // the co-leaders follow the leader in the order of the sealers
+        if sealer_set.get_offset_to_leader(sealer_index, height) > self.signer_limit {
+            return Err(BlockRejection::SealerNotInTurn {
+                sealer,
+                height,
+            });
+        }
+
+        match self.validate_key_generation(chain, parent_height, parent, block) {
+            Ok(()) => {}
+            Err(rejection) => {
+                return Err(rejection);
+            }
+        }
+
+        match self.validate_voter_registry(chain, parent_height, parent, block) {
+            Ok(()) => {}
+            Err(rejection) => {
+                return Err(rejection);
+            }
+        }
+
+        self.validate_final_tallies(chain, parent_height, parent, block)
+    }
+
+    /// Check that each commitment of the key generation the given block includes is the first one of a dealer
+    /// on the branch of the given parent, and that the election key it includes is the product of the contributions
+    /// of all dealers. If the sealers generate the election key jointly, its votes must be encrypted with the election key
+    /// committed on the branch, and the voting must not be opened before. Without, neither commitments nor keys are permitted.
+    /// If the feature `voter-registry` is enabled, the proofs of votes are verified by `validate_voter_registry` instead.
+    fn validate_key_generation(&self, chain: &Chain, parent_height: Height, parent: &Block, block: &Block) -> Result<(), BlockRejection> {
+        let is_affected = |transaction: &Transaction| match transaction.trx_type {
+            TransactionType::DkgCommitment | TransactionType::ElectionKey => true,
+            TransactionType::Vote | TransactionType::VoteOpened => self.key_generation.is_some(),
+            _ => false
+        };
+        // walking the branch is only worth it for the affected blocks
+        if !block.data.transactions.iter().any(is_affected) {
+            return Ok(());
+        }
+
+        let mut key_generation_visitor = KeyGenerationVisitor::new();
+        LongestPathWalker::new().walk_from(chain, parent_height, parent, &mut key_generation_visitor);
+        let mut dkg_commitments = key_generation_visitor.get_dkg_commitments().clone();
+        let mut election_key = key_generation_visitor.get_election_key().cloned();
+        let registry = TransactionRegistry::with_default_kinds();
+
+        for transaction in block.data.transactions.iter() {
+            let is_valid = match (self.key_generation.as_ref(), &transaction.trx_type) {
+                (None, &TransactionType::DkgCommitment) | (None, &TransactionType::ElectionKey) => false,
+                (Some(key_generation), &TransactionType::DkgCommitment) => match transaction.dkg_commitment {
+                    Some(ref dkg_commitment) if election_key.is_none() && key_generation.threshold == dkg_commitment.commitments.len() && self.dealers.contains(&dkg_commitment.dealer) => {
+                        dkg_commitments.insert(dkg_commitment.dealer, dkg_commitment.commitments.clone()).is_none()
+                    }
+                    _ => false
+                },
+                (Some(_), &TransactionType::ElectionKey) => match transaction.election_key {
+                    Some(ref key) if election_key.is_none() && self.dealers.len() == dkg_commitments.len() && *key == dkg::get_election_key(&self.public_key, &dkg_commitments) => {
+                        election_key = Some(key.clone());
+                        true
+                    }
+                    _ => false
+                },
+                (Some(_), &TransactionType::Vote) => match election_key {
+                    Some(ref key) => self.voter_registry || registry.is_valid(transaction, &PublicKey { h: key.clone(), ..self.public_key.clone() }, &self.public_uciv, &self.questions, &self.voting_options, self.registrar.as_ref()),
+                    None => false
+                },
+                (Some(_), &TransactionType::VoteOpened) => election_key.is_some(),
+                _ => true
+            };
+
+            if !is_valid {
+                return Err(BlockRejection::InvalidTransaction(transaction.identifier.clone()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that the voters registered and revoked by the given block are permitted on the branch of the given parent,
+    /// see `check_voter_registration`, and that its votes are cast by voters eligible on the branch, including those
+    /// registered before them in the same block, and encrypted with the election key of the branch.
+    fn validate_voter_registry(&self, chain: &Chain, parent_height: Height, parent: &Block, block: &Block) -> Result<(), BlockRejection> {
+        let is_affected = |transaction: &Transaction| match transaction.trx_type {
+            TransactionType::VoterRegistered | TransactionType::VoterRevoked => true,
+            TransactionType::Vote => self.voter_registry,
+            _ => false
+        };
+        if !block.data.transactions.iter().any(is_affected) {
+            return Ok(());
+        }
+
+        let mut voter_registry_visitor = match chain.voter_registry_of(&parent.identifier) {
+            Ok(Some(voter_registry)) => VoterRegistryVisitor::clone(&voter_registry),
+            Ok(None) | Err(_) => {
+                return Err(BlockRejection::UnknownParent(parent.identifier.clone()));
+            }
+        };
+        let public_key = self.tally_calculator.get_election_public_key(chain, parent_height, parent);
+        let registry = TransactionRegistry::with_default_kinds();
+
+        let mut image_sets = voter_registry_visitor.get_image_sets(&None, &self.public_uciv);
+        let mut questions = voter_registry_visitor.get_question_image_sets(&self.questions);
+        for transaction in block.data.transactions.iter().filter(|transaction| is_affected(transaction)) {
+            let is_valid = match transaction.voter_registration {
+                Some(ref voter_registration) if TransactionType::Vote != transaction.trx_type => {
+                    match self.check_voter_registration(&voter_registry_visitor, transaction) {
+                        Ok(()) => {
+                            voter_registry_visitor.register(voter_registration.clone());
+                            image_sets = voter_registry_visitor.get_image_sets(&None, &self.public_uciv);
+                            questions = voter_registry_visitor.get_question_image_sets(&self.questions);
+                            true
+                        }
+                        Err(reason) => {
+                            warn!("Transaction {:?} is invalid: {}", transaction.identifier, reason);
+                            false
+                        }
+                    }
+                }
+                _ => registry.is_valid(transaction, &public_key, &image_sets, &questions, &self.voting_options, self.registrar.as_ref())
+            };
+
+            if !is_valid {
+                return Err(BlockRejection::InvalidTransaction(transaction.identifier.clone()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that each final tally the given block includes is the first one of its question on the branch of the given parent,
+    /// that the voting is closed on the branch and that the tally matches the one calculated from the branch, including
+    /// the decrypted sum and the decryption shares it is decrypted with, if the sealers decrypt the tally jointly.
+    fn validate_final_tallies(&self, chain: &Chain, parent_height: Height, parent: &Block, block: &Block) -> Result<(), BlockRejection> {
+        let final_tallies: Vec<&Transaction> = block.data.transactions.iter()
+            .filter(|transaction| TransactionType::FinalTally == transaction.trx_type)
+            .collect();
+        // walking the branch is only worth it for the affected blocks
+        if final_tallies.is_empty() {
+            return Ok(());
+        }
+
+        let longest_path_walker = LongestPathWalker::new();
+        let mut voting_closed_visitor = VotingClosedVisitor::new();
+        longest_path_walker.walk_from(chain, parent_height, parent, &mut voting_closed_visitor);
+        let mut final_tally_visitor = FinalTallyVisitor::new();
+        longest_path_walker.walk_from(chain, parent_height, parent, &mut final_tally_visitor);
+
+        let calculated_tallies = match voting_closed_visitor.get_election_phase() {
+            ElectionPhase::Closed => self.tally_calculator.calculate(chain, parent_height, parent),
+            _ => vec![]
+        };
+
+        let mut committed_questions = HashSet::new();
+        for transaction in final_tallies {
+            let is_valid = match transaction.final_tally {
+                Some(ref final_tally) => final_tally_visitor.get_final_tally(&final_tally.question_id).is_none()
+                    && committed_questions.insert(final_tally.question_id.clone())
+                    && (final_tally.decrypted_sum.is_some() || !self.tally_calculator.is_decrypted())
+                    && calculated_tallies.contains(final_tally),
+                None => false
+            };
+
+            if !is_valid {
+                return Err(BlockRejection::InvalidTransaction(transaction.identifier.clone()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod block_validator_test {
+
+    use super::*;
+    use ::chain::transaction::{DkgCommitment, SealerVote, VoterRegistration};
+    use ::signer::key::NodeKey;
+
+    fn new_block(parent: &BlockHash, timestamp: u64, sealer: SocketAddr) -> Block {
+        new_block_with_hasher(Hasher::default(), parent, timestamp, sealer)
+    }
+
+    fn new_block_with_hasher(hasher: Hasher, parent: &BlockHash, timestamp: u64, sealer: SocketAddr) -> Block {
+        let mut block = Block::new(hasher, parent.clone(), Some(sealer), vec![]);
+        block.data.timestamp = timestamp;
+        block.identifier = block.get_header().unwrap().hash(hasher);
+
+        block
+    }
+
+    #[test]
+    fn test_validate_against_chain() {
+        let mut genesis = Genesis::new("genesis.json", "public_uciv.json", "public_key.json");
+        genesis.clique.signer_limit = 1;
+        let validator = BlockValidator::new(&genesis);
+
+        let mut chain = Chain::new(genesis.get_configuration_hash(), Hasher::default());
+        chain.set_sealer_set(Some(genesis.get_sealer_set())).unwrap();
+        let genesis_block = chain.blocks[&chain.genesis_identifier_hash].clone();
+        let timestamp = genesis_block.data.timestamp + 1;
+
+        // the leader of height one and its co-leader
+        assert_eq!(Ok(()), validator.validate_against_chain(&chain, &new_block(&genesis_block.identifier, timestamp, genesis.sealer[0])));
+        assert_eq!(Ok(()), validator.validate_against_chain(&chain, &new_block(&genesis_block.identifier, timestamp, genesis.sealer[1])));
+        assert_eq!(
+            Err(BlockRejection::SealerNotInTurn { sealer: genesis.sealer[2], height: Height::new(1) }),
+            validator.validate_against_chain(&chain, &new_block(&genesis_block.identifier, timestamp, genesis.sealer[2]))
+        );
+
+        assert_eq!(
+            Err(BlockRejection::TimestampNotIncreasing { timestamp: genesis_block.data.timestamp, parent_timestamp: genesis_block.data.timestamp }),
+            validator.validate_against_chain(&chain, &new_block(&genesis_block.identifier, genesis_block.data.timestamp, genesis.sealer[0]))
+        );
+        let future_block = new_block(&genesis_block.identifier, timestamp + 2 * MAX_TIMESTAMP_DRIFT, genesis.sealer[0]);
+        assert!(validator.validate_against_chain(&chain, &future_block).is_err());
+
+        let orphan = new_block(&BlockHash::from("unknown"), timestamp, genesis.sealer[0]);
+        assert_eq!(Err(BlockRejection::UnknownParent(BlockHash::from("unknown"))), validator.validate_against_chain(&chain, &orphan));
+
+        let foreign_sealer: SocketAddr = "127.0.0.1:1".parse().unwrap();
+        let foreign_block = new_block(&genesis_block.identifier, timestamp, foreign_sealer);
+        assert_eq!(Err(BlockRejection::UnknownSealer(Some(foreign_sealer))), validator.validate_against_chain(&chain, &foreign_block));
+    }
+
+    #[test]
+    fn test_validate_hasher() {
+        let mut genesis = Genesis::new("genesis.json", "public_uciv.json", "public_key.json");
+        let mut chain = Chain::new(genesis.get_configuration_hash(), Hasher::default());
+        chain.set_sealer_set(Some(genesis.get_sealer_set())).unwrap();
+        let genesis_block = chain.blocks[&chain.genesis_identifier_hash].clone();
+        let timestamp = genesis_block.data.timestamp + 1;
+
+        let legacy_block = new_block_with_hasher(Hasher::Sha1, &genesis_block.identifier, timestamp, genesis.sealer[0]);
+        assert!(legacy_block.has_valid_identifier());
+        assert_eq!(Ok(()), BlockValidator::new(&genesis).validate_integrity(&legacy_block));
+        assert_eq!(
+            Err(BlockRejection::UnexpectedHasher(legacy_block.identifier.to_string())),
+            BlockValidator::new(&genesis).validate_against_chain(&chain, &legacy_block)
+        );
+        let blake2s_block = new_block_with_hasher(Hasher::Blake2s, &genesis_block.identifier, timestamp, genesis.sealer[0]);
+        assert_eq!(
+            Err(BlockRejection::UnexpectedHasher(blake2s_block.identifier.to_string())),
+            BlockValidator::new(&genesis).validate_against_chain(&chain, &blake2s_block)
+        );
+
+        // legacy identifiers are only accepted below the configured height
+        genesis.clique.legacy_sha1_height = Some(Height::new(2));
+        let validator = BlockValidator::new(&genesis);
+        assert_eq!(Ok(()), validator.validate_against_chain(&chain, &legacy_block));
+        assert!(validator.is_expected_hasher(legacy_block.identifier.as_str(), Height::new(1)));
+        assert!(!validator.is_expected_hasher(legacy_block.identifier.as_str(), Height::new(2)));
+        assert!(!validator.is_expected_hasher(blake2s_block.identifier.as_str(), Height::new(1)));
+        assert!(validator.is_expected_hasher(new_block(&genesis_block.identifier, timestamp, genesis.sealer[0]).identifier.as_str(), Height::new(2)));
+    }
+
+    #[test]
+    fn test_validate_final_tallies() {
+        let genesis = Genesis::new("genesis.json", "public_uciv.json", "public_key.json");
+        let validator = BlockValidator::new(&genesis);
+
+        let mut chain = Chain::new(genesis.get_configuration_hash(), Hasher::default());
+        chain.set_sealer_set(Some(genesis.get_sealer_set())).unwrap();
+        let mut parent = chain.blocks[&chain.genesis_identifier_hash].clone();
+        let opening = Transaction::new_voting_opened(Hasher::default(), genesis.get_configuration_hash(), 0);
+        let closing = Transaction::new_voting_closed(Hasher::default(), genesis.get_configuration_hash(), 1);
+        for (height, transaction) in vec![opening, closing].into_iter().enumerate() {
+            let mut block = Block::new(Hasher::default(), parent.identifier.clone(), Some(genesis.sealer[height]), vec![transaction]);
+            block.data.timestamp = parent.data.timestamp + 1;
+            block.identifier = block.get_header().unwrap().hash(Hasher::default());
+            chain.add_block(block.clone());
+            parent = block;
+        }
+
+        let final_tallies = TallyCalculator::new(&genesis).calculate(&chain, Height::new(2), &parent);
+        let final_tally = Transaction::new_final_tally(Hasher::default(), final_tallies[0].clone());
+        let new_tally_block = |transactions: Vec<Transaction>| {
+            let mut block = Block::new(Hasher::default(), parent.identifier.clone(), Some(genesis.sealer[2]), transactions);
+            block.data.timestamp = parent.data.timestamp + 1;
+            block.identifier = block.get_header().unwrap().hash(Hasher::default());
+            block
+        };
+        assert_eq!(Ok(()), validator.validate_against_chain(&chain, &new_tally_block(vec![final_tally.clone()])));
+
+        // a question is tallied once
+        assert_eq!(
+            Err(BlockRejection::InvalidTransaction(final_tally.identifier.clone())),
+            validator.validate_against_chain(&chain, &new_tally_block(vec![final_tally.clone(), final_tally.clone()]))
+        );
+
+        let mut forged_tally = final_tallies[0].clone();
+        forged_tally.total_votes += 1;
+        let forged_tally = Transaction::new_final_tally(Hasher::default(), forged_tally);
+        assert_eq!(
+            Err(BlockRejection::InvalidTransaction(forged_tally.identifier.clone())),
+            validator.validate_against_chain(&chain, &new_tally_block(vec![forged_tally.clone()]))
+        );
+    }
+
+    #[test]
+    fn test_validate_integrity() {
+        let genesis = Genesis::new("genesis.json", "public_uciv.json", "public_key.json");
+        let validator = BlockValidator::new(&genesis);
+
+        let block = Block::new(Hasher::default(), BlockHash::from("parent"), Some(genesis.sealer[0]), vec![]);
+        assert_eq!(Ok(()), validator.validate_integrity(&block));
+
+        let mut tampered_block = block.clone();
+        tampered_block.data.timestamp += 1;
+        assert_eq!(Err(BlockRejection::InvalidIdentifier), validator.validate_integrity(&tampered_block));
+
+        let unsealed_block = Block::new(Hasher::default(), BlockHash::from("parent"), None, vec![]);
+        assert_eq!(Err(BlockRejection::UnknownSealer(None)), validator.validate_integrity(&unsealed_block));
+
+        let vote = SealerVote { voter: genesis.sealer[1], candidate: "127.0.0.1:1".parse().unwrap(), authorize: true, public_key: None, cast_at: 0 };
+        let vote_transaction = Transaction::new_sealer_vote(Hasher::default(), vote);
+        let foreign_vote_block = Block::new(Hasher::default(), BlockHash::from("parent"), Some(genesis.sealer[0]), vec![vote_transaction.clone()]);
+        assert_eq!(Err(BlockRejection::ForeignSealerVote(vote_transaction.identifier.clone())), validator.validate_integrity(&foreign_vote_block));
+
+        let dkg_commitment = Transaction::new_dkg_commitment(Hasher::default(), DkgCommitment { dealer: genesis.sealer[1], commitments: vec![] });
+        let foreign_commitment_block = Block::new(Hasher::default(), BlockHash::from("parent"), Some(genesis.sealer[0]), vec![dkg_commitment.clone()]);
+        assert_eq!(Err(BlockRejection::ForeignDkgCommitment(dkg_commitment.identifier.clone())), validator.validate_integrity(&foreign_commitment_block));
+
+        // sizes are checked before any proof is verified
+        let mut strict_validator = validator.clone();
+        strict_validator.transaction_limits.max_transaction_size = 1;
+        match strict_validator.validate_transactions(&[vote_transaction.clone()]) {
+            Err(BlockRejection::OversizedTransaction(identifier, LimitViolation::TransactionTooLarge { .. })) => assert_eq!(vote_transaction.identifier, identifier),
+            other => panic!("Expected an oversized transaction, got {:?}", other)
+        }
+        // transactions opening the voting must be signed by the election authority, if any
+        let election_authority = NodeKey::generate();
+        let mut authorized_validator = validator.clone();
+        authorized_validator.election_authority = Some(election_authority.public_key.clone());
+        let unsigned_opening = Transaction::new_voting_opened(Hasher::default(), genesis.get_configuration_hash(), 0);
+        let unsigned_block = Block::new(Hasher::default(), BlockHash::from("parent"), Some(genesis.sealer[0]), vec![unsigned_opening.clone()]);
+        assert_eq!(Ok(()), validator.validate_integrity(&unsigned_block));
+        assert_eq!(Err(BlockRejection::UnauthorizedPhaseChange(unsigned_opening.identifier.clone())), authorized_validator.validate_integrity(&unsigned_block));
+
+        let phase_change = unsigned_opening.phase_change.clone().unwrap().sign(&TransactionType::VoteOpened, &election_authority).unwrap();
+        let signed_opening = Transaction::from_phase_change(Hasher::default(), TransactionType::VoteOpened, phase_change);
+        let signed_block = Block::new(Hasher::default(), BlockHash::from("parent"), Some(genesis.sealer[0]), vec![signed_opening]);
+        assert_eq!(Ok(()), authorized_validator.validate_integrity(&signed_block));
+    }
+    #[test]
+    fn test_validate_voter_registry() {
+        let genesis = Genesis::new("genesis.json", "public_uciv.json", "public_key.json");
+        let election_authority = NodeKey::generate();
+        let mut validator = BlockValidator::new(&genesis);
+        validator.election_authority = Some(election_authority.public_key.clone());
+        validator.voter_registry = true;
+
+        let mut chain = Chain::new(genesis.get_configuration_hash(), Hasher::default());
+        chain.set_sealer_set(Some(genesis.get_sealer_set())).unwrap();
+        let genesis_block = chain.blocks[&chain.genesis_identifier_hash].clone();
+        let new_registry_block = |parent: &Block, sealer: SocketAddr, transactions: Vec<Transaction>| {
+            let mut block = Block::new(Hasher::default(), parent.identifier.clone(), Some(sealer), transactions);
+            block.data.timestamp = parent.data.timestamp + 1;
+            block.identifier = block.get_header().unwrap().hash(Hasher::default());
+            block
+        };
+        let new_registration = |trx_type: TransactionType, voter_idx: usize| {
+            let voter_registration = VoterRegistration {
+                election: genesis.get_configuration_hash(),
+                question_id: None,
+                voter_idx,
+                image_set: match trx_type {
+                    TransactionType::VoterRegistered => Some(genesis.public_uciv[0].clone()),
+                    _ => None
+                },
+                credential: format!("voter-{}", voter_idx),
+                registered_at: 0,
+                signature: None,
+            };
+            Transaction::from_voter_registration(Hasher::default(), trx_type.clone(), voter_registration.sign(&trx_type, &election_authority).unwrap())
+        };
+
+        // voters beyond the ones declared in the genesis configuration are registered late
+        let late_voter = genesis.public_uciv.len();
+        let registration = new_registration(TransactionType::VoterRegistered, late_voter);
+        let registration_block = new_registry_block(&genesis_block, genesis.sealer[0], vec![registration.clone()]);
+        assert_eq!(Ok(()), validator.validate_integrity(&registration_block));
+        assert_eq!(Ok(()), validator.validate_against_chain(&chain, &registration_block));
+
+        // a voter is registered once, and revoked once registered
+        let duplicate = new_registration(TransactionType::VoterRegistered, 0);
+        assert_eq!(
+            Err(BlockRejection::InvalidTransaction(duplicate.identifier.clone())),
+            validator.validate_against_chain(&chain, &new_registry_block(&genesis_block, genesis.sealer[0], vec![duplicate.clone()]))
+        );
+        let revocation = new_registration(TransactionType::VoterRevoked, late_voter);
+        assert_eq!(
+            Err(BlockRejection::InvalidTransaction(revocation.identifier.clone())),
+            validator.validate_against_chain(&chain, &new_registry_block(&genesis_block, genesis.sealer[0], vec![revocation.clone()]))
+        );
+        assert_eq!(Ok(()), validator.validate_against_chain(&chain, &new_registry_block(&genesis_block, genesis.sealer[0], vec![registration.clone(), revocation.clone()])));
+
+        chain.add_block(registration_block.clone());
+        assert_eq!(Ok(()), validator.validate_against_chain(&chain, &new_registry_block(&registration_block, genesis.sealer[1], vec![revocation.clone()])));
+
+        // registrations require the signature of the election authority and the feature to be enabled
+        let mut unsigned_registration = registration.clone();
+        unsigned_registration.voter_registration.as_mut().unwrap().signature = None;
+        let unsigned_block = new_registry_block(&genesis_block, genesis.sealer[0], vec![unsigned_registration.clone()]);
+        assert_eq!(Err(BlockRejection::UnauthorizedVoterRegistration(unsigned_registration.identifier.clone())), validator.validate_integrity(&unsigned_block));
+
+        let mut disabled_validator = validator.clone();
+        disabled_validator.voter_registry = false;
+        assert_eq!(
+            Err(BlockRejection::InvalidTransaction(registration.identifier.clone())),
+            disabled_validator.validate_against_chain(&chain, &new_registry_block(&genesis_block, genesis.sealer[0], vec![registration.clone()]))
+        );
+    }
+}
@@ -1,17 +1,81 @@
-use ::chain::block::{Block};
-use ::chain::chain::Chain;
-use ::chain::chain_visitor::{FindTransactionVisitor, SumCipherTextVisitor};
+use ::chain::block::{Block, BlockHeader};
+use ::chain::block_store::BlockStore;
+use ::chain::chain::{Chain, HeadInfo};
+use ::chain::chain_visitor::{DecryptionSharesVisitor, ElectionPhase, EpochStatisticsVisitor, FinalTallyVisitor, FinalizedBlocksVisitor, FindTransactionVisitor, KeyGenerationVisitor, QueryTransactionsVisitor, SealerStatisticsVisitor, StateSnapshotVisitor, SumCipherTextVisitor, VoterRegistryVisitor};
 use ::chain::chain_walker::{ChainWalker, LongestPathWalker};
-use ::chain::transaction::Transaction;
+use ::chain::epoch::{Epoch, EpochSchedule, EpochStatistics};
+use ::chain::hasher::Hasher;
+use ::chain::merkle::MerkleProof;
+use ::chain::sealer_set::SealerSet;
+use ::chain::snapshot::StateSnapshot;
+use ::chain::transaction::{DecryptionShare, DkgCommitment, FinalTally, SealerVote, Transaction, TransactionQuery, TransactionType};
+use ::chain::transaction_registry::{RevotePolicy, TransactionRegistry};
+use ::chain::types::{BlockHash, Height};
+use ::chain::write_ahead_log::{WalEntry, WriteAheadLog};
+use ::config::feature::{self, Feature};
 use ::config::genesis::Genesis;
-use ::p2p::codec::Message;
-use bincode;
+use ::config::version::{VersionCheck, VersionPolicy};
+use ::metrics::{Metrics, MetricsRegistry, PruningMetrics};
+use ::onboarding::JoinRequest;
+use ::p2p::admin::{AdminChannel, AdminIdentity, AdminRequest};
+use ::p2p::clock;
+use ::p2p::codec::{self, Codec, CodecKind, JsonCodec, Message, MAX_CHUNK_SIZE};
+use ::p2p::health::{PeerHealth, PeerHealthTracker};
+use ::p2p::peers;
+use ::protocol::block_validator::{BlockValidator, MAX_TIMESTAMP_DRIFT};
+use ::protocol::mempool::{self, Mempool, MempoolEntry, PendingTransaction};
+use ::protocol::receipt::{ReceiptStatus, ReceiptTracker, Reversion};
+use ::protocol::tally::{DecryptionKeys, TallyCalculator};
+use ::threshold::{self, KeyShare};
+use ::threshold::dkg::{self, DealtShare, Dealing, KeyGeneration};
+use serde_json;
+use crypto_rs::arithmetic::mod_int::ModInt;
+use crypto_rs::cai::uciv::ImageSet;
 use crypto_rs::el_gamal::ciphertext::CipherText;
-use sha1::Sha1;
+use crypto_rs::el_gamal::encryption::PublicKey;
+use std::borrow::Borrow;
+use std::cmp;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::mem;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use std::vec::Vec;
 
+/// The version of this node software.
+pub const NODE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The maximum amount of join requests kept until the authorities decide on them.
+pub const MAX_PENDING_JOIN_REQUESTS: usize = 100;
+
+/// The maximum amount of pending join requests of candidates listed under the same IP address,
+/// so that a single host cannot occupy all places for join requests.
+pub const MAX_JOIN_REQUESTS_PER_HOST: usize = 3;
+
+/// The time in seconds after its issuance a join request is dropped, unless the candidate was voted in.
+/// Candidates still wanting to join submit their request again.
+pub const JOIN_REQUEST_LIFETIME: u64 = 3 * 24 * 60 * 60;
+
+/// The amount of blocks the tip of a branch must fall behind
+/// the head of the canonical chain for the branch to be abandoned.
+pub const BRANCH_ABANDON_DEPTH: usize = 6;
+
+/// The maximum amount of transactions returned by a single query.
+pub const MAX_QUERY_RESULTS: usize = 100;
+
+/// The maximum amount of blocks returned for a single `Message::BlockRangeRequest`.
+pub const MAX_BLOCK_RANGE: usize = 100;
+
+/// The reason of rejecting a transaction whose proofs are invalid.
+pub const INVALID_TRANSACTION_REASON: &str = "Transaction is invalid";
+
+/// Called with the previous and the new head whenever the head of the canonical chain changes.
+pub type HeadListener = Box<Fn(&HeadInfo, &HeadInfo) + Send>;
+
+/// Called with the identifier of a transaction whenever its block was reverted
+/// by a reorganization, and again once it is included in a block of the new branch.
+pub type ReceiptListener = Box<Fn(&str, &ReceiptStatus) + Send>;
+
 /// A protocol handler implements specific business logic
 /// on what should be done when a message is received,
 /// either from other running nodes or client applications.
@@ -36,11 +100,106 @@ pub trait ProtocolHandler {
 /// sybil control mechanism.
 #[derive(Serialize)]
 pub struct CliqueProtocol {
-    transactions: Vec<Transaction>,
-    signer_index: usize,
-    signer_count: usize,
+    /// The transactions waiting to be included in a block, maintained whether or not this node leads the sealing.
+    mempool: Mempool,
+    /// The socket address under which other nodes know this node.
+    own_address: SocketAddr,
     genesis: Genesis,
     chain: Chain,
+    version_check: VersionCheck,
+    /// Checks the blocks received from other nodes against the rules of the genesis configuration.
+    #[serde(skip)]
+    block_validator: BlockValidator,
+    /// Calculates the final tallies committed on the chain once the voting is closed.
+    #[serde(skip)]
+    tally_calculator: TallyCalculator,
+    /// The channel accepting commands of the designated admin identity.
+    /// None, if admin commands are not accepted over the peer protocol.
+    #[serde(skip)]
+    admin_channel: Option<AdminChannel>,
+    /// The join requests of candidates which want to become sealers.
+    join_requests: Vec<JoinRequest>,
+    /// The tips of the branches abandoned by fork choice.
+    abandoned_branches: HashSet<BlockHash>,
+    /// The backend persisting the chain. None, if the chain is kept in memory only.
+    #[serde(skip)]
+    block_store: Option<Box<BlockStore>>,
+    /// The log of blocks and buffered transactions not persisted otherwise yet.
+    /// None, if they are kept in memory only.
+    #[serde(skip)]
+    write_ahead_log: Option<WriteAheadLog>,
+    /// The directives the log output of this node is filtered with.
+    #[serde(skip)]
+    log_filters: Vec<String>,
+    /// The amount of milliseconds the clocks of the peers are ahead of the local one.
+    /// None, if the clock was not checked yet.
+    #[serde(skip)]
+    clock_offset: Option<i64>,
+    /// The registry the thread pools of this node are reported in.
+    /// None, if no metrics are collected.
+    #[serde(skip)]
+    metrics_registry: Option<Arc<MetricsRegistry>>,
+    /// The connectivity to each peer, as observed by the transport of the node.
+    /// None, if the connectivity is not tracked.
+    #[serde(skip)]
+    peer_health: Option<Arc<PeerHealthTracker>>,
+    /// The listeners notified whenever the head of the canonical chain changes.
+    #[serde(skip)]
+    head_listeners: Vec<HeadListener>,
+    /// The transactions whose blocks were reverted by reorganizations.
+    #[serde(skip)]
+    reverted_transactions: ReceiptTracker,
+    /// The listeners notified whenever the receipt of a reverted transaction changes.
+    #[serde(skip)]
+    receipt_listeners: Vec<ReceiptListener>,
+    /// The share of the private key of the election held by this sealer, with which it
+    /// publishes its decryption shares once the voting is closed. None, if it holds no share.
+    #[serde(skip)]
+    key_share: Option<KeyShare>,
+    /// The progress of this sealer in generating the election key jointly,
+    /// if the genesis configuration declares the key generation.
+    #[serde(skip)]
+    key_generation: KeyGeneration,
+}
+
+/// Describes where a transaction is included in the canonical chain.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct TransactionInclusion {
+    /// The identifier of the included transaction.
+    pub transaction_identifier: String,
+    /// The identifier of the block including the transaction.
+    pub block_identifier: BlockHash,
+    /// The height of the block including the transaction.
+    pub height: Height,
+    /// The amount of blocks on top of the block including the transaction.
+    pub confirmations: usize,
+    /// True, if the block including the transaction is finalized, i.e. the transaction is irreversible.
+    /// False for nodes of earlier versions.
+    #[serde(default)]
+    pub finalized: bool,
+}
+
+/// Proves that a transaction is included in a block of the canonical chain,
+/// allowing a voter to verify that their ballot is committed on the chain
+/// without downloading the whole chain.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct InclusionProof {
+    /// Where the transaction is included in the canonical chain.
+    pub inclusion: TransactionInclusion,
+    /// The header of the block including the transaction, whose hash is the identifier of the block.
+    pub header: BlockHeader,
+    /// The path from the transaction to the root of the transactions in the header.
+    pub merkle_proof: MerkleProof,
+}
+
+impl InclusionProof {
+    /// Returns true, if the given transaction is included in the block
+    /// with the identifier named by this proof.
+    pub fn verify(&self, transaction: &Transaction) -> bool {
+        transaction.identifier == self.inclusion.transaction_identifier
+            && self.header.is_identified_by(&self.inclusion.block_identifier)
+            && self.merkle_proof.verify(transaction, &self.header.transactions_root)
+    }
 }
 
 /// Holds the tally of the voting.
@@ -48,123 +207,2253 @@ pub struct CliqueProtocol {
 pub struct Tally {
     pub total_votes: usize,
     pub cipher_text: CipherText,
+    /// The decrypted sum of the votes, once a threshold of the sealers published valid decryption shares.
+    /// None, if the genesis configuration does not configure threshold decryption.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decrypted_sum: Option<u64>,
+    /// The tally of each further question declared in the genesis configuration, by its identifier.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub questions: BTreeMap<String, QuestionTally>,
+}
+
+/// Holds the tally of the voting on a single question.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct QuestionTally {
+    pub total_votes: usize,
+    pub cipher_text: CipherText,
+    /// The decrypted sum of the votes on the question, see `Tally`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decrypted_sum: Option<u64>,
+}
+
+/// Holds the interim turnout of the voting, i.e. the amount of votes counted on the canonical chain without their sum.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct Turnout {
+    /// The height of the head of the canonical chain the votes are counted on.
+    pub height: Height,
+    /// The identifier of the head of the canonical chain the votes are counted on.
+    pub head_identifier: BlockHash,
+    /// The current phase of the election.
+    pub phase: ElectionPhase,
+    /// The amount of votes counted on the question whose voters are declared in `public_uciv.json`.
+    pub total_votes: usize,
+    /// The amount of votes counted on each further question declared in the genesis configuration, by its identifier.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub questions: BTreeMap<String, usize>,
+}
+
+/// Holds the amount of blocks each sealer has produced on the canonical chain.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct SealerStatistics {
+    /// Pairs of sealer address and the amount of blocks sealed by it,
+    /// in the order of the sealers in the genesis configuration.
+    pub sealed_blocks: Vec<(SocketAddr, usize)>,
+    /// The amount of blocks which do not specify their sealer.
+    pub unattributed_blocks: usize,
+}
+
+/// A summary of the state of the election on the canonical chain.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct ElectionStatus {
+    /// The height of the head of the canonical chain.
+    pub height: Height,
+    /// The identifier of the head of the canonical chain.
+    pub head_identifier: BlockHash,
+    /// The time the head of the canonical chain was created, in seconds since the epoch.
+    pub head_timestamp: u64,
+    /// The current phase of the election.
+    pub phase: ElectionPhase,
+    /// The amount of votes counted so far.
+    pub total_votes: usize,
+    /// The amount of transactions waiting to be included in a block.
+    pub pending_transactions: usize,
+    /// The amount of blocks sealed by each sealer.
+    pub sealer_statistics: SealerStatistics,
+    /// The epoch the next block to be sealed belongs to.
+    pub current_epoch: Epoch,
+    /// The tips of the branches abandoned by fork choice, whose blocks are orphaned.
+    pub abandoned_branches: Vec<BlockHash>,
+    /// The height of the finalized head of the canonical chain, up to which blocks are irreversible.
+    /// Zero for nodes of earlier versions.
+    #[serde(default)]
+    pub finalized_height: Height,
+    /// The election key votes are encrypted with, once the sealers generated it jointly.
+    /// None, if the election key is declared in the genesis configuration.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub election_key: Option<ModInt>,
+}
+
+/// Describes a node and the network it belongs to,
+/// allowing peers to diagnose whether they are compatible.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct NodeStatus {
+    /// The version of the node software.
+    pub node_version: String,
+    /// The name of the codec used to encode messages.
+    pub codec: String,
+    /// The names of all codecs the node decodes, the most compact first.
+    /// Empty for nodes of earlier versions, which only decode `codec`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub codecs: Vec<String>,
+    /// The version declared in the genesis configuration.
+    pub genesis_version: String,
+    /// The hash of the genesis configuration.
+    pub genesis_configuration_hash: String,
+    /// The sealers declared in the genesis configuration.
+    pub sealer: Vec<SocketAddr>,
+    /// The optional behaviours enabled in the genesis configuration.
+    /// Empty for nodes of earlier versions.
+    #[serde(default)]
+    pub features: BTreeSet<Feature>,
+    /// The sealers authorized on the canonical chain, differing from the declared ones
+    /// once sealers were voted in or out. Empty for nodes of earlier versions.
+    #[serde(default)]
+    pub authorized_sealers: Vec<SocketAddr>,
+    /// The result of checking the version declared in the genesis configuration
+    /// against the version of the node.
+    pub version_check: VersionCheck,
+    /// The height of the head of the canonical chain.
+    pub height: Height,
+    /// The identifier of the head of the canonical chain.
+    pub head_identifier: BlockHash,
+    /// The directives the log output of the node is filtered with, e.g. `node_rs::p2p=trace`.
+    /// Empty for nodes of earlier versions.
+    #[serde(default)]
+    pub log_filters: Vec<String>,
+    /// The amount of milliseconds the clocks of the peers are ahead of the clock of the node.
+    /// None, if the clock was not checked yet or for nodes of earlier versions.
+    #[serde(default)]
+    pub clock_offset: Option<i64>,
+}
+
+/// Introduces a node to a peer, so that nodes running different
+/// genesis configurations notice at connect time rather than when exchanging chains.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct Handshake {
+    /// The address under which other nodes know the introduced node.
+    pub address: SocketAddr,
+    /// The version of the node software.
+    pub node_version: String,
+    /// The hash of the genesis configuration.
+    pub genesis_configuration_hash: String,
+    /// The optional behaviours enabled in the genesis configuration.
+    /// Empty for nodes of earlier versions.
+    #[serde(default)]
+    pub features: BTreeSet<Feature>,
+    /// The names of the codecs the introduced node decodes, the most compact first.
+    /// Empty for nodes of earlier versions, which only decode JSON.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub codecs: Vec<String>,
+    /// The names of the compressions the introduced node decompresses, in the order of preference.
+    /// Empty if the node does not compress messages or is of an earlier version.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub compressions: Vec<String>,
+}
+
+/// Identifies the position of the next chunk of a chain to request, i.e. the last block of the previous chunk.
+///
+/// The next chunk continues with the blocks following this one in the order of `Chain::get_blocks_by_height`,
+/// so that blocks added to the chain while it is requested do not make the chunks start over.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct ChainChunkToken {
+    /// The height of the last block of the previous chunk.
+    pub height: Height,
+    /// The identifier of the last block of the previous chunk.
+    pub after: BlockHash,
+}
+
+/// A bounded segment of the blocks of a chain.
+///
+/// Blocks are ordered by their height and then their identifier, so that each block is preceded
+/// by its parent, starting with the genesis block in the first chunk.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct ChainChunk {
+    /// The hash of the genesis configuration of the chain.
+    pub genesis_configuration_hash: String,
+    /// The blocks contained in this chunk.
+    pub blocks: Vec<Block>,
+    /// The token to request the next chunk with, or None if this is the last chunk.
+    pub next: Option<ChainChunkToken>,
+}
+
+impl ChainChunk {
+    /// Create the chunk of the given blocks of a chain which follows the given token.
+    /// The chunk contains as many blocks as fit into `MAX_CHUNK_SIZE` bytes,
+    /// but at least one.
+    ///
+    /// - genesis_configuration_hash: The hash of the genesis configuration of the chain.
+    /// - blocks_by_height: The blocks of the chain as returned by `Chain::get_blocks_by_height`.
+    /// - token: The token identifying the last block of the previous chunk, or None for the first chunk.
+    pub fn new(genesis_configuration_hash: String, blocks_by_height: &[(Height, Block)], token: Option<&ChainChunkToken>) -> ChainChunk {
+        let offset = match token {
+            Some(token) => blocks_by_height.partition_point(|entry| (entry.0, &entry.1.identifier) <= (token.height, &token.after)),
+            None => 0
+        };
+
+        let mut blocks = vec![];
+        let mut chunk_size = 0;
+        let mut next_offset = offset;
+
+        for entry in blocks_by_height.iter().skip(offset) {
+            let block_size = serde_json::to_string(&entry.1).unwrap().len();
+            if !blocks.is_empty() && chunk_size + block_size > MAX_CHUNK_SIZE {
+                break;
+            }
+
+            chunk_size += block_size;
+            blocks.push(entry.1.clone());
+            next_offset += 1;
+        }
+
+        let next = if next_offset < blocks_by_height.len() {
+            let last = &blocks_by_height[next_offset - 1];
+            Some(ChainChunkToken {
+                height: last.0,
+                after: last.1.identifier.clone(),
+            })
+        } else {
+            None
+        };
+
+        ChainChunk {
+            genesis_configuration_hash,
+            blocks,
+            next,
+        }
+    }
+}
+
+/// A segment of the canonical chain, sent in response to a `Message::BlockRangeRequest`.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct BlockRange {
+    /// The hash of the genesis configuration of the chain.
+    pub genesis_configuration_hash: String,
+    /// The height of the head of the canonical chain of the responder.
+    pub head_height: Height,
+    /// The height of the first contained block.
+    pub from_height: Height,
+    /// The blocks of the canonical chain ordered by their height, starting at `from_height`.
+    /// Contains at most `MAX_BLOCK_RANGE` blocks and, unless it consists of a single block,
+    /// at most `MAX_CHUNK_SIZE` bytes, hence it may end before the requested height.
+    pub blocks: Vec<Block>,
+}
+
+impl BlockRange {
+    /// Create the range of the given canonical blocks from `from_height` up to and including `to_height`,
+    /// bounded by `MAX_BLOCK_RANGE` blocks and `MAX_CHUNK_SIZE` bytes.
+    /// No blocks are returned from below the root of a chain starting with a state snapshot.
+    ///
+    /// - genesis_configuration_hash: The hash of the genesis configuration of the chain.
+    /// - root_height: The height of the first block of the chain.
+    /// - canonical_blocks: The blocks of the canonical chain ordered by their height, starting at the root.
+    pub fn new<B: Borrow<Block>>(genesis_configuration_hash: String, root_height: Height, canonical_blocks: &[B], from_height: Height, to_height: Height) -> BlockRange {
+        let amount_requested_blocks = if to_height < from_height {
+            0
+        } else {
+            cmp::min(to_height.distance_to(from_height), MAX_BLOCK_RANGE - 1) + 1
+        };
+
+        let skipped_blocks = if from_height < root_height {
+            canonical_blocks.len()
+        } else {
+            from_height.distance_to(root_height)
+        };
+
+        let mut blocks = vec![];
+        let mut range_size = 0;
+        for block in canonical_blocks.iter().skip(skipped_blocks).take(amount_requested_blocks) {
+            let block_size = serde_json::to_string(block.borrow()).unwrap().len();
+            if !blocks.is_empty() && range_size + block_size > MAX_CHUNK_SIZE {
+                break;
+            }
+
+            range_size += block_size;
+            blocks.push(block.borrow().clone());
+        }
+
+        BlockRange {
+            genesis_configuration_hash,
+            head_height: root_height + canonical_blocks.len().saturating_sub(1),
+            from_height,
+            blocks,
+        }
+    }
 }
 
 impl CliqueProtocol {
     /// Create a new protocol instance.
     ///
-    /// - own_address: The socket address the node is listening on. Used to find the own
-    ///                sealer index in the genesis configuration.
+    /// - own_address: The socket address under which other nodes know this node. Used to find
+    ///   the own sealer index among the authorized sealers.
     /// - genesis: The initial configuration of the clique protocol.
+    /// - version_policy: The policy defining which versions declared in the genesis
+    ///   configuration are accepted.
     ///
     /// # Panics
-    /// Panics if the given own_address is not contained in the genesis configuration.
-    pub fn new(own_address: SocketAddr, genesis: Genesis) -> Self {
-        let own_signer_index = genesis.sealer.clone()
-            .iter()
-            .enumerate()
-            .find(|&element| element.1.eq(&own_address.clone()))
-            .expect("Could not find own socket address in sealers of genesis configuration")
-            .0;
-
-        trace!("Found own sealer index to be {} for own listening address {} in genesis configuration", own_signer_index.clone(), own_address.clone());
+    /// Panics if the declared version is not accepted by the given policy.
+    pub fn new(own_address: SocketAddr, genesis: Genesis, version_policy: VersionPolicy) -> Self {
+        let version_check = VersionCheck::new(version_policy, &genesis.version, NODE_VERSION);
+        if let Some(ref incompatibility) = version_check.incompatibility {
+            panic!("Refusing to join network: {}", incompatibility);
+        }
 
-        let own_signer_count = genesis.sealer.len().clone();
-        trace!("Found a total of {} sealer in genesis configuration", own_signer_count.clone());
+        match genesis.sealer.iter().position(|sealer| peers::is_same_peer(sealer, &own_address)) {
+            Some(own_signer_index) => trace!("Found own sealer index to be {} for own listening address {} in genesis configuration", own_signer_index, own_address),
+            None => warn!("Own listening address {} is not a sealer of the genesis configuration. Sealing only once the sealers voted to add it", own_address)
+        }
 
         // Create a sha1 digest of the genesis configuration so that we can later
         // ensure, that we only accept blocks from a chain with the same configuration.
-        let bytes = bincode::serialize(&genesis).unwrap();
-        let digest: String = Sha1::from(bytes).hexdigest();
+        let digest: String = genesis.get_configuration_hash();
+        let mut chain = Chain::new(digest, genesis.clique.get_hasher());
+        chain.set_sealer_set(Some(genesis.get_sealer_set())).expect("Failed to index the genesis block");
+        let block_validator = BlockValidator::new(&genesis);
+        let tally_calculator = TallyCalculator::new(&genesis);
 
         CliqueProtocol {
-            transactions: vec![],
-            signer_index: own_signer_index,
-            signer_count: own_signer_count,
+            mempool: Mempool::new(),
+            own_address,
             genesis,
-            chain: Chain::new(digest),
+            chain,
+            version_check,
+            block_validator,
+            tally_calculator,
+            admin_channel: None,
+            join_requests: vec![],
+            abandoned_branches: HashSet::new(),
+            block_store: None,
+            write_ahead_log: None,
+            log_filters: vec![],
+            clock_offset: None,
+            metrics_registry: None,
+            peer_health: None,
+            head_listeners: vec![],
+            reverted_transactions: ReceiptTracker::new(),
+            receipt_listeners: vec![],
+            key_share: None,
+            key_generation: KeyGeneration::new(),
+        }
+    }
+
+    /// Accept admin commands of the given identity over the peer protocol.
+    pub fn set_admin_identity(&mut self, admin_identity: AdminIdentity) {
+        self.admin_channel = Some(AdminChannel::new(admin_identity));
+    }
+
+    /// Publish decryption shares with the given share of the private key of the election
+    /// once the voting is closed, see `publish_decryption_shares`.
+    pub fn set_key_share(&mut self, key_share: KeyShare) {
+        self.key_share = Some(key_share);
+    }
+
+    /// Keep the progress of this sealer in generating the election key jointly in the given key generation,
+    /// which is continued if it was persisted before, see `publish_key_generation`.
+    pub fn set_key_generation(&mut self, key_generation: KeyGeneration) {
+        self.key_generation = key_generation;
+    }
+
+    /// Reject transactions once the buffered ones use the given amount of bytes,
+    /// until they are included in a block.
+    pub fn set_mempool_cap(&mut self, mempool_cap: usize) {
+        self.mempool.set_cap(mempool_cap);
+    }
+
+    /// Report the given directives, with which the log output is filtered, in the status of this node.
+    pub fn set_log_filters(&mut self, log_filters: Vec<String>) {
+        self.log_filters = log_filters;
+    }
+
+    /// Take note of the amount of milliseconds the clocks of the peers are ahead of the local one.
+    pub fn set_clock_offset(&mut self, clock_offset: i64) {
+        self.clock_offset = Some(clock_offset);
+    }
+
+    /// Answer metrics requests with the metrics collected by the given registry.
+    pub fn set_metrics_registry(&mut self, metrics_registry: Arc<MetricsRegistry>) {
+        self.metrics_registry = Some(metrics_registry);
+    }
+
+    /// Answer peer status requests with the connectivity tracked by the given tracker.
+    pub fn set_peer_health(&mut self, peer_health: Arc<PeerHealthTracker>) {
+        self.peer_health = Some(peer_health);
+    }
+
+    /// Call the given listener with the previous and the new head whenever the head of the canonical
+    /// chain changes, be it by a block extending the chain, by a block of another branch outweighing it,
+    /// or by replacing the chain with the copy of a peer. A change is a reorganization, if the previous
+    /// head is not an ancestor of the new one, see `is_reorganization`.
+    ///
+    /// Listeners are called while the protocol is locked, hence they must not lock it themselves
+    /// and should hand off any lengthy work, e.g. over a channel.
+    pub fn on_head_changed<F>(&mut self, listener: F)
+        where
+            F: Fn(&HeadInfo, &HeadInfo) + Send + 'static
+    {
+        self.head_listeners.push(Box::new(listener));
+    }
+
+    /// Returns true, if the given previous head is not part of the canonical chain anymore,
+    /// i.e. the blocks on top of the common ancestor were reverted.
+    pub fn is_reorganization(&self, old_head: &HeadInfo) -> bool {
+        match self.chain.block_by_number(old_head.height) {
+            Ok(Some(block)) => block.identifier != old_head.block.identifier,
+            Ok(None) | Err(_) => true
+        }
+    }
+
+    /// Call the given listener with the identifier and the new receipt of a transaction whenever
+    /// its block is reverted by a reorganization, see `get_receipt_status`, and once it is included again.
+    ///
+    /// As for `on_head_changed`, listeners are called while the protocol is locked.
+    pub fn on_receipt_changed<F>(&mut self, listener: F)
+        where
+            F: Fn(&str, &ReceiptStatus) + Send + 'static
+    {
+        self.receipt_listeners.push(Box::new(listener));
+    }
+
+    /// Returns the state of the given transaction: whether it is included in the canonical chain,
+    /// waits in the buffer to be included for the first time or again after its block was reverted,
+    /// or was dropped after its block was reverted.
+    pub fn get_receipt_status(&self, transaction_identifier: &str) -> ReceiptStatus {
+        if let Some(inclusion) = self.find_transaction_inclusion(transaction_identifier) {
+            return ReceiptStatus::Included(inclusion);
+        }
+
+        match self.reverted_transactions.get(transaction_identifier) {
+            Some(Reversion::Reinserted) => ReceiptStatus::PendingReinsertion,
+            Some(Reversion::Dropped(reason)) => ReceiptStatus::Dropped(reason.clone()),
+            None if self.mempool.contains_identifier(transaction_identifier) => ReceiptStatus::Pending,
+            None => ReceiptStatus::Unknown
+        }
+    }
+
+    /// Notify the head listeners, if the head differs from the given previous one.
+    /// On a reorganization, the transactions of the reverted blocks are added to the buffer again.
+    ///
+    /// - `old_head` The head before the change.
+    /// - `old_chain` The chain before the change, if it was replaced. None, if the own chain was extended.
+    fn notify_head_change(&mut self, old_head: Option<HeadInfo>, old_chain: Option<&Chain>) {
+        let (old_head, new_head) = match (old_head, self.get_head()) {
+            (Some(old_head), Some(new_head)) => (old_head, new_head),
+            _ => {
+                return;
+            }
+        };
+        if old_head.block.identifier == new_head.block.identifier {
+            return;
+        }
+
+        if new_head.block.data.parent != old_head.block.identifier && self.is_reorganization(&old_head) {
+            info!("Reorganized the chain from head {} at height {} to head {} at height {}", old_head.block.identifier, old_head.height, new_head.block.identifier, new_head.height);
+            let reverted_transactions = self.find_reverted_transactions(old_chain.unwrap_or(&self.chain), &old_head, &new_head);
+            self.reinsert_transactions(reverted_transactions);
+        }
+
+        for listener in self.head_listeners.iter() {
+            listener(&old_head, &new_head);
+        }
+
+        for transaction_identifier in self.reverted_transactions.get_reinserted() {
+            if let Some(inclusion) = self.find_transaction_inclusion(&transaction_identifier) {
+                self.reverted_transactions.remove(&transaction_identifier);
+                self.notify_receipt_change(&transaction_identifier, &ReceiptStatus::Included(inclusion));
+            }
+        }
+    }
+
+    /// Returns the transactions of the blocks which are part of the canonical chain of `old_chain`
+    /// ending in the previous head, but not of the own canonical chain ending in the new head.
+    fn find_reverted_transactions(&self, old_chain: &Chain, old_head: &HeadInfo, new_head: &HeadInfo) -> Vec<Transaction> {
+        let (mut old_identifier, mut old_height) = (old_head.block.identifier.clone(), old_head.height);
+        let (mut new_identifier, mut new_height) = (new_head.block.identifier.clone(), new_head.height);
+        let mut reverted_transactions = vec![];
+
+        // walk both branches down to their common ancestor
+        while old_identifier != new_identifier {
+            if new_height > old_height {
+                match self.chain.blocks.get(&new_identifier) {
+                    Some(block) => new_identifier = block.data.parent.clone(),
+                    None => break
+                }
+                new_height -= 1;
+            } else {
+                if Height::GENESIS == old_height {
+                    break;
+                }
+                match old_chain.blocks.get(&old_identifier) {
+                    Some(block) => {
+                        reverted_transactions.extend(block.data.transactions.iter().cloned());
+                        old_identifier = block.data.parent.clone();
+                    }
+                    None => break
+                }
+                old_height -= 1;
+            }
+        }
+
+        reverted_transactions
+    }
+
+    /// Add the given transactions of reverted blocks to the buffer again, unless the new branch includes them.
+    fn reinsert_transactions(&mut self, transactions: Vec<Transaction>) {
+        for transaction in transactions {
+            let transaction_identifier = transaction.identifier.clone();
+            let receipt_status = match self.on_transaction_receive(transaction) {
+                Ok(Some(_)) => continue,
+                Ok(None) => {
+                    self.reverted_transactions.track(&transaction_identifier, Reversion::Reinserted);
+                    ReceiptStatus::PendingReinsertion
+                }
+                Err(reason) => {
+                    warn!("Dropping transaction {} of a reverted block: {}", transaction_identifier, reason);
+                    self.reverted_transactions.track(&transaction_identifier, Reversion::Dropped(reason.clone()));
+                    ReceiptStatus::Dropped(reason)
+                }
+            };
+
+            self.notify_receipt_change(&transaction_identifier, &receipt_status);
+        }
+    }
+
+    fn notify_receipt_change(&self, transaction_identifier: &str, receipt_status: &ReceiptStatus) {
+        for listener in self.receipt_listeners.iter() {
+            listener(transaction_identifier, receipt_status);
+        }
+    }
+
+    /// Returns the metrics of the thread pools of this node, or none if no metrics are collected.
+    pub fn get_metrics(&self) -> Metrics {
+        match self.metrics_registry {
+            Some(ref metrics_registry) => metrics_registry.snapshot(),
+            None => Metrics { thread_pools: vec![], pruning: PruningMetrics::default(), rate_limits: vec![] }
+        }
+    }
+
+    /// Returns the connectivity to each other sealer authorized to seal the next block,
+    /// or none if the connectivity is not tracked.
+    pub fn get_peer_status(&self) -> Vec<PeerHealth> {
+        match self.peer_health {
+            Some(ref peer_health) => {
+                let sealers: Vec<SocketAddr> = self.get_sealer_set().get_sealers().iter()
+                    .filter(|sealer| !peers::is_same_peer(sealer, &self.own_address))
+                    .cloned()
+                    .collect();
+                peer_health.get_status(&sealers)
+            }
+            None => vec![]
+        }
+    }
+
+    /// Returns false, if the local clock deviates from the clocks of the peers by more than
+    /// the drift tolerated for the timestamps of blocks, so that blocks sealed by this node
+    /// would be rejected or would distort the block period. True, if the clock was not checked yet.
+    pub fn is_clock_sane(&self) -> bool {
+        match self.clock_offset {
+            Some(clock_offset) => clock_offset.unsigned_abs() <= MAX_TIMESTAMP_DRIFT * 1000,
+            None => true
+        }
+    }
+
+    /// Returns the summaries of the pending transactions, ordered by their time of arrival.
+    /// Their payloads are only included if requested, see `MempoolEntry`.
+    pub fn get_mempool(&self, include_payloads: bool) -> Vec<MempoolEntry> {
+        mempool::inspect(self.mempool.iter(), include_payloads)
+    }
+
+    /// Returns the amount of bytes used by pending transactions.
+    pub fn get_mempool_size(&self) -> usize {
+        self.mempool.get_size()
+    }
+
+    /// Persist the chain in the given store from now on.
+    ///
+    /// If the store already holds a chain, it replaces the own one after each of its blocks passed
+    /// the same validation as a block received from another node, unless `refuse_stored_chain` is set,
+    /// e.g. as the node should start fresh. Otherwise, the own chain is stored. Returns whether a stored
+    /// chain was restored, or an error if the stored chain cannot be loaded, is refused, is invalid
+    /// or belongs to a different genesis configuration.
+    pub fn set_block_store(&mut self, mut block_store: Box<BlockStore>, refuse_stored_chain: bool) -> Result<bool, String> {
+        let stored_chain = match block_store.load() {
+            Ok(stored_chain) => stored_chain,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+
+        let is_restored = match stored_chain {
+            // a store holding just the genesis block is as good as an empty one
+            Some(ref chain) if refuse_stored_chain && chain.blocks.len() > 1 => {
+                return Err(format!("Block store already holds a chain of {} blocks", chain.blocks.len()));
+            }
+            Some(mut chain) => {
+                if !chain.genesis_configuration_hash.eq(&self.chain.genesis_configuration_hash) {
+                    return Err(format!("Stored chain belongs to genesis configuration {} instead of {}", chain.genesis_configuration_hash, self.chain.genesis_configuration_hash));
+                }
+                if let Err(e) = chain.set_sealer_set(Some(self.genesis.get_sealer_set())) {
+                    return Err(format!("Stored chain is inconsistent: {:?}", e));
+                }
+
+                // the store may have been altered while the node was not running
+                for block in chain.get_ordered_blocks().iter().filter(|block| block.identifier != chain.genesis_identifier_hash) {
+                    if let Err(rejection) = self.block_validator.validate(&chain, block) {
+                        return Err(format!("Stored block {} is invalid: {}", block.identifier, rejection));
+                    }
+                }
+
+                info!("Restored chain of {} blocks from the block store", chain.blocks.len());
+                self.chain = chain;
+                self.purge_stale_transactions();
+                true
+            }
+            None => {
+                match block_store.replace(&self.chain) {
+                    Ok(()) => {}
+                    Err(e) => {
+                        return Err(e);
+                    }
+                }
+                false
+            }
+        };
+
+        self.block_store = Some(block_store);
+
+        Ok(is_restored)
+    }
+
+    /// Persist the given block, which was just added to the chain.
+    fn store_block(&mut self, block: &Block) {
+        if let Some(ref mut block_store) = self.block_store {
+            match block_store.append(block) {
+                Ok(()) => {}
+                Err(e) => {
+                    error!("{}", e);
+                }
+            }
+        }
+    }
+
+    /// Log blocks and buffered transactions in the given write-ahead log from now on,
+    /// after replaying the entries it already holds. Must be called after `set_block_store`,
+    /// as logged blocks are dropped from the log once they are stored.
+    ///
+    /// Returns an error if the logged entries cannot be read.
+    pub fn set_write_ahead_log(&mut self, write_ahead_log: WriteAheadLog) -> Result<(), String> {
+        let entries = match write_ahead_log.replay() {
+            Ok(entries) => entries,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+
+        let mut amount_replayed_blocks = 0;
+        for entry in entries {
+            match entry {
+                WalEntry::Block(block) => {
+                    if self.chain.blocks.contains_key(&block.identifier) {
+                        continue;
+                    }
+
+                    // the log may have been altered while the node was not running
+                    if let Err(rejection) = self.block_validator.validate(&self.chain, &block) {
+                        warn!("Not replaying logged block {:?} as it is invalid: {}", block.identifier, rejection);
+                        continue;
+                    }
+
+                    if self.chain.add_block(block.clone()) {
+                        self.store_block(&block);
+                        amount_replayed_blocks += 1;
+                    }
+                }
+                WalEntry::Transaction { transaction, arrived_at } => {
+                    if let Err(e) = self.mempool.insert(PendingTransaction::arrived_at(*transaction, arrived_at)) {
+                        warn!("Failed to replay buffered transaction: {}", e);
+                    }
+                }
+            }
+        }
+
+        // the transactions may have been included in a block before the crash
+        self.remove_canonical_transactions();
+        self.purge_stale_transactions();
+
+        info!("Replayed {} blocks and {} buffered transactions from the write-ahead log", amount_replayed_blocks, self.mempool.len());
+
+        self.write_ahead_log = Some(write_ahead_log);
+        self.compact_write_ahead_log();
+
+        Ok(())
+    }
+
+    /// Log the given entry, which is about to be applied.
+    fn log(&mut self, entry: &WalEntry) {
+        if let Some(ref mut write_ahead_log) = self.write_ahead_log {
+            match write_ahead_log.append(entry) {
+                Ok(()) => {}
+                Err(e) => {
+                    error!("{}", e);
+                }
+            }
         }
     }
 
+    /// Drop the logged blocks, which are stored in the block store by now,
+    /// along with the logged transactions which are no longer buffered.
+    fn compact_write_ahead_log(&mut self) {
+        if let Some(ref mut write_ahead_log) = self.write_ahead_log {
+            let entries: Vec<WalEntry> = self.mempool.iter()
+                .map(|pending| WalEntry::Transaction { transaction: Box::new(pending.transaction.clone()), arrived_at: pending.arrived_at })
+                .collect();
+
+            match write_ahead_log.compact(&entries) {
+                Ok(()) => {}
+                Err(e) => {
+                    error!("{}", e);
+                }
+            }
+        }
+    }
+
+    /// Close the block store, after which blocks are no longer persisted.
+    /// Called last when the node shuts down.
+    pub fn close_block_store(&mut self) {
+        if self.block_store.take().is_some() {
+            info!("Closed the block store");
+        }
+    }
+
+    /// Returns the transactions waiting to be included in a block.
+    pub fn get_pending_transactions(&self) -> Vec<Transaction> {
+        self.mempool.iter().map(|pending| pending.transaction.clone()).collect()
+    }
+
+    /// Returns the identifiers of the pending transactions to announce to the sealers.
+    pub fn get_mempool_announcement(&self) -> Vec<String> {
+        self.mempool.get_announcement()
+    }
+
+    /// Returns the pending transactions with the given identifiers,
+    /// in batches of at most `MAX_CHUNK_SIZE` bytes unless a batch consists of a single transaction.
+    pub fn get_pending_transaction_batches(&self, transaction_identifiers: &[String]) -> Vec<Vec<Transaction>> {
+        self.mempool.get_batches(transaction_identifiers, MAX_CHUNK_SIZE)
+    }
+
+    /// Returns those of the given announced identifiers whose transactions
+    /// are neither pending nor included in the canonical chain.
+    fn get_unknown_transactions(&self, transaction_identifiers: Vec<String>) -> Vec<String> {
+        transaction_identifiers.into_iter()
+            .filter(|identifier| !self.mempool.contains_identifier(identifier) && self.find_transaction_inclusion(identifier).is_none())
+            .collect()
+    }
+
+    /// Handle the pending transactions gossiped by another node.
+    /// Returns the amount of transactions added to the mempool.
+    fn on_pending_transactions_receive(&mut self, transactions: Vec<Transaction>) -> usize {
+        let mut amount_added = 0;
+        for transaction in transactions {
+            let transaction_identifier = transaction.identifier.clone();
+            let was_pending = self.mempool.contains_identifier(&transaction_identifier);
+            match self.on_transaction_receive(transaction) {
+                Ok(None) if !was_pending => amount_added += 1,
+                Ok(_) => {}
+                Err(reason) => {
+                    debug!("Rejected gossiped transaction {:?}: {}", transaction_identifier, reason);
+                }
+            }
+        }
+
+        amount_added
+    }
+
     /// Replace the own block chain with the given instance, if the given instance
-    /// has a branch with a greater height than our longest branch.
-    pub fn replace_chain(&mut self, chain: Chain) {
-        let own_chain_height = self.chain.get_current_block_number();
-        let other_chain_height = chain.get_current_block_number();
+    /// has a branch with a greater total difficulty than our heaviest branch.
+    /// A chain starting with a state snapshot is only accepted if the own chain starts with the same one.
+    pub fn replace_chain(&mut self, mut chain: Chain) {
+        // snapshots are only adopted once the majority of the peers agrees on them
+        if chain.get_state_snapshot().is_some() && chain.get_state_snapshot() != self.chain.get_state_snapshot() {
+            warn!("Not replacing chain as it starts with a different state snapshot");
+            return;
+        }
+
+        // the subtree depths and difficulties of chains received from other nodes are not known yet
+        match chain.set_sealer_set(Some(self.genesis.get_sealer_set())) {
+            Ok(()) => {}
+            Err(e) => {
+                warn!("Not replacing chain as it is inconsistent: {:?}", e);
+                return;
+            }
+        }
+
+        let (own_chain_height, own_total_difficulty) = match self.get_head() {
+            Some(head) => (head.height, head.total_difficulty),
+            None => (Height::GENESIS, 0)
+        };
+
+        let (other_chain_height, other_total_difficulty) = match chain.head() {
+            Ok(head) => (head.height, head.total_difficulty),
+            Err(e) => {
+                warn!("Not replacing chain as it is inconsistent: {:?}", e);
+                return;
+            }
+        };
 
         if !chain.genesis_configuration_hash.eq(&self.chain.genesis_configuration_hash) {
             warn!("Not replacing chain {:?} as its genesis configuration does not match ours.", chain.clone());
             return;
         }
 
-        trace!("My height: {}, other height: {}", own_chain_height, other_chain_height);
+        // each block must satisfy the same rules as a block sent on its own, e.g. match its identifier,
+        // be signed by a sealer in turn and carry valid transactions. Blocks known already were validated when they were added
+        for block in chain.get_ordered_blocks().iter().filter(|block| block.identifier != chain.genesis_identifier_hash) {
+            if self.chain.blocks.contains_key(&block.identifier) {
+                continue;
+            }
+
+            if let Err(rejection) = self.block_validator.validate(&chain, block) {
+                warn!("Not replacing chain as its block {:?} is invalid: {}", block.identifier, rejection);
+                return;
+            }
+        }
+
+        // blocks up to the own finalized head are irreversible
+        let (finalized_height, finalized_identifier) = match self.chain.finalized_head() {
+            Ok((height, block)) => (height, block.identifier.clone()),
+            Err(e) => {
+                warn!("Not replacing chain as the own finalized head cannot be determined: {:?}", e);
+                return;
+            }
+        };
+        match chain.block_by_number(finalized_height) {
+            Ok(Some(block)) if block.identifier == finalized_identifier => {}
+            _ => {
+                warn!("Not replacing chain as it does not contain the finalized block {:?} at height {}", finalized_identifier, finalized_height);
+                return;
+            }
+        }
+
+        trace!("My height: {}, other height: {}, my total difficulty: {}, other total difficulty: {}", own_chain_height, other_chain_height, own_total_difficulty, other_total_difficulty);
 
-        if own_chain_height < other_chain_height {
-            debug!("Replacing own chain of length {:?} with remote chain of length {:?}", own_chain_height, other_chain_height);
-            self.chain = chain;
+        if own_total_difficulty < other_total_difficulty {
+            debug!("Replacing own chain of length {:?} and total difficulty {} with remote chain of length {:?} and total difficulty {}", own_chain_height, own_total_difficulty, other_chain_height, other_total_difficulty);
+            let old_head = self.get_head();
+            let old_chain = mem::replace(&mut self.chain, chain);
+            self.remove_canonical_transactions();
+            self.purge_stale_transactions();
+
+            if let Some(ref mut block_store) = self.block_store {
+                match block_store.replace(&self.chain) {
+                    Ok(()) => {}
+                    Err(e) => {
+                        error!("{}", e);
+                    }
+                }
+            }
+            self.compact_write_ahead_log();
+            self.notify_head_change(old_head, Some(&old_chain));
         }
     }
 
-    /// Returns true, if the node is a leader in the current
-    /// epoch and therefore allowed to sign blocks.
-    pub fn is_leader(&self) -> bool {
-        let current_block_number = self.chain.get_current_block_number();
-        let expected_leader_index = current_block_number % self.signer_count;
-        let am_i_leader = self.signer_index == expected_leader_index;
+    /// Returns the head of the own chain, or None if it cannot be determined.
+    pub fn get_head(&self) -> Option<HeadInfo> {
+        match self.chain.head() {
+            Ok(head) => Some(head),
+            Err(e) => {
+                error!("Failed to determine the head of the own chain: {:?}", e);
+                None
+            }
+        }
+    }
 
-        trace!("Current block number is {}, expected leader is {}. Am I the leader? {}", current_block_number, expected_leader_index, am_i_leader);
+    /// Returns a key identifying the current state of the chain.
+    /// See `Chain::get_snapshot_key`.
+    pub fn get_chain_snapshot_key(&self) -> (usize, BlockHash) {
+        self.chain.get_snapshot_key()
+    }
 
-        am_i_leader
+    /// Returns the hash of the genesis configuration of the chain.
+    pub fn get_genesis_configuration_hash(&self) -> String {
+        self.chain.genesis_configuration_hash.clone()
     }
 
-    /// Returns true, if the node is a co-leader in the current
-    /// epoch and therefore allowed to sign a blocks after waiting for
-    /// a particular wiggle time.
-    pub fn is_co_leader(&self) -> bool {
-        let current_block_number = self.chain.get_current_block_number();
+    /// Returns all blocks of the chain along with their height. See `Chain::get_blocks_by_height`.
+    pub fn get_blocks_by_height(&self) -> Vec<(Height, Block)> {
+        self.chain.get_blocks_by_height()
+    }
+
+    /// Returns the blocks of the canonical chain above the given height which are
+    /// followed by at least the given amount of blocks, from the oldest to the newest.
+    pub fn get_finalized_blocks(&self, confirmations: usize, after_height: Height) -> Vec<(Height, Block)> {
+        let mut finalized_blocks_visitor = FinalizedBlocksVisitor::new(confirmations, after_height);
+        let longest_path_walker = LongestPathWalker::new();
+
+        longest_path_walker.walk_chain(&self.chain, &mut finalized_blocks_visitor);
+
+        finalized_blocks_visitor.get_finalized_blocks()
+    }
+
+    /// Returns the height of the finalized head of the canonical chain. See `Chain::finalized_head`.
+    pub fn get_finalized_height(&self) -> Height {
+        match self.chain.finalized_head() {
+            Ok((height, _)) => height,
+            Err(e) => {
+                error!("Failed to determine the finalized head of the own chain: {:?}", e);
+                Height::GENESIS
+            }
+        }
+    }
+
+    /// Returns the block of the canonical chain which is followed by exactly the given amount
+    /// of blocks along with its height, or None if the canonical chain is not as long.
+    pub fn get_finalized_head(&self, confirmations: usize) -> Option<(Height, Block)> {
+        let head = match self.get_head() {
+            Some(head) => head,
+            None => {
+                return None;
+            }
+        };
+        if head.height.as_usize() < confirmations {
+            return None;
+        }
+
+        let height = head.height.saturating_sub(confirmations);
+        self.get_block_by_number(height).map(|block| (height, block))
+    }
+
+    /// Returns the chunk of the chain following the given token,
+    /// or the first chunk if the token is None.
+    fn get_chain_chunk(&self, token: Option<ChainChunkToken>) -> ChainChunk {
+        ChainChunk::new(self.get_genesis_configuration_hash(), &self.get_blocks_by_height(), token.as_ref())
+    }
+
+    /// Returns the blocks of the canonical chain from `from_height` up to and including `to_height`.
+    /// See `BlockRange::new`.
+    pub fn get_block_range(&self, from_height: Height, to_height: Height) -> BlockRange {
+        BlockRange::new(self.get_genesis_configuration_hash(), self.chain.root_height(), &self.collect_canonical_blocks(), from_height, to_height)
+    }
+
+    /// Returns the blocks of the canonical chain ordered by their height, starting at the root of the chain.
+    pub fn get_canonical_blocks(&self) -> Vec<Block> {
+        self.collect_canonical_blocks().into_iter().cloned().collect()
+    }
+
+    fn collect_canonical_blocks(&self) -> Vec<&Block> {
+        let mut canonical_path = match self.chain.get_canonical_path() {
+            Ok(canonical_path) => canonical_path,
+            Err(e) => {
+                error!("Failed to determine the canonical chain: {:?}", e);
+                vec![]
+            }
+        };
+        // the path starts at the head, but blocks are returned starting with the lowest one
+        canonical_path.reverse();
+
+        canonical_path.iter()
+            .map_while(|block_hash| self.chain.blocks.get(block_hash))
+            .collect()
+    }
+
+    /// Returns the block of the canonical chain at the given height, if any.
+    /// See `Chain::block_by_number`.
+    pub fn get_block_by_number(&self, height: Height) -> Option<Block> {
+        match self.chain.block_by_number(height) {
+            Ok(block) => block.cloned(),
+            Err(e) => {
+                error!("Failed to determine the canonical chain: {:?}", e);
+                None
+            }
+        }
+    }
+
+    /// Returns the height of the first block of the own chain, i.e. zero unless it starts with a state snapshot.
+    pub fn get_root_height(&self) -> Height {
+        self.chain.root_height()
+    }
+
+    /// Returns a summary of the canonical chain up to the finalized block at the given height,
+    /// or up to the finalized head if None. All nodes return the same snapshot for the same block.
+    ///
+    /// Returns None if the block is not finalized, is the genesis block or lies below the root of the own chain.
+    pub fn get_state_snapshot(&self, height: Option<Height>) -> Option<StateSnapshot> {
+        let (finalized_height, finalized_block) = match self.chain.finalized_head() {
+            Ok(finalized_head) => finalized_head,
+            Err(e) => {
+                error!("Failed to determine the finalized head of the own chain: {:?}", e);
+                return None;
+            }
+        };
+
+        let (height, block) = match height {
+            None => (finalized_height, finalized_block.clone()),
+            Some(height) if height <= finalized_height => match self.get_block_by_number(height) {
+                Some(block) => (height, block),
+                None => {
+                    return None;
+                }
+            },
+            Some(_) => {
+                return None;
+            }
+        };
+        if Height::GENESIS == height {
+            return None;
+        }
+
+        let (total_difficulty, sealer_set) = match (self.chain.total_difficulty_of(&block.identifier), self.chain.get_sealer_set(&block.identifier)) {
+            (Some(total_difficulty), Ok(Some(sealer_set))) => (total_difficulty, sealer_set),
+            _ => {
+                warn!("Not taking a snapshot at block {:?} as its total difficulty or sealers are not known", block.identifier);
+                return None;
+            }
+        };
+
+        let mut state_snapshot_visitor = StateSnapshotVisitor::new(self.genesis.get_revote_policy());
+        let longest_path_walker = LongestPathWalker::new();
+        longest_path_walker.walk_from(&self.chain, height, &block, &mut state_snapshot_visitor);
+
+        Some(state_snapshot_visitor.into_state_snapshot(self.get_genesis_configuration_hash(), height, total_difficulty, block, &sealer_set))
+    }
+
+    /// Replace the own chain by a chain starting with the given snapshot, e.g. one on which
+    /// the majority of the peers agrees, so that only the blocks above it must be fetched.
+    ///
+    /// Returns an error if the snapshot belongs to a different genesis configuration, is
+    /// malformed, or if the own chain already reaches the height of the snapshot.
+    pub fn adopt_state_snapshot(&mut self, state_snapshot: StateSnapshot) -> Result<(), String> {
+        if !state_snapshot.genesis_configuration_hash.eq(&self.chain.genesis_configuration_hash) {
+            return Err(format!("Snapshot belongs to genesis configuration {} instead of {}", state_snapshot.genesis_configuration_hash, self.chain.genesis_configuration_hash));
+        }
+        if state_snapshot.sealers.is_empty() {
+            return Err(format!("Snapshot at block {:?} does not contain any sealers", state_snapshot.block.identifier));
+        }
+        for (question_id, _) in state_snapshot.get_question_snapshots() {
+            if let Err(e) = state_snapshot.get_question_voters(&question_id) {
+                return Err(format!("Snapshot at block {:?} is malformed: {}", state_snapshot.block.identifier, e));
+            }
+        }
+
+        let old_head = self.get_head();
+        if let Some(ref old_head) = old_head {
+            if old_head.height >= state_snapshot.height {
+                return Err(format!("Own chain already reaches height {}, not adopting snapshot at height {}", old_head.height, state_snapshot.height));
+            }
+        }
+
+        let (height, block_hash) = (state_snapshot.height, state_snapshot.block.identifier.clone());
+        let mut chain = Chain::from_state_snapshot(state_snapshot);
+        if let Err(e) = chain.set_sealer_set(Some(self.genesis.get_sealer_set())) {
+            return Err(format!("Failed to index the chain starting with the snapshot: {:?}", e));
+        }
+
+        info!("Adopting snapshot at block {:?} at height {}", block_hash, height);
+        let old_chain = mem::replace(&mut self.chain, chain);
+        self.purge_stale_transactions();
+
+        if let Some(ref mut block_store) = self.block_store {
+            match block_store.replace(&self.chain) {
+                Ok(()) => {}
+                Err(e) => {
+                    error!("{}", e);
+                }
+            }
+        }
+        self.compact_write_ahead_log();
+        self.notify_head_change(old_head, Some(&old_chain));
+
+        Ok(())
+    }
+
+    /// Returns the blocks at the given height on all branches. See `Chain::blocks_at_height`.
+    pub fn get_blocks_at_height(&self, height: Height) -> Vec<Block> {
+        match self.chain.blocks_at_height(height) {
+            Ok(blocks) => blocks.into_iter().cloned().collect(),
+            Err(e) => {
+                error!("Failed to determine the blocks at height {}: {:?}", height, e);
+                vec![]
+            }
+        }
+    }
+
+    /// Returns true, if the parent of the given block is contained in the own chain.
+    pub fn has_parent_of_block(&self, block: &Block) -> bool {
+        self.chain.has_parent_of_block(block)
+    }
+
+    /// Add a block received from another node, whose integrity and transactions were
+    /// verified already, e.g. by the block pipeline. Only the rules depending on the chain are checked.
+    ///
+    /// Returns the response to the node which sent the block.
+    pub fn add_verified_block(&mut self, block: Block) -> Message {
+        if self.chain.blocks.contains_key(&block.identifier) {
+            return Message::BlockDuplicated;
+        }
+
+        if let Err(rejection) = self.block_validator.validate_against_chain(&self.chain, &block) {
+            warn!("Rejecting block {:?}: {}", block.identifier, rejection);
+            return Message::BlockReject(block.identifier, rejection.to_string());
+        }
+
+        self.insert_block(block)
+    }
+
+    /// Add the given, validated block to the chain and remove its transactions from the buffer.
+    fn insert_block(&mut self, block: Block) -> Message {
+        // the transactions of the block must not be included again by the next leader
+        self.mempool.remove_included(&block.data.transactions);
+
+        self.log(&WalEntry::Block(block.clone()));
+        let old_head = self.get_head();
+        let is_added = self.chain.add_block(block.clone());
+
+        if is_added {
+            self.store_block(&block);
+            self.purge_stale_transactions();
+            self.compact_write_ahead_log();
+            self.notify_head_change(old_head, None);
+
+            return Message::BlockAccept;
+        }
+
+        Message::BlockDuplicated
+    }
+
+    /// Add the blocks of a range received from another peer, each preceded by its parent,
+    /// as if they were sent to this node one by one.
+    ///
+    /// Returns the amount of blocks which were not known before, or an error
+    /// if a block does not extend the own chain.
+    pub fn add_block_range(&mut self, blocks: Vec<Block>) -> Result<usize, String> {
+        let mut amount_added_blocks = 0;
+
+        for block in blocks {
+            if self.chain.blocks.contains_key(&block.identifier) {
+                continue;
+            }
+
+            if !self.chain.has_parent_of_block(&block) {
+                return Err(format!("Parent of block {} is unknown", block.identifier));
+            }
+
+            if Message::BlockAccept == self.handle(Message::BlockPayload(block)) {
+                amount_added_blocks += 1;
+            }
+        }
+
+        Ok(amount_added_blocks)
+    }
+
+    /// Returns the sealers authorized to seal the next block on the canonical chain,
+    /// or the sealers of the genesis configuration if the head cannot be determined.
+    pub fn get_sealer_set(&self) -> Arc<SealerSet> {
+        let sealer_set = self.get_head().and_then(|head| {
+            match self.chain.get_sealer_set(&head.block.identifier) {
+                Ok(sealer_set) => sealer_set,
+                Err(e) => {
+                    error!("Failed to determine the sealers of the own chain: {:?}", e);
+                    None
+                }
+            }
+        });
+
+        sealer_set.unwrap_or_else(|| Arc::new(self.genesis.get_sealer_set()))
+    }
+
+    /// Returns the schedule mapping heights to epochs and their leaders among the authorized sealers.
+    pub fn get_epoch_schedule(&self) -> EpochSchedule {
+        self.get_sealer_set().get_epoch_schedule()
+    }
+
+    /// Returns true, if the node is the leader of the next block
+    /// in the current epoch and therefore allowed to sign it.
+    pub fn is_leader(&self) -> bool {
+        let current_block_number = match self.get_head() {
+            Some(head) => head.height,
+            None => {
+                return false;
+            }
+        };
+        let sealer_set = self.get_sealer_set();
+        let own_signer_index = match sealer_set.index_of(&self.own_address) {
+            Some(own_signer_index) => own_signer_index,
+            None => {
+                return false;
+            }
+        };
+        let expected_leader_index = sealer_set.get_epoch_schedule().get_leader_index(current_block_number + 1).unwrap();
+        let am_i_leader = own_signer_index == expected_leader_index;
+
+        trace!("Current block number is {}, expected leader is {}. Am I the leader? {}", current_block_number, expected_leader_index, am_i_leader);
+
+        am_i_leader
+    }
+
+    /// Returns true, if the node is a co-leader of the next block in the
+    /// current epoch and therefore allowed to sign it after waiting for
+    /// a particular wiggle time.
+    pub fn is_co_leader(&self) -> bool {
+        let current_block_number = match self.get_head() {
+            Some(head) => head.height,
+            None => {
+                return false;
+            }
+        };
+
+        let sealer_set = self.get_sealer_set();
+        let own_signer_index = match sealer_set.index_of(&self.own_address) {
+            Some(own_signer_index) => own_signer_index,
+            None => {
+                return false;
+            }
+        };
+
+        // the co-leaders follow the leader in the order of the sealers
+        let offset_to_leader = sealer_set.get_offset_to_leader(own_signer_index, current_block_number + 1);
+
+        let am_i_co_leader = offset_to_leader >= 1 && offset_to_leader <= self.genesis.clique.signer_limit;
+
+        trace!("Current block number is {}, my offset to its leader is {}. Am I co-leader? {}", current_block_number, offset_to_leader, am_i_co_leader);
+
+        am_i_co_leader
+    }
+
+    /// Handle a received transaction.
+    /// Returns where the transaction is included, if it is already part of the canonical chain,
+    /// or an error describing the reason, if the transaction is rejected.
+    ///
+    /// Receiving a transaction again yields the same result as receiving it the first time,
+    /// or its inclusion once it was included, so that clients may safely retry submissions.
+    fn on_transaction_receive(&mut self, transaction: Transaction) -> Result<Option<TransactionInclusion>, String> {
+        if let Some(inclusion) = self.find_transaction_inclusion(&transaction.identifier) {
+            trace!("Transaction {:?} is already included in block {:?}. Not adding to chain", transaction.identifier.clone(), inclusion.block_identifier.clone());
+            return Ok(Some(inclusion));
+        }
+
+        // the transaction is included in the block following the head at the earliest
+        let next_height = match self.chain.head() {
+            Ok(head) => head.height + 1,
+            Err(e) => {
+                error!("Failed to determine the head of the chain: {:?}", e);
+                return Err(INVALID_TRANSACTION_REASON.to_string());
+            }
+        };
+        if !self.block_validator.is_expected_hasher(&transaction.identifier, next_height) {
+            warn!("Rejecting transaction {:?} not identified by the hash function of the genesis configuration.", transaction.identifier.clone());
+            return Err(format!("Transactions must be identified by {}", self.genesis.clique.get_hasher().name()));
+        }
+
+        if TransactionType::SealerVote == transaction.trx_type {
+            warn!("Rejecting sealer vote {:?} not proposed to this node.", transaction.identifier.clone());
+            return Err("Sealer votes are only cast by proposing them to the voting sealer".to_string());
+        }
+
+        if TransactionType::DkgCommitment == transaction.trx_type {
+            warn!("Rejecting commitments {:?} of the key generation not published by this node.", transaction.identifier.clone());
+            return Err("Commitments of the key generation are only published by their dealer".to_string());
+        }
+
+        if let Err(reason) = self.check_election_phase(&transaction) {
+            warn!("Rejecting transaction {:?}: {}", transaction.identifier.clone(), reason);
+            return Err(reason);
+        }
+
+        // checked before the proofs, whose verification grows with the size of their numbers
+        if let Err(violation) = self.genesis.get_transaction_limits().check(&transaction) {
+            warn!("Transaction {:?} is oversized: {}", transaction.identifier.clone(), violation);
+            return Err(violation.to_string());
+        }
+
+        if self.mempool.contains(&transaction) {
+            trace!("Transaction {:?} is already contained. Not adding to chain", transaction.identifier.clone());
+            return Ok(None);
+        }
+
+        if let Err(reason) = self.check_first_vote(&transaction) {
+            warn!("Rejecting vote {:?}: {}", transaction.identifier.clone(), reason);
+            return Err(reason);
+        }
+
+        if let Err(reason) = self.check_voter_registry(&transaction) {
+            warn!("Rejecting transaction {:?}: {}", transaction.identifier.clone(), reason);
+            return Err(reason);
+        }
+
+        if let Err(reason) = self.check_decryption_share(&transaction) {
+            warn!("Rejecting decryption share {:?}: {}", transaction.identifier.clone(), reason);
+            return Err(reason);
+        }
+
+        if let Err(reason) = self.check_key_generation(&transaction) {
+            warn!("Rejecting transaction {:?}: {}", transaction.identifier.clone(), reason);
+            return Err(reason);
+        }
+
+        if let Err(reason) = self.check_final_tally(&transaction) {
+            warn!("Rejecting final tally {:?}: {}", transaction.identifier.clone(), reason);
+            return Err(reason);
+        }
+
+        let (public_uciv, questions) = self.get_voter_image_sets();
+        if !transaction.is_valid(self.get_election_public_key(), public_uciv, &questions, &self.genesis.get_voting_options(), self.genesis.registrar.as_ref()) {
+            warn!("Transaction {:?} is invalid. Not adding to chain.", transaction.clone());
+            return Err(INVALID_TRANSACTION_REASON.to_string());
+        }
+
+        // every node keeps the transaction, as any sealer may lead the sealing of the next block
+        let pending = PendingTransaction::new(transaction);
+        let entry = WalEntry::Transaction { transaction: Box::new(pending.transaction.clone()), arrived_at: pending.arrived_at };
+        info!("Adding transaction {:?} to mempool with current len {}", pending.transaction.identifier.clone(), self.mempool.len());
+        match self.mempool.insert(pending) {
+            Ok(_) => {
+                // logged before the transaction is acknowledged
+                self.log(&entry);
+                Ok(None)
+            }
+            Err(reason) => Err(reason)
+        }
+    }
+
+    /// Handle a received transaction opening or closing the voting.
+    /// Returns the given accepting message, or the rejection of the transaction, as response
+    /// along with the transaction to broadcast the first time it is received.
+    fn on_phase_change_receive(&mut self, transaction: Transaction, accept: Message) -> (Message, Message) {
+        match self.on_transaction_receive(transaction.clone()) {
+            Ok(None) => (accept, Message::TransactionPayload(transaction)),
+            Ok(Some(_)) => (accept, Message::None),
+            Err(reason) => (Message::TransactionReject(transaction.identifier, reason), Message::None)
+        }
+    }
+
+    /// Create the unsigned transaction opening or closing the voting of this election now.
+    fn create_phase_change(&self, trx_type: TransactionType) -> Transaction {
+        let since_the_epoch = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards");
+        let changed_at = since_the_epoch.as_secs() * 1000 + u64::from(since_the_epoch.subsec_nanos()) / 1_000_000;
+
+        match trx_type {
+            TransactionType::VoteClosed => Transaction::new_voting_closed(self.genesis.clique.get_hasher(), self.genesis.get_configuration_hash(), changed_at),
+            _ => Transaction::new_voting_opened(self.genesis.clique.get_hasher(), self.genesis.get_configuration_hash(), changed_at)
+        }
+    }
+
+    /// Check that the given transaction is permitted in the phase of the election on the canonical chain.
+    /// The voting is opened once and closed once, and votes are only accepted while it is open.
+    /// Opening or closing the voting is rejected while another transaction doing so is pending,
+    /// if it belongs to another election or if it is not signed by the election authority, if any.
+    /// The same holds for registering or revoking voters, which is only accepted until the voting is closed.
+    /// Decryption shares and final tallies are only accepted once the voting is closed. Other transactions are permitted in any phase.
+    fn check_election_phase(&self, transaction: &Transaction) -> Result<(), String> {
+        if let Some(ref phase_change) = transaction.phase_change {
+            if phase_change.election != self.genesis.get_configuration_hash() {
+                return Err(format!("Transaction belongs to election {}", phase_change.election));
+            }
+        }
+
+        if let Some(ref voter_registration) = transaction.voter_registration {
+            if voter_registration.election != self.genesis.get_configuration_hash() {
+                return Err(format!("Transaction belongs to election {}", voter_registration.election));
+            }
+        }
+
+        match self.block_validator.check_phase_change_signature(transaction) {
+            Ok(()) => {}
+            Err(reason) => {
+                return Err(reason);
+            }
+        }
+
+        match self.block_validator.check_voter_registration_signature(transaction) {
+            Ok(()) => {}
+            Err(reason) => {
+                return Err(reason);
+            }
+        }
+
+        let is_other_pending = |trx_type: TransactionType| {
+            trx_type == transaction.trx_type && self.mempool.iter().any(|pending| pending.transaction.trx_type == trx_type && pending.transaction.identifier != transaction.identifier)
+        };
+        if is_other_pending(TransactionType::VoteOpened) {
+            return Err("Opening the voting is already pending".to_string());
+        }
+        if is_other_pending(TransactionType::VoteClosed) {
+            return Err("Closing the voting is already pending".to_string());
+        }
+
+        let phase = self.get_election_phase();
+        match (&transaction.trx_type, phase) {
+            (&TransactionType::Vote, ElectionPhase::NotOpened) => Err("Voting is not opened yet".to_string()),
+            (&TransactionType::Vote, ElectionPhase::Closed) => Err("Voting is closed".to_string()),
+            (&TransactionType::VoteOpened, ElectionPhase::Opened) => Err("Voting is already opened".to_string()),
+            (&TransactionType::VoteOpened, ElectionPhase::Closed) => Err("Voting is closed".to_string()),
+            (&TransactionType::VoteClosed, ElectionPhase::NotOpened) => Err("Voting is not opened yet".to_string()),
+            (&TransactionType::VoteClosed, ElectionPhase::Closed) => Err("Voting is already closed".to_string()),
+            (&TransactionType::VoterRegistered, ElectionPhase::Closed) | (&TransactionType::VoterRevoked, ElectionPhase::Closed) => Err("Voting is closed".to_string()),
+            (&TransactionType::DecryptionShare, ElectionPhase::NotOpened) | (&TransactionType::DecryptionShare, ElectionPhase::Opened) => Err("Voting is not closed yet".to_string()),
+            (&TransactionType::FinalTally, ElectionPhase::NotOpened) | (&TransactionType::FinalTally, ElectionPhase::Opened) => Err("Voting is not closed yet".to_string()),
+            _ => Ok(())
+        }
+    }
+
+    /// Check that the voter of the given transaction neither voted on the canonical chain nor has a vote pending,
+    /// unless the revote policy lets the last vote of a voter count. Other transactions always pass.
+    /// Returns the reason if the voter has already voted.
+    fn check_first_vote(&self, transaction: &Transaction) -> Result<(), String> {
+        let (question_id, voter_idx) = match transaction.data {
+            Some(ref trx_data) if TransactionType::Vote == transaction.trx_type => (&trx_data.question_id, trx_data.voter_idx),
+            _ => {
+                return Ok(());
+            }
+        };
+
+        if RevotePolicy::LastVoteCounts == self.genesis.get_revote_policy() {
+            return Ok(());
+        }
+
+        match self.chain.has_canonical_vote(question_id, voter_idx) {
+            Ok(true) => {
+                return Err(format!("Voter {} has already voted", voter_idx));
+            }
+            Ok(false) => {}
+            Err(e) => {
+                error!("Failed to determine the canonical chain: {:?}", e);
+            }
+        }
+
+        if self.mempool.contains_voter(question_id, voter_idx) {
+            return Err(format!("Voter {} has already a vote pending", voter_idx));
+        }
+
+        Ok(())
+    }
+
+    /// Check that the voter registered by the given transaction is not eligible yet, or that the voter revoked by it is eligible,
+    /// on the canonical chain, see `BlockValidator::check_voter_registration`, and that no other registration of the voter is pending.
+    /// Revoking a voter is rejected while a vote of the voter is pending, and a vote is rejected while its voter's registration is pending,
+    /// as both only take effect once included in a block. Other transactions always pass.
+    fn check_voter_registry(&self, transaction: &Transaction) -> Result<(), String> {
+        match (&transaction.trx_type, transaction.voter_registration.as_ref(), transaction.data.as_ref()) {
+            (&TransactionType::VoterRegistered, Some(voter_registration), _) | (&TransactionType::VoterRevoked, Some(voter_registration), _) => {
+                match self.block_validator.check_voter_registration(&self.get_voter_registry(), transaction) {
+                    Ok(()) => {}
+                    Err(reason) => {
+                        return Err(reason);
+                    }
+                }
+
+                if self.mempool.contains_voter_registration(&voter_registration.question_id, voter_registration.voter_idx) {
+                    return Err(format!("Voter {} has already a registration pending", voter_registration.voter_idx));
+                }
+
+                if TransactionType::VoterRevoked == transaction.trx_type && self.mempool.contains_voter(&voter_registration.question_id, voter_registration.voter_idx) {
+                    return Err(format!("Voter {} has a vote pending", voter_registration.voter_idx));
+                }
+
+                Ok(())
+            }
+            (&TransactionType::Vote, _, Some(trx_data)) if self.genesis.is_enabled(Feature::VoterRegistry) => {
+                if self.mempool.contains_voter_registration(&trx_data.question_id, trx_data.voter_idx) {
+                    return Err(format!("Registration of voter {} is pending", trx_data.voter_idx));
+                }
+
+                Ok(())
+            }
+            (&TransactionType::VoterRegistered, None, _) | (&TransactionType::VoterRevoked, None, _) => Err("Transaction does not name a voter".to_string()),
+            _ => Ok(())
+        }
+    }
+
+    /// Returns the image sets of the voters eligible on the canonical chain, first of those declared in `public_uciv.json`,
+    /// then of each question. Without the feature `voter-registry`, these are the ones declared in the genesis configuration.
+    fn get_voter_image_sets(&self) -> (Vec<ImageSet>, BTreeMap<String, Vec<ImageSet>>) {
+        if !self.genesis.is_enabled(Feature::VoterRegistry) {
+            return (self.genesis.public_uciv.clone(), self.genesis.questions.clone());
+        }
+
+        let voter_registry = self.get_voter_registry();
+
+        (voter_registry.get_image_sets(&None, &self.genesis.public_uciv), voter_registry.get_question_image_sets(&self.genesis.questions))
+    }
+
+    /// Returns the latest registration or revocation of each voter on the canonical chain,
+    /// as maintained by the chain along with its head.
+    fn get_voter_registry(&self) -> Arc<VoterRegistryVisitor> {
+        let voter_registry = match self.chain.head() {
+            Ok(head) => self.chain.voter_registry_of(&head.block.identifier),
+            Err(e) => Err(e)
+        };
+
+        match voter_registry {
+            Ok(Some(voter_registry)) => voter_registry,
+            Ok(None) => Arc::new(VoterRegistryVisitor::new()),
+            Err(e) => {
+                error!("Failed to determine the registered voters: {:?}", e);
+                Arc::new(VoterRegistryVisitor::new())
+            }
+        }
+    }
+
+    /// Check that the given decryption share is published by a sealer holding a key share and matches
+    /// the sum of the votes on the canonical chain. Other transactions always pass.
+    /// Returns the reason if the share is not valid.
+    fn check_decryption_share(&self, transaction: &Transaction) -> Result<(), String> {
+        let decryption_share = match transaction.decryption_share {
+            Some(ref decryption_share) if TransactionType::DecryptionShare == transaction.trx_type => decryption_share,
+            _ => {
+                return Ok(());
+            }
+        };
+
+        let decryption_keys = self.get_decryption_keys();
+        let public_key_share = match decryption_keys.as_ref().and_then(|decryption_keys| decryption_keys.public_key_shares.get(&decryption_share.sealer)) {
+            Some((_, public_key_share)) => public_key_share,
+            None => {
+                return Err(format!("Sealer {} holds no key share", decryption_share.sealer));
+            }
+        };
+
+        let (_, cipher_text) = self.sum_votes().get_votes_on(decryption_share.question_id.clone());
+        if !threshold::verify_partial_decryption(&self.get_election_public_key(), public_key_share, &cipher_text, &decryption_share.partial_decryption, &decryption_share.proof) {
+            return Err("Decryption share does not match the sum of the votes".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Check that the given election key is the product of the contributions of all dealers committed on the canonical chain,
+    /// and that votes and the opening of the voting follow the election key, if the sealers generate it jointly.
+    /// Other transactions always pass. Returns the reason if the transaction is not permitted.
+    fn check_key_generation(&self, transaction: &Transaction) -> Result<(), String> {
+        if self.genesis.key_generation.is_none() {
+            return match transaction.trx_type {
+                TransactionType::ElectionKey => Err("The election key is declared in the genesis configuration".to_string()),
+                _ => Ok(())
+            };
+        }
+
+        match transaction.trx_type {
+            TransactionType::Vote | TransactionType::VoteOpened if self.chain.get_election_key().is_none() => Err("The election key is not generated yet".to_string()),
+            TransactionType::ElectionKey => {
+                let mut key_generation_visitor = KeyGenerationVisitor::new();
+                LongestPathWalker::new().walk_chain(&self.chain, &mut key_generation_visitor);
+
+                if key_generation_visitor.get_election_key().is_some() {
+                    return Err("The election key is already committed".to_string());
+                }
+                if key_generation_visitor.get_dkg_commitments().len() < self.genesis.sealer.len() {
+                    return Err("Not all dealers committed their contribution to the election key yet".to_string());
+                }
+
+                match transaction.election_key {
+                    Some(ref election_key) if *election_key == dkg::get_election_key(&self.genesis.public_key, key_generation_visitor.get_dkg_commitments()) => Ok(()),
+                    _ => Err("Election key does not match the commitments of the dealers".to_string())
+                }
+            }
+            _ => Ok(())
+        }
+    }
+
+    /// Check that the given final tally is the first one of its question on the canonical chain and matches the tally
+    /// calculated from it, including the decrypted sum if the sealers decrypt the tally jointly. Other transactions always pass.
+    /// Returns the reason if the final tally is not valid.
+    fn check_final_tally(&self, transaction: &Transaction) -> Result<(), String> {
+        let final_tally = match transaction.final_tally {
+            Some(ref final_tally) if TransactionType::FinalTally == transaction.trx_type => final_tally,
+            _ => {
+                return Ok(());
+            }
+        };
+
+        let mut final_tally_visitor = FinalTallyVisitor::new();
+        LongestPathWalker::new().walk_chain(&self.chain, &mut final_tally_visitor);
+        if final_tally_visitor.get_final_tally(&final_tally.question_id).is_some() {
+            return Err("The final tally of the question is already committed".to_string());
+        }
+
+        if final_tally.decrypted_sum.is_none() && self.tally_calculator.is_decrypted() {
+            return Err("The sum of the votes is not decrypted yet".to_string());
+        }
+
+        if !self.calculate_final_tallies().contains(final_tally) {
+            return Err("Final tally does not match the votes on the chain".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Cast the vote of this node on adding the given candidate to the sealers, or on removing it.
+    /// The vote is kept in the buffer until this node seals a block, as it only counts in a block sealed by the voter.
+    ///
+    /// Returns the transaction casting the vote, or the reason if this node is not a sealer
+    /// or the vote would have no effect.
+    pub fn propose_sealer_vote(&mut self, candidate: SocketAddr, authorize: bool, public_key: Option<String>) -> Result<Transaction, String> {
+        let sealer_set = self.get_sealer_set();
+        let voter = match sealer_set.index_of(&self.own_address) {
+            Some(own_signer_index) => sealer_set.get_sealers()[own_signer_index],
+            None => {
+                return Err(format!("{} is not an authorized sealer", self.own_address));
+            }
+        };
+
+        let since_the_epoch = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards");
+        let sealer_vote = SealerVote {
+            voter,
+            candidate,
+            authorize,
+            public_key,
+            cast_at: since_the_epoch.as_secs() * 1000 + u64::from(since_the_epoch.subsec_nanos()) / 1_000_000,
+        };
+        let transaction = match sealer_set.check_vote(&sealer_vote) {
+            Ok(()) => Transaction::new_sealer_vote(self.genesis.clique.get_hasher(), sealer_vote),
+            Err(reason) => {
+                return Err(reason);
+            }
+        };
+        if !TransactionRegistry::with_default_kinds().is_valid(&transaction, &self.genesis.public_key, &self.genesis.public_uciv, &self.genesis.questions, &self.genesis.get_voting_options(), self.genesis.registrar.as_ref()) {
+            return Err(INVALID_TRANSACTION_REASON.to_string());
+        }
+
+        info!("Voting to {} {} as sealer in transaction {:?}", if authorize { "add" } else { "remove" }, candidate, transaction.identifier);
+        let pending = PendingTransaction::new(transaction.clone());
+        let entry = WalEntry::Transaction { transaction: Box::new(pending.transaction.clone()), arrived_at: pending.arrived_at };
+        match self.mempool.insert(pending) {
+            Ok(_) => {
+                self.log(&entry);
+                Ok(transaction)
+            }
+            Err(reason) => Err(reason)
+        }
+    }
+
+    /// Returns where the transaction with the given identifier is included in the canonical chain, if at all.
+    pub fn find_transaction_inclusion(&self, transaction_identifier: &str) -> Option<TransactionInclusion> {
+        let head_height = match self.chain.head() {
+            Ok(head) => head.height,
+            Err(e) => {
+                error!("Failed to determine the head of the chain: {:?}", e);
+                return None;
+            }
+        };
+        let finalized_height = self.get_finalized_height();
+
+        match self.chain.find_canonical_transaction(transaction_identifier) {
+            Ok(Some((height, block))) => Some(TransactionInclusion {
+                transaction_identifier: transaction_identifier.to_string(),
+                block_identifier: block.identifier.clone(),
+                height,
+                confirmations: head_height.distance_to(height),
+                finalized: height <= finalized_height,
+            }),
+            Ok(None) => None,
+            Err(e) => {
+                error!("Failed to determine the canonical chain: {:?}", e);
+                None
+            }
+        }
+    }
+
+    /// Returns the proof that the transaction with the given identifier is included in the canonical chain,
+    /// or None if it is not included or if the block including it does not commit to its transactions by their root.
+    pub fn get_inclusion_proof(&self, transaction_identifier: &str) -> Option<InclusionProof> {
+        let inclusion = match self.find_transaction_inclusion(transaction_identifier) {
+            Some(inclusion) => inclusion,
+            None => {
+                return None;
+            }
+        };
+
+        let block = match self.chain.blocks.get(&inclusion.block_identifier) {
+            Some(block) => block,
+            None => {
+                return None;
+            }
+        };
+
+        let header = match block.get_header() {
+            Some(header) => header,
+            None => {
+                debug!("Block {} including transaction {} was sealed without transactions root. Cannot prove inclusion", block.identifier, transaction_identifier);
+                return None;
+            }
+        };
+
+        let index = block.data.transactions.iter().position(|transaction| transaction.identifier == transaction_identifier).unwrap();
+        let hasher = Hasher::from_identifier(&header.transactions_root).unwrap_or_default();
+        let merkle_proof = MerkleProof::new(hasher, &block.data.transactions, index).unwrap();
+
+        Some(InclusionProof {
+            inclusion,
+            header,
+            merkle_proof,
+        })
+    }
+
+    /// Returns true, if the voting was closed on the canonical chain.
+    pub fn is_voting_closed(&self) -> bool {
+        ElectionPhase::Closed == self.get_election_phase()
+    }
+
+    /// Returns the phase of the election on the canonical chain, as maintained by the chain along with its head.
+    pub fn get_election_phase(&self) -> ElectionPhase {
+        let election_phase = match self.chain.head() {
+            Ok(head) => self.chain.election_phase_of(&head.block.identifier),
+            Err(e) => Err(e)
+        };
+
+        match election_phase {
+            Ok(Some(election_phase)) => election_phase,
+            Ok(None) => ElectionPhase::NotOpened,
+            Err(e) => {
+                error!("Failed to determine the phase of the election: {:?}", e);
+                ElectionPhase::NotOpened
+            }
+        }
+    }
+
+    /// Returns the status of this node.
+    pub fn get_status(&self) -> NodeStatus {
+        let (height, head_identifier) = match self.get_head() {
+            Some(head) => (head.height, head.block.identifier),
+            None => (Height::GENESIS, BlockHash::default())
+        };
+
+        NodeStatus {
+            node_version: NODE_VERSION.to_string(),
+            codec: JsonCodec::name().to_string(),
+            codecs: CodecKind::supported_names(),
+            genesis_version: self.genesis.version.clone(),
+            genesis_configuration_hash: self.get_genesis_configuration_hash(),
+            sealer: self.genesis.sealer.clone(),
+            features: self.genesis.features.clone(),
+            authorized_sealers: self.get_sealer_set().get_sealers().to_vec(),
+            version_check: self.version_check.clone(),
+            height,
+            head_identifier,
+            log_filters: self.log_filters.clone(),
+            clock_offset: self.clock_offset,
+        }
+    }
+
+    /// Create the handshake introducing this node, known under the given address, to its peers.
+    pub fn create_handshake(&self, address: SocketAddr) -> Handshake {
+        Handshake {
+            address,
+            node_version: NODE_VERSION.to_string(),
+            genesis_configuration_hash: self.get_genesis_configuration_hash(),
+            features: self.genesis.features.clone(),
+            codecs: CodecKind::supported_names(),
+            compressions: vec![],
+        }
+    }
+
+    /// Handle the handshake of a peer. Returns `FeatureMismatch` along with the own features,
+    /// if the peer enables different optional behaviours of the election, or `GenesisMismatch`
+    /// along with the own hash of the genesis configuration, if the peer runs a different one.
+    /// Handshakes announcing codecs are accepted with `HandshakeAcceptCodec`, naming the one chosen among them.
+    fn on_handshake(&self, handshake: Handshake) -> Message {
+        if let Some(difference) = feature::find_difference(&self.genesis.features, &handshake.features) {
+            error!("Peer {} enables different features than we do: {}. Rejecting it", handshake.address, difference);
+            return Message::FeatureMismatch(self.genesis.features.clone());
+        }
+
+        let genesis_configuration_hash = self.get_genesis_configuration_hash();
+        if handshake.genesis_configuration_hash != genesis_configuration_hash {
+            error!("Peer {} runs genesis configuration {} instead of {}. Rejecting it", handshake.address, handshake.genesis_configuration_hash, genesis_configuration_hash);
+            return Message::GenesisMismatch(genesis_configuration_hash);
+        }
+
+        info!("Peer {} with node version {} runs the same genesis configuration", handshake.address, handshake.node_version);
+        if handshake.codecs.is_empty() {
+            return Message::HandshakeAccept;
+        }
+
+        Message::HandshakeAcceptCodec(codec::negotiate(&handshake.codecs).name().to_string())
+    }
+
+    /// Returns a summary of the state of the election on the canonical chain.
+    pub fn get_election_status(&self) -> ElectionStatus {
+        let (height, head_identifier, head_timestamp) = match self.get_head() {
+            Some(head) => (head.height, head.block.identifier, head.block.data.timestamp),
+            None => (Height::GENESIS, BlockHash::default(), 0)
+        };
+
+        ElectionStatus {
+            height,
+            head_identifier,
+            head_timestamp,
+            phase: self.get_election_phase(),
+            total_votes: self.calculate_result().total_votes,
+            pending_transactions: self.mempool.len(),
+            sealer_statistics: self.calculate_sealer_statistics(),
+            current_epoch: self.get_epoch_schedule().get_epoch(height + 1).unwrap(),
+            abandoned_branches: self.get_abandoned_branches(),
+            finalized_height: self.get_finalized_height(),
+            election_key: self.chain.get_election_key().cloned(),
+        }
+    }
+
+    /// Count the votes on each question on the canonical chain, without summing them up.
+    pub fn get_turnout(&self) -> Turnout {
+        let (height, head_identifier) = match self.get_head() {
+            Some(head) => (head.height, head.block.identifier),
+            None => (Height::GENESIS, BlockHash::default())
+        };
+
+        let sum_cipher_visitor = self.sum_votes();
+        let questions = self.tally_calculator.get_question_ids().iter()
+            .filter_map(|question_id| question_id.clone())
+            .map(|question_id| (question_id.clone(), sum_cipher_visitor.get_votes_on(Some(question_id)).0))
+            .collect();
+
+        Turnout {
+            height,
+            head_identifier,
+            phase: self.get_election_phase(),
+            total_votes: sum_cipher_visitor.get_votes_on(None).0,
+            questions,
+        }
+    }
+
+    /// Remove all buffered votes once the voting is closed on the finalized chain, as these will never
+    /// be counted anymore. Votes are kept as long as the block closing the voting may still be reverted.
+    fn purge_stale_transactions(&mut self) {
+        let finalized_phase = match self.chain.finalized_head() {
+            Ok((_, finalized_block)) => self.chain.election_phase_of(&finalized_block.identifier),
+            Err(e) => Err(e)
+        };
+        match finalized_phase {
+            Ok(Some(ElectionPhase::Closed)) => {}
+            Ok(_) => {
+                return;
+            }
+            Err(e) => {
+                error!("Failed to determine the finalized phase of the election: {:?}", e);
+                return;
+            }
+        }
+
+        let amount_before = self.mempool.len();
+        self.mempool.retain(|pending| TransactionType::Vote != pending.transaction.trx_type);
+
+        let amount_purged = amount_before - self.mempool.len();
+        if amount_purged > 0 {
+            info!("Closing the voting is final. Purged {} pending votes from buffer", amount_purged);
+        }
+    }
+
+    fn calculate_result(&self) -> Tally {
+        let mut final_tallies = self.calculate_final_tallies();
+        // the tally of the question whose voters are declared in `public_uciv.json` comes first
+        let result = final_tallies.remove(0);
+        let questions = final_tallies.into_iter()
+            .filter_map(|FinalTally { question_id, total_votes, cipher_text, decrypted_sum, .. }| question_id.map(|question_id| (question_id, QuestionTally {
+                total_votes,
+                cipher_text,
+                decrypted_sum,
+            })))
+            .collect();
+
+        Tally {
+            cipher_text: result.cipher_text,
+            total_votes: result.total_votes,
+            decrypted_sum: result.decrypted_sum,
+            questions
+        }
+    }
+
+    /// Compare the given tally reported by another node with the own one, warning if they differ.
+    fn compare_tally(&self, tally: &Tally) {
+        let own_tally = self.calculate_result();
+        if own_tally == *tally {
+            debug!("Reported tally of {} votes matches the own tally", tally.total_votes);
+            return;
+        }
+
+        if own_tally.total_votes == tally.total_votes {
+            warn!("Reported tally of {} votes differs from the own tally of the same votes: {:?} vs. {:?}", tally.total_votes, tally, own_tally);
+        } else {
+            // the other node may just not have received the latest blocks yet
+            warn!("Reported tally of {} votes differs from the own tally of {} votes", tally.total_votes, own_tally.total_votes);
+        }
+    }
+
+    /// Calculate the final tally of each question on the canonical chain, see `TallyCalculator::calculate`.
+    fn calculate_final_tallies(&self) -> Vec<FinalTally> {
+        let (height, block) = match self.get_head() {
+            Some(head) => (head.height, head.block),
+            // without a head, only the root of the chain is counted
+            None => (self.chain.root_height(), self.chain.blocks[&self.chain.genesis_identifier_hash].clone())
+        };
+
+        self.tally_calculator.calculate(&self.chain, height, &block)
+    }
+
+    /// Sum up the votes on the canonical chain.
+    fn sum_votes(&self) -> SumCipherTextVisitor {
+        let mut sum_cipher_visitor = SumCipherTextVisitor::new(self.get_election_public_key(), self.genesis.get_revote_policy());
+        let longest_path_walker = LongestPathWalker::new();
+
+        longest_path_walker.walk_chain(&self.chain, &mut sum_cipher_visitor);
+
+        sum_cipher_visitor
+    }
+
+    /// Returns the decryption shares published on the canonical chain, from the oldest to the newest.
+    fn get_decryption_shares(&self) -> Vec<DecryptionShare> {
+        let mut decryption_shares_visitor = DecryptionSharesVisitor::new();
+        let longest_path_walker = LongestPathWalker::new();
+
+        longest_path_walker.walk_chain(&self.chain, &mut decryption_shares_visitor);
+
+        decryption_shares_visitor.get_decryption_shares()
+    }
+
+    /// Returns the election key votes are encrypted with, i.e. the one declared in the genesis configuration,
+    /// or the one committed on the chain if the sealers generate it jointly.
+    fn get_election_public_key(&self) -> PublicKey {
+        match (&self.genesis.key_generation, self.chain.get_election_key()) {
+            (&Some(_), Some(election_key)) => PublicKey { h: election_key.clone(), ..self.genesis.public_key.clone() },
+            _ => self.genesis.public_key.clone()
+        }
+    }
+
+    /// Returns the public key shares of the sealers holding a key share, along with the amount of decryption shares
+    /// required to decrypt the tally on the canonical chain, see `TallyCalculator::get_decryption_keys`.
+    fn get_decryption_keys(&self) -> Option<DecryptionKeys> {
+        self.get_head().and_then(|head| self.tally_calculator.get_decryption_keys(&self.chain, head.height, &head.block))
+    }
+
+    /// Publish the decryption shares of this sealer on the sum of the votes on each question, once the voting
+    /// is closed on the canonical chain, unless valid ones are already published or pending.
+    /// Does nothing if this node holds no key share or is not a sealer of the genesis configuration.
+    pub fn publish_decryption_shares(&mut self) {
+        let key_share = match self.key_share {
+            Some(ref key_share) => key_share.clone(),
+            None => {
+                return;
+            }
+        };
+
+        let own_sealer = match self.genesis.sealer.iter().find(|sealer| peers::is_same_peer(sealer, &self.own_address)) {
+            Some(own_sealer) => *own_sealer,
+            None => {
+                return;
+            }
+        };
+
+        if !self.is_voting_closed() {
+            return;
+        }
+
+        let public_key_share = match self.get_decryption_keys().and_then(|mut decryption_keys| decryption_keys.public_key_shares.remove(&own_sealer)) {
+            Some((_, public_key_share)) => public_key_share,
+            None => {
+                return;
+            }
+        };
+        let public_key = self.get_election_public_key();
+
+        let sum_cipher_visitor = self.sum_votes();
+        let published_shares: Vec<DecryptionShare> = self.get_decryption_shares().into_iter()
+            .chain(self.mempool.iter().filter_map(|pending| pending.transaction.decryption_share.clone()))
+            .filter(|decryption_share| decryption_share.sealer == own_sealer)
+            .collect();
+
+        let question_ids: Vec<Option<String>> = Some(None).into_iter()
+            .chain(self.genesis.questions.keys().map(|question_id| Some(question_id.clone())))
+            .collect();
+        for question_id in question_ids {
+            let (_, cipher_text) = sum_cipher_visitor.get_votes_on(question_id.clone());
+
+            // shares on a sum changed by a reorganization are published again
+            let is_published = published_shares.iter()
+                .filter(|decryption_share| decryption_share.question_id == question_id)
+                .any(|decryption_share| threshold::verify_partial_decryption(&public_key, &public_key_share, &cipher_text, &decryption_share.partial_decryption, &decryption_share.proof));
+            if is_published {
+                continue;
+            }
+
+            let (partial_decryption, proof) = threshold::partially_decrypt(&public_key, &key_share, &cipher_text);
+            let transaction = Transaction::new_decryption_share(self.genesis.clique.get_hasher(), DecryptionShare {
+                sealer: own_sealer,
+                question_id,
+                partial_decryption,
+                proof,
+            });
+
+            info!("Publishing decryption share in transaction {:?}", transaction.identifier);
+            let pending = PendingTransaction::new(transaction);
+            let entry = WalEntry::Transaction { transaction: Box::new(pending.transaction.clone()), arrived_at: pending.arrived_at };
+            match self.mempool.insert(pending) {
+                Ok(_) => self.log(&entry),
+                Err(reason) => {
+                    warn!("Failed to publish decryption share: {}", reason);
+                }
+            }
+        }
+    }
+
+    /// Publish the final tally of each question on the canonical chain, once the voting is closed and, if the sealers
+    /// decrypt the tally jointly, its sum is decrypted, unless it is already committed or pending. Only the leader publishes
+    /// the final tallies. Pending ones no longer matching the canonical chain, e.g. after a reorganization, are removed,
+    /// as they would invalidate the block including them.
+    pub fn publish_final_tally(&mut self) {
+        let final_tallies = if self.is_voting_closed() {
+            self.calculate_final_tallies()
+        } else {
+            vec![]
+        };
+
+        let amount_before = self.mempool.len();
+        self.mempool.retain(|pending| pending.transaction.final_tally.as_ref().map(|final_tally| final_tallies.contains(final_tally)) != Some(false));
+        let amount_removed = amount_before - self.mempool.len();
+        if amount_removed > 0 {
+            info!("Removed {} pending final tallies no longer matching the chain", amount_removed);
+        }
+
+        if final_tallies.is_empty() || !self.is_leader() {
+            return;
+        }
+
+        let mut final_tally_visitor = FinalTallyVisitor::new();
+        LongestPathWalker::new().walk_chain(&self.chain, &mut final_tally_visitor);
+
+        for final_tally in final_tallies {
+            if final_tally.decrypted_sum.is_none() && self.tally_calculator.is_decrypted() {
+                continue;
+            }
+
+            let is_published = final_tally_visitor.get_final_tally(&final_tally.question_id).is_some()
+                || self.mempool.iter().any(|pending| pending.transaction.final_tally.as_ref().map(|pending_tally| &pending_tally.question_id) == Some(&final_tally.question_id));
+            if is_published {
+                continue;
+            }
+
+            let transaction = Transaction::new_final_tally(self.genesis.clique.get_hasher(), final_tally);
+
+            info!("Publishing final tally in transaction {:?}", transaction.identifier);
+            let pending = PendingTransaction::new(transaction);
+            let entry = WalEntry::Transaction { transaction: Box::new(pending.transaction.clone()), arrived_at: pending.arrived_at };
+            match self.mempool.insert(pending) {
+                Ok(_) => self.log(&entry),
+                Err(reason) => {
+                    warn!("Failed to publish final tally: {}", reason);
+                }
+            }
+        }
+    }
+
+    /// Returns the sealer of the genesis configuration this node is, along with its index as dealer, starting at one.
+    fn get_own_dealer(&self) -> Option<(usize, SocketAddr)> {
+        self.genesis.sealer.iter()
+            .position(|sealer| peers::is_same_peer(sealer, &self.own_address))
+            .map(|position| (position + 1, self.genesis.sealer[position]))
+    }
+
+    /// Returns the dealing of this sealer, dealing it first if necessary.
+    fn get_or_create_dealing(&mut self, threshold: usize) -> Result<Dealing, String> {
+        if let Some(dealing) = self.key_generation.get_dealing() {
+            return Ok(dealing.clone());
+        }
+
+        info!("Dealing the own contribution to the election key");
+        let dealing = Dealing::generate(&self.genesis.public_key, threshold);
+        match self.key_generation.set_dealing(dealing.clone()) {
+            Ok(()) => Ok(dealing),
+            Err(e) => Err(e)
+        }
+    }
+
+    /// Take part in generating the election key jointly, if the genesis configuration declares the key generation
+    /// and this node is one of its sealers: Deal the own contribution to the election key and publish its commitments,
+    /// and publish the election key once the commitments of all dealers are committed on the canonical chain,
+    /// unless already published or pending. The key share of this sealer is derived once it holds a share of each dealer.
+    pub fn publish_key_generation(&mut self) {
+        let threshold = match self.genesis.key_generation {
+            Some(ref key_generation) => key_generation.threshold,
+            None => {
+                return;
+            }
+        };
+
+        let (own_index, own_sealer) = match self.get_own_dealer() {
+            Some(own_dealer) => own_dealer,
+            None => {
+                return;
+            }
+        };
+
+        let dealing = match self.get_or_create_dealing(threshold) {
+            Ok(dealing) => dealing,
+            Err(e) => {
+                error!("Failed to deal the own contribution to the election key: {}", e);
+                return;
+            }
+        };
+
+        // the own share is never sent over the network
+        if !self.key_generation.get_received_shares().contains_key(&own_sealer) {
+            let own_share = dealing.get_share(&self.genesis.public_key, own_index);
+            if let Err(e) = self.key_generation.add_received_share(own_sealer, own_share) {
+                error!("Failed to keep the own share of the election key: {}", e);
+            }
+        }
+        self.derive_key_share();
+
+        let mut key_generation_visitor = KeyGenerationVisitor::new();
+        LongestPathWalker::new().walk_chain(&self.chain, &mut key_generation_visitor);
+        if key_generation_visitor.get_election_key().is_some() {
+            return;
+        }
+
+        let dkg_commitments = key_generation_visitor.get_dkg_commitments();
+        let transaction = if !dkg_commitments.contains_key(&own_sealer) {
+            let is_pending = self.mempool.iter().any(|pending| pending.transaction.dkg_commitment.as_ref().map(|dkg_commitment| dkg_commitment.dealer) == Some(own_sealer));
+            if is_pending {
+                return;
+            }
+
+            Transaction::new_dkg_commitment(self.genesis.clique.get_hasher(), DkgCommitment {
+                dealer: own_sealer,
+                commitments: dealing.get_commitments(&self.genesis.public_key),
+            })
+        } else if dkg_commitments.len() == self.genesis.sealer.len() {
+            // every sealer publishes the same election key, which is included once
+            let election_key = dkg::get_election_key(&self.genesis.public_key, dkg_commitments);
+            let transaction = Transaction::new_election_key(self.genesis.clique.get_hasher(), election_key);
+            if self.mempool.iter().any(|pending| pending.transaction.identifier == transaction.identifier) {
+                return;
+            }
+
+            transaction
+        } else {
+            return;
+        };
+
+        info!("Publishing {:?} of the key generation in transaction {:?}", transaction.trx_type, transaction.identifier);
+        let pending = PendingTransaction::new(transaction);
+        let entry = WalEntry::Transaction { transaction: Box::new(pending.transaction.clone()), arrived_at: pending.arrived_at };
+        match self.mempool.insert(pending) {
+            Ok(_) => self.log(&entry),
+            Err(reason) => {
+                warn!("Failed to publish the transaction of the key generation: {}", reason);
+            }
+        }
+    }
+
+    /// Derive the key share of this sealer, once it holds a share of each dealer of the genesis configuration.
+    fn derive_key_share(&mut self) {
+        if self.key_share.is_some() || self.key_generation.get_received_shares().len() < self.genesis.sealer.len() {
+            return;
+        }
+
+        info!("Received the shares of all {} dealers, deriving the own key share", self.genesis.sealer.len());
+        self.key_share = Some(dkg::combine_shares(&self.genesis.public_key, self.key_generation.get_received_shares()));
+    }
+
+    /// Returns the shares of the own contribution to the election key dealt to each other sealer of the genesis
+    /// configuration, once the commitments of this sealer are committed on the canonical chain, so that
+    /// their recipients can verify them. Empty if this node does not take part in generating the election key.
+    pub fn get_dealt_shares(&self) -> Vec<DealtShare> {
+        let (dealing, own_sealer) = match (self.key_generation.get_dealing(), self.get_own_dealer()) {
+            (Some(dealing), Some((_, own_sealer))) => (dealing, own_sealer),
+            _ => {
+                return vec![];
+            }
+        };
 
-        let lower_leader_index_bound = (current_block_number % self.signer_count) + 1;
-        let upper_leader_index_bound = (current_block_number + self.genesis.clique.signer_limit) % self.signer_count;
+        let mut key_generation_visitor = KeyGenerationVisitor::new();
+        LongestPathWalker::new().walk_chain(&self.chain, &mut key_generation_visitor);
+        if key_generation_visitor.get_dkg_commitments().get(&own_sealer) != Some(&dealing.get_commitments(&self.genesis.public_key)) {
+            return vec![];
+        }
 
-        let am_i_co_leader = (self.signer_index >= lower_leader_index_bound) && (self.signer_index <= upper_leader_index_bound);
+        self.genesis.sealer.iter().enumerate()
+            .filter(|&(_, sealer)| *sealer != own_sealer)
+            .map(|(position, sealer)| DealtShare {
+                dealer: own_sealer,
+                recipient: *sealer,
+                share: dealing.get_share(&self.genesis.public_key, position + 1),
+            })
+            .collect()
+    }
 
-        trace!("Current block number is {}, leader index bound is [{}..{}]. Am I co-leader? {}", current_block_number, lower_leader_index_bound, upper_leader_index_bound, am_i_co_leader);
+    /// Handle a share of the contribution of a dealer to the election key sent to this sealer.
+    /// The share is kept if it matches the commitments of its dealer on the canonical chain.
+    /// Returns the reason if the share is refused.
+    fn on_dkg_share(&mut self, dealt_share: DealtShare) -> Result<(), String> {
+        if self.genesis.key_generation.is_none() {
+            return Err("The election key is declared in the genesis configuration".to_string());
+        }
 
-        am_i_co_leader
-    }
+        let own_index = match self.get_own_dealer() {
+            Some((own_index, _)) if peers::is_same_peer(&dealt_share.recipient, &self.own_address) => own_index,
+            _ => {
+                return Err(format!("Share is dealt to {}, not to {}", dealt_share.recipient, self.own_address));
+            }
+        };
 
-    /// Handle a received transaction.
-    fn on_transaction_receive(&mut self, transaction: Transaction) {
-        if !transaction.is_valid(self.genesis.public_key.clone(), self.genesis.public_uciv.clone()) {
-            warn!("Transaction {:?} is invalid. Not adding to chain.", transaction.clone());
-            return;
+        if self.key_generation.get_received_shares().get(&dealt_share.dealer) == Some(&dealt_share.share) {
+            return Ok(());
         }
 
-        if self.transactions.contains(&transaction) {
-            trace!("Transaction {:?} is already contained. Not adding to chain", transaction.identifier.clone());
-            return;
+        let mut key_generation_visitor = KeyGenerationVisitor::new();
+        LongestPathWalker::new().walk_chain(&self.chain, &mut key_generation_visitor);
+        let commitments = match key_generation_visitor.get_dkg_commitments().get(&dealt_share.dealer) {
+            Some(commitments) => commitments,
+            None => {
+                return Err(format!("Commitments of dealer {} are not committed yet", dealt_share.dealer));
+            }
+        };
+
+        if !dkg::verify_share(&self.genesis.public_key, commitments, own_index, &dealt_share.share) {
+            warn!("Share dealt by {} does not match its commitments", dealt_share.dealer);
+            return Err(format!("Share does not match the commitments of dealer {}", dealt_share.dealer));
         }
 
-        if self.is_leader() || self.is_co_leader() {
-            info!("Adding transaction {:?} to buffer with current len {}", transaction.identifier.clone(), self.transactions.len());
-            self.transactions.push(transaction);
+        info!("Received valid share of the election key from dealer {}", dealt_share.dealer);
+        match self.key_generation.add_received_share(dealt_share.dealer, dealt_share.share) {
+            Ok(()) => {}
+            Err(e) => {
+                error!("{}", e);
+                return Err("Failed to keep the share".to_string());
+            }
         }
+        self.derive_key_share();
+
+        Ok(())
     }
 
-    fn calculate_result(&self) -> Tally {
-        let mut sum_cipher_visitor = SumCipherTextVisitor::new(self.genesis.public_key.clone());
+    fn calculate_sealer_statistics(&self) -> SealerStatistics {
+        let mut sealer_statistics_visitor = SealerStatisticsVisitor::new();
         let longest_path_walker = LongestPathWalker::new();
 
-        longest_path_walker.walk_chain(&self.chain, &mut sum_cipher_visitor);
+        longest_path_walker.walk_chain(&self.chain, &mut sealer_statistics_visitor);
 
-        let result = sum_cipher_visitor.get_votes();
+        // sealers voted out keep their statistics, sealers voted in follow the declared ones
+        let mut sealers = self.genesis.sealer.clone();
+        for sealer in self.get_sealer_set().get_sealers() {
+            if !sealers.contains(sealer) {
+                sealers.push(*sealer);
+            }
+        }
+        let sealed_blocks = sealers.iter()
+            .map(|sealer| (*sealer, sealer_statistics_visitor.get_sealed_blocks(sealer)))
+            .collect();
 
-        Tally {
-            cipher_text: result.1,
-            total_votes: result.0
+        SealerStatistics {
+            sealed_blocks,
+            unattributed_blocks: sealer_statistics_visitor.get_unattributed_blocks()
         }
     }
 
+    /// Returns the statistics of each epoch of the canonical chain, from the oldest to the newest.
+    pub fn get_epoch_statistics(&self) -> Vec<EpochStatistics> {
+        let mut epoch_statistics_visitor = EpochStatisticsVisitor::new(self.get_epoch_schedule());
+        let longest_path_walker = LongestPathWalker::new();
+
+        longest_path_walker.walk_chain(&self.chain, &mut epoch_statistics_visitor);
+
+        epoch_statistics_visitor.get_epoch_statistics()
+    }
+
     fn find_transaction(&self, trx_identifier: String) -> Option<Transaction> {
         let mut find_trx_visitor = FindTransactionVisitor::new(trx_identifier);
         let longest_path_walker = LongestPathWalker::new();
@@ -174,11 +2463,36 @@ impl CliqueProtocol {
         find_trx_visitor.get_found_transaction()
     }
 
+    /// Returns the transactions of the canonical chain selected by the given query,
+    /// from the newest to the oldest. At most `MAX_QUERY_RESULTS` are returned.
+    fn query_transactions(&self, mut query: TransactionQuery) -> Vec<Transaction> {
+        query.limit = query.limit.min(MAX_QUERY_RESULTS);
+
+        let mut query_trx_visitor = QueryTransactionsVisitor::new(query);
+        let longest_path_walker = LongestPathWalker::new();
+
+        longest_path_walker.walk_chain(&self.chain, &mut query_trx_visitor);
+
+        query_trx_visitor.get_found_transactions()
+    }
+
+    /// Returns the minimum amount of seconds between two blocks.
+    pub fn get_block_period(&self) -> u64 {
+        self.genesis.clique.block_period
+    }
+
     pub fn is_block_period_over(&self) -> bool {
         let now = SystemTime::now();
         let now_unix = now.duration_since(UNIX_EPOCH).expect("Time went backwards").as_secs();
 
-        let next_run = self.genesis.clique.block_period + self.chain.get_current_block_timestamp();
+        let current_block_timestamp = match self.get_head() {
+            Some(head) => head.block.data.timestamp,
+            None => {
+                return false;
+            }
+        };
+
+        let next_run = self.genesis.clique.block_period + current_block_timestamp;
 
         if now_unix < next_run {
             trace!("Block period is not yet over. {:?} seconds left.", next_run - now_unix);
@@ -189,22 +2503,37 @@ impl CliqueProtocol {
         return true;
     }
 
-    pub fn create_current_block_and_reset_transaction_buffer(&mut self) -> Block {
-        let current_block = self.chain.get_current_block();
+    /// Create a new block on top of the current head containing all pending transactions of the mempool.
+    /// Transactions are ordered by their time of arrival at this node, and by their
+    /// identifier if they arrived at the same time. They remain in the mempool until
+    /// the block is added to the chain, so that they are not lost if it is not sealed.
+    /// Returns None if the head of the chain cannot be determined or this node is not an authorized sealer.
+    pub fn create_current_block(&self) -> Option<Block> {
+        let current_block = match self.get_head() {
+            Some(head) => head.block,
+            None => {
+                return None;
+            }
+        };
 
-        let block = Block::new(
-            current_block.1.identifier.clone(),
-            self.transactions.clone(),
-        );
+        let sealer_set = self.get_sealer_set();
+        let own_sealer = match sealer_set.index_of(&self.own_address) {
+            Some(own_signer_index) => sealer_set.get_sealers()[own_signer_index],
+            None => {
+                return None;
+            }
+        };
 
-        // reset current state again
-        self.transactions = vec![];
+        let pending_transactions = self.mempool.get_ordered();
 
-        block
-    }
+        let block = Block::new(
+            self.genesis.clique.get_hasher(),
+            current_block.identifier.clone(),
+            Some(own_sealer),
+            pending_transactions.iter().map(|pending| pending.transaction.clone()).collect(),
+        );
 
-    pub fn reset_transaction_buffer(&mut self) {
-        self.transactions = vec![];
+        Some(block)
     }
 
     /// Sign a block with all current known transactions.
@@ -219,21 +2548,299 @@ impl CliqueProtocol {
         }
 
         // add block to our chain as well
+        self.log(&WalEntry::Block(block.clone()));
+        let old_head = self.get_head();
         let is_added = self.chain.add_block(block.clone());
 
         if ! is_added {
             trace!("Block {} was already contained in the chain, possibly due to a leader broadcast earlier. Skipping broadcast.", block.identifier);
-            let current_block_after_sign = self.chain.get_current_block();
-            debug!("Current block without signing has height {:?} and identifier {:?}", current_block_after_sign.0, current_block_after_sign.1.identifier);
+            if let Some(head) = self.get_head() {
+                debug!("Current block without signing has height {:?} and identifier {:?}", head.height, head.block.identifier);
+            }
 
             return None;
         }
 
-        let current_block_after_sign = self.chain.get_current_block();
-        debug!("Current block after signing has height {:?} and identifier {:?}", current_block_after_sign.0, current_block_after_sign.1.identifier);
+        self.mempool.remove_included(&block.data.transactions);
+        self.store_block(&block);
+        self.purge_stale_transactions();
+        self.compact_write_ahead_log();
+        self.notify_head_change(old_head, None);
+
+        if let Some(head) = self.get_head() {
+            debug!("Current block after signing has height {:?} and identifier {:?}", head.height, head.block.identifier);
+        }
 
         Some(block)
     }
+
+    /// Returns the join requests of all candidates which want to become sealers.
+    pub fn get_join_requests(&self) -> Vec<JoinRequest> {
+        self.join_requests.clone()
+    }
+
+    /// Keep the given join request until the authorities decide on it, but at most `JOIN_REQUEST_LIFETIME` seconds
+    /// after it was issued. Requests which were issued longer ago or in the future, and requests of candidates listed
+    /// under an IP address which already has `MAX_JOIN_REQUESTS_PER_HOST` pending requests are rejected.
+    ///
+    /// Returns true if the request was added, false if the candidate
+    /// already submitted the same request, or the reason of the rejection.
+    pub fn add_join_request(&mut self, join_request: JoinRequest) -> Result<bool, String> {
+        if !join_request.has_valid_identifier() {
+            return Err("Identifier of the candidate is not a SHA-1 digest".to_string());
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_secs();
+        if join_request.timestamp > now + MAX_TIMESTAMP_DRIFT || join_request.timestamp + JOIN_REQUEST_LIFETIME <= now {
+            return Err(format!("Join request issued at {} is not current", join_request.timestamp));
+        }
+
+        // the places of stale requests are freed before any new request is admitted
+        let amount_join_requests = self.join_requests.len();
+        self.join_requests.retain(|request| request.timestamp + JOIN_REQUEST_LIFETIME > now);
+        if self.join_requests.len() < amount_join_requests {
+            info!("Dropped {} join requests pending for longer than {}s", amount_join_requests - self.join_requests.len(), JOIN_REQUEST_LIFETIME);
+        }
+
+        if !join_request.genesis_configuration_hash.eq(&self.get_genesis_configuration_hash()) {
+            return Err(format!("Genesis configuration {} differs from ours", join_request.genesis_configuration_hash));
+        }
+
+        if self.get_sealer_set().index_of(&join_request.address).is_some() {
+            return Err(format!("{} is already a sealer", join_request.address));
+        }
+
+        if let Some(known_request) = self.join_requests.iter().find(|request| request.identifier.eq(&join_request.identifier)) {
+            if known_request.address.eq(&join_request.address) {
+                return Ok(false);
+            }
+
+            return Err(format!("Candidate already requested to join as {}", known_request.address));
+        }
+
+        if self.join_requests.len() >= MAX_PENDING_JOIN_REQUESTS {
+            return Err("Too many pending join requests".to_string());
+        }
+
+        if self.join_requests.iter().filter(|request| request.address.ip() == join_request.address.ip()).count() >= MAX_JOIN_REQUESTS_PER_HOST {
+            return Err(format!("Too many pending join requests of candidates at {}", join_request.address.ip()));
+        }
+
+        info!("Candidate {} requested to join as sealer {}", join_request.identifier, join_request.address);
+        self.join_requests.push(join_request);
+
+        Ok(true)
+    }
+
+    /// Returns the tips of the branches abandoned by fork choice, in alphabetical order.
+    pub fn get_abandoned_branches(&self) -> Vec<BlockHash> {
+        let mut abandoned_branches: Vec<BlockHash> = self.abandoned_branches.iter().cloned().collect();
+        abandoned_branches.sort();
+
+        abandoned_branches
+    }
+
+    /// Remove the transactions which are included in the canonical chain from the mempool,
+    /// e.g. after the chain was replaced.
+    fn remove_canonical_transactions(&mut self) {
+        if let Some(canonical_transactions) = self.get_canonical_transactions() {
+            self.mempool.retain(|pending| !canonical_transactions.contains(&pending.transaction.identifier));
+        }
+    }
+
+    /// Returns the identifiers of the transactions included in the canonical chain,
+    /// or None if the canonical chain cannot be determined.
+    fn get_canonical_transactions(&self) -> Option<HashSet<String>> {
+        match self.chain.get_canonical_path() {
+            Ok(canonical_path) => Some(canonical_path.iter()
+                .filter_map(|block_hash| self.chain.blocks.get(block_hash))
+                .flat_map(|block| block.data.transactions.iter().map(|transaction| transaction.identifier.clone()))
+                .collect()),
+            Err(e) => {
+                error!("Failed to determine the canonical chain: {:?}", e);
+                None
+            }
+        }
+    }
+
+    /// Abandon all branches whose tip fell at least `BRANCH_ABANDON_DEPTH`
+    /// blocks behind the head of the canonical chain.
+    ///
+    /// Blocks of abandoned branches are kept in the chain, so that a branch is
+    /// revived once it is extended again. Transactions which were only included
+    /// in abandoned branches are put back into the buffer, so that they are
+    /// not lost along with the branch.
+    ///
+    /// Returns the tips of the branches which were abandoned by this call.
+    pub fn abandon_stale_branches(&mut self) -> Vec<BlockHash> {
+        let stale_branches = match self.chain.get_abandoned_branches(BRANCH_ABANDON_DEPTH) {
+            Ok(stale_branches) => stale_branches,
+            Err(e) => {
+                error!("Failed to determine abandoned branches: {:?}", e);
+                return vec![];
+            }
+        };
+
+        // forget branches which were extended or became canonical in the meantime
+        self.abandoned_branches.retain(|tip| stale_branches.iter().any(|branch| branch.tip.eq(tip)));
+
+        let new_branches: Vec<_> = stale_branches.into_iter()
+            .filter(|branch| !self.abandoned_branches.contains(&branch.tip))
+            .collect();
+        if new_branches.is_empty() {
+            return vec![];
+        }
+
+        let canonical_transactions = match self.get_canonical_transactions() {
+            Some(canonical_transactions) => canonical_transactions,
+            None => {
+                return vec![];
+            }
+        };
+
+        let mut new_tips = vec![];
+        for branch in new_branches {
+            let mut requeued_transactions: Vec<PendingTransaction> = vec![];
+            for block_hash in branch.blocks.iter() {
+                let block = match self.chain.blocks.get(block_hash) {
+                    Some(block) => block,
+                    None => continue
+                };
+
+                for transaction in block.data.transactions.iter() {
+                    let is_known = canonical_transactions.contains(&transaction.identifier)
+                        || self.mempool.contains_identifier(&transaction.identifier)
+                        || requeued_transactions.iter().any(|requeued| requeued.transaction.identifier.eq(&transaction.identifier));
+
+                    if !is_known {
+                        requeued_transactions.push(PendingTransaction::new(transaction.clone()));
+                    }
+                }
+            }
+
+            info!("Abandoning branch {} of {} blocks, requeueing {} of its transactions", branch.tip, branch.blocks.len(), requeued_transactions.len());
+            for requeued in requeued_transactions {
+                let entry = WalEntry::Transaction { transaction: Box::new(requeued.transaction.clone()), arrived_at: requeued.arrived_at };
+                match self.mempool.insert(requeued) {
+                    Ok(_) => self.log(&entry),
+                    Err(reason) => warn!("Dropping transaction of abandoned branch {}: {}", branch.tip, reason)
+                }
+            }
+
+            self.abandoned_branches.insert(branch.tip.clone());
+            new_tips.push(branch.tip);
+        }
+
+        self.purge_stale_transactions();
+
+        new_tips
+    }
+
+    /// Remove the branches from the chain which forked off below the finalized height,
+    /// as they can never become canonical, see `Chain::prune_stale_branches`.
+    /// The chain is rewritten to the block store, if any.
+    ///
+    /// Returns the amount of removed blocks.
+    pub fn prune_stale_branches(&mut self) -> usize {
+        let stale_blocks = match self.chain.prune_stale_branches() {
+            Ok(stale_blocks) => stale_blocks,
+            Err(e) => {
+                error!("Failed to prune stale branches: {:?}", e);
+                return 0;
+            }
+        };
+        if stale_blocks.is_empty() {
+            return 0;
+        }
+
+        let stale_bytes: usize = stale_blocks.iter()
+            .map(|block| serde_json::to_string(block).map(|encoded| encoded.len()).unwrap_or(0))
+            .sum();
+        info!("Pruned {} blocks of {} bytes on branches forked off below the finalized height", stale_blocks.len(), stale_bytes);
+        if let Some(ref metrics_registry) = self.metrics_registry {
+            metrics_registry.record_pruning(stale_blocks.len(), stale_bytes);
+        }
+
+        let chain = &self.chain;
+        self.abandoned_branches.retain(|tip| chain.blocks.contains_key(tip));
+        if let Some(ref mut block_store) = self.block_store {
+            match block_store.replace(&self.chain) {
+                Ok(()) => {}
+                Err(e) => {
+                    error!("{}", e);
+                }
+            }
+        }
+
+        stale_blocks.len()
+    }
+
+    /// Take note of a branch which another peer claims to have abandoned.
+    ///
+    /// The claim is only accepted if the own fork choice abandons the branch as well,
+    /// so that peers are not able to censor blocks by declaring them abandoned.
+    /// Returns true if the claim was accepted.
+    pub fn acknowledge_abandoned_branch(&mut self, tip: BlockHash) -> bool {
+        if self.abandoned_branches.contains(&tip) {
+            return true;
+        }
+
+        let is_abandoned = match self.chain.get_abandoned_branches(BRANCH_ABANDON_DEPTH) {
+            Ok(stale_branches) => stale_branches.iter().any(|branch| branch.tip.eq(&tip)),
+            Err(e) => {
+                error!("Failed to determine abandoned branches: {:?}", e);
+                false
+            }
+        };
+
+        if !is_abandoned {
+            match self.get_head() {
+                Some(ref head) if head.block.identifier.eq(&tip) => warn!("A peer claims to have abandoned our head {}. Its fork choice differs from ours", tip),
+                _ => debug!("Ignoring claim that branch {} is abandoned as our fork choice does not confirm it", tip)
+            }
+
+            return false;
+        }
+
+        // requeue the transactions of the branch as if we abandoned it on our own
+        self.abandon_stale_branches();
+
+        true
+    }
+
+    /// Handle a command tunneled over the peer protocol by the admin identity.
+    /// The command is only run if the request is authentic and the admin
+    /// is granted the capability required by the command.
+    ///
+    /// Returns the authenticated response to the admin along with a message
+    /// which must be broadcast to all other peers, equal to `handle_rpc`.
+    pub fn handle_admin_request(&mut self, request: AdminRequest) -> (Message, Message) {
+        let authorization = match self.admin_channel {
+            Some(ref mut admin_channel) => admin_channel.authorize(&request),
+            None => {
+                debug!("Rejecting admin request as no admin identity is configured");
+                return (Message::None, Message::None);
+            }
+        };
+
+        let (result, broadcast_message) = match authorization {
+            Ok(command) => {
+                info!("Running admin command {:?} of {:?}", command, request.identity);
+                match self.handle_rpc(command) {
+                    Some((response, broadcast_message)) => (Ok(response), broadcast_message),
+                    None => (Ok(Message::None), Message::None)
+                }
+            }
+            Err(reason) => {
+                warn!("Rejecting admin request of {:?}: {}", request.identity, reason);
+                (Err(reason), Message::None)
+            }
+        };
+
+        let response = self.admin_channel.as_ref().unwrap().respond(request.nonce, result);
+
+        (Message::AdminResponse(response), broadcast_message)
+    }
 }
 
 impl ProtocolHandler for CliqueProtocol {
@@ -246,68 +2853,156 @@ impl ProtocolHandler for CliqueProtocol {
                 // if we received the transaction from another node
                 // there is no need to broadcast it again, as this
                 // was the task of the node from which we've received it.
-                self.on_transaction_receive(transaction.clone());
-
-                Message::TransactionAccept(transaction.identifier.clone())
+                match self.on_transaction_receive(transaction.clone()) {
+                    Ok(None) => Message::TransactionAccept(transaction.identifier.clone()),
+                    Ok(Some(inclusion)) => Message::TransactionIncluded(inclusion),
+                    Err(reason) => Message::TransactionReject(transaction.identifier.clone(), reason)
+                }
             }
             Message::TransactionAccept(_) => Message::None,
+            Message::TransactionReject(_, _) => Message::None,
+            Message::TransactionIncluded(_) => Message::None,
             Message::BlockRequest(_) => unimplemented!("Not yet implemented: Return block requested"),
             Message::BlockPayload(block) => {
-
-                // Scenario is as follows:
-                // - I am co-leader
-                // - I receive a transaction -> add transaction to buffer
-                // - I receive a block from leader containing that transaction
-                // - I'm the leader now, and still have the transaction im my buffer
-                // - I create a block containing that transaction again.
-                // => two different blocks with the same transaction in them
-
-                // check whether we have the contained transaction already in our buffer
-                // and if so, remove it
-                for transaction in block.data.transactions.clone() {
-                    self.transactions.retain(|ref trx| {
-                       // remove all where false is returned
-                        ! (transaction.identifier.clone() == trx.identifier.clone())
-                    });
+                if self.chain.blocks.contains_key(&block.identifier) {
+                    return Message::BlockDuplicated;
                 }
 
-                let is_added = self.chain.add_block(block);
-
-                if is_added {
-                    return Message::BlockAccept;
+                if let Err(rejection) = self.block_validator.validate(&self.chain, &block) {
+                    warn!("Rejecting block {:?}: {}", block.identifier, rejection);
+                    return Message::BlockReject(block.identifier, rejection.to_string());
                 }
 
-                Message::BlockDuplicated
+                self.insert_block(block)
             }
             Message::BlockAccept => Message::None,
             Message::BlockDuplicated => Message::None,
-            Message::ChainRequest => Message::ChainResponse(self.chain.clone()),
+            Message::BlockReject(_, _) => Message::None,
+            Message::ChainRequest => Message::ChainResponse(Box::new(self.chain.clone())),
             Message::ChainResponse(chain) => {
-                self.replace_chain(chain);
+                self.replace_chain(*chain);
 
                 Message::ChainAccept
             }
             Message::ChainAccept => Message::None,
+            Message::ChainChunkRequest(token) => Message::ChainChunkResponse(self.get_chain_chunk(token)),
+            Message::ChainChunkResponse(_) => Message::None,
+            Message::SnapshotRequest(height) => Message::SnapshotResponse(self.get_state_snapshot(height)),
+            Message::SnapshotResponse(_) => Message::None,
             Message::OpenVote => {
-                self.on_transaction_receive(Transaction::new_voting_opened());
-
-                Message::OpenVoteAccept
+                let transaction = self.create_phase_change(TransactionType::VoteOpened);
+                self.on_phase_change_receive(transaction, Message::OpenVoteAccept).0
             },
             Message::OpenVoteAccept => Message::None,
             Message::CloseVote => {
-                self.on_transaction_receive(Transaction::new_voting_closed());
-
-                Message::CloseVoteAccept
+                let transaction = self.create_phase_change(TransactionType::VoteClosed);
+                self.on_phase_change_receive(transaction, Message::CloseVoteAccept).0
             },
             Message::CloseVoteAccept => Message::None,
-            Message::RequestTally => Message::None,
-            Message::RequestTallyPayload(_) => Message::None,
+            Message::SignedOpenVote(phase_change) => {
+                let transaction = Transaction::from_phase_change(self.genesis.clique.get_hasher(), TransactionType::VoteOpened, phase_change);
+                self.on_phase_change_receive(transaction, Message::OpenVoteAccept).0
+            },
+            Message::SignedCloseVote(phase_change) => {
+                let transaction = Transaction::from_phase_change(self.genesis.clique.get_hasher(), TransactionType::VoteClosed, phase_change);
+                self.on_phase_change_receive(transaction, Message::CloseVoteAccept).0
+            },
+            Message::RequestTally => Message::RequestTallyPayload(self.calculate_result()),
+            Message::RequestTallyPayload(tally) => {
+                self.compare_tally(&tally);
+
+                Message::None
+            }
+            // subscriptions are only kept open by the RPC listener
+            Message::TurnoutSubscribe => Message::None,
+            Message::TurnoutUpdate(_) => Message::None,
             Message::FindTransaction(identifier) => {
                 let found_trx = self.find_transaction(identifier);
 
                 Message::FindTransactionResponse(found_trx)
             },
-            Message::FindTransactionResponse(_) => Message::None
+            Message::FindTransactionResponse(_) => Message::None,
+            Message::InclusionProofRequest(identifier) => Message::InclusionProofResponse(self.get_inclusion_proof(&identifier)),
+            Message::InclusionProofResponse(_) => Message::None,
+            Message::ReceiptRequest(identifier) => Message::ReceiptResponse(self.get_receipt_status(&identifier)),
+            Message::ReceiptResponse(_) => Message::None,
+            Message::QueryTransactions(query) => Message::QueryTransactionsResponse(self.query_transactions(query)),
+            Message::QueryTransactionsResponse(_) => Message::None,
+            Message::SealerStatisticsRequest => Message::SealerStatisticsResponse(self.calculate_sealer_statistics()),
+            Message::SealerStatisticsResponse(_) => Message::None,
+            Message::EpochStatisticsRequest => Message::EpochStatisticsResponse(self.get_epoch_statistics()),
+            Message::EpochStatisticsResponse(_) => Message::None,
+            Message::ElectionStatusRequest => Message::ElectionStatusResponse(self.get_election_status()),
+            Message::ElectionStatusResponse(_) => Message::None,
+            Message::ElectionResultRequest => Message::ElectionResultResponse(self.get_election_status(), self.calculate_result()),
+            Message::ElectionResultResponse(_, _) => Message::None,
+            Message::StatusRequest => Message::StatusResponse(self.get_status()),
+            Message::StatusResponse(_) => Message::None,
+            Message::MetricsRequest => Message::MetricsResponse(self.get_metrics()),
+            Message::MetricsResponse(_) => Message::None,
+            // the reachability of the sealers is only revealed to RPC clients and admins
+            Message::PeerStatusRequest => Message::None,
+            Message::PeerStatusResponse(_) => Message::None,
+            Message::Handshake(handshake) => self.on_handshake(handshake),
+            Message::HandshakeAccept => Message::None,
+            Message::HandshakeAcceptCodec(_) => Message::None,
+            Message::TimeRequest => Message::TimeResponse(clock::now_millis()),
+            Message::TimeResponse(_) => Message::None,
+            Message::GenesisMismatch(_) => Message::None,
+            Message::FeatureMismatch(_) => Message::None,
+            // the node broadcasts resulting messages, hence this is only reached by direct callers
+            Message::AdminRequest(request) => self.handle_admin_request(request).0,
+            Message::AdminResponse(_) => Message::None,
+            Message::JoinRequest(join_request) => {
+                let identifier = join_request.identifier.clone();
+                match self.add_join_request(join_request) {
+                    Ok(_) => Message::JoinRequestAccept(identifier),
+                    Err(reason) => Message::JoinRequestReject(identifier, reason)
+                }
+            }
+            Message::JoinRequestAccept(_) => Message::None,
+            Message::JoinRequestReject(_, _) => Message::None,
+            Message::PendingJoinRequests => Message::PendingJoinRequestsResponse(self.get_join_requests()),
+            Message::PendingJoinRequestsResponse(_) => Message::None,
+            // payloads are only revealed to admins over RPC or the admin channel
+            Message::MempoolRequest { .. } => Message::MempoolResponse(self.get_mempool(false)),
+            Message::MempoolResponse(_) => Message::None,
+            Message::MempoolAnnouncement(transaction_identifiers) => Message::MempoolAnnouncementResponse(self.get_unknown_transactions(transaction_identifiers)),
+            Message::MempoolAnnouncementResponse(_) => Message::None,
+            // every node gossips its own mempool, hence received transactions are not broadcast again
+            Message::PendingTransactions(transactions) => Message::PendingTransactionsAccept(self.on_pending_transactions_receive(transactions)),
+            Message::PendingTransactionsAccept(_) => Message::None,
+            // votes are only proposed by the admin of the voting sealer
+            Message::SealerVoteProposal { .. } => Message::None,
+            Message::SealerVoteReject(_) => Message::None,
+            Message::DkgShare(dealt_share) => match self.on_dkg_share(dealt_share) {
+                Ok(()) => Message::DkgShareAccept,
+                Err(reason) => Message::DkgShareReject(reason)
+            },
+            Message::DkgShareAccept => Message::None,
+            Message::DkgShareReject(_) => Message::None,
+            Message::BranchAbandoned(tip) => {
+                self.acknowledge_abandoned_branch(tip);
+
+                Message::None
+            }
+            // relay connections are registered by the node, hence this is only reached by direct callers
+            Message::RelayRegister(_) => Message::None,
+            Message::BlockRangeRequest { from_height, to_height } => Message::BlockRangeResponse(self.get_block_range(from_height, to_height)),
+            Message::BlockRangeResponse(_) => Message::None,
+            Message::BlockByNumberRequest(height) => Message::BlockByNumberResponse(self.get_block_by_number(height)),
+            Message::BlockByNumberResponse(_) => Message::None,
+            Message::BlocksAtHeightRequest(height) => Message::BlocksAtHeightResponse(self.get_blocks_at_height(height)),
+            Message::BlocksAtHeightResponse(_) => Message::None,
+            Message::HeadQuery => match self.get_head() {
+                Some(head) => Message::HeadQueryResponse(head.height, head.block.identifier),
+                None => Message::None
+            },
+            Message::HeadQueryResponse(_, _) => Message::None,
+            Message::RpcAuthorized(_, _) => Message::None,
+            Message::RpcUnauthorized(_) => Message::None,
+            // dummy traffic, nothing to do
+            Message::Padding(_) => Message::None
         }
     }
 
@@ -321,44 +3016,604 @@ impl ProtocolHandler for CliqueProtocol {
                 // which means that we have to add it to our set of known
                 // transactions (in case we are a co-/leader) and then
                 // notify all other nodes in the network about this new transaction.
-                self.on_transaction_receive(transaction.clone());
-
-                Some((Message::TransactionAccept(transaction.identifier.clone()), Message::TransactionPayload(transaction)))
+                match self.on_transaction_receive(transaction.clone()) {
+                    Ok(None) => Some((Message::TransactionAccept(transaction.identifier.clone()), Message::TransactionPayload(transaction))),
+                    // other nodes know the transaction from the block including it
+                    Ok(Some(inclusion)) => Some((Message::TransactionIncluded(inclusion), Message::None)),
+                    // no need to bother other nodes with rejected transactions
+                    Err(reason) => Some((Message::TransactionReject(transaction.identifier.clone(), reason), Message::None))
+                }
             }
             Message::TransactionAccept(_) => None,
+            Message::TransactionReject(_, _) => None,
+            Message::TransactionIncluded(_) => None,
             Message::BlockRequest(_) => None,
             Message::BlockPayload(_) => None,
             Message::BlockAccept => None,
             Message::BlockDuplicated => None,
-            Message::ChainRequest => Some((Message::ChainResponse(self.chain.clone()), Message::None)),
+            Message::BlockReject(_, _) => None,
+            Message::ChainRequest => Some((Message::ChainResponse(Box::new(self.chain.clone())), Message::None)),
             Message::ChainResponse(_) => None,
             Message::ChainAccept => None,
-            // TODO: add flag to chain
+            Message::ChainChunkRequest(token) => Some((Message::ChainChunkResponse(self.get_chain_chunk(token)), Message::None)),
+            Message::ChainChunkResponse(_) => None,
+            Message::SnapshotRequest(height) => Some((Message::SnapshotResponse(self.get_state_snapshot(height)), Message::None)),
+            Message::SnapshotResponse(_) => None,
+            // the voting is opened once, hence other nodes are only notified the first time.
+            // They receive the transaction itself, as its identifier depends on the time it was created at
             Message::OpenVote => {
-                self.on_transaction_receive(Transaction::new_voting_opened());
-
-                Some((Message::OpenVoteAccept, Message::OpenVote))
+                let transaction = self.create_phase_change(TransactionType::VoteOpened);
+                Some(self.on_phase_change_receive(transaction, Message::OpenVoteAccept))
             },
             Message::OpenVoteAccept => None,
-            // TODO: add flag to chain
             Message::CloseVote => {
-                self.on_transaction_receive(Transaction::new_voting_closed());
-
-                Some((Message::CloseVoteAccept, Message::CloseVote))
+                let transaction = self.create_phase_change(TransactionType::VoteClosed);
+                Some(self.on_phase_change_receive(transaction, Message::CloseVoteAccept))
             },
             Message::CloseVoteAccept => None,
+            Message::SignedOpenVote(phase_change) => {
+                let transaction = Transaction::from_phase_change(self.genesis.clique.get_hasher(), TransactionType::VoteOpened, phase_change);
+                Some(self.on_phase_change_receive(transaction, Message::OpenVoteAccept))
+            },
+            Message::SignedCloseVote(phase_change) => {
+                let transaction = Transaction::from_phase_change(self.genesis.clique.get_hasher(), TransactionType::VoteClosed, phase_change);
+                Some(self.on_phase_change_receive(transaction, Message::CloseVoteAccept))
+            },
             Message::RequestTally => {
                 let final_tally = self.calculate_result();
 
                 Some((Message::RequestTallyPayload(final_tally), Message::None))
             }
             Message::RequestTallyPayload(_) => None,
+            // the node keeps subscriptions open, hence this is only reached by direct callers
+            Message::TurnoutSubscribe => Some((Message::TurnoutUpdate(self.get_turnout()), Message::None)),
+            Message::TurnoutUpdate(_) => None,
             Message::FindTransaction(identifier) => {
                 let found_trx = self.find_transaction(identifier);
 
                 Some((Message::FindTransactionResponse(found_trx), Message::None))
             },
-            Message::FindTransactionResponse(_) => None
+            Message::FindTransactionResponse(_) => None,
+            Message::InclusionProofRequest(identifier) => Some((Message::InclusionProofResponse(self.get_inclusion_proof(&identifier)), Message::None)),
+            Message::InclusionProofResponse(_) => None,
+            Message::ReceiptRequest(identifier) => Some((Message::ReceiptResponse(self.get_receipt_status(&identifier)), Message::None)),
+            Message::ReceiptResponse(_) => None,
+            Message::QueryTransactions(query) => Some((Message::QueryTransactionsResponse(self.query_transactions(query)), Message::None)),
+            Message::QueryTransactionsResponse(_) => None,
+            Message::SealerStatisticsRequest => Some((Message::SealerStatisticsResponse(self.calculate_sealer_statistics()), Message::None)),
+            Message::SealerStatisticsResponse(_) => None,
+            Message::EpochStatisticsRequest => Some((Message::EpochStatisticsResponse(self.get_epoch_statistics()), Message::None)),
+            Message::EpochStatisticsResponse(_) => None,
+            Message::ElectionStatusRequest => Some((Message::ElectionStatusResponse(self.get_election_status()), Message::None)),
+            Message::ElectionStatusResponse(_) => None,
+            Message::ElectionResultRequest => Some((Message::ElectionResultResponse(self.get_election_status(), self.calculate_result()), Message::None)),
+            Message::ElectionResultResponse(_, _) => None,
+            Message::StatusRequest => Some((Message::StatusResponse(self.get_status()), Message::None)),
+            Message::StatusResponse(_) => None,
+            Message::MetricsRequest => Some((Message::MetricsResponse(self.get_metrics()), Message::None)),
+            Message::MetricsResponse(_) => None,
+            Message::PeerStatusRequest => Some((Message::PeerStatusResponse(self.get_peer_status()), Message::None)),
+            Message::PeerStatusResponse(_) => None,
+            // handshakes are exchanged between nodes only
+            Message::Handshake(_) => None,
+            Message::HandshakeAccept => None,
+            Message::HandshakeAcceptCodec(_) => None,
+            Message::TimeRequest => None,
+            Message::TimeResponse(_) => None,
+            Message::GenesisMismatch(_) => None,
+            Message::FeatureMismatch(_) => None,
+            // admin commands are only accepted over the peer protocol
+            Message::AdminRequest(_) => None,
+            Message::AdminResponse(_) => None,
+            Message::JoinRequest(join_request) => {
+                let identifier = join_request.identifier.clone();
+                match self.add_join_request(join_request.clone()) {
+                    // let all other authorities know about the candidate
+                    Ok(true) => Some((Message::JoinRequestAccept(identifier), Message::JoinRequest(join_request))),
+                    Ok(false) => Some((Message::JoinRequestAccept(identifier), Message::None)),
+                    Err(reason) => Some((Message::JoinRequestReject(identifier, reason), Message::None))
+                }
+            }
+            Message::JoinRequestAccept(_) => None,
+            Message::JoinRequestReject(_, _) => None,
+            Message::PendingJoinRequests => Some((Message::PendingJoinRequestsResponse(self.get_join_requests()), Message::None)),
+            Message::PendingJoinRequestsResponse(_) => None,
+            Message::MempoolRequest { include_payloads } => Some((Message::MempoolResponse(self.get_mempool(include_payloads)), Message::None)),
+            Message::MempoolResponse(_) => None,
+            // mempools are only gossiped between nodes, clients submit transactions directly
+            Message::MempoolAnnouncement(_) => None,
+            Message::MempoolAnnouncementResponse(_) => None,
+            Message::PendingTransactions(_) => None,
+            Message::PendingTransactionsAccept(_) => None,
+            // the vote only counts in a block sealed by this node, hence it is not broadcast
+            Message::SealerVoteProposal { candidate, authorize, public_key } => match self.propose_sealer_vote(candidate, authorize, public_key) {
+                Ok(transaction) => Some((Message::TransactionAccept(transaction.identifier), Message::None)),
+                Err(reason) => Some((Message::SealerVoteReject(reason), Message::None))
+            },
+            Message::SealerVoteReject(_) => None,
+            // shares are only dealt between sealers
+            Message::DkgShare(_) => None,
+            Message::DkgShareAccept => None,
+            Message::DkgShareReject(_) => None,
+            Message::BranchAbandoned(_) => None,
+            Message::RelayRegister(_) => None,
+            Message::BlockRangeRequest { from_height, to_height } => Some((Message::BlockRangeResponse(self.get_block_range(from_height, to_height)), Message::None)),
+            Message::BlockRangeResponse(_) => None,
+            Message::BlockByNumberRequest(height) => Some((Message::BlockByNumberResponse(self.get_block_by_number(height)), Message::None)),
+            Message::BlockByNumberResponse(_) => None,
+            Message::BlocksAtHeightRequest(height) => Some((Message::BlocksAtHeightResponse(self.get_blocks_at_height(height)), Message::None)),
+            Message::BlocksAtHeightResponse(_) => None,
+            Message::HeadQuery => self.get_head().map(|head| (Message::HeadQueryResponse(head.height, head.block.identifier), Message::None)),
+            Message::HeadQueryResponse(_, _) => None,
+            Message::RpcAuthorized(_, _) => None,
+            Message::RpcUnauthorized(_) => None,
+            Message::Padding(_) => None
+        }
+    }
+}
+#[cfg(test)]
+mod clique_test {
+
+    use super::*;
+    use ::chain::block_store::MemoryBlockStore;
+    use ::onboarding::CandidateIdentity;
+    use crypto_rs::cai::uciv::{CaiProof, PreImageSet};
+    use crypto_rs::el_gamal::encryption::encrypt;
+    use crypto_rs::el_gamal::membership_proof::MembershipProof;
+    use num::One;
+    use std::sync::Mutex;
+
+    fn new_protocol() -> CliqueProtocol {
+        let genesis = Genesis::new("genesis.json", "public_uciv.json", "public_key.json");
+        CliqueProtocol::new(genesis.sealer[0], genesis, VersionPolicy::Ignore)
+    }
+
+    fn new_block(parent: &Block, sealer: SocketAddr, transactions: Vec<Transaction>) -> Block {
+        let mut block = Block::new(Hasher::default(), parent.identifier.clone(), Some(sealer), transactions);
+        block.data.timestamp = parent.data.timestamp + 1;
+        block.identifier = block.get_header().unwrap().hash(Hasher::default());
+
+        block
+    }
+
+    /// Seal the given amount of blocks on top of the given parent, by the given sealers in turn.
+    fn new_blocks(parent: &Block, sealers: &[SocketAddr], amount: usize) -> Vec<Block> {
+        let mut blocks: Vec<Block> = vec![];
+        for index in 0..amount {
+            let block = new_block(blocks.last().unwrap_or(parent), sealers[index % sealers.len()], vec![]);
+            blocks.push(block);
+        }
+
+        blocks
+    }
+
+    /// Create a vote of the voter with the given index, whose proofs are not valid.
+    fn new_vote(voter_idx: usize) -> Transaction {
+        let public_key = PublicKey { p: ModInt::one(), q: ModInt::one(), h: ModInt::one(), g: ModInt::one() };
+        let cipher_text = CipherText { big_g: ModInt::one(), big_h: ModInt::one(), random: ModInt::one() };
+
+        Transaction::new_vote(
+            Hasher::default(),
+            voter_idx,
+            cipher_text.clone(),
+            MembershipProof::new(public_key.clone(), ModInt::one(), cipher_text.clone(), vec![ModInt::one()]),
+            CaiProof::new(public_key, cipher_text, PreImageSet { pre_images: vec![ModInt::one()] }, ImageSet { images: vec![ModInt::one()] }, 0, vec![ModInt::one()]),
+        )
+    }
+
+    #[test]
+    fn test_handshake() {
+        let mut protocol = new_protocol();
+        let peer_addr = protocol.genesis.sealer[1];
+        let genesis_configuration_hash = protocol.get_genesis_configuration_hash();
+
+        // peers running the same genesis configuration are accepted, those announcing codecs with one of them
+        let handshake = protocol.create_handshake(peer_addr);
+        assert_eq!(Message::HandshakeAcceptCodec(codec::negotiate(&handshake.codecs).name().to_string()), protocol.handle(Message::Handshake(handshake.clone())));
+        let codecless_handshake = Handshake { codecs: vec![], ..handshake.clone() };
+        assert_eq!(Message::HandshakeAccept, protocol.handle(Message::Handshake(codecless_handshake)));
+
+        // peers running a different one learn the own hash
+        let mismatching_handshake = Handshake { genesis_configuration_hash: "other".to_string(), ..handshake };
+        assert_eq!(Message::GenesisMismatch(genesis_configuration_hash), protocol.handle(Message::Handshake(mismatching_handshake)));
+    }
+
+    #[test]
+    fn test_check_election_phase() {
+        let mut protocol = new_protocol();
+        let sealers = protocol.genesis.sealer.clone();
+        let genesis_block = protocol.chain.blocks[&protocol.chain.genesis_identifier_hash].clone();
+        let election = protocol.get_genesis_configuration_hash();
+        let vote = new_vote(0);
+
+        assert_eq!(ElectionPhase::NotOpened, protocol.get_election_phase());
+        assert_eq!(Err("Voting is not opened yet".to_string()), protocol.on_transaction_receive(vote.clone()));
+        assert_eq!(Err("Voting is not opened yet".to_string()), protocol.on_transaction_receive(Transaction::new_voting_closed(Hasher::default(), election.clone(), 1)));
+
+        let opening_block = new_block(&genesis_block, sealers[0], vec![Transaction::new_voting_opened(Hasher::default(), election.clone(), 1)]);
+        assert_eq!(Ok(1), protocol.add_block_range(vec![opening_block.clone()]));
+        assert_eq!(ElectionPhase::Opened, protocol.get_election_phase());
+        assert_eq!(Ok(()), protocol.check_election_phase(&vote));
+        assert_eq!(Err("Voting is already opened".to_string()), protocol.on_transaction_receive(Transaction::new_voting_opened(Hasher::default(), election.clone(), 2)));
+
+        let closing_block = new_block(&opening_block, sealers[1], vec![Transaction::new_voting_closed(Hasher::default(), election.clone(), 2)]);
+        assert_eq!(Ok(1), protocol.add_block_range(vec![closing_block.clone()]));
+        assert_eq!(ElectionPhase::Closed, protocol.get_election_phase());
+        assert_eq!(Err("Voting is closed".to_string()), protocol.on_transaction_receive(vote.clone()));
+
+        // the phase follows the head onto a heavier branch which did not close the voting yet
+        let branch = new_blocks(&opening_block, &[sealers[1], sealers[2], sealers[0]], 3);
+        assert_eq!(Ok(3), protocol.add_block_range(branch.clone()));
+        assert_eq!(branch[2].identifier, protocol.get_head().unwrap().block.identifier);
+        assert_eq!(ElectionPhase::Opened, protocol.get_election_phase());
+        assert_eq!(Ok(()), protocol.check_election_phase(&vote));
+
+        // a chain which is not indexed finds the phase by walking it
+        let mut chain = protocol.chain.clone();
+        assert_eq!(Ok(Some(ElectionPhase::Closed)), chain.election_phase_of(&closing_block.identifier));
+        chain.blocks.remove(&branch[2].identifier);
+        assert_eq!(Ok(Some(ElectionPhase::Closed)), chain.election_phase_of(&closing_block.identifier));
+        assert_eq!(Ok(Some(ElectionPhase::Opened)), chain.election_phase_of(&branch[1].identifier));
+        assert_eq!(Ok(Some(ElectionPhase::NotOpened)), chain.election_phase_of(&genesis_block.identifier));
+    }
+
+    #[test]
+    fn test_purge_stale_transactions() {
+        let mut protocol = new_protocol();
+        let sealers = protocol.genesis.sealer.clone();
+        let genesis_block = protocol.chain.blocks[&protocol.chain.genesis_identifier_hash].clone();
+        let election = protocol.get_genesis_configuration_hash();
+
+        let opening_block = new_block(&genesis_block, sealers[0], vec![Transaction::new_voting_opened(Hasher::default(), election.clone(), 1)]);
+        assert_eq!(Ok(1), protocol.add_block_range(vec![opening_block.clone()]));
+        let vote = new_vote(0);
+        assert_eq!(Ok(true), protocol.mempool.insert(PendingTransaction::new(vote.clone())));
+
+        // closing the voting on the canonical chain may still be reverted, hence the vote is kept
+        let closing_block = new_block(&opening_block, sealers[1], vec![Transaction::new_voting_closed(Hasher::default(), election.clone(), 2)]);
+        assert_eq!(Ok(1), protocol.add_block_range(vec![closing_block.clone()]));
+        assert!(protocol.is_voting_closed());
+        assert!(protocol.mempool.contains(&vote));
+        let next_block = new_block(&closing_block, sealers[2], vec![]);
+        assert_eq!(Ok(1), protocol.add_block_range(vec![next_block.clone()]));
+        assert!(protocol.get_finalized_height() < Height::new(2));
+        assert!(protocol.mempool.contains(&vote));
+
+        // once more than half of the sealers built on the closing block, the vote is purged
+        assert_eq!(Ok(1), protocol.add_block_range(vec![new_block(&next_block, sealers[0], vec![])]));
+        assert_eq!(Height::new(2), protocol.get_finalized_height());
+        assert!(!protocol.mempool.contains(&vote));
+    }
+
+    #[test]
+    fn test_vote_option_count() {
+        let mut protocol = new_protocol();
+        let sealers = protocol.genesis.sealer.clone();
+        let genesis_block = protocol.chain.blocks[&protocol.chain.genesis_identifier_hash].clone();
+        let election = protocol.get_genesis_configuration_hash();
+        let registry = TransactionRegistry::with_default_kinds();
+        let voting_options = protocol.genesis.get_voting_options();
+        let dummy_vote = new_vote(0).data.unwrap();
+        let vote = Transaction::new_vote(Hasher::default(), 0, encrypt(&protocol.genesis.public_key, voting_options[0].clone()), dummy_vote.membership_proof, dummy_vote.cai_proof);
+
+        // a voter holding images for another amount of options is rejected rather than failing the node
+        let mismatching_image_sets = vec![ImageSet { images: vec![ModInt::one(); voting_options.len() + 1] }];
+        assert!(!registry.is_valid(&vote, &protocol.genesis.public_key, &mismatching_image_sets, &BTreeMap::new(), &voting_options, None));
+        let missing_image_sets = vec![ImageSet { images: vec![ModInt::one(); voting_options.len() - 1] }];
+        assert!(!registry.is_valid(&vote, &protocol.genesis.public_key, &missing_image_sets, &BTreeMap::new(), &voting_options, None));
+
+        // a vote encrypting one of the declared options is counted,
+        // its proofs having been verified when its block was received
+        let opening_block = new_block(&genesis_block, sealers[0], vec![Transaction::new_voting_opened(Hasher::default(), election.clone(), 1)]);
+        let vote_block = new_block(&opening_block, sealers[1], vec![vote]);
+        let closing_block = new_block(&vote_block, sealers[2], vec![Transaction::new_voting_closed(Hasher::default(), election, 2)]);
+        assert!(protocol.chain.add_block(opening_block));
+        assert!(protocol.chain.add_block(vote_block));
+        assert!(protocol.chain.add_block(closing_block));
+        assert_eq!(1, protocol.sum_votes().get_votes_on(None).0);
+    }
+
+    #[test]
+    fn test_get_block_range() {
+        let mut protocol = new_protocol();
+        let sealers = protocol.genesis.sealer.clone();
+        let genesis_block = protocol.chain.blocks[&protocol.chain.genesis_identifier_hash].clone();
+        let blocks = new_blocks(&genesis_block, &sealers, 8);
+        assert_eq!(Ok(8), protocol.add_block_range(blocks.clone()));
+
+        let block_range = protocol.get_block_range(Height::new(2), Height::new(4));
+        assert_eq!(Height::new(8), block_range.head_height);
+        assert_eq!(Height::new(2), block_range.from_height);
+        assert_eq!(blocks[1..4].to_vec(), block_range.blocks);
+
+        // ranges are bounded by the head
+        assert_eq!(blocks[6..8].to_vec(), protocol.get_block_range(Height::new(7), Height::new(20)).blocks);
+        assert!(protocol.get_block_range(Height::new(9), Height::new(20)).blocks.is_empty());
+        assert!(protocol.get_block_range(Height::new(4), Height::new(2)).blocks.is_empty());
+
+        // a chain starting with a snapshot has no blocks below its root
+        let state_snapshot = protocol.get_state_snapshot(Some(Height::new(3))).unwrap();
+        let mut snapshot_protocol = new_protocol();
+        assert_eq!(Ok(()), snapshot_protocol.adopt_state_snapshot(state_snapshot));
+        assert_eq!(Ok(5), snapshot_protocol.add_block_range(blocks[3..].to_vec()));
+
+        let block_range = snapshot_protocol.get_block_range(Height::new(1), Height::new(5));
+        assert_eq!(Height::new(8), block_range.head_height);
+        assert!(block_range.blocks.is_empty());
+        assert_eq!(blocks[2..5].to_vec(), snapshot_protocol.get_block_range(Height::new(3), Height::new(5)).blocks);
+        assert_eq!(blocks[5..8].to_vec(), snapshot_protocol.get_block_range(Height::new(6), Height::new(8)).blocks);
+    }
+
+    #[test]
+    fn test_acknowledge_abandoned_branch() {
+        let mut protocol = new_protocol();
+        let sealers = protocol.genesis.sealer.clone();
+        let genesis_block = protocol.chain.blocks[&protocol.chain.genesis_identifier_hash].clone();
+
+        // a branch forking off at the genesis block falls behind the canonical chain
+        let transaction = Transaction::new_voting_opened(Hasher::default(), protocol.chain.genesis_configuration_hash.clone(), 0);
+        let stale_block = new_block(&genesis_block, sealers[1], vec![transaction.clone()]);
+        assert!(protocol.chain.add_block(stale_block.clone()));
+        let canonical_blocks = new_blocks(&genesis_block, &sealers, BRANCH_ABANDON_DEPTH + 1);
+        assert_eq!(Ok(BRANCH_ABANDON_DEPTH + 1), protocol.add_block_range(canonical_blocks.clone()));
+        let head = protocol.get_head().unwrap();
+        assert_eq!(canonical_blocks.last().unwrap().identifier, head.block.identifier);
+        // moving the head off the stale branch requeued its transaction already
+        assert!(protocol.mempool.contains(&transaction));
+        protocol.mempool.retain(|_| false);
+
+        // a peer whose fork choice differs claims our head is abandoned
+        assert!(!protocol.acknowledge_abandoned_branch(head.block.identifier.clone()));
+        assert!(!protocol.acknowledge_abandoned_branch(BlockHash::from("unknown")));
+        assert!(protocol.abandoned_branches.is_empty());
+        assert!(protocol.mempool.is_empty());
+        assert_eq!(head.block.identifier, protocol.get_head().unwrap().block.identifier);
+
+        // the claim on the stale branch is confirmed, and its transactions are requeued once
+        assert!(protocol.acknowledge_abandoned_branch(stale_block.identifier.clone()));
+        assert!(protocol.abandoned_branches.contains(&stale_block.identifier));
+        assert!(protocol.mempool.contains(&transaction));
+        assert!(protocol.abandon_stale_branches().is_empty());
+        assert!(protocol.acknowledge_abandoned_branch(stale_block.identifier.clone()));
+        assert_eq!(1, protocol.mempool.len());
+        assert_eq!(head.block.identifier, protocol.get_head().unwrap().block.identifier);
+    }
+
+    #[test]
+    fn test_set_block_store_validates_stored_chain() {
+        let mut protocol = new_protocol();
+        let sealers = protocol.genesis.sealer.clone();
+        let genesis_block = protocol.chain.blocks[&protocol.chain.genesis_identifier_hash].clone();
+        let genesis_chain = protocol.chain.clone();
+        let mut stored_chain = genesis_chain.clone();
+        stored_chain.add_block(new_block(&genesis_block, sealers[0], vec![]));
+        let mut block_store = MemoryBlockStore::new();
+        block_store.replace(&stored_chain).unwrap();
+
+        // starting fresh refuses the stored chain, but not an empty store
+        assert!(new_protocol().set_block_store(Box::new(MemoryBlockStore::new()), true).is_ok());
+        let mut refused_store = MemoryBlockStore::new();
+        refused_store.replace(&stored_chain).unwrap();
+        assert!(protocol.set_block_store(Box::new(refused_store), true).is_err());
+        assert_eq!(Height::GENESIS, protocol.get_head().unwrap().height);
+
+        assert_eq!(Ok(true), protocol.set_block_store(Box::new(block_store), false));
+        assert_eq!(Height::new(1), protocol.get_head().unwrap().height);
+
+        // the block is sealed by a node which is not a sealer
+        // the genesis block is stamped with the time the protocol was created, hence it is shared with the other chains
+        let mut invalid_chain = genesis_chain.clone();
+        invalid_chain.add_block(new_block(&genesis_block, "127.0.0.1:9999".parse().unwrap(), vec![]));
+        let mut invalid_store = MemoryBlockStore::new();
+        invalid_store.replace(&invalid_chain).unwrap();
+        let mut protocol = new_protocol();
+        assert!(protocol.set_block_store(Box::new(invalid_store), false).is_err());
+        assert_eq!(Height::GENESIS, protocol.get_head().unwrap().height);
+    }
+
+    #[test]
+    fn test_on_head_changed() {
+        let mut protocol = new_protocol();
+        let sealers = protocol.genesis.sealer.clone();
+        let genesis_block = protocol.chain.blocks[&protocol.chain.genesis_identifier_hash].clone();
+        let head_changes: Arc<Mutex<Vec<(HeadInfo, HeadInfo)>>> = Arc::new(Mutex::new(vec![]));
+        let cloned_head_changes = Arc::clone(&head_changes);
+        protocol.on_head_changed(move |old_head, new_head| cloned_head_changes.lock().unwrap().push((old_head.clone(), new_head.clone())));
+
+        let leader_block = new_block(&genesis_block, sealers[0], vec![]);
+        assert_eq!(Ok(1), protocol.add_block_range(vec![leader_block.clone()]));
+
+        // blocks of a lighter branch do not change the head
+        let branch = new_blocks(&genesis_block, &[sealers[1], sealers[2], sealers[0]], 3);
+        assert_eq!(Ok(2), protocol.add_block_range(branch[..2].to_vec()));
+        assert_eq!(1, head_changes.lock().unwrap().len());
+
+        // once the branch outweighs the own one, the previous head is reverted
+        assert_eq!(Ok(1), protocol.add_block_range(vec![branch[2].clone()]));
+        let head_changes = head_changes.lock().unwrap();
+        let identifiers: Vec<(BlockHash, BlockHash)> = head_changes.iter()
+            .map(|(old_head, new_head)| (old_head.block.identifier.clone(), new_head.block.identifier.clone()))
+            .collect();
+        assert_eq!(vec![
+            (genesis_block.identifier.clone(), leader_block.identifier.clone()),
+            (leader_block.identifier.clone(), branch[2].identifier.clone()),
+        ], identifiers);
+        assert!(!protocol.is_reorganization(&head_changes[0].0));
+        assert!(protocol.is_reorganization(&head_changes[1].0));
+    }
+
+    #[test]
+    fn test_prune_stale_branches() {
+        let mut protocol = new_protocol();
+        let sealers = protocol.genesis.sealer.clone();
+        let genesis_block = protocol.chain.blocks[&protocol.chain.genesis_identifier_hash].clone();
+        protocol.set_metrics_registry(Arc::new(MetricsRegistry::new()));
+        assert_eq!(Ok(false), protocol.set_block_store(Box::new(MemoryBlockStore::new()), true));
+
+        let stale_block = new_block(&genesis_block, sealers[1], vec![]);
+        assert_eq!(Ok(1), protocol.add_block_range(vec![stale_block.clone()]));
+        let blocks = new_blocks(&genesis_block, &sealers, 3);
+        assert_eq!(Ok(2), protocol.add_block_range(blocks[..2].to_vec()));
+
+        // the fork is kept as long as the height it forked off at is not finalized
+        assert_eq!(Height::GENESIS, protocol.get_finalized_height());
+        assert_eq!(0, protocol.prune_stale_branches());
+        assert!(protocol.chain.blocks.contains_key(&stale_block.identifier));
+
+        assert_eq!(Ok(1), protocol.add_block_range(vec![blocks[2].clone()]));
+        assert_eq!(Height::new(1), protocol.get_finalized_height());
+        assert_eq!(1, protocol.prune_stale_branches());
+        assert!(!protocol.chain.blocks.contains_key(&stale_block.identifier));
+        assert_eq!(blocks[2].identifier, protocol.get_head().unwrap().block.identifier);
+
+        // the block store is rewritten without the pruned blocks
+        let stored_chain = protocol.block_store.as_ref().unwrap().load().unwrap().unwrap();
+        assert!(!stored_chain.blocks.contains_key(&stale_block.identifier));
+        assert_eq!(protocol.chain.blocks.len(), stored_chain.blocks.len());
+
+        let pruning = protocol.get_metrics().pruning;
+        assert_eq!(1, pruning.passes);
+        assert_eq!(1, pruning.blocks);
+        assert!(pruning.bytes > 0);
+
+        // passes without any stale blocks are not counted
+        assert_eq!(0, protocol.prune_stale_branches());
+        assert_eq!(1, protocol.get_metrics().pruning.passes);
+    }
+
+    #[test]
+    fn test_create_current_block() {
+        let mut protocol = new_protocol();
+        let genesis_identifier = protocol.chain.genesis_identifier_hash.to_string();
+        let later = PendingTransaction::arrived_at(Transaction::new_voting_opened(Hasher::default(), genesis_identifier.clone(), 0), 2);
+        let mut simultaneous = vec![
+            PendingTransaction::arrived_at(Transaction::new_voting_opened(Hasher::default(), genesis_identifier.clone(), 1), 1),
+            PendingTransaction::arrived_at(Transaction::new_voting_closed(Hasher::default(), genesis_identifier.clone(), 2), 1),
+        ];
+        assert_eq!(Ok(true), protocol.mempool.insert(later.clone()));
+        assert_eq!(Ok(true), protocol.mempool.insert(simultaneous[1].clone()));
+        assert_eq!(Ok(true), protocol.mempool.insert(simultaneous[0].clone()));
+
+        // transactions are ordered by their time of arrival, then by their identifier
+        simultaneous.sort_by(|first, second| first.transaction.identifier.cmp(&second.transaction.identifier));
+        let expected_transactions = vec![simultaneous[0].transaction.clone(), simultaneous[1].transaction.clone(), later.transaction.clone()];
+        let block = protocol.create_current_block().unwrap();
+        assert_eq!(expected_transactions, block.data.transactions);
+
+        // the transactions of a block which was not sealed are included again in the next one
+        assert_eq!(3, protocol.mempool.len());
+        assert_eq!(expected_transactions, protocol.create_current_block().unwrap().data.transactions);
+
+        // arriving later than the pending transactions, a transaction is appended to the next block
+        let latest = PendingTransaction::arrived_at(Transaction::new_voting_closed(Hasher::default(), genesis_identifier.clone(), 3), 3);
+        assert_eq!(Ok(true), protocol.mempool.insert(latest.clone()));
+        assert_eq!(Some(&latest.transaction), protocol.create_current_block().unwrap().data.transactions.last());
+
+        // once the block is added to the chain, only the transactions it did not include remain pending
+        assert!(protocol.sign(block).is_some());
+        assert_eq!(vec![latest.transaction.clone()], protocol.create_current_block().unwrap().data.transactions);
+
+        // nodes which are not sealers do not create blocks
+        let genesis = Genesis::new("genesis.json", "public_uciv.json", "public_key.json");
+        assert!(CliqueProtocol::new("127.0.0.1:9999".parse().unwrap(), genesis, VersionPolicy::Ignore).create_current_block().is_none());
+    }
+
+    #[test]
+    fn test_add_join_request() {
+        let mut protocol = new_protocol();
+        let genesis_configuration_hash = protocol.get_genesis_configuration_hash();
+        let new_join_request = |address: &str| JoinRequest::new(&CandidateIdentity::generate(), address.parse().unwrap(), genesis_configuration_hash.clone(), NODE_VERSION);
+
+        let join_request = new_join_request("10.0.0.1:9000");
+        assert_eq!(Ok(true), protocol.add_join_request(join_request.clone()));
+        assert_eq!(Ok(false), protocol.add_join_request(join_request.clone()));
+
+        // requests issued long ago or in the future are not current
+        let mut stale_request = new_join_request("10.0.0.2:9000");
+        stale_request.timestamp -= JOIN_REQUEST_LIFETIME;
+        assert!(protocol.add_join_request(stale_request).is_err());
+        let mut future_request = new_join_request("10.0.0.2:9000");
+        future_request.timestamp += 2 * MAX_TIMESTAMP_DRIFT;
+        assert!(protocol.add_join_request(future_request).is_err());
+
+        // a single host only occupies a few places
+        for port in 1..MAX_JOIN_REQUESTS_PER_HOST {
+            assert_eq!(Ok(true), protocol.add_join_request(new_join_request(&format!("10.0.0.1:{}", 9000 + port))));
+        }
+        assert!(protocol.add_join_request(new_join_request("10.0.0.1:9100")).is_err());
+        assert_eq!(MAX_JOIN_REQUESTS_PER_HOST, protocol.get_join_requests().len());
+
+        // once all places are taken, only requests pending for too long make room for new ones
+        protocol.join_requests.clear();
+        for index in 0..MAX_PENDING_JOIN_REQUESTS {
+            let mut pending_request = new_join_request(&format!("10.1.{}.{}:9000", index / 250, index % 250));
+            if index == 0 {
+                pending_request.timestamp -= JOIN_REQUEST_LIFETIME;
+            }
+            protocol.join_requests.push(pending_request);
         }
+        assert_eq!(Ok(true), protocol.add_join_request(new_join_request("10.0.0.3:9000")));
+        assert_eq!(MAX_PENDING_JOIN_REQUESTS, protocol.get_join_requests().len());
+        assert!(protocol.add_join_request(new_join_request("10.0.0.4:9000")).is_err());
+    }
+
+    #[test]
+    fn test_chain_chunk_continues_after_token() {
+        let mut protocol = new_protocol();
+        let sealers = protocol.genesis.sealer.clone();
+        let genesis_block = protocol.chain.blocks[&protocol.chain.genesis_identifier_hash].clone();
+        let first_block = new_block(&genesis_block, sealers[0], vec![]);
+        protocol.chain.add_block(first_block.clone());
+
+        let first_chunk = protocol.get_chain_chunk(None);
+        assert_eq!(vec![genesis_block.clone(), first_block.clone()], first_chunk.blocks);
+        assert_eq!(None, first_chunk.next);
+
+        // blocks added after the token was issued follow it instead of the chunks starting over
+        let token = ChainChunkToken { height: Height::new(1), after: first_block.identifier.clone() };
+        let second_block = new_block(&first_block, sealers[1], vec![]);
+        protocol.chain.add_block(second_block.clone());
+        let fork_block = new_block(&genesis_block, sealers[1], vec![]);
+        protocol.chain.add_block(fork_block.clone());
+
+        let next_chunk = protocol.get_chain_chunk(Some(token));
+        assert!(!next_chunk.blocks.contains(&genesis_block));
+        assert!(!next_chunk.blocks.contains(&first_block));
+        assert_eq!(Some(&second_block), next_chunk.blocks.last());
+        assert_eq!(fork_block.identifier > first_block.identifier, next_chunk.blocks.contains(&fork_block));
+
+        let blocks_by_height = protocol.get_blocks_by_height();
+        let chunk = ChainChunk::new(protocol.get_genesis_configuration_hash(), &blocks_by_height[..2], None);
+        assert_eq!(2, chunk.blocks.len());
+        assert_eq!(None, chunk.next);
+        let token = ChainChunkToken { height: blocks_by_height[1].0, after: blocks_by_height[1].1.identifier.clone() };
+        let chunk = ChainChunk::new(protocol.get_genesis_configuration_hash(), &blocks_by_height, Some(&token));
+        assert_eq!(blocks_by_height[2..].iter().map(|entry| entry.1.clone()).collect::<Vec<Block>>(), chunk.blocks);
+    }
+
+    #[test]
+    fn test_replace_chain_validates_blocks() {
+        let mut protocol = new_protocol();
+        let sealers = protocol.genesis.sealer.clone();
+        let genesis_block = protocol.chain.blocks[&protocol.chain.genesis_identifier_hash].clone();
+
+        // the transactions of a block sealed in turn were swapped after sealing
+        let mut tampered_block = new_block(&genesis_block, sealers[0], vec![]);
+        tampered_block.data.transactions.push(Transaction::new_voting_opened(Hasher::default(), protocol.chain.genesis_configuration_hash.clone(), 0));
+        let mut tampered_chain = protocol.chain.clone();
+        tampered_chain.add_block(tampered_block);
+        protocol.replace_chain(tampered_chain);
+        assert_eq!(Height::GENESIS, protocol.get_head().unwrap().height);
+
+        // the block is sealed by a node which is not a sealer
+        let mut unknown_sealer_chain = protocol.chain.clone();
+        unknown_sealer_chain.add_block(new_block(&genesis_block, "127.0.0.1:9999".parse().unwrap(), vec![]));
+        protocol.replace_chain(unknown_sealer_chain);
+        assert_eq!(Height::GENESIS, protocol.get_head().unwrap().height);
+
+        let mut valid_chain = protocol.chain.clone();
+        valid_chain.add_block(new_block(&genesis_block, sealers[0], vec![]));
+        protocol.replace_chain(valid_chain);
+        assert_eq!(Height::new(1), protocol.get_head().unwrap().height);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,326 @@
+use ::chain::transaction::{Transaction, TransactionType};
+use serde_json;
+use std::slice;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A transaction waiting to be included in a block,
+/// along with the time it was received by this node.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct PendingTransaction {
+    pub transaction: Transaction,
+    /// The local time of arrival, in milliseconds since the epoch.
+    pub arrived_at: u64,
+    /// The size of the encoded transaction in bytes, accounted against the mempool cap.
+    #[serde(skip)]
+    size: usize,
+}
+
+impl PendingTransaction {
+    /// Wrap the given transaction, which has just arrived.
+    pub fn new(transaction: Transaction) -> PendingTransaction {
+        let since_the_epoch = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards");
+
+        PendingTransaction::arrived_at(transaction, since_the_epoch.as_secs() * 1000 + u64::from(since_the_epoch.subsec_nanos()) / 1_000_000)
+    }
+
+    /// Wrap the given transaction, which arrived at the given time in milliseconds since the epoch.
+    pub fn arrived_at(transaction: Transaction, arrived_at: u64) -> PendingTransaction {
+        let size = serde_json::to_string(&transaction).unwrap().len();
+
+        PendingTransaction {
+            transaction,
+            arrived_at,
+            size,
+        }
+    }
+
+    /// Returns the size of the encoded transaction in bytes.
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+}
+
+/// The summary of a pending transaction returned when inspecting the mempool.
+///
+/// The payload, i.e. the encrypted vote and its proofs, is only included on request of an admin,
+/// so that operators can see what is pending without learning more about the votes than the chain reveals later.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct MempoolEntry {
+    /// The identifier of the transaction.
+    pub identifier: String,
+    /// The type of the transaction.
+    pub trx_type: TransactionType,
+    /// The local time of arrival, in milliseconds since the epoch.
+    pub arrived_at: u64,
+    /// The size of the encoded transaction in bytes.
+    pub size: usize,
+    /// The whole transaction, or None if it is redacted.
+    pub payload: Option<Transaction>,
+}
+
+impl MempoolEntry {
+    /// Summarize the given pending transaction, including its payload only if requested.
+    pub fn new(pending: &PendingTransaction, include_payload: bool) -> MempoolEntry {
+        MempoolEntry {
+            identifier: pending.transaction.identifier.clone(),
+            trx_type: pending.transaction.trx_type.clone(),
+            arrived_at: pending.arrived_at,
+            size: pending.size,
+            payload: if include_payload {
+                Some(pending.transaction.clone())
+            } else {
+                None
+            },
+        }
+    }
+}
+
+/// The transactions waiting to be included in a block.
+///
+/// Every node maintains a mempool, regardless of whether it currently leads the sealing,
+/// so that whichever sealer leads next includes the transactions submitted in the meantime.
+/// A transaction is only removed once a block including it is added to the chain, or if it
+/// can never be included anymore. Blocks are assembled from a copy, hence the transactions
+/// of a block which is not sealed remain pending.
+#[derive(Serialize, Default)]
+pub struct Mempool {
+    transactions: Vec<PendingTransaction>,
+    /// The maximum amount of bytes of the pending transactions.
+    /// None, if the mempool is not capped.
+    #[serde(skip)]
+    cap: Option<usize>,
+}
+
+impl Mempool {
+    /// Create an empty mempool without cap.
+    pub fn new() -> Mempool {
+        Mempool {
+            transactions: vec![],
+            cap: None,
+        }
+    }
+
+    /// Reject transactions once the pending ones use the given amount of bytes.
+    pub fn set_cap(&mut self, cap: usize) {
+        self.cap = Some(cap);
+    }
+
+    /// Returns the amount of pending transactions.
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    /// Returns true, if no transaction is pending.
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+
+    /// Returns the amount of bytes used by the pending transactions.
+    pub fn get_size(&self) -> usize {
+        self.transactions.iter().map(|pending| pending.get_size()).sum()
+    }
+
+    /// Returns an iterator over the pending transactions, in the order they were added.
+    pub fn iter(&self) -> slice::Iter<'_, PendingTransaction> {
+        self.transactions.iter()
+    }
+
+    /// Returns true, if the given transaction is pending.
+    pub fn contains(&self, transaction: &Transaction) -> bool {
+        self.transactions.iter().any(|pending| pending.transaction == *transaction)
+    }
+
+    /// Returns true, if the transaction with the given identifier is pending.
+    pub fn contains_identifier(&self, transaction_identifier: &str) -> bool {
+        self.transactions.iter().any(|pending| pending.transaction.identifier == transaction_identifier)
+    }
+
+    /// Returns true, if a vote of the voter with the given index on the question with the given identifier is pending.
+    pub fn contains_voter(&self, question_id: &Option<String>, voter_idx: usize) -> bool {
+        self.transactions.iter().any(|pending| match pending.transaction.data {
+            Some(ref trx_data) => TransactionType::Vote == pending.transaction.trx_type && trx_data.voter_idx == voter_idx && trx_data.question_id == *question_id,
+            None => false
+        })
+    }
+
+    /// Returns true, if a transaction registering or revoking the voter with the given index
+    /// on the question with the given identifier is pending.
+    pub fn contains_voter_registration(&self, question_id: &Option<String>, voter_idx: usize) -> bool {
+        self.transactions.iter().any(|pending| match pending.transaction.voter_registration {
+            Some(ref voter_registration) => voter_registration.voter_idx == voter_idx && voter_registration.question_id == *question_id,
+            None => false
+        })
+    }
+
+    /// Add the given transaction, unless it is already pending.
+    ///
+    /// Returns true if it was added, false if it is already pending,
+    /// or the reason of the rejection if the mempool is full.
+    pub fn insert(&mut self, pending: PendingTransaction) -> Result<bool, String> {
+        if self.contains(&pending.transaction) {
+            return Ok(false);
+        }
+
+        if let Some(cap) = self.cap {
+            let size = self.get_size();
+            if size + pending.get_size() > cap {
+                warn!("Mempool of {} bytes is full. Rejecting transaction {:?}.", size, pending.transaction.identifier.clone());
+                return Err("The buffer of pending transactions is full, retry later".to_string());
+            }
+        }
+
+        self.transactions.push(pending);
+
+        Ok(true)
+    }
+
+    /// Remove the given transactions, e.g. once a block including them was added to the chain.
+    pub fn remove_included(&mut self, transactions: &[Transaction]) {
+        self.transactions.retain(|pending| {
+            !transactions.iter().any(|transaction| transaction.identifier == pending.transaction.identifier)
+        });
+    }
+
+    /// Keep only the pending transactions for which the given predicate holds.
+    pub fn retain<F: FnMut(&PendingTransaction) -> bool>(&mut self, predicate: F) {
+        self.transactions.retain(predicate);
+    }
+
+    /// Returns the pending transactions in the order they are included in a block, i.e.
+    /// by their time of arrival at this node, and by their identifier if they arrived at the same time.
+    pub fn get_ordered(&self) -> Vec<PendingTransaction> {
+        let mut transactions = self.transactions.clone();
+        transactions.sort_by(|first, second| {
+            first.arrived_at.cmp(&second.arrived_at)
+                .then_with(|| first.transaction.identifier.cmp(&second.transaction.identifier))
+        });
+
+        transactions
+    }
+
+    /// Returns the identifiers of the pending transactions which are announced to other nodes,
+    /// in the order they are included in a block. Sealer votes and commitments of the key generation
+    /// are left out, as they only count in a block sealed by the voter or the dealer, respectively.
+    pub fn get_announcement(&self) -> Vec<String> {
+        self.get_ordered().into_iter()
+            .filter(|pending| TransactionType::SealerVote != pending.transaction.trx_type && TransactionType::DkgCommitment != pending.transaction.trx_type)
+            .map(|pending| pending.transaction.identifier)
+            .collect()
+    }
+
+    /// Returns the pending transactions with the given identifiers in batches of at most the given
+    /// amount of bytes, unless a batch consists of a single transaction. Unknown identifiers are skipped.
+    pub fn get_batches(&self, identifiers: &[String], max_batch_size: usize) -> Vec<Vec<Transaction>> {
+        let mut batches = vec![];
+        let mut batch = vec![];
+        let mut batch_size = 0;
+
+        for pending in self.transactions.iter().filter(|pending| identifiers.contains(&pending.transaction.identifier)) {
+            if !batch.is_empty() && batch_size + pending.get_size() > max_batch_size {
+                batches.push(batch);
+                batch = vec![];
+                batch_size = 0;
+            }
+
+            batch_size += pending.get_size();
+            batch.push(pending.transaction.clone());
+        }
+
+        if !batch.is_empty() {
+            batches.push(batch);
+        }
+
+        batches
+    }
+}
+
+/// Summarize the given pending transactions, ordered by their time of arrival.
+pub fn inspect<'a, I: Iterator<Item = &'a PendingTransaction>>(pending_transactions: I, include_payloads: bool) -> Vec<MempoolEntry> {
+    let mut entries: Vec<MempoolEntry> = pending_transactions
+        .map(|pending| MempoolEntry::new(pending, include_payloads))
+        .collect();
+    entries.sort_by(|first, second| {
+        first.arrived_at.cmp(&second.arrived_at)
+            .then_with(|| first.identifier.cmp(&second.identifier))
+    });
+
+    entries
+}
+
+#[cfg(test)]
+mod mempool_test {
+
+    use super::*;
+    use ::chain::hasher::Hasher;
+    use ::chain::transaction::SealerVote;
+
+    #[test]
+    fn test_inspect() {
+        let opened = PendingTransaction::arrived_at(Transaction::new_voting_opened(Hasher::default(), "genesis".to_string(), 0), 2);
+        let closed = PendingTransaction::arrived_at(Transaction::new_voting_closed(Hasher::default(), "genesis".to_string(), 0), 1);
+
+        let entries = inspect(vec![&opened, &closed].into_iter(), false);
+        assert_eq!(vec![closed.transaction.identifier.clone(), opened.transaction.identifier.clone()], entries.iter().map(|entry| entry.identifier.clone()).collect::<Vec<String>>());
+        assert_eq!(TransactionType::VoteClosed, entries[0].trx_type);
+        assert_eq!(closed.get_size(), entries[0].size);
+        assert!(entries.iter().all(|entry| entry.payload.is_none()));
+
+        let entries = inspect(vec![&opened].into_iter(), true);
+        assert_eq!(Some(opened.transaction.clone()), entries[0].payload);
+    }
+
+    #[test]
+    fn test_mempool() {
+        let opened = PendingTransaction::arrived_at(Transaction::new_voting_opened(Hasher::default(), "genesis".to_string(), 0), 2);
+        let closed = PendingTransaction::arrived_at(Transaction::new_voting_closed(Hasher::default(), "genesis".to_string(), 0), 1);
+
+        let mut mempool = Mempool::new();
+        mempool.set_cap(opened.get_size() + closed.get_size());
+        assert_eq!(Ok(true), mempool.insert(opened.clone()));
+        assert_eq!(Ok(false), mempool.insert(opened.clone()));
+        assert_eq!(Ok(true), mempool.insert(closed.clone()));
+        let sealer_vote = SealerVote {
+            voter: "127.0.0.1:3000".parse().unwrap(),
+            candidate: "127.0.0.1:3001".parse().unwrap(),
+            authorize: true,
+            public_key: None,
+            cast_at: 3,
+        };
+        assert!(mempool.insert(PendingTransaction::arrived_at(Transaction::new_sealer_vote(Hasher::default(), sealer_vote), 3)).is_err());
+        assert_eq!(vec![closed.clone(), opened.clone()], mempool.get_ordered());
+
+        // assembling a block does not remove its transactions
+        assert_eq!(2, mempool.len());
+        mempool.remove_included(&[opened.transaction.clone()]);
+        assert!(!mempool.contains_identifier(&opened.transaction.identifier));
+        assert!(mempool.contains(&closed.transaction));
+    }
+
+    #[test]
+    fn test_mempool_gossip() {
+        let opened = PendingTransaction::arrived_at(Transaction::new_voting_opened(Hasher::default(), "genesis".to_string(), 0), 2);
+        let closed = PendingTransaction::arrived_at(Transaction::new_voting_closed(Hasher::default(), "genesis".to_string(), 0), 1);
+        let sealer_vote = SealerVote {
+            voter: "127.0.0.1:3000".parse().unwrap(),
+            candidate: "127.0.0.1:3001".parse().unwrap(),
+            authorize: true,
+            public_key: None,
+            cast_at: 3,
+        };
+        let sealer_vote = PendingTransaction::arrived_at(Transaction::new_sealer_vote(Hasher::default(), sealer_vote), 3);
+
+        let mut mempool = Mempool::new();
+        assert_eq!(Ok(true), mempool.insert(opened.clone()));
+        assert_eq!(Ok(true), mempool.insert(closed.clone()));
+        assert_eq!(Ok(true), mempool.insert(sealer_vote.clone()));
+
+        // sealer votes are never announced
+        let announcement = mempool.get_announcement();
+        assert_eq!(vec![closed.transaction.identifier.clone(), opened.transaction.identifier.clone()], announcement);
+
+        let unknown = vec!["unknown".to_string(), opened.transaction.identifier.clone(), closed.transaction.identifier.clone()];
+        assert_eq!(vec![vec![opened.transaction.clone(), closed.transaction.clone()]], mempool.get_batches(&unknown, opened.get_size() + closed.get_size()));
+        assert_eq!(vec![vec![opened.transaction.clone()], vec![closed.transaction.clone()]], mempool.get_batches(&unknown, 1));
+        assert!(mempool.get_batches(&["unknown".to_string()], 1).is_empty());
+    }
+}
@@ -1,3 +1,12 @@
+/// The rules a block received from another node must satisfy
+/// before it is added to the chain.
+pub mod block_validator;
 /// A simplified version of the Clique protocol where
 /// voting on network participants is omitted.
-pub mod clique;
\ No newline at end of file
+pub mod clique;
+/// The transactions received but not yet included in a block.
+pub mod mempool;
+/// The receipts of transactions whose blocks were reverted by reorganizations.
+pub mod receipt;
+/// The final tally of the election, calculated alike by all nodes from the chain.
+pub mod tally;
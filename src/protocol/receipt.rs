@@ -0,0 +1,116 @@
+use ::protocol::clique::TransactionInclusion;
+use std::collections::{HashMap, VecDeque};
+
+/// The maximum amount of transactions reverted by reorganizations whose receipts are tracked.
+/// Once exceeded, the receipts reverted first are forgotten.
+pub const MAX_TRACKED_RECEIPTS: usize = 10_000;
+
+/// The state of a transaction submitted to the network, as returned for a `ReceiptRequest`.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReceiptStatus {
+    /// Included in a block of the canonical chain.
+    Included(TransactionInclusion),
+    /// Waiting in the buffer to be included for the first time.
+    Pending,
+    /// Its block was reverted by a reorganization, hence it waits in the buffer to be included again.
+    PendingReinsertion,
+    /// Its block was reverted by a reorganization and it cannot be included again, for the given reason.
+    /// The voter must submit a new vote, if the voting is still open.
+    Dropped(String),
+    /// Neither included nor pending, as far as this node knows.
+    Unknown,
+}
+
+/// What happened to a transaction whose block was reverted by a reorganization.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum Reversion {
+    /// Added to the buffer again.
+    Reinserted,
+    /// Rejected when adding it to the buffer again, for the given reason.
+    Dropped(String),
+}
+
+/// Remembers the transactions whose blocks were reverted by reorganizations,
+/// so that the receipts of voters do not silently become stale.
+pub struct ReceiptTracker {
+    reversions: HashMap<String, Reversion>,
+    /// The identifiers of the tracked transactions, from the one reverted first to the last one.
+    order: VecDeque<String>,
+}
+
+impl ReceiptTracker {
+    /// Create a tracker without any reverted transactions.
+    pub fn new() -> ReceiptTracker {
+        ReceiptTracker {
+            reversions: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Take note of what happened to the given reverted transaction,
+    /// forgetting the transaction reverted first once `MAX_TRACKED_RECEIPTS` is exceeded.
+    pub fn track(&mut self, transaction_identifier: &str, reversion: Reversion) {
+        if self.reversions.insert(transaction_identifier.to_string(), reversion).is_none() {
+            self.order.push_back(transaction_identifier.to_string());
+        }
+
+        while self.order.len() > MAX_TRACKED_RECEIPTS {
+            if let Some(identifier) = self.order.pop_front() {
+                self.reversions.remove(&identifier);
+            }
+        }
+    }
+
+    /// Returns what happened to the given transaction, if it was reverted.
+    pub fn get(&self, transaction_identifier: &str) -> Option<&Reversion> {
+        self.reversions.get(transaction_identifier)
+    }
+
+    /// Stop tracking the given transaction, e.g. once it is included again.
+    pub fn remove(&mut self, transaction_identifier: &str) {
+        if self.reversions.remove(transaction_identifier).is_some() {
+            self.order.retain(|identifier| identifier != transaction_identifier);
+        }
+    }
+
+    /// Returns the identifiers of the transactions which were added to the buffer again.
+    pub fn get_reinserted(&self) -> Vec<String> {
+        self.order.iter()
+            .filter(|identifier| Some(&Reversion::Reinserted) == self.reversions.get(*identifier))
+            .cloned()
+            .collect()
+    }
+}
+
+impl Default for ReceiptTracker {
+    fn default() -> Self {
+        ReceiptTracker::new()
+    }
+}
+
+#[cfg(test)]
+mod receipt_test {
+
+    use super::*;
+
+    #[test]
+    fn test_track() {
+        let mut tracker = ReceiptTracker::new();
+        tracker.track("vote-1", Reversion::Reinserted);
+        tracker.track("vote-2", Reversion::Dropped("Voting is closed".to_string()));
+        tracker.track("vote-3", Reversion::Reinserted);
+        assert_eq!(vec!["vote-1".to_string(), "vote-3".to_string()], tracker.get_reinserted());
+
+        // included again
+        tracker.remove("vote-1");
+        assert_eq!(None, tracker.get("vote-1"));
+        assert_eq!(Some(&Reversion::Dropped("Voting is closed".to_string())), tracker.get("vote-2"));
+
+        for index in 0..MAX_TRACKED_RECEIPTS {
+            tracker.track(&format!("other-{}", index), Reversion::Reinserted);
+        }
+        assert_eq!(None, tracker.get("vote-2"));
+        assert_eq!(Some(&Reversion::Reinserted), tracker.get("other-0"));
+    }
+}
@@ -0,0 +1,219 @@
+use ::chain::block::Block;
+use ::chain::chain::Chain;
+use ::chain::chain_visitor::{DecryptionSharesVisitor, KeyGenerationVisitor, SumCipherTextVisitor};
+use ::chain::chain_walker::LongestPathWalker;
+use ::chain::transaction::{DecryptionShare, FinalTally};
+use ::chain::transaction_registry::RevotePolicy;
+use ::chain::types::Height;
+use ::config::genesis::Genesis;
+use ::threshold::{self, dkg};
+use crypto_rs::arithmetic::mod_int::ModInt;
+use crypto_rs::el_gamal::ciphertext::CipherText;
+use crypto_rs::el_gamal::encryption::PublicKey;
+use num::ToPrimitive;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+
+/// The public key shares of the sealers able to decrypt the tally jointly.
+#[derive(Clone)]
+pub struct DecryptionKeys {
+    /// The amount of decryption shares required to decrypt the tally.
+    pub threshold: usize,
+    /// The index of the key share of each sealer holding one, starting at one, along with its public key share.
+    pub public_key_shares: BTreeMap<SocketAddr, (usize, ModInt)>,
+}
+
+/// Calculates the final tally of each question from the votes and the decryption shares on a branch of the chain,
+/// so that the sealer committing the tallies and the nodes validating its block derive the same ones.
+#[derive(Clone)]
+pub struct TallyCalculator {
+    /// The public key declared in the genesis configuration.
+    public_key: PublicKey,
+    /// The sealers of the genesis configuration, which deal the election key if it is generated jointly.
+    sealers: Vec<SocketAddr>,
+    /// The identifiers of all questions, starting with None for the one whose voters are declared in `public_uciv.json`.
+    question_ids: Vec<Option<String>>,
+    /// The largest plaintext a vote may encrypt.
+    max_voting_option: u64,
+    /// The decryption keys declared in the genesis configuration, if any.
+    threshold_decryption: Option<DecryptionKeys>,
+    /// The amount of decryption shares required to decrypt the tally, if the sealers generate the election key jointly.
+    key_generation_threshold: Option<usize>,
+    /// Which vote counts if a voter votes several times on the same question.
+    revote_policy: RevotePolicy,
+}
+
+impl TallyCalculator {
+    /// Create a calculator for the election of the given genesis configuration.
+    pub fn new(genesis: &Genesis) -> TallyCalculator {
+        let threshold_decryption = genesis.threshold_decryption.as_ref().map(|threshold_decryption| DecryptionKeys {
+            threshold: threshold_decryption.threshold,
+            public_key_shares: genesis.sealer.iter()
+                .filter_map(|sealer| genesis.get_public_key_share(sealer).map(|(index, public_key_share)| (*sealer, (index, public_key_share.clone()))))
+                .collect(),
+        });
+
+        TallyCalculator {
+            public_key: genesis.public_key.clone(),
+            sealers: genesis.sealer.clone(),
+            question_ids: Some(None).into_iter()
+                .chain(genesis.questions.keys().map(|question_id| Some(question_id.clone())))
+                .collect(),
+            max_voting_option: genesis.get_voting_options().iter()
+                .filter_map(|voting_option| voting_option.value.to_u64())
+                .max()
+                .unwrap_or(0),
+            threshold_decryption,
+            key_generation_threshold: genesis.key_generation.as_ref().map(|key_generation| key_generation.threshold),
+            revote_policy: genesis.get_revote_policy(),
+        }
+    }
+
+    /// Returns the identifiers of all questions, starting with None for the one whose voters are declared in `public_uciv.json`.
+    pub fn get_question_ids(&self) -> &[Option<String>] {
+        &self.question_ids
+    }
+
+    /// Returns true, if the sealers decrypt the tally jointly, hence a final tally requires the decrypted sum.
+    pub fn is_decrypted(&self) -> bool {
+        self.threshold_decryption.is_some() || self.key_generation_threshold.is_some()
+    }
+
+    /// Returns the election key votes on the branch of the given block at the given height are encrypted with,
+    /// i.e. the one declared in the genesis configuration, or the one committed on the branch if the sealers generate it jointly.
+    pub fn get_election_public_key(&self, chain: &Chain, height: Height, block: &Block) -> PublicKey {
+        let key_generation_visitor = self.walk_key_generation(chain, height, block);
+
+        self.get_election_public_key_of(key_generation_visitor.as_ref())
+    }
+
+    /// Returns the public key shares of the sealers holding a key share, along with the amount of decryption shares
+    /// required to decrypt the tally. These are declared in the genesis configuration, or derived from the commitments
+    /// of the dealers on the branch of the given block at the given height if the sealers generate the election key jointly.
+    /// Returns None if neither is configured or not all dealers committed their contribution yet.
+    pub fn get_decryption_keys(&self, chain: &Chain, height: Height, block: &Block) -> Option<DecryptionKeys> {
+        let key_generation_visitor = self.walk_key_generation(chain, height, block);
+
+        self.get_decryption_keys_of(key_generation_visitor.as_ref())
+    }
+
+    /// Calculate the tally of each question on the branch of the given block at the given height,
+    /// in the order of `get_question_ids`. The sums are decrypted by the first valid decryption shares
+    /// published on the branch, if the sealers decrypt the tally jointly.
+    pub fn calculate(&self, chain: &Chain, height: Height, block: &Block) -> Vec<FinalTally> {
+        let key_generation_visitor = self.walk_key_generation(chain, height, block);
+        let public_key = self.get_election_public_key_of(key_generation_visitor.as_ref());
+        let decryption_keys = self.get_decryption_keys_of(key_generation_visitor.as_ref());
+
+        let longest_path_walker = LongestPathWalker::new();
+        let mut sum_cipher_visitor = SumCipherTextVisitor::new(public_key.clone(), self.revote_policy);
+        longest_path_walker.walk_from(chain, height, block, &mut sum_cipher_visitor);
+        let mut decryption_shares_visitor = DecryptionSharesVisitor::new();
+        longest_path_walker.walk_from(chain, height, block, &mut decryption_shares_visitor);
+        let decryption_shares = decryption_shares_visitor.get_decryption_shares();
+
+        self.question_ids.iter()
+            .map(|question_id| {
+                let (total_votes, cipher_text) = sum_cipher_visitor.get_votes_on(question_id.clone());
+                let (decrypted_sum, decryption_shares) = match decryption_keys {
+                    Some(ref decryption_keys) => self.decrypt_sum(&public_key, decryption_keys, question_id, total_votes, &cipher_text, &decryption_shares),
+                    None => (None, vec![])
+                };
+
+                FinalTally {
+                    question_id: question_id.clone(),
+                    total_votes,
+                    cipher_text,
+                    decrypted_sum,
+                    decryption_shares,
+                }
+            })
+            .collect()
+    }
+
+    /// Decrypt the given sum of the given amount of votes on the question with the given identifier,
+    /// by combining the first valid decryption share of each sealer among the given ones.
+    /// Returns the decrypted sum along with the shares combined, or None and no shares if fewer valid shares than the threshold are given.
+    pub fn decrypt_sum(&self, public_key: &PublicKey, decryption_keys: &DecryptionKeys, question_id: &Option<String>, total_votes: usize, cipher_text: &CipherText, decryption_shares: &[DecryptionShare]) -> (Option<u64>, Vec<DecryptionShare>) {
+        let mut partial_decryptions: Vec<(usize, ModInt)> = vec![];
+        let mut combined_shares = vec![];
+        for decryption_share in decryption_shares.iter().filter(|decryption_share| decryption_share.question_id == *question_id) {
+            if partial_decryptions.len() == decryption_keys.threshold {
+                break;
+            }
+
+            let (index, public_key_share) = match decryption_keys.public_key_shares.get(&decryption_share.sealer) {
+                Some(&(index, ref public_key_share)) => (index, public_key_share),
+                None => {
+                    continue;
+                }
+            };
+
+            if partial_decryptions.iter().any(|&(other_index, _)| other_index == index) {
+                continue;
+            }
+
+            if threshold::verify_partial_decryption(public_key, public_key_share, cipher_text, &decryption_share.partial_decryption, &decryption_share.proof) {
+                partial_decryptions.push((index, decryption_share.partial_decryption.clone()));
+                combined_shares.push(decryption_share.clone());
+            } else {
+                warn!("Decryption share of sealer {} does not match the sum of the votes. Ignoring it", decryption_share.sealer);
+            }
+        }
+
+        if partial_decryptions.len() < decryption_keys.threshold {
+            debug!("Only {} of {} required decryption shares are published", partial_decryptions.len(), decryption_keys.threshold);
+            return (None, vec![]);
+        }
+
+        // the sum is at most the largest voting option chosen by all voters
+        match threshold::combine(public_key, cipher_text, &partial_decryptions, self.max_voting_option.saturating_mul(total_votes as u64)) {
+            Some(decrypted_sum) => (Some(decrypted_sum), combined_shares),
+            None => (None, vec![])
+        }
+    }
+
+    /// Walk the key generation on the branch of the given block, if the sealers generate the election key jointly.
+    fn walk_key_generation(&self, chain: &Chain, height: Height, block: &Block) -> Option<KeyGenerationVisitor> {
+        self.key_generation_threshold.map(|_| {
+            let mut key_generation_visitor = KeyGenerationVisitor::new();
+            LongestPathWalker::new().walk_from(chain, height, block, &mut key_generation_visitor);
+
+            key_generation_visitor
+        })
+    }
+
+    fn get_election_public_key_of(&self, key_generation_visitor: Option<&KeyGenerationVisitor>) -> PublicKey {
+        match key_generation_visitor.and_then(|key_generation_visitor| key_generation_visitor.get_election_key()) {
+            Some(election_key) => PublicKey { h: election_key.clone(), ..self.public_key.clone() },
+            None => self.public_key.clone()
+        }
+    }
+
+    fn get_decryption_keys_of(&self, key_generation_visitor: Option<&KeyGenerationVisitor>) -> Option<DecryptionKeys> {
+        if self.threshold_decryption.is_some() {
+            return self.threshold_decryption.clone();
+        }
+
+        let (threshold, dkg_commitments) = match (self.key_generation_threshold, key_generation_visitor) {
+            (Some(threshold), Some(key_generation_visitor)) => (threshold, key_generation_visitor.get_dkg_commitments()),
+            _ => {
+                return None;
+            }
+        };
+
+        // the public key shares are known once all dealers committed their contribution
+        if dkg_commitments.len() < self.sealers.len() {
+            return None;
+        }
+
+        let public_key_shares = self.sealers.iter().enumerate()
+            .map(|(position, sealer)| (*sealer, (position + 1, dkg::get_public_key_share(&self.public_key, dkg_commitments, position + 1))))
+            .collect();
+
+        Some(DecryptionKeys {
+            threshold,
+            public_key_shares,
+        })
+    }
+}
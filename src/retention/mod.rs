@@ -0,0 +1,281 @@
+use ::archive::MANIFEST_FILE_NAME;
+use ::chain::storage::{BINCODE_FILE_NAME, JSON_FILE_NAME};
+use ::chain::write_ahead_log::WAL_FILE_NAME;
+use ::p2p::audit::AUDIT_LOG_FILE_NAME;
+use ::p2p::ban_list::OFFENDERS_FILE_NAME;
+use ::threshold::dkg::KEY_GENERATION_FILE_NAME;
+use serde_json;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Files which are never deleted, even if they are covered by a policy, as they define the chain,
+/// are required to verify it or hold the state of a node. Extend this list with each file a node persists.
+/// All files in the data directory of a node are protected as well, see `RetentionPolicy::protect_directory`.
+const PROTECTED_FILE_NAMES: [&str; 9] = [
+    "genesis.json", "public_key.json", "public_uciv.json",
+    JSON_FILE_NAME, BINCODE_FILE_NAME, WAL_FILE_NAME, KEY_GENERATION_FILE_NAME, OFFENDERS_FILE_NAME, MANIFEST_FILE_NAME
+];
+
+/// The size of the buffer used to overwrite files before deleting them.
+const OVERWRITE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Defines which off-chain artifacts, possibly linkable to voters,
+/// are deleted once the election is certified, i.e. once the voting is closed.
+///
+/// The chain itself is immutable and never subject to a retention policy.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RetentionPolicy {
+    /// Files or directories containing voter-linkable artifacts,
+    /// e.g. audit logs containing IP addresses or spooled transactions.
+    /// Directories are purged recursively.
+    pub paths: Vec<PathBuf>,
+    /// Only delete files which were not modified for at least this amount of seconds.
+    /// If None, all files are deleted.
+    pub max_age_secs: Option<u64>,
+    /// The interval in seconds in which a running node applies the policy.
+    pub interval_secs: u64,
+    /// The directories whose files are never deleted, e.g. the data directory of the node.
+    #[serde(skip)]
+    protected_directories: Vec<PathBuf>,
+}
+
+/// The outcome of applying a retention policy.
+#[derive(Debug, Clone, Default)]
+pub struct PurgeReport {
+    /// The files which were securely deleted.
+    pub deleted_files: Vec<PathBuf>,
+    /// The files which could not be deleted along with the reason.
+    pub failed_files: Vec<(PathBuf, String)>,
+}
+
+impl RetentionPolicy {
+    /// Read the retention policy from the given JSON file.
+    pub fn from_file(file_name: &str) -> Result<RetentionPolicy, String> {
+        let mut contents = String::new();
+        let read_result = File::open(file_name).and_then(|mut file| file.read_to_string(&mut contents));
+        match read_result {
+            Ok(_) => {}
+            Err(e) => {
+                return Err(format!("Failed to read retention policy {:?}: {:?}", file_name, e));
+            }
+        }
+
+        match serde_json::from_str(&contents) {
+            Ok(policy) => Ok(policy),
+            Err(e) => Err(format!("Failed to parse retention policy {:?}: {:?}", file_name, e))
+        }
+    }
+
+    /// Never delete the files in the given directory, e.g. the data directory of the node holding its blocks,
+    /// except for the audit log, which contains IP addresses and is meant to be covered by a policy.
+    ///
+    /// Returns an error if this policy covers a path inside the directory other than the audit log,
+    /// so that a policy deleting the state of the node is refused rather than silently skipped.
+    pub fn protect_directory(&mut self, directory: &Path) -> Result<(), String> {
+        let directory = match fs::canonicalize(directory) {
+            Ok(directory) => directory,
+            Err(e) => {
+                return Err(format!("Failed to resolve {:?}: {:?}", directory, e));
+            }
+        };
+
+        self.protected_directories.push(directory.clone());
+        match self.paths.iter().find(|path| self.is_in_protected_directory(path)) {
+            Some(path) => Err(format!("Retention policy must not cover {:?} inside the data directory {:?}", path, directory)),
+            None => Ok(())
+        }
+    }
+
+    /// Securely delete all files covered by this policy.
+    ///
+    /// Directories themselves are kept, so that other components
+    /// may continue to write into them.
+    pub fn purge(&self) -> PurgeReport {
+        let mut report = PurgeReport::default();
+
+        for path in self.paths.iter() {
+            self.purge_path(path, &mut report);
+        }
+
+        report
+    }
+
+    fn purge_path(&self, path: &Path, report: &mut PurgeReport) {
+        let metadata = match fs::symlink_metadata(path) {
+            Ok(metadata) => metadata,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                return;
+            }
+            Err(e) => {
+                report.failed_files.push((path.to_path_buf(), format!("{:?}", e)));
+                return;
+            }
+        };
+
+        if metadata.is_dir() {
+            let entries = match fs::read_dir(path) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    report.failed_files.push((path.to_path_buf(), format!("{:?}", e)));
+                    return;
+                }
+            };
+
+            for entry in entries {
+                match entry {
+                    Ok(entry) => self.purge_path(&entry.path(), report),
+                    Err(e) => report.failed_files.push((path.to_path_buf(), format!("{:?}", e)))
+                }
+            }
+
+            return;
+        }
+
+        if is_protected(path) || self.is_in_protected_directory(path) {
+            warn!("Not deleting {:?} as it is required to verify the chain or holds the state of the node", path);
+            return;
+        }
+
+        if !self.is_expired(&metadata.modified().ok()) {
+            return;
+        }
+
+        // symbolic links are removed without following them
+        let delete_result = if metadata.file_type().is_symlink() {
+            fs::remove_file(path)
+        } else {
+            secure_delete(path)
+        };
+
+        match delete_result {
+            Ok(()) => {
+                debug!("Securely deleted {:?}", path);
+                report.deleted_files.push(path.to_path_buf());
+            }
+            Err(e) => {
+                report.failed_files.push((path.to_path_buf(), format!("{:?}", e)));
+            }
+        }
+    }
+
+    /// Returns true, if the given path is inside one of the protected directories, or is one of them,
+    /// and is not the audit log. Paths which cannot be resolved, e.g. as they do not exist, are
+    /// resolved by their parent directory.
+    fn is_in_protected_directory(&self, path: &Path) -> bool {
+        let resolved_path = match (fs::canonicalize(path), path.parent(), path.file_name()) {
+            (Ok(resolved_path), _, _) => resolved_path,
+            (Err(_), Some(parent), Some(file_name)) => match fs::canonicalize(if parent.as_os_str().is_empty() { Path::new(".") } else { parent }) {
+                Ok(resolved_parent) => resolved_parent.join(file_name),
+                Err(_) => {
+                    return false;
+                }
+            },
+            (Err(_), _, _) => {
+                return false;
+            }
+        };
+
+        self.protected_directories.iter().any(|directory| {
+            let is_audit_log = Some(directory.as_path()) == resolved_path.parent() && Some(AUDIT_LOG_FILE_NAME.as_ref()) == resolved_path.file_name();
+            resolved_path.starts_with(directory) && !is_audit_log
+        })
+    }
+
+    fn is_expired(&self, modified: &Option<SystemTime>) -> bool {
+        let max_age = match self.max_age_secs {
+            Some(max_age_secs) => Duration::from_secs(max_age_secs),
+            None => {
+                return true;
+            }
+        };
+
+        match *modified {
+            Some(modified) => {
+                match SystemTime::now().duration_since(modified) {
+                    Ok(age) => age >= max_age,
+                    // modified in the future
+                    Err(_) => false
+                }
+            }
+            // if the platform does not support modification times,
+            // the file is kept rather than deleted too early
+            None => false
+        }
+    }
+}
+
+fn is_protected(path: &Path) -> bool {
+    match path.file_name().and_then(|file_name| file_name.to_str()) {
+        Some(file_name) => PROTECTED_FILE_NAMES.contains(&file_name),
+        None => false
+    }
+}
+
+/// Overwrite the content of the given file with zeros, flush it
+/// to the disk and only then remove it.
+///
+/// Note, that this does not guarantee the data to be unrecoverable on
+/// copy-on-write file systems or storage performing wear levelling.
+fn secure_delete(path: &Path) -> io::Result<()> {
+    {
+        let mut file = OpenOptions::new().write(true).open(path)?;
+        let length = file.metadata()?.len();
+        file.seek(SeekFrom::Start(0))?;
+
+        let zeros = [0u8; OVERWRITE_BUFFER_SIZE];
+        let mut remaining = length;
+        while remaining > 0 {
+            let chunk_size = if remaining < OVERWRITE_BUFFER_SIZE as u64 { remaining as usize } else { OVERWRITE_BUFFER_SIZE };
+            file.write_all(&zeros[..chunk_size])?;
+            remaining -= chunk_size as u64;
+        }
+
+        file.sync_all()?;
+    }
+
+    fs::remove_file(path)
+}
+
+#[cfg(test)]
+mod retention_test {
+
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_protect_directory() {
+        let directory = env::temp_dir().join(format!("node_rs_retention_test_{}", ::std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let data_directory = directory.join("data");
+        fs::create_dir_all(&data_directory).unwrap();
+        for file_name in [BINCODE_FILE_NAME, WAL_FILE_NAME, "blocks.tmp", AUDIT_LOG_FILE_NAME].iter() {
+            fs::write(data_directory.join(file_name), b"data").unwrap();
+        }
+        fs::write(directory.join("requests.log"), b"127.0.0.1").unwrap();
+
+        let mut retention_policy = RetentionPolicy {
+            paths: vec![data_directory.join(BINCODE_FILE_NAME)],
+            max_age_secs: None,
+            interval_secs: 60,
+            protected_directories: vec![],
+        };
+        assert!(retention_policy.protect_directory(&data_directory).is_err());
+
+        // covering the parent of the data directory purges all but the state of the node
+        retention_policy.paths = vec![directory.clone()];
+        retention_policy.protected_directories = vec![];
+        assert_eq!(Ok(()), retention_policy.protect_directory(&data_directory));
+        let report = retention_policy.purge();
+        assert!(report.failed_files.is_empty());
+        assert_eq!(2, report.deleted_files.len());
+        assert!(!directory.join("requests.log").exists());
+        assert!(!data_directory.join(AUDIT_LOG_FILE_NAME).exists());
+        assert!(data_directory.join(BINCODE_FILE_NAME).exists());
+        assert!(data_directory.join(WAL_FILE_NAME).exists());
+        assert!(data_directory.join("blocks.tmp").exists());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,274 @@
+use ::chain::chain_visitor::ElectionPhase;
+use ::chain::hasher::Hasher;
+use ::chain::transaction::{PhaseChange, Transaction, TransactionType};
+use ::chain::transaction_registry;
+use ::config::genesis::Genesis;
+use ::config::node::NodeConfig;
+use ::export;
+use ::p2p::client;
+use ::p2p::codec::Message;
+use ::p2p::node::Node;
+use ::protocol::clique::ElectionStatus;
+use ::signer::key::NodeKey;
+use crypto_rs::cai::uciv::{CaiProof, ImageSet, PreImageSet};
+use crypto_rs::el_gamal::encryption::{PrivateKey, PublicKey, encrypt};
+use crypto_rs::el_gamal::membership_proof::MembershipProof;
+use rand::{thread_rng, Rng};
+use serde_json;
+use std::fs::File;
+use std::io::Read;
+use std::mem;
+use std::net::{SocketAddr, TcpListener};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The amount of nodes of the ephemeral network.
+pub const SELFTEST_NODES: usize = 3;
+
+/// The block period of the ephemeral network in seconds, shortened to finish the cycle quickly.
+const SELFTEST_BLOCK_PERIOD: u64 = 1;
+
+/// The time to wait for the network to reach the next step of the cycle.
+const STEP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The interval in which the election status is polled while waiting.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The election run on the ephemeral network.
+struct SelftestElection {
+    /// The hash function deriving the identifiers of the votes.
+    hasher: Hasher,
+    /// The hash of the genesis configuration of the ephemeral nodes.
+    configuration_hash: String,
+    /// The public key the votes are encrypted with.
+    public_key: PublicKey,
+    /// The public UCIV information of the voters.
+    image_sets: Vec<ImageSet>,
+    /// The ephemeral election authority opening and closing the voting.
+    authority: NodeKey,
+}
+
+impl SelftestElection {
+    /// Sign opening or closing the voting now, depending on the given transaction type.
+    fn sign_phase_change(&self, trx_type: TransactionType) -> Result<PhaseChange, String> {
+        let since_the_epoch = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards");
+        let changed_at = since_the_epoch.as_secs() * 1000 + u64::from(since_the_epoch.subsec_nanos()) / 1_000_000;
+
+        PhaseChange::new(self.configuration_hash.clone(), changed_at).sign(&trx_type, &self.authority)
+    }
+}
+
+/// The outcome of a successful selftest.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct SelftestReport {
+    /// The amount of votes cast.
+    pub total_votes: usize,
+    /// The amount of votes in favour, as decrypted from the tally of each node.
+    pub yes_votes: u64,
+    /// The time the whole cycle took, in seconds.
+    pub duration: u64,
+}
+
+/// Read the pre-images of the UCIV information of all voters from the given JSON file,
+/// as generated along with the public UCIV information.
+pub fn load_pre_image_sets(file_name: &str) -> Result<Vec<PreImageSet>, String> {
+    let mut contents = String::new();
+    let read_result = File::open(file_name).and_then(|mut file| file.read_to_string(&mut contents));
+    match read_result {
+        Ok(_) => {}
+        Err(e) => {
+            return Err(format!("Failed to read private UCIV {:?}: {:?}", file_name, e));
+        }
+    }
+
+    match serde_json::from_str(&contents) {
+        Ok(pre_image_sets) => Ok(pre_image_sets),
+        Err(e) => Err(format!("Failed to parse private UCIV {:?}: {:?}", file_name, e))
+    }
+}
+
+/// Run a whole election on an ephemeral network of `SELFTEST_NODES` sealers running in this process:
+/// open the voting, cast the given amount of votes with random choices, close the voting,
+/// and verify that the decrypted tally of each node matches the votes cast.
+/// The voting is opened and closed by an ephemeral election authority.
+///
+/// - `genesis_file_name` The genesis configuration, whose sealers are replaced by the ephemeral nodes.
+/// - `public_uciv_file_name` The public UCIV information of the voters.
+/// - `public_key_file_name` The public key the votes are encrypted with.
+/// - `private_key` The private key to decrypt the tally with.
+/// - `pre_image_sets` The private UCIV information of the voters, used to prove the votes cast as intended.
+/// - `amount_of_votes` The amount of votes to cast, at most one per voter.
+pub fn run(genesis_file_name: &str, public_uciv_file_name: &str, public_key_file_name: &str, private_key: &PrivateKey, pre_image_sets: &[PreImageSet], amount_of_votes: usize) -> Result<SelftestReport, String> {
+    let started_at = Instant::now();
+
+    let listen_addresses: Vec<SocketAddr> = (0..SELFTEST_NODES).map(|_| free_address()).collect();
+    let rpc_addresses: Vec<SocketAddr> = (0..SELFTEST_NODES).map(|_| free_address()).collect();
+
+    let Genesis { public_key, public_uciv: image_sets, clique, .. } = Genesis::new(genesis_file_name, public_uciv_file_name, public_key_file_name);
+    if amount_of_votes > pre_image_sets.len() || amount_of_votes > image_sets.len() {
+        return Err(format!("Cannot cast {} votes with the UCIV information of {} voters", amount_of_votes, pre_image_sets.len().min(image_sets.len())));
+    }
+
+    let mut election = SelftestElection {
+        hasher: clique.get_hasher(),
+        configuration_hash: String::new(),
+        public_key,
+        image_sets,
+        authority: NodeKey::generate(),
+    };
+
+    let mut nodes = vec![];
+    for index in 0..SELFTEST_NODES {
+        // each node owns its genesis configuration, with the ephemeral nodes as sealers
+        let mut genesis = Genesis::new(genesis_file_name, public_uciv_file_name, public_key_file_name);
+        genesis.sealer = listen_addresses.clone();
+        genesis.sealer_keys.clear();
+        genesis.clique.block_period = SELFTEST_BLOCK_PERIOD;
+        genesis.election_authority = Some(election.authority.public_key.clone());
+        election.configuration_hash = genesis.get_configuration_hash();
+
+        let node_config = NodeConfig {
+            advertised_address: Some(listen_addresses[index]),
+            ..NodeConfig::default()
+        };
+
+        let node = Node::new(listen_addresses[index], rpc_addresses[index], genesis, node_config);
+        node.listen();
+        node.listen_rpc();
+        node.gossip();
+
+        nodes.push(node);
+    }
+    info!("Started {} nodes listening on {:?}", SELFTEST_NODES, listen_addresses);
+
+    for node in nodes.iter_mut() {
+        node.sign();
+    }
+
+    let result = run_cycle(&rpc_addresses, &election, private_key, pre_image_sets, amount_of_votes);
+
+    for node in nodes {
+        node.shutdown(Duration::from_secs(1));
+        // the listener threads never terminate, hence the node must not be
+        // dropped as this would wait for them to finish
+        mem::forget(node);
+    }
+
+    result.map(|yes_votes| SelftestReport {
+        total_votes: amount_of_votes,
+        yes_votes,
+        duration: started_at.elapsed().as_secs(),
+    })
+}
+
+/// Run the open, vote, close and tally cycle of the given election against the nodes listening
+/// for RPC connections on the given addresses.
+/// Returns the amount of votes in favour, if all nodes agree on the expected tally.
+fn run_cycle(rpc_addresses: &[SocketAddr], election: &SelftestElection, private_key: &PrivateKey, pre_image_sets: &[PreImageSet], amount_of_votes: usize) -> Result<u64, String> {
+    let rpc_address = &rpc_addresses[0];
+
+    info!("Opening the voting");
+    let opening = election.sign_phase_change(TransactionType::VoteOpened)?;
+    match client::rpc_request(rpc_address, None, Message::SignedOpenVote(opening)) {
+        Ok(Message::OpenVoteAccept) => {}
+        Ok(other) => {
+            return Err(format!("Expected the voting to be opened but got {:?}", other));
+        }
+        Err(e) => {
+            return Err(e);
+        }
+    }
+    wait_until(rpc_address, "the voting to be opened", |status| ElectionPhase::Opened == status.phase)?;
+
+    let voting_options = transaction_registry::voting_options();
+
+    info!("Casting {} votes", amount_of_votes);
+    let mut rng = thread_rng();
+    let mut expected_yes_votes = 0;
+    for (voter_idx, pre_image_set) in pre_image_sets.iter().enumerate().take(amount_of_votes) {
+        let chosen_index = if rng.gen() { 0 } else { 1 };
+        if 0 == chosen_index {
+            expected_yes_votes += 1;
+        }
+
+        let message = voting_options[chosen_index].clone();
+        let cipher_text = encrypt(&election.public_key, message.clone());
+        let transaction = Transaction::new_vote(
+            election.hasher,
+            voter_idx,
+            cipher_text.clone(),
+            MembershipProof::new(election.public_key.clone(), message, cipher_text.clone(), voting_options.clone()),
+            CaiProof::new(election.public_key.clone(), cipher_text, pre_image_set.clone(), election.image_sets[voter_idx].clone(), chosen_index, voting_options.clone()),
+        );
+
+        match client::rpc_request(rpc_address, None, Message::TransactionPayload(transaction)) {
+            Ok(Message::TransactionAccept(_)) | Ok(Message::TransactionIncluded(_)) => {}
+            Ok(Message::TransactionReject(identifier, reason)) => {
+                return Err(format!("Vote {} of voter {} was rejected: {}", identifier, voter_idx, reason));
+            }
+            Ok(other) => {
+                return Err(format!("Expected the vote of voter {} to be accepted but got {:?}", voter_idx, other));
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        }
+    }
+    wait_until(rpc_address, "all votes to be included", |status| amount_of_votes == status.total_votes)?;
+
+    info!("Closing the voting");
+    let closing = election.sign_phase_change(TransactionType::VoteClosed)?;
+    match client::rpc_request(rpc_address, None, Message::SignedCloseVote(closing)) {
+        Ok(Message::CloseVoteAccept) => {}
+        Ok(other) => {
+            return Err(format!("Expected the voting to be closed but got {:?}", other));
+        }
+        Err(e) => {
+            return Err(e);
+        }
+    }
+
+    for address in rpc_addresses.iter() {
+        wait_until(address, "the voting to be closed", |status| ElectionPhase::Closed == status.phase)?;
+
+        let result = export::fetch_result(address, None, "selftest".to_string(), "Selftest".to_string(), Some(private_key))?;
+        if amount_of_votes != result.total_votes || Some(expected_yes_votes) != result.yes_votes {
+            return Err(format!(
+                "Node {} tallied {} votes with {:?} in favour, but {} votes with {} in favour were cast",
+                address, result.total_votes, result.yes_votes, amount_of_votes, expected_yes_votes
+            ));
+        }
+        info!("Node {} tallied {} votes with {} in favour", address, result.total_votes, expected_yes_votes);
+    }
+
+    Ok(expected_yes_votes)
+}
+
+/// Poll the election status of the node listening for RPC connections on the given address,
+/// until it satisfies the given condition or `STEP_TIMEOUT` is exceeded.
+fn wait_until<F>(rpc_address: &SocketAddr, description: &str, condition: F) -> Result<(), String>
+    where F: Fn(&ElectionStatus) -> bool {
+    let started_at = Instant::now();
+    loop {
+        match client::rpc_request(rpc_address, None, Message::ElectionStatusRequest) {
+            Ok(Message::ElectionStatusResponse(ref election_status)) if condition(election_status) => {
+                return Ok(());
+            }
+            Ok(_) => {}
+            Err(e) => {
+                debug!("Failed to request the election status from {}: {}", rpc_address, e);
+            }
+        }
+
+        if started_at.elapsed() >= STEP_TIMEOUT {
+            return Err(format!("Timed out after {:?} waiting for {} on node {}", STEP_TIMEOUT, description, rpc_address));
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Returns a local address on which no other process is listening.
+fn free_address() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap()
+}
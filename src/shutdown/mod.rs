@@ -0,0 +1,38 @@
+use libc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// The interval in which the flag set by the signal handler is checked.
+const SIGNAL_POLL_INTERVAL: u64 = 100;
+
+/// Set once SIGINT or SIGTERM was received.
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn request_shutdown(_signal: libc::c_int) {
+    // only async-signal-safe operations are allowed here
+    SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Handle SIGINT and SIGTERM by requesting a graceful shutdown
+/// instead of terminating the process immediately.
+pub fn install_signal_handlers() {
+    let handler = request_shutdown as extern "C" fn(libc::c_int) as libc::sighandler_t;
+
+    unsafe {
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+/// Returns true, if a graceful shutdown was requested.
+pub fn is_requested() -> bool {
+    SHUTDOWN_REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Block until a graceful shutdown is requested.
+pub fn wait_until_requested() {
+    while !is_requested() {
+        thread::sleep(Duration::from_millis(SIGNAL_POLL_INTERVAL));
+    }
+}
@@ -0,0 +1,301 @@
+use ::mac::{decode_hex, encode_hex};
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signature, Verifier, Signer as DalekSigner};
+#[cfg(feature = "ecdsa-p256")]
+use p256::ecdsa::{Signature as P256Signature, SigningKey as P256SigningKey, VerifyingKey as P256VerifyingKey};
+#[cfg(feature = "ecdsa-p256")]
+use p256::ecdsa::signature::{Signer as P256Signer, Verifier as P256Verifier};
+use rand::{thread_rng, Rng};
+use std::fmt;
+use std::str::FromStr;
+
+/// Separates the entries of a list of keys or signatures.
+const ENTRY_SEPARATOR: char = ',';
+
+/// Separates the identifier of the algorithm from the hex encoded key or signature of an entry.
+const ALGORITHM_SEPARATOR: char = ':';
+
+/// The algorithms with which nodes sign blocks.
+///
+/// Keys and signatures are encoded as a list of entries, each prefixed with the identifier of its algorithm,
+/// e.g. `ecdsa-p256:02ab...,ed25519:0d0e...`. Entries without identifier are Ed25519 keys or signatures,
+/// hence chains and genesis configurations using Ed25519 only are encoded as before.
+/// A sealer declaring keys of several algorithms signs its blocks with each of them, so that
+/// a new algorithm, e.g. a post-quantum one, is introduced alongside the existing ones.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum SignatureAlgorithm {
+    Ed25519,
+    #[cfg(feature = "ecdsa-p256")]
+    EcdsaP256,
+}
+
+impl SignatureAlgorithm {
+    /// Returns the algorithms supported by this build.
+    pub fn supported() -> Vec<SignatureAlgorithm> {
+        vec![
+            SignatureAlgorithm::Ed25519,
+            #[cfg(feature = "ecdsa-p256")]
+            SignatureAlgorithm::EcdsaP256,
+        ]
+    }
+
+    /// Returns the identifier of the algorithm in encoded keys and signatures.
+    pub fn identifier(&self) -> &'static str {
+        match *self {
+            SignatureAlgorithm::Ed25519 => "ed25519",
+            #[cfg(feature = "ecdsa-p256")]
+            SignatureAlgorithm::EcdsaP256 => "ecdsa-p256",
+        }
+    }
+
+    /// Generate a new secret key from a random secret.
+    pub fn generate_secret_key(&self) -> Vec<u8> {
+        loop {
+            let mut secret = [0u8; 32];
+            thread_rng().fill_bytes(&mut secret);
+
+            // only a negligible share of secrets is out of range
+            if self.derive_public_key(&secret).is_some() {
+                return secret.to_vec();
+            }
+        }
+    }
+
+    /// Returns the public key belonging to the given secret key, or None if it is malformed.
+    pub fn derive_public_key(&self, secret_key: &[u8]) -> Option<Vec<u8>> {
+        match *self {
+            SignatureAlgorithm::Ed25519 => SecretKey::from_bytes(secret_key).ok()
+                .map(|secret_key| PublicKey::from(&secret_key).as_bytes().to_vec()),
+            #[cfg(feature = "ecdsa-p256")]
+            SignatureAlgorithm::EcdsaP256 => P256SigningKey::from_slice(secret_key).ok()
+                .map(|signing_key| signing_key.verifying_key().to_encoded_point(true).as_bytes().to_vec()),
+        }
+    }
+
+    /// Returns true, if the given bytes are a public key of this algorithm.
+    pub fn is_valid_public_key(&self, public_key: &[u8]) -> bool {
+        match *self {
+            SignatureAlgorithm::Ed25519 => PublicKey::from_bytes(public_key).is_ok(),
+            #[cfg(feature = "ecdsa-p256")]
+            SignatureAlgorithm::EcdsaP256 => P256VerifyingKey::from_sec1_bytes(public_key).is_ok(),
+        }
+    }
+
+    /// Sign the given message with the given secret key.
+    /// Returns None if the secret key is malformed.
+    pub fn sign(&self, secret_key: &[u8], message: &[u8]) -> Option<Vec<u8>> {
+        match *self {
+            SignatureAlgorithm::Ed25519 => {
+                let secret_key = match SecretKey::from_bytes(secret_key) {
+                    Ok(secret_key) => secret_key,
+                    Err(_) => {
+                        return None;
+                    }
+                };
+                let keypair = Keypair {
+                    public: PublicKey::from(&secret_key),
+                    secret: secret_key,
+                };
+
+                Some(keypair.sign(message).to_bytes().to_vec())
+            }
+            #[cfg(feature = "ecdsa-p256")]
+            SignatureAlgorithm::EcdsaP256 => P256SigningKey::from_slice(secret_key).ok()
+                .map(|signing_key| {
+                    let signature: P256Signature = signing_key.sign(message);
+                    signature.to_bytes().to_vec()
+                }),
+        }
+    }
+
+    /// Returns true, if the given signature of the given message was produced
+    /// with the secret key belonging to the given public key.
+    pub fn verify(&self, public_key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+        match *self {
+            SignatureAlgorithm::Ed25519 => {
+                match (PublicKey::from_bytes(public_key), Signature::from_bytes(signature)) {
+                    (Ok(public_key), Ok(signature)) => public_key.verify(message, &signature).is_ok(),
+                    _ => false
+                }
+            }
+            #[cfg(feature = "ecdsa-p256")]
+            SignatureAlgorithm::EcdsaP256 => {
+                match (P256VerifyingKey::from_sec1_bytes(public_key), P256Signature::from_slice(signature)) {
+                    (Ok(public_key), Ok(signature)) => public_key.verify(message, &signature).is_ok(),
+                    _ => false
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for SignatureAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.identifier())
+    }
+}
+
+impl FromStr for SignatureAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match SignatureAlgorithm::supported().into_iter().find(|algorithm| algorithm.identifier() == s) {
+            Some(algorithm) => Ok(algorithm),
+            None => Err(format!("Signature algorithm {:?} is not supported", s))
+        }
+    }
+}
+
+/// A hex encoded key or signature along with the identifier of its algorithm.
+///
+/// The identifier is kept as is, since keys and signatures of algorithms
+/// not supported by this build are passed on unchanged.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct AlgorithmEntry {
+    pub algorithm: String,
+    pub value: String,
+}
+
+impl AlgorithmEntry {
+    pub fn new(algorithm: SignatureAlgorithm, value: &[u8]) -> AlgorithmEntry {
+        AlgorithmEntry {
+            algorithm: algorithm.identifier().to_string(),
+            value: encode_hex(value),
+        }
+    }
+
+    /// Returns the algorithm along with the decoded key or signature,
+    /// or None if the algorithm is not supported or the value is not hex encoded.
+    pub fn decode(&self) -> Option<(SignatureAlgorithm, Vec<u8>)> {
+        match (self.algorithm.parse::<SignatureAlgorithm>(), decode_hex(&self.value)) {
+            (Ok(algorithm), Some(value)) => Some((algorithm, value)),
+            _ => None
+        }
+    }
+}
+
+/// Encode the given entries, omitting the identifier of Ed25519 entries.
+pub fn encode_entries(entries: &[AlgorithmEntry]) -> String {
+    entries.iter()
+        .map(|entry| {
+            if SignatureAlgorithm::Ed25519.identifier() == entry.algorithm {
+                entry.value.clone()
+            } else {
+                format!("{}{}{}", entry.algorithm, ALGORITHM_SEPARATOR, entry.value)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(&ENTRY_SEPARATOR.to_string())
+}
+
+/// Decode the entries of the given encoded keys or signatures, see `encode_entries`.
+pub fn decode_entries(encoded: &str) -> Vec<AlgorithmEntry> {
+    encoded.split(ENTRY_SEPARATOR)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match entry.find(ALGORITHM_SEPARATOR) {
+            Some(index) => AlgorithmEntry {
+                algorithm: entry[..index].to_string(),
+                value: entry[index + 1..].to_string(),
+            },
+            None => AlgorithmEntry {
+                algorithm: SignatureAlgorithm::Ed25519.identifier().to_string(),
+                value: entry.to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Returns true, if the given encoded public keys contain at least one valid key of a supported algorithm,
+/// and all keys of supported algorithms are valid.
+pub fn is_valid_public_keys(public_keys: &str) -> bool {
+    let entries = decode_entries(public_keys);
+    let supported_entries: Vec<&AlgorithmEntry> = entries.iter()
+        .filter(|entry| entry.algorithm.parse::<SignatureAlgorithm>().is_ok())
+        .collect();
+
+    !supported_entries.is_empty() && supported_entries.iter().all(|entry| match entry.decode() {
+        Some((algorithm, public_key)) => algorithm.is_valid_public_key(&public_key),
+        None => false
+    })
+}
+
+/// Returns true, if the given encoded signatures of the given message hold a valid signature
+/// for each of the given encoded public keys of a supported algorithm.
+///
+/// Keys of algorithms not supported by this build are skipped, but at least one key must be verified.
+pub fn verify_entries(public_keys: &str, message: &[u8], signatures: &str) -> bool {
+    let signatures = decode_entries(signatures);
+    let mut amount_verified = 0;
+    for public_key in decode_entries(public_keys) {
+        let (algorithm, public_key) = match public_key.decode() {
+            Some(decoded) => decoded,
+            None if public_key.algorithm.parse::<SignatureAlgorithm>().is_err() => continue,
+            None => {
+                return false;
+            }
+        };
+
+        let is_verified = signatures.iter()
+            .filter(|signature| signature.algorithm == algorithm.identifier())
+            .filter_map(|signature| decode_hex(&signature.value))
+            .any(|signature| algorithm.verify(&public_key, message, &signature));
+        if !is_verified {
+            return false;
+        }
+
+        amount_verified += 1;
+    }
+
+    amount_verified > 0
+}
+
+#[cfg(test)]
+mod algorithm_test {
+
+    use super::*;
+
+    #[test]
+    fn test_verify_entries() {
+        let secret_key = SignatureAlgorithm::Ed25519.generate_secret_key();
+        let public_key = AlgorithmEntry::new(SignatureAlgorithm::Ed25519, &SignatureAlgorithm::Ed25519.derive_public_key(&secret_key).unwrap());
+        let signature = AlgorithmEntry::new(SignatureAlgorithm::Ed25519, &SignatureAlgorithm::Ed25519.sign(&secret_key, b"message").unwrap());
+
+        // Ed25519 entries are encoded without identifier
+        let public_keys = encode_entries(&[public_key.clone()]);
+        assert_eq!(public_key.value, public_keys);
+        assert_eq!(vec![public_key.clone()], decode_entries(&public_keys));
+        assert!(is_valid_public_keys(&public_keys));
+        assert!(verify_entries(&public_keys, b"message", &encode_entries(&[signature.clone()])));
+        assert!(!verify_entries(&public_keys, b"other message", &encode_entries(&[signature.clone()])));
+
+        // keys of unsupported algorithms are skipped, but must not be the only ones
+        let unknown_key = AlgorithmEntry { algorithm: "dilithium3".to_string(), value: "00".to_string() };
+        let unknown_signature = AlgorithmEntry { algorithm: "dilithium3".to_string(), value: "01".to_string() };
+        let public_keys = encode_entries(&[unknown_key.clone(), public_key.clone()]);
+        assert_eq!(format!("dilithium3:00,{}", public_key.value), public_keys);
+        assert!(is_valid_public_keys(&public_keys));
+        assert!(verify_entries(&public_keys, b"message", &encode_entries(&[unknown_signature.clone(), signature])));
+        assert!(!is_valid_public_keys(&encode_entries(&[unknown_key.clone()])));
+        assert!(!verify_entries(&encode_entries(&[unknown_key]), b"message", &encode_entries(&[unknown_signature])));
+    }
+
+    #[cfg(feature = "ecdsa-p256")]
+    #[test]
+    fn test_verify_entries_of_several_algorithms() {
+        let algorithms = SignatureAlgorithm::supported();
+        let secret_keys: Vec<Vec<u8>> = algorithms.iter().map(|algorithm| algorithm.generate_secret_key()).collect();
+        let public_keys: Vec<AlgorithmEntry> = algorithms.iter().zip(secret_keys.iter())
+            .map(|(algorithm, secret_key)| AlgorithmEntry::new(*algorithm, &algorithm.derive_public_key(secret_key).unwrap()))
+            .collect();
+        let signatures: Vec<AlgorithmEntry> = algorithms.iter().zip(secret_keys.iter())
+            .map(|(algorithm, secret_key)| AlgorithmEntry::new(*algorithm, &algorithm.sign(secret_key, b"message").unwrap()))
+            .collect();
+
+        let encoded_public_keys = encode_entries(&public_keys);
+        assert!(is_valid_public_keys(&encoded_public_keys));
+        assert!(verify_entries(&encoded_public_keys, b"message", &encode_entries(&signatures)));
+
+        // a sealer declaring several keys must sign with each of them
+        assert!(!verify_entries(&encoded_public_keys, b"message", &encode_entries(&signatures[..1])));
+        assert!(verify_entries(&encode_entries(&public_keys[1..]), b"message", &encode_entries(&signatures[1..])));
+    }
+}
@@ -0,0 +1,206 @@
+use ::chain::block::Block;
+use ::chain::types::BlockHash;
+use ::p2p::peers;
+use ::signer::algorithm::{self, AlgorithmEntry, SignatureAlgorithm};
+use ::signer::Signer;
+use serde_json;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+
+/// The signing keypair of a node, with which it signs the blocks it seals.
+///
+/// The public key must be declared for the address of the node
+/// in the `sealer_keys` of the genesis configuration.
+/// Both keys are encoded along with their algorithm, see `SignatureAlgorithm`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NodeKey {
+    /// The encoded public key.
+    pub public_key: String,
+    /// The encoded secret key.
+    pub secret_key: String,
+}
+
+impl NodeKey {
+    /// Generate a new Ed25519 keypair from a random secret.
+    pub fn generate() -> NodeKey {
+        NodeKey::generate_with(SignatureAlgorithm::Ed25519)
+    }
+
+    /// Generate a new keypair of the given algorithm from a random secret.
+    pub fn generate_with(algorithm: SignatureAlgorithm) -> NodeKey {
+        let secret_key = algorithm.generate_secret_key();
+        let public_key = algorithm.derive_public_key(&secret_key).unwrap();
+
+        NodeKey {
+            public_key: algorithm::encode_entries(&[AlgorithmEntry::new(algorithm, &public_key)]),
+            secret_key: algorithm::encode_entries(&[AlgorithmEntry::new(algorithm, &secret_key)]),
+        }
+    }
+
+    /// Read the keypair from the given JSON file.
+    pub fn from_file(file_name: &str) -> Result<NodeKey, String> {
+        let mut contents = String::new();
+        let read_result = File::open(file_name).and_then(|mut file| file.read_to_string(&mut contents));
+        match read_result {
+            Ok(_) => {}
+            Err(e) => {
+                return Err(format!("Failed to read node key {:?}: {:?}", file_name, e));
+            }
+        }
+
+        let node_key: NodeKey = match serde_json::from_str(&contents) {
+            Ok(node_key) => node_key,
+            Err(e) => {
+                return Err(format!("Failed to parse node key {:?}: {:?}", file_name, e));
+            }
+        };
+
+        if !node_key.is_valid() {
+            return Err(format!("Node key {:?} does not hold a valid keypair of a supported algorithm", file_name));
+        }
+
+        Ok(node_key)
+    }
+
+    /// Read the keypair from the given JSON file,
+    /// or generate a new one and store it there if the file does not exist.
+    ///
+    /// A generated keypair is never overwritten, so that the public key
+    /// declared in the genesis configuration stays valid.
+    pub fn load_or_generate(file_name: &str) -> Result<NodeKey, String> {
+        if Path::new(file_name).exists() {
+            return NodeKey::from_file(file_name);
+        }
+
+        let node_key = NodeKey::generate();
+        let write_result = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(file_name)
+            .and_then(|mut file| file.write_all(serde_json::to_string_pretty(&node_key).unwrap().as_bytes()));
+        match write_result {
+            Ok(()) => {
+                info!("Generated node key with public key {} at {:?}", node_key.public_key, file_name);
+                Ok(node_key)
+            }
+            Err(e) => Err(format!("Failed to write node key {:?}: {:?}", file_name, e))
+        }
+    }
+
+    /// Returns true, if the keys are well-formed and belong together.
+    pub fn is_valid(&self) -> bool {
+        self.to_secret_key().is_some()
+    }
+
+    /// Returns the algorithm of the keypair, or None if the keys are malformed.
+    pub fn get_algorithm(&self) -> Option<SignatureAlgorithm> {
+        self.to_secret_key().map(|(algorithm, _)| algorithm)
+    }
+
+    /// Sign the given message, e.g. the opening of the voting by the election authority.
+    /// Returns the signature encoded along with its algorithm.
+    pub fn sign_message(&self, message: &[u8]) -> Result<String, String> {
+        match self.to_secret_key().and_then(|(algorithm, secret_key)| algorithm.sign(&secret_key, message).map(|signature| (algorithm, signature))) {
+            Some((algorithm, signature)) => Ok(algorithm::encode_entries(&[AlgorithmEntry::new(algorithm, &signature)])),
+            None => Err("Node key does not hold a valid keypair of a supported algorithm".to_string())
+        }
+    }
+
+    /// Returns the algorithm and the secret key, or None if the keys are malformed,
+    /// of an unsupported algorithm or do not belong together.
+    fn to_secret_key(&self) -> Option<(SignatureAlgorithm, Vec<u8>)> {
+        let entries = algorithm::decode_entries(&self.secret_key);
+        let (algorithm, secret_key) = match entries.first().and_then(|entry| entry.decode()) {
+            Some(decoded) if 1 == entries.len() => decoded,
+            _ => {
+                return None;
+            }
+        };
+
+        match algorithm.derive_public_key(&secret_key) {
+            Some(ref public_key) if algorithm::encode_entries(&[AlgorithmEntry::new(algorithm, public_key)]) == self.public_key => Some((algorithm, secret_key)),
+            _ => None
+        }
+    }
+}
+
+impl Signer for NodeKey {
+    fn sign(&self, block_identifier: &BlockHash) -> Result<String, String> {
+        self.sign_message(block_identifier.as_str().as_bytes())
+    }
+}
+
+/// Returns true, if the given string holds encoded public keys, see `SignatureAlgorithm`.
+pub fn is_valid_public_key(public_key: &str) -> bool {
+    algorithm::is_valid_public_keys(public_key)
+}
+
+/// Returns true, if the given encoded signatures of the given block identifier were produced
+/// with the secret keys belonging to the given encoded public keys.
+pub fn verify_signature(public_key: &str, block_identifier: &BlockHash, signature: &str) -> bool {
+    algorithm::verify_entries(public_key, block_identifier.as_str().as_bytes(), signature)
+}
+
+/// Check that the given block is signed by its sealer, using the public keys of the given sealers.
+/// As the signature only covers the identifier of the block, the identifier must match the content of the block.
+///
+/// Returns a description of the failure, if the identifier does not match, the block is unsigned, its sealer
+/// has no public key or the signature does not match.
+pub fn verify_block_signature(sealer_keys: &BTreeMap<SocketAddr, String>, block: &Block) -> Result<(), String> {
+    if !block.has_valid_identifier() {
+        return Err(format!("Identifier {:?} does not match the content of the block", block.identifier));
+    }
+
+    let sealer = match block.data.sealer {
+        Some(ref sealer) => sealer,
+        None => {
+            return Err("Block does not specify its sealer".to_string());
+        }
+    };
+
+    let public_key = match sealer_keys.iter().find(|&(address, _)| peers::is_same_peer(address, sealer)) {
+        Some((_, public_key)) => public_key,
+        None => {
+            return Err(format!("No public key is declared for sealer {}", sealer));
+        }
+    };
+
+    match block.signature {
+        Some(ref signature) if verify_signature(public_key, &block.identifier, signature) => Ok(()),
+        Some(_) => Err(format!("Signature does not match the public key of sealer {}", sealer)),
+        None => Err(format!("Block sealed by {} is not signed", sealer))
+    }
+}
+
+#[cfg(test)]
+mod key_test {
+
+    use super::*;
+    use ::chain::hasher::Hasher;
+
+    #[test]
+    fn test_verify_block_signature() {
+        let sealer: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let node_key = NodeKey::generate();
+        let other_node_key = NodeKey::generate();
+
+        let mut sealer_keys = BTreeMap::new();
+        sealer_keys.insert(sealer, node_key.public_key.clone());
+
+        let mut block = Block::new(Hasher::default(), BlockHash::from("parent"), Some(sealer), vec![]);
+        assert!(verify_block_signature(&sealer_keys, &block).is_err());
+
+        block.signature = Some(other_node_key.sign(&block.identifier).unwrap());
+        assert!(verify_block_signature(&sealer_keys, &block).is_err());
+
+        block.signature = Some(node_key.sign(&block.identifier).unwrap());
+        assert!(verify_block_signature(&sealer_keys, &block).is_ok());
+
+        // the signature does not cover content changed after signing
+        block.data.timestamp += 1;
+        assert!(verify_block_signature(&sealer_keys, &block).is_err());
+    }
+}
@@ -0,0 +1,221 @@
+use ::mac::{decode_hex, encode_hex};
+use ::signer::key::NodeKey;
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use rand::{thread_rng, Rng};
+use scrypt::{self, Params};
+use serde_json;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+
+/// The version of the keystore format.
+pub const KEYSTORE_VERSION: u32 = 1;
+
+/// The base two logarithm of the scrypt cost parameter used for new keystores.
+const SCRYPT_LOG_N: u8 = 15;
+
+/// The scrypt block size used for new keystores.
+const SCRYPT_R: u32 = 8;
+
+/// The scrypt parallelization parameter used for new keystores.
+const SCRYPT_P: u32 = 1;
+
+/// The amount of bytes of the random salt.
+const SALT_LENGTH: usize = 16;
+
+/// The amount of bytes of the random nonce.
+const NONCE_LENGTH: usize = 12;
+
+/// The parameters of the key derivation, with which the encryption key is derived from the passphrase.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct KdfParameters {
+    /// The base two logarithm of the scrypt cost parameter.
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+    /// The hex encoded random salt.
+    pub salt: String,
+}
+
+/// A node identity keypair, whose secret key is encrypted with a passphrase.
+///
+/// The encryption key is derived from the passphrase with scrypt and the keypair
+/// is encrypted with ChaCha20-Poly1305, authenticating the public key along with it.
+/// The public key is stored in plain, so that it can be listed without the passphrase.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct Keystore {
+    pub version: u32,
+    /// The hex encoded Ed25519 public key.
+    pub public_key: String,
+    pub kdf: KdfParameters,
+    /// The hex encoded nonce of the encryption.
+    pub nonce: String,
+    /// The hex encoded encrypted keypair.
+    pub ciphertext: String,
+}
+
+impl Keystore {
+    /// Encrypt the given keypair with the given passphrase.
+    pub fn seal(node_key: &NodeKey, passphrase: &str) -> Keystore {
+        Keystore::seal_with_cost(node_key, passphrase, SCRYPT_LOG_N)
+    }
+
+    /// Encrypt the given keypair with the given passphrase,
+    /// deriving the encryption key with the given scrypt cost parameter.
+    fn seal_with_cost(node_key: &NodeKey, passphrase: &str, log_n: u8) -> Keystore {
+        let mut salt = [0u8; SALT_LENGTH];
+        thread_rng().fill_bytes(&mut salt);
+        let mut nonce = [0u8; NONCE_LENGTH];
+        thread_rng().fill_bytes(&mut nonce);
+
+        let kdf = KdfParameters {
+            log_n,
+            r: SCRYPT_R,
+            p: SCRYPT_P,
+            salt: encode_hex(&salt),
+        };
+        let cipher = Keystore::derive_cipher(&kdf, passphrase).unwrap();
+
+        let plaintext = serde_json::to_string(node_key).unwrap();
+        let payload = Payload {
+            msg: plaintext.as_bytes(),
+            aad: node_key.public_key.as_bytes(),
+        };
+        let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce), payload).unwrap();
+
+        Keystore {
+            version: KEYSTORE_VERSION,
+            public_key: node_key.public_key.clone(),
+            kdf,
+            nonce: encode_hex(&nonce),
+            ciphertext: encode_hex(&ciphertext),
+        }
+    }
+
+    /// Decrypt the keypair with the given passphrase.
+    ///
+    /// Returns an error if the passphrase is wrong, the keystore was tampered with
+    /// or the decrypted keypair does not match the public key of the keystore.
+    pub fn unseal(&self, passphrase: &str) -> Result<NodeKey, String> {
+        if KEYSTORE_VERSION != self.version {
+            return Err(format!("Unsupported keystore version {}, expected {}", self.version, KEYSTORE_VERSION));
+        }
+
+        let cipher = match Keystore::derive_cipher(&self.kdf, passphrase) {
+            Ok(cipher) => cipher,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+        let nonce = match decode_hex(&self.nonce) {
+            Some(ref nonce) if NONCE_LENGTH == nonce.len() => nonce.clone(),
+            _ => {
+                return Err("The nonce of the keystore is malformed".to_string());
+            }
+        };
+        let ciphertext = match decode_hex(&self.ciphertext) {
+            Some(ciphertext) => ciphertext,
+            None => {
+                return Err("The ciphertext of the keystore is malformed".to_string());
+            }
+        };
+
+        let payload = Payload {
+            msg: &ciphertext,
+            aad: self.public_key.as_bytes(),
+        };
+        let plaintext = match cipher.decrypt(Nonce::from_slice(&nonce), payload) {
+            Ok(plaintext) => plaintext,
+            Err(_) => {
+                return Err("Failed to decrypt the keystore, the passphrase is wrong or the keystore was modified".to_string());
+            }
+        };
+
+        let node_key: NodeKey = match serde_json::from_slice(&plaintext) {
+            Ok(node_key) => node_key,
+            Err(e) => {
+                return Err(format!("Failed to parse the decrypted keypair: {:?}", e));
+            }
+        };
+
+        if node_key.public_key != self.public_key || !node_key.is_valid() {
+            return Err("The decrypted keypair does not match the public key of the keystore".to_string());
+        }
+
+        Ok(node_key)
+    }
+
+    /// Read the keystore from the given JSON file.
+    pub fn from_file(file_name: &str) -> Result<Keystore, String> {
+        let mut contents = String::new();
+        let read_result = File::open(file_name).and_then(|mut file| file.read_to_string(&mut contents));
+        match read_result {
+            Ok(_) => {}
+            Err(e) => {
+                return Err(format!("Failed to read keystore {:?}: {:?}", file_name, e));
+            }
+        }
+
+        match serde_json::from_str(&contents) {
+            Ok(keystore) => Ok(keystore),
+            Err(e) => Err(format!("Failed to parse keystore {:?}: {:?}", file_name, e))
+        }
+    }
+
+    /// Write the keystore to the given file.
+    /// An existing file is never overwritten, so that no identity gets lost.
+    pub fn write_to_file(&self, file_name: &str) -> Result<(), String> {
+        let write_result = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(file_name)
+            .and_then(|mut file| file.write_all(serde_json::to_string_pretty(self).unwrap().as_bytes()));
+
+        match write_result {
+            Ok(()) => Ok(()),
+            Err(e) => Err(format!("Failed to write keystore {:?}: {:?}", file_name, e))
+        }
+    }
+
+    /// Derive the cipher encrypting the keypair from the given passphrase.
+    fn derive_cipher(kdf: &KdfParameters, passphrase: &str) -> Result<ChaCha20Poly1305, String> {
+        let salt = match decode_hex(&kdf.salt) {
+            Some(salt) => salt,
+            None => {
+                return Err("The salt of the keystore is malformed".to_string());
+            }
+        };
+        let params = match Params::new(kdf.log_n, kdf.r, kdf.p, 32) {
+            Ok(params) => params,
+            Err(e) => {
+                return Err(format!("Invalid key derivation parameters of the keystore: {:?}", e));
+            }
+        };
+
+        let mut key = [0u8; 32];
+        match scrypt::scrypt(passphrase.as_bytes(), &salt, &params, &mut key) {
+            Ok(()) => Ok(ChaCha20Poly1305::new(Key::from_slice(&key))),
+            Err(e) => Err(format!("Failed to derive the key of the keystore: {:?}", e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod keystore_test {
+
+    use super::*;
+
+    #[test]
+    fn test_seal_and_unseal() {
+        let node_key = NodeKey::generate();
+        // a low cost keeps the test fast
+        let mut keystore = Keystore::seal_with_cost(&node_key, "correct horse", 4);
+
+        let unsealed = keystore.unseal("correct horse").unwrap();
+        assert_eq!(node_key.secret_key, unsealed.secret_key);
+        assert!(keystore.unseal("wrong horse").is_err());
+
+        keystore.public_key = NodeKey::generate().public_key;
+        assert!(keystore.unseal("correct horse").is_err());
+    }
+}
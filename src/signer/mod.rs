@@ -0,0 +1,184 @@
+use ::chain::types::BlockHash;
+use serde_json;
+use std::io::{BufRead, BufReader, Write};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// The algorithms with which blocks are signed and the encoding of their keys and signatures.
+pub mod algorithm;
+
+/// The signing keypair of a node and the verification of block signatures.
+pub mod key;
+
+/// The encrypted storage of node identity keypairs.
+pub mod keystore;
+
+/// Produces the signatures of the blocks sealed by this node.
+pub trait Signer: Send + Sync {
+    /// Sign the block with the given identifier.
+    ///
+    /// Returns the signatures encoded along with their algorithms, see `SignatureAlgorithm`,
+    /// or a description of the failure if no signature could be obtained.
+    fn sign(&self, block_identifier: &BlockHash) -> Result<String, String>;
+}
+
+/// A request sent to an external signer.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SignRequest {
+    pub block_identifier: BlockHash,
+}
+
+/// The response of an external signer, containing either
+/// the signature or the reason why the block was not signed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SignResponse {
+    pub signature: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Delegates signing to an external process, e.g. one with access
+/// to a hardware security module, listening on a Unix domain socket.
+/// This way, the key of the sealer never resides on the host exposed to the network.
+///
+/// For each block, a new connection is opened on which a single line containing
+/// a JSON encoded `SignRequest` is sent. The signer must respond with a single line
+/// containing a JSON encoded `SignResponse`.
+pub struct UnixSocketSigner {
+    /// The path of the socket the external signer listens on.
+    socket_path: PathBuf,
+    /// The maximum time to wait for the external signer to respond.
+    timeout: Duration,
+}
+
+impl UnixSocketSigner {
+    /// Create a new signer connecting to the socket at the given path.
+    pub fn new(socket_path: PathBuf, timeout: Duration) -> UnixSocketSigner {
+        UnixSocketSigner {
+            socket_path,
+            timeout,
+        }
+    }
+
+    #[cfg(unix)]
+    fn request_signature(&self, request: &SignRequest) -> Result<SignResponse, String> {
+        let mut stream = match UnixStream::connect(&self.socket_path) {
+            Ok(stream) => stream,
+            Err(e) => {
+                return Err(format!("Failed to connect to external signer at {:?}: {:?}", self.socket_path, e));
+            }
+        };
+
+        let timeout_result = stream.set_read_timeout(Some(self.timeout))
+            .and_then(|_| stream.set_write_timeout(Some(self.timeout)));
+        match timeout_result {
+            Ok(()) => {}
+            Err(e) => {
+                return Err(format!("Failed to configure connection to external signer: {:?}", e));
+            }
+        }
+
+        let mut encoded_request = serde_json::to_string(request).unwrap();
+        encoded_request.push('\n');
+        match stream.write_all(encoded_request.as_bytes()).and_then(|_| stream.flush()) {
+            Ok(()) => {}
+            Err(e) => {
+                return Err(format!("Failed to send request to external signer: {:?}", e));
+            }
+        }
+
+        let mut encoded_response = String::new();
+        match BufReader::new(stream).read_line(&mut encoded_response) {
+            Ok(0) => Err("External signer closed the connection without responding".to_string()),
+            Ok(_) => {
+                match serde_json::from_str(&encoded_response) {
+                    Ok(response) => Ok(response),
+                    Err(e) => Err(format!("Failed to decode response of external signer: {:?}", e))
+                }
+            }
+            Err(e) => Err(format!("Failed to read response of external signer: {:?}", e))
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn request_signature(&self, _request: &SignRequest) -> Result<SignResponse, String> {
+        Err("External signers are only supported on Unix platforms".to_string())
+    }
+}
+
+impl Signer for UnixSocketSigner {
+    fn sign(&self, block_identifier: &BlockHash) -> Result<String, String> {
+        let request = SignRequest {
+            block_identifier: block_identifier.clone(),
+        };
+
+        let response = match self.request_signature(&request) {
+            Ok(response) => response,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+
+        match (response.signature, response.error) {
+            (Some(signature), None) => Ok(signature),
+            (_, Some(error)) => Err(format!("External signer refused to sign: {}", error)),
+            (None, None) => Err("External signer responded without a signature".to_string())
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod signer_test {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::os::unix::net::UnixListener;
+    use std::thread;
+
+    /// Answer each request on a socket in the given directory with the given response line.
+    fn serve(directory: &PathBuf, name: &str, responses: Vec<&'static str>) -> PathBuf {
+        let socket_path = directory.join(name);
+        let listener = UnixListener::bind(&socket_path).unwrap();
+
+        thread::spawn(move || {
+            for (stream, response) in listener.incoming().zip(responses) {
+                let mut stream = stream.unwrap();
+                let mut request = String::new();
+                BufReader::new(stream.try_clone().unwrap()).read_line(&mut request).unwrap();
+                let request: SignRequest = serde_json::from_str(&request).unwrap();
+                assert_eq!(BlockHash::from("block"), request.block_identifier);
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        socket_path
+    }
+
+    #[test]
+    fn test_sign() {
+        let directory = env::temp_dir().join(format!("node_rs_signer_test_{}", ::std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let timeout = Duration::from_secs(5);
+
+        let socket_path = serve(&directory, "signer.sock", vec![
+            "{\"signature\":\"ed25519:c2lnbmF0dXJl\",\"error\":null}\n",
+            "{\"signature\":null,\"error\":\"key locked\"}\n",
+            "{\"signature\":null,\"error\":null}\n",
+            "not json\n",
+        ]);
+        let signer = UnixSocketSigner::new(socket_path, timeout);
+        assert_eq!(Ok("ed25519:c2lnbmF0dXJl".to_string()), signer.sign(&BlockHash::from("block")));
+        assert_eq!(Err("External signer refused to sign: key locked".to_string()), signer.sign(&BlockHash::from("block")));
+        assert_eq!(Err("External signer responded without a signature".to_string()), signer.sign(&BlockHash::from("block")));
+        assert!(signer.sign(&BlockHash::from("block")).is_err());
+
+        // a signer which is not running or does not respond in time does not block sealing
+        assert!(UnixSocketSigner::new(directory.join("missing.sock"), timeout).sign(&BlockHash::from("block")).is_err());
+        let silent_path = directory.join("silent.sock");
+        let _silent_listener = UnixListener::bind(&silent_path).unwrap();
+        assert!(UnixSocketSigner::new(silent_path, Duration::from_millis(100)).sign(&BlockHash::from("block")).is_err());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
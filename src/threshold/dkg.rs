@@ -0,0 +1,278 @@
+use ::threshold::{self, KeyShare};
+use crypto_rs::arithmetic::mod_int::{From as ModIntFrom, ModInt};
+use crypto_rs::el_gamal::encryption::PublicKey;
+use num::{BigInt, Integer, One, Zero};
+use serde_json;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+/// The name of the file in the data directory holding the progress of this sealer in the key generation.
+pub const KEY_GENERATION_FILE_NAME: &str = "key_generation.json";
+
+/// The secret polynomial a sealer deals its contribution to the election key with.
+///
+/// The election key is the product of the commitments `g^a_0` of all dealers, whereas
+/// each sealer receives a share of the polynomial of each dealer, from which it derives
+/// its share of the private key without anyone learning the private key itself.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Dealing {
+    /// The coefficients `a_0, ..., a_(t-1)` of the polynomial, whereas `a_0` is the contribution of the dealer.
+    coefficients: Vec<ModInt>,
+}
+
+impl Dealing {
+    /// Choose a random polynomial of degree `threshold - 1` in the group of the given public key.
+    pub fn generate(public_key: &PublicKey, threshold: usize) -> Dealing {
+        let q = &public_key.q.value;
+        let coefficients = (0..threshold)
+            .map(|_| ModInt::from_value_modulus(threshold::random_below(q), q.clone()))
+            .collect();
+
+        Dealing { coefficients }
+    }
+
+    /// Returns the commitments `g^a_j` to the coefficients, which are published on the chain.
+    pub fn get_commitments(&self, public_key: &PublicKey) -> Vec<ModInt> {
+        let p = &public_key.p.value;
+
+        self.coefficients.iter()
+            .map(|coefficient| ModInt::from_value_modulus(public_key.g.value.modpow(&coefficient.value, p), p.clone()))
+            .collect()
+    }
+
+    /// Returns the share `f(index)` of the sealer with the given index, starting at one.
+    pub fn get_share(&self, public_key: &PublicKey, index: usize) -> ModInt {
+        let q = &public_key.q.value;
+        let x = BigInt::from(index as u64);
+
+        // Horner's method, starting with the highest coefficient
+        let share = self.coefficients.iter().rev()
+            .fold(BigInt::zero(), |share, coefficient| (share * &x + &coefficient.value).mod_floor(q));
+
+        ModInt::from_value_modulus(share, q.clone())
+    }
+}
+
+/// The share of the polynomial of a dealer, sent by the dealer directly to the sealer it belongs to.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct DealtShare {
+    /// The sealer which dealt the share.
+    pub dealer: SocketAddr,
+    /// The sealer the share belongs to.
+    pub recipient: SocketAddr,
+    /// The value `f(i)` of the polynomial of the dealer at the index `i` of the recipient.
+    pub share: ModInt,
+}
+
+/// Returns true, if the given share of the sealer with the given index matches
+/// the given commitments of its dealer, i.e. `g^f(i)` equals the product of `C_j^(i^j)`.
+pub fn verify_share(public_key: &PublicKey, commitments: &[ModInt], index: usize, share: &ModInt) -> bool {
+    let p = &public_key.p.value;
+
+    public_key.g.value.modpow(&share.value, p) == evaluate_commitments(public_key, commitments, index)
+}
+
+/// Returns the election key, i.e. the product of the commitments `g^a_0` of all given dealers.
+pub fn get_election_key(public_key: &PublicKey, commitments: &BTreeMap<SocketAddr, Vec<ModInt>>) -> ModInt {
+    let p = &public_key.p.value;
+    let election_key = commitments.values()
+        .filter_map(|dealer_commitments| dealer_commitments.first())
+        .fold(BigInt::one(), |election_key, commitment| (election_key * &commitment.value).mod_floor(p));
+
+    ModInt::from_value_modulus(election_key, p.clone())
+}
+
+/// Returns the public key share `g^x_i` of the sealer with the given index, starting at one,
+/// derived from the given commitments of all dealers.
+pub fn get_public_key_share(public_key: &PublicKey, commitments: &BTreeMap<SocketAddr, Vec<ModInt>>, index: usize) -> ModInt {
+    let p = &public_key.p.value;
+    let public_key_share = commitments.values()
+        .fold(BigInt::one(), |public_key_share, dealer_commitments| (public_key_share * evaluate_commitments(public_key, dealer_commitments, index)).mod_floor(p));
+
+    ModInt::from_value_modulus(public_key_share, p.clone())
+}
+
+/// Combine the given shares received from all dealers into the key share of their recipient.
+pub fn combine_shares(public_key: &PublicKey, shares: &BTreeMap<SocketAddr, ModInt>) -> KeyShare {
+    let q = &public_key.q.value;
+    let x = shares.values()
+        .fold(BigInt::zero(), |x, share| (x + &share.value).mod_floor(q));
+
+    KeyShare {
+        x: ModInt::from_value_modulus(x, q.clone())
+    }
+}
+
+/// Returns the product of `C_j^(i^j)` of the given commitments.
+fn evaluate_commitments(public_key: &PublicKey, commitments: &[ModInt], index: usize) -> BigInt {
+    let p = &public_key.p.value;
+    let q = &public_key.q.value;
+    let x = BigInt::from(index as u64);
+
+    let mut exponent = BigInt::one();
+    let mut result = BigInt::one();
+    for commitment in commitments {
+        result = (result * commitment.value.modpow(&exponent, p)).mod_floor(p);
+        exponent = (exponent * &x).mod_floor(q);
+    }
+
+    result
+}
+
+/// The progress of this sealer in the key generation, i.e. its dealing and the shares it received from the dealers.
+///
+/// If opened in a data directory, the progress survives restarts, so that a sealer keeps its dealing
+/// published on the chain and derives the same key share again.
+#[derive(Default)]
+pub struct KeyGeneration {
+    /// The directory holding the progress, or None if kept in memory only.
+    data_directory: Option<PathBuf>,
+    progress: KeyGenerationProgress,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct KeyGenerationProgress {
+    dealing: Option<Dealing>,
+    received_shares: BTreeMap<SocketAddr, ModInt>,
+}
+
+impl KeyGeneration {
+    /// Create a new key generation, which is kept in memory only.
+    pub fn new() -> KeyGeneration {
+        KeyGeneration::default()
+    }
+
+    /// Open the key generation persisted in the given data directory, creating the directory if necessary.
+    pub fn open(data_directory: &Path) -> Result<KeyGeneration, String> {
+        match fs::create_dir_all(data_directory) {
+            Ok(()) => {}
+            Err(e) => {
+                return Err(format!("Failed to create data directory {:?}: {:?}", data_directory, e));
+            }
+        }
+
+        let path = data_directory.join(KEY_GENERATION_FILE_NAME);
+        let mut contents = String::new();
+        let progress = match File::open(&path).and_then(|mut file| file.read_to_string(&mut contents)) {
+            Ok(_) => match serde_json::from_str(&contents) {
+                Ok(progress) => progress,
+                Err(e) => {
+                    return Err(format!("Failed to parse key generation {:?}: {:?}", path, e));
+                }
+            },
+            Err(_) => KeyGenerationProgress::default()
+        };
+
+        Ok(KeyGeneration {
+            data_directory: Some(data_directory.to_path_buf()),
+            progress,
+        })
+    }
+
+    /// Returns the dealing of this sealer, or None if it did not deal yet.
+    pub fn get_dealing(&self) -> Option<&Dealing> {
+        self.progress.dealing.as_ref()
+    }
+
+    /// Keep the given dealing of this sealer.
+    pub fn set_dealing(&mut self, dealing: Dealing) -> Result<(), String> {
+        self.progress.dealing = Some(dealing);
+        self.persist()
+    }
+
+    /// Returns the verified shares this sealer received, by their dealer.
+    pub fn get_received_shares(&self) -> &BTreeMap<SocketAddr, ModInt> {
+        &self.progress.received_shares
+    }
+
+    /// Keep the given verified share this sealer received from the given dealer.
+    pub fn add_received_share(&mut self, dealer: SocketAddr, share: ModInt) -> Result<(), String> {
+        self.progress.received_shares.insert(dealer, share);
+        self.persist()
+    }
+
+    fn persist(&self) -> Result<(), String> {
+        let data_directory = match self.data_directory {
+            Some(ref data_directory) => data_directory,
+            None => {
+                return Ok(());
+            }
+        };
+
+        // replace the file atomically, so that a crash does not lose the dealing
+        let path = data_directory.join(KEY_GENERATION_FILE_NAME);
+        let temporary_path = path.with_extension("tmp");
+        let contents = serde_json::to_string(&self.progress).unwrap();
+        let write_result = File::create(&temporary_path)
+            .and_then(|mut file| file.write_all(contents.as_bytes()).and_then(|_| file.sync_all()))
+            .and_then(|_| fs::rename(&temporary_path, &path));
+
+        match write_result {
+            Ok(()) => Ok(()),
+            Err(e) => Err(format!("Failed to persist key generation to {:?}: {:?}", path, e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod dkg_test {
+
+    use super::*;
+    use crypto_rs::el_gamal::ciphertext::CipherText;
+    use num::ToPrimitive;
+
+    #[test]
+    fn test_distributed_key_generation() {
+        // the subgroup of order 11 of the integers modulo 23, generated by 4
+        let mod_int = |value: u64, modulus: u64| ModInt::from_value_modulus(BigInt::from(value), BigInt::from(modulus));
+        let public_key = PublicKey {
+            p: mod_int(23, 23),
+            q: mod_int(11, 11),
+            h: mod_int(1, 23),
+            g: mod_int(4, 23),
+        };
+        let sealers: Vec<SocketAddr> = (0..3).map(|port| format!("127.0.0.1:{}", 3000 + port).parse().unwrap()).collect();
+
+        let dealings: Vec<Dealing> = sealers.iter().map(|_| Dealing::generate(&public_key, 2)).collect();
+        let commitments: BTreeMap<SocketAddr, Vec<ModInt>> = sealers.iter().cloned()
+            .zip(dealings.iter().map(|dealing| dealing.get_commitments(&public_key)))
+            .collect();
+
+        let key_shares: Vec<KeyShare> = (1..4)
+            .map(|index| {
+                let shares: BTreeMap<SocketAddr, ModInt> = sealers.iter().cloned()
+                    .zip(dealings.iter().map(|dealing| dealing.get_share(&public_key, index)))
+                    .collect();
+                for (dealer, share) in shares.iter() {
+                    assert!(verify_share(&public_key, &commitments[dealer], index, share));
+                }
+                let forged_share = mod_int((shares[&sealers[0]].value.to_u64().unwrap() + 1) % 11, 11);
+                assert!(!verify_share(&public_key, &commitments[&sealers[0]], index, &forged_share));
+
+                let key_share = combine_shares(&public_key, &shares);
+                assert_eq!(get_public_key_share(&public_key, &commitments, index).value, public_key.g.value.modpow(&key_share.x.value, &public_key.p.value));
+
+                key_share
+            })
+            .collect();
+
+        // any two sealers decrypt 3, encrypted with the random 5 under the election key
+        let p = &public_key.p.value;
+        let election_key = get_election_key(&public_key, &commitments);
+        let election_public_key = PublicKey { h: election_key.clone(), ..public_key.clone() };
+        let cipher_text = CipherText {
+            big_g: mod_int(public_key.g.value.modpow(&BigInt::from(5), p).to_u64().unwrap(), 23),
+            big_h: mod_int((election_key.value.modpow(&BigInt::from(5), p) * public_key.g.value.modpow(&BigInt::from(3), p)).mod_floor(p).to_u64().unwrap(), 23),
+            random: mod_int(5, 11),
+        };
+
+        let partial_decryptions: Vec<(usize, ModInt)> = key_shares.iter().enumerate()
+            .map(|(position, key_share)| (position + 1, threshold::partially_decrypt(&election_public_key, key_share, &cipher_text).0))
+            .collect();
+        assert_eq!(Some(3), threshold::combine(&election_public_key, &cipher_text, &partial_decryptions[1..], 10));
+        assert_eq!(Some(3), threshold::combine(&election_public_key, &cipher_text, &[partial_decryptions[0].clone(), partial_decryptions[2].clone()], 10));
+    }
+}
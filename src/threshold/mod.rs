@@ -0,0 +1,209 @@
+use bincode;
+use crypto_rs::arithmetic::mod_int::{From as ModIntFrom, ModInt};
+use crypto_rs::el_gamal::ciphertext::CipherText;
+use crypto_rs::el_gamal::encryption::PublicKey;
+use num::bigint::Sign;
+use num::{BigInt, Integer, One, Zero};
+use rand::{thread_rng, Rng};
+use serde_json;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::Read;
+
+/// Generating the election key jointly by the sealers, so that no one ever holds the private key.
+pub mod dkg;
+
+/// Separates the challenges of decryption proofs from other digests.
+const PROOF_DOMAIN: &str = "node-rs-decryption-proof";
+
+/// The share of the private key of the election held by a single sealer.
+///
+/// Any `threshold` of the shares, as declared in the genesis configuration, together
+/// decrypt the tally, while fewer shares reveal nothing about it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct KeyShare {
+    /// The secret share `x_i` of the private key, whose public key share `g^x_i` is declared in the genesis configuration.
+    pub x: ModInt,
+}
+
+impl KeyShare {
+    /// Read the key share from the given JSON file.
+    pub fn from_file(file_name: &str) -> Result<KeyShare, String> {
+        let mut contents = String::new();
+        let read_result = File::open(file_name).and_then(|mut file| file.read_to_string(&mut contents));
+        match read_result {
+            Ok(_) => {}
+            Err(e) => {
+                return Err(format!("Failed to read key share {:?}: {:?}", file_name, e));
+            }
+        }
+
+        match serde_json::from_str(&contents) {
+            Ok(key_share) => Ok(key_share),
+            Err(e) => Err(format!("Failed to parse key share {:?}: {:?}", file_name, e))
+        }
+    }
+}
+
+/// A non-interactive Chaum-Pedersen proof that a partial decryption `G^x_i` of a cipher text `(G, H)`
+/// uses the same secret as the public key share `g^x_i` of its sealer, without revealing it.
+#[derive(Eq, PartialEq, Hash, Serialize, Deserialize, Debug, Clone)]
+pub struct DecryptionProof {
+    /// The commitment `g^w` to the random nonce `w`.
+    pub commitment_g: ModInt,
+    /// The commitment `G^w` to the random nonce `w`.
+    pub commitment_c: ModInt,
+    /// The response `w + c * x_i mod q` to the challenge `c`.
+    pub response: ModInt,
+}
+
+/// Partially decrypt the given cipher text with the given key share, i.e. compute `G^x_i`,
+/// along with the proof of its correctness.
+pub fn partially_decrypt(public_key: &PublicKey, key_share: &KeyShare, cipher_text: &CipherText) -> (ModInt, DecryptionProof) {
+    let p = &public_key.p.value;
+    let q = &public_key.q.value;
+    let g = &public_key.g.value;
+    let big_g = &cipher_text.big_g.value;
+
+    let public_key_share = g.modpow(&key_share.x.value, p);
+    let partial_decryption = big_g.modpow(&key_share.x.value, p);
+
+    let nonce = random_below(q);
+    let commitment_g = g.modpow(&nonce, p);
+    let commitment_c = big_g.modpow(&nonce, p);
+    let challenge = get_challenge(public_key, &public_key_share, big_g, &partial_decryption, &commitment_g, &commitment_c);
+    let response = (nonce + challenge * &key_share.x.value).mod_floor(q);
+
+    let proof = DecryptionProof {
+        commitment_g: ModInt::from_value_modulus(commitment_g, p.clone()),
+        commitment_c: ModInt::from_value_modulus(commitment_c, p.clone()),
+        response: ModInt::from_value_modulus(response, q.clone()),
+    };
+
+    (ModInt::from_value_modulus(partial_decryption, p.clone()), proof)
+}
+
+/// Returns true, if the given partial decryption of the given cipher text is proven
+/// to be computed with the secret belonging to the given public key share.
+pub fn verify_partial_decryption(public_key: &PublicKey, public_key_share: &ModInt, cipher_text: &CipherText, partial_decryption: &ModInt, proof: &DecryptionProof) -> bool {
+    let p = &public_key.p.value;
+    let g = &public_key.g.value;
+    let big_g = &cipher_text.big_g.value;
+
+    let is_in_range = |value: &BigInt| value.sign() == Sign::Plus && value < p;
+    if ![&public_key_share.value, &partial_decryption.value, &proof.commitment_g.value, &proof.commitment_c.value].iter().all(|value| is_in_range(value)) {
+        return false;
+    }
+
+    let challenge = get_challenge(public_key, &public_key_share.value, big_g, &partial_decryption.value, &proof.commitment_g.value, &proof.commitment_c.value);
+
+    // g^r = g^w * (g^x_i)^c and G^r = G^w * (G^x_i)^c
+    g.modpow(&proof.response.value, p) == (&proof.commitment_g.value * public_key_share.value.modpow(&challenge, p)).mod_floor(p)
+        && big_g.modpow(&proof.response.value, p) == (&proof.commitment_c.value * partial_decryption.value.modpow(&challenge, p)).mod_floor(p)
+}
+
+/// Combine the given partial decryptions of the given cipher text into the plaintext,
+/// each given along with the index of its key share, starting at one.
+///
+/// The shares are combined by Lagrange interpolation in the exponent, which yields `g^m`, from which
+/// the plaintext `m` is recovered by searching up to `max_plaintext`. Returns None if it is not found,
+/// e.g. if fewer partial decryptions than the threshold are given.
+pub fn combine(public_key: &PublicKey, cipher_text: &CipherText, partial_decryptions: &[(usize, ModInt)], max_plaintext: u64) -> Option<u64> {
+    let p = &public_key.p.value;
+    let q = &public_key.q.value;
+    let g = &public_key.g.value;
+
+    // G^x = product of (G^x_i)^lambda_i, where lambda_i is the Lagrange coefficient of i at zero
+    let mut big_g_x = BigInt::one();
+    for &(index, ref partial_decryption) in partial_decryptions.iter() {
+        let mut coefficient = BigInt::one();
+        for &(other_index, _) in partial_decryptions.iter().filter(|&&(other_index, _)| other_index != index) {
+            let numerator = BigInt::from(other_index as u64);
+            let denominator = (BigInt::from(other_index as u64) - BigInt::from(index as u64)).mod_floor(q);
+            coefficient = (coefficient * numerator * denominator.modpow(&(q - BigInt::from(2)), q)).mod_floor(q);
+        }
+
+        big_g_x = (big_g_x * partial_decryption.value.modpow(&coefficient, p)).mod_floor(p);
+    }
+
+    if big_g_x.is_zero() {
+        return None;
+    }
+
+    // g^m = H / G^x
+    let g_m = (&cipher_text.big_h.value * big_g_x.modpow(&(p - BigInt::from(2)), p)).mod_floor(p);
+
+    let mut candidate = BigInt::one();
+    for plaintext in 0..max_plaintext + 1 {
+        if candidate == g_m {
+            return Some(plaintext);
+        }
+        candidate = (candidate * g).mod_floor(p);
+    }
+
+    None
+}
+
+/// Derive the challenge of a decryption proof from all public values.
+fn get_challenge(public_key: &PublicKey, public_key_share: &BigInt, big_g: &BigInt, partial_decryption: &BigInt, commitment_g: &BigInt, commitment_c: &BigInt) -> BigInt {
+    let values: Vec<Vec<u8>> = [&public_key.g.value, public_key_share, big_g, partial_decryption, commitment_g, commitment_c].iter()
+        .map(|value| value.to_bytes_be().1)
+        .collect();
+    let bytes = bincode::serialize(&(PROOF_DOMAIN, values)).unwrap();
+
+    BigInt::from_bytes_be(Sign::Plus, &Sha256::digest(&bytes)).mod_floor(&public_key.q.value)
+}
+
+/// Returns a random number below the given bound.
+fn random_below(bound: &BigInt) -> BigInt {
+    // the surplus of random bytes makes the bias of the reduction negligible
+    let mut bytes = vec![0u8; bound.to_bytes_be().1.len() + 16];
+    thread_rng().fill_bytes(&mut bytes);
+
+    BigInt::from_bytes_be(Sign::Plus, &bytes).mod_floor(bound)
+}
+
+#[cfg(test)]
+mod threshold_test {
+
+    use super::*;
+    use num::ToPrimitive;
+
+    #[test]
+    fn test_threshold_decryption() {
+        // the subgroup of order 11 of the integers modulo 23, generated by 4
+        let mod_int = |value: u64, modulus: u64| ModInt::from_value_modulus(BigInt::from(value), BigInt::from(modulus));
+        let g = BigInt::from(4);
+        let p = BigInt::from(23);
+
+        // shares of the private key 7 on the line 7 + 3i, of which any two decrypt
+        let key_shares: Vec<KeyShare> = (1..4).map(|index| KeyShare { x: mod_int((7 + 3 * index) % 11, 11) }).collect();
+        let public_key = PublicKey {
+            p: mod_int(23, 23),
+            q: mod_int(11, 11),
+            h: mod_int(g.modpow(&BigInt::from(7), &p).to_u64().unwrap(), 23),
+            g: mod_int(4, 23),
+        };
+
+        // encrypt 3 with the random 5
+        let cipher_text = CipherText {
+            big_g: mod_int(g.modpow(&BigInt::from(5), &p).to_u64().unwrap(), 23),
+            big_h: mod_int((public_key.h.value.modpow(&BigInt::from(5), &p) * g.modpow(&BigInt::from(3), &p)).mod_floor(&p).to_u64().unwrap(), 23),
+            random: mod_int(5, 11),
+        };
+
+        let partial_decryptions: Vec<(ModInt, DecryptionProof)> = key_shares.iter().map(|key_share| partially_decrypt(&public_key, key_share, &cipher_text)).collect();
+        for (key_share, &(ref partial_decryption, ref proof)) in key_shares.iter().zip(partial_decryptions.iter()) {
+            let public_key_share = mod_int(g.modpow(&key_share.x.value, &p).to_u64().unwrap(), 23);
+            assert!(verify_partial_decryption(&public_key, &public_key_share, &cipher_text, partial_decryption, proof));
+        }
+
+        // a partial decryption does not match the public key share of another sealer
+        let other_public_key_share = mod_int(g.modpow(&key_shares[1].x.value, &p).to_u64().unwrap(), 23);
+        assert!(!verify_partial_decryption(&public_key, &other_public_key_share, &cipher_text, &partial_decryptions[0].0, &partial_decryptions[0].1));
+
+        assert_eq!(Some(3), combine(&public_key, &cipher_text, &[(1, partial_decryptions[0].0.clone()), (3, partial_decryptions[2].0.clone())], 10));
+        assert_eq!(Some(3), combine(&public_key, &cipher_text, &[(2, partial_decryptions[1].0.clone()), (3, partial_decryptions[2].0.clone())], 10));
+        assert_eq!(None, combine(&public_key, &cipher_text, &[(1, partial_decryptions[0].0.clone()), (3, partial_decryptions[2].0.clone())], 2));
+    }
+}
@@ -0,0 +1,111 @@
+use brotli::CompressorWriter;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::io::Write;
+use std::str::FromStr;
+
+/// Bodies smaller than this amount of bytes are not worth compressing.
+pub const MIN_COMPRESSION_SIZE: usize = 1024;
+
+/// The quality of brotli compression, trading off the size of the body against the time to compress it.
+const BROTLI_QUALITY: u32 = 5;
+
+/// The base two logarithm of the window size used by brotli compression.
+const BROTLI_WINDOW_SIZE: u32 = 22;
+
+/// An encoding the dashboard may compress its responses with.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum ContentEncoding {
+    Brotli,
+    Gzip,
+}
+
+impl ContentEncoding {
+    /// Returns the name of the encoding, as used in the `Accept-Encoding`
+    /// and `Content-Encoding` headers.
+    pub fn name(self) -> &'static str {
+        match self {
+            ContentEncoding::Brotli => "br",
+            ContentEncoding::Gzip => "gzip",
+        }
+    }
+
+    /// Parse a comma separated list of encodings, e.g. `br,gzip`, in the order of preference.
+    /// `none` disables compression.
+    pub fn parse_list(encodings: &str) -> Result<Vec<ContentEncoding>, String> {
+        if "none" == encodings.trim() {
+            return Ok(vec![]);
+        }
+
+        encodings.split(',').map(|encoding| encoding.trim().parse::<ContentEncoding>()).collect()
+    }
+
+    /// Returns the first of the given encodings, which is accepted according to the given
+    /// value of an `Accept-Encoding` header, or None if the body must not be compressed.
+    pub fn negotiate(accept_encoding: &str, encodings: &[ContentEncoding]) -> Option<ContentEncoding> {
+        encodings.iter().cloned().find(|encoding| {
+            accept_encoding.split(',').any(|accepted| {
+                let mut parts = accepted.split(';');
+                let name = parts.next().unwrap_or("").trim();
+                // an encoding with quality zero is explicitly refused
+                let refused = parts.any(|parameter| {
+                    let parameter = parameter.trim();
+                    parameter.starts_with("q=") && parameter[2..].parse::<f32>().map(|quality| quality <= 0.0).unwrap_or(false)
+                });
+
+                (name.eq_ignore_ascii_case(encoding.name()) || "*" == name) && !refused
+            })
+        })
+    }
+
+    /// Compress the given body.
+    pub fn compress(self, body: &[u8]) -> Vec<u8> {
+        match self {
+            ContentEncoding::Brotli => {
+                let mut writer = CompressorWriter::new(Vec::new(), 4096, BROTLI_QUALITY, BROTLI_WINDOW_SIZE);
+                writer.write_all(body).unwrap();
+
+                writer.into_inner()
+            }
+            ContentEncoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(body).unwrap();
+
+                encoder.finish().unwrap()
+            }
+        }
+    }
+}
+
+impl FromStr for ContentEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ContentEncoding, String> {
+        match s {
+            "br" => Ok(ContentEncoding::Brotli),
+            "gzip" => Ok(ContentEncoding::Gzip),
+            _ => Err(format!("Unknown content encoding {:?}, expected br or gzip", s))
+        }
+    }
+}
+
+#[cfg(test)]
+mod compression_test {
+
+    use super::*;
+
+    #[test]
+    fn test_negotiate() {
+        let encodings = vec![ContentEncoding::Brotli, ContentEncoding::Gzip];
+
+        assert_eq!(Some(ContentEncoding::Brotli), ContentEncoding::negotiate("gzip, deflate, br", &encodings));
+        assert_eq!(Some(ContentEncoding::Gzip), ContentEncoding::negotiate("gzip, br;q=0", &encodings));
+        assert_eq!(Some(ContentEncoding::Brotli), ContentEncoding::negotiate("*", &encodings));
+        assert_eq!(None, ContentEncoding::negotiate("identity", &encodings));
+        assert_eq!(None, ContentEncoding::negotiate("gzip", &[]));
+
+        assert_eq!(Ok(vec![]), ContentEncoding::parse_list("none"));
+        assert_eq!(Ok(vec![ContentEncoding::Gzip]), ContentEncoding::parse_list("gzip"));
+        assert!(ContentEncoding::parse_list("deflate").is_err());
+    }
+}
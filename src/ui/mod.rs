@@ -0,0 +1,292 @@
+use ::p2p::thread::ThreadPool;
+use ::protocol::clique::CliqueProtocol;
+use serde_json;
+use sha1::Sha1;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+pub mod compression;
+
+use self::compression::{ContentEncoding, MIN_COMPRESSION_SIZE};
+
+/// The single page application rendering the election status.
+const DASHBOARD_HTML: &str = include_str!("dashboard.html");
+
+/// The maximum amount of bytes read from an HTTP request.
+const MAX_REQUEST_SIZE: u64 = 8 * 1024;
+
+/// The amount of connections served at the same time.
+const DASHBOARD_WORKERS: usize = 4;
+
+/// The time in milliseconds to wait for a client when reading its request or writing the response,
+/// so that a stalled client does not occupy a worker indefinitely.
+const DASHBOARD_CONNECTION_TIMEOUT: u64 = 5000;
+
+/// Serves a static dashboard showing the state of the election,
+/// along with the explorer API it fetches its data from:
+///
+/// - `GET /` The dashboard itself.
+/// - `GET /api/status` The `ElectionStatus` of the node as JSON.
+/// - `GET /api/epochs` The `EpochStatistics` of each epoch as JSON.
+///
+/// Responses carry an `ETag`, so that clients sending it back in `If-None-Match`
+/// are answered with `304 Not Modified` as long as the data did not change.
+/// Responses are compressed with the first of the configured encodings accepted by the client.
+/// Connections are served on a pool of `DASHBOARD_WORKERS` threads.
+#[derive(Clone)]
+pub struct Dashboard {
+    /// The address on which the dashboard listens for HTTP requests.
+    listen_address: SocketAddr,
+
+    /// The protocol of the node whose state is shown.
+    protocol: Arc<Mutex<CliqueProtocol>>,
+
+    /// The encodings responses may be compressed with, in the order of preference.
+    compression: Vec<ContentEncoding>,
+}
+
+/// The parts of an HTTP request the dashboard is interested in.
+struct DashboardRequest {
+    method: String,
+    path: String,
+    /// The headers of the request, with their names in lower case.
+    headers: HashMap<String, String>,
+}
+
+impl Dashboard {
+    /// Creates a new dashboard.
+    ///
+    /// - `listen_address` The address on which the dashboard listens for HTTP requests.
+    /// - `protocol` The protocol of the node whose state should be shown.
+    /// - `compression` The encodings responses may be compressed with, in the order of preference.
+    pub fn new(listen_address: SocketAddr, protocol: Arc<Mutex<CliqueProtocol>>, compression: Vec<ContentEncoding>) -> Dashboard {
+        Dashboard {
+            listen_address,
+            protocol,
+            compression,
+        }
+    }
+
+    /// Listen for incoming HTTP requests. Blocks the current thread
+    /// until the given flag is cleared and the next connection arrives.
+    pub fn listen(&self, is_running: &AtomicBool) {
+        let listener = match TcpListener::bind(&self.listen_address) {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("Failed to start dashboard on {:?}: {:?}", self.listen_address, e);
+                return;
+            }
+        };
+        info!("Serving dashboard on http://{}", self.listen_address);
+
+        let connection_pool = ThreadPool::new("dashboard-connections", DASHBOARD_WORKERS);
+        connection_pool.abort_on_poisoned("protocol", &self.protocol);
+
+        for stream in listener.incoming() {
+            if !is_running.load(Ordering::SeqCst) {
+                info!("Stopped serving the dashboard");
+                break;
+            }
+
+            match stream {
+                Ok(mut stream) => {
+                    let timeout = Some(Duration::from_millis(DASHBOARD_CONNECTION_TIMEOUT));
+                    if let Err(e) = stream.set_read_timeout(timeout).and_then(|_| stream.set_write_timeout(timeout)) {
+                        trace!("Dropping dashboard connection from {:?} as its timeouts cannot be set: {:?}", stream.peer_addr(), e);
+                        continue;
+                    }
+
+                    let dashboard = self.clone();
+                    connection_pool.execute("dashboard-connection", move || {
+                        dashboard.handle_connection(&mut stream);
+                    });
+                }
+                Err(e) => {
+                    trace!("Failed to accept dashboard connection: {:?}", e);
+                }
+            }
+        }
+    }
+
+    fn handle_connection(&self, stream: &mut TcpStream) {
+        let request = match Dashboard::read_request(stream) {
+            Some(request) => request,
+            None => {
+                return;
+            }
+        };
+        trace!("Got dashboard request {} {} from {:?}", request.method, request.path, stream.peer_addr());
+
+        let (status, content_type, body) = match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/") | ("GET", "/index.html") => {
+                ("200 OK", "text/html; charset=utf-8", DASHBOARD_HTML.to_string())
+            }
+            ("GET", "/api/status") => {
+                let election_status = self.protocol.lock().unwrap().get_election_status();
+                ("200 OK", "application/json", serde_json::to_string(&election_status).unwrap())
+            }
+            ("GET", "/api/epochs") => {
+                let epoch_statistics = self.protocol.lock().unwrap().get_epoch_statistics();
+                ("200 OK", "application/json", serde_json::to_string(&epoch_statistics).unwrap())
+            }
+            ("GET", _) => {
+                ("404 Not Found", "text/plain; charset=utf-8", "Not Found".to_string())
+            }
+            _ => {
+                ("405 Method Not Allowed", "text/plain; charset=utf-8", "Method Not Allowed".to_string())
+            }
+        };
+
+        let response = if "200 OK" == status {
+            self.build_response(&request, content_type, body.into_bytes())
+        } else {
+            Dashboard::format_response(status, &[("Content-Type", content_type.to_string())], body.into_bytes())
+        };
+
+        let write_result = stream.write_all(&response).and_then(|_| stream.flush());
+        match write_result {
+            Ok(()) => {}
+            Err(e) => {
+                trace!("Failed to send dashboard response to {:?}: {:?}", stream.peer_addr(), e);
+            }
+        }
+    }
+
+    /// Build the response to a successful request, which is cached by the
+    /// client by its entity tag, and compressed if the client accepts it.
+    fn build_response(&self, request: &DashboardRequest, content_type: &str, body: Vec<u8>) -> Vec<u8> {
+        // the tag is weak, as compressed and uncompressed representations of the body share it
+        let entity_tag = format!("W/\"{}\"", Sha1::from(&body).hexdigest());
+        let mut headers = vec![
+            ("Content-Type", content_type.to_string()),
+            ("ETag", entity_tag.clone()),
+            ("Vary", "Accept-Encoding".to_string()),
+        ];
+
+        if let Some(if_none_match) = request.headers.get("if-none-match") {
+            let is_unchanged = if_none_match.split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| "*" == tag || tag == entity_tag.trim_start_matches("W/"));
+            if is_unchanged {
+                return Dashboard::format_response("304 Not Modified", &headers, vec![]);
+            }
+        }
+
+        let encoding = match request.headers.get("accept-encoding") {
+            Some(accept_encoding) if body.len() >= MIN_COMPRESSION_SIZE => ContentEncoding::negotiate(accept_encoding, &self.compression),
+            _ => None
+        };
+
+        match encoding {
+            Some(encoding) => {
+                headers.push(("Content-Encoding", encoding.name().to_string()));
+                Dashboard::format_response("200 OK", &headers, encoding.compress(&body))
+            }
+            None => Dashboard::format_response("200 OK", &headers, body)
+        }
+    }
+
+    /// Format an HTTP response with the given status, headers and body.
+    fn format_response(status: &str, headers: &[(&str, String)], body: Vec<u8>) -> Vec<u8> {
+        let mut head = format!("HTTP/1.1 {}\r\n", status);
+        for &(name, ref value) in headers.iter() {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str(&format!("Content-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n", body.len()));
+
+        let mut response = head.into_bytes();
+        response.extend(body);
+
+        response
+    }
+
+    /// Read the request line and the headers of an HTTP request.
+    /// Returns None if the request could not be read.
+    fn read_request(stream: &TcpStream) -> Option<DashboardRequest> {
+        let mut reader = BufReader::new(stream.take(MAX_REQUEST_SIZE));
+
+        let mut request_line = String::new();
+        match reader.read_line(&mut request_line) {
+            Ok(0) => {
+                return None;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                trace!("Failed to read dashboard request: {:?}", e);
+                return None;
+            }
+        }
+
+        // headers must be consumed before responding, even if not of interest
+        let mut headers = HashMap::new();
+        loop {
+            let mut header = String::new();
+            match reader.read_line(&mut header) {
+                Ok(0) => break,
+                Ok(_) if header.trim().is_empty() => break,
+                Ok(_) => {
+                    if let Some(separator) = header.find(':') {
+                        headers.insert(header[..separator].trim().to_lowercase(), header[separator + 1..].trim().to_string());
+                    }
+                }
+                Err(_) => break
+            }
+        }
+
+        let mut parts = request_line.split_whitespace();
+
+        Some(DashboardRequest {
+            method: parts.next().unwrap_or("").to_string(),
+            path: parts.next().unwrap_or("").to_string(),
+            headers,
+        })
+    }
+}
+
+#[cfg(test)]
+mod dashboard_test {
+    use ::config::genesis::Genesis;
+    use ::config::version::VersionPolicy;
+    use ::protocol::clique::CliqueProtocol;
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpListener, TcpStream};
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::AtomicBool;
+    use std::thread;
+    use std::time::Duration;
+    use super::Dashboard;
+
+    #[test]
+    fn test_stalled_client_does_not_block_dashboard() {
+        let genesis = Genesis::new("genesis.json", "public_uciv.json", "public_key.json");
+        let protocol = CliqueProtocol::new("127.0.0.1:3000".parse().unwrap(), genesis, VersionPolicy::Ignore);
+        let listen_address: SocketAddr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let dashboard = Dashboard::new(listen_address, Arc::new(Mutex::new(protocol)), vec![]);
+        thread::spawn(move || dashboard.listen(&AtomicBool::new(true)));
+
+        let mut stalled_stream = None;
+        for _ in 0..50 {
+            match TcpStream::connect(listen_address) {
+                Ok(stream) => {
+                    stalled_stream = Some(stream);
+                    break;
+                }
+                Err(_) => thread::sleep(Duration::from_millis(20))
+            }
+        }
+        let mut stalled_stream = stalled_stream.expect("Dashboard must listen");
+        stalled_stream.write_all(b"GET / HTTP/1.1\r\n").unwrap();
+
+        let mut stream = TcpStream::connect(listen_address).unwrap();
+        stream.set_read_timeout(Some(Duration::from_millis(2000))).unwrap();
+        stream.write_all(b"GET /api/status HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+    }
+}
@@ -0,0 +1,307 @@
+use ::chain::block::Block;
+use ::chain::transaction::TransactionType;
+use ::chain::types::{BlockHash, Height};
+use ::http;
+use ::mac::{encode_hex, hmac_sha1};
+use serde_json;
+use sha1::Sha1;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The maximum time to wait for an endpoint to accept a connection or respond.
+const DELIVERY_TIMEOUT: u64 = 5;
+
+/// The name of the file in the spool directory holding the height
+/// of the last block whose events were enqueued.
+const CURSOR_FILE_NAME: &str = "cursor.json";
+
+fn default_confirmations() -> usize {
+    6
+}
+
+/// Defines the HTTP endpoints notified about finalized blocks
+/// and the lifecycle of the election.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WebhookConfig {
+    /// The endpoints to notify.
+    pub endpoints: Vec<WebhookEndpoint>,
+    /// The directory in which undelivered events are persisted until
+    /// their endpoint accepts them. Survives restarts of the node.
+    pub spool_directory: PathBuf,
+    /// The amount of blocks which must follow a block on the canonical chain
+    /// for it to be considered final.
+    #[serde(default = "default_confirmations")]
+    pub confirmations: usize,
+    /// The interval in seconds in which new events are collected and delivery is retried.
+    pub interval_secs: u64,
+}
+
+/// An HTTP endpoint receiving events.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WebhookEndpoint {
+    /// The URL to which events are posted. Only `http://` URLs are supported.
+    pub url: String,
+    /// The secret used to sign the events sent to this endpoint.
+    pub secret: String,
+}
+
+/// The kinds of events an endpoint is notified about.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum WebhookEventKind {
+    BlockFinalized,
+    VotingOpened,
+    VotingClosed,
+}
+
+/// An event posted as JSON to all endpoints.
+///
+/// Events are delivered at least once, in the order of the blocks they refer to.
+/// Endpoints must deduplicate events by their identifier.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct WebhookEvent {
+    /// Identifies the event uniquely.
+    pub identifier: String,
+    pub kind: WebhookEventKind,
+    /// The height of the finalized block causing this event.
+    pub height: Height,
+    /// The identifier of the finalized block causing this event.
+    pub block_identifier: BlockHash,
+    /// The time the block was sealed at, in seconds since the epoch.
+    pub block_timestamp: u64,
+    /// The amount of transactions contained in the block.
+    pub transactions: usize,
+}
+
+impl WebhookEvent {
+    /// Returns the events caused by the given finalized block.
+    pub fn from_block(height: Height, block: &Block) -> Vec<WebhookEvent> {
+        let mut kinds = vec![WebhookEventKind::BlockFinalized];
+        for transaction in block.data.transactions.iter() {
+            match transaction.trx_type {
+                TransactionType::VoteOpened => kinds.push(WebhookEventKind::VotingOpened),
+                TransactionType::VoteClosed => kinds.push(WebhookEventKind::VotingClosed),
+                _ => {}
+            }
+        }
+
+        kinds.iter().map(|kind| {
+            WebhookEvent {
+                identifier: format!("{}-{:?}", block.identifier, kind),
+                kind: *kind,
+                height,
+                block_identifier: block.identifier.clone(),
+                block_timestamp: block.data.timestamp,
+                transactions: block.data.transactions.len(),
+            }
+        }).collect()
+    }
+}
+
+impl WebhookConfig {
+    /// Read the webhook configuration from the given JSON file.
+    pub fn from_file(file_name: &str) -> Result<WebhookConfig, String> {
+        let mut contents = String::new();
+        let read_result = File::open(file_name).and_then(|mut file| file.read_to_string(&mut contents));
+        match read_result {
+            Ok(_) => {}
+            Err(e) => {
+                return Err(format!("Failed to read webhook configuration {:?}: {:?}", file_name, e));
+            }
+        }
+
+        let config: WebhookConfig = match serde_json::from_str(&contents) {
+            Ok(config) => config,
+            Err(e) => {
+                return Err(format!("Failed to parse webhook configuration {:?}: {:?}", file_name, e));
+            }
+        };
+
+        for endpoint in config.endpoints.iter() {
+            match http::parse_url(&endpoint.url) {
+                Ok(_) => {}
+                Err(e) => {
+                    return Err(format!("Invalid endpoint in webhook configuration {:?}: {}", file_name, e));
+                }
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// Delivers the events of finalized blocks to the configured endpoints.
+///
+/// Events are first persisted in a spool directory per endpoint and only deleted
+/// once the endpoint responded with a success status. Failed deliveries are retried
+/// in order, so that an endpoint never receives an event before the preceding ones.
+pub struct WebhookDispatcher {
+    config: WebhookConfig,
+    /// The height of the last block whose events were enqueued.
+    cursor: Height,
+}
+
+impl WebhookDispatcher {
+    /// Create a new dispatcher, resuming from the state persisted in the spool directory.
+    pub fn new(config: WebhookConfig) -> Result<WebhookDispatcher, String> {
+        let mut spool_directories = vec![config.spool_directory.clone()];
+        spool_directories.extend(config.endpoints.iter().map(|endpoint| WebhookDispatcher::get_spool_directory(&config, endpoint)));
+
+        for spool_directory in spool_directories {
+            match fs::create_dir_all(&spool_directory) {
+                Ok(()) => {}
+                Err(e) => {
+                    return Err(format!("Failed to create spool directory {:?}: {:?}", spool_directory, e));
+                }
+            }
+        }
+
+        let cursor_path = config.spool_directory.join(CURSOR_FILE_NAME);
+        let cursor = match fs::read_to_string(&cursor_path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or(Height::GENESIS),
+            Err(_) => Height::GENESIS
+        };
+
+        Ok(WebhookDispatcher {
+            config,
+            cursor,
+        })
+    }
+
+    /// Returns the amount of blocks which must follow a block for it to be final.
+    pub fn get_confirmations(&self) -> usize {
+        self.config.confirmations
+    }
+
+    /// Returns the height of the last block whose events were enqueued.
+    pub fn get_cursor(&self) -> Height {
+        self.cursor
+    }
+
+    /// Persist the events of the given finalized blocks for all endpoints.
+    ///
+    /// - `finalized_blocks` The finalized blocks above the cursor along with their height,
+    ///   from the oldest to the newest.
+    pub fn enqueue(&mut self, finalized_blocks: &[(Height, Block)]) -> Result<(), String> {
+        for &(height, ref block) in finalized_blocks.iter() {
+            for (index, event) in WebhookEvent::from_block(height, block).iter().enumerate() {
+                // events re-enqueued after a crash replace their previous copy
+                let file_name = format!("{:020}-{}.json", height, index);
+                let contents = serde_json::to_string(event).unwrap();
+
+                for endpoint in self.config.endpoints.iter() {
+                    let path = WebhookDispatcher::get_spool_directory(&self.config, endpoint).join(&file_name);
+                    match write_atomically(&path, &contents) {
+                        Ok(()) => {}
+                        Err(e) => {
+                            return Err(format!("Failed to spool event {} to {:?}: {:?}", event.identifier, path, e));
+                        }
+                    }
+                }
+            }
+
+            self.cursor = height;
+            let cursor_path = self.config.spool_directory.join(CURSOR_FILE_NAME);
+            match write_atomically(&cursor_path, &self.cursor.to_string()) {
+                Ok(()) => {}
+                Err(e) => {
+                    return Err(format!("Failed to persist webhook cursor to {:?}: {:?}", cursor_path, e));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Try to deliver all spooled events. Delivery to an endpoint
+    /// stops at its first failure and is retried on the next call.
+    pub fn deliver(&self) {
+        for endpoint in self.config.endpoints.iter() {
+            let spool_directory = WebhookDispatcher::get_spool_directory(&self.config, endpoint);
+
+            let mut spooled_events: Vec<PathBuf> = match fs::read_dir(&spool_directory) {
+                Ok(entries) => entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect(),
+                Err(e) => {
+                    warn!("Failed to read spool directory {:?}: {:?}", spool_directory, e);
+                    continue;
+                }
+            };
+            spooled_events.retain(|path| path.extension().is_some_and(|extension| extension == "json"));
+            spooled_events.sort();
+
+            for path in spooled_events.iter() {
+                let body = match fs::read_to_string(path) {
+                    Ok(body) => body,
+                    Err(e) => {
+                        warn!("Failed to read spooled event {:?}: {:?}", path, e);
+                        break;
+                    }
+                };
+
+                match post(endpoint, &body) {
+                    Ok(()) => {
+                        trace!("Delivered event {:?} to {}", path, endpoint.url);
+                        match fs::remove_file(path) {
+                            Ok(()) => {}
+                            Err(e) => {
+                                warn!("Failed to remove delivered event {:?}: {:?}", path, e);
+                                break;
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        debug!("Failed to deliver event {:?} to {}: {}. Will retry", path, endpoint.url, e);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    fn get_spool_directory(config: &WebhookConfig, endpoint: &WebhookEndpoint) -> PathBuf {
+        config.spool_directory.join(Sha1::from(endpoint.url.as_bytes()).hexdigest())
+    }
+}
+
+fn write_atomically(path: &Path, contents: &str) -> ::std::io::Result<()> {
+    let temporary_path = path.with_extension("tmp");
+
+    File::create(&temporary_path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()).and_then(|_| file.sync_all()))
+        .and_then(|_| fs::rename(&temporary_path, path))
+}
+
+/// Post the given event to the endpoint, signed by the secret of the endpoint.
+fn post(endpoint: &WebhookEndpoint, body: &str) -> Result<(), String> {
+    let (authority, path) = match http::parse_url(&endpoint.url) {
+        Ok(parsed_url) => parsed_url,
+        Err(e) => {
+            return Err(e);
+        }
+    };
+
+    let signature = encode_hex(&hmac_sha1(endpoint.secret.as_bytes(), body.as_bytes()));
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nX-Node-Rs-Signature: sha1={}\r\nConnection: close\r\n\r\n{}",
+        path,
+        authority,
+        body.len(),
+        signature,
+        body
+    );
+
+    let response = match http::send(&authority, &request, Duration::from_secs(DELIVERY_TIMEOUT), 1024) {
+        Ok(response) => response,
+        Err(e) => {
+            return Err(e);
+        }
+    };
+
+    match http::get_status(&response) {
+        Some(status) if (200..300).contains(&status) => Ok(()),
+        Some(status) => Err(format!("Endpoint responded with status {}", status)),
+        None => Err("Endpoint sent no valid HTTP response".to_string())
+    }
+}
+
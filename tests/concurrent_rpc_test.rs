@@ -0,0 +1,75 @@
+extern crate node_rs;
+
+use node_rs::config::genesis::Genesis;
+use node_rs::config::node::NodeConfig;
+use node_rs::p2p::codec::{Codec, JsonCodec, Message};
+use node_rs::p2p::node::Node;
+use std::io::{Read, Write};
+use std::mem;
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+const CONCURRENT_CLIENTS: usize = 32;
+
+fn free_address() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap()
+}
+
+fn send_request(address: SocketAddr, message: Message) -> Message {
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+
+    stream.write_all(&JsonCodec::encode(message)).unwrap();
+    stream.shutdown(Shutdown::Write).unwrap();
+
+    let mut response = vec![];
+    stream.read_to_end(&mut response).unwrap();
+
+    JsonCodec::decode(&response)
+}
+
+#[test]
+fn test_concurrent_rpc_submissions() {
+    let genesis = Genesis::new("genesis.json", "public_uciv.json", "public_key.json");
+    let sealer_count = genesis.sealer.len();
+
+    let mut node_config = NodeConfig::default();
+    node_config.advertised_address = Some(genesis.sealer[0]);
+    node_config.connection_workers = 4;
+
+    let rpc_address = free_address();
+    let node = Node::new(free_address(), rpc_address, genesis, node_config);
+    node.listen_rpc();
+    node.gossip();
+
+    // a client which connects but never finishes its request must not
+    // prevent any other client from being served
+    let stalled_client = TcpStream::connect(rpc_address).unwrap();
+
+    let (sender, receiver) = mpsc::channel();
+    for _ in 0..CONCURRENT_CLIENTS {
+        let sender = sender.clone();
+        thread::spawn(move || {
+            let response = send_request(rpc_address, Message::SealerStatisticsRequest);
+            sender.send(response).unwrap();
+        });
+    }
+
+    for _ in 0..CONCURRENT_CLIENTS {
+        let response = receiver.recv_timeout(Duration::from_secs(10)).expect("RPC request was not handled in time");
+        match response {
+            Message::SealerStatisticsResponse(statistics) => {
+                assert_eq!(sealer_count, statistics.sealed_blocks.len());
+            }
+            other => panic!("Unexpected response {:?}", other)
+        }
+    }
+
+    drop(stalled_client);
+
+    // the listener threads never terminate, hence the node must not be
+    // dropped as this would wait for them to finish
+    mem::forget(node);
+}